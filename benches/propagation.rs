@@ -0,0 +1,101 @@
+//! Timings of the propagation paths against their simple alternatives, on the bundled nav
+//! file, best of several runs:
+//!
+//! - one satellite over a million epochs, `Satellite::propagate` against a loop of
+//!   single-epoch `PreparedEphemeris::evaluate` calls
+//! - 100k `position_at` calls on one record, prepared once against `NavRecord::position_at`
+//!
+//! `cargo bench --bench propagation`
+
+use chrono::{TimeZone, Utc};
+use pnt_rust::{
+    ephemeris::EphemerisState,
+    gnss::{self, GpsTime, RinexNav, SatId},
+    satellite::{PropagationConfig, Satellite},
+};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const NAV: &str = include_str!("../constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
+const RUNS: usize = 5;
+
+/// Shortest wall time of `RUNS` calls
+fn best<T>(mut run: impl FnMut() -> T) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            black_box(run());
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, baseline: (&str, Duration), candidate: (&str, Duration)) {
+    println!("{}", name);
+    println!("  {:<28} {:>10.2?}", baseline.0, baseline.1);
+    println!("  {:<28} {:>10.2?}", candidate.0, candidate.1);
+    println!(
+        "  speedup {:.2}x",
+        baseline.1.as_secs_f64() / candidate.1.as_secs_f64()
+    );
+}
+
+fn main() {
+    let nav: RinexNav = NAV.parse().unwrap();
+    let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+    let record = *nav.records_for_slice(SatId::gps(17)).first().unwrap();
+    let prepared = record.prepare();
+
+    // One record over a million epochs 0.1 s apart, with velocity and clock
+    let epochs = 1_000_000;
+    let config = PropagationConfig::new()
+        .step(Duration::from_millis(100))
+        .with_velocity(true)
+        .with_clock(true);
+    let duration = config.step * epochs;
+    let mut satellite = Satellite::builder(17).build();
+    let strips = best(|| {
+        satellite
+            .propagate(start, duration, &config, [&record])
+            .unwrap();
+    });
+    assert_eq!(satellite.states.len(), epochs as usize);
+    let first = gnss::gps_seconds(start);
+    let mut scalar_states: Vec<EphemerisState> = Vec::with_capacity(epochs as usize);
+    let scalar = best(|| {
+        scalar_states.clear();
+        scalar_states.extend((0..epochs).map(|k| {
+            let time = GpsTime::from_seconds(first + 0.1 * k as f64);
+            prepared.evaluate(time, config.kepler_tolerance, config.kepler_max_iter)
+        }));
+    });
+    report(
+        "one satellite, 1M epochs",
+        ("scalar evaluate loop", scalar),
+        ("Satellite::propagate", strips),
+    );
+
+    // 100k single epochs a second apart on one record
+    let calls = 100_000;
+    let times: Vec<GpsTime> = (0..calls)
+        .map(|k| GpsTime::from_seconds(first + k as f64))
+        .collect();
+    let unprepared = best(|| {
+        times
+            .iter()
+            .map(|&time| black_box(&record).position_at(time).x)
+            .sum::<f64>()
+    });
+    let prepared = best(|| {
+        times
+            .iter()
+            .map(|&time| black_box(&prepared).position_at(time).x)
+            .sum::<f64>()
+    });
+    report(
+        "100k position_at calls",
+        ("NavRecord::position_at", unprepared),
+        ("PreparedEphemeris", prepared),
+    );
+}
@@ -0,0 +1,45 @@
+use std::path::Path;
+
+const NEQUICK_FILES: [&str; 13] = [
+    "modipNeQG_wrapped.asc",
+    "ccir11.asc",
+    "ccir12.asc",
+    "ccir13.asc",
+    "ccir14.asc",
+    "ccir15.asc",
+    "ccir16.asc",
+    "ccir17.asc",
+    "ccir18.asc",
+    "ccir19.asc",
+    "ccir20.asc",
+    "ccir21.asc",
+    "ccir22.asc",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // The NeQuick-G grids are embedded when the Galileo files are in data/nequick
+    println!("cargo:rustc-check-cfg=cfg(nequick_data)");
+    println!("cargo:rerun-if-changed=data/nequick");
+    let nequick = Path::new("data/nequick");
+    if NEQUICK_FILES
+        .iter()
+        .all(|file| nequick.join(file).is_file())
+    {
+        println!("cargo:rustc-cfg=nequick_data");
+    } else {
+        println!(
+            "cargo:warning=NeQuick-G grids not found in data/nequick: NeQuickData::embedded \
+             is left out and Galileo falls back to Klobuchar unless they are loaded at run time"
+        );
+    }
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        cbindgen::generate(&crate_dir)
+            .expect("Failed to generate the C header")
+            .write_to_file(format!("{}/include/pnt_rust.h", crate_dir));
+    }
+}
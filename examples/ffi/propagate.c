@@ -0,0 +1,63 @@
+/*
+ * Load a nav file, propagate one satellite and print its states through the C ABI.
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *   cc examples/ffi/propagate.c -Iinclude -Ltarget/release -lpnt_rust -o target/propagate
+ *   LD_LIBRARY_PATH=target/release target/propagate constellation/GCGO00USA_R_20231630000_01D_GN.rnx G05
+ *
+ * Exits non-zero and prints the library's message if any call fails.
+ */
+#include <stdio.h>
+
+#include "pnt_rust.h"
+
+static int check(PntStatus status, const char *call)
+{
+    if (status != PNT_STATUS_OK) {
+        fprintf(stderr, "%s failed (%d): %s\n", call, (int)status, pnt_last_error_message());
+        return 1;
+    }
+    return 0;
+}
+
+int main(int argc, char **argv)
+{
+    const char *path = argc > 1 ? argv[1] : "constellation/GCGO00USA_R_20231630000_01D_GN.rnx";
+    const char *sat_id = argc > 2 ? argv[2] : "G05";
+    PntNav *nav = NULL;
+    PntSatellite *satellite = NULL;
+    size_t records = 0, count = 0;
+    int failed = 1;
+
+    if (check(pnt_nav_load(path, &nav), "pnt_nav_load") ||
+        check(pnt_nav_record_count(nav, &records), "pnt_nav_record_count") ||
+        check(pnt_satellite_new(nav, sat_id, &satellite), "pnt_satellite_new") ||
+        /* 2023-06-12 00:00:00 UTC, one hour in 10 minute steps */
+        check(pnt_satellite_propagate(satellite, 1686528000.0, 3600.0, 600.0),
+              "pnt_satellite_propagate") ||
+        check(pnt_satellite_state_count(satellite, &count), "pnt_satellite_state_count"))
+        goto done;
+
+    printf("%zu records, %zu states of %s\n", records, count, sat_id);
+    for (size_t i = 0; i < count; i++) {
+        PntState state;
+        PntLla lla;
+        if (check(pnt_state_get(satellite, i, &state), "pnt_state_get") ||
+            check(pnt_ecef_to_lla(state.x, state.y, state.z, &lla), "pnt_ecef_to_lla"))
+            goto done;
+        printf("%.0f %14.3f %14.3f %14.3f  %9.4f %10.4f %12.1f\n", state.t, state.x, state.y,
+               state.z, lla.latitude, lla.longitude, lla.altitude);
+    }
+
+    /* Errors are reported, not crashed on */
+    PntState state;
+    if (pnt_state_get(satellite, count, &state) != PNT_STATUS_OUT_OF_RANGE)
+        goto done;
+    printf("expected error: %s\n", pnt_last_error_message());
+    failed = 0;
+
+done:
+    pnt_satellite_free(satellite);
+    pnt_nav_free(nav);
+    return failed;
+}
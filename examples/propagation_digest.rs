@@ -0,0 +1,54 @@
+//! Every state of the bundled nav file's satellites over two hours, batch propagated, and
+//! the single-epoch position of each at the same epochs, printed to round-trip precision.
+//! `scripts/check_features.sh` compares the output of builds with different features.
+
+use chrono::{TimeZone, Utc};
+use pnt_rust::{
+    constellation::{Constellation, SatelliteStatus},
+    gnss::RinexNav,
+    satellite::PropagationConfig,
+    store::EphemerisStore,
+};
+use std::time::Duration;
+
+const NAV_FILE: &str = "constellation/GCGO00USA_R_20231630000_01D_GN.rnx";
+
+fn main() -> std::io::Result<()> {
+    let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+    let duration = Duration::from_secs(2 * 3600);
+    let config = PropagationConfig::new()
+        .step(Duration::from_secs(30))
+        .with_velocity(true);
+
+    let mut constellation = Constellation::from_nav(RinexNav::from_file(NAV_FILE)?);
+    let statuses = constellation.propagate_all(start, duration, &config);
+    let store = EphemerisStore::new(RinexNav::from_file(NAV_FILE)?, config);
+
+    for (sat_id, status) in &statuses {
+        if !matches!(status, SatelliteStatus::Propagated(_)) {
+            println!("{} {:?}", sat_id, status);
+            continue;
+        }
+        for state in constellation.get(*sat_id).unwrap().states.iter() {
+            let position = state.position();
+            let velocity = state.velocity().unwrap_or_default();
+            println!(
+                "{} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?}",
+                sat_id,
+                state.time(),
+                position.x,
+                position.y,
+                position.z,
+                velocity.x,
+                velocity.y,
+                velocity.z,
+                state.clock_bias()
+            );
+            match store.position_at(*sat_id, state.epoch()) {
+                Ok(single) => println!("  {:?} {:?} {:?}", single.x, single.y, single.z),
+                Err(err) => println!("  {}", err),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Print live positions and per-satellite C/N0 from a GNSS receiver on a serial port,
+//! whether it speaks NMEA, UBX or both.
+//!
+//!     cargo run --example serial_monitor --features serial -- [PORT] [BAUD]
+//!
+//! Without a port the first USB serial port found is used; the speed defaults to 9600.
+
+use pnt_rust::nmea::Sentence;
+use pnt_rust::serial::{self, Message, SerialConfig, SerialSource, UbxMessage};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args
+        .next()
+        .or_else(|| serial::usb_ports().into_iter().next())
+    else {
+        eprintln!("error: no USB serial port found; give the port as the first argument");
+        return ExitCode::FAILURE;
+    };
+    let baud_rate = match args.next().map(|baud| baud.parse()) {
+        None => 9600,
+        Some(Ok(baud)) => baud,
+        Some(Err(_)) => {
+            eprintln!("error: the speed must be a whole number of baud");
+            return ExitCode::FAILURE;
+        }
+    };
+    let source = match SerialSource::open(SerialConfig::new(&path).baud_rate(baud_rate)) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: cannot open {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("reading {} at {} baud", path, baud_rate);
+
+    // GSV groups span several sentences; C/N0 is printed once a group is complete
+    let mut in_view = Vec::new();
+    for message in source.messages() {
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        };
+        match message {
+            Message::Nmea(Sentence::Gga(gga)) => {
+                if let (Some(latitude), Some(longitude)) = (gga.latitude, gga.longitude) {
+                    println!(
+                        "GGA {} {:.7} {:.7} {:.1} m MSL, {:?}, {} satellites",
+                        gga.time.map(|time| time.to_string()).unwrap_or_default(),
+                        latitude,
+                        longitude,
+                        gga.altitude.unwrap_or(f64::NAN),
+                        gga.quality,
+                        gga.satellites.unwrap_or(0)
+                    );
+                }
+            }
+            Message::Nmea(Sentence::Gsv(gsv)) => {
+                if gsv.number == 1 {
+                    in_view.clear();
+                }
+                in_view.extend(
+                    gsv.satellites
+                        .iter()
+                        .filter_map(|sat| Some(format!("{}:{:.0}", sat.id, sat.snr?))),
+                );
+                if gsv.number == gsv.total {
+                    println!("GSV C/N0 {}", in_view.join(" "));
+                }
+            }
+            Message::Ubx(UbxMessage::NavPvt(pvt)) if pvt.fix_type > 0 => println!(
+                "PVT {} {:.7} {:.7} {:.1} m, fix {}, {} satellites, ±{:.1} m",
+                pvt.time.map(|time| time.to_rfc3339()).unwrap_or_default(),
+                pvt.position.latitude,
+                pvt.position.longitude,
+                pvt.position.altitude,
+                pvt.fix_type,
+                pvt.satellites,
+                pvt.horizontal_accuracy
+            ),
+            Message::Ubx(UbxMessage::NavSat(sat)) => {
+                let tracked: Vec<String> = sat
+                    .satellites
+                    .iter()
+                    .filter(|info| info.cn0 > 0.0)
+                    .map(|info| format!("{}:{:.0}", info.sat_id, info.cn0))
+                    .collect();
+                println!("SAT C/N0 {}", tracked.join(" "));
+            }
+            _ => {}
+        }
+    }
+    ExitCode::SUCCESS
+}
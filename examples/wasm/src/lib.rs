@@ -0,0 +1,58 @@
+//! Browser bindings: parse nav text, propagate one satellite, read back positions.
+//!
+//!   cargo build --release --target wasm32-unknown-unknown
+//!   wasm-bindgen --target web --out-dir pkg \
+//!       target/wasm32-unknown-unknown/release/pnt_rust_wasm.wasm
+//!
+//! then, from JavaScript, `positions(navText, "G05", Date.UTC(2023, 5, 12), 3600, 60)`.
+
+use chrono::{TimeZone, Utc};
+use pnt_rust::gnss::{RinexNav, SatId};
+use pnt_rust::satellite::{PropagationConfig, Satellite};
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+
+/// Propagate a satellite of the RINEX nav text from `start_ms` (Unix milliseconds, UTC)
+/// over `duration_s` in `step_s` steps. Returns [unix_ms, x, y, z, ...] with ECEF meters.
+#[wasm_bindgen]
+pub fn positions(
+    nav_text: &str,
+    sat_id: &str,
+    start_ms: f64,
+    duration_s: f64,
+    step_s: f64,
+) -> Result<Vec<f64>, JsError> {
+    let nav: RinexNav = nav_text.parse()?;
+    let sat_id: SatId = sat_id
+        .parse()
+        .map_err(|err| JsError::new(&format!("{}", err)))?;
+    // Infinite, NaN or overflowing seconds would make `Duration` panic
+    let (duration, step) = match (
+        Duration::try_from_secs_f64(duration_s),
+        Duration::try_from_secs_f64(step_s),
+    ) {
+        (Ok(duration), Ok(step)) if !step.is_zero() => (duration, step),
+        _ => {
+            return Err(JsError::new(
+                "duration must be finite and non-negative, step finite and positive",
+            ))
+        }
+    };
+    let start = Utc
+        .timestamp_millis_opt(start_ms as i64)
+        .single()
+        .ok_or_else(|| JsError::new("start is out of range"))?;
+
+    let mut satellite = Satellite::builder(sat_id).build();
+    let config = PropagationConfig::new().step(step);
+    satellite.propagate_from_nav(&nav, start, duration, &config)?;
+
+    Ok(satellite
+        .states
+        .into_iter()
+        .flat_map(|(epoch, position)| {
+            let time = epoch.to_utc().timestamp_millis() as f64;
+            [time, position.x, position.y, position.z]
+        })
+        .collect())
+}
@@ -0,0 +1,147 @@
+#ifndef PNT_RUST_H
+#define PNT_RUST_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Result of every call
+ */
+typedef enum PntStatus {
+  PNT_STATUS_OK = 0,
+  PNT_STATUS_NULL_POINTER = 1,
+  PNT_STATUS_INVALID_ARGUMENT = 2,
+  PNT_STATUS_IO = 3,
+  PNT_STATUS_NOT_FOUND = 4,
+  PNT_STATUS_PROPAGATION = 5,
+  PNT_STATUS_OUT_OF_RANGE = 6,
+  PNT_STATUS_PANIC = 7,
+} PntStatus;
+
+/**
+ * Parsed navigation file
+ */
+typedef struct PntNav PntNav;
+
+/**
+ * A satellite with its ephemeris records and propagated states
+ */
+typedef struct PntSatellite PntSatellite;
+
+/**
+ * Propagated state: time as Unix seconds (UTC) and ECEF position in meters
+ */
+typedef struct PntState {
+  double t;
+  double x;
+  double y;
+  double z;
+} PntState;
+
+/**
+ * Geodetic coordinates: degrees and meters above the WGS-84 ellipsoid
+ */
+typedef struct PntLla {
+  double latitude;
+  double longitude;
+  double altitude;
+} PntLla;
+
+/**
+ * Message of the last failed call on this thread, empty after a successful one. The
+ * pointer stays valid until the next call on this thread.
+ */
+const char *pnt_last_error_message(void);
+
+/**
+ * Parse a RINEX navigation file into a new handle.
+ *
+ * # Safety
+ * `path` must be a NUL-terminated string and `out` a valid pointer to write the handle to.
+ */
+enum PntStatus pnt_nav_load(const char *path, struct PntNav **out);
+
+/**
+ * Release a handle from `pnt_nav_load`; null is ignored.
+ *
+ * # Safety
+ * `nav` must be null or a handle not freed before.
+ */
+void pnt_nav_free(struct PntNav *nav);
+
+/**
+ * Number of ephemeris records in the file.
+ *
+ * # Safety
+ * `nav` must be a live handle and `out` a valid pointer.
+ */
+enum PntStatus pnt_nav_record_count(const struct PntNav *nav, size_t *out);
+
+/**
+ * New satellite handle for a RINEX id such as "G05", holding a copy of its ephemeris
+ * records from `nav`.
+ *
+ * # Safety
+ * `nav` must be a live handle, `sat_id` a NUL-terminated string and `out` a valid pointer.
+ */
+enum PntStatus pnt_satellite_new(const struct PntNav *nav,
+                                 const char *sat_id,
+                                 struct PntSatellite **out);
+
+/**
+ * Release a handle from `pnt_satellite_new`; null is ignored.
+ *
+ * # Safety
+ * `satellite` must be null or a handle not freed before.
+ */
+void pnt_satellite_free(struct PntSatellite *satellite);
+
+/**
+ * Propagate from `start_unix_s` (UTC) over `duration_s` in steps of `step_s`, replacing
+ * any earlier states.
+ *
+ * # Safety
+ * `satellite` must be a live handle.
+ */
+enum PntStatus pnt_satellite_propagate(struct PntSatellite *satellite,
+                                       double start_unix_s,
+                                       double duration_s,
+                                       double step_s);
+
+/**
+ * Number of propagated states.
+ *
+ * # Safety
+ * `satellite` must be a live handle and `out` a valid pointer.
+ */
+enum PntStatus pnt_satellite_state_count(const struct PntSatellite *satellite, size_t *out);
+
+/**
+ * Propagated state `index`.
+ *
+ * # Safety
+ * `satellite` must be a live handle and `out` a valid pointer.
+ */
+enum PntStatus pnt_state_get(const struct PntSatellite *satellite,
+                             size_t index,
+                             struct PntState *out);
+
+/**
+ * ECEF meters to geodetic coordinates.
+ *
+ * # Safety
+ * `out` must be a valid pointer.
+ */
+enum PntStatus pnt_ecef_to_lla(double x, double y, double z, struct PntLla *out);
+
+/**
+ * Geodetic coordinates to ECEF meters, written to `out[0..3]`.
+ *
+ * # Safety
+ * `lla` must be a valid pointer and `out` point to three writable doubles.
+ */
+enum PntStatus pnt_lla_to_ecef(const struct PntLla *lla, double *out);
+
+#endif  /* PNT_RUST_H */
@@ -0,0 +1,48 @@
+#!/bin/sh
+# Build and test with the default features and without ndarray, then check that both
+# builds propagate the bundled nav file to the same bits. The tests check that output
+# on its own: batch states against single epochs and the orbits against vis-viva.
+# Where the wasm32 and thumbv7em targets are installed, the core is built for them too.
+set -eu
+cd "$(dirname "$0")/.."
+
+out=$(mktemp -d)
+trap 'rm -rf "$out"' EXIT
+
+check() {
+    name=$1
+    shift
+    echo "== $name"
+    cargo test --quiet "$@"
+    cargo run --quiet --release --example propagation_digest "$@" >"$out/$name.txt"
+}
+
+check default
+check no-ndarray --no-default-features --features std-fs
+
+if cargo tree --quiet --no-default-features --features std-fs -e normal | grep -q ndarray; then
+    echo "ndarray is still a dependency without the ndarray feature" >&2
+    exit 1
+fi
+
+# Build the library for a target if it is installed
+cross() {
+    target=$1
+    shift
+    if rustup target list --installed 2>/dev/null | grep -qx "$target"; then
+        echo "== $target"
+        cargo build --quiet --lib --target "$target" "$@"
+    else
+        echo "== $target skipped, the target is not installed"
+    fi
+}
+
+cross wasm32-unknown-unknown --no-default-features --features std
+cross thumbv7em-none-eabihf --no-default-features
+
+if ! cmp -s "$out/default.txt" "$out/no-ndarray.txt"; then
+    echo "propagation differs between the feature sets:" >&2
+    diff "$out/default.txt" "$out/no-ndarray.txt" | head -20 >&2
+    exit 1
+fi
+echo "$(wc -l <"$out/default.txt") lines of propagation output agree"
@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""Reference statistics of the G17 state pair in data/analysis, independently of the crate.
+
+The states of the record of toe 04:00 are differenced against those of toe 06:00 on their
+common epochs, rotated into radial, along-track (from the inertial velocity of the second
+series) and cross-track components, and reduced to RMS and maximum absolute values, with
+the GPS SISRE weights 0.98 and 1/7.
+
+    python3 scripts/orbit_comparison_reference.py
+"""
+import math
+from pathlib import Path
+
+C = 299792458.0
+OMEGA_E = 7.2921151467e-5
+W_RADIAL, W_CROSS = 0.98, math.sqrt(1.0 / 49.0)
+DATA = Path(__file__).resolve().parent.parent / "data" / "analysis"
+
+
+def read(name):
+    rows = []
+    for line in (DATA / name).read_text().splitlines():
+        if line.startswith("#") or not line.strip():
+            continue
+        rows.append([float(v) for v in line.split()])
+    return rows
+
+
+def dot(a, b):
+    return sum(x * y for x, y in zip(a, b))
+
+
+def cross(a, b):
+    return [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+
+
+def unit(a):
+    n = math.sqrt(dot(a, a))
+    return [x / n for x in a]
+
+
+def stats(values):
+    rms = math.sqrt(sum(v * v for v in values) / len(values))
+    return rms, max(abs(v) for v in values)
+
+
+def main():
+    broadcast = {row[0]: row for row in read("G17_toe0400.txt")}
+    columns = {name: [] for name in ("radial", "along", "cross", "clock", "orbit", "sisre")}
+    for row in read("G17_toe0600.txt"):
+        other = broadcast.get(row[0])
+        if other is None:
+            continue
+        position, velocity = row[1:4], row[4:7]
+        inertial = [velocity[0] - OMEGA_E * position[1], velocity[1] + OMEGA_E * position[0], velocity[2]]
+        r_hat = unit(position)
+        c_hat = unit(cross(position, inertial))
+        a_hat = cross(c_hat, r_hat)
+        diff = [b - p for b, p in zip(other[1:4], position)]
+        r, a, c = dot(diff, r_hat), dot(diff, a_hat), dot(diff, c_hat)
+        dt = (other[7] - row[7]) * C
+        horizontal = W_CROSS**2 * (a * a + c * c)
+        for name, value in zip(columns, (r, a, c, dt)):
+            columns[name].append(value)
+        columns["orbit"].append(math.sqrt((W_RADIAL * r) ** 2 + horizontal))
+        columns["sisre"].append(math.sqrt((W_RADIAL * r - dt) ** 2 + horizontal))
+    print("epochs", len(columns["radial"]))
+    for name, values in columns.items():
+        print("{:7} rms {:.6f} max {:.6f}".format(name, *stats(values)))
+
+
+if __name__ == "__main__":
+    main()
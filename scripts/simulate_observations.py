@@ -0,0 +1,216 @@
+#!/usr/bin/env python3
+"""Simulate the L1 C/A observations of data/observations/GCGO_20230612_0400_sim.rnx.
+
+The satellites follow the bundled GCGO broadcast ephemeris, evaluated here from IS-GPS-200
+independently of the crate, and the receiver sits at the station's published coordinates.
+On top of the geometry the pseudoranges carry a receiver clock, the broadcast satellite
+clock and group delay, an ionosphere 1.25 times the Klobuchar model of the coefficients
+below, a troposphere mapped with Chao's function, a constant per-satellite
+orbit/clock error and white noise. Dopplers follow the range rate and both clock drifts.
+The output is a RINEX 3.04 observation file whose header says it is simulated.
+
+    python3 scripts/simulate_observations.py > data/observations/GCGO_20230612_0400_sim.rnx
+"""
+import datetime
+import math
+import random
+from pathlib import Path
+
+MU = 3.986005e14
+OMEGA_E = 7.2921151467e-5
+C = 299792458.0
+F_REL = -4.442807633e-10
+L1 = 1575.42e6
+WEEK = 2266
+START = 101400.0  # 04:10 GPS time on 2023-06-12
+EPOCHS = 40
+INTERVAL = 30.0
+MASK = 10.0  # degrees
+STATION = (-2281621.6297, -1453585.1138, 5756964.9518)
+ALPHA = [1.8626e-08, 1.4901e-08, -1.1921e-07, -5.9605e-08]
+BETA = [1.2698e05, 0.0, -1.9661e05, -6.5536e04]
+CLOCK_BIAS = 1.25e-4  # Receiver clock at the first epoch, s
+CLOCK_DRIFT = 2.0e-9  # s/s
+IONO_SCALE = 1.25
+ZENITH_TROPO = 2.35  # m at the station
+SISRE = 0.6  # m, per satellite
+CODE_NOISE = 0.3  # m at the zenith
+DOPPLER_NOISE = 0.03  # Hz
+
+
+def parse_nav(path):
+    lines = Path(path).read_text().splitlines()
+    body = lines[[i for i, l in enumerate(lines) if "END OF HEADER" in l][0] + 1 :]
+    records = []
+    for i in range(0, len(body), 8):
+        block = body[i : i + 8]
+        head = block[0]
+        values = [float(head[23 + 19 * k : 42 + 19 * k].replace("D", "E")) for k in range(3)]
+        for line in block[1:]:
+            values += [
+                float(line[4 + 19 * k : 23 + 19 * k].replace("D", "E") or 0.0)
+                for k in range(4)
+                if line[4 + 19 * k : 23 + 19 * k].strip()
+            ]
+        hh, mm, ss = int(head[15:17]), int(head[18:20]), int(head[21:23])
+        day = int(head[12:14]) - 11  # Days since Sunday 2023-06-11
+        records.append(
+            {"prn": head[:3], "toc": day * 86400 + hh * 3600 + mm * 60 + ss, "v": values}
+        )
+    return records
+
+
+def evaluate(record, t):
+    (af0, af1, af2, _, crs, dn, m0, cuc, e, cus, sqrt_a, toe, cic, omega0, cis, i0, crc,
+     omega, omega_dot, idot) = record["v"][:20]
+    tgd = record["v"][25]
+    a = sqrt_a**2
+    tk = t - toe
+    mean = m0 + (math.sqrt(MU / a**3) + dn) * tk
+    ecc = mean
+    for _ in range(30):
+        ecc -= (ecc - e * math.sin(ecc) - mean) / (1 - e * math.cos(ecc))
+    nu = math.atan2(math.sqrt(1 - e * e) * math.sin(ecc), math.cos(ecc) - e)
+    phi = nu + omega
+    s2, c2 = math.sin(2 * phi), math.cos(2 * phi)
+    u = phi + cus * s2 + cuc * c2
+    r = a * (1 - e * math.cos(ecc)) + crs * s2 + crc * c2
+    inc = i0 + cis * s2 + cic * c2 + idot * tk
+    node = omega0 + (omega_dot - OMEGA_E) * tk - OMEGA_E * toe
+    xp, yp = r * math.cos(u), r * math.sin(u)
+    position = (
+        xp * math.cos(node) - yp * math.cos(inc) * math.sin(node),
+        xp * math.sin(node) + yp * math.cos(inc) * math.cos(node),
+        yp * math.sin(inc),
+    )
+    dt = t - record["toc"]
+    clock = af0 + af1 * dt + af2 * dt * dt + F_REL * e * sqrt_a * math.sin(ecc)
+    return position, clock, tgd
+
+
+def geodetic(x, y, z):
+    a, f = 6378137.0, 1 / 298.257223563
+    e2 = f * (2 - f)
+    lon = math.atan2(y, x)
+    p = math.hypot(x, y)
+    lat = math.atan2(z, p * (1 - e2))
+    for _ in range(10):
+        n = a / math.sqrt(1 - e2 * math.sin(lat) ** 2)
+        h = p / math.cos(lat) - n
+        lat = math.atan2(z, p * (1 - e2 * n / (n + h)))
+    return lat, lon, h
+
+
+def look_angles(receiver, satellite):
+    lat, lon, _ = geodetic(*receiver)
+    d = [s - r for s, r in zip(satellite, receiver)]
+    east = -math.sin(lon) * d[0] + math.cos(lon) * d[1]
+    north = (-math.sin(lat) * math.cos(lon) * d[0] - math.sin(lat) * math.sin(lon) * d[1]
+             + math.cos(lat) * d[2])
+    up = (math.cos(lat) * math.cos(lon) * d[0] + math.cos(lat) * math.sin(lon) * d[1]
+          + math.sin(lat) * d[2])
+    return math.atan2(east, north) % (2 * math.pi), math.atan2(up, math.hypot(east, north))
+
+
+def klobuchar(lat, lon, azimuth, elevation, t):
+    el = elevation / math.pi
+    psi = 0.0137 / (el + 0.11) - 0.022
+    phi_i = max(-0.416, min(0.416, lat / math.pi + psi * math.cos(azimuth)))
+    lam_i = lon / math.pi + psi * math.sin(azimuth) / math.cos(phi_i * math.pi)
+    phi_m = phi_i + 0.064 * math.cos((lam_i - 1.617) * math.pi)
+    local = (4.32e4 * lam_i + t) % 86400.0
+    obliquity = 1.0 + 16.0 * (0.53 - el) ** 3
+    amp = max(0.0, sum(c * phi_m**k for k, c in enumerate(ALPHA)))
+    per = max(72000.0, sum(c * phi_m**k for k, c in enumerate(BETA)))
+    x = 2 * math.pi * (local - 50400.0) / per
+    vertical = 5e-9 + (amp * (1 - x * x / 2 + x**4 / 24) if abs(x) < 1.57 else 0.0)
+    return C * obliquity * vertical
+
+
+def chao(elevation):
+    s, t = math.sin(elevation), math.tan(elevation)
+    return 1.0 / (s + 0.00143 / (t + 0.0445))
+
+
+def signal(record, receive_time):
+    """Rotated satellite position, satellite clock and group delay for a receive time"""
+    flight = 0.075
+    for _ in range(10):
+        position, clock, tgd = evaluate(record, receive_time - flight)
+        theta = OMEGA_E * flight
+        rotated = (
+            math.cos(theta) * position[0] + math.sin(theta) * position[1],
+            -math.sin(theta) * position[0] + math.cos(theta) * position[1],
+            position[2],
+        )
+        flight = math.dist(rotated, STATION) / C
+    return rotated, clock, tgd
+
+
+def main():
+    random.seed(20230612)
+    root = Path(__file__).resolve().parent.parent
+    records = parse_nav(root / "constellation/GCGO00USA_R_20231630000_01D_GN.rnx")
+    healthy = [r for r in records if r["v"][24] == 0.0]
+    lat, lon, _ = geodetic(*STATION)
+    biases = {prn: random.gauss(0.0, SISRE) for prn in sorted({r["prn"] for r in healthy})}
+
+    first = gps_calendar(START)
+    header = [
+        ("     3.04           OBSERVATION DATA    G", "RINEX VERSION / TYPE"),
+        ("simulate_observations.py", "PGM / RUN BY / DATE"),
+        ("SIMULATED, NOT RECORDED: scripts/simulate_observations.py", "COMMENT"),
+        (f"Ionosphere: {IONO_SCALE} x Klobuchar", "COMMENT"),
+        ("GCGO", "MARKER NAME"),
+        ("40408M006", "MARKER NUMBER"),
+        ("".join(f"{v:14.4f}" for v in STATION), "APPROX POSITION XYZ"),
+        ("G    3 C1C S1C D1C", "SYS / # / OBS TYPES"),
+        (f"{INTERVAL:10.3f}", "INTERVAL"),
+        (f"{first.year:6d}{first.month:6d}{first.day:6d}{first.hour:6d}{first.minute:6d}"
+         f"{first.second:13.7f}     GPS", "TIME OF FIRST OBS"),
+        ("", "END OF HEADER"),
+    ]
+    for data, label in header:
+        print(f"{data:<60}{label}")
+    for k in range(EPOCHS):
+        tag = START + k * INTERVAL
+        clock_bias = CLOCK_BIAS + CLOCK_DRIFT * k * INTERVAL
+        receive = tag - clock_bias
+        lines = []
+        for prn, bias in biases.items():
+            candidates = [r for r in healthy if r["prn"] == prn]
+            # Nearest toe, ties to the earlier record, as the broadcast model is used
+            record = min(candidates, key=lambda r: (abs(r["v"][11] - receive), r["v"][11]))
+            rotated, sat_clock, tgd = signal(record, receive)
+            azimuth, elevation = look_angles(STATION, rotated)
+            if math.degrees(elevation) < MASK:
+                continue
+            geometric = math.dist(rotated, STATION)
+            iono = IONO_SCALE * klobuchar(lat, lon, azimuth, elevation, receive)
+            tropo = ZENITH_TROPO * chao(elevation)
+            noise = random.gauss(0.0, CODE_NOISE / math.sin(elevation))
+            pseudorange = (geometric + C * (clock_bias - sat_clock) + C * tgd + iono + tropo
+                           + bias + noise)
+            # Range rate of the rotated range, by central difference over one second
+            ahead = math.dist(signal(record, receive + 0.5)[0], STATION)
+            behind = math.dist(signal(record, receive - 0.5)[0], STATION)
+            sat_drift = evaluate(record, receive + 0.5)[1] - evaluate(record, receive - 0.5)[1]
+            range_rate = ahead - behind + C * (CLOCK_DRIFT - sat_drift)
+            doppler = -range_rate * L1 / C + random.gauss(0.0, DOPPLER_NOISE)
+            snr = 32.0 + 17.0 * math.sin(elevation) + random.gauss(0.0, 1.0)
+            snr = round(snr, 1)
+            lines.append(f"{prn}" + "".join(f"{v:14.3f}  " for v in (pseudorange, snr, doppler)))
+        epoch = gps_calendar(tag)
+        print(f"> {epoch:%Y %m %d %H %M}{epoch.second:11.7f}  0{len(lines):3d}")
+        for line in lines:
+            print(line.rstrip())
+
+
+def gps_calendar(time_of_week):
+    """GPS calendar time of a time of week, without leap seconds"""
+    start = datetime.datetime(1980, 1, 6) + datetime.timedelta(weeks=WEEK)
+    return start + datetime.timedelta(seconds=time_of_week)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,313 @@
+use crate::constellation::Constellation;
+use crate::doppler::predict_doppler;
+use crate::gnss::{self, GpsTime, SatId, AER, ECEF, LLA};
+use crate::horizon::ElevationMask;
+use crate::propagator::{BroadcastPropagator, OrbitPropagator};
+use crate::pseudorange;
+use crate::satellite::PropagationConfig;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+
+const RATE_SPAN: f64 = 1.0; // Half the interval the Doppler rate is differenced over, s
+
+/// What a receiver knows going into a cold start, as 1-sigma uncertainties, and how wide
+/// the search windows are drawn around the predictions
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssistConfig {
+    pub mask: f64,             // Elevation mask, degrees
+    pub position_sigma: f64,   // Of the approximate receiver position, m
+    pub time_sigma: f64,       // Of the receiver's idea of GPS time, s
+    pub oscillator_sigma: f64, // Receiver oscillator frequency error, s/s
+    // Growth of the satellite velocity error with ephemeris age, m/s per s; broadcast
+    // ephemerides stay near mm/s within their fit interval and drift off beyond it
+    pub velocity_error_growth: f64,
+    pub sigmas: f64, // Half-widths as this many standard deviations
+    pub propagation: PropagationConfig,
+}
+
+impl Default for AssistConfig {
+    fn default() -> Self {
+        Self {
+            mask: 5.0,
+            position_sigma: 10_000.0,
+            time_sigma: 2.0,
+            oscillator_sigma: 0.5e-6,
+            velocity_error_growth: 1e-5,
+            sigmas: 3.0,
+            propagation: PropagationConfig::new(),
+        }
+    }
+}
+
+impl AssistConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mask(mut self, degrees: f64) -> Self {
+        self.mask = degrees;
+        self
+    }
+
+    pub fn position_sigma(mut self, meters: f64) -> Self {
+        self.position_sigma = meters;
+        self
+    }
+
+    pub fn time_sigma(mut self, seconds: f64) -> Self {
+        self.time_sigma = seconds;
+        self
+    }
+
+    pub fn oscillator_sigma(mut self, fraction: f64) -> Self {
+        self.oscillator_sigma = fraction;
+        self
+    }
+
+    pub fn velocity_error_growth(mut self, meters_per_second: f64) -> Self {
+        self.velocity_error_growth = meters_per_second;
+        self
+    }
+
+    pub fn sigmas(mut self, sigmas: f64) -> Self {
+        self.sigmas = sigmas;
+        self
+    }
+
+    /// Velocities and clocks are always propagated
+    pub fn propagation(mut self, propagation: PropagationConfig) -> Self {
+        self.propagation = propagation;
+        self
+    }
+}
+
+/// Where to search for one satellite's signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AcqAssist {
+    pub sat_id: SatId,
+    pub signal: Signal,
+    pub aer: AER,
+    pub doppler: f64, // Hz, positive when approaching, receiver oscillator ideal
+    pub doppler_rate: f64, // Hz/s
+    pub doppler_half_width: f64, // Search ± this around `doppler`, Hz
+    // Chip of the primary code arriving at the epoch, [0, code length), from the transmit
+    // time on the satellite clock
+    pub code_phase: f64,
+    pub code_phase_half_width: f64, // Chips; half the code length once the code is unknown
+    pub ephemeris_age: f64,         // Epoch minus toe of the record used, s
+}
+
+impl Constellation {
+    /// Predicted Doppler and code phase on `signal` of every healthy satellite of its system
+    /// above the mask, for a static receiver at roughly `observer` at roughly `epoch`, with
+    /// search windows from the uncertainties in `config`. Sorted by elevation, highest
+    /// first. GLONASS and SBAS, whose state-vector ephemerides are not propagated, get none.
+    ///
+    /// The Doppler window adds in quadrature the effect of the position error on the range
+    /// rate, the Doppler rate over the time error, the receiver oscillator error and the
+    /// ephemeris velocity error; the code window the time and position errors as chips.
+    pub fn acquisition_assist(
+        &self,
+        observer: &LLA,
+        epoch: DateTime<Utc>,
+        signal: Signal,
+        config: &AssistConfig,
+    ) -> Vec<AcqAssist> {
+        let receiver = observer.to_ecef();
+        let receive_time = gnss::gps_seconds(epoch);
+        let propagation = config
+            .propagation
+            .clone()
+            .with_velocity(true)
+            .with_clock(true);
+        let mut assists: Vec<AcqAssist> = self
+            .iter()
+            .filter(|satellite| satellite.active)
+            .filter_map(|satellite| {
+                if signal.constellation() != satellite.id.constellation
+                    || !satellite.id.constellation.has_keplerian_ephemeris()
+                {
+                    return None;
+                }
+                let propagator =
+                    BroadcastPropagator::new(self.records(satellite.id), propagation.clone())
+                        .ok()?;
+                let record = propagator.record_at(GpsTime::from_seconds(receive_time));
+                if !record.is_healthy() {
+                    return None;
+                }
+                let model =
+                    pseudorange::model_pseudorange(&receiver, &propagator, receive_time, 0.0)
+                        .ok()?;
+                if !config.mask.is_above(&model.aer) {
+                    return None;
+                }
+                let drift = record.sv_clock_drift;
+                let doppler_at = |time: f64| {
+                    let state = propagator.state_at(GpsTime::from_seconds(time)).ok()?;
+                    let velocity = state.velocity?;
+                    let doppler = predict_doppler(
+                        &state.position,
+                        &velocity,
+                        drift,
+                        &receiver,
+                        &ECEF::default(),
+                        signal.frequency_hz(),
+                    )?;
+                    Some((state, velocity, doppler))
+                };
+                let transmit_time = model.transmit_time;
+                let (state, velocity, doppler) = doppler_at(transmit_time)?;
+                let (_, _, before) = doppler_at(transmit_time - RATE_SPAN)?;
+                let (_, _, after) = doppler_at(transmit_time + RATE_SPAN)?;
+                let doppler_rate = (after - before) / (2.0 * RATE_SPAN);
+
+                // Range rate sensitivity to the receiver position: the relative velocity
+                // across the line of sight over the range
+                let line_of_sight = gnss::unit_line_of_sight(&receiver, &state.position)?;
+                let along = velocity.dot(&line_of_sight);
+                let across = (velocity.dot(&velocity) - along * along).max(0.0).sqrt();
+                let range = gnss::range(&receiver, &state.position);
+                let hz_per_mps = signal.frequency_hz() / gnss::C_LIGHT;
+                let doppler_sigma = rss(&[
+                    config.position_sigma * across / range * hz_per_mps,
+                    config.time_sigma * doppler_rate.abs(),
+                    config.oscillator_sigma * signal.frequency_hz(),
+                    config.velocity_error_growth * state.ephemeris_age.abs() * hz_per_mps,
+                ]);
+
+                // Every primary code period divides a second, so only the time within the
+                // second matters; working there keeps the sub-chip precision that GPS
+                // seconds since 1980 lose
+                let flight_time = (model.geometric_range + model.sagnac) / gnss::C_LIGHT;
+                let satellite_time = epoch.timestamp_subsec_nanos() as f64 * 1e-9 - flight_time
+                    + state.clock_bias.unwrap_or(0.0);
+                let code_length = signal.code_length() as f64;
+                let code_phase = (satellite_time / signal.code_period()).rem_euclid(1.0);
+                let code_sigma = rss(&[config.time_sigma, config.position_sigma / gnss::C_LIGHT])
+                    * signal.chip_rate();
+                Some(AcqAssist {
+                    sat_id: satellite.id,
+                    signal,
+                    aer: model.aer,
+                    doppler,
+                    doppler_rate,
+                    doppler_half_width: config.sigmas * doppler_sigma,
+                    code_phase: code_phase * code_length,
+                    code_phase_half_width: (config.sigmas * code_sigma).min(code_length / 2.0),
+                    ephemeris_age: state.ephemeris_age,
+                })
+            })
+            .collect();
+        assists.sort_by(|a, b| b.aer.elevation.total_cmp(&a.aer.elevation));
+        assists
+    }
+}
+
+/// Root sum of squares
+fn rss(terms: &[f64]) -> f64 {
+    terms.iter().map(|term| term * term).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::RinexNav;
+    use crate::simulation::{GaussianNoise, SimulationConfig};
+    use crate::test_support::{station, NAV};
+    use chrono::TimeZone;
+
+    const GLONASS_RECORD: &str = "\
+R01 2023 06 12 02 15 00 1.519024372101D-05 0.000000000000D+00 5.184000000000D+05
+     1.182464062500D+04-2.217864990234D+00 1.862645149231D-09 0.000000000000D+00
+     1.259628808594D+04 1.081981658936D+00-9.313225746155D-10 1.000000000000D+00
+     1.924823583984D+04 1.225566864014D+00-1.862645149231D-09 0.000000000000D+00
+";
+
+    #[test]
+    fn assists_healthy_keplerian_satellites_in_view() {
+        let nav: RinexNav = format!("{}{}", NAV, GLONASS_RECORD).parse().unwrap();
+        let mut constellation = Constellation::from_nav(nav);
+        constellation
+            .get_mut("R01".parse::<SatId>().unwrap())
+            .unwrap()
+            .frequency_channel = Some(1);
+        let site = station().to_lla();
+        let epoch = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let config = AssistConfig::new();
+
+        let assists = constellation.acquisition_assist(&site, epoch, Signal::GpsL1, &config);
+        assert!(assists.len() >= 4);
+        assert!(assists
+            .windows(2)
+            .all(|pair| pair[0].aer.elevation >= pair[1].aer.elevation));
+        for assist in &assists {
+            assert!(assist.aer.elevation >= config.mask);
+            assert!(assist.doppler.abs() < 6_000.0);
+            assert!((0.0..1023.0).contains(&assist.code_phase));
+            assert!(assist.doppler_half_width > 0.0);
+            assert!(assist.code_phase_half_width <= 511.5);
+        }
+        assert!(assists
+            .iter()
+            .all(|assist| assist.sat_id != "G22".parse().unwrap()));
+
+        let glonass = Signal::GlonassG1(1);
+        assert!(constellation
+            .acquisition_assist(&site, epoch, glonass, &config)
+            .is_empty());
+    }
+
+    /// Doppler simulated for a receiver with a drifting oscillator and noise, not recorded,
+    /// against the assistance computed from a position 2.7 km off and a clock 1 s off
+    #[test]
+    fn simulated_doppler_falls_in_the_search_window() {
+        let nav: RinexNav = NAV.parse().unwrap();
+        let constellation = Constellation::from_nav(nav);
+        let truth = station();
+        let epoch = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let simulation = SimulationConfig {
+            receiver_clock_drift: 3e-7,
+            ..SimulationConfig::default()
+        };
+        let mut noise = GaussianNoise::new(0.0, 0.0, 0.5, 7);
+        let simulated =
+            constellation.simulate_observations(&[(epoch, truth)], &simulation, &mut noise);
+
+        let guess = ECEF::new(truth.x + 2000.0, truth.y - 1500.0, truth.z + 1000.0).to_lla();
+        let config = AssistConfig::new()
+            .position_sigma(3000.0)
+            .time_sigma(1.0)
+            .mask(simulation.elevation_mask);
+        let assists = constellation.acquisition_assist(
+            &guess,
+            epoch + chrono::Duration::seconds(1),
+            Signal::GpsL1,
+            &config,
+        );
+        assert!(assists.len() >= 6);
+        for assist in &assists {
+            let Some(satellite) = simulated[0]
+                .satellites
+                .iter()
+                .find(|satellite| satellite.sat_id == assist.sat_id)
+            else {
+                // Just above the mask from the guessed position, below it from the truth
+                assert!(assist.aer.elevation < simulation.elevation_mask + 1.0);
+                continue;
+            };
+            let doppler = satellite.signals[0].doppler.unwrap();
+            let error = (doppler - assist.doppler).abs();
+            // The oscillator alone shifts every satellite by about 470 Hz
+            assert!(error > 100.0, "{}: {} Hz", assist.sat_id, error);
+            assert!(
+                error <= assist.doppler_half_width,
+                "{}: {} Hz off, window {} Hz",
+                assist.sat_id,
+                error,
+                assist.doppler_half_width
+            );
+            assert!(assist.doppler_half_width < 3000.0);
+        }
+    }
+}
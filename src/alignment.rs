@@ -0,0 +1,215 @@
+use crate::constellation::Constellation;
+use crate::gnss::{self, GpsTime, SatId, ECEF};
+use crate::positioning::PseudorangeObservation;
+use crate::propagator::BroadcastPropagator;
+use crate::pseudorange::{self, PseudorangeModel};
+use crate::satellite::{PropagationConfig, PropagationError};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// An observation with its satellite modeled at the transmit time
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedObservation {
+    pub observation: PseudorangeObservation,
+    pub model: PseudorangeModel, // Transmit time, satellite state, range terms and look angles
+    pub line_of_sight: ECEF,     // Unit vector from the receiver to the satellite
+    pub satellite_velocity: Option<ECEF>, // At transmit time, if the propagator provides it
+    pub satellite_clock_drift: f64, // Broadcast, s/s
+}
+
+/// Observations of one epoch aligned to their transmit times
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedEpoch {
+    pub epoch: DateTime<Utc>, // Receiver time tag
+    pub receive_time: f64,    // GPS seconds, the time tag corrected by `clock_bias`
+    pub clock_bias: f64,      // Receiver clock offset used, s
+    pub observations: Vec<AlignedObservation>,
+    pub missing: Vec<(SatId, PropagationError)>, // Satellites that could not be modeled
+}
+
+impl AlignedEpoch {
+    pub fn get(&self, sat_id: SatId) -> Option<&AlignedObservation> {
+        self.observations
+            .iter()
+            .find(|aligned| aligned.observation.sat_id == sat_id)
+    }
+}
+
+/// Finds the transmit-time satellite state of every observation of an epoch by iterating
+/// the light-time equation from the receiver's true receive time. Propagators are built
+/// once per satellite and kept across epochs.
+pub struct EpochAligner<'a> {
+    constellation: &'a Constellation,
+    config: PropagationConfig,
+    propagators: BTreeMap<SatId, Option<BroadcastPropagator>>,
+}
+
+impl<'a> EpochAligner<'a> {
+    /// Clocks are always propagated; enable velocity in `config` for Doppler work
+    pub fn new(constellation: &'a Constellation, config: PropagationConfig) -> Self {
+        Self {
+            constellation,
+            config: config.with_clock(true),
+            propagators: BTreeMap::new(),
+        }
+    }
+
+    /// Propagator of a satellite, None without usable ephemeris
+    pub fn propagator(&mut self, sat_id: SatId) -> Option<&BroadcastPropagator> {
+        let constellation = self.constellation;
+        let config = &self.config;
+        self.propagators
+            .entry(sat_id)
+            .or_insert_with(|| {
+                BroadcastPropagator::new(constellation.records(sat_id), config.clone()).ok()
+            })
+            .as_ref()
+    }
+
+    /// Model every observation of an epoch for a receiver at `receiver`. With the clock
+    /// offset (s) unknown, it is taken from the median code residual of a first pass, so
+    /// offsets of milliseconds do not shift the satellites along their orbits; this needs
+    /// a receiver position within a few kilometres.
+    pub fn align(
+        &mut self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+        receiver: &ECEF,
+        clock_bias: Option<f64>,
+    ) -> AlignedEpoch {
+        let time_tag = gnss::gps_seconds(epoch);
+        let clock_bias = match clock_bias {
+            Some(clock_bias) => clock_bias,
+            None => {
+                let first = self.align_at(epoch, time_tag, 0.0, observations, receiver);
+                let mut offsets: Vec<f64> = first
+                    .observations
+                    .iter()
+                    .map(|aligned| aligned.observation.pseudorange - aligned.model.total())
+                    .collect();
+                offsets.sort_by(f64::total_cmp);
+                offsets.get(offsets.len() / 2).copied().unwrap_or(0.0) / gnss::C_LIGHT
+            }
+        };
+        self.align_at(epoch, time_tag, clock_bias, observations, receiver)
+    }
+
+    fn align_at(
+        &mut self,
+        epoch: DateTime<Utc>,
+        time_tag: f64,
+        clock_bias: f64,
+        observations: &[PseudorangeObservation],
+        receiver: &ECEF,
+    ) -> AlignedEpoch {
+        let receive_time = time_tag - clock_bias;
+        let mut aligned = AlignedEpoch {
+            epoch,
+            receive_time,
+            clock_bias,
+            observations: Vec::with_capacity(observations.len()),
+            missing: Vec::new(),
+        };
+        for obs in observations {
+            let Some(propagator) = self.propagator(obs.sat_id) else {
+                aligned
+                    .missing
+                    .push((obs.sat_id, PropagationError::NoEphemeris));
+                continue;
+            };
+            let record = propagator.record_at(GpsTime::from_seconds(receive_time));
+            match pseudorange::model_pseudorange(receiver, propagator, receive_time, record.tgd) {
+                Ok(model) => aligned.observations.push(AlignedObservation {
+                    observation: *obs,
+                    line_of_sight: gnss::unit_line_of_sight(receiver, &model.satellite_position)
+                        .unwrap_or_default(),
+                    satellite_velocity: model.satellite_state.velocity,
+                    satellite_clock_drift: record.sv_clock_drift,
+                    model,
+                }),
+                Err(err) => aligned.missing.push((obs.sat_id, err)),
+            }
+        }
+        aligned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Signal;
+    use crate::simulation::{NoError, SimulationConfig};
+    use crate::test_support::{constellation, station};
+    use chrono::TimeZone;
+
+    /// Exact observations of the station by a receiver whose clock runs `clock_bias` ahead
+    fn observe(
+        constellation: &Constellation,
+        clock_bias: f64,
+    ) -> (DateTime<Utc>, Vec<PseudorangeObservation>) {
+        let truth = [(
+            Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap(),
+            station(),
+        )];
+        let config = SimulationConfig {
+            receiver_clock_bias: clock_bias,
+            ..SimulationConfig::default()
+        };
+        let epoch = &constellation.simulate_observations(&truth, &config, &mut NoError)[0];
+        (epoch.epoch, epoch.pseudoranges(Signal::GpsL1))
+    }
+
+    #[test]
+    fn millisecond_clock_offsets_are_estimated_before_modeling() {
+        let constellation = constellation();
+        let mut aligner = EpochAligner::new(&constellation, PropagationConfig::new());
+        let (epoch, observations) = observe(&constellation, 0.0);
+        let reference = aligner.align(epoch, &observations, &station(), Some(0.0));
+        for clock_bias in [1e-3, -5e-3, 2e-2] {
+            let (epoch, observations) = observe(&constellation, clock_bias);
+            let aligned = aligner.align(epoch, &observations, &station(), None);
+            // The first pass places the satellites up to tens of metres off, so the offset
+            // comes out within tens of nanoseconds
+            assert!((aligned.clock_bias - clock_bias).abs() < 1e-7);
+            assert!((aligned.receive_time - reference.receive_time).abs() < 1e-7);
+            assert_eq!(aligned.observations.len(), reference.observations.len());
+            for (aligned, reference) in aligned.observations.iter().zip(&reference.observations) {
+                let model = &aligned.model;
+                assert!((model.transmit_time - reference.model.transmit_time).abs() < 1e-7);
+                let shift = (model.satellite_position - reference.model.satellite_position).norm();
+                assert!(shift < 1e-3, "{}", shift);
+                let residual = aligned.observation.pseudorange - model.total();
+                assert!((residual - gnss::C_LIGHT * clock_bias).abs() < 1e-3);
+            }
+
+            // Taking the time tag as the receive time moves the satellites along their orbits
+            let naive = aligner.align(epoch, &observations, &station(), Some(0.0));
+            let shift = (naive.observations[0].model.satellite_position
+                - reference.observations[0].model.satellite_position)
+                .norm();
+            assert!(shift > 2.0, "{}", shift);
+        }
+    }
+
+    #[test]
+    fn satellites_without_ephemeris_are_reported_missing() {
+        let constellation = constellation();
+        let mut aligner = EpochAligner::new(&constellation, PropagationConfig::new());
+        let (epoch, mut observations) = observe(&constellation, 1e-3);
+        let visible = observations.len();
+        let galileo = SatId::new(gnss::Constellation::Galileo, 11);
+        observations.push(PseudorangeObservation {
+            sat_id: galileo,
+            pseudorange: 2.3e7,
+            snr: None,
+            doppler: None,
+        });
+        let aligned = aligner.align(epoch, &observations, &station(), None);
+        assert_eq!(aligned.observations.len(), visible);
+        assert_eq!(aligned.missing, [(galileo, PropagationError::NoEphemeris)]);
+        assert!(aligned.get(galileo).is_none());
+        assert!(aligned.get(observations[0].sat_id).is_some());
+        assert!(aligner.propagator(galileo).is_none());
+        assert!((aligned.clock_bias - 1e-3).abs() < 1e-7);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::gnss::{self, Constellation, StateSeries, ECEF};
+use crate::satellite::Satellite;
+
+const EPOCH_MATCH_TOL: f64 = 1e-3; // Seconds within which two states count as the same epoch
+
+/// RMS and maximum absolute value of one difference component, in meters
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComponentStats {
+    pub rms: f64,
+    pub max: f64,
+}
+
+impl ComponentStats {
+    fn from_values(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let sum_sq: f64 = values.iter().map(|v| v * v).sum();
+        Self {
+            rms: (sum_sq / values.len() as f64).sqrt(),
+            max: values.iter().fold(0.0, |max, v| v.abs().max(max)),
+        }
+    }
+}
+
+/// Broadcast minus precise differences in radial/along-track/cross-track components
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrbitComparison {
+    pub epochs: usize,
+    pub radial: ComponentStats,
+    pub along_track: ComponentStats,
+    pub cross_track: ComponentStats,
+    pub clock: Option<ComponentStats>, // Clock difference times c, when both sides have clocks
+    pub orbit_sisre: ComponentStats,   // Orbit-only signal-in-space range error
+    pub sisre: Option<ComponentStats>, // Including the clock, when available
+}
+
+/// Weights of the radial and of the along/cross-track errors in the global-average SISRE
+pub fn sisre_weights(constellation: Constellation) -> (f64, f64) {
+    match constellation {
+        Constellation::Glonass => (0.98, (1.0f64 / 45.0).sqrt()),
+        Constellation::Galileo => (0.98, (1.0f64 / 61.0).sqrt()),
+        Constellation::BeiDou => (0.98, (1.0f64 / 54.0).sqrt()),
+        _ => (0.98, (1.0f64 / 49.0).sqrt()),
+    }
+}
+
+/// Compare two state series on their common epochs. The along-track direction comes from
+/// the inertial velocity of the precise states, or from neighbouring positions if absent.
+pub fn compare_states(
+    broadcast: &StateSeries,
+    precise: &StateSeries,
+    constellation: Constellation,
+) -> OrbitComparison {
+    let (w_radial, w_cross) = sisre_weights(constellation);
+    let mut radial = Vec::new();
+    let mut along = Vec::new();
+    let mut cross = Vec::new();
+    let mut clock = Vec::new();
+    let mut orbit_sisre = Vec::new();
+    let mut sisre = Vec::new();
+
+    let broadcast_times = broadcast.times();
+    for reference in precise.iter() {
+        let time = reference.time();
+        let pos =
+            broadcast_times.partition_point(|&state_time| state_time < time - EPOCH_MATCH_TOL);
+        let Some(state) = broadcast
+            .get(pos)
+            .filter(|state| (state.time() - time).abs() <= EPOCH_MATCH_TOL)
+        else {
+            continue;
+        };
+        let Some(velocity) = velocity_at(precise, reference.index()) else {
+            continue;
+        };
+
+        let position = reference.position();
+        // Inertial velocity keeps the along-track axis tied to the orbit, not the rotating frame
+        let omega = ECEF::new(0.0, 0.0, gnss::OMEGA_E_DOT);
+        let velocity = velocity + omega.cross(&position);
+        let r_hat = position * (1.0 / position.norm());
+        let c_vec = position.cross(&velocity);
+        let c_hat = c_vec * (1.0 / c_vec.norm());
+        let a_hat = c_hat.cross(&r_hat);
+
+        let diff = state.position() - position;
+        let (r, a, c) = (diff.dot(&r_hat), diff.dot(&a_hat), diff.dot(&c_hat));
+        radial.push(r);
+        along.push(a);
+        cross.push(c);
+        let orbit_term = w_cross * w_cross * (a * a + c * c);
+        orbit_sisre.push(((w_radial * r).powi(2) + orbit_term).sqrt());
+
+        if let (Some(dt_b), Some(dt_p)) = (state.clock_bias(), reference.clock_bias()) {
+            let dt = (dt_b - dt_p) * gnss::C_LIGHT;
+            clock.push(dt);
+            sisre.push(((w_radial * r - dt).powi(2) + orbit_term).sqrt());
+        }
+    }
+
+    OrbitComparison {
+        epochs: radial.len(),
+        radial: ComponentStats::from_values(&radial),
+        along_track: ComponentStats::from_values(&along),
+        cross_track: ComponentStats::from_values(&cross),
+        clock: (!clock.is_empty()).then(|| ComponentStats::from_values(&clock)),
+        orbit_sisre: ComponentStats::from_values(&orbit_sisre),
+        sisre: (!sisre.is_empty()).then(|| ComponentStats::from_values(&sisre)),
+    }
+}
+
+/// Stored velocity, or a finite difference of the neighbouring positions
+pub(crate) fn velocity_at(states: &StateSeries, idx: usize) -> Option<ECEF> {
+    if let Some(&velocity) = states.velocities().get(idx) {
+        return Some(velocity);
+    }
+    let (times, positions) = (states.times(), states.positions());
+    let before = idx.checked_sub(1).unwrap_or(idx);
+    let after = if idx + 1 < times.len() { idx + 1 } else { idx };
+    let dt = times[after] - times[before];
+    (dt > 0.0).then(|| (positions[after] - positions[before]) * (1.0 / dt))
+}
+
+impl Satellite {
+    /// Difference this (broadcast) series against a precise one for the same satellite
+    pub fn compare_with(&self, precise: &Satellite) -> OrbitComparison {
+        compare_states(&self.states, &precise.states, self.id.constellation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::State;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::NAV;
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+
+    fn broadcast() -> Satellite {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(300))
+            .with_velocity(true)
+            .with_clock(true);
+        let mut satellite = Satellite::builder(17).build();
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let records = nav.records_for_slice(17.into());
+        satellite
+            .propagate(start, Duration::from_secs(3600), &config, records)
+            .unwrap();
+        satellite
+    }
+
+    /// Precise orbit offset from the broadcast one by known radial, along-track and
+    /// cross-track errors and a 1 ns clock error, plus an epoch the broadcast lacks
+    fn precise(broadcast: &Satellite, along: impl Fn(usize) -> f64) -> Satellite {
+        let mut precise = Satellite::builder(17).build();
+        let mut first = State::new(broadcast.states.times()[0] - 150.0, ECEF::default());
+        first.velocity = Some(ECEF::new(3000.0, 0.0, 0.0));
+        first.clock_bias = Some(0.0);
+        precise.states.push(&first);
+        for (k, state) in broadcast.states.iter_states().enumerate() {
+            let position = state.position;
+            let velocity = state.velocity.unwrap();
+            let inertial = velocity + ECEF::new(0.0, 0.0, gnss::OMEGA_E_DOT).cross(&position);
+            let r_hat = position * (1.0 / position.norm());
+            let c_vec = position.cross(&inertial);
+            let c_hat = c_vec * (1.0 / c_vec.norm());
+            let a_hat = c_hat.cross(&r_hat);
+            let error = r_hat * 1.0 + a_hat * along(k) + c_hat * -0.5;
+            precise.states.push(&State {
+                position: position - error,
+                clock_bias: Some(state.clock_bias.unwrap() - 1e-9),
+                ..state
+            });
+        }
+        precise
+    }
+
+    #[test]
+    fn statistics_reproduce_the_injected_errors() {
+        let broadcast = broadcast();
+        let along = |k: usize| if k.is_multiple_of(2) { 2.0 } else { -4.0 };
+        let comparison = broadcast.compare_with(&precise(&broadcast, along));
+
+        assert_eq!(comparison.epochs, 12);
+        let close = |stats: ComponentStats, rms: f64, max: f64| {
+            (stats.rms - rms).abs() < 1e-5 && (stats.max - max).abs() < 1e-5
+        };
+        assert!(close(comparison.radial, 1.0, 1.0));
+        assert!(close(comparison.along_track, 10f64.sqrt(), 4.0));
+        assert!(close(comparison.cross_track, 0.5, 0.5));
+        let clock = 1e-9 * gnss::C_LIGHT;
+        assert!(close(comparison.clock.unwrap(), clock, clock));
+
+        // Per epoch 0.98 R against (A² + C²) / 49, the GPS weights
+        let orbit = |a: f64| (0.98f64.powi(2) + (a * a + 0.25) / 49.0).sqrt();
+        let sisre = |a: f64| ((0.98 - clock).powi(2) + (a * a + 0.25) / 49.0).sqrt();
+        let rms = |f: &dyn Fn(f64) -> f64| ((f(2.0).powi(2) + f(-4.0).powi(2)) / 2.0).sqrt();
+        assert!(close(comparison.orbit_sisre, rms(&orbit), orbit(-4.0)));
+        assert!(close(comparison.sisre.unwrap(), rms(&sisre), sisre(-4.0)));
+    }
+
+    /// Satellite of a state file in data/analysis
+    fn fixture(text: &str) -> Satellite {
+        let mut satellite = Satellite::builder(17).build();
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let v: Vec<f64> = line
+                .split_whitespace()
+                .map(|v| v.parse().unwrap())
+                .collect();
+            let mut state = State::new(v[0], ECEF::new(v[1], v[2], v[3]));
+            state.velocity = Some(ECEF::new(v[4], v[5], v[6]));
+            state.clock_bias = Some(v[7]);
+            satellite.states.push(&state);
+        }
+        satellite
+    }
+
+    #[test]
+    fn fixture_pair_reproduces_the_reference_statistics() {
+        // G17 from two consecutive broadcast records over the two hours between their
+        // toes; the numbers are from scripts/orbit_comparison_reference.py
+        let earlier = fixture(include_str!("../data/analysis/G17_toe0400.txt"));
+        let mut later = fixture(include_str!("../data/analysis/G17_toe0600.txt"));
+        let comparison = earlier.compare_with(&later);
+        assert_eq!(comparison.epochs, 13);
+        let close = |stats: ComponentStats, rms: f64, max: f64| {
+            (stats.rms - rms).abs() < 1e-6 && (stats.max - max).abs() < 1e-6
+        };
+        assert!(close(comparison.radial, 0.397489, 0.782086));
+        assert!(close(comparison.along_track, 1.718129, 3.882167));
+        assert!(close(comparison.cross_track, 0.127565, 0.320966));
+        assert!(close(comparison.clock.unwrap(), 0.052833, 0.053063));
+        assert!(close(comparison.orbit_sisre, 0.460779, 0.947162));
+        assert!(close(comparison.sisre.unwrap(), 0.488425, 0.990344));
+
+        later.states.clear();
+        assert_eq!(earlier.compare_with(&later), OrbitComparison::default());
+    }
+}
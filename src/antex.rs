@@ -0,0 +1,470 @@
+use crate::celestial::sun_position;
+use crate::gnss::{self, SatId, AER, ECEF};
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A line of an ANTEX file that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseAntexError {
+    pub line: usize, // 1-based
+    pub message: String,
+}
+
+impl fmt::Display for ParseAntexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseAntexError {}
+
+/// Phase center offset and variations of an antenna on one frequency, m. Receiver offsets
+/// are north, east, up from the antenna reference point; satellite offsets are x, y, z in
+/// the body frame from the center of mass.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FrequencyPattern {
+    pub offset: [f64; 3],
+    pub zenith: (f64, f64, f64), // First, last and step of the zenith (nadir) grid, degrees
+    pub azimuth_step: f64,       // Degrees, 0 if there are only NOAZI values
+    pub noazi: Vec<f64>,         // Azimuth-independent variations along the zenith grid
+    pub grid: Vec<Vec<f64>>,     // Variations per azimuth row, 0° to 360°
+}
+
+impl FrequencyPattern {
+    /// Phase center variation at a zenith (receivers) or nadir (satellites) angle and an
+    /// azimuth in degrees, bilinear in the grid and clamped to its zenith range
+    pub fn variation(&self, zenith: f64, azimuth: f64) -> f64 {
+        let (first, last, step) = self.zenith;
+        if step <= 0.0 || self.noazi.is_empty() {
+            return 0.0;
+        }
+        let position = (zenith.clamp(first, last) - first) / step;
+        let along = |row: &[f64]| {
+            let i = (position.floor() as usize).min(row.len().saturating_sub(2));
+            let t = (position - i as f64).clamp(0.0, 1.0);
+            match (row.get(i), row.get(i + 1)) {
+                (Some(a), Some(b)) => a + (b - a) * t,
+                (Some(a), None) => *a,
+                _ => 0.0,
+            }
+        };
+        if self.azimuth_step <= 0.0 || self.grid.len() < 2 {
+            return along(&self.noazi);
+        }
+        let position = azimuth.rem_euclid(360.0) / self.azimuth_step;
+        let i = (position.floor() as usize).min(self.grid.len() - 2);
+        let t = position - i as f64;
+        let a = along(&self.grid[i]);
+        a + (along(&self.grid[i + 1]) - a) * t
+    }
+
+    /// Correction to a receiver's modeled range towards a satellite, for a position solved
+    /// at the antenna reference point: the offset projected on the line of sight,
+    /// subtracted, plus the variation
+    pub fn receiver_range(&self, aer: &AER) -> f64 {
+        let (sin_el, cos_el) = aer.elevation.to_radians().sin_cos();
+        let (sin_az, cos_az) = aer.azimuth.to_radians().sin_cos();
+        let [north, east, up] = self.offset;
+        let projected = north * cos_el * cos_az + east * cos_el * sin_az + up * sin_el;
+        -projected + self.variation(90.0 - aer.elevation, aer.azimuth)
+    }
+}
+
+/// An antenna of an ANTEX file: a satellite (by PRN and validity period) or a receiver
+/// antenna type with its radome
+#[derive(Debug, Clone, PartialEq)]
+pub struct Antenna {
+    pub kind: String,   // Antenna type and radome, or the satellite block
+    pub serial: String, // PRN for satellites, e.g. "G01"; usually blank for receivers
+    pub svn: String,    // Satellite vehicle number, e.g. "G063"
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub frequencies: BTreeMap<String, FrequencyPattern>, // By ANTEX frequency, e.g. "G01"
+}
+
+impl Antenna {
+    pub fn is_satellite(&self) -> bool {
+        !self.svn.is_empty()
+    }
+
+    pub fn is_valid_at(&self, epoch: DateTime<Utc>) -> bool {
+        self.valid_from.is_none_or(|from| from <= epoch)
+            && self.valid_until.is_none_or(|until| epoch < until)
+    }
+
+    pub fn frequency(&self, frequency: &str) -> Option<&FrequencyPattern> {
+        self.frequencies.get(frequency)
+    }
+
+    /// Phase center of a satellite antenna on a frequency, from its center of mass (e.g. an
+    /// SP3 position) and the Sun, both ECEF, under nominal yaw attitude
+    pub fn phase_center(&self, center_of_mass: &ECEF, sun: &ECEF, frequency: &str) -> Option<ECEF> {
+        let [x, y, z] = self.frequency(frequency)?.offset;
+        let [ex, ey, ez] = nominal_attitude(center_of_mass, sun)?;
+        Some(*center_of_mass + ex * x + ey * y + ez * z)
+    }
+
+    /// Phase center of a satellite antenna at a UTC epoch, with the analytic Sun position
+    pub fn phase_center_at(
+        &self,
+        center_of_mass: &ECEF,
+        epoch: DateTime<Utc>,
+        frequency: &str,
+    ) -> Option<ECEF> {
+        self.phase_center(
+            center_of_mass,
+            &sun_position(gnss::gps_seconds(epoch)),
+            frequency,
+        )
+    }
+}
+
+/// Body axes (x, y, z) of a GNSS satellite under nominal yaw steering, ECEF unit vectors:
+/// z towards the Earth's center, y along the solar panel axis normal to the Sun direction,
+/// x completing the frame on the sunlit side. None when the Sun is collinear with z.
+pub fn nominal_attitude(position: &ECEF, sun: &ECEF) -> Option<[ECEF; 3]> {
+    let ez = -*position * (1.0 / position.norm());
+    let to_sun = *sun - *position;
+    let y = ez.cross(&to_sun);
+    let norm = y.norm();
+    if norm <= 1e-9 * to_sun.norm() {
+        return None;
+    }
+    let ey = y * (1.0 / norm);
+    Some([ey.cross(&ez), ey, ez])
+}
+
+/// Satellite and receiver antennas of an ANTEX 1.4 file, such as the IGS igs20.atx
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Antex {
+    pub satellites: Vec<Antenna>,
+    pub receivers: Vec<Antenna>,
+}
+
+impl Antex {
+    /// Read the antenna blocks of an ANTEX file. Offsets and variations are converted from
+    /// mm to m; the RMS blocks are skipped.
+    pub fn parse(text: &str) -> Result<Self, ParseAntexError> {
+        let mut antex = Self::default();
+        let mut antenna: Option<Antenna> = None;
+        let mut frequency: Option<(String, FrequencyPattern)> = None;
+        let mut zenith = (0.0, 0.0, 0.0);
+        let mut azimuth_step = 0.0;
+        let mut in_rms = false;
+        let mut in_header = true;
+
+        for (idx, line) in text.lines().enumerate() {
+            let error = |message: &str| ParseAntexError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            let label = line.get(60..).unwrap_or("").trim();
+            let data = line.get(..60.min(line.len())).unwrap_or(line);
+            let numbers = || -> Result<Vec<f64>, ParseAntexError> {
+                data.split_whitespace()
+                    .map(|value| value.parse().map_err(|_| error("invalid number")))
+                    .collect()
+            };
+            if in_header {
+                in_header = label != "END OF HEADER";
+                continue;
+            }
+            match label {
+                "START OF ANTENNA" => {
+                    antenna = Some(Antenna {
+                        kind: String::new(),
+                        serial: String::new(),
+                        svn: String::new(),
+                        valid_from: None,
+                        valid_until: None,
+                        frequencies: BTreeMap::new(),
+                    });
+                    azimuth_step = 0.0;
+                }
+                "END OF ANTENNA" => {
+                    let antenna = antenna.take().ok_or_else(|| error("unexpected end"))?;
+                    if antenna.is_satellite() {
+                        antex.satellites.push(antenna);
+                    } else {
+                        antex.receivers.push(antenna);
+                    }
+                }
+                "TYPE / SERIAL NO" => {
+                    let antenna = antenna.as_mut().ok_or_else(|| error("outside antenna"))?;
+                    let field = |start: usize, end: usize| {
+                        line.get(start..end.min(line.len())).unwrap_or("").trim()
+                    };
+                    antenna.kind = field(0, 20).to_string();
+                    antenna.serial = field(20, 40).to_string();
+                    antenna.svn = field(40, 50).to_string();
+                }
+                "DAZI" => {
+                    azimuth_step = *numbers()?.first().ok_or_else(|| error("missing DAZI"))?;
+                }
+                "ZEN1 / ZEN2 / DZEN" => match numbers()?[..] {
+                    [first, last, step] => zenith = (first, last, step),
+                    _ => return Err(error("expected ZEN1, ZEN2 and DZEN")),
+                },
+                "VALID FROM" | "VALID UNTIL" => {
+                    let epoch = parse_epoch(&numbers()?).ok_or_else(|| error("invalid epoch"))?;
+                    let antenna = antenna.as_mut().ok_or_else(|| error("outside antenna"))?;
+                    if label == "VALID FROM" {
+                        antenna.valid_from = Some(epoch);
+                    } else {
+                        antenna.valid_until = Some(epoch);
+                    }
+                }
+                "START OF FREQUENCY" => {
+                    let name = line.get(3..6).unwrap_or("").trim().to_string();
+                    let pattern = FrequencyPattern {
+                        zenith,
+                        azimuth_step,
+                        ..FrequencyPattern::default()
+                    };
+                    frequency = Some((name, pattern));
+                }
+                "NORTH / EAST / UP" if in_rms => {}
+                "NORTH / EAST / UP" => {
+                    let (_, pattern) = frequency
+                        .as_mut()
+                        .ok_or_else(|| error("outside frequency"))?;
+                    match numbers()?[..] {
+                        [a, b, c] => pattern.offset = [a * 1e-3, b * 1e-3, c * 1e-3],
+                        _ => return Err(error("expected three offsets")),
+                    }
+                }
+                "END OF FREQUENCY" => {
+                    let (name, pattern) =
+                        frequency.take().ok_or_else(|| error("unexpected end"))?;
+                    let antenna = antenna.as_mut().ok_or_else(|| error("outside antenna"))?;
+                    antenna.frequencies.insert(name, pattern);
+                }
+                "COMMENT" => {}
+                "START OF FREQ RMS" => in_rms = true,
+                "END OF FREQ RMS" => in_rms = false,
+                _ => {
+                    // Variation rows carry no label and may run past column 60
+                    let Some((_, pattern)) = frequency.as_mut().filter(|_| !in_rms) else {
+                        continue;
+                    };
+                    let mut fields = line.split_whitespace();
+                    let Some(first) = fields.next() else {
+                        continue;
+                    };
+                    let values = fields
+                        .map(|value| value.parse::<f64>().map(|v| v * 1e-3))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| error("invalid variation"))?;
+                    if first == "NOAZI" {
+                        pattern.noazi = values;
+                    } else {
+                        first.parse::<f64>().map_err(|_| error("invalid azimuth"))?;
+                        pattern.grid.push(values);
+                    }
+                }
+            }
+        }
+        Ok(antex)
+    }
+
+    /// Antenna a satellite flew at an epoch
+    pub fn satellite(&self, sat_id: SatId, epoch: DateTime<Utc>) -> Option<&Antenna> {
+        let prn = sat_id.to_string();
+        self.satellites
+            .iter()
+            .find(|antenna| antenna.serial == prn && antenna.is_valid_at(epoch))
+    }
+
+    /// Receiver antenna by type, e.g. "TRM59800.00     SCIS". A type given without a
+    /// radome matches the entry with none ("NONE").
+    pub fn receiver(&self, kind: &str) -> Option<&Antenna> {
+        let kind = kind.trim();
+        self.receivers
+            .iter()
+            .find(|antenna| antenna.kind == kind)
+            .or_else(|| {
+                self.receivers.iter().find(|antenna| {
+                    antenna.kind.get(..16).map(str::trim) == Some(kind)
+                        && antenna.kind.get(16..).map(str::trim) == Some("NONE")
+                })
+            })
+    }
+}
+
+fn parse_epoch(fields: &[f64]) -> Option<DateTime<Utc>> {
+    let [year, month, day, hour, minute, second] = fields[..] else {
+        return None;
+    };
+    let start = Utc
+        .with_ymd_and_hms(
+            year as i32,
+            month as u32,
+            day as u32,
+            hour as u32,
+            minute as u32,
+            0,
+        )
+        .single()?;
+    Some(start + chrono::Duration::milliseconds((second * 1000.0).round() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two antennas flown as G01 and a receiver antenna with an azimuth grid, in the layout of
+    /// igs20.atx
+    const ATX: &str = "\
+     1.4            M                                       ANTEX VERSION / SYST
+A                                                           PCV TYPE / REFANT
+                                                            END OF HEADER
+                                                            START OF ANTENNA
+BLOCK IIA           G01                 G032      1992-079A TYPE / SERIAL NO
+CODE                IGS                      0    01-JAN-23 METH / BY / # / DATE
+     0.0                                                    DAZI
+     0.0  17.0   1.0                                        ZEN1 / ZEN2 / DZEN
+     1                                                      # OF FREQUENCIES
+  1992    11    22     0     0  0.0000000                   VALID FROM
+  2008    10    16    23    59 59.9999999                   VALID UNTIL
+   G01                                                      START OF FREQUENCY
+    279.00      0.00   2319.50                              NORTH / EAST / UP
+   NOAZI    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00
+   G01                                                      END OF FREQUENCY
+                                                            END OF ANTENNA
+                                                            START OF ANTENNA
+BLOCK IIF           G01                 G063      2011-036A TYPE / SERIAL NO
+CODE                IGS                      0    01-JAN-23 METH / BY / # / DATE
+     0.0                                                    DAZI
+     0.0  17.0   1.0                                        ZEN1 / ZEN2 / DZEN
+     1                                                      # OF FREQUENCIES
+  2011     7    16     0     0  0.0000000                   VALID FROM
+   G01                                                      START OF FREQUENCY
+    394.00      0.00   1500.00                              NORTH / EAST / UP
+   NOAZI    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00    0.00
+   G01                                                      END OF FREQUENCY
+                                                            END OF ANTENNA
+                                                            START OF ANTENNA
+TRM59800.00     NONE                                        TYPE / SERIAL NO
+ROBOT               Geo++ GmbH               0    01-JAN-23 METH / BY / # / DATE
+   180.0                                                    DAZI
+     0.0  90.0  45.0                                        ZEN1 / ZEN2 / DZEN
+     1                                                      # OF FREQUENCIES
+   G01                                                      START OF FREQUENCY
+      1.00     -0.50     66.00                              NORTH / EAST / UP
+   NOAZI    0.00   -2.00    4.00
+     0.0    0.00   -2.00    4.00
+   180.0    0.00   -4.00    8.00
+   360.0    0.00   -2.00    4.00
+   G01                                                      END OF FREQUENCY
+   G01                                                      START OF FREQ RMS
+      0.10      0.10      0.20                              NORTH / EAST / UP
+   NOAZI    9.00    9.00    9.00
+   G01                                                      END OF FREQ RMS
+                                                            END OF ANTENNA
+";
+
+    fn epoch(year: i32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, 6, 12, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn satellites_are_found_by_prn_and_validity() {
+        let antex = Antex::parse(ATX).unwrap();
+        assert_eq!((antex.satellites.len(), antex.receivers.len()), (2, 1));
+        let old = antex.satellite(SatId::gps(1), epoch(2005)).unwrap();
+        assert_eq!((old.kind.as_str(), old.svn.as_str()), ("BLOCK IIA", "G032"));
+        assert_eq!(old.frequency("G01").unwrap().offset, [0.279, 0.0, 2.3195]);
+        assert_eq!(
+            old.valid_until,
+            Some(Utc.with_ymd_and_hms(2008, 10, 17, 0, 0, 0).unwrap())
+        );
+        let new = antex.satellite(SatId::gps(1), epoch(2023)).unwrap();
+        assert_eq!(new.svn, "G063");
+        assert!(new.valid_until.is_none());
+        // Between the two, and for other satellites, there is none
+        assert!(antex.satellite(SatId::gps(1), epoch(2010)).is_none());
+        assert!(antex.satellite(SatId::gps(2), epoch(2023)).is_none());
+    }
+
+    #[test]
+    fn receivers_match_with_or_without_the_radome() {
+        let antex = Antex::parse(ATX).unwrap();
+        let receiver = antex.receiver("TRM59800.00     NONE").unwrap();
+        assert!(!receiver.is_satellite());
+        assert_eq!(antex.receiver("TRM59800.00"), Some(receiver));
+        assert!(antex.receiver("TRM59800.00     SCIS").is_none());
+        let pattern = receiver.frequency("G01").unwrap();
+        assert_eq!(pattern.offset, [0.001, -0.0005, 0.066]);
+        // The RMS block does not overwrite the variations
+        assert_eq!(pattern.noazi, [0.0, -0.002, 0.004]);
+        assert_eq!(pattern.grid.len(), 3);
+    }
+
+    #[test]
+    fn variations_are_bilinear_and_clamped() {
+        let antex = Antex::parse(ATX).unwrap();
+        let pattern = antex
+            .receiver("TRM59800.00")
+            .unwrap()
+            .frequency("G01")
+            .unwrap();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+        assert!(close(pattern.variation(45.0, 0.0), -0.002));
+        assert!(close(pattern.variation(45.0, 90.0), -0.003));
+        assert!(close(pattern.variation(67.5, 180.0), 0.002));
+        assert!(close(pattern.variation(120.0, 360.0), 0.004));
+
+        // A satellite overhead sees the up offset, one on the eastern horizon the east offset
+        let overhead = AER {
+            azimuth: 0.0,
+            elevation: 90.0,
+            range: 2e7,
+        };
+        assert!(close(pattern.receiver_range(&overhead), -0.066));
+        let east = AER {
+            azimuth: 90.0,
+            elevation: 0.0,
+            range: 2e7,
+        };
+        assert!(close(pattern.receiver_range(&east), 0.0005 + 0.006));
+    }
+
+    #[test]
+    fn phase_center_follows_the_nominal_attitude() {
+        let antex = Antex::parse(ATX).unwrap();
+        let antenna = antex.satellite(SatId::gps(1), epoch(2023)).unwrap();
+        let center_of_mass = ECEF::new(26_560e3, 0.0, 0.0);
+        let sun = ECEF::new(0.0, 1.496e11, 0.0);
+        let [ex, ey, ez] = nominal_attitude(&center_of_mass, &sun).unwrap();
+        // z to the Earth, x towards the Sun's side, y along the panels
+        assert!(ez.approx_eq(&ECEF::new(-1.0, 0.0, 0.0), 1e-12));
+        assert!(ex.approx_eq(&ECEF::new(0.0, 1.0, 0.0), 1e-3));
+        assert!(ey.dot(&(sun - center_of_mass)).abs() < 1e-3);
+
+        let phase_center = antenna.phase_center(&center_of_mass, &sun, "G01").unwrap();
+        let expected = ECEF::new(26_560e3 - 1.5, 0.394, 0.0);
+        assert!(
+            (phase_center - expected).norm() < 1e-3,
+            "{:?}",
+            phase_center
+        );
+        assert!(antenna.phase_center(&center_of_mass, &sun, "G02").is_none());
+        // With the Sun behind the Earth the yaw is undefined
+        let eclipse = ECEF::new(-1.496e11, 0.0, 0.0);
+        assert!(nominal_attitude(&center_of_mass, &eclipse).is_none());
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let broken = ATX.replace("      1.00     -0.50", "      x.00     -0.50");
+        let err = Antex::parse(&broken).unwrap_err();
+        assert_eq!(err.line, 36);
+        let unopened = ATX.replacen(
+            "                                                            START OF ANTENNA\n",
+            "",
+            1,
+        );
+        assert!(Antex::parse(&unopened).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::constellation::Constellation;
+use crate::gnss::{self, GpsTime, SatId, ECEF, ENU, LLA};
+use crate::linalg;
+use crate::observation::{self, ObservationEpoch};
+use crate::positioning::{PositioningError, Weighting};
+use crate::propagator::BroadcastPropagator;
+use crate::pseudorange::{self, PseudorangeModel};
+use crate::satellite::PropagationConfig;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+use ndarray::{Array1, Array2, Axis};
+
+/// Options for relative positioning between a base and a rover receiver
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineConfig {
+    pub signal: Signal,
+    pub epoch_tolerance: f64, // Largest base/rover time tag difference paired, s
+    pub elevation_mask: f64,  // Degrees, at the base
+    pub weighting: Weighting, // Undifferenced code sigma, by base elevation
+    pub max_iter: u32,
+    pub tolerance: f64, // Baseline/clock update below which the solution has converged, m
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        Self {
+            signal: Signal::GpsL1,
+            epoch_tolerance: 0.5,
+            elevation_mask: 10.0,
+            weighting: Weighting::Sine { sigma0: 1.0 },
+            max_iter: 10,
+            tolerance: 1e-4,
+        }
+    }
+}
+
+/// Rover minus base observations of one satellite
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SingleDifference {
+    pub sat_id: SatId,
+    pub pseudorange: Option<f64>, // m
+    pub phase: Option<f64>,       // m, biased by the differenced ambiguity
+}
+
+/// Rover position relative to the base from one epoch of single differences
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineSolution {
+    pub epoch: DateTime<Utc>,  // Rover time tag
+    pub baseline: ECEF,        // Rover minus base
+    pub baseline_enu: ENU,     // In the local frame at the base
+    pub clock_difference: f64, // Rover minus base receiver clock, s
+    pub iterations: u32,
+    pub residuals: Vec<(SatId, f64)>, // Observed minus modeled single differences, m
+    pub covariance: Array2<f64>,      // Of (x, y, z, c*dt), scaled by the a posteriori variance
+    pub covariance_enu: Array2<f64>,  // Of the baseline in east, north, up
+}
+
+/// Single differences on one signal for the satellites both receivers observed
+pub fn single_differences(
+    rover: &ObservationEpoch,
+    base: &ObservationEpoch,
+    signal: Signal,
+) -> Vec<SingleDifference> {
+    let mut differences = Vec::new();
+    for rover_sat in &rover.satellites {
+        let Some(base_sat) = base.satellite(rover_sat.sat_id) else {
+            continue;
+        };
+        let (Some(r), Some(b)) = (rover_sat.signal(signal), base_sat.signal(signal)) else {
+            continue;
+        };
+        let pseudorange = r.code().zip(b.code()).map(|(r, b)| r - b);
+        let phase = r.phase_meters().zip(b.phase_meters()).map(|(r, b)| r - b);
+        if pseudorange.is_some() || phase.is_some() {
+            differences.push(SingleDifference {
+                sat_id: rover_sat.sat_id,
+                pseudorange,
+                phase,
+            });
+        }
+    }
+    differences
+}
+
+impl Constellation {
+    /// Code baseline for every pair of base and rover epochs within the epoch tolerance.
+    /// Rover epochs without a base counterpart are skipped.
+    pub fn process_baseline(
+        &self,
+        base_position: &ECEF,
+        base: &[ObservationEpoch],
+        rover: &[ObservationEpoch],
+        config: &BaselineConfig,
+    ) -> Vec<Result<BaselineSolution, PositioningError>> {
+        observation::align_epochs(rover, base, config.epoch_tolerance)
+            .into_iter()
+            .map(|(rover, base)| self.solve_baseline(base_position, base, rover, config))
+            .collect()
+    }
+
+    /// Baseline and clock difference by least squares on single-differenced pseudoranges.
+    /// Satellite clocks and orbit errors cancel; each receiver is modeled at its own time
+    /// tag, so slightly misaligned epochs are handled too.
+    pub fn solve_baseline(
+        &self,
+        base_position: &ECEF,
+        base: &ObservationEpoch,
+        rover: &ObservationEpoch,
+        config: &BaselineConfig,
+    ) -> Result<BaselineSolution, PositioningError> {
+        let base_time = gnss::gps_seconds(base.epoch);
+        let rover_time = gnss::gps_seconds(rover.epoch);
+        let propagation = PropagationConfig::new().with_clock(true);
+        let mut differences = Vec::new();
+        for difference in single_differences(rover, base, config.signal) {
+            let Some(pseudorange) = difference.pseudorange else {
+                continue;
+            };
+            let records = self.records(difference.sat_id);
+            let Ok(propagator) = BroadcastPropagator::new(records, propagation.clone()) else {
+                continue;
+            };
+            let base_model = model(base_position, &propagator, base_time, difference.sat_id)?;
+            if base_model.aer.elevation < config.elevation_mask {
+                continue;
+            }
+            let sigma = config.weighting.sigma(base_model.aer.elevation);
+            // Both receivers contribute their own code noise
+            let weight = 1.0 / (2.0 * sigma * sigma);
+            differences.push((
+                difference.sat_id,
+                pseudorange,
+                base_model,
+                propagator,
+                weight,
+            ));
+        }
+        if differences.len() < 4 {
+            return Err(PositioningError::TooFewSatellites {
+                usable: differences.len(),
+            });
+        }
+
+        let weights = Array1::from_iter(differences.iter().map(|row| row.4));
+        let mut position = *base_position;
+        let mut clock_m = 0.0;
+        for iteration in 1..=config.max_iter {
+            let mut design = Array2::zeros((differences.len(), 4));
+            let mut prefit = Array1::zeros(differences.len());
+            for (i, (sat_id, observed, base_model, propagator, _)) in differences.iter().enumerate()
+            {
+                let rover_model = model(&position, propagator, rover_time, *sat_id)?;
+                let unit = gnss::unit_line_of_sight(&position, &rover_model.satellite_position)
+                    .unwrap_or_default();
+                design[[i, 0]] = -unit.x;
+                design[[i, 1]] = -unit.y;
+                design[[i, 2]] = -unit.z;
+                design[[i, 3]] = 1.0;
+                prefit[i] = observed - (rover_model.total() - base_model.total()) - clock_m;
+            }
+            let weighted = &design * &weights.view().insert_axis(Axis(1));
+            let normal_inverse = linalg::invert(&design.t().dot(&weighted))
+                .ok_or(PositioningError::SingularGeometry)?;
+            let update = normal_inverse.dot(&weighted.t().dot(&prefit));
+            position = position + ECEF::new(update[0], update[1], update[2]);
+            clock_m += update[3];
+
+            if update.iter().map(|v| v * v).sum::<f64>().sqrt() < config.tolerance {
+                let postfit = &prefit - &design.dot(&update);
+                let dof = differences.len() - 4;
+                let variance = match dof {
+                    0 => 1.0,
+                    _ => (&postfit * &weights).dot(&postfit) / dof as f64,
+                };
+                let covariance = &normal_inverse * variance;
+                let base_lla = base_position.to_lla();
+                let baseline = position - *base_position;
+                return Ok(BaselineSolution {
+                    epoch: rover.epoch,
+                    baseline,
+                    baseline_enu: base_lla.rotate_to_enu(&baseline),
+                    clock_difference: clock_m / gnss::C_LIGHT,
+                    iterations: iteration,
+                    residuals: differences.iter().map(|row| row.0).zip(postfit).collect(),
+                    covariance_enu: rotate_covariance(&base_lla, &covariance),
+                    covariance,
+                });
+            }
+        }
+        Err(PositioningError::NotConverged {
+            iterations: config.max_iter,
+        })
+    }
+}
+
+/// Pseudorange model at a receive time, with propagation errors tagged by satellite
+pub(crate) fn model(
+    receiver: &ECEF,
+    propagator: &BroadcastPropagator,
+    receive_time: f64,
+    sat_id: SatId,
+) -> Result<PseudorangeModel, PositioningError> {
+    let tgd = propagator
+        .record_at(GpsTime::from_seconds(receive_time))
+        .tgd;
+    pseudorange::model_pseudorange(receiver, propagator, receive_time, tgd)
+        .map_err(|err| PositioningError::Propagation(sat_id, err))
+}
+
+/// R C Rᵀ for the position block of an ECEF covariance, R rotating into the local frame
+fn rotate_covariance(lla: &LLA, covariance: &Array2<f64>) -> Array2<f64> {
+    let rotate = |v: [f64; 3]| {
+        let enu = lla.rotate_to_enu(&ECEF::new(v[0], v[1], v[2]));
+        [enu.east, enu.north, enu.up]
+    };
+    // Columns of R C, then the rows of (R C) Rᵀ
+    let mut rc = [[0.0; 3]; 3];
+    for j in 0..3 {
+        let column = rotate([covariance[[0, j]], covariance[[1, j]], covariance[[2, j]]]);
+        for i in 0..3 {
+            rc[i][j] = column[i];
+        }
+    }
+    let mut local = Array2::zeros((3, 3));
+    for i in 0..3 {
+        let row = rotate(rc[i]);
+        for j in 0..3 {
+            local[[i, j]] = row[j];
+        }
+    }
+    local
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{GaussianNoise, SimulationConfig};
+    use crate::test_support::station as base;
+    use crate::test_support::NAV;
+    use chrono::{Duration, TimeZone};
+
+    /// Point at an east/north/up offset from the base
+    fn offset(east: f64, north: f64, up: f64) -> ECEF {
+        let lla = base().to_lla();
+        let (sin_lat, cos_lat) = lla.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = lla.longitude.to_radians().sin_cos();
+        base()
+            + ECEF::new(
+                -sin_lon * east - sin_lat * cos_lon * north + cos_lat * cos_lon * up,
+                cos_lon * east - sin_lat * sin_lon * north + cos_lat * sin_lon * up,
+                cos_lat * north + sin_lat * up,
+            )
+    }
+
+    /// Twenty 30 s epochs of a static receiver with atmosphere both receivers share
+    fn observe(
+        constellation: &Constellation,
+        position: ECEF,
+        clock_bias: f64,
+        start_offset: i64,
+        seed: u64,
+    ) -> Vec<ObservationEpoch> {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+        let truth: Vec<_> = (0..20)
+            .map(|k| (start + Duration::seconds(30 * k + start_offset), position))
+            .collect();
+        let config = SimulationConfig {
+            zenith_ionosphere: 3.0,
+            zenith_troposphere: 2.3,
+            receiver_clock_bias: clock_bias,
+            ambiguity_seed: seed,
+            ..SimulationConfig::default()
+        };
+        let mut noise = GaussianNoise::new(0.3, 0.002, 0.05, seed);
+        constellation.simulate_observations(&truth, &config, &mut noise)
+    }
+
+    #[test]
+    fn zero_baseline_recovers_nothing_but_the_clock_difference() {
+        let constellation = Constellation::from_nav(NAV.parse::<gnss::RinexNav>().unwrap());
+        let base_obs = observe(&constellation, base(), 1e-4, 0, 1);
+        let rover_obs = observe(&constellation, base(), -3e-5, 0, 2);
+        let config = BaselineConfig::default();
+        let solutions = constellation.process_baseline(&base(), &base_obs, &rover_obs, &config);
+        assert_eq!(solutions.len(), 20);
+        for solution in solutions {
+            let solution = solution.unwrap();
+            assert!(
+                solution.baseline.norm() < 3.0,
+                "{:?}",
+                solution.baseline_enu
+            );
+            assert!((solution.clock_difference + 1.3e-4).abs() < 5e-9);
+        }
+    }
+
+    #[test]
+    fn short_baseline_is_recovered_at_the_sub_meter_level() {
+        let constellation = Constellation::from_nav(NAV.parse::<gnss::RinexNav>().unwrap());
+        let (east, north, up) = (120.0, -45.0, 3.0);
+        let base_obs = observe(&constellation, base(), 1e-4, 0, 3);
+        let rover_obs = observe(&constellation, offset(east, north, up), 2e-4, 0, 4);
+        let config = BaselineConfig {
+            weighting: Weighting::Sine { sigma0: 0.3 },
+            ..BaselineConfig::default()
+        };
+        let solutions: Vec<BaselineSolution> = constellation
+            .process_baseline(&base(), &base_obs, &rover_obs, &config)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        let n = solutions.len() as f64;
+        let mean = |component: fn(&ENU) -> f64| {
+            solutions
+                .iter()
+                .map(|s| component(&s.baseline_enu))
+                .sum::<f64>()
+                / n
+        };
+        assert!((mean(|enu| enu.east) - east).abs() < 0.3);
+        assert!((mean(|enu| enu.north) - north).abs() < 0.3);
+        assert!((mean(|enu| enu.up) - up).abs() < 0.6);
+        for solution in &solutions {
+            let error = solution
+                .baseline
+                .distance_to(&(offset(east, north, up) - base()));
+            assert!(error < 2.0, "{}", error);
+            // The local covariance is the ECEF one rotated: same trace, up the weakest
+            let trace = (0..3).map(|i| solution.covariance[[i, i]]).sum::<f64>();
+            let local_trace = (0..3).map(|i| solution.covariance_enu[[i, i]]).sum::<f64>();
+            assert!((trace - local_trace).abs() < 1e-9 * trace);
+            assert!(solution.covariance_enu[[2, 2]] > solution.covariance_enu[[0, 0]]);
+        }
+    }
+
+    #[test]
+    fn epochs_beyond_the_tolerance_are_not_paired() {
+        let constellation = Constellation::from_nav(NAV.parse::<gnss::RinexNav>().unwrap());
+        let base_obs = observe(&constellation, base(), 0.0, 0, 5);
+        let rover_obs = observe(&constellation, base(), 0.0, 1, 6);
+        let strict = BaselineConfig::default();
+        assert!(constellation
+            .process_baseline(&base(), &base_obs, &rover_obs, &strict)
+            .is_empty());
+        // Within the tolerance, each receiver is modeled at its own time tag
+        let loose = BaselineConfig {
+            epoch_tolerance: 1.5,
+            ..strict
+        };
+        let solutions = constellation.process_baseline(&base(), &base_obs, &rover_obs, &loose);
+        assert_eq!(solutions.len(), 20);
+        for solution in solutions {
+            assert!(solution.unwrap().baseline.norm() < 3.0);
+        }
+    }
+
+    #[test]
+    fn single_differences_need_both_receivers() {
+        let constellation = Constellation::from_nav(NAV.parse::<gnss::RinexNav>().unwrap());
+        let base_obs = observe(&constellation, base(), 0.0, 0, 7);
+        let mut rover_obs = observe(&constellation, base(), 0.0, 0, 8);
+        let dropped = rover_obs[0].satellites.remove(0).sat_id;
+        rover_obs[0].satellites[0].signals[0].phase = None;
+        let differences = single_differences(&rover_obs[0], &base_obs[0], Signal::GpsL1);
+        assert_eq!(differences.len(), base_obs[0].satellites.len() - 1);
+        assert!(differences.iter().all(|d| d.sat_id != dropped));
+        assert!(differences[0].phase.is_none() && differences[0].pseudorange.is_some());
+        assert!(single_differences(&rover_obs[0], &base_obs[0], Signal::GpsL2).is_empty());
+    }
+}
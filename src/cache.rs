@@ -0,0 +1,447 @@
+use crate::constellation::Constellation;
+use crate::gnss::RinexNav;
+use crate::satellite::Satellite;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+
+// Cache file layout, little-endian:
+//   magic "PNTCACHE" | format version u32 | content kind u8 | crate version (u8 length + UTF-8)
+//   | payload length u64 | FNV-1a 64 checksum of the payload u64 | bincode payload
+//
+// Bump FORMAT_VERSION whenever a cached type changes shape. Caches from another crate
+// version are rejected too, so a forgotten bump cannot decode into garbage.
+
+const MAGIC: &[u8; 8] = b"PNTCACHE";
+const FORMAT_VERSION: u32 = 3;
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What a cache file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    Nav,
+    Satellite,
+    Constellation,
+}
+
+impl CacheKind {
+    fn code(self) -> u8 {
+        match self {
+            Self::Nav => 1,
+            Self::Satellite => 2,
+            Self::Constellation => 3,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::Nav),
+            2 => Some(Self::Satellite),
+            3 => Some(Self::Constellation),
+            _ => None,
+        }
+    }
+}
+
+/// Why a cache could not be written or was rejected
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    NotACache,
+    FormatVersion {
+        found: u32,
+        expected: u32,
+    },
+    CrateVersion {
+        found: String,
+        expected: String,
+    },
+    Kind {
+        found: Option<CacheKind>,
+        expected: CacheKind,
+    },
+    Truncated {
+        length: u64,
+        expected: u64,
+    },
+    Checksum {
+        found: u64,
+        expected: u64,
+    },
+    Encoding(bincode::Error),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "cache I/O error: {}", error),
+            Self::NotACache => write!(f, "not a pnt_rust cache file"),
+            Self::FormatVersion { found, expected } => write!(
+                f,
+                "cache format version {}, this build reads version {}; rebuild the cache",
+                found, expected
+            ),
+            Self::CrateVersion { found, expected } => write!(
+                f,
+                "cache written by pnt_rust {}, this is {}; rebuild the cache",
+                found, expected
+            ),
+            Self::Kind { found, expected } => match found {
+                Some(found) => write!(f, "cache holds {:?}, expected {:?}", found, expected),
+                None => write!(f, "cache holds unknown content, expected {:?}", expected),
+            },
+            Self::Truncated { length, expected } => write!(
+                f,
+                "cache payload is {} bytes, header says {}; the file is truncated",
+                length, expected
+            ),
+            Self::Checksum { found, expected } => write!(
+                f,
+                "cache checksum {:016x} does not match the stored {:016x}; the file is corrupt",
+                found, expected
+            ),
+            Self::Encoding(error) => write!(f, "cache encoding error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Encoding(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CacheError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<bincode::Error> for CacheError {
+    fn from(error: bincode::Error) -> Self {
+        Self::Encoding(error)
+    }
+}
+
+impl RinexNav {
+    /// Parsed records as a binary cache, much faster to load than re-parsing the RINEX file
+    pub fn save_cache(&self, path: &str) -> Result<(), CacheError> {
+        save(path, CacheKind::Nav, self)
+    }
+
+    /// Records written by `save_cache`; stale, corrupt or foreign caches are rejected
+    pub fn load_cache(path: &str) -> Result<Self, CacheError> {
+        load(path, CacheKind::Nav)
+    }
+}
+
+impl Satellite {
+    /// Satellite with its propagated states as a binary cache
+    pub fn save_cache(&self, path: &str) -> Result<(), CacheError> {
+        save(path, CacheKind::Satellite, self)
+    }
+
+    pub fn load_cache(path: &str) -> Result<Self, CacheError> {
+        load(path, CacheKind::Satellite)
+    }
+}
+
+impl Constellation {
+    /// Ephemerides and propagated states of every satellite as a binary cache
+    pub fn save_cache(&self, path: &str) -> Result<(), CacheError> {
+        save(path, CacheKind::Constellation, self)
+    }
+
+    pub fn load_cache(path: &str) -> Result<Self, CacheError> {
+        load(path, CacheKind::Constellation)
+    }
+}
+
+fn save<T: Serialize>(path: &str, kind: CacheKind, value: &T) -> Result<(), CacheError> {
+    let payload = bincode::serialize(value)?;
+    let mut bytes = Vec::with_capacity(payload.len() + 64);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.push(kind.code());
+    bytes.push(CRATE_VERSION.len() as u8);
+    bytes.extend_from_slice(CRATE_VERSION.as_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+fn load<T: DeserializeOwned>(path: &str, expected: CacheKind) -> Result<T, CacheError> {
+    let bytes = fs::read(path)?;
+    let mut reader = Reader(&bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(CacheError::NotACache);
+    }
+    let version = u32::from_le_bytes(reader.array()?);
+    if version != FORMAT_VERSION {
+        return Err(CacheError::FormatVersion {
+            found: version,
+            expected: FORMAT_VERSION,
+        });
+    }
+    let kind = CacheKind::from_code(reader.array::<1>()?[0]);
+    if kind != Some(expected) {
+        return Err(CacheError::Kind {
+            found: kind,
+            expected,
+        });
+    }
+    let length = reader.array::<1>()?[0] as usize;
+    let crate_version = String::from_utf8_lossy(reader.take(length)?);
+    if crate_version != CRATE_VERSION {
+        return Err(CacheError::CrateVersion {
+            found: crate_version.into_owned(),
+            expected: CRATE_VERSION.to_string(),
+        });
+    }
+    let length = u64::from_le_bytes(reader.array()?);
+    let checksum = u64::from_le_bytes(reader.array()?);
+    let payload = reader.0;
+    if payload.len() as u64 != length {
+        return Err(CacheError::Truncated {
+            length: payload.len() as u64,
+            expected: length,
+        });
+    }
+    let found = fnv1a(payload);
+    if found != checksum {
+        return Err(CacheError::Checksum {
+            found,
+            expected: checksum,
+        });
+    }
+    Ok(bincode::deserialize(payload)?)
+}
+
+/// Header fields read off the front of the file; running short means it is no cache
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], CacheError> {
+        if self.0.len() < length {
+            return Err(CacheError::NotACache);
+        }
+        let (head, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CacheError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::SatId;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::nav;
+    use crate::test_support::NAV;
+    use chrono::{TimeZone, Utc};
+    use std::time::{Duration, Instant};
+
+    /// Path in a fresh directory holding a cache of the fixture's records
+    fn saved_nav(dir: &tempfile::TempDir) -> String {
+        let path = dir.path().join("nav.cache");
+        let path = path.to_str().unwrap().to_string();
+        nav().save_cache(&path).unwrap();
+        path
+    }
+
+    /// Rewrite the cache file with `edit` applied to its bytes
+    fn tamper(path: &str, edit: impl FnOnce(&mut Vec<u8>)) {
+        let mut bytes = fs::read(path).unwrap();
+        edit(&mut bytes);
+        fs::write(path, bytes).unwrap();
+    }
+
+    /// The error of a load that must fail; caches have no Debug for `unwrap_err`
+    fn rejection<T>(result: Result<T, CacheError>) -> CacheError {
+        match result {
+            Ok(_) => panic!("the cache was accepted"),
+            Err(error) => error,
+        }
+    }
+
+    /// Offset of the payload length field, after the crate version string
+    fn payload_length_offset() -> usize {
+        MAGIC.len() + 4 + 1 + 1 + CRATE_VERSION.len()
+    }
+
+    #[test]
+    fn nav_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = saved_nav(&dir);
+        let loaded = RinexNav::load_cache(&path).unwrap();
+        let nav = nav();
+        assert_eq!(loaded.records(), nav.records());
+        assert_eq!(loaded.leap_seconds, nav.leap_seconds);
+        assert_eq!(
+            loaded.records_for_slice(SatId::gps(17)),
+            nav.records_for_slice(SatId::gps(17))
+        );
+    }
+
+    #[test]
+    fn propagated_states_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut constellation = Constellation::from_nav(nav());
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(300))
+            .with_velocity(true)
+            .with_clock(true);
+        constellation.propagate_all(start, Duration::from_secs(3600), &config);
+
+        let path = dir.path().join("constellation.cache");
+        let path = path.to_str().unwrap();
+        constellation.save_cache(path).unwrap();
+        let loaded = Constellation::load_cache(path).unwrap();
+        assert_eq!(loaded.len(), constellation.len());
+        for (satellite, original) in loaded.iter().zip(constellation.iter()) {
+            assert_eq!(satellite.id, original.id);
+            assert_eq!(satellite.states, original.states);
+            assert_eq!(
+                loaded.records(original.id),
+                constellation.records(original.id)
+            );
+        }
+
+        let satellite = constellation.get(SatId::gps(17)).unwrap();
+        let path = dir.path().join("g17.cache");
+        let path = path.to_str().unwrap();
+        satellite.save_cache(path).unwrap();
+        let loaded = Satellite::load_cache(path).unwrap();
+        assert_eq!(loaded.id, satellite.id);
+        assert_eq!(loaded.name, satellite.name);
+        assert_eq!(loaded.states, satellite.states);
+    }
+
+    #[test]
+    fn loading_is_faster_than_parsing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = saved_nav(&dir);
+        // Best of a few runs, so a scheduler hiccup does not decide the comparison
+        let best = |run: &dyn Fn()| {
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    run();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+        let parse = best(&|| drop(nav()));
+        let load = best(&|| drop(RinexNav::load_cache(&path).unwrap()));
+        assert!(load < parse, "load {:?}, parse {:?}", load, parse);
+    }
+
+    #[test]
+    fn foreign_files_are_not_caches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nav.rnx");
+        fs::write(&path, NAV).unwrap();
+        let error = rejection(RinexNav::load_cache(path.to_str().unwrap()));
+        assert!(matches!(error, CacheError::NotACache), "{}", error);
+
+        fs::write(&path, b"PNTCA").unwrap();
+        let error = rejection(RinexNav::load_cache(path.to_str().unwrap()));
+        assert!(matches!(error, CacheError::NotACache), "{}", error);
+
+        let error = rejection(RinexNav::load_cache("no/such/file.cache"));
+        assert!(matches!(error, CacheError::Io(_)), "{}", error);
+    }
+
+    #[test]
+    fn other_format_versions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = saved_nav(&dir);
+        tamper(&path, |bytes| {
+            bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes())
+        });
+        let error = rejection(RinexNav::load_cache(&path));
+        assert!(matches!(
+            error,
+            CacheError::FormatVersion { found, expected }
+                if found == FORMAT_VERSION + 1 && expected == FORMAT_VERSION
+        ));
+        assert!(error.to_string().contains("rebuild the cache"));
+    }
+
+    #[test]
+    fn other_crate_versions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = saved_nav(&dir);
+        tamper(&path, |bytes| {
+            let version = MAGIC.len() + 4 + 2;
+            bytes[version] = b'9';
+        });
+        let error = rejection(RinexNav::load_cache(&path));
+        match error {
+            CacheError::CrateVersion { found, expected } => {
+                assert_eq!(expected, CRATE_VERSION);
+                assert!(found.starts_with('9'));
+            }
+            error => panic!("{}", error),
+        }
+    }
+
+    #[test]
+    fn other_content_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = saved_nav(&dir);
+        let error = rejection(Constellation::load_cache(&path));
+        assert!(matches!(
+            error,
+            CacheError::Kind {
+                found: Some(CacheKind::Nav),
+                expected: CacheKind::Constellation
+            }
+        ));
+        tamper(&path, |bytes| bytes[12] = 42);
+        let error = rejection(RinexNav::load_cache(&path));
+        assert!(matches!(error, CacheError::Kind { found: None, .. }));
+    }
+
+    #[test]
+    fn truncated_and_corrupt_payloads_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = saved_nav(&dir);
+        tamper(&path, |bytes| bytes.truncate(bytes.len() - 10));
+        let error = rejection(RinexNav::load_cache(&path));
+        assert!(
+            matches!(error, CacheError::Truncated { length, expected } if expected == length + 10)
+        );
+
+        let path = saved_nav(&dir);
+        tamper(&path, |bytes| {
+            let middle = payload_length_offset() + 16 + 1000;
+            bytes[middle] ^= 0x01;
+        });
+        let error = rejection(RinexNav::load_cache(&path));
+        assert!(matches!(error, CacheError::Checksum { .. }), "{}", error);
+        assert!(error.to_string().contains("corrupt"));
+    }
+}
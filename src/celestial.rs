@@ -0,0 +1,201 @@
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::F64Ext;
+use crate::gnss::{self, ECEF};
+use core::f64::consts::PI;
+
+pub const J2000_JD: f64 = 2_451_545.0;
+pub const GPS_EPOCH_JD: f64 = 2_444_244.5; // 1980-01-06 00:00:00 GPS time
+const TT_MINUS_GPS: f64 = 51.184; // TAI - GPS (19 s) plus TT - TAI (32.184 s)
+const OBLIQUITY_J2000: f64 = 23.43929111; // Degrees
+const OBLIQUITY_RATE: f64 = -46.8150 / 3600.0; // Degrees per Julian century
+
+// Analytic Sun and Moon ephemerides after Montenbruck & Gill, Satellite Orbits, 3.3.2.
+// Times are seconds since the GPS epoch or Julian dates, so the module needs neither
+// chrono nor std.
+
+/// Julian date in UTC of a GPS time, with the crate's fixed leap seconds
+pub fn julian_date(gps_seconds: f64) -> f64 {
+    GPS_EPOCH_JD + (gps_seconds - gnss::GPS_LEAP_SECONDS) / 86_400.0
+}
+
+/// Julian date in Terrestrial Time, which the ephemeris arguments are expressed in
+pub fn julian_date_tt(gps_seconds: f64) -> f64 {
+    GPS_EPOCH_JD + (gps_seconds + TT_MINUS_GPS) / 86_400.0
+}
+
+/// Greenwich mean sidereal time in radians, UT1 approximated by UTC
+pub fn gmst(jd_ut: f64) -> f64 {
+    (280.46061837 + 360.98564736629 * (jd_ut - J2000_JD))
+        .to_radians()
+        .rem_euclid(2.0 * PI)
+}
+
+/// Sun position in meters, ECI (mean equator and equinox of date), good to about 1'
+pub fn sun_eci(jd_tt: f64) -> ECEF {
+    let t = (jd_tt - J2000_JD) / 36525.0;
+    let m = (357.5256 + 35999.049 * t).to_radians();
+    let longitude = (282.9400 + 1.3972 * t).to_radians()
+        + m
+        + (6892.0 * m.sin() + 72.0 * (2.0 * m).sin()) / 3600.0 * PI / 180.0;
+    let distance = (149.619 - 2.499 * m.cos() - 0.021 * (2.0 * m).cos()) * 1e9;
+    ecliptic_to_equatorial(distance, longitude, 0.0, t)
+}
+
+/// Moon position in meters, ECI (mean equator and equinox of date), good to a few arcminutes
+/// and a few hundred km
+pub fn moon_eci(jd_tt: f64) -> ECEF {
+    let t = (jd_tt - J2000_JD) / 36525.0;
+    let deg = |x: f64| x.to_radians();
+    let arcsec = |x: f64| (x / 3600.0).to_radians();
+    let l0 = deg(218.31617 + 481267.88088 * t);
+    let l = deg(134.96292 + 477198.86753 * t); // Moon mean anomaly
+    let lp = deg(357.52543 + 35999.04944 * t); // Sun mean anomaly
+    let f = deg(93.27283 + 483202.01873 * t); // Argument of latitude
+    let d = deg(297.85027 + 445267.11135 * t); // Elongation from the Sun
+
+    let longitude = l0
+        + arcsec(
+            22640.0 * l.sin() + 769.0 * (2.0 * l).sin() - 4586.0 * (l - 2.0 * d).sin()
+                + 2370.0 * (2.0 * d).sin()
+                - 668.0 * lp.sin()
+                - 412.0 * (2.0 * f).sin()
+                - 212.0 * (2.0 * l - 2.0 * d).sin()
+                - 206.0 * (l + lp - 2.0 * d).sin()
+                + 192.0 * (l + 2.0 * d).sin()
+                - 165.0 * (lp - 2.0 * d).sin()
+                + 148.0 * (l - lp).sin()
+                - 125.0 * d.sin()
+                - 110.0 * (l + lp).sin()
+                - 55.0 * (2.0 * f - 2.0 * d).sin(),
+        );
+    let latitude = arcsec(
+        18520.0 * (f + longitude - l0 + arcsec(412.0 * (2.0 * f).sin() + 541.0 * lp.sin())).sin()
+            - 526.0 * (f - 2.0 * d).sin()
+            + 44.0 * (l + f - 2.0 * d).sin()
+            - 31.0 * (-l + f - 2.0 * d).sin()
+            - 25.0 * (-2.0 * l + f).sin()
+            - 23.0 * (lp + f - 2.0 * d).sin()
+            + 21.0 * (-l + f).sin()
+            + 11.0 * (-lp + f - 2.0 * d).sin(),
+    );
+    let distance = (385000.0
+        - 20905.0 * l.cos()
+        - 3699.0 * (2.0 * d - l).cos()
+        - 2956.0 * (2.0 * d).cos()
+        - 570.0 * (2.0 * l).cos()
+        + 246.0 * (2.0 * l - 2.0 * d).cos()
+        - 205.0 * (lp - 2.0 * d).cos()
+        - 171.0 * (l + 2.0 * d).cos()
+        - 152.0 * (l + lp - 2.0 * d).cos())
+        * 1e3;
+    ecliptic_to_equatorial(distance, longitude, latitude, t)
+}
+
+/// Rotate an ECI-of-date vector into ECEF, ignoring nutation and polar motion
+pub fn eci_to_ecef(eci: &ECEF, jd_ut: f64) -> ECEF {
+    let (sin_g, cos_g) = gmst(jd_ut).sin_cos();
+    ECEF::new(
+        cos_g * eci.x + sin_g * eci.y,
+        -sin_g * eci.x + cos_g * eci.y,
+        eci.z,
+    )
+}
+
+/// Sun position in ECEF at a GPS time, seconds since the GPS epoch
+pub fn sun_position(gps_seconds: f64) -> ECEF {
+    eci_to_ecef(
+        &sun_eci(julian_date_tt(gps_seconds)),
+        julian_date(gps_seconds),
+    )
+}
+
+/// Moon position in ECEF at a GPS time, seconds since the GPS epoch
+pub fn moon_position(gps_seconds: f64) -> ECEF {
+    eci_to_ecef(
+        &moon_eci(julian_date_tt(gps_seconds)),
+        julian_date(gps_seconds),
+    )
+}
+
+/// Mean obliquity of the ecliptic of date, degrees, at a time in Julian centuries of TT
+/// since J2000
+pub fn mean_obliquity(t: f64) -> f64 {
+    OBLIQUITY_J2000 + OBLIQUITY_RATE * t
+}
+
+/// Ecliptic coordinates of date to the mean equator and equinox of date
+fn ecliptic_to_equatorial(distance: f64, longitude: f64, latitude: f64, t: f64) -> ECEF {
+    let (sin_eps, cos_eps) = mean_obliquity(t).to_radians().sin_cos();
+    let x = distance * latitude.cos() * longitude.cos();
+    let y = distance * latitude.cos() * longitude.sin();
+    let z = distance * latitude.sin();
+    ECEF::new(x, cos_eps * y - sin_eps * z, sin_eps * y + cos_eps * z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Angle between an ECI vector and a direction given as right ascension and
+    /// declination, arcminutes
+    fn separation(eci: &ECEF, ra: f64, dec: f64) -> f64 {
+        let (sin_ra, cos_ra) = ra.to_radians().sin_cos();
+        let (sin_dec, cos_dec) = dec.to_radians().sin_cos();
+        let reference = ECEF::new(cos_dec * cos_ra, cos_dec * sin_ra, sin_dec);
+        let cos = eci.dot(&reference) / eci.norm();
+        cos.clamp(-1.0, 1.0).acos().to_degrees() * 60.0
+    }
+
+    // References are the worked examples of Meeus, Astronomical Algorithms (2nd ed.), from
+    // the full VSOP87 and ELP-2000 theories, which agree with the JPL DE ephemerides and
+    // Horizons to arcseconds: apparent place of date, so within a minute of arc of the
+    // mean place the model gives
+
+    #[test]
+    fn sun_matches_the_reference_within_its_accuracy() {
+        // Example 25.a, 1992 October 13.0 TT: 13h13m31.4s, -7°47'06", 0.99760775 AU
+        let sun = sun_eci(2_448_908.5);
+        let ra = (13.0 + 13.0 / 60.0 + 31.4 / 3600.0) * 15.0;
+        let dec = -(7.0 + 47.0 / 60.0 + 6.0 / 3600.0);
+        assert!(separation(&sun, ra, dec) < 1.5);
+        let distance = 0.99760775 * 149_597_870_700.0;
+        assert!((sun.norm() / distance - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn moon_matches_the_reference_within_its_accuracy() {
+        // Example 47.a, 1992 April 12.0 TT: 134.688470°, 13.768368°, 368,409.7 km
+        let moon = moon_eci(2_448_724.5);
+        assert!(separation(&moon, 134.688470, 13.768368) < 1.0);
+        assert!((moon.norm() - 368_409.7e3).abs() < 100e3);
+    }
+
+    #[test]
+    fn obliquity_is_of_date() {
+        // Example 22.a, 1987 April 10.0 TT: 23°26'27.407"
+        let t = (2_446_895.5 - J2000_JD) / 36525.0;
+        let expected = 23.0 + 26.0 / 60.0 + 27.407 / 3600.0;
+        assert!((mean_obliquity(t) - expected).abs() * 3600.0 < 0.01);
+    }
+
+    #[test]
+    fn gps_time_converts_to_julian_dates() {
+        // J2000.0, 2000-01-01 12:00 TT, is 51.184 s earlier on the GPS clock
+        let j2000 = 7300.5 * 86_400.0 - TT_MINUS_GPS;
+        assert!((julian_date_tt(j2000) - J2000_JD).abs() < 1e-9);
+        let noon_utc = 7300.5 * 86_400.0 + gnss::GPS_LEAP_SECONDS;
+        assert!((julian_date(noon_utc) - J2000_JD).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sun_is_overhead_near_greenwich_at_noon() {
+        // 2000-01-01 12:00 UTC: the equation of time puts the Sun 3 minutes short of the
+        // meridian, about 0.8° east, at the declination of early January
+        let noon_utc = 7300.5 * 86_400.0 + gnss::GPS_LEAP_SECONDS;
+        let sun = sun_position(noon_utc);
+        let longitude = sun.y.atan2(sun.x).to_degrees();
+        let latitude = (sun.z / sun.norm()).asin().to_degrees();
+        assert!(longitude > 0.5 && longitude < 1.1, "{}", longitude);
+        assert!((latitude + 23.0).abs() < 0.1, "{}", latitude);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::gnss;
+#[cfg(feature = "ndarray")]
+use crate::kalman::FilterSolution;
+#[cfg(feature = "ndarray")]
+use crate::positioning::SppSolution;
+use chrono::{DateTime, Utc};
+
+pub const CLOCK_JUMP: f64 = 1e-3; // Receiver clock steering step, s
+const JUMP_DETECTION: f64 = 0.5 * CLOCK_JUMP; // Unexplained bias change treated as a jump, s
+
+/// Receiver clock estimate at one epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockEpoch {
+    pub epoch: DateTime<Utc>,
+    pub bias: f64,  // s, with earlier jumps removed when repairing
+    pub drift: f64, // s/s, from the estimator or by differencing
+    pub jump: i64,  // Milliseconds stepped at this epoch, 0 if none
+}
+
+impl ClockEpoch {
+    pub fn bias_meters(&self) -> f64 {
+        self.bias * gnss::C_LIGHT
+    }
+}
+
+/// Receiver clock bias and drift over a session, with millisecond jump detection
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReceiverClockSeries {
+    epochs: Vec<ClockEpoch>,
+    repair: bool,                 // Remove detected jumps so the series stays continuous
+    correction: f64,              // Sum of the jumps removed so far, s
+    raw_last: Option<(f64, f64)>, // Unrepaired (gps time, bias) of the last epoch
+}
+
+impl ReceiverClockSeries {
+    pub fn new(repair: bool) -> Self {
+        Self {
+            repair,
+            ..Self::default()
+        }
+    }
+
+    /// Add an epoch's bias (s) and, if the estimator has one, drift (s/s). A change in bias
+    /// that the drift does not explain by more than half a millisecond is taken as a jump.
+    pub fn push(&mut self, epoch: DateTime<Utc>, bias: f64, drift: Option<f64>) -> &ClockEpoch {
+        let time = gnss::gps_seconds(epoch);
+        let mut jump = 0;
+        let mut differenced = None;
+        if let (Some((last_time, last_bias)), Some(previous)) = (self.raw_last, self.epochs.last())
+        {
+            let dt = time - last_time;
+            let unexplained = bias - last_bias - previous.drift * dt;
+            if unexplained.abs() > JUMP_DETECTION {
+                jump = (unexplained / CLOCK_JUMP).round() as i64;
+            }
+            if dt > 0.0 {
+                differenced = Some((bias - last_bias - jump as f64 * CLOCK_JUMP) / dt);
+            }
+        }
+        self.raw_last = Some((time, bias));
+        if self.repair {
+            self.correction += jump as f64 * CLOCK_JUMP;
+        }
+        self.epochs.push(ClockEpoch {
+            epoch,
+            bias: bias - self.correction,
+            drift: drift.or(differenced).unwrap_or(0.0),
+            jump,
+        });
+        self.epochs.last().unwrap()
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn push_spp(&mut self, epoch: DateTime<Utc>, solution: &SppSolution) -> &ClockEpoch {
+        self.push(epoch, solution.clock_bias, None)
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn push_filter(&mut self, solution: &FilterSolution) -> &ClockEpoch {
+        self.push(
+            solution.epoch,
+            solution.clock_bias,
+            Some(solution.clock_drift),
+        )
+    }
+
+    pub fn epochs(&self) -> &[ClockEpoch] {
+        &self.epochs
+    }
+
+    /// Epochs at which the clock stepped
+    pub fn jumps(&self) -> impl Iterator<Item = &ClockEpoch> {
+        self.epochs.iter().filter(|epoch| epoch.jump != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    const DRIFT: f64 = 5e-8; // s/s
+
+    /// A clock drifting steadily with a little noise, stepping by `step` ms at epoch 50
+    fn session(repair: bool, step: i64, with_drift: bool) -> ReceiverClockSeries {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let mut series = ReceiverClockSeries::new(repair);
+        for k in 0..100 {
+            let noise = 3e-9 * ((k * 7 % 11) as f64 - 5.0) / 5.0;
+            let mut bias = 2e-4 + DRIFT * k as f64 + noise;
+            if k >= 50 {
+                bias += step as f64 * CLOCK_JUMP;
+            }
+            let drift = with_drift.then_some(DRIFT);
+            series.push(start + Duration::seconds(k), bias, drift);
+        }
+        series
+    }
+
+    #[test]
+    fn flags_a_millisecond_jump() {
+        let series = session(false, 1, false);
+        let jumps: Vec<_> = series.jumps().collect();
+        assert_eq!(jumps.len(), 1);
+        assert_eq!(jumps[0].jump, 1);
+        assert_eq!(jumps[0], &series.epochs()[50]);
+        // Not repaired, the step stays in the bias but not in the drift
+        let epochs = series.epochs();
+        assert!((epochs[50].bias - epochs[49].bias - CLOCK_JUMP).abs() < 1e-7);
+        for epoch in &epochs[1..] {
+            assert!((epoch.drift - DRIFT).abs() < 1e-8, "{}", epoch.drift);
+        }
+    }
+
+    #[test]
+    fn repairs_a_millisecond_jump() {
+        for (step, with_drift) in [(1, false), (-1, false), (1, true), (-2, true)] {
+            let repaired = session(true, step, with_drift);
+            let clean = session(true, 0, with_drift);
+            assert_eq!(repaired.jumps().count(), 1);
+            assert_eq!(repaired.epochs()[50].jump, step);
+            assert_eq!(clean.jumps().count(), 0);
+            for (repaired, clean) in repaired.epochs().iter().zip(clean.epochs()) {
+                assert!((repaired.bias - clean.bias).abs() < 1e-15);
+                assert!((repaired.drift - clean.drift).abs() < 1e-12);
+            }
+            let epochs = repaired.epochs();
+            let largest_step = epochs
+                .windows(2)
+                .map(|pair| (pair[1].bias - pair[0].bias).abs())
+                .fold(0.0, f64::max);
+            assert!(largest_step < 1e-7);
+        }
+    }
+}
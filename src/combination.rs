@@ -0,0 +1,164 @@
+use crate::gnss::{self, SatId};
+use crate::observation::SignalObservation;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+
+/// Observations of one satellite on two signals at the same epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObservationPair {
+    pub sat_id: SatId,
+    pub epoch: DateTime<Utc>,
+    pub first: SignalObservation,
+    pub second: SignalObservation,
+}
+
+impl ObservationPair {
+    /// Ionosphere-free pseudorange, m
+    pub fn ionosphere_free_code(&self) -> Option<f64> {
+        let (a, b) = ionosphere_free_coefficients(self.first.signal, self.second.signal)?;
+        Some(a * self.first.code()? + b * self.second.code()?)
+    }
+
+    /// Ionosphere-free carrier phase, m. Ambiguities make it biased by a constant.
+    pub fn ionosphere_free_phase(&self) -> Option<f64> {
+        let (a, b) = ionosphere_free_coefficients(self.first.signal, self.second.signal)?;
+        Some(a * self.first.phase_meters()? + b * self.second.phase_meters()?)
+    }
+
+    /// Geometry-free pseudorange P2 - P1, m. Grows with the ionospheric delay.
+    pub fn geometry_free_code(&self) -> Option<f64> {
+        distinct(self.first.signal, self.second.signal)?;
+        Some(self.second.code()? - self.first.code()?)
+    }
+
+    /// Geometry-free carrier phase L1 - L2, m. Grows with the ionospheric delay, biased
+    /// by the ambiguities.
+    pub fn geometry_free_phase(&self) -> Option<f64> {
+        distinct(self.first.signal, self.second.signal)?;
+        Some(self.first.phase_meters()? - self.second.phase_meters()?)
+    }
+
+    /// Melbourne-Wübbena combination, wide-lane phase minus narrow-lane code, m. Free of
+    /// geometry, clocks and first-order ionosphere; constant between cycle slips.
+    pub fn melbourne_wubbena(&self) -> Option<f64> {
+        let (f1, f2) = distinct(self.first.signal, self.second.signal)?;
+        let wide_lane =
+            (f1 * self.first.phase_meters()? - f2 * self.second.phase_meters()?) / (f1 - f2);
+        let narrow_lane = (f1 * self.first.code()? + f2 * self.second.code()?) / (f1 + f2);
+        Some(wide_lane - narrow_lane)
+    }
+
+    /// Wide-lane wavelength c / (f1 - f2), m, the unit the Melbourne-Wübbena ambiguity
+    /// is an integer in
+    pub fn wide_lane_wavelength(&self) -> Option<f64> {
+        let (f1, f2) = distinct(self.first.signal, self.second.signal)?;
+        Some(gnss::C_LIGHT / (f1 - f2))
+    }
+}
+
+/// Coefficients (f1², -f2²) / (f1² - f2²) that cancel first-order ionospheric delay
+/// between two signals. None if they share a carrier.
+pub fn ionosphere_free_coefficients(first: Signal, second: Signal) -> Option<(f64, f64)> {
+    let (f1, f2) = distinct(first, second)?;
+    let denominator = f1 * f1 - f2 * f2;
+    Some((f1 * f1 / denominator, -f2 * f2 / denominator))
+}
+
+/// Frequencies of the two signals, None if they are the same carrier
+fn distinct(first: Signal, second: Signal) -> Option<(f64, f64)> {
+    let (f1, f2) = (first.frequency_hz(), second.frequency_hz());
+    (f1 != f2).then_some((f1, f2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Observations of a range on two signals with a first-order ionospheric delay on the
+    /// first one and whole-cycle ambiguities on both carriers
+    fn pair(first: Signal, second: Signal, iono: f64, ambiguities: (f64, f64)) -> ObservationPair {
+        let range = 22_345_678.901;
+        let observe = |signal: Signal, ambiguity: f64| {
+            let delay = iono * signal.iono_factor(first);
+            let phase = (range - delay) / signal.wavelength() + ambiguity;
+            SignalObservation::new(signal, Some(range + delay), Some(phase))
+        };
+        ObservationPair {
+            sat_id: "G05".parse().unwrap(),
+            epoch: Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap(),
+            first: observe(first, ambiguities.0),
+            second: observe(second, ambiguities.1),
+        }
+    }
+
+    #[test]
+    fn ionosphere_free_removes_the_delay() {
+        let range = 22_345_678.901;
+        for (first, second) in [
+            (Signal::GpsL1, Signal::GpsL2),
+            (Signal::GpsL1, Signal::GpsL5),
+            (Signal::GalileoE1, Signal::GalileoE5a),
+            (Signal::BeiDouB1I, Signal::BeiDouB3),
+            (Signal::GlonassG1(-3), Signal::GlonassG2(-3)),
+        ] {
+            for iono in [0.0, 3.0, 25.0] {
+                let pair = pair(first, second, iono, (0.0, 0.0));
+                let code = pair.ionosphere_free_code().unwrap();
+                assert!((code - range).abs() < 1e-6, "{} {} {}", first, second, code);
+                let phase = pair.ionosphere_free_phase().unwrap();
+                assert!(
+                    (phase - range).abs() < 1e-6,
+                    "{} {} {}",
+                    first,
+                    second,
+                    phase
+                );
+            }
+            let (a, b) = ionosphere_free_coefficients(first, second).unwrap();
+            assert!((a + b - 1.0).abs() < 1e-12);
+        }
+        // L1/L2 coefficients of the textbooks
+        let (a, b) = ionosphere_free_coefficients(Signal::GpsL1, Signal::GpsL2).unwrap();
+        assert!((a - 2.545727780163).abs() < 1e-9 && (b + 1.545727780163).abs() < 1e-9);
+    }
+
+    #[test]
+    fn geometry_free_and_melbourne_wubbena() {
+        let iono = 5.0;
+        let pair = pair(Signal::GpsL1, Signal::GpsL2, iono, (12.0, 5.0));
+        let gamma = Signal::GpsL2.iono_factor(Signal::GpsL1);
+        let code = pair.geometry_free_code().unwrap();
+        assert!((code - iono * (gamma - 1.0)).abs() < 1e-6);
+        let (l1, l2) = (Signal::GpsL1.wavelength(), Signal::GpsL2.wavelength());
+        let phase = pair.geometry_free_phase().unwrap();
+        assert!((phase - (iono * (gamma - 1.0) + 12.0 * l1 - 5.0 * l2)).abs() < 1e-6);
+
+        // Only the wide-lane ambiguity N1 - N2 is left, in wide-lane cycles
+        let wide_lane = pair.wide_lane_wavelength().unwrap();
+        assert!((wide_lane - 0.861918400322).abs() < 1e-9);
+        let mw = pair.melbourne_wubbena().unwrap();
+        assert!((mw / wide_lane - 7.0).abs() < 1e-6, "{}", mw / wide_lane);
+    }
+
+    #[test]
+    fn missing_or_identical_signals_give_none() {
+        let mut pair = pair(Signal::GpsL1, Signal::GpsL2, 5.0, (0.0, 0.0));
+        pair.second.phase = None;
+        assert!(pair.ionosphere_free_code().is_some());
+        assert_eq!(pair.ionosphere_free_phase(), None);
+        assert_eq!(pair.melbourne_wubbena(), None);
+        pair.first.pseudorange = Some(f64::NAN);
+        assert_eq!(pair.ionosphere_free_code(), None);
+        assert_eq!(pair.geometry_free_code(), None);
+
+        let same = self::pair(Signal::GpsL1, Signal::GalileoE1, 5.0, (0.0, 0.0));
+        assert_eq!(same.ionosphere_free_code(), None);
+        assert_eq!(same.geometry_free_phase(), None);
+        assert_eq!(same.wide_lane_wavelength(), None);
+        assert_eq!(
+            ionosphere_free_coefficients(Signal::GpsL5, Signal::GalileoE5a),
+            None
+        );
+    }
+}
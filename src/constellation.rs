@@ -0,0 +1,492 @@
+use crate::gnss::{self, NavRecord, RinexNav, SatId, AER, LLA};
+use crate::horizon::ElevationMask;
+use crate::propagator::OrbitPropagator;
+use crate::sat_info::SatInfo;
+use crate::satellite::{PropagationConfig, PropagationError, PropagationReport, Satellite};
+use chrono::{DateTime, Utc};
+use log::debug;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Outcome of propagating one satellite in `Constellation::propagate_all`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SatelliteStatus {
+    Propagated(PropagationReport),
+    Unhealthy,
+    NoData,
+    Unsupported, // State-vector ephemeris (GLONASS, SBAS), which is not propagated
+    Failed(PropagationError),
+}
+
+/// Every satellite observed in a nav file, together with the file its ephemeris records
+/// are borrowed from
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Constellation {
+    satellites: BTreeMap<SatId, Satellite>,
+    nav: Arc<RinexNav>,
+}
+
+impl Constellation {
+    /// Takes the nav file, or shares one already behind an `Arc`; each satellite's records
+    /// are read in place rather than copied
+    pub fn from_nav(nav: impl Into<Arc<RinexNav>>) -> Self {
+        let nav = nav.into();
+        let satellites = nav
+            .satellites()
+            .map(|sat_id| {
+                let first = &nav.records_for_slice(sat_id)[0];
+                (sat_id, Self::describe(sat_id, first))
+            })
+            .collect();
+        Self { satellites, nav }
+    }
+
+    /// Satellite named after the spacecraft transmitting the PRN at the first record's epoch
+    fn describe(sat_id: SatId, record: &NavRecord) -> Satellite {
+        let epoch = gnss::gps_seconds_to_utc(record.toc_gps_seconds());
+        let mut builder = Satellite::builder(sat_id);
+        if let Some(info) = SatInfo::lookup(sat_id, epoch) {
+            builder = builder.name(format!("SVN{} {}", info.svn, info.block));
+        }
+        builder.build()
+    }
+
+    /// Propagate every satellite over the same grid, skipping unhealthy or data-starved ones
+    /// and those of GLONASS and SBAS, whose state-vector ephemerides are not propagated
+    pub fn propagate_all(
+        &mut self,
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+    ) -> BTreeMap<SatId, SatelliteStatus> {
+        let started = Instant::now();
+        let nav = &self.nav;
+        #[cfg(not(feature = "rayon"))]
+        let satellites = self.satellites.iter_mut();
+        #[cfg(feature = "rayon")]
+        let satellites = self.satellites.par_iter_mut();
+        let statuses: BTreeMap<SatId, SatelliteStatus> = satellites
+            .map(|(sat_id, satellite)| {
+                let records = nav.records_for_slice(*sat_id);
+                let status = Self::propagate_one(satellite, records, start, duration, config);
+                (*sat_id, status)
+            })
+            .collect();
+        debug!(
+            "propagated {} satellites in {:?}",
+            statuses.len(),
+            started.elapsed()
+        );
+        statuses
+    }
+
+    /// Propagate every satellite with its own orbit model over the grid of `config`;
+    /// satellites without one get NoData
+    pub fn propagate_all_with<P: OrbitPropagator + Sync>(
+        &mut self,
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+        propagators: &BTreeMap<SatId, P>,
+    ) -> BTreeMap<SatId, SatelliteStatus> {
+        #[cfg(not(feature = "rayon"))]
+        let satellites = self.satellites.iter_mut();
+        #[cfg(feature = "rayon")]
+        let satellites = self.satellites.par_iter_mut();
+        satellites
+            .map(|(sat_id, satellite)| {
+                satellite.states.clear();
+                let status = match propagators.get(sat_id) {
+                    None => SatelliteStatus::NoData,
+                    Some(propagator) => {
+                        match satellite.propagate_with(start, duration, config, propagator) {
+                            Ok(report) if report.states == 0 => SatelliteStatus::NoData,
+                            Ok(report) => SatelliteStatus::Propagated(report),
+                            Err(err) => SatelliteStatus::Failed(err),
+                        }
+                    }
+                };
+                (*sat_id, status)
+            })
+            .collect()
+    }
+
+    fn propagate_one(
+        satellite: &mut Satellite,
+        records: &[NavRecord],
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+    ) -> SatelliteStatus {
+        satellite.states.clear();
+        Self::status(satellite, records, |satellite| {
+            satellite.propagate(start, duration, config, records)
+        })
+    }
+
+    /// Status of a satellite after `propagate`, which is left out when its system has no
+    /// Keplerian ephemeris or every record is unhealthy
+    fn status(
+        satellite: &mut Satellite,
+        records: &[NavRecord],
+        propagate: impl FnOnce(&mut Satellite) -> Result<PropagationReport, PropagationError>,
+    ) -> SatelliteStatus {
+        if !satellite.id.constellation.has_keplerian_ephemeris() {
+            debug!("{}: state-vector ephemeris, skipped", satellite.id);
+            return SatelliteStatus::Unsupported;
+        }
+        if records.iter().all(|record| !record.is_healthy()) {
+            debug!("{}: every record is unhealthy, skipped", satellite.id);
+            return SatelliteStatus::Unhealthy;
+        }
+        match propagate(satellite) {
+            Ok(report) if report.states == 0 => SatelliteStatus::NoData,
+            Ok(report) => SatelliteStatus::Propagated(report),
+            Err(PropagationError::NoEphemeris) => SatelliteStatus::NoData,
+            Err(err) => SatelliteStatus::Failed(err),
+        }
+    }
+
+    /// Active satellites above the elevation mask (flat, in degrees, or a horizon profile) at
+    /// an epoch, seen from an observer.
+    /// Positions come from the propagated states, interpolated between grid epochs;
+    /// satellites whose ephemeris in effect is unhealthy are left out.
+    pub fn visible(
+        &self,
+        observer: &LLA,
+        epoch: DateTime<Utc>,
+        mask: impl ElevationMask,
+    ) -> Vec<(SatId, AER)> {
+        let gps_time = gnss::gps_seconds(epoch);
+        self.satellites
+            .iter()
+            .filter(|(sat_id, satellite)| {
+                satellite.active
+                    && self
+                        .record_at(**sat_id, gps_time)
+                        .is_some_and(|record| record.is_healthy())
+            })
+            .filter_map(|(sat_id, satellite)| {
+                let position = match satellite.state_at(epoch) {
+                    Some(state) => state.position(),
+                    None => satellite.interpolate_at(epoch).ok()?,
+                };
+                let aer = observer.aer_to(&position);
+                mask.is_above(&aer).then_some((*sat_id, aer))
+            })
+            .collect()
+    }
+
+    /// Record with the toe nearest to a GPS time
+    fn record_at(&self, sat_id: SatId, gps_time: f64) -> Option<&NavRecord> {
+        self.records(sat_id).iter().min_by(|a, b| {
+            let age = |record: &NavRecord| (record.toe_gps_seconds() - gps_time).abs();
+            age(a).total_cmp(&age(b))
+        })
+    }
+
+    pub fn get(&self, sat_id: impl Into<SatId>) -> Option<&Satellite> {
+        self.satellites.get(&sat_id.into())
+    }
+
+    pub fn get_mut(&mut self, sat_id: impl Into<SatId>) -> Option<&mut Satellite> {
+        self.satellites.get_mut(&sat_id.into())
+    }
+
+    /// Satellites ordered by constellation, then PRN
+    pub fn iter(&self) -> impl Iterator<Item = &Satellite> {
+        self.satellites.values()
+    }
+
+    pub fn records(&self, sat_id: impl Into<SatId>) -> &[NavRecord] {
+        self.nav.records_for_slice(sat_id.into())
+    }
+
+    pub fn nav(&self) -> &RinexNav {
+        &self.nav
+    }
+
+    pub fn len(&self) -> usize {
+        self.satellites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.satellites.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::ECEF;
+    use crate::propagator::BroadcastPropagator;
+    use crate::satellite::GridEnd;
+    use crate::store::EphemerisStore;
+    use crate::test_support::NAV;
+    use chrono::TimeZone;
+
+    // State-vector ephemerides: position, velocity and acceleration in km, km/s, km/s²
+    const STATE_VECTORS: &str = "\
+R01 2023 06 12 02 15 00 1.519024372101D-05 0.000000000000D+00 5.184000000000D+05
+     1.182464062500D+04-2.217864990234D+00 1.862645149231D-09 0.000000000000D+00
+     1.259628808594D+04 1.081981658936D+00-9.313225746155D-10 1.000000000000D+00
+     1.924823583984D+04 1.225566864014D+00-1.862645149231D-09 0.000000000000D+00
+S31 2023 06 12 02 01 04 1.862645149231D-09 0.000000000000D+00 5.184000000000D+05
+     3.934520000000D+04 0.000000000000D+00 0.000000000000D+00 6.300000000000D+01
+    -1.127248000000D+04 0.000000000000D+00 0.000000000000D+00 3.276700000000D+04
+     0.000000000000D+00 0.000000000000D+00 0.000000000000D+00 7.200000000000D+01
+";
+
+    #[test]
+    fn records_are_borrowed_from_the_nav() {
+        let nav: Arc<RinexNav> = Arc::new(NAV.parse().unwrap());
+        let constellation = Constellation::from_nav(Arc::clone(&nav));
+        assert_eq!(Arc::strong_count(&nav), 2);
+        assert_eq!(constellation.len(), 32);
+        for satellite in constellation.iter() {
+            let records = constellation.records(satellite.id);
+            let in_nav = nav.records_for_slice(satellite.id);
+            assert!(!records.is_empty());
+            assert!(std::ptr::eq(records, in_nav));
+        }
+        assert!(constellation
+            .records("R01".parse::<SatId>().unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn propagating_with_broadcast_models_matches_propagate_all() {
+        let nav: Arc<RinexNav> = Arc::new(NAV.parse().unwrap());
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 1, 0, 0).unwrap();
+        let duration = Duration::from_secs(3 * 3600 + 45);
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(30))
+            .grid_end(GridEnd::Inclusive)
+            .with_velocity(true)
+            .with_clock(true);
+        let mut constellation = Constellation::from_nav(Arc::clone(&nav));
+        let statuses = constellation.propagate_all(start, duration, &config);
+
+        // Models for the satellites `propagate_all` does not leave out as unhealthy
+        let propagators: BTreeMap<SatId, BroadcastPropagator> = nav
+            .satellites()
+            .filter_map(|sat_id| {
+                let records = nav.records_for_slice(sat_id);
+                records.iter().any(NavRecord::is_healthy).then_some(())?;
+                Some((
+                    sat_id,
+                    BroadcastPropagator::new(records, config.clone()).ok()?,
+                ))
+            })
+            .collect();
+        let mut with = Constellation::from_nav(Arc::clone(&nav));
+        let with_statuses = with.propagate_all_with(start, duration, &config, &propagators);
+        assert_eq!(with_statuses.len(), 32);
+        for satellite in constellation.iter() {
+            let other = with.get(satellite.id).unwrap();
+            match &statuses[&satellite.id] {
+                SatelliteStatus::Unhealthy => {
+                    assert_eq!(with_statuses[&satellite.id], SatelliteStatus::NoData);
+                    assert!(other.states.is_empty());
+                }
+                status => {
+                    assert_eq!(&with_statuses[&satellite.id], status);
+                    assert!(other.states == satellite.states, "{} differs", satellite.id);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_propagation_matches_serial() {
+        let nav: Arc<RinexNav> = Arc::new(NAV.parse().unwrap());
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let duration = Duration::from_secs(4 * 3600);
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(10))
+            .with_velocity(true)
+            .with_clock(true);
+
+        let mut constellation = Constellation::from_nav(Arc::clone(&nav));
+        let statuses = constellation.propagate_all(start, duration, &config);
+        for satellite in constellation.iter() {
+            if statuses[&satellite.id] == SatelliteStatus::Unhealthy {
+                continue;
+            }
+            // One satellite at a time on this thread
+            let mut serial = Satellite::builder(satellite.id).build();
+            let records = nav.records_for_slice(satellite.id);
+            let report = serial.propagate(start, duration, &config, records);
+            assert_eq!(
+                statuses[&satellite.id],
+                SatelliteStatus::Propagated(report.unwrap())
+            );
+            assert!(
+                satellite.states == serial.states,
+                "{} differs",
+                satellite.id
+            );
+        }
+    }
+
+    /// What `examples/propagation_digest.rs` prints, which `scripts/check_features.sh`
+    /// compares between builds with and without ndarray: batch states agree with
+    /// single-epoch evaluation and obey vis-viva for their record's orbit
+    #[test]
+    fn batch_states_match_single_epochs_and_vis_viva() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let duration = Duration::from_secs(2 * 3600);
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(30))
+            .with_velocity(true);
+
+        let mut constellation = Constellation::from_nav(NAV.parse::<RinexNav>().unwrap());
+        let statuses = constellation.propagate_all(start, duration, &config);
+        let store = EphemerisStore::new(NAV.parse().unwrap(), config);
+        let mut propagated = 0;
+        for satellite in constellation.iter() {
+            if !matches!(statuses[&satellite.id], SatelliteStatus::Propagated(_)) {
+                continue;
+            }
+            propagated += 1;
+            assert_eq!(satellite.states.len(), 240);
+            for state in satellite.states.iter() {
+                let position = state.position();
+                let single = store.position_at(satellite.id, state.epoch()).unwrap();
+                assert!(position.approx_eq(&single, 1e-6), "{}", satellite.id);
+
+                // Inertial speed from the earth-fixed velocity, against the two-body speed
+                // at this radius; broadcast harmonic corrections stay well under 0.01%
+                let velocity = state.velocity().unwrap();
+                let inertial = ECEF::new(
+                    velocity.x - gnss::OMEGA_E_DOT * position.y,
+                    velocity.y + gnss::OMEGA_E_DOT * position.x,
+                    velocity.z,
+                );
+                let record = constellation.record_at(satellite.id, state.time()).unwrap();
+                let radius = position.norm();
+                let vis_viva =
+                    (gnss::MU_EARTH * (2.0 / radius - 1.0 / record.semi_major_axis())).sqrt();
+                assert!(
+                    (inertial.norm() / vis_viva - 1.0).abs() < 1e-4,
+                    "{} at {}",
+                    satellite.id,
+                    state.time()
+                );
+            }
+        }
+        assert_eq!(propagated, 31);
+    }
+
+    #[test]
+    fn visible_leaves_out_unhealthy_and_masked_satellites_and_interpolates() {
+        let nav: Arc<RinexNav> = Arc::new(NAV.parse().unwrap());
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let duration = Duration::from_secs(3600);
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(60))
+            .with_velocity(true);
+        let mut constellation = Constellation::from_nav(Arc::clone(&nav));
+        constellation.propagate_all(start, duration, &config);
+        // G22's records are all unhealthy; propagate it anyway so it has a position
+        let g22 = SatId::from(22);
+        constellation
+            .get_mut(g22)
+            .unwrap()
+            .propagate(start, duration, &config, nav.records_for_slice(g22))
+            .unwrap();
+
+        // Half a step between grid epochs, from right under G22
+        let epoch = start + chrono::Duration::seconds(10 * 60 + 30);
+        let beneath = constellation
+            .get(g22)
+            .unwrap()
+            .interpolate_at(epoch)
+            .unwrap()
+            .to_lla();
+        let observer = LLA::new(beneath.latitude, beneath.longitude, 0.0);
+        let visible = constellation.visible(&observer, epoch, 0.0);
+        assert!(!visible.is_empty());
+        assert!(visible.iter().all(|(sat_id, _)| *sat_id != g22));
+
+        for (sat_id, aer) in &visible {
+            let satellite = constellation.get(*sat_id).unwrap();
+            let interpolated = observer.aer_to(&satellite.interpolate_at(epoch).unwrap());
+            assert_eq!(*aer, interpolated);
+            // Not the state at either neighbouring grid epoch
+            for neighbour in [10, 11].map(|minutes| start + chrono::Duration::minutes(minutes)) {
+                let state = satellite.state_at(neighbour).unwrap();
+                let at_grid = observer.aer_to(&state.position());
+                assert!(
+                    (at_grid.elevation - aer.elevation).abs() > 1e-4,
+                    "{}",
+                    sat_id
+                );
+            }
+        }
+
+        // A mask just above a satellite's elevation drops it, one just below keeps it
+        let (lowest, aer) = *visible
+            .iter()
+            .min_by(|a, b| a.1.elevation.total_cmp(&b.1.elevation))
+            .unwrap();
+        let above = constellation.visible(&observer, epoch, aer.elevation + 1e-9);
+        assert!(above.iter().all(|(sat_id, _)| *sat_id != lowest));
+        assert_eq!(above.len(), visible.len() - 1);
+        let below = constellation.visible(&observer, epoch, aer.elevation - 1e-9);
+        assert_eq!(below, visible);
+    }
+
+    fn mixed() -> Constellation {
+        let nav: RinexNav = format!("{}{}", NAV, STATE_VECTORS).parse().unwrap();
+        Constellation::from_nav(nav)
+    }
+
+    #[test]
+    fn satellites_are_named_after_their_spacecraft() {
+        let constellation = mixed();
+        // PRN 17 was SVN 53, a Block IIR-M, in June 2023
+        let g17 = constellation.get(17).unwrap();
+        assert_eq!(g17.name, "SVN53 IIR-M");
+        assert_eq!(g17.to_string(), "G17 (SVN53 IIR-M)");
+        assert_eq!(constellation.get(22).unwrap().name, "SVN47 IIR");
+        // The table only covers GPS
+        let glonass = constellation.get("R01".parse::<SatId>().unwrap()).unwrap();
+        assert!(glonass.name.is_empty());
+        assert_eq!(glonass.to_string(), "R01");
+        for satellite in constellation.iter() {
+            assert_eq!(satellite.states.len(), 0);
+            assert!(satellite.active);
+        }
+    }
+
+    #[test]
+    fn state_vector_ephemerides_are_unsupported() {
+        let glonass: SatId = "R01".parse().unwrap();
+        let sbas: SatId = "S31".parse().unwrap();
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let duration = Duration::from_secs(3600);
+        let config = PropagationConfig::new();
+
+        let mut constellation = mixed();
+        assert_eq!(constellation.len(), 34);
+        let statuses = constellation.propagate_all(start, duration, &config);
+        assert_eq!(statuses[&glonass], SatelliteStatus::Unsupported);
+        assert_eq!(statuses[&sbas], SatelliteStatus::Unsupported);
+        assert!(matches!(
+            statuses[&"G17".parse().unwrap()],
+            SatelliteStatus::Propagated(_)
+        ));
+        assert_eq!(
+            statuses[&"G22".parse().unwrap()],
+            SatelliteStatus::Unhealthy
+        );
+        assert!(constellation.get(glonass).unwrap().states.is_empty());
+        assert!(constellation.get(sbas).unwrap().states.is_empty());
+    }
+}
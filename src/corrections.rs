@@ -0,0 +1,143 @@
+use crate::antex::FrequencyPattern;
+use crate::dcb::CodeBiasCorrection;
+use crate::gnss::{self, Constellation, SatId, LLA};
+use crate::klobuchar::Klobuchar;
+use crate::nequick::NeQuickG;
+use crate::pseudorange::PseudorangeModel;
+use crate::troposphere::Saastamoinen;
+use chrono::{Datelike, Timelike};
+
+/// Delay and bias models the solvers apply to their modeled pseudoranges. Each filled
+/// model sets its term of `PseudorangeModel`; a model left out keeps its term unchanged.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Corrections {
+    pub troposphere: Option<Saastamoinen>,
+    pub ionosphere: Option<NeQuickG>, // Galileo L1 delay, Klobuchar without it; scale other signals
+    pub klobuchar: Option<Klobuchar>, // GPS model, also for the systems without their own
+    pub beidou_klobuchar: Option<Klobuchar>, // BeiDou model, likewise as an L1 delay
+    pub code_biases: Option<CodeBiasCorrection>, // Added to the broadcast group delay
+    pub receiver_antenna: Option<FrequencyPattern>, // Of the tracked frequency, from ANTEX
+}
+
+impl Corrections {
+    /// Fill in the delay terms of a satellite's model for a receiver at a known position
+    pub fn apply(&self, sat_id: SatId, receiver: &LLA, model: &mut PseudorangeModel) {
+        if let Some(troposphere) = &self.troposphere {
+            let utc = gnss::gps_seconds_to_utc(model.transmit_time);
+            let day_of_year =
+                utc.ordinal() as f64 + utc.num_seconds_from_midnight() as f64 / 86400.0;
+            model.troposphere = troposphere.slant_delay(receiver, model.aer.elevation, day_of_year);
+        }
+        // Each system's satellites take the model of its own navigation message; Galileo
+        // falls back to the GPS one when there is no NeQuick-G data
+        let (position, time) = (&model.satellite_position, model.transmit_time);
+        let gps = self.klobuchar.map(|m| m.delay(receiver, position, time));
+        let ionosphere = match sat_id.constellation {
+            Constellation::Galileo => match &self.ionosphere {
+                Some(nequick) => Some(nequick.delay(receiver, position, time)),
+                None => gps,
+            },
+            Constellation::BeiDou => self
+                .beidou_klobuchar
+                .map(|m| m.beidou_delay(receiver, position, time)),
+            _ => gps,
+        };
+        if let Some(delay) = ionosphere {
+            model.ionosphere = delay;
+        }
+        if let Some(code_biases) = &self.code_biases {
+            model.group_delay += code_biases.bias(sat_id);
+        }
+        if let Some(antenna) = &self.receiver_antenna {
+            model.antenna = antenna.receiver_range(&model.aer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::{State, ECEF};
+    use crate::nequick::NeQuickData;
+    use std::sync::Arc;
+
+    fn model(receiver: &LLA, satellite: ECEF) -> PseudorangeModel {
+        let time = 1_370_000_000.0;
+        PseudorangeModel {
+            transmit_time: time,
+            satellite_position: satellite,
+            geometric_range: 0.0,
+            sagnac: 0.0,
+            satellite_clock: 0.0,
+            group_delay: 0.0,
+            ionosphere: 0.0,
+            troposphere: 0.0,
+            antenna: 0.0,
+            aer: receiver.aer_to(&satellite),
+            satellite_state: State::new(time, satellite),
+        }
+    }
+
+    #[test]
+    fn ionosphere_follows_the_satellite_system() {
+        let receiver = LLA::new(34.0, -118.0, 100.0);
+        let satellite = LLA::new(30.0, -110.0, 20_200_000.0).to_ecef();
+        let gps = Klobuchar::new(
+            [1.8626e-08, 1.4901e-08, -1.1921e-07, -5.9605e-08],
+            [1.2698e+05, 0.0, -1.9661e+05, -6.5536e+04],
+        );
+        let beidou = Klobuchar::new(
+            [1.2107e-08, 1.4901e-07, -1.0729e-06, 1.7881e-06],
+            [1.2902e+05, -1.6384e+05, 3.9322e+05, -3.9322e+05],
+        );
+        let corrections = Corrections {
+            klobuchar: Some(gps),
+            beidou_klobuchar: Some(beidou),
+            ..Corrections::default()
+        };
+        let delay = |constellation| {
+            let mut model = model(&receiver, satellite);
+            corrections.apply(SatId::new(constellation, 5), &receiver, &mut model);
+            model.ionosphere
+        };
+        let time = 1_370_000_000.0;
+
+        assert_eq!(
+            delay(Constellation::Gps),
+            gps.delay(&receiver, &satellite, time)
+        );
+        assert_eq!(
+            delay(Constellation::BeiDou),
+            beidou.beidou_delay(&receiver, &satellite, time)
+        );
+        // GLONASS broadcasts no model and takes the GPS one
+        assert_eq!(delay(Constellation::Glonass), delay(Constellation::Gps));
+        // Nor does Galileo without NeQuick-G data
+        assert_eq!(delay(Constellation::Galileo), delay(Constellation::Gps));
+        assert!(delay(Constellation::Gps) > 1.0);
+
+        // With it Galileo takes NeQuick-G and the others keep Klobuchar
+        let nequick = NeQuickG::new(
+            [100.0, 0.0, 0.0],
+            Arc::new(NeQuickData::flat(50.0, 8.0, 3.0)),
+        );
+        let corrections = Corrections {
+            ionosphere: Some(nequick.clone()),
+            ..corrections
+        };
+        let delay = |constellation| {
+            let mut model = model(&receiver, satellite);
+            corrections.apply(SatId::new(constellation, 5), &receiver, &mut model);
+            model.ionosphere
+        };
+        assert_eq!(
+            delay(Constellation::Galileo),
+            nequick.delay(&receiver, &satellite, time)
+        );
+        assert_eq!(
+            delay(Constellation::Gps),
+            gps.delay(&receiver, &satellite, time)
+        );
+        assert!(delay(Constellation::Galileo) > 0.0);
+    }
+}
@@ -0,0 +1,519 @@
+#[cfg(feature = "ndarray")]
+use crate::dop_map::DopGrid;
+use crate::gnss::{self, StateRef, ECEF};
+#[cfg(feature = "ndarray")]
+use crate::kalman::FilterSolution;
+#[cfg(feature = "ndarray")]
+use crate::positioning::{Dop, SppSolution};
+use crate::satellite::Satellite;
+#[cfg(feature = "ndarray")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std-fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+
+const STATE_COLUMNS: [&str; 17] = [
+    "time",
+    "gps_week",
+    "tow",
+    "x",
+    "y",
+    "z",
+    "vx",
+    "vy",
+    "vz",
+    "clock_bias",
+    "latitude",
+    "longitude",
+    "altitude",
+    "kepler_converged",
+    "extrapolated",
+    "ephemeris_age",
+    "accuracy",
+];
+
+#[cfg(feature = "ndarray")]
+const DOP_MAP_COLUMNS: [&str; 8] = [
+    "latitude",
+    "longitude",
+    "gdop",
+    "pdop",
+    "hdop",
+    "vdop",
+    "tdop",
+    "availability",
+];
+
+#[cfg(feature = "ndarray")]
+const SPP_COLUMNS: [&str; 20] = [
+    "time",
+    "gps_week",
+    "tow",
+    "x",
+    "y",
+    "z",
+    "latitude",
+    "longitude",
+    "altitude",
+    "clock_bias",
+    "sigma_x",
+    "sigma_y",
+    "sigma_z",
+    "satellites",
+    "excluded",
+    "gdop",
+    "pdop",
+    "hdop",
+    "vdop",
+    "test_statistic",
+];
+
+#[cfg(feature = "ndarray")]
+const FILTER_COLUMNS: [&str; 19] = [
+    "time",
+    "gps_week",
+    "tow",
+    "x",
+    "y",
+    "z",
+    "vx",
+    "vy",
+    "vz",
+    "latitude",
+    "longitude",
+    "altitude",
+    "clock_bias",
+    "clock_drift",
+    "sigma_x",
+    "sigma_y",
+    "sigma_z",
+    "used",
+    "rejected",
+];
+
+/// How the time column is written
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// UTC, e.g. 2023-06-12T12:00:00.000Z
+    #[default]
+    Iso8601,
+    GpsSeconds, // Since the GPS epoch
+    Unix,       // UTC seconds since 1970
+    /// A chrono strftime pattern applied to UTC
+    Strftime(String),
+}
+
+/// Layout of a CSV export
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub columns: Vec<String>, // Subset and order of the columns; empty for all of them
+    pub delimiter: char,
+    pub time_format: TimeFormat,
+    pub precision: Option<usize>, // Decimals of float cells; None for the shortest exact form
+    pub nan_as_empty: bool,       // Write NaN and infinite values as empty cells, else literally
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            columns: Vec::new(),
+            delimiter: ',',
+            time_format: TimeFormat::default(),
+            precision: None,
+            nan_as_empty: true,
+            header: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn with_columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|column| column.to_string()).collect();
+        self
+    }
+
+    /// Indices of the selected columns among those a record type provides
+    fn select(&self, available: &[&str]) -> io::Result<Vec<usize>> {
+        if self.columns.is_empty() {
+            return Ok((0..available.len()).collect());
+        }
+        self.columns
+            .iter()
+            .map(|name| {
+                available
+                    .iter()
+                    .position(|column| column == name)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("unknown column {}", name),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    fn format(&self, cell: &Cell) -> String {
+        match *cell {
+            Cell::Time(gps_seconds) => {
+                let utc = gnss::gps_seconds_to_utc(gps_seconds);
+                match &self.time_format {
+                    TimeFormat::Iso8601 => utc.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+                    TimeFormat::GpsSeconds => gps_seconds.to_string(),
+                    TimeFormat::Unix => (utc.timestamp_micros() as f64 / 1e6).to_string(),
+                    TimeFormat::Strftime(pattern) => utc.format(pattern).to_string(),
+                }
+            }
+            Cell::Float(value) if !value.is_finite() && self.nan_as_empty => String::new(),
+            Cell::Float(value) => match self.precision {
+                Some(precision) => format!("{:.*}", precision, value),
+                None => value.to_string(),
+            },
+            Cell::Int(value) => value.to_string(),
+            Cell::Bool(value) => value.to_string(),
+            Cell::Missing => String::new(),
+        }
+    }
+}
+
+/// One value of a row
+enum Cell {
+    Time(f64), // GPS seconds
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+    Missing,
+}
+
+impl From<Option<f64>> for Cell {
+    fn from(value: Option<f64>) -> Self {
+        value.map_or(Cell::Missing, Cell::Float)
+    }
+}
+
+fn write_table<const N: usize>(
+    mut writer: impl Write,
+    columns: &[&str; N],
+    rows: impl IntoIterator<Item = [Cell; N]>,
+    options: &CsvOptions,
+) -> io::Result<()> {
+    let selected = options.select(columns)?;
+    let delimiter = options.delimiter.to_string();
+    if options.header {
+        let header: Vec<&str> = selected.iter().map(|&i| columns[i]).collect();
+        writeln!(writer, "{}", header.join(&delimiter))?;
+    }
+    for row in rows {
+        let cells: Vec<String> = selected.iter().map(|&i| options.format(&row[i])).collect();
+        writeln!(writer, "{}", cells.join(&delimiter))?;
+    }
+    writer.flush()
+}
+
+/// Time, GPS week and time of week cells
+fn time_cells(gps_seconds: f64) -> [Cell; 3] {
+    let week = (gps_seconds / gnss::SECONDS_PER_WEEK).floor();
+    [
+        Cell::Time(gps_seconds),
+        Cell::Int(week as i64),
+        Cell::Float(gps_seconds - week * gnss::SECONDS_PER_WEEK),
+    ]
+}
+
+fn position_cells(position: &ECEF) -> [Cell; 6] {
+    let lla = position.to_lla();
+    [
+        Cell::Float(position.x),
+        Cell::Float(position.y),
+        Cell::Float(position.z),
+        Cell::Float(lla.latitude),
+        Cell::Float(lla.longitude),
+        Cell::Float(lla.altitude),
+    ]
+}
+
+#[cfg(feature = "ndarray")]
+fn gps_seconds(epoch: DateTime<Utc>) -> f64 {
+    gnss::gps_seconds(epoch)
+}
+
+fn state_row(state: StateRef<'_>) -> [Cell; 17] {
+    let [time, week, tow] = time_cells(state.time());
+    let [x, y, z, latitude, longitude, altitude] = position_cells(&state.position());
+    let velocity = state.velocity();
+    [
+        time,
+        week,
+        tow,
+        x,
+        y,
+        z,
+        velocity.map(|v| v.x).into(),
+        velocity.map(|v| v.y).into(),
+        velocity.map(|v| v.z).into(),
+        state.clock_bias().into(),
+        latitude,
+        longitude,
+        altitude,
+        Cell::Bool(state.kepler_converged()),
+        Cell::Bool(state.extrapolated()),
+        Cell::Float(state.ephemeris_age()),
+        state.accuracy().into(),
+    ]
+}
+
+impl Satellite {
+    /// Write one row per propagated state. Columns: time, gps_week, tow, x, y, z, vx, vy,
+    /// vz, clock_bias, latitude, longitude, altitude, kepler_converged, extrapolated,
+    /// ephemeris_age and accuracy; velocity and clock cells are empty when not propagated.
+    pub fn write_csv(&self, writer: impl Write, options: &CsvOptions) -> io::Result<()> {
+        write_table(
+            writer,
+            &STATE_COLUMNS,
+            self.states.iter().map(state_row),
+            options,
+        )
+    }
+
+    /// `write_csv` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_csv(&self, path: &str, options: &CsvOptions) -> io::Result<()> {
+        self.write_csv(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl DopGrid {
+    /// Write one row per cell, south to north and west to east within a latitude. Columns:
+    /// latitude, longitude, gdop, pdop, hdop, vdop, tdop and availability; DOP cells are
+    /// empty where fewer than four satellites were ever in view.
+    pub fn write_csv(&self, writer: impl Write, options: &CsvOptions) -> io::Result<()> {
+        let rows = self.iter().map(|(lla, dop, availability)| {
+            let component = |get: fn(&Dop) -> f64| Cell::from(dop.as_ref().map(get));
+            [
+                Cell::Float(lla.latitude),
+                Cell::Float(lla.longitude),
+                component(|dop| dop.gdop),
+                component(|dop| dop.pdop),
+                component(|dop| dop.hdop),
+                component(|dop| dop.vdop),
+                component(|dop| dop.tdop),
+                Cell::Float(availability),
+            ]
+        });
+        write_table(writer, &DOP_MAP_COLUMNS, rows, options)
+    }
+
+    /// `write_csv` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_csv(&self, path: &str, options: &CsvOptions) -> io::Result<()> {
+        self.write_csv(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+/// Write one row per SPP solution at its epoch. Columns: time, gps_week, tow, x, y, z,
+/// latitude, longitude, altitude, clock_bias, sigma_x, sigma_y, sigma_z, satellites,
+/// excluded, gdop, pdop, hdop, vdop and test_statistic.
+#[cfg(feature = "ndarray")]
+pub fn write_spp_csv<'a>(
+    writer: impl Write,
+    solutions: impl IntoIterator<Item = (DateTime<Utc>, &'a SppSolution)>,
+    options: &CsvOptions,
+) -> io::Result<()> {
+    let rows = solutions.into_iter().map(|(epoch, solution)| {
+        let [time, week, tow] = time_cells(gps_seconds(epoch));
+        let [x, y, z, latitude, longitude, altitude] = position_cells(&solution.position);
+        let sigma = |i: usize| Cell::Float(solution.covariance[[i, i]].sqrt());
+        [
+            time,
+            week,
+            tow,
+            x,
+            y,
+            z,
+            latitude,
+            longitude,
+            altitude,
+            Cell::Float(solution.clock_bias),
+            sigma(0),
+            sigma(1),
+            sigma(2),
+            Cell::Int(solution.residuals.len() as i64),
+            Cell::Int(solution.excluded.len() as i64),
+            Cell::Float(solution.dop.gdop),
+            Cell::Float(solution.dop.pdop),
+            Cell::Float(solution.dop.hdop),
+            Cell::Float(solution.dop.vdop),
+            solution.test_statistic.into(),
+        ]
+    });
+    write_table(writer, &SPP_COLUMNS, rows, options)
+}
+
+/// Write one row per filter epoch. Columns: time, gps_week, tow, x, y, z, vx, vy, vz,
+/// latitude, longitude, altitude, clock_bias, clock_drift, sigma_x, sigma_y, sigma_z,
+/// used and rejected.
+#[cfg(feature = "ndarray")]
+pub fn write_filter_csv<'a>(
+    writer: impl Write,
+    solutions: impl IntoIterator<Item = &'a FilterSolution>,
+    options: &CsvOptions,
+) -> io::Result<()> {
+    let rows = solutions.into_iter().map(|solution| {
+        let [time, week, tow] = time_cells(gps_seconds(solution.epoch));
+        let [x, y, z, latitude, longitude, altitude] = position_cells(&solution.position);
+        let sigma = |i: usize| Cell::Float(solution.covariance[[i, i]].sqrt());
+        [
+            time,
+            week,
+            tow,
+            x,
+            y,
+            z,
+            Cell::Float(solution.velocity.x),
+            Cell::Float(solution.velocity.y),
+            Cell::Float(solution.velocity.z),
+            latitude,
+            longitude,
+            altitude,
+            Cell::Float(solution.clock_bias),
+            Cell::Float(solution.clock_drift),
+            sigma(0),
+            sigma(1),
+            sigma(2),
+            Cell::Int(solution.used.len() as i64),
+            Cell::Int(solution.rejected.len() as i64),
+        ]
+    });
+    write_table(writer, &FILTER_COLUMNS, rows, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::NAV;
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+
+    fn satellite(config: &PropagationConfig) -> Satellite {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut satellite = Satellite::builder(17).build();
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        satellite
+            .propagate(
+                start,
+                Duration::from_secs(3600),
+                config,
+                nav.records_for_slice(17.into()),
+            )
+            .unwrap();
+        satellite
+    }
+
+    fn export(satellite: &Satellite, options: &CsvOptions) -> String {
+        let mut out = Vec::new();
+        satellite.write_csv(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parsing_the_export_back_recovers_the_states() {
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(300))
+            .with_velocity(true)
+            .with_clock(true);
+        let satellite = satellite(&config);
+        let text = export(&satellite, &CsvOptions::default());
+        let mut lines = text.lines();
+        assert_eq!(lines.next().unwrap(), STATE_COLUMNS.join(","));
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), 12);
+
+        let column = |name: &str| STATE_COLUMNS.iter().position(|c| *c == name).unwrap();
+        let first = &rows[0];
+        assert_eq!(first[column("time")], "2023-06-12T02:00:00.000Z");
+        assert_eq!(rows[1][column("time")], "2023-06-12T02:05:00.000Z");
+        assert_eq!(first[column("gps_week")], "2266");
+        // Monday 02:00 plus the 18 leap seconds
+        assert_eq!(first[column("tow")], "93618");
+        for (row, state) in rows.iter().zip(satellite.states.iter()) {
+            // The shortest exact form parses back to the same bits
+            let value = |name: &str| row[column(name)].parse::<f64>().unwrap();
+            let position = state.position();
+            assert_eq!(value("x"), position.x);
+            assert_eq!(value("y"), position.y);
+            assert_eq!(value("z"), position.z);
+            assert_eq!(value("vx"), state.velocity().unwrap().x);
+            assert_eq!(value("clock_bias"), state.clock_bias().unwrap());
+            assert_eq!(value("latitude"), position.to_lla().latitude);
+            assert_eq!(row[column("kepler_converged")], "true");
+            assert_eq!(row[column("extrapolated")], "false");
+        }
+    }
+
+    #[test]
+    fn unpropagated_velocity_and_clock_are_empty_cells() {
+        let satellite = satellite(&PropagationConfig::new().step(Duration::from_secs(600)));
+        let options = CsvOptions::default().with_columns(&["vx", "vy", "vz", "clock_bias", "x"]);
+        let text = export(&satellite, &options);
+        let row = text.lines().nth(1).unwrap();
+        assert!(row.starts_with(",,,,"));
+        assert!(row[4..].parse::<f64>().is_ok());
+    }
+
+    #[test]
+    fn options_select_columns_delimiter_precision_and_time_format() {
+        let satellite = satellite(&PropagationConfig::new().step(Duration::from_secs(600)));
+        let options = CsvOptions {
+            delimiter: ';',
+            time_format: TimeFormat::GpsSeconds,
+            precision: Some(2),
+            header: false,
+            ..CsvOptions::default()
+        }
+        .with_columns(&["z", "time"]);
+        let text = export(&satellite, &options);
+        assert_eq!(text.lines().count(), 6);
+        let state = satellite.states.first().unwrap();
+        let expected = format!("{:.2};{}", state.position().z, state.time());
+        assert_eq!(text.lines().next().unwrap(), expected);
+
+        let options = CsvOptions {
+            time_format: TimeFormat::Strftime("%H%M".into()),
+            ..CsvOptions::default()
+        }
+        .with_columns(&["time"]);
+        let text = export(&satellite, &options);
+        assert_eq!(text.lines().nth(2).unwrap(), "0210");
+
+        let options = CsvOptions {
+            time_format: TimeFormat::Unix,
+            ..CsvOptions::default()
+        };
+        assert_eq!(options.format(&Cell::Time(state.time())), "1686535200");
+    }
+
+    #[test]
+    fn nan_is_empty_or_literal() {
+        let mut options = CsvOptions::default();
+        assert_eq!(options.format(&Cell::Float(f64::NAN)), "");
+        assert_eq!(options.format(&Cell::Float(f64::INFINITY)), "");
+        options.nan_as_empty = false;
+        assert_eq!(options.format(&Cell::Float(f64::NAN)), "NaN");
+        assert_eq!(options.format(&Cell::Float(f64::INFINITY)), "inf");
+        assert_eq!(options.format(&Cell::Missing), "");
+    }
+
+    #[test]
+    fn unknown_columns_are_rejected() {
+        let satellite = satellite(&PropagationConfig::new().step(Duration::from_secs(600)));
+        let options = CsvOptions::default().with_columns(&["x", "speed"]);
+        let error = satellite.write_csv(Vec::new(), &options).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("speed"));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::gnss::{self, SatId};
+use crate::observation::SatelliteObservations;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// Thresholds of `CycleSlipDetector`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlipConfig {
+    pub geometry_free_threshold: f64, // Jump in L1 - L2 between epochs, m
+    pub doppler_threshold: f64,       // Phase change unexplained by the integrated Doppler, cycles
+    pub max_gap: f64,                 // Outage after which continuity is not assumed, s
+}
+
+impl Default for SlipConfig {
+    fn default() -> Self {
+        Self {
+            geometry_free_threshold: 0.05,
+            doppler_threshold: 0.5,
+            max_gap: 60.0,
+        }
+    }
+}
+
+/// Slip evidence for one satellite at one epoch
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SlipFlags {
+    pub loss_of_lock: bool,  // Set by the receiver on any signal
+    pub gap: bool,           // Not seen within the maximum gap, or first seen
+    pub geometry_free: bool, // Dual-frequency jump test
+    pub doppler: bool,       // Single-frequency Doppler consistency test on any signal
+}
+
+impl SlipFlags {
+    /// Whether the carrier ambiguities should be treated as new
+    pub fn slipped(&self) -> bool {
+        self.loss_of_lock || self.gap || self.geometry_free || self.doppler
+    }
+}
+
+/// Last epoch seen from one satellite
+#[derive(Debug, Clone, PartialEq)]
+struct Track {
+    time: f64,
+    geometry_free: Option<f64>,
+    carriers: Vec<(Signal, f64, Option<f64>)>, // Phase in cycles and Doppler per signal
+}
+
+/// Per-satellite cycle slip detection over a stream of epochs, combining the receiver's LLI
+/// bits, the geometry-free phase jump test on the first two signals and the Doppler
+/// consistency test on every signal with phase and Doppler.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CycleSlipDetector {
+    config: SlipConfig,
+    tracks: BTreeMap<SatId, Track>,
+}
+
+impl CycleSlipDetector {
+    pub fn new(config: SlipConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Test one epoch against the previous one of each satellite
+    pub fn update(
+        &mut self,
+        epoch: DateTime<Utc>,
+        observations: &[SatelliteObservations],
+    ) -> BTreeMap<SatId, SlipFlags> {
+        let time = gnss::gps_seconds(epoch);
+        let mut flags = BTreeMap::new();
+        for obs in observations {
+            let geometry_free = match obs.signals.as_slice() {
+                [first, second, ..] => obs
+                    .pair(epoch, first.signal, second.signal)
+                    .and_then(|pair| pair.geometry_free_phase()),
+                _ => None,
+            };
+            let carriers: Vec<_> = obs
+                .signals
+                .iter()
+                .filter_map(|signal| Some((signal.signal, signal.phase?, signal.doppler)))
+                .collect();
+
+            let mut sat_flags = SlipFlags {
+                loss_of_lock: obs.signals.iter().any(|signal| signal.loss_of_lock),
+                ..SlipFlags::default()
+            };
+            match self.tracks.get(&obs.sat_id) {
+                Some(track) if time > track.time && time - track.time <= self.config.max_gap => {
+                    let dt = time - track.time;
+                    if let (Some(now), Some(before)) = (geometry_free, track.geometry_free) {
+                        sat_flags.geometry_free =
+                            (now - before).abs() > self.config.geometry_free_threshold;
+                    }
+                    sat_flags.doppler = carriers.iter().any(|&(signal, phase, doppler)| {
+                        let Some(&(_, last_phase, last_doppler)) =
+                            track.carriers.iter().find(|carrier| carrier.0 == signal)
+                        else {
+                            return false;
+                        };
+                        let (Some(doppler), Some(last_doppler)) = (doppler, last_doppler) else {
+                            return false;
+                        };
+                        // Phase grows with range while Doppler is positive on approach
+                        let predicted = -(doppler + last_doppler) / 2.0 * dt;
+                        (phase - last_phase - predicted).abs() > self.config.doppler_threshold
+                    });
+                }
+                _ => sat_flags.gap = true,
+            }
+            self.tracks.insert(
+                obs.sat_id,
+                Track {
+                    time,
+                    geometry_free,
+                    carriers,
+                },
+            );
+            flags.insert(obs.sat_id, sat_flags);
+        }
+        flags
+    }
+
+    /// Forget every satellite's history
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observation::SignalObservation;
+    use chrono::{Duration, TimeZone};
+
+    fn epoch(t: f64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap() + Duration::seconds(t as i64)
+    }
+
+    /// Carrier and Doppler on a signal at `t` s, over a slowly growing ionosphere, with
+    /// `slip` cycles added to the ambiguity
+    fn carrier(signal: Signal, t: f64, slip: f64) -> SignalObservation {
+        let range = 21_500_000.0 + 480.0 * t - 0.05 * t * t;
+        let range_rate = 480.0 - 0.1 * t;
+        let factor = signal.iono_factor(Signal::GpsL1);
+        let (iono, iono_rate) = ((4.0 + 0.002 * t) * factor, 0.002 * factor);
+        let wavelength = signal.wavelength();
+        SignalObservation {
+            phase: Some((range - iono) / wavelength + 98765.0 + slip),
+            doppler: Some(-(range_rate - iono_rate) / wavelength),
+            ..SignalObservation::new(signal, Some(range + iono), None)
+        }
+    }
+
+    fn satellite(signals: Vec<SignalObservation>) -> SatelliteObservations {
+        SatelliteObservations {
+            sat_id: SatId::from(11),
+            signals,
+        }
+    }
+
+    /// Flags of every epoch of a dual-frequency track with slips on L1 and L2 from
+    /// given epochs on
+    fn run(slip_l1: (usize, f64), slip_l2: (usize, f64)) -> Vec<SlipFlags> {
+        let mut detector = CycleSlipDetector::new(SlipConfig::default());
+        (0..300)
+            .map(|k| {
+                let t = k as f64;
+                let l1 = if k >= slip_l1.0 { slip_l1.1 } else { 0.0 };
+                let l2 = if k >= slip_l2.0 { slip_l2.1 } else { 0.0 };
+                let obs = satellite(vec![
+                    carrier(Signal::GpsL1, t, l1),
+                    carrier(Signal::GpsL2, t, l2),
+                ]);
+                detector.update(epoch(t), &[obs])[&SatId::from(11)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clean_data_raises_no_flags() {
+        let flags = run((usize::MAX, 0.0), (usize::MAX, 0.0));
+        assert!(flags[0].gap && flags[0].slipped());
+        assert!(flags[1..].iter().all(|flags| !flags.slipped()));
+    }
+
+    #[test]
+    fn one_and_five_cycle_slips_are_detected() {
+        for (slip_l1, slip_l2) in [
+            ((120, 1.0), (usize::MAX, 0.0)),
+            ((120, -5.0), (usize::MAX, 0.0)),
+            ((usize::MAX, 0.0), (120, 1.0)),
+            ((usize::MAX, 0.0), (120, 5.0)),
+        ] {
+            let flags = run(slip_l1, slip_l2);
+            let slipped: Vec<usize> = (1..flags.len()).filter(|&k| flags[k].slipped()).collect();
+            assert_eq!(slipped, vec![120], "{:?} {:?}", slip_l1, slip_l2);
+            assert!(flags[120].geometry_free && flags[120].doppler);
+        }
+    }
+
+    #[test]
+    fn single_frequency_slips_are_caught_by_the_doppler() {
+        for slip in [1.0, 5.0] {
+            let mut detector = CycleSlipDetector::new(SlipConfig::default());
+            for k in 0..200 {
+                let t = k as f64;
+                let cycles = if k >= 50 { slip } else { 0.0 };
+                let obs = satellite(vec![carrier(Signal::GpsL1, t, cycles)]);
+                let flags = detector.update(epoch(t), &[obs])[&SatId::from(11)];
+                assert!(!flags.geometry_free);
+                assert_eq!(flags.slipped(), k == 0 || k == 50, "{} at {}", slip, k);
+            }
+        }
+    }
+
+    #[test]
+    fn loss_of_lock_and_gaps_are_flagged() {
+        let mut detector = CycleSlipDetector::new(SlipConfig::default());
+        let sat_id = SatId::from(11);
+        detector.update(
+            epoch(0.0),
+            &[satellite(vec![carrier(Signal::GpsL1, 0.0, 0.0)])],
+        );
+        let mut flagged = carrier(Signal::GpsL1, 1.0, 0.0);
+        flagged.loss_of_lock = true;
+        let flags = detector.update(epoch(1.0), &[satellite(vec![flagged])])[&sat_id];
+        assert!(flags.loss_of_lock && !flags.doppler && !flags.gap);
+        let late = satellite(vec![carrier(Signal::GpsL1, 100.0, 0.0)]);
+        let flags = detector.update(epoch(100.0), &[late])[&sat_id];
+        assert!(flags.gap && !flags.loss_of_lock);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::constellation::Constellation;
+use crate::gnss::{self, Constellation as System};
+use crate::json::{number, string};
+use crate::satellite::Satellite;
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std-fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// Layout of a CZML export
+#[derive(Debug, Clone, PartialEq)]
+pub struct CzmlOptions {
+    pub name: String,
+    pub decimation: usize, // Write every Nth state; the last one is always written
+    pub interpolation_degree: u32, // Lagrange degree Cesium interpolates the samples with
+    pub multiplier: f64,   // Clock speed-up of the playback
+    pub point_size: f64,   // Pixels
+    pub labels: bool,
+}
+
+impl Default for CzmlOptions {
+    fn default() -> Self {
+        Self {
+            name: "pnt_rust".to_string(),
+            decimation: 1,
+            interpolation_degree: 5,
+            multiplier: 60.0,
+            point_size: 6.0,
+            labels: true,
+        }
+    }
+}
+
+impl Constellation {
+    /// Every propagated satellite as a CZML document for CesiumJS: a document packet with
+    /// a clock over the propagated span, then one packet per satellite with its ECEF
+    /// states as epoch-relative samples in the FIXED frame
+    pub fn write_czml(&self, mut writer: impl Write, options: &CzmlOptions) -> io::Result<()> {
+        let spans: Vec<(f64, f64)> = self
+            .iter()
+            .filter_map(|satellite| {
+                Some((
+                    *satellite.states.times().first()?,
+                    *satellite.states.times().last()?,
+                ))
+            })
+            .collect();
+        let start = spans.iter().map(|span| span.0).reduce(f64::min);
+        let end = spans.iter().map(|span| span.1).reduce(f64::max);
+
+        write!(
+            writer,
+            "[\n{{\"id\":\"document\",\"name\":{},\"version\":\"1.0\"",
+            string(&options.name)
+        )?;
+        if let (Some(start), Some(end)) = (start, end) {
+            write!(
+                writer,
+                r#","clock":{{"interval":"{}","currentTime":"{}","multiplier":{},"range":"LOOP_STOP","step":"SYSTEM_CLOCK_MULTIPLIER"}}"#,
+                interval(start, end),
+                time(start),
+                number(options.multiplier)
+            )?;
+        }
+        write!(writer, "}}")?;
+        for satellite in self.iter() {
+            if let Some(packet) = satellite.czml_packet(options) {
+                write!(writer, ",\n{}", packet)?;
+            }
+        }
+        writeln!(writer, "\n]")?;
+        writer.flush()
+    }
+
+    /// `write_czml` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_czml(&self, path: &str, options: &CzmlOptions) -> io::Result<()> {
+        self.write_czml(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+impl Satellite {
+    /// Packet with availability over the propagated span, a point, an optional label and
+    /// the sampled position; None without states
+    fn czml_packet(&self, options: &CzmlOptions) -> Option<String> {
+        let times = self.states.times();
+        let (epoch, last) = (*times.first()?, *times.last()?);
+        let step = options.decimation.max(1);
+        let last_index = self.states.len() - 1;
+        let samples: Vec<String> = self
+            .states
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % step == 0 || *i == last_index)
+            .map(|(_, state)| {
+                let position = state.position();
+                format!(
+                    "{},{},{},{}",
+                    number(state.time() - epoch),
+                    number(position.x),
+                    number(position.y),
+                    number(position.z)
+                )
+            })
+            .collect();
+        let [r, g, b] = color(self.constellation());
+        let label = match options.labels {
+            true => format!(
+                r#","label":{{"text":"{}","font":"11pt sans-serif","horizontalOrigin":"LEFT","pixelOffset":{{"cartesian2":[8,0]}},"fillColor":{{"rgba":[{},{},{},255]}}}}"#,
+                self.id, r, g, b
+            ),
+            false => String::new(),
+        };
+        Some(format!(
+            r#"{{"id":"{}","name":{},"availability":"{}","point":{{"pixelSize":{},"color":{{"rgba":[{},{},{},255]}}}}{},"position":{{"epoch":"{}","referenceFrame":"FIXED","interpolationAlgorithm":"LAGRANGE","interpolationDegree":{},"cartesian":[{}]}}}}"#,
+            self.id,
+            string(&self.name),
+            interval(epoch, last),
+            number(options.point_size),
+            r,
+            g,
+            b,
+            label,
+            time(epoch),
+            options.interpolation_degree,
+            samples.join(",")
+        ))
+    }
+}
+
+/// ISO 8601 UTC time of GPS seconds
+fn time(gps_seconds: f64) -> String {
+    gnss::gps_seconds_to_utc(gps_seconds)
+        .format(TIME_FORMAT)
+        .to_string()
+}
+
+fn interval(start: f64, end: f64) -> String {
+    format!("{}/{}", time(start), time(end))
+}
+
+/// Point and label colour of a system
+fn color(system: System) -> [u8; 3] {
+    match system {
+        System::Gps => [80, 160, 255],
+        System::Glonass => [255, 90, 90],
+        System::Galileo => [255, 200, 60],
+        System::BeiDou => [90, 220, 120],
+        System::Qzss => [220, 120, 255],
+        System::Irnss => [255, 150, 60],
+        System::Sbas => [200, 200, 200],
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::test_support::propagated_hour;
+    use serde_json::Value;
+
+    fn packets(constellation: &Constellation, options: &CzmlOptions) -> Vec<Value> {
+        let mut out = Vec::new();
+        constellation.write_czml(&mut out, options).unwrap();
+        let document: Value = serde_json::from_slice(&out).unwrap();
+        document.as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn document_packet_then_one_packet_per_satellite() {
+        let constellation = propagated_hour();
+        let packets = packets(&constellation, &CzmlOptions::default());
+        let propagated = constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .count();
+        assert_eq!(packets.len(), propagated + 1);
+
+        let document = &packets[0];
+        assert_eq!(document["id"], "document");
+        assert_eq!(document["version"], "1.0");
+        assert_eq!(document["name"], "pnt_rust");
+        let clock = &document["clock"];
+        assert_eq!(
+            clock["interval"],
+            "2023-06-12T02:00:00.000Z/2023-06-12T02:59:00.000Z"
+        );
+        assert_eq!(clock["currentTime"], "2023-06-12T02:00:00.000Z");
+        assert_eq!(clock["multiplier"], 60.0);
+
+        for packet in &packets[1..] {
+            assert_eq!(packet["availability"], clock["interval"]);
+            assert_eq!(packet["label"]["text"], packet["id"]);
+            let position = &packet["position"];
+            assert_eq!(position["epoch"], "2023-06-12T02:00:00.000Z");
+            assert_eq!(position["referenceFrame"], "FIXED");
+            assert_eq!(position["interpolationAlgorithm"], "LAGRANGE");
+            assert_eq!(position["interpolationDegree"], 5);
+        }
+        let g17 = packets.iter().find(|packet| packet["id"] == "G17").unwrap();
+        assert_eq!(
+            g17["point"]["color"]["rgba"],
+            serde_json::json!([80, 160, 255, 255])
+        );
+    }
+
+    #[test]
+    fn decimated_samples_are_monotonic_and_epoch_relative() {
+        let constellation = propagated_hour();
+        let options = CzmlOptions {
+            decimation: 7,
+            labels: false,
+            ..CzmlOptions::default()
+        };
+        let packets = packets(&constellation, &options);
+        let g17 = packets.iter().find(|packet| packet["id"] == "G17").unwrap();
+        assert!(g17.get("label").is_none());
+        let cartesian: Vec<f64> = g17["position"]["cartesian"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_f64().unwrap())
+            .collect();
+        assert_eq!(cartesian.len() % 4, 0);
+        let samples: Vec<&[f64]> = cartesian.chunks(4).collect();
+        // Every seventh of the 60 states, then the last one
+        let offsets: Vec<f64> = samples.iter().map(|sample| sample[0]).collect();
+        let mut expected: Vec<f64> = (0..60).step_by(7).map(|i| i as f64 * 60.0).collect();
+        expected.push(3540.0);
+        assert_eq!(offsets, expected);
+        assert!(offsets.windows(2).all(|pair| pair[1] > pair[0]));
+
+        let satellite = constellation.get(gnss::SatId::gps(17)).unwrap();
+        let states = &satellite.states;
+        for (sample, index) in samples.iter().zip((0..60).step_by(7).chain([59])) {
+            let position = states.get(index).unwrap().position();
+            assert_eq!(sample[1..], [position.x, position.y, position.z]);
+        }
+    }
+}
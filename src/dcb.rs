@@ -0,0 +1,365 @@
+use crate::gnss::{self, SatId};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A line of a bias file that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseBiasError {
+    pub line: usize, // 1-based
+    pub message: String,
+}
+
+impl fmt::Display for ParseBiasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseBiasError {}
+
+/// Satellite code biases by RINEX 3 observation code (e.g. "C1C"), m. Differential signal
+/// biases DSB(a, b) = B(a) - B(b) are what a pseudorange on code a carries in excess of
+/// one on code b.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BiasTable {
+    differential: BTreeMap<(SatId, String, String), f64>,
+    observable: BTreeMap<(SatId, String), f64>, // Observable-specific biases
+}
+
+impl BiasTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Satellite DSB and OSB entries of a SINEX-BIAS file (such as CODE's .BSX products) in
+    /// ns; receiver biases are skipped. A later entry for the same pair replaces an earlier
+    /// one, so only a single validity period should be passed in.
+    pub fn from_sinex_bias(text: &str) -> Result<Self, ParseBiasError> {
+        let mut table = Self::new();
+        let mut in_solution = false;
+        for (idx, line) in text.lines().enumerate() {
+            if line.starts_with("+BIAS/SOLUTION") {
+                in_solution = true;
+                continue;
+            }
+            if line.starts_with("-BIAS/SOLUTION") {
+                in_solution = false;
+                continue;
+            }
+            if !in_solution || !line.starts_with(' ') {
+                continue;
+            }
+            let error = |message: &str| ParseBiasError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            let field =
+                |start: usize, end: usize| line.get(start..end.min(line.len())).map(str::trim);
+            let kind = field(1, 5).ok_or_else(|| error("missing bias type"))?;
+            let station = field(15, 24).unwrap_or("");
+            if !station.is_empty() {
+                continue;
+            }
+            let sat_id: SatId = field(11, 14)
+                .and_then(|prn| prn.parse().ok())
+                .ok_or_else(|| error("invalid satellite"))?;
+            let first = field(25, 29).ok_or_else(|| error("missing observation code"))?;
+            let second = field(30, 34).unwrap_or("");
+            let unit = field(65, 69).unwrap_or("");
+            if unit != "ns" {
+                continue; // Phase biases in cycles are not code biases
+            }
+            let value: f64 = field(70, 91)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| error("invalid bias value"))?;
+            let meters = value * 1e-9 * gnss::C_LIGHT;
+            match kind {
+                "DSB" => table.insert_differential(sat_id, first, second, meters),
+                "OSB" => table.insert_observable(sat_id, first, meters),
+                _ => {}
+            }
+        }
+        Ok(table)
+    }
+
+    /// Satellite lines of a CODE monthly DCB file (e.g. P1C1yymm.DCB, P1P2yymm.DCB) in ns.
+    /// The file does not name RINEX codes, so `first` and `second` give them, e.g.
+    /// ("C1W", "C1C") for P1-C1.
+    pub fn from_code_dcb(text: &str, first: &str, second: &str) -> Result<Self, ParseBiasError> {
+        let mut table = Self::new();
+        for (idx, line) in text.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let (Some(name), Some(value)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            // Satellite lines start with a system letter and PRN; receivers have longer names
+            let Ok(sat_id) = name.parse::<SatId>() else {
+                continue;
+            };
+            if name.len() != 3 {
+                continue;
+            }
+            let value: f64 = value.parse().map_err(|_| ParseBiasError {
+                line: idx + 1,
+                message: format!("invalid bias value {}", value),
+            })?;
+            table.insert_differential(sat_id, first, second, value * 1e-9 * gnss::C_LIGHT);
+        }
+        Ok(table)
+    }
+
+    /// Record DSB(first, second) for a satellite, m
+    pub fn insert_differential(&mut self, sat_id: SatId, first: &str, second: &str, bias: f64) {
+        self.differential
+            .insert((sat_id, first.to_string(), second.to_string()), bias);
+    }
+
+    /// Record the observable-specific bias of a code, m
+    pub fn insert_observable(&mut self, sat_id: SatId, code: &str, bias: f64) {
+        self.observable.insert((sat_id, code.to_string()), bias);
+    }
+
+    /// Merge another table in, its entries taking precedence
+    pub fn extend(&mut self, other: &BiasTable) {
+        self.differential
+            .extend(other.differential.iter().map(|(k, v)| (k.clone(), *v)));
+        self.observable
+            .extend(other.observable.iter().map(|(k, v)| (k.clone(), *v)));
+    }
+
+    /// DSB(first, second) of a satellite in m: directly, reversed, from two OSBs, or through
+    /// one intermediate code. None if the satellite or codes are not covered.
+    pub fn differential(&self, sat_id: SatId, first: &str, second: &str) -> Option<f64> {
+        if first == second {
+            return Some(0.0);
+        }
+        if let Some(bias) = self.direct(sat_id, first, second) {
+            return Some(bias);
+        }
+        // DSB(a, b) = DSB(a, c) + DSB(c, b)
+        self.differential
+            .keys()
+            .filter(|(sat, _, _)| *sat == sat_id)
+            .flat_map(|(_, a, b)| [a, b])
+            .filter(|code| *code != first && *code != second)
+            .find_map(|via| {
+                Some(self.direct(sat_id, first, via)? + self.direct(sat_id, via, second)?)
+            })
+    }
+
+    fn direct(&self, sat_id: SatId, first: &str, second: &str) -> Option<f64> {
+        let key = |a: &str, b: &str| (sat_id, a.to_string(), b.to_string());
+        if let Some(bias) = self.differential.get(&key(first, second)) {
+            return Some(*bias);
+        }
+        if let Some(bias) = self.differential.get(&key(second, first)) {
+            return Some(-bias);
+        }
+        let first = self.observable.get(&(sat_id, first.to_string()))?;
+        let second = self.observable.get(&(sat_id, second.to_string()))?;
+        Some(first - second)
+    }
+}
+
+/// Code the broadcast group delay of a constellation refers to: GPS TGD gives L1 P(Y),
+/// Galileo BGD(E1, E5a) E1, BeiDou TGD1 B1I. The broadcast clock plus that term models a
+/// pseudorange on this code, so other codes only need their DSB against it.
+pub fn group_delay_reference(constellation: gnss::Constellation) -> Option<&'static str> {
+    match constellation {
+        gnss::Constellation::Gps | gnss::Constellation::Qzss => Some("C1W"),
+        gnss::Constellation::Galileo => Some("C1C"),
+        gnss::Constellation::BeiDou => Some("C2I"),
+        _ => None,
+    }
+}
+
+/// Code biases the solvers add to their modeled pseudoranges, for observations tracked
+/// on a different code than the broadcast group delay refers to
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBiasCorrection {
+    pub table: Arc<BiasTable>,
+    pub observed: BTreeMap<gnss::Constellation, String>, // Code of the pseudoranges per system
+}
+
+impl CodeBiasCorrection {
+    pub fn new(table: Arc<BiasTable>) -> Self {
+        Self {
+            table,
+            observed: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_code(mut self, constellation: gnss::Constellation, code: &str) -> Self {
+        self.observed.insert(constellation, code.to_string());
+        self
+    }
+
+    /// Bias of a satellite's pseudoranges relative to what the broadcast clock and group
+    /// delay model, m. Zero for satellites or systems the table does not cover.
+    pub fn bias(&self, sat_id: SatId) -> f64 {
+        let Some(observed) = self.observed.get(&sat_id.constellation) else {
+            return 0.0;
+        };
+        let Some(reference) = group_delay_reference(sat_id.constellation) else {
+            return 0.0;
+        };
+        self.table
+            .differential(sat_id, observed, reference)
+            .unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINEX_BIAS: &str = "\
+%=BIA 1.00 COD 2023:165:00000 COD 2023:163:00000 2023:164:00000 R 00000007
++BIAS/SOLUTION
+*BIAS SVN_ PRN STATION__ OBS1 OBS2 BIAS_START____ BIAS_END______ UNIT __ESTIMATED_VALUE____ _STD_DEV___
+ DSB  G063 G01           C1C  C1W  2023:163:00000 2023:164:00000 ns                 -0.7600      0.0066
+ DSB  G061 G02           C1C  C1W  2023:163:00000 2023:164:00000 ns                  1.2345      0.0071
+ DSB  G061 G02           C1W  C2W  2023:163:00000 2023:164:00000 ns                 -3.5000      0.0100
+ OSB  G048 G07           C1C       2023:163:00000 2023:164:00000 ns                  2.0000      0.0100
+ OSB  G048 G07           C1W       2023:163:00000 2023:164:00000 ns                  0.5000      0.0100
+ DSB       G01 ALGO00CAN C1C  C1W  2023:163:00000 2023:164:00000 ns                  9.9900      0.1000
+ OSB  G048 G07           L1C       2023:163:00000 2023:164:00000 cyc                 0.1200      0.0100
+-BIAS/SOLUTION
+%=ENDBIA
+";
+
+    const CODE_DCB: &str = "\
+CODE'S MONTHLY GNSS P1-C1 DCB SOLUTION, YEAR 2023, MONTH 06
+--------------------------------------------------------------------------------
+
+DIFFERENTIAL (P1-C1) CODE BIASES FOR SATELLITES AND RECEIVERS:
+
+PRN / STATION NAME        VALUE (NS)  RMS (NS)
+***   ****************    *****.***   *****.***
+G01                          -0.760      0.010
+G02                           1.234      0.012
+ALGO 40104M002               -2.500      0.050
+";
+
+    fn ns(value: f64) -> f64 {
+        value * 1e-9 * gnss::C_LIGHT
+    }
+
+    #[test]
+    fn sinex_bias_keeps_satellite_code_biases() {
+        let table = BiasTable::from_sinex_bias(SINEX_BIAS).unwrap();
+        let (g01, g02, g07) = (SatId::gps(1), SatId::gps(2), SatId::gps(7));
+        assert_eq!(table.differential(g01, "C1C", "C1W"), Some(ns(-0.76)));
+        assert_eq!(table.differential(g01, "C1W", "C1C"), Some(-ns(-0.76)));
+        assert_eq!(table.differential(g01, "C1C", "C1C"), Some(0.0));
+        // Through the intermediate C1W
+        let via = table.differential(g02, "C1C", "C2W").unwrap();
+        assert!((via - ns(1.2345 - 3.5)).abs() < 1e-12);
+        // From two observable-specific biases
+        let osb = table.differential(g07, "C1C", "C1W").unwrap();
+        assert!((osb - ns(1.5)).abs() < 1e-12);
+        // Receiver and phase entries are skipped, unknown satellites are not covered
+        assert_eq!(table.differential(g07, "L1C", "C1W"), None);
+        assert_eq!(table.differential(SatId::gps(9), "C1C", "C1W"), None);
+    }
+
+    #[test]
+    fn code_dcb_takes_the_codes_from_the_caller() {
+        let table = BiasTable::from_code_dcb(CODE_DCB, "C1W", "C1C").unwrap();
+        assert_eq!(
+            table.differential(SatId::gps(1), "C1W", "C1C"),
+            Some(ns(-0.76))
+        );
+        assert_eq!(
+            table.differential(SatId::gps(2), "C1C", "C1W"),
+            Some(-ns(1.234))
+        );
+
+        let broken = CODE_DCB.replace("-0.760", "x0.760");
+        let err = BiasTable::from_code_dcb(&broken, "C1W", "C1C").unwrap_err();
+        assert_eq!(err.line, 8);
+    }
+
+    #[test]
+    fn correction_is_relative_to_the_group_delay_code() {
+        let table = Arc::new(BiasTable::from_sinex_bias(SINEX_BIAS).unwrap());
+        let correction =
+            CodeBiasCorrection::new(table.clone()).with_code(gnss::Constellation::Gps, "C1C");
+        assert_eq!(correction.bias(SatId::gps(1)), ns(-0.76));
+        // Satellites, systems and codes the table does not cover fall back to no bias
+        assert_eq!(correction.bias(SatId::gps(9)), 0.0);
+        assert_eq!(
+            correction.bias(SatId::new(gnss::Constellation::Galileo, 1)),
+            0.0
+        );
+        let reference = CodeBiasCorrection::new(table).with_code(gnss::Constellation::Gps, "C1W");
+        assert_eq!(reference.bias(SatId::gps(1)), 0.0);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn switching_codes_moves_spp_unless_the_bias_is_applied() {
+        use crate::corrections::Corrections;
+        use crate::positioning::SppOptions;
+        use crate::signal::Signal;
+        use crate::simulation::{NoError, SimulationConfig};
+        use crate::test_support::{constellation, station};
+        use chrono::{TimeZone, Utc};
+
+        let constellation = constellation();
+        let station = station();
+        let truth = [(Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap(), station)];
+        let config = SimulationConfig {
+            elevation_mask: 10.0,
+            ..SimulationConfig::default()
+        };
+        // The simulator models the code the broadcast group delay refers to, P(Y) on L1
+        let epoch = &constellation.simulate_observations(&truth, &config, &mut NoError)[0];
+        let p1 = epoch.pseudoranges(Signal::GpsL1);
+
+        // C1C pseudoranges of the same epoch, with a different DSB on every satellite
+        let mut table = BiasTable::new();
+        let mut c1 = p1.clone();
+        for (k, obs) in c1.iter_mut().enumerate() {
+            let bias = ns(k as f64 - 4.0);
+            table.insert_differential(obs.sat_id, "C1C", "C1W", bias);
+            obs.pseudorange += bias;
+        }
+        let solve = |observations, code_biases| {
+            let options = SppOptions {
+                corrections: Corrections {
+                    code_biases,
+                    ..Corrections::default()
+                },
+                ..SppOptions::default()
+            };
+            constellation
+                .solve_spp(epoch.epoch, observations, &options)
+                .unwrap()
+        };
+        let error =
+            |solution: &crate::positioning::SppSolution| (solution.position - station).norm();
+
+        assert!(error(&solve(&p1, None)) < 1e-3);
+        assert!(error(&solve(&c1, None)) > 1.0);
+        let correction =
+            CodeBiasCorrection::new(Arc::new(table)).with_code(gnss::Constellation::Gps, "C1C");
+        let corrected = solve(&c1, Some(correction));
+        assert!(error(&corrected) < 1e-3, "{}", error(&corrected));
+
+        // A bias common to every satellite only shifts the clock, by exactly that much
+        let common: Vec<_> = p1
+            .iter()
+            .map(|obs| {
+                let mut obs = *obs;
+                obs.pseudorange += ns(2.0);
+                obs
+            })
+            .collect();
+        let shifted = solve(&common, None);
+        let clock_shift = shifted.clock_bias - solve(&p1, None).clock_bias;
+        assert!((clock_shift - 2e-9).abs() < 1e-13, "{}", clock_shift);
+        assert!(error(&shifted) < 1e-3);
+    }
+}
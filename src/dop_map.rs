@@ -0,0 +1,253 @@
+use crate::constellation::Constellation;
+use crate::gnss::LLA;
+use crate::horizon::ElevationMask;
+use crate::positioning::Dop;
+use chrono::{DateTime, Utc};
+use ndarray::Array2;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+const AXIS_TOLERANCE: f64 = 1e-9; // Degrees an axis may overshoot its end by rounding
+
+/// Latitude/longitude grid of a DOP map, degrees, both ends included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSpec {
+    pub latitude: (f64, f64),  // South to north
+    pub longitude: (f64, f64), // West to east
+    pub step: f64,
+    pub altitude: f64, // Of every cell, m above the ellipsoid
+}
+
+impl GridSpec {
+    /// Pole to pole and once around, without repeating the antimeridian
+    pub fn world(step: f64) -> Self {
+        Self::region((-90.0, 90.0), (-180.0, 180.0 - step), step)
+    }
+
+    pub fn region(latitude: (f64, f64), longitude: (f64, f64), step: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+            step,
+            altitude: 0.0,
+        }
+    }
+
+    pub fn altitude(mut self, altitude: f64) -> Self {
+        self.altitude = altitude;
+        self
+    }
+
+    pub fn latitudes(&self) -> Vec<f64> {
+        axis(self.latitude, self.step)
+    }
+
+    pub fn longitudes(&self) -> Vec<f64> {
+        axis(self.longitude, self.step)
+    }
+}
+
+/// DOP over a grid, rows by latitude and columns by longitude
+#[derive(Debug, Clone, PartialEq)]
+pub struct DopGrid {
+    pub latitudes: Vec<f64>,
+    pub longitudes: Vec<f64>,
+    pub altitude: f64,
+    pub step: f64,
+    // Mean of each component over the epochs with at least four satellites in view; None
+    // where no epoch had four
+    pub cells: Array2<Option<Dop>>,
+    pub availability: Array2<f64>, // Share of the epochs with at least four in view
+}
+
+impl DopGrid {
+    pub fn get(&self, row: usize, column: usize) -> Option<Dop> {
+        *self.cells.get((row, column))?
+    }
+
+    /// Position of a cell's center
+    pub fn lla(&self, row: usize, column: usize) -> LLA {
+        LLA::new(self.latitudes[row], self.longitudes[column], self.altitude)
+    }
+
+    /// Every cell with its center, row by row from the south-west corner
+    pub fn iter(&self) -> impl Iterator<Item = (LLA, Option<Dop>, f64)> + '_ {
+        self.cells.indexed_iter().map(|((row, column), dop)| {
+            (
+                self.lla(row, column),
+                *dop,
+                self.availability[[row, column]],
+            )
+        })
+    }
+}
+
+impl Constellation {
+    /// DOP on every cell of the grid, from the satellites above the mask at each epoch,
+    /// averaged over the epochs. Give one epoch for a snapshot or a sampled window for
+    /// coverage. The satellites must be propagated over the epochs. Cells run in parallel
+    /// with the `rayon` feature.
+    pub fn dop_map(
+        &self,
+        epochs: &[DateTime<Utc>],
+        grid: &GridSpec,
+        mask: impl ElevationMask + Sync,
+    ) -> DopGrid {
+        let latitudes = grid.latitudes();
+        let longitudes = grid.longitudes();
+        let shape = (latitudes.len(), longitudes.len());
+        let cell = |index: usize| {
+            let site = LLA::new(
+                latitudes[index / shape.1],
+                longitudes[index % shape.1],
+                grid.altitude,
+            );
+            let dops: Vec<Dop> = epochs
+                .iter()
+                .filter_map(|&epoch| self.dop_at(&site, epoch, &mask))
+                .collect();
+            let availability = match epochs.len() {
+                0 => 0.0,
+                len => dops.len() as f64 / len as f64,
+            };
+            (mean(&dops), availability)
+        };
+        #[cfg(not(feature = "rayon"))]
+        let indices = 0..shape.0 * shape.1;
+        #[cfg(feature = "rayon")]
+        let indices = (0..shape.0 * shape.1).into_par_iter();
+        let (cells, availability): (Vec<Option<Dop>>, Vec<f64>) = indices.map(cell).unzip();
+        DopGrid {
+            latitudes,
+            longitudes,
+            altitude: grid.altitude,
+            step: grid.step,
+            cells: Array2::from_shape_vec(shape, cells).expect("one cell per grid point"),
+            availability: Array2::from_shape_vec(shape, availability)
+                .expect("one cell per grid point"),
+        }
+    }
+}
+
+/// From `start` to `end` in steps, with `end` included if a step lands on it
+fn axis((start, end): (f64, f64), step: f64) -> Vec<f64> {
+    // Not finite for a zero step or NaN bounds, negative for a step pointing away from end
+    let steps = (end - start) / step;
+    if !steps.is_finite() || steps < 0.0 {
+        return vec![start];
+    }
+    let count = (steps + AXIS_TOLERANCE).floor() as usize + 1;
+    (0..count).map(|i| start + i as f64 * step).collect()
+}
+
+fn mean(dops: &[Dop]) -> Option<Dop> {
+    if dops.is_empty() {
+        return None;
+    }
+    let n = dops.len() as f64;
+    let component = |get: fn(&Dop) -> f64| dops.iter().map(get).sum::<f64>() / n;
+    Some(Dop {
+        gdop: component(|dop| dop.gdop),
+        pdop: component(|dop| dop.pdop),
+        hdop: component(|dop| dop.hdop),
+        vdop: component(|dop| dop.vdop),
+        tdop: component(|dop| dop.tdop),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::CsvOptions;
+    use crate::test_support::{hour_start, propagated_hour};
+
+    #[test]
+    fn axes_include_their_ends_but_not_the_antimeridian_twice() {
+        let world = GridSpec::world(60.0);
+        assert_eq!(world.latitudes(), [-90.0, -30.0, 30.0, 90.0]);
+        assert_eq!(
+            world.longitudes(),
+            [-180.0, -120.0, -60.0, 0.0, 60.0, 120.0]
+        );
+        // 0.1 steps that land on the end only by rounding still reach it
+        assert_eq!(
+            GridSpec::region((0.0, 0.3), (0.0, 0.0), 0.1)
+                .latitudes()
+                .len(),
+            4
+        );
+        assert_eq!(
+            GridSpec::region((0.0, 1.0), (0.0, 1.0), 0.0).latitudes(),
+            [0.0]
+        );
+        assert_eq!(
+            GridSpec::region((1.0, 0.0), (0.0, 1.0), 0.5).latitudes(),
+            [1.0]
+        );
+    }
+
+    #[test]
+    fn coarse_grid_matches_cells_computed_alone() {
+        let constellation = propagated_hour();
+        let grid = GridSpec::world(45.0).altitude(100.0);
+        let map = constellation.dop_map(&[hour_start()], &grid, 10.0);
+        assert_eq!(map.cells.dim(), (5, 8));
+        for (lla, dop, availability) in map.iter() {
+            let alone = constellation.dop_at(&lla, hour_start(), 10.0);
+            assert_eq!(dop, alone, "{:?}", lla);
+            assert_eq!(availability, if alone.is_some() { 1.0 } else { 0.0 });
+        }
+        assert!(map.cells.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn windows_average_the_epochs_with_a_fix() {
+        let constellation = propagated_hour();
+        let epochs: Vec<_> = (0..4)
+            .map(|k| hour_start() + chrono::Duration::minutes(15 * k))
+            .collect();
+        let grid = GridSpec::region((10.0, 50.0), (-120.0, -80.0), 20.0);
+        let map = constellation.dop_map(&epochs, &grid, 15.0);
+        for (row, column) in [(0, 0), (1, 2), (2, 1)] {
+            let lla = map.lla(row, column);
+            let dops: Vec<Dop> = epochs
+                .iter()
+                .filter_map(|&epoch| constellation.dop_at(&lla, epoch, 15.0))
+                .collect();
+            let expected = mean(&dops).unwrap();
+            let pdop = map.get(row, column).unwrap().pdop;
+            assert!(
+                (pdop - expected.pdop).abs() < 1e-12,
+                "{} {}",
+                pdop,
+                expected.pdop
+            );
+            assert_eq!(map.availability[[row, column]], dops.len() as f64 / 4.0);
+        }
+    }
+
+    #[test]
+    fn cells_without_four_satellites_are_none() {
+        let constellation = propagated_hour();
+        let grid = GridSpec::region((-10.0, 10.0), (0.0, 20.0), 10.0);
+        let map = constellation.dop_map(&[hour_start()], &grid, 89.0);
+        assert!(map.cells.iter().all(Option::is_none));
+        assert!(map.availability.iter().all(|&share| share == 0.0));
+        assert_eq!(map.get(0, 0), None);
+        assert_eq!(map.get(3, 0), None);
+
+        let mut csv = Vec::new();
+        map.write_csv(&mut csv, &CsvOptions::default()).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert!(lines[1].starts_with("-10"), "{}", lines[1]);
+        assert!(lines[1].contains(",,,,,"), "{}", lines[1]);
+
+        let mut geojson = Vec::new();
+        map.write_geojson(&mut geojson).unwrap();
+        let geojson = String::from_utf8(geojson).unwrap();
+        assert_eq!(geojson.matches("\"Polygon\"").count(), 9);
+        assert_eq!(geojson.matches("\"pdop\":null").count(), 9);
+    }
+}
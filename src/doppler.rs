@@ -0,0 +1,151 @@
+use crate::analysis::velocity_at;
+use crate::gnss::{self, ECEF};
+use crate::satellite::Satellite;
+use chrono::{DateTime, Utc};
+
+/// Predicted Doppler shift in Hz on a carrier, positive when the satellite approaches.
+///
+/// The satellite clock drift (s/s) shifts the transmitted frequency like a range rate.
+/// None if the receiver and satellite positions coincide.
+pub fn predict_doppler(
+    satellite_position: &ECEF,
+    satellite_velocity: &ECEF,
+    satellite_clock_drift: f64,
+    receiver_position: &ECEF,
+    receiver_velocity: &ECEF,
+    carrier_hz: f64,
+) -> Option<f64> {
+    let range_rate = gnss::range_rate(
+        receiver_position,
+        receiver_velocity,
+        satellite_position,
+        satellite_velocity,
+    )?;
+    Some(-(range_rate - gnss::C_LIGHT * satellite_clock_drift) * carrier_hz / gnss::C_LIGHT)
+}
+
+impl Satellite {
+    /// Doppler of every propagated state for a static receiver. Velocities and clock drifts
+    /// are differenced from neighbouring states when they were not propagated; the drift is
+    /// taken as zero without clocks.
+    pub fn doppler_series(&self, receiver: &ECEF, carrier_hz: f64) -> Vec<(DateTime<Utc>, f64)> {
+        let mut series = Vec::with_capacity(self.states.len());
+        for state in self.states.iter() {
+            let idx = state.index();
+            let Some(velocity) = velocity_at(&self.states, idx) else {
+                continue;
+            };
+            let Some(doppler) = predict_doppler(
+                &state.position(),
+                &velocity,
+                self.clock_drift_at(idx),
+                receiver,
+                &ECEF::default(),
+                carrier_hz,
+            ) else {
+                continue;
+            };
+            series.push((state.time_utc(), doppler));
+        }
+        series
+    }
+
+    fn clock_drift_at(&self, idx: usize) -> f64 {
+        let (times, clock_biases) = (self.states.times(), self.states.clock_biases());
+        let before = idx.saturating_sub(1);
+        let after = if idx + 1 < times.len() { idx + 1 } else { idx };
+        match (clock_biases.get(before), clock_biases.get(after)) {
+            (Some(b0), Some(b1)) if times[after] > times[before] => {
+                (b1 - b0) / (times[after] - times[before])
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::epochs;
+    use crate::test_support::station;
+    use crate::test_support::NAV;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn approaching_satellites_are_shifted_up() {
+        let receiver = ECEF::new(gnss::WGS84_A, 0.0, 0.0);
+        let satellite = ECEF::new(2.6e7, 0.0, 0.0);
+        let towards = ECEF::new(-1000.0, 0.0, 0.0);
+        let doppler = |velocity: &ECEF, drift: f64| {
+            predict_doppler(
+                &satellite,
+                velocity,
+                drift,
+                &receiver,
+                &ECEF::default(),
+                1e9,
+            )
+            .unwrap()
+        };
+        assert!((doppler(&towards, 0.0) - 1000.0 * 1e9 / gnss::C_LIGHT).abs() < 1e-9);
+        assert!((doppler(&(towards * -1.0), 0.0) + 1000.0 * 1e9 / gnss::C_LIGHT).abs() < 1e-9);
+        // A fast satellite clock raises the received frequency by the same fraction
+        assert!((doppler(&ECEF::default(), 1e-9) - 1.0).abs() < 1e-9);
+        assert_eq!(
+            predict_doppler(&receiver, &towards, 0.0, &receiver, &towards, 1e9),
+            None
+        );
+    }
+
+    #[test]
+    fn predictions_match_the_simulated_d1c() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut observed: BTreeMap<gnss::SatId, Vec<(DateTime<Utc>, f64)>> = BTreeMap::new();
+        let epochs = epochs();
+        for (epoch, observations) in &epochs {
+            for obs in observations {
+                let entry = observed.entry(obs.sat_id).or_default();
+                entry.push((*epoch, obs.doppler.unwrap()));
+            }
+        }
+        let start = epochs[0].0;
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(30))
+            .with_velocity(true)
+            .with_clock(true);
+
+        let mut differences = Vec::new();
+        for (&sat_id, series) in &observed {
+            let mut satellite = Satellite::builder(sat_id.prn).build();
+            satellite
+                .propagate(
+                    start,
+                    Duration::from_secs(1200),
+                    &config,
+                    nav.records_for_slice(sat_id),
+                )
+                .unwrap();
+            let predicted: BTreeMap<DateTime<Utc>, f64> = satellite
+                .doppler_series(&station(), gnss::L1_FREQUENCY)
+                .into_iter()
+                .collect();
+            for (epoch, doppler) in series {
+                differences.push(doppler - predicted[epoch]);
+            }
+        }
+        // Within a few Hz; what is left is mostly the receiver clock drift of 2 ns/s, common
+        // to every satellite
+        assert!(differences.len() > 400);
+        assert!(
+            differences.iter().all(|d| d.abs() < 5.0),
+            "{:?}",
+            differences
+        );
+        let mean = differences.iter().sum::<f64>() / differences.len() as f64;
+        let drift_hz = -gnss::C_LIGHT * 2e-9 * gnss::L1_FREQUENCY / gnss::C_LIGHT;
+        assert!((mean - drift_hz).abs() < 0.1, "{}", mean);
+        assert!(differences.iter().all(|d| (d - mean).abs() < 0.3));
+    }
+}
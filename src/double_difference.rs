@@ -0,0 +1,456 @@
+use crate::baseline;
+use crate::constellation::Constellation;
+use crate::gnss::{self, GpsTime, SatId, ECEF, LLA};
+use crate::observation::ObservationEpoch;
+use crate::positioning::Weighting;
+use crate::propagator::{BroadcastPropagator, OrbitPropagator};
+use crate::satellite::PropagationConfig;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+use ndarray::Array2;
+use std::collections::BTreeMap;
+
+/// Options for forming double differences
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoubleDifferenceConfig {
+    pub signal: Signal, // GLONASS FDMA signals differ per satellite and are not supported
+    pub elevation_mask: f64, // Degrees, at the base
+    pub code_weighting: Weighting, // Undifferenced code sigma by elevation, m
+    pub phase_weighting: Weighting, // Undifferenced phase sigma by elevation, m
+}
+
+impl Default for DoubleDifferenceConfig {
+    fn default() -> Self {
+        Self {
+            signal: Signal::GpsL1,
+            elevation_mask: 10.0,
+            code_weighting: Weighting::Sine { sigma0: 0.3 },
+            phase_weighting: Weighting::Sine { sigma0: 0.003 },
+        }
+    }
+}
+
+/// Between-satellite difference of rover-minus-base single differences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoubleDifference {
+    pub sat_id: SatId,
+    pub reference: SatId,
+    pub pseudorange: f64, // m
+    pub phase: f64,       // m, including λ times the ambiguity
+    pub ambiguity: f64,   // Tracked float estimate of the DD ambiguity, cycles
+    pub elevation: f64,   // Degrees, at the base
+}
+
+/// Double differences of one epoch. Rows of the covariances follow `differences`; those
+/// sharing a reference satellite are correlated through it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoubleDifferenceEpoch {
+    pub epoch: DateTime<Utc>, // Rover time tag
+    pub references: BTreeMap<gnss::Constellation, SatId>,
+    pub differences: Vec<DoubleDifference>,
+    pub code_covariance: Array2<f64>,  // m²
+    pub phase_covariance: Array2<f64>, // m²
+}
+
+/// Float estimate of one DD ambiguity, relative to the current reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AmbiguityTrack {
+    cycles: f64, // Mean of the phase-minus-code DD
+    epochs: usize,
+}
+
+/// Forms double differences epoch by epoch against a reference satellite per constellation.
+/// The reference is the highest satellite and is kept until it is lost or slips; DD
+/// ambiguities are re-parented onto the new reference when it changes.
+pub struct DoubleDifferencer<'a> {
+    constellation: &'a Constellation,
+    base_position: ECEF,
+    config: DoubleDifferenceConfig,
+    propagators: BTreeMap<SatId, Option<BroadcastPropagator>>,
+    references: BTreeMap<gnss::Constellation, SatId>,
+    ambiguities: BTreeMap<SatId, AmbiguityTrack>,
+}
+
+impl<'a> DoubleDifferencer<'a> {
+    pub fn new(
+        constellation: &'a Constellation,
+        base_position: ECEF,
+        config: DoubleDifferenceConfig,
+    ) -> Self {
+        Self {
+            constellation,
+            base_position,
+            config,
+            propagators: BTreeMap::new(),
+            references: BTreeMap::new(),
+            ambiguities: BTreeMap::new(),
+        }
+    }
+
+    /// Current reference satellite of each constellation
+    pub fn references(&self) -> &BTreeMap<gnss::Constellation, SatId> {
+        &self.references
+    }
+
+    /// Tracked DD ambiguity of a satellite against its reference, cycles
+    pub fn ambiguity(&self, sat_id: SatId) -> Option<f64> {
+        self.ambiguities.get(&sat_id).map(|track| track.cycles)
+    }
+
+    /// Form the double differences of an epoch. Satellites in `slipped` (on either
+    /// receiver) restart their ambiguity; a slipped reference is replaced.
+    pub fn update(
+        &mut self,
+        base: &ObservationEpoch,
+        rover: &ObservationEpoch,
+        slipped: &[SatId],
+    ) -> DoubleDifferenceEpoch {
+        let time = gnss::gps_seconds(base.epoch);
+        let base_lla = self.base_position.to_lla();
+        // Single differences with both code and phase, above the mask
+        let mut singles = Vec::new();
+        for single in baseline::single_differences(rover, base, self.config.signal) {
+            let (Some(pseudorange), Some(phase)) = (single.pseudorange, single.phase) else {
+                continue;
+            };
+            let Some(elevation) = self.elevation(single.sat_id, time, &base_lla) else {
+                continue;
+            };
+            if elevation >= self.config.elevation_mask {
+                singles.push((single.sat_id, pseudorange, phase, elevation));
+            }
+        }
+        for &sat_id in slipped {
+            self.ambiguities.remove(&sat_id);
+        }
+
+        let mut by_system: BTreeMap<gnss::Constellation, Vec<_>> = BTreeMap::new();
+        for single in singles {
+            by_system
+                .entry(single.0.constellation)
+                .or_default()
+                .push(single);
+        }
+        let wavelength = self.config.signal.wavelength();
+        let mut differences = Vec::new();
+        let mut reference_elevations = Vec::new();
+        let mut references = BTreeMap::new();
+        for (system, singles) in by_system {
+            if singles.len() < 2 {
+                continue;
+            }
+            let mut current = self.references.get(&system).copied();
+            if current.is_some_and(|reference| slipped.contains(&reference)) {
+                // A reference slip corrupts every ambiguity formed against it
+                self.ambiguities
+                    .retain(|sat_id, _| sat_id.constellation != system);
+                current = None;
+            }
+            let keep = current.filter(|sat_id| singles.iter().any(|single| single.0 == *sat_id));
+            let reference = match keep {
+                Some(reference) => reference,
+                None => {
+                    let highest = singles
+                        .iter()
+                        .max_by(|a, b| a.3.total_cmp(&b.3))
+                        .map(|single| single.0)
+                        .unwrap();
+                    if let Some(old) = current {
+                        self.reparent(system, old, highest);
+                    }
+                    highest
+                }
+            };
+            references.insert(system, reference);
+            self.references.insert(system, reference);
+            let reference_single = *singles.iter().find(|single| single.0 == reference).unwrap();
+            for &(sat_id, pseudorange, phase, elevation) in &singles {
+                if sat_id == reference {
+                    continue;
+                }
+                let dd_code = pseudorange - reference_single.1;
+                let dd_phase = phase - reference_single.2;
+                let observed = (dd_phase - dd_code) / wavelength;
+                let track = self.ambiguities.entry(sat_id).or_insert(AmbiguityTrack {
+                    cycles: observed,
+                    epochs: 0,
+                });
+                track.epochs += 1;
+                track.cycles += (observed - track.cycles) / track.epochs as f64;
+                differences.push(DoubleDifference {
+                    sat_id,
+                    reference,
+                    pseudorange: dd_code,
+                    phase: dd_phase,
+                    ambiguity: track.cycles,
+                    elevation,
+                });
+                reference_elevations.push(reference_single.3);
+            }
+        }
+
+        let code_covariance = self.covariance(&differences, &reference_elevations, |el| {
+            self.config.code_weighting.sigma(el)
+        });
+        let phase_covariance = self.covariance(&differences, &reference_elevations, |el| {
+            self.config.phase_weighting.sigma(el)
+        });
+        DoubleDifferenceEpoch {
+            epoch: rover.epoch,
+            references,
+            differences,
+            code_covariance,
+            phase_covariance,
+        }
+    }
+
+    /// Move the ambiguities of a constellation from one reference to another:
+    /// N(new, k) = N(old, k) - N(old, new), and the old reference gets -N(old, new).
+    fn reparent(&mut self, system: gnss::Constellation, old: SatId, new: SatId) {
+        let Some(pivot) = self.ambiguities.remove(&new) else {
+            // Nothing links the two references, so every ambiguity starts again
+            self.ambiguities
+                .retain(|sat_id, _| sat_id.constellation != system);
+            return;
+        };
+        for (sat_id, track) in self.ambiguities.iter_mut() {
+            if sat_id.constellation == system {
+                track.cycles -= pivot.cycles;
+                track.epochs = track.epochs.min(pivot.epochs);
+            }
+        }
+        self.ambiguities.insert(
+            old,
+            AmbiguityTrack {
+                cycles: -pivot.cycles,
+                epochs: pivot.epochs,
+            },
+        );
+    }
+
+    /// D C Dᵀ for diagonal single-difference variances 2σ², D subtracting each row's
+    /// reference
+    fn covariance(
+        &self,
+        differences: &[DoubleDifference],
+        reference_elevations: &[f64],
+        sigma: impl Fn(f64) -> f64,
+    ) -> Array2<f64> {
+        let n = differences.len();
+        let mut covariance = Array2::zeros((n, n));
+        for i in 0..n {
+            let reference_variance = 2.0 * sigma(reference_elevations[i]).powi(2);
+            for j in 0..n {
+                if differences[i].reference != differences[j].reference {
+                    continue;
+                }
+                covariance[[i, j]] = reference_variance;
+                if i == j {
+                    covariance[[i, j]] += 2.0 * sigma(differences[i].elevation).powi(2);
+                }
+            }
+        }
+        covariance
+    }
+
+    fn elevation(&mut self, sat_id: SatId, gps_time: f64, base: &LLA) -> Option<f64> {
+        let constellation = self.constellation;
+        let propagator = self.propagators.entry(sat_id).or_insert_with(|| {
+            let config = PropagationConfig::new();
+            BroadcastPropagator::new(constellation.records(sat_id), config).ok()
+        });
+        let state = propagator
+            .as_ref()?
+            .state_at(GpsTime::from_seconds(gps_time))
+            .ok()?;
+        Some(base.aer_to(&state.position).elevation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{ErrorModel, GaussianNoise, NoError, SimulationConfig};
+    use crate::test_support::{constellation, station as base};
+    use chrono::{Duration, TimeZone};
+
+    fn rover() -> ECEF {
+        base() + ECEF::new(35.0, -20.0, 12.0)
+    }
+
+    /// 200 epochs a second apart of a receiver with its own clock and ambiguities
+    fn observe(
+        constellation: &Constellation,
+        position: ECEF,
+        seed: u64,
+        errors: &mut impl ErrorModel,
+    ) -> Vec<ObservationEpoch> {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+        let truth: Vec<_> = (0..200)
+            .map(|k| (start + Duration::seconds(k), position))
+            .collect();
+        let config = SimulationConfig {
+            zenith_ionosphere: 4.0,
+            zenith_troposphere: 2.3,
+            receiver_clock_bias: seed as f64 * 1e-5,
+            ambiguity_seed: seed,
+            ..SimulationConfig::default()
+        };
+        constellation.simulate_observations(&truth, &config, errors)
+    }
+
+    /// Base and rover epochs, noisy on the code and exact. The differential ionosphere over
+    /// the short baseline keeps the exact ambiguities within a hundredth of a cycle of whole numbers.
+    fn sessions(constellation: &Constellation) -> [Vec<ObservationEpoch>; 4] {
+        [
+            observe(
+                constellation,
+                base(),
+                1,
+                &mut GaussianNoise::new(0.3, 0.0, 0.0, 11),
+            ),
+            observe(
+                constellation,
+                rover(),
+                2,
+                &mut GaussianNoise::new(0.3, 0.0, 0.0, 12),
+            ),
+            observe(constellation, base(), 1, &mut NoError),
+            observe(constellation, rover(), 2, &mut NoError),
+        ]
+    }
+
+    #[test]
+    fn exact_double_differences_have_integer_ambiguities() {
+        let constellation = constellation();
+        let [_, _, base_obs, rover_obs] = sessions(&constellation);
+        let config = DoubleDifferenceConfig::default();
+        let mut differencer = DoubleDifferencer::new(&constellation, base(), config.clone());
+        let epoch = differencer.update(&base_obs[0], &rover_obs[0], &[]);
+
+        // The reference is the highest satellite
+        let reference = epoch.references[&gnss::Constellation::Gps];
+        let highest = epoch
+            .differences
+            .iter()
+            .map(|dd| dd.elevation)
+            .fold(f64::MIN, f64::max);
+        let reference_elevation = differencer
+            .elevation(
+                reference,
+                gnss::gps_seconds(base_obs[0].epoch),
+                &base().to_lla(),
+            )
+            .unwrap();
+        assert!(reference_elevation > highest);
+        assert!(epoch.differences.len() >= 6);
+        for dd in &epoch.differences {
+            assert_eq!(dd.reference, reference);
+            assert!(
+                (dd.ambiguity - dd.ambiguity.round()).abs() < 1e-2,
+                "{:?}",
+                dd
+            );
+        }
+
+        // Every row shares the reference's variance; the diagonal adds its own
+        let sigma = |el: f64| config.code_weighting.sigma(el);
+        let shared = 2.0 * sigma(reference_elevation).powi(2);
+        let covariance = &epoch.code_covariance;
+        for (i, dd) in epoch.differences.iter().enumerate() {
+            for j in 0..epoch.differences.len() {
+                let expected = match i == j {
+                    true => shared + 2.0 * sigma(dd.elevation).powi(2),
+                    false => shared,
+                };
+                assert!((covariance[[i, j]] - expected).abs() < 1e-12);
+            }
+        }
+        let ratio = epoch.phase_covariance[[0, 0]] / covariance[[0, 0]];
+        assert!((ratio - 1e-4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn losing_the_reference_reparents_the_ambiguities() {
+        let constellation = constellation();
+        let [base_noisy, rover_noisy, base_exact, rover_exact] = sessions(&constellation);
+        let config = DoubleDifferenceConfig::default();
+        let mut noisy = DoubleDifferencer::new(&constellation, base(), config.clone());
+        let mut exact = DoubleDifferencer::new(&constellation, base(), config);
+
+        let mut old_reference = None;
+        let mut before = BTreeMap::new();
+        for k in 0..200 {
+            let (mut rover_noisy, mut rover_exact) =
+                (rover_noisy[k].clone(), rover_exact[k].clone());
+            // The rover loses the first reference for epochs 100 to 149
+            if let (Some(lost), 100..=149) = (old_reference, k) {
+                rover_noisy.satellites.retain(|sat| sat.sat_id != lost);
+                rover_exact.satellites.retain(|sat| sat.sat_id != lost);
+            }
+            let noisy_epoch = noisy.update(&base_noisy[k], &rover_noisy, &[]);
+            let exact_epoch = exact.update(&base_exact[k], &rover_exact, &[]);
+            let reference = noisy_epoch.references[&gnss::Constellation::Gps];
+            assert_eq!(reference, exact_epoch.references[&gnss::Constellation::Gps]);
+            match k {
+                0 => old_reference = Some(reference),
+                99 => {
+                    for dd in &exact_epoch.differences {
+                        before.insert(dd.sat_id, dd.ambiguity);
+                    }
+                }
+                100 => {
+                    let old = old_reference.unwrap();
+                    assert_ne!(reference, old);
+                    // N(new, k) = N(old, k) - N(old, new)
+                    for dd in &exact_epoch.differences {
+                        let expected = before[&dd.sat_id] - before[&reference];
+                        assert!((dd.ambiguity - expected).abs() < 1e-2, "{:?}", dd);
+                    }
+                    // The averages carry over rather than starting again from one noisy epoch
+                    for (dd, truth) in noisy_epoch.differences.iter().zip(&exact_epoch.differences)
+                    {
+                        assert!((dd.ambiguity - truth.ambiguity).abs() < 1.0, "{:?}", dd);
+                    }
+                }
+                150 => {
+                    // The old reference returns as an ordinary satellite, N(new, old) = -N(old, new)
+                    let old = old_reference.unwrap();
+                    assert_ne!(reference, old);
+                    let returned = exact_epoch.differences.iter().find(|dd| dd.sat_id == old);
+                    assert!((returned.unwrap().ambiguity + before[&reference]).abs() < 1e-2);
+                    assert!((noisy.ambiguity(old).unwrap() + before[&reference]).abs() < 1.0);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn a_reference_slip_restarts_its_system() {
+        let constellation = constellation();
+        let [base_obs, rover_obs, _, _] = sessions(&constellation);
+        let mut differencer =
+            DoubleDifferencer::new(&constellation, base(), DoubleDifferenceConfig::default());
+        // A restarted ambiguity is the phase-minus-code of its epoch alone
+        let wavelength = Signal::GpsL1.wavelength();
+        let fresh = |dd: &DoubleDifference| (dd.phase - dd.pseudorange) / wavelength;
+        let first = differencer.update(&base_obs[0], &rover_obs[0], &[]);
+        let reference = first.references[&gnss::Constellation::Gps];
+        let other = first.differences[0].sat_id;
+
+        // A slip on an ordinary satellite restarts only its own ambiguity
+        let epoch = differencer.update(&base_obs[1], &rover_obs[1], &[other]);
+        assert_eq!(epoch.references[&gnss::Constellation::Gps], reference);
+        for dd in &epoch.differences {
+            assert_eq!(dd.ambiguity == fresh(dd), dd.sat_id == other, "{:?}", dd);
+        }
+        // A slip on the reference restarts every ambiguity formed against it
+        let epoch = differencer.update(&base_obs[2], &rover_obs[2], &[reference]);
+        for dd in &epoch.differences {
+            assert_eq!(dd.ambiguity, fresh(dd));
+        }
+        let epoch = differencer.update(&base_obs[3], &rover_obs[3], &[]);
+        assert!(epoch.differences.iter().all(|dd| dd.ambiguity != fresh(dd)));
+    }
+}
@@ -0,0 +1,540 @@
+//! Daily broadcast ephemeris (BRDC) files from public IGS data centers: downloaded over
+//! HTTPS, decompressed and cached by date, so each day is fetched once and the returned
+//! path goes straight to `RinexNav::from_file`.
+//!
+//! A day's merged file is only complete once the day is over; the copy fetched during
+//! the day stays cached, so delete it to pick up the final version.
+
+use crate::ntrip::base64;
+use chrono::{Datelike, NaiveDate};
+use flate2::read::MultiGzDecoder;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const EARTHDATA_LOGIN_HOST: &str = "urs.earthdata.nasa.gov";
+const MAX_REDIRECTS: usize = 10;
+const MAX_DOWNLOAD_LEN: u64 = 64 << 20; // Bytes; a daily multi-GNSS BRDC file is a few MB
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const COMPRESS_MAGIC: [u8; 2] = [0x1f, 0x9d]; // Unix compress (.Z)
+const RINEX_LABEL: &str = "RINEX VERSION / TYPE";
+
+/// Data center and naming convention of the daily BRDC file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrdcSource {
+    Bkg,   // BKG, Frankfurt: BRDC00WRD, merged by BKG
+    Ign,   // IGN, Paris: the IGS BRDC00IGS file
+    Cddis, // NASA CDDIS: the IGS BRDC00IGS file, needs Earthdata credentials
+    // URL with {yyyy}, {yy} and {ddd} (day of year) filled in from the date
+    Custom { url_template: String },
+}
+
+impl BrdcSource {
+    /// Download URL of the file for a day
+    pub fn url(&self, date: NaiveDate) -> String {
+        let template = match self {
+            Self::Bkg => {
+                "https://igs.bkg.bund.de/root_ftp/IGS/BRDC/{yyyy}/{ddd}/\
+                          BRDC00WRD_R_{yyyy}{ddd}0000_01D_MN.rnx.gz"
+            }
+            Self::Ign => {
+                "https://igs.ign.fr/pub/igs/data/{yyyy}/{ddd}/\
+                          BRDC00IGS_R_{yyyy}{ddd}0000_01D_MN.rnx.gz"
+            }
+            Self::Cddis => {
+                "https://cddis.nasa.gov/archive/gnss/data/daily/{yyyy}/brdc/\
+                            BRDC00IGS_R_{yyyy}{ddd}0000_01D_MN.rnx.gz"
+            }
+            Self::Custom { url_template } => url_template,
+        };
+        template
+            .replace("{yyyy}", &format!("{:04}", date.year()))
+            .replace("{yy}", &format!("{:02}", date.year().rem_euclid(100)))
+            .replace("{ddd}", &format!("{:03}", date.ordinal()))
+    }
+
+    /// Name of the decompressed file in the cache, the URL's last segment without
+    /// its compression suffix
+    pub fn filename(&self, date: NaiveDate) -> String {
+        let url = self.url(date);
+        let name = url.rsplit('/').next().unwrap_or_default();
+        [".gz", ".Z"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+            .unwrap_or(name)
+            .to_string()
+    }
+
+    /// Cached file of a day: `<cache_dir>/<yyyy>/<ddd>/<filename>`. IGN and CDDIS mirror the
+    /// same IGS file, so they share one entry.
+    pub fn cache_path(&self, date: NaiveDate, cache_dir: &Path) -> PathBuf {
+        cache_dir
+            .join(format!("{:04}", date.year()))
+            .join(format!("{:03}", date.ordinal()))
+            .join(self.filename(date))
+    }
+}
+
+/// Login sent as basic auth, only over HTTPS and only to `host`
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub host: String,
+    pub user: String,
+    pub password: String,
+}
+
+impl Credentials {
+    /// NASA Earthdata login, which CDDIS redirects to
+    pub fn earthdata(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            host: EARTHDATA_LOGIN_HOST.to_string(),
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+}
+
+/// Keeps the password out of logs
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("host", &self.host)
+            .field("user", &self.user)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadConfig {
+    pub credentials: Option<Credentials>,
+    pub timeout: Duration, // Per request, connect through the last byte
+    pub user_agent: String,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            credentials: None,
+            timeout: Duration::from_secs(60),
+            user_agent: format!("pnt_rust/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl DownloadConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Why a BRDC file could not be provided
+#[derive(Debug)]
+pub enum DownloadError {
+    Io(io::Error),     // Reading the download or writing the cache
+    Transport(String), // DNS, connection or TLS failure
+    Status { url: String, code: u16 },
+    TooManyRedirects { url: String },
+    UnsupportedCompression,   // Unix compress (.Z) files are not decoded
+    NotRinex { url: String }, // Decompressed content is no RINEX file, e.g. a login page
+}
+
+impl DownloadError {
+    /// Whether the server denied access, so credentials are missing or wrong
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(
+            self,
+            Self::Status {
+                code: 401 | 403,
+                ..
+            }
+        )
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "download I/O error: {}", error),
+            Self::Transport(error) => write!(f, "download failed: {}", error),
+            Self::Status { url, code } => write!(f, "{} answered HTTP {}", url, code),
+            Self::TooManyRedirects { url } => write!(f, "too many redirects from {}", url),
+            Self::UnsupportedCompression => write!(f, "Unix compress (.Z) files are not supported"),
+            Self::NotRinex { url } => write!(f, "{} did not return a RINEX file", url),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Body of a URL. `HttpFetcher` goes to the network; other implementations can serve
+/// files from elsewhere.
+pub trait Fetch {
+    fn fetch(&self, url: &str, config: &DownloadConfig) -> Result<Vec<u8>, DownloadError>;
+}
+
+/// HTTPS client that follows redirects itself, so credentials reach only their host while
+/// the session cookies of a login round trip are kept
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpFetcher;
+
+impl Fetch for HttpFetcher {
+    fn fetch(&self, url: &str, config: &DownloadConfig) -> Result<Vec<u8>, DownloadError> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(config.timeout)
+            .redirects(0)
+            .user_agent(&config.user_agent)
+            .build();
+        let mut current = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let mut request = agent.get(&current);
+            if let Some(credentials) = config
+                .credentials
+                .as_ref()
+                .filter(|credentials| https_host(&current) == Some(credentials.host.as_str()))
+            {
+                let token =
+                    base64(format!("{}:{}", credentials.user, credentials.password).as_bytes());
+                request = request.set("Authorization", &format!("Basic {}", token));
+            }
+            let response = match request.call() {
+                Ok(response) => response,
+                Err(ureq::Error::Status(code, _)) => {
+                    return Err(DownloadError::Status { url: current, code })
+                }
+                Err(error) => return Err(DownloadError::Transport(error.to_string())),
+            };
+            if (300..400).contains(&response.status()) {
+                let location =
+                    response
+                        .header("location")
+                        .ok_or_else(|| DownloadError::Status {
+                            url: current.clone(),
+                            code: response.status(),
+                        })?;
+                current = resolve_location(&current, location);
+                continue;
+            }
+            let mut body = Vec::new();
+            response
+                .into_reader()
+                .take(MAX_DOWNLOAD_LEN)
+                .read_to_end(&mut body)?;
+            return Ok(body);
+        }
+        Err(DownloadError::TooManyRedirects {
+            url: url.to_string(),
+        })
+    }
+}
+
+/// Local path of a day's BRDC file, downloaded on the first call and read from the cache
+/// after
+pub fn fetch_brdc(
+    date: NaiveDate,
+    source: &BrdcSource,
+    cache_dir: &Path,
+) -> Result<PathBuf, DownloadError> {
+    fetch_brdc_with(
+        date,
+        source,
+        cache_dir,
+        &DownloadConfig::default(),
+        &HttpFetcher,
+    )
+}
+
+/// `fetch_brdc` with credentials or a timeout, through any fetcher
+pub fn fetch_brdc_with(
+    date: NaiveDate,
+    source: &BrdcSource,
+    cache_dir: &Path,
+    config: &DownloadConfig,
+    fetcher: &impl Fetch,
+) -> Result<PathBuf, DownloadError> {
+    let path = source.cache_path(date, cache_dir);
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > 0) {
+        return Ok(path);
+    }
+    let url = source.url(date);
+    let text = decompress(fetcher.fetch(&url, config)?)?;
+    // An HTML login or error page served with status 200 must not land in the cache
+    let first_line = text.split(|&byte| byte == b'\n').next().unwrap_or_default();
+    if !String::from_utf8_lossy(first_line).contains(RINEX_LABEL) {
+        return Err(DownloadError::NotRinex { url });
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Written aside and renamed, so an interrupted download never looks cached
+    let partial = path.with_extension("part");
+    fs::write(&partial, &text)?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Gzip content decoded, plain text passed through
+fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, DownloadError> {
+    match bytes.get(..2) {
+        Some(magic) if magic == GZIP_MAGIC => {
+            let mut text = Vec::new();
+            MultiGzDecoder::new(bytes.as_slice()).read_to_end(&mut text)?;
+            Ok(text)
+        }
+        Some(magic) if magic == COMPRESS_MAGIC => Err(DownloadError::UnsupportedCompression),
+        _ => Ok(bytes),
+    }
+}
+
+/// Host of an https URL; None for any other scheme
+fn https_host(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("https://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+    host_port.split(':').next()
+}
+
+/// Redirect target as an absolute URL
+fn resolve_location(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let scheme_end = base.find("://").map_or(0, |index| index + 3);
+    let origin_end = base[scheme_end..]
+        .find('/')
+        .map_or(base.len(), |index| scheme_end + index);
+    match location.strip_prefix('/') {
+        Some(path) => format!("{}/{}", &base[..origin_end], path),
+        None => {
+            let directory_end = base[origin_end..]
+                .rfind('/')
+                .map_or(base.len(), |index| origin_end + index);
+            format!("{}/{}", &base[..directory_end], location)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::NAV;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::cell::RefCell;
+    use std::io::Write;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 6, 12).unwrap()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Serves one canned body and records the URLs asked for
+    struct MockFetcher {
+        body: Result<Vec<u8>, u16>,
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl MockFetcher {
+        fn new(body: Result<Vec<u8>, u16>) -> Self {
+            Self {
+                body,
+                requests: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.borrow().clone()
+        }
+    }
+
+    impl Fetch for MockFetcher {
+        fn fetch(&self, url: &str, _: &DownloadConfig) -> Result<Vec<u8>, DownloadError> {
+            self.requests.borrow_mut().push(url.to_string());
+            self.body.clone().map_err(|code| DownloadError::Status {
+                url: url.to_string(),
+                code,
+            })
+        }
+    }
+
+    fn fetch(fetcher: &MockFetcher, cache_dir: &Path) -> Result<PathBuf, DownloadError> {
+        fetch_brdc_with(
+            date(),
+            &BrdcSource::Ign,
+            cache_dir,
+            &DownloadConfig::new(),
+            fetcher,
+        )
+    }
+
+    #[test]
+    fn urls_and_filenames_follow_each_centers_convention() {
+        // Day 163 of 2023
+        assert_eq!(
+            BrdcSource::Bkg.url(date()),
+            "https://igs.bkg.bund.de/root_ftp/IGS/BRDC/2023/163/\
+             BRDC00WRD_R_20231630000_01D_MN.rnx.gz"
+        );
+        assert_eq!(
+            BrdcSource::Ign.url(date()),
+            "https://igs.ign.fr/pub/igs/data/2023/163/BRDC00IGS_R_20231630000_01D_MN.rnx.gz"
+        );
+        assert_eq!(
+            BrdcSource::Cddis.url(date()),
+            "https://cddis.nasa.gov/archive/gnss/data/daily/2023/brdc/\
+             BRDC00IGS_R_20231630000_01D_MN.rnx.gz"
+        );
+        let legacy = BrdcSource::Custom {
+            url_template: "https://example.org/{yyyy}/{ddd}/brdc{ddd}0.{yy}n.Z".to_string(),
+        };
+        let new_year = NaiveDate::from_ymd_opt(2009, 1, 2).unwrap();
+        assert_eq!(
+            legacy.url(new_year),
+            "https://example.org/2009/002/brdc0020.09n.Z"
+        );
+
+        assert_eq!(legacy.filename(new_year), "brdc0020.09n");
+        assert_eq!(
+            BrdcSource::Bkg.filename(date()),
+            "BRDC00WRD_R_20231630000_01D_MN.rnx"
+        );
+        let cache = Path::new("/cache");
+        assert_eq!(
+            BrdcSource::Cddis.cache_path(date(), cache),
+            Path::new("/cache/2023/163/BRDC00IGS_R_20231630000_01D_MN.rnx")
+        );
+        assert_eq!(
+            BrdcSource::Ign.cache_path(date(), cache),
+            BrdcSource::Cddis.cache_path(date(), cache)
+        );
+    }
+
+    #[test]
+    fn downloads_are_decompressed_and_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let fetcher = MockFetcher::new(Ok(gzip(NAV.as_bytes())));
+        let path = fetch(&fetcher, dir.path()).unwrap();
+        assert_eq!(path, BrdcSource::Ign.cache_path(date(), dir.path()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), NAV);
+        assert!(!path.with_extension("part").exists());
+        assert_eq!(fetcher.requests(), [BrdcSource::Ign.url(date())]);
+
+        // Served from the cache, also for the mirror of the same file
+        assert_eq!(fetch(&fetcher, dir.path()).unwrap(), path);
+        let cddis = fetch_brdc_with(
+            date(),
+            &BrdcSource::Cddis,
+            dir.path(),
+            &DownloadConfig::new(),
+            &fetcher,
+        );
+        assert_eq!(cddis.unwrap(), path);
+        assert_eq!(fetcher.requests().len(), 1);
+
+        // An empty file, as a full disk leaves it, is fetched again
+        fs::write(&path, "").unwrap();
+        let plain = MockFetcher::new(Ok(NAV.as_bytes().to_vec()));
+        fetch(&plain, dir.path()).unwrap();
+        assert_eq!(plain.requests().len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), NAV);
+    }
+
+    #[test]
+    fn failures_leave_nothing_in_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = BrdcSource::Ign.cache_path(date(), dir.path());
+
+        let login_page = MockFetcher::new(Ok(b"<!DOCTYPE html><html>Earthdata Login".to_vec()));
+        let error = fetch(&login_page, dir.path()).unwrap_err();
+        assert!(matches!(error, DownloadError::NotRinex { .. }), "{}", error);
+
+        let compressed = MockFetcher::new(Ok(vec![0x1f, 0x9d, 0x90, 0x00]));
+        let error = fetch(&compressed, dir.path()).unwrap_err();
+        assert!(matches!(error, DownloadError::UnsupportedCompression));
+
+        let denied = MockFetcher::new(Err(401));
+        let error = fetch(&denied, dir.path()).unwrap_err();
+        assert!(error.is_unauthorized());
+        assert!(!MockFetcher::new(Err(404))
+            .fetch("https://x/", &DownloadConfig::new())
+            .unwrap_err()
+            .is_unauthorized());
+
+        assert!(!path.exists() && !path.with_extension("part").exists());
+    }
+
+    #[test]
+    fn credentials_stay_with_their_host_and_out_of_logs() {
+        let credentials = Credentials::earthdata("user", "hunter2");
+        let config = DownloadConfig::new().credentials(credentials);
+        let printed = format!("{:?}", config);
+        assert!(printed.contains("urs.earthdata.nasa.gov") && printed.contains("\"user\""));
+        assert!(!printed.contains("hunter2"), "{}", printed);
+
+        assert_eq!(
+            https_host("https://urs.earthdata.nasa.gov/oauth/authorize?x=1"),
+            Some(EARTHDATA_LOGIN_HOST)
+        );
+        assert_eq!(
+            https_host("https://name@urs.earthdata.nasa.gov:443/"),
+            Some(EARTHDATA_LOGIN_HOST)
+        );
+        assert_eq!(https_host("http://urs.earthdata.nasa.gov/"), None);
+    }
+
+    #[test]
+    fn redirects_resolve_against_the_current_url() {
+        let base = "https://cddis.nasa.gov/archive/gnss/data/file.gz";
+        assert_eq!(
+            resolve_location(base, "https://urs.earthdata.nasa.gov/login"),
+            "https://urs.earthdata.nasa.gov/login"
+        );
+        assert_eq!(
+            resolve_location(base, "/login?next=1"),
+            "https://cddis.nasa.gov/login?next=1"
+        );
+        assert_eq!(
+            resolve_location(base, "other.gz"),
+            "https://cddis.nasa.gov/archive/gnss/data/other.gz"
+        );
+    }
+
+    #[test]
+    #[ignore = "downloads from BKG"]
+    fn fetches_a_day_from_bkg() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fetch_brdc(date(), &BrdcSource::Bkg, dir.path()).unwrap();
+        let nav = crate::gnss::RinexNav::from_file(path.to_str().unwrap()).unwrap();
+        assert!(nav.satellites().count() > 100);
+    }
+}
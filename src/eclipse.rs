@@ -0,0 +1,191 @@
+use crate::celestial::sun_position;
+use crate::gnss::{self, ECEF};
+use crate::satellite::Satellite;
+use chrono::{DateTime, Utc};
+
+const SUN_RADIUS: f64 = 696_000_000.0; // m
+const EARTH_RADIUS: f64 = gnss::WGS84_A;
+
+/// Illumination of a satellite by the Sun, from the conical Earth-shadow model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shadow {
+    Sunlit,
+    Penumbra(f64), // Fraction of the solar disc still visible
+    Umbra,
+}
+
+impl Shadow {
+    pub fn is_eclipsed(&self) -> bool {
+        !matches!(self, Self::Sunlit)
+    }
+
+    pub fn sunlight_fraction(&self) -> f64 {
+        match self {
+            Self::Sunlit => 1.0,
+            Self::Penumbra(fraction) => *fraction,
+            Self::Umbra => 0.0,
+        }
+    }
+}
+
+/// A contiguous pass through the Earth's shadow
+#[derive(Debug, Clone, PartialEq)]
+pub struct EclipseInterval {
+    pub entry: DateTime<Utc>,
+    pub exit: DateTime<Utc>,
+    pub umbra_seconds: f64, // Part of the interval spent in full shadow
+}
+
+impl EclipseInterval {
+    pub fn duration_seconds(&self) -> f64 {
+        (self.exit - self.entry).num_milliseconds() as f64 / 1000.0
+    }
+}
+
+/// Shadow state of a satellite at an ECEF position and epoch
+pub fn shadow(position: &ECEF, epoch: DateTime<Utc>) -> Shadow {
+    shadow_from_sun(position, &sun_position(gnss::gps_seconds(epoch)))
+}
+
+fn shadow_from_sun(position: &ECEF, sun: &ECEF) -> Shadow {
+    let to_sun = *sun - *position;
+    let sun_distance = to_sun.norm();
+    let sat_distance = position.norm();
+    // Apparent radii of the Sun and Earth and their separation, seen from the satellite
+    let a = (SUN_RADIUS / sun_distance).asin();
+    let b = (EARTH_RADIUS / sat_distance).asin();
+    let c = (-position.dot(&to_sun) / (sat_distance * sun_distance))
+        .clamp(-1.0, 1.0)
+        .acos();
+
+    if c >= a + b {
+        Shadow::Sunlit
+    } else if c <= b - a {
+        Shadow::Umbra
+    } else if c <= a - b {
+        Shadow::Penumbra(1.0 - (b * b) / (a * a))
+    } else {
+        // Area of the overlapping discs
+        let x = (c * c + a * a - b * b) / (2.0 * c);
+        let y = (a * a - x * x).max(0.0).sqrt();
+        let overlap = a * a * (x / a).acos() + b * b * ((c - x) / b).acos() - c * y;
+        Shadow::Penumbra(1.0 - overlap / (std::f64::consts::PI * a * a))
+    }
+}
+
+impl Satellite {
+    /// Shadow state of every propagated state
+    pub fn shadow_series(&self) -> Vec<(DateTime<Utc>, Shadow)> {
+        let (times, positions) = (self.states.times(), self.states.positions());
+        times
+            .iter()
+            .zip(positions)
+            .map(|(&time, position)| {
+                let epoch = gnss::gps_seconds_to_utc(time);
+                (epoch, shadow(position, epoch))
+            })
+            .collect()
+    }
+
+    /// Entry/exit times of each shadow pass, resolved to the propagation step
+    pub fn eclipse_intervals(&self) -> Vec<EclipseInterval> {
+        let mut intervals = Vec::new();
+        let mut current: Option<EclipseInterval> = None;
+        let mut last_epoch = None;
+        for (idx, (epoch, shadow)) in self.shadow_series().into_iter().enumerate() {
+            if shadow.is_eclipsed() {
+                let interval = current.get_or_insert(EclipseInterval {
+                    entry: epoch,
+                    exit: epoch,
+                    umbra_seconds: 0.0,
+                });
+                if shadow == Shadow::Umbra && idx > 0 {
+                    let step = self.states.times()[idx] - self.states.times()[idx - 1];
+                    interval.umbra_seconds += step;
+                }
+                interval.exit = epoch;
+            } else if let Some(interval) = current.take() {
+                intervals.push(EclipseInterval {
+                    exit: epoch,
+                    ..interval
+                });
+            }
+            last_epoch = Some(epoch);
+        }
+        if let (Some(interval), Some(epoch)) = (current, last_epoch) {
+            intervals.push(EclipseInterval {
+                exit: epoch,
+                ..interval
+            });
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::NAV;
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    /// Shadow passes over the fixture's day, 2023-06-12, in the eclipse season of G20's plane
+    fn intervals(prn: u8) -> Vec<EclipseInterval> {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let config = PropagationConfig::new().step(Duration::from_secs(30));
+        let mut satellite = Satellite::builder(prn).build();
+        let records = nav.records_for_slice(prn.into());
+        satellite
+            .propagate(start, Duration::from_secs(86400), &config, records)
+            .unwrap();
+        satellite.eclipse_intervals()
+    }
+
+    #[test]
+    fn eclipse_season_passes_last_about_55_minutes() {
+        let passes = intervals(20);
+        // Cut by the start and end of the day, with one whole pass between
+        assert_eq!(passes.len(), 3);
+        let whole = &passes[1];
+        assert!((whole.duration_seconds() / 60.0 - 55.0).abs() < 3.0);
+        // A minute or two of penumbra around the umbra
+        let penumbra = whole.duration_seconds() - whole.umbra_seconds;
+        assert!(penumbra > 0.0 && penumbra < 300.0);
+        // Once per orbit, half a sidereal day apart
+        let orbit = (passes[2].entry - whole.entry).num_seconds() as f64;
+        assert!((orbit - 43_082.0).abs() < 120.0);
+    }
+
+    #[test]
+    fn satellites_outside_their_season_stay_sunlit() {
+        assert!(intervals(1).is_empty());
+        // A grazing pass only reaches the penumbra
+        let grazing = intervals(16);
+        assert_eq!(grazing.len(), 1);
+        assert_eq!(grazing[0].umbra_seconds, 0.0);
+    }
+
+    #[test]
+    fn shadow_geometry() {
+        let sun = ECEF::new(1.496e11, 0.0, 0.0);
+        let radius = 26_560e3;
+        assert_eq!(
+            shadow_from_sun(&ECEF::new(radius, 0.0, 0.0), &sun),
+            Shadow::Sunlit
+        );
+        assert_eq!(
+            shadow_from_sun(&ECEF::new(-radius, 0.0, 0.0), &sun),
+            Shadow::Umbra
+        );
+        assert_eq!(
+            shadow_from_sun(&ECEF::new(0.0, radius, 0.0), &sun),
+            Shadow::Sunlit
+        );
+        // At the edge of the Earth's disc the Sun is about half covered
+        let edge = ECEF::new(-radius, EARTH_RADIUS, 0.0);
+        let fraction = shadow_from_sun(&edge, &sun).sunlight_fraction();
+        assert!(fraction > 0.3 && fraction < 0.7, "{}", fraction);
+    }
+}
@@ -0,0 +1,341 @@
+//! Scalar broadcast-ephemeris evaluation for a single epoch, without allocation, so it
+//! also builds for no_std targets. `Satellite::propagate` is the vectorized std path.
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::F64Ext;
+use crate::gnss::{GpsTime, NavRecord, ECEF, REL_F};
+use crate::units::Seconds;
+use core::f64::consts::PI;
+
+const KEPLER_TOLERANCE: f64 = 1e-12; // Radians, as in `PropagationConfig::default`
+const KEPLER_MAX_ITER: u32 = 30;
+const BEIDOU_GEO_TILT: f64 = -5.0 * PI / 180.0; // About x, from the GEO frame to ECEF
+
+/// Satellite position, velocity and clock offset at one epoch
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EphemerisState {
+    pub position: ECEF,
+    pub velocity: ECEF,
+    pub clock_bias: Seconds, // Polynomial plus relativistic term, TGD not applied
+    pub kepler_converged: bool,
+}
+
+/// Solve Kepler's equation E - e*sin(E) = M with Newton-Raphson, returning
+/// (E, iterations, converged)
+pub fn solve_kepler(m: f64, e: f64, tolerance: f64, max_iter: u32) -> (f64, u32, bool) {
+    // Start from M + e*sin(M), or from pi for highly eccentric orbits
+    let m_wrapped = (m + PI).rem_euclid(2.0 * PI) - PI;
+    let mut e_val = if e < 0.8 {
+        m + e * m.sin()
+    } else {
+        m - m_wrapped + PI.copysign(m_wrapped)
+    };
+    for iteration in 1..=max_iter {
+        let delta = (e_val - e * e_val.sin() - m) / (1.0 - e * e_val.cos());
+        e_val -= delta;
+        if delta.abs() < tolerance {
+            return (e_val, iteration, true);
+        }
+    }
+    (e_val, max_iter, false)
+}
+
+/// A record with the constants of the broadcast model it implies, derived once so that
+/// evaluating it at many epochs repeats only the per-epoch work
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreparedEphemeris {
+    pub record: NavRecord,
+    pub a: f64,               // Semi-major axis, m
+    pub n: f64,               // Corrected mean motion, rad/s
+    pub sqrt_1_minus_e2: f64, // sqrt(1 - e^2)
+    pub omega_rate: f64,      // Rate of the node in ECEF, or inertially for BeiDou GEO, rad/s
+    pub earth_rotation: f64,  // Of the system's model, rad/s
+    pub geo: bool,            // BeiDou GEO, rotated into ECEF at the end
+    pub toe_gps: GpsTime,
+    pub toc_gps: GpsTime,
+}
+
+impl PreparedEphemeris {
+    pub fn new(record: &NavRecord) -> Self {
+        let a = record.sqrt_a * record.sqrt_a;
+        let e = record.eccentricity;
+        let system = record.sat_id.constellation;
+        let earth_rotation = system.earth_rotation_rate();
+        let geo = record.sat_id.is_beidou_geo();
+        Self {
+            record: *record,
+            a,
+            n: (system.gravitational_constant() / a.powi(3)).sqrt() + record.delta_n,
+            sqrt_1_minus_e2: (1.0 - e * e).sqrt(),
+            omega_rate: match geo {
+                true => record.omega_dot,
+                false => record.omega_dot - earth_rotation,
+            },
+            earth_rotation,
+            geo,
+            toe_gps: record.toe_epoch(),
+            toc_gps: record.toc_epoch(),
+        }
+    }
+
+    pub fn position_at(&self, gps_time: GpsTime) -> ECEF {
+        self.evaluate(gps_time, KEPLER_TOLERANCE, KEPLER_MAX_ITER)
+            .position
+    }
+
+    /// Position, velocity and clock at a GPS time, the same model as
+    /// `Satellite::propagate` evaluated for one epoch
+    pub fn evaluate(&self, gps_time: GpsTime, tolerance: f64, max_iter: u32) -> EphemerisState {
+        let record = &self.record;
+        let (a, n) = (self.a, self.n);
+        let e = record.eccentricity;
+        // From the toe in GPS time: a BeiDou toe counts BDT seconds, 14 s behind
+        let tk = (gps_time - self.toe_gps).as_f64();
+        let m = record.m0 + n * tk;
+        let (e_anomaly, _, converged) = solve_kepler(m, e, tolerance, max_iter);
+
+        let (sin_e, cos_e) = (e_anomaly.sin(), e_anomaly.cos());
+        let sqrt_1_minus_e2 = self.sqrt_1_minus_e2;
+        let nu = (sqrt_1_minus_e2 * sin_e).atan2(cos_e - e);
+        let phi = nu + record.omega;
+        let (sin_2phi, cos_2phi) = ((2.0 * phi).sin(), (2.0 * phi).cos());
+
+        // Second-harmonic corrections
+        let u = phi + record.cus * sin_2phi + record.cuc * cos_2phi;
+        let r = a * (1.0 - e * cos_e) + record.crs * sin_2phi + record.crc * cos_2phi;
+        let i = record.i0 + record.cis * sin_2phi + record.cic * cos_2phi + record.idot * tk;
+
+        let (sin_u, cos_u) = (u.sin(), u.cos());
+        let (x, y) = (r * cos_u, r * sin_u);
+        let omega_rate = self.omega_rate;
+        let omega = record.omega0 + omega_rate * tk - self.earth_rotation * record.toe;
+        let (sin_omega, cos_omega) = (omega.sin(), omega.cos());
+        let (sin_i, cos_i) = (i.sin(), i.cos());
+        let position = ECEF::new(
+            x * cos_omega - y * cos_i * sin_omega,
+            x * sin_omega + y * cos_i * cos_omega,
+            y * sin_i,
+        );
+
+        // Time derivatives of the same terms
+        let one_minus_e_cos_e = 1.0 - e * cos_e;
+        let e_dot = n / one_minus_e_cos_e;
+        let nu_dot = e_dot * sqrt_1_minus_e2 / one_minus_e_cos_e;
+        let u_dot = nu_dot * (1.0 + 2.0 * (record.cus * cos_2phi - record.cuc * sin_2phi));
+        let r_dot =
+            a * e * sin_e * e_dot + 2.0 * (record.crs * cos_2phi - record.crc * sin_2phi) * nu_dot;
+        let i_dot = record.idot + 2.0 * (record.cis * cos_2phi - record.cic * sin_2phi) * nu_dot;
+        let x_dot = r_dot * cos_u - r * u_dot * sin_u;
+        let y_dot = r_dot * sin_u + r * u_dot * cos_u;
+        let velocity = ECEF::new(
+            x_dot * cos_omega - y_dot * cos_i * sin_omega + y * sin_i * sin_omega * i_dot
+                - position.y * omega_rate,
+            x_dot * sin_omega + y_dot * cos_i * cos_omega - y * sin_i * cos_omega * i_dot
+                + position.x * omega_rate,
+            y_dot * sin_i + y * cos_i * i_dot,
+        );
+
+        let (position, velocity) = match self.geo {
+            true => self.geo_to_ecef(position, velocity, tk),
+            false => (position, velocity),
+        };
+
+        let dt = (gps_time - self.toc_gps).as_f64();
+        let clock_bias = record.sv_clock_bias
+            + record.sv_clock_drift * dt
+            + record.sv_clock_drift_rate * dt * dt
+            + REL_F * e * record.sqrt_a * sin_e;
+
+        EphemerisState {
+            position,
+            velocity,
+            clock_bias: Seconds(clock_bias),
+            kepler_converged: converged,
+        }
+    }
+}
+
+impl PreparedEphemeris {
+    /// A BeiDou GEO position and velocity from the inclined frame of its model into ECEF:
+    /// tilted -5 degrees about x, then turned by the earth's rotation since toe
+    pub(crate) fn geo_to_ecef(&self, position: ECEF, velocity: ECEF, tk: f64) -> (ECEF, ECEF) {
+        let (sin_x, cos_x) = (BEIDOU_GEO_TILT.sin(), BEIDOU_GEO_TILT.cos());
+        let (sin_z, cos_z) = (
+            (self.earth_rotation * tk).sin(),
+            (self.earth_rotation * tk).cos(),
+        );
+        let rotate = |v: ECEF| {
+            let (y, z) = (v.y * cos_x + v.z * sin_x, -v.y * sin_x + v.z * cos_x);
+            ECEF::new(v.x * cos_z + y * sin_z, -v.x * sin_z + y * cos_z, z)
+        };
+        let position = rotate(position);
+        let velocity = rotate(velocity)
+            + ECEF::new(
+                self.earth_rotation * position.y,
+                -self.earth_rotation * position.x,
+                0.0,
+            );
+        (position, velocity)
+    }
+}
+
+impl NavRecord {
+    /// The record with its derived constants, for evaluating it at many epochs
+    pub fn prepare(&self) -> PreparedEphemeris {
+        PreparedEphemeris::new(self)
+    }
+
+    pub fn position_at(&self, gps_time: GpsTime) -> ECEF {
+        self.prepare().position_at(gps_time)
+    }
+
+    /// Position, velocity and clock at a GPS time, the same model as
+    /// `Satellite::propagate` evaluated for one epoch
+    pub fn evaluate(&self, gps_time: GpsTime, tolerance: f64, max_iter: u32) -> EphemerisState {
+        self.prepare().evaluate(gps_time, tolerance, max_iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::{Constellation, SatId};
+    use crate::units::GpsSeconds;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn kepler_residual_is_below_1e_12(e in 0.0..=0.97f64, m in -PI..=PI) {
+            let (e_anomaly, iterations, converged) =
+                solve_kepler(m, e, KEPLER_TOLERANCE, KEPLER_MAX_ITER);
+            prop_assert!(converged);
+            prop_assert!(iterations < KEPLER_MAX_ITER);
+            let residual = e_anomaly - e * e_anomaly.sin() - m;
+            prop_assert!(residual.abs() < 1e-12, "residual {:e}", residual);
+        }
+
+        // Half a week from toe puts a GPS mean anomaly about seven revolutions out
+        #[test]
+        fn kepler_solves_mean_anomalies_beyond_one_revolution(
+            e in 0.0..=0.97f64,
+            m in -PI..=PI,
+            revolutions in -10i32..=10,
+        ) {
+            let m = m + 2.0 * PI * revolutions as f64;
+            let (e_anomaly, _, converged) = solve_kepler(m, e, KEPLER_TOLERANCE, KEPLER_MAX_ITER);
+            prop_assert!(converged);
+            let residual = e_anomaly - e * e_anomaly.sin() - m;
+            prop_assert!(residual.abs() < 1e-12, "residual {:e}", residual);
+        }
+    }
+
+    #[test]
+    fn prepared_constants_of_a_gps_record() {
+        let record = NavRecord {
+            sat_id: SatId::new(Constellation::Gps, 17),
+            gps_week: 2266.0,
+            toe: 93_600.0,
+            sqrt_a: 5153.6,
+            eccentricity: 0.6,
+            delta_n: 5e-9,
+            omega_dot: -8e-9,
+            ..Default::default()
+        };
+        let prepared = record.prepare();
+        assert_eq!(prepared.a, 5153.6 * 5153.6);
+        assert_eq!(prepared.sqrt_1_minus_e2, 0.8);
+        assert_eq!(prepared.omega_rate, -8e-9 - crate::gnss::OMEGA_E_DOT);
+        assert_eq!(prepared.toe_gps, GpsTime::from_week_seconds(2266, 93_600.0));
+        assert!(!prepared.geo);
+        // Two revolutions a sidereal day
+        let period = 2.0 * PI / (prepared.n - record.delta_n);
+        assert!((period - 43_082.0).abs() < 30.0, "{} s", period);
+
+        // The record evaluated through its prepared constants, as each call of the record's
+        // own methods does
+        for hours in -4..=4 {
+            let time = prepared.toe_gps + GpsSeconds(hours as f64 * 1800.0);
+            let state = prepared.evaluate(time, KEPLER_TOLERANCE, KEPLER_MAX_ITER);
+            assert_eq!(
+                state,
+                record.evaluate(time, KEPLER_TOLERANCE, KEPLER_MAX_ITER)
+            );
+            assert_eq!(prepared.position_at(time), record.position_at(time));
+        }
+    }
+
+    /// A circular BeiDou MEO orbit with toe at 96 h into BDT week 910, GPS week 2266
+    fn beidou_record() -> NavRecord {
+        NavRecord {
+            sat_id: SatId::new(Constellation::BeiDou, 20),
+            gps_week: 910.0,
+            toe: 345_600.0,
+            sqrt_a: 5282.6,
+            i0: 0.96,
+            omega0: 1.0,
+            omega: 0.2,
+            m0: 0.3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn beidou_is_evaluated_from_its_toe_in_gps_time() {
+        let record = beidou_record();
+        let toe = GpsTime::from_week_seconds(2266, 345_614.0);
+        assert_eq!(record.prepare().toe_gps, toe);
+
+        // At toe: E = M = m0, u = omega + m0 = 0.5 rad, r = a, i = i0 and the node
+        // omega0 - OMEGA_E_DOT_CGCS2000 * 345600 s
+        let state = record.evaluate(toe, KEPLER_TOLERANCE, KEPLER_MAX_ITER);
+        let expected = ECEF::new(8461075.021477077, 24228711.616697147, 10959786.460320136);
+        assert!(state.position.approx_eq(&expected, 1e-6));
+
+        // 14 s early, as a BDT toe read as GPS time would have it, is tens of km off
+        let early = record.position_at(GpsTime::from_week_seconds(2266, 345_600.0));
+        assert!((early - expected).norm() > 30_000.0);
+    }
+
+    /// A circular orbit inclined 5 degrees in the BeiDou GEO frame, its node placed so the
+    /// tilt into ECEF makes it equatorial and geostationary
+    fn beidou_geo_record() -> NavRecord {
+        NavRecord {
+            sat_id: SatId::new(Constellation::BeiDou, 3),
+            sqrt_a: 6493.4,
+            i0: 5f64.to_radians(),
+            omega0: 3.210400864871449,
+            omega_dot: 0.0,
+            ..beidou_record()
+        }
+    }
+
+    #[test]
+    fn beidou_geo_is_tilted_into_ecef() {
+        let record = beidou_geo_record();
+        let prepared = record.prepare();
+        assert!(prepared.geo && !beidou_record().prepare().geo);
+
+        // At toe, with u = 0.5 rad and the node at omega0 - OMEGA_E_DOT_CGCS2000 * 345600 s
+        // = pi, then rotated -5 degrees about x and not at all about z
+        let state = prepared.evaluate(prepared.toe_gps, KEPLER_TOLERANCE, KEPLER_MAX_ITER);
+        let expected = ECEF::new(-37002604.88355443, -20214615.17859183, 0.0);
+        assert!(state.position.approx_eq(&expected, 1e-6));
+
+        // Hours either side it stays over the same point, with the velocity to match
+        for hours in [-3.0, -1.0, 1.0, 3.0] {
+            let time = prepared.toe_gps + GpsSeconds(hours * 3600.0);
+            let state = prepared.evaluate(time, KEPLER_TOLERANCE, KEPLER_MAX_ITER);
+            assert!(state.position.approx_eq(&expected, 100.0));
+            assert!(state.position.z.abs() < 1e-3);
+            assert!(state.velocity.norm() < 0.01);
+        }
+    }
+
+    #[test]
+    fn kepler_reports_non_convergence() {
+        let (_, iterations, converged) = solve_kepler(1.0, 0.5, KEPLER_TOLERANCE, 1);
+        assert_eq!((iterations, converged), (1, false));
+        let (e_anomaly, _, converged) = solve_kepler(0.0, 0.97, KEPLER_TOLERANCE, 30);
+        assert!(converged && e_anomaly.abs() < 1e-15);
+    }
+}
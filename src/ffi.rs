@@ -0,0 +1,505 @@
+//! C ABI over nav loading, single-satellite propagation and coordinate conversion.
+//!
+//! Every function returns a `PntStatus`; on failure the reason is available from
+//! `pnt_last_error_message` on the same thread. Panics are caught at the boundary and
+//! reported as `PNT_STATUS_PANIC`. Handles are created by `pnt_*_load`/`pnt_*_new` and
+//! must be released with the matching `pnt_*_free`. The header is generated by cbindgen
+//! into include/pnt_rust.h when building with the `ffi` feature.
+
+use crate::gnss::{NavRecord, RinexNav, SatId, ECEF, LLA};
+use crate::satellite::{PropagationConfig, Satellite};
+use chrono::{TimeZone, Utc};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::Duration;
+
+/// Result of every call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PntStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidArgument = 2,
+    Io = 3,
+    NotFound = 4,
+    Propagation = 5,
+    OutOfRange = 6,
+    Panic = 7,
+}
+
+/// Parsed navigation file
+pub struct PntNav {
+    nav: RinexNav,
+}
+
+/// A satellite with its ephemeris records and propagated states
+pub struct PntSatellite {
+    satellite: Satellite,
+    records: Vec<NavRecord>,
+}
+
+/// Propagated state: time as Unix seconds (UTC) and ECEF position in meters
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PntState {
+    pub t: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Geodetic coordinates: degrees and meters above the WGS-84 ellipsoid
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PntLla {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Run the body, turning an Err into its status and message and a panic into
+/// `PntStatus::Panic`
+fn guard(body: impl FnOnce() -> Result<(), (PntStatus, String)>) -> PntStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => {
+            set_error("");
+            PntStatus::Ok
+        }
+        Ok(Err((status, message))) => {
+            set_error(message);
+            status
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_error(format!("panic: {}", message));
+            PntStatus::Panic
+        }
+    }
+}
+
+fn null(name: &str) -> (PntStatus, String) {
+    (PntStatus::NullPointer, format!("{} is null", name))
+}
+
+/// # Safety
+/// The pointer must be null or a NUL-terminated string.
+unsafe fn string<'a>(pointer: *const c_char, name: &str) -> Result<&'a str, (PntStatus, String)> {
+    if pointer.is_null() {
+        return Err(null(name));
+    }
+    CStr::from_ptr(pointer).to_str().map_err(|_| {
+        (
+            PntStatus::InvalidArgument,
+            format!("{} is not valid UTF-8", name),
+        )
+    })
+}
+
+/// Message of the last failed call on this thread, empty after a successful one. The
+/// pointer stays valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn pnt_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Parse a RINEX navigation file into a new handle.
+///
+/// # Safety
+/// `path` must be a NUL-terminated string and `out` a valid pointer to write the handle to.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_nav_load(path: *const c_char, out: *mut *mut PntNav) -> PntStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(null("out"));
+        }
+        *out = ptr::null_mut();
+        let path = string(path, "path")?;
+        let nav = RinexNav::from_file(path)
+            .map_err(|err| (PntStatus::Io, format!("cannot open {}: {}", path, err)))?;
+        *out = Box::into_raw(Box::new(PntNav { nav }));
+        Ok(())
+    })
+}
+
+/// Release a handle from `pnt_nav_load`; null is ignored.
+///
+/// # Safety
+/// `nav` must be null or a handle not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_nav_free(nav: *mut PntNav) {
+    if !nav.is_null() {
+        drop(Box::from_raw(nav));
+    }
+}
+
+/// Number of ephemeris records in the file.
+///
+/// # Safety
+/// `nav` must be a live handle and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_nav_record_count(nav: *const PntNav, out: *mut usize) -> PntStatus {
+    guard(|| {
+        let nav = nav.as_ref().ok_or_else(|| null("nav"))?;
+        let out = out.as_mut().ok_or_else(|| null("out"))?;
+        *out = nav.nav.records().len();
+        Ok(())
+    })
+}
+
+/// New satellite handle for a RINEX id such as "G05", holding a copy of its ephemeris
+/// records from `nav`.
+///
+/// # Safety
+/// `nav` must be a live handle, `sat_id` a NUL-terminated string and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_satellite_new(
+    nav: *const PntNav,
+    sat_id: *const c_char,
+    out: *mut *mut PntSatellite,
+) -> PntStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(null("out"));
+        }
+        *out = ptr::null_mut();
+        let nav = nav.as_ref().ok_or_else(|| null("nav"))?;
+        let name = string(sat_id, "sat_id")?;
+        let id: SatId = name.parse().map_err(|_| {
+            (
+                PntStatus::InvalidArgument,
+                format!("invalid satellite id {:?}", name),
+            )
+        })?;
+        let records = nav.nav.records_for_slice(id).to_vec();
+        if records.is_empty() {
+            return Err((
+                PntStatus::NotFound,
+                format!("no ephemeris records for {}", id),
+            ));
+        }
+        *out = Box::into_raw(Box::new(PntSatellite {
+            satellite: Satellite::new(id, String::new()),
+            records,
+        }));
+        Ok(())
+    })
+}
+
+/// Release a handle from `pnt_satellite_new`; null is ignored.
+///
+/// # Safety
+/// `satellite` must be null or a handle not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_satellite_free(satellite: *mut PntSatellite) {
+    if !satellite.is_null() {
+        drop(Box::from_raw(satellite));
+    }
+}
+
+/// Propagate from `start_unix_s` (UTC) over `duration_s` in steps of `step_s`, replacing
+/// any earlier states.
+///
+/// # Safety
+/// `satellite` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_satellite_propagate(
+    satellite: *mut PntSatellite,
+    start_unix_s: f64,
+    duration_s: f64,
+    step_s: f64,
+) -> PntStatus {
+    guard(|| {
+        let handle = satellite.as_mut().ok_or_else(|| null("satellite"))?;
+        let invalid = |message: &str| (PntStatus::InvalidArgument, message.to_string());
+        if !start_unix_s.is_finite() {
+            return Err(invalid("start is not finite"));
+        }
+        if !(duration_s.is_finite() && duration_s >= 0.0) {
+            return Err(invalid("duration must be finite and non-negative"));
+        }
+        if !(step_s.is_finite() && step_s > 0.0) {
+            return Err(invalid("step must be finite and positive"));
+        }
+        let start = Utc
+            .timestamp_micros((start_unix_s * 1e6).round() as i64)
+            .single()
+            .ok_or_else(|| invalid("start is out of range"))?;
+        let config = PropagationConfig::new().step(Duration::from_secs_f64(step_s));
+        handle.satellite.states.clear();
+        handle
+            .satellite
+            .propagate(
+                start,
+                Duration::from_secs_f64(duration_s),
+                &config,
+                &handle.records,
+            )
+            .map_err(|error| (PntStatus::Propagation, error.to_string()))?;
+        Ok(())
+    })
+}
+
+/// Number of propagated states.
+///
+/// # Safety
+/// `satellite` must be a live handle and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_satellite_state_count(
+    satellite: *const PntSatellite,
+    out: *mut usize,
+) -> PntStatus {
+    guard(|| {
+        let handle = satellite.as_ref().ok_or_else(|| null("satellite"))?;
+        let out = out.as_mut().ok_or_else(|| null("out"))?;
+        *out = handle.satellite.states.len();
+        Ok(())
+    })
+}
+
+/// Propagated state `index`.
+///
+/// # Safety
+/// `satellite` must be a live handle and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_state_get(
+    satellite: *const PntSatellite,
+    index: usize,
+    out: *mut PntState,
+) -> PntStatus {
+    guard(|| {
+        let handle = satellite.as_ref().ok_or_else(|| null("satellite"))?;
+        let out = out.as_mut().ok_or_else(|| null("out"))?;
+        let states = &handle.satellite.states;
+        let state = states.get(index).ok_or_else(|| {
+            (
+                PntStatus::OutOfRange,
+                format!("state {} of {}", index, states.len()),
+            )
+        })?;
+        let position = state.position();
+        *out = PntState {
+            t: state.time_utc().timestamp_micros() as f64 / 1e6,
+            x: position.x,
+            y: position.y,
+            z: position.z,
+        };
+        Ok(())
+    })
+}
+
+/// ECEF meters to geodetic coordinates.
+///
+/// # Safety
+/// `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_ecef_to_lla(x: f64, y: f64, z: f64, out: *mut PntLla) -> PntStatus {
+    guard(|| {
+        let out = out.as_mut().ok_or_else(|| null("out"))?;
+        if ![x, y, z].iter().all(|value| value.is_finite()) {
+            return Err((PntStatus::InvalidArgument, "position is not finite".into()));
+        }
+        let lla = ECEF::new(x, y, z).to_lla();
+        *out = PntLla {
+            latitude: lla.latitude,
+            longitude: lla.longitude,
+            altitude: lla.altitude,
+        };
+        Ok(())
+    })
+}
+
+/// Geodetic coordinates to ECEF meters, written to `out[0..3]`.
+///
+/// # Safety
+/// `lla` must be a valid pointer and `out` point to three writable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn pnt_lla_to_ecef(lla: *const PntLla, out: *mut f64) -> PntStatus {
+    guard(|| {
+        let lla = lla.as_ref().ok_or_else(|| null("lla"))?;
+        if out.is_null() {
+            return Err(null("out"));
+        }
+        let ecef = LLA::new(lla.latitude, lla.longitude, lla.altitude).to_ecef();
+        let out = std::slice::from_raw_parts_mut(out, 3);
+        out.copy_from_slice(&[ecef.x, ecef.y, ecef.z]);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAV_PATH: &CStr = c"constellation/GCGO00USA_R_20231630000_01D_GN.rnx";
+    const START: f64 = 1_686_528_000.0; // 2023-06-12 00:00:00 UTC
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(pnt_last_error_message()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn load() -> *mut PntNav {
+        let mut nav = ptr::null_mut();
+        assert_eq!(
+            unsafe { pnt_nav_load(NAV_PATH.as_ptr(), &mut nav) },
+            PntStatus::Ok
+        );
+        assert!(!nav.is_null());
+        nav
+    }
+
+    #[test]
+    fn load_propagate_and_read_states() {
+        let nav = load();
+        let mut records = 0;
+        assert_eq!(
+            unsafe { pnt_nav_record_count(nav, &mut records) },
+            PntStatus::Ok
+        );
+        let expected = RinexNav::from_file(NAV_PATH.to_str().unwrap()).unwrap();
+        assert_eq!(records, expected.records().len());
+
+        let mut satellite = ptr::null_mut();
+        let status = unsafe { pnt_satellite_new(nav, c"G05".as_ptr(), &mut satellite) };
+        assert_eq!(status, PntStatus::Ok);
+        assert_eq!(last_error(), "");
+        unsafe { pnt_nav_free(nav) };
+
+        let status = unsafe { pnt_satellite_propagate(satellite, START, 3600.0, 600.0) };
+        assert_eq!(status, PntStatus::Ok);
+        let mut count = 0;
+        unsafe { pnt_satellite_state_count(satellite, &mut count) };
+        assert_eq!(count, 6);
+
+        let reference = unsafe { &(*satellite).satellite.states };
+        for index in 0..count {
+            let mut state = PntState {
+                t: 0.0,
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            };
+            let status = unsafe { pnt_state_get(satellite, index, &mut state) };
+            assert_eq!(status, PntStatus::Ok);
+            assert_eq!(state.t, START + 600.0 * index as f64);
+            let position = reference.get(index).unwrap().position();
+            assert_eq!(
+                [state.x, state.y, state.z],
+                [position.x, position.y, position.z]
+            );
+        }
+
+        let mut state = PntState {
+            t: 0.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let status = unsafe { pnt_state_get(satellite, count, &mut state) };
+        assert_eq!(status, PntStatus::OutOfRange);
+        assert_eq!(last_error(), "state 6 of 6");
+
+        // A second run replaces the states
+        let status = unsafe { pnt_satellite_propagate(satellite, START, 600.0, 60.0) };
+        assert_eq!(status, PntStatus::Ok);
+        unsafe { pnt_satellite_state_count(satellite, &mut count) };
+        assert_eq!(count, 10);
+        unsafe { pnt_satellite_free(satellite) };
+    }
+
+    #[test]
+    fn failures_set_a_status_and_message() {
+        let mut nav = ptr::null_mut();
+        let status = unsafe { pnt_nav_load(c"no/such/file.rnx".as_ptr(), &mut nav) };
+        assert_eq!(status, PntStatus::Io);
+        assert!(nav.is_null());
+        assert!(last_error().starts_with("cannot open no/such/file.rnx"));
+
+        let status = unsafe { pnt_nav_load(ptr::null(), &mut nav) };
+        assert_eq!(status, PntStatus::NullPointer);
+        assert_eq!(last_error(), "path is null");
+
+        let nav = load();
+        let mut satellite = ptr::null_mut();
+        let status = unsafe { pnt_satellite_new(nav, c"X99".as_ptr(), &mut satellite) };
+        assert_eq!(status, PntStatus::InvalidArgument);
+        assert_eq!(last_error(), "invalid satellite id \"X99\"");
+        let status = unsafe { pnt_satellite_new(nav, c"E11".as_ptr(), &mut satellite) };
+        assert_eq!(status, PntStatus::NotFound);
+        assert!(satellite.is_null());
+
+        unsafe { pnt_satellite_new(nav, c"G05".as_ptr(), &mut satellite) };
+        for (duration, step) in [(3600.0, 0.0), (-1.0, 60.0), (f64::NAN, 60.0)] {
+            let status = unsafe { pnt_satellite_propagate(satellite, START, duration, step) };
+            assert_eq!(status, PntStatus::InvalidArgument);
+        }
+        let status = unsafe { pnt_satellite_propagate(satellite, f64::INFINITY, 60.0, 60.0) };
+        assert_eq!(status, PntStatus::InvalidArgument);
+        assert_eq!(last_error(), "start is not finite");
+
+        let status = unsafe { pnt_satellite_state_count(ptr::null(), &mut 0) };
+        assert_eq!(status, PntStatus::NullPointer);
+        assert_eq!(last_error(), "satellite is null");
+        unsafe {
+            pnt_satellite_free(satellite);
+            pnt_nav_free(nav);
+            pnt_satellite_free(ptr::null_mut());
+            pnt_nav_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_are_caught_at_the_boundary() {
+        let status = guard(|| panic!("boom"));
+        assert_eq!(status, PntStatus::Panic);
+        assert_eq!(last_error(), "panic: boom");
+        assert_eq!(guard(|| Ok(())), PntStatus::Ok);
+        assert_eq!(last_error(), "");
+    }
+
+    #[test]
+    fn coordinate_conversions_round_trip() {
+        let lla = PntLla {
+            latitude: -33.8688,
+            longitude: 151.2093,
+            altitude: 58.0,
+        };
+        let mut ecef = [0.0; 3];
+        assert_eq!(
+            unsafe { pnt_lla_to_ecef(&lla, ecef.as_mut_ptr()) },
+            PntStatus::Ok
+        );
+        let mut back = PntLla {
+            latitude: 0.0,
+            longitude: 0.0,
+            altitude: 0.0,
+        };
+        let status = unsafe { pnt_ecef_to_lla(ecef[0], ecef[1], ecef[2], &mut back) };
+        assert_eq!(status, PntStatus::Ok);
+        assert!((back.latitude - lla.latitude).abs() < 1e-9);
+        assert!((back.longitude - lla.longitude).abs() < 1e-9);
+        assert!((back.altitude - lla.altitude).abs() < 1e-4);
+
+        let status = unsafe { pnt_ecef_to_lla(f64::NAN, 0.0, 0.0, &mut back) };
+        assert_eq!(status, PntStatus::InvalidArgument);
+        let status = unsafe { pnt_lla_to_ecef(&lla, ptr::null_mut()) };
+        assert_eq!(status, PntStatus::NullPointer);
+    }
+}
@@ -0,0 +1,158 @@
+//! The f64 methods the math core needs, from libm, for no_std builds where core has no
+//! float intrinsics. With std the inherent methods are used instead.
+
+pub(crate) trait F64Ext {
+    fn sqrt(self) -> f64;
+    fn sin(self) -> f64;
+    fn cos(self) -> f64;
+    fn sin_cos(self) -> (f64, f64);
+    fn atan2(self, other: f64) -> f64;
+    fn hypot(self, other: f64) -> f64;
+    fn powi(self, n: i32) -> f64;
+    fn powf(self, n: f64) -> f64;
+    fn round(self) -> f64;
+    fn floor(self) -> f64;
+    fn rem_euclid(self, rhs: f64) -> f64;
+}
+
+impl F64Ext for f64 {
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+
+    fn sin(self) -> f64 {
+        libm::sin(self)
+    }
+
+    fn cos(self) -> f64 {
+        libm::cos(self)
+    }
+
+    fn sin_cos(self) -> (f64, f64) {
+        libm::sincos(self)
+    }
+
+    fn atan2(self, other: f64) -> f64 {
+        libm::atan2(self, other)
+    }
+
+    fn hypot(self, other: f64) -> f64 {
+        libm::hypot(self, other)
+    }
+
+    fn powi(self, n: i32) -> f64 {
+        libm::pow(self, n as f64)
+    }
+
+    fn powf(self, n: f64) -> f64 {
+        libm::pow(self, n)
+    }
+
+    fn round(self) -> f64 {
+        libm::round(self)
+    }
+
+    fn floor(self) -> f64 {
+        libm::floor(self)
+    }
+
+    fn rem_euclid(self, rhs: f64) -> f64 {
+        let r = libm::fmod(self, rhs);
+        if r < 0.0 {
+            r + libm::fabs(rhs)
+        } else {
+            r
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::F64Ext;
+    use core::f64::consts::PI;
+
+    /// Angles over several revolutions either way, as Kepler and the node updates see them
+    fn angles() -> impl DoubleEndedIterator<Item = f64> {
+        (-2000..=2000).map(|i| i as f64 * 0.0173 * PI)
+    }
+
+    fn assert_close(libm: f64, std: f64, what: &str) {
+        let tolerance = 4.0 * f64::EPSILON * std.abs().max(1.0);
+        assert!(
+            (libm - std).abs() <= tolerance,
+            "{}: libm {:e}, std {:e}",
+            what,
+            libm,
+            std
+        );
+    }
+
+    #[test]
+    fn trigonometry_matches_std() {
+        for x in angles() {
+            assert_close(F64Ext::sin(x), x.sin(), "sin");
+            assert_close(F64Ext::cos(x), x.cos(), "cos");
+            let (sin, cos) = F64Ext::sin_cos(x);
+            assert_close(sin, x.sin(), "sin_cos");
+            assert_close(cos, x.cos(), "sin_cos");
+            let (y, x) = (x.sin() * 7e6, x.cos() * 2e7);
+            assert_close(F64Ext::atan2(y, x), y.atan2(x), "atan2");
+            assert_close(F64Ext::hypot(y, x), y.hypot(x), "hypot");
+        }
+    }
+
+    #[test]
+    fn powers_and_roots_match_std() {
+        for sqrt_a in [5153.6, 5282.6, 5440.6, 6493.4] {
+            let a = sqrt_a * sqrt_a;
+            assert_close(F64Ext::sqrt(a), a.sqrt(), "sqrt");
+            assert_close(F64Ext::powi(a, 3), a.powi(3), "powi");
+            assert_close(F64Ext::powf(a, 1.5), a.powf(1.5), "powf");
+        }
+    }
+
+    #[test]
+    fn rounding_and_remainders_match_std() {
+        for x in [
+            -604_800.5, -86_400.25, -0.5, -0.0, 0.0, 0.49, 2.5, 345_614.0,
+        ] {
+            assert_eq!(F64Ext::round(x), x.round());
+            assert_eq!(F64Ext::floor(x), x.floor());
+            for rhs in [604_800.0, 2.0 * PI, -86_400.0] {
+                assert_close(F64Ext::rem_euclid(x, rhs), x.rem_euclid(rhs), "rem_euclid");
+            }
+        }
+        for x in angles() {
+            assert_close(
+                F64Ext::rem_euclid(x, 2.0 * PI),
+                x.rem_euclid(2.0 * PI),
+                "rem_euclid",
+            );
+        }
+    }
+
+    /// The orbital-plane to ECEF step of the ephemeris, both ways, agrees far below a
+    /// millimetre at GNSS radii
+    #[test]
+    fn orbit_positions_match_std() {
+        for (u, node) in angles().zip(angles().rev()) {
+            let r = 26_560_000.0;
+            let i = 0.96;
+            let (x, y) = (r * F64Ext::cos(u), r * F64Ext::sin(u));
+            let libm = [
+                x * F64Ext::cos(node) - y * F64Ext::cos(i) * F64Ext::sin(node),
+                x * F64Ext::sin(node) + y * F64Ext::cos(i) * F64Ext::cos(node),
+                y * F64Ext::sin(i),
+            ];
+            let (x, y) = (r * u.cos(), r * u.sin());
+            let std = [
+                x * node.cos() - y * i.cos() * node.sin(),
+                x * node.sin() + y * i.cos() * node.cos(),
+                y * i.sin(),
+            ];
+            for (libm, std) in libm.iter().zip(std) {
+                assert!((libm - std).abs() < 1e-6, "{} vs {}", libm, std);
+            }
+        }
+    }
+}
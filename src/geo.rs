@@ -0,0 +1,285 @@
+use crate::constellation::Constellation;
+use crate::gnss::{SatId, LLA};
+use crate::satellite::{split_at_antimeridian, GroundTrackOptions, Satellite};
+use chrono::{DateTime, Utc};
+use geo_types::{Coord, LineString, MultiLineString, Point, Polygon};
+
+// georust geometries in degrees with x = longitude and y = latitude, as GIS tools expect.
+// Altitude has no place in them and is dropped; converting back gives altitude 0.
+
+const MEAN_EARTH_RADIUS: f64 = 6371008.8; // m, for the spherical footprint
+const FOOTPRINT_VERTICES: usize = 72;
+
+impl From<LLA> for Coord<f64> {
+    fn from(lla: LLA) -> Self {
+        Coord {
+            x: lla.longitude,
+            y: lla.latitude,
+        }
+    }
+}
+
+impl From<Coord<f64>> for LLA {
+    fn from(coord: Coord<f64>) -> Self {
+        LLA::new(coord.y, coord.x, 0.0)
+    }
+}
+
+impl From<LLA> for Point<f64> {
+    fn from(lla: LLA) -> Self {
+        Point(lla.into())
+    }
+}
+
+impl From<Point<f64>> for LLA {
+    fn from(point: Point<f64>) -> Self {
+        point.0.into()
+    }
+}
+
+impl Satellite {
+    /// Ground track as one line with continuous longitudes, which run past ±180° instead of
+    /// jumping across the map
+    pub fn ground_track_line_string(&self) -> LineString<f64> {
+        let options = GroundTrackOptions {
+            unwrap_longitude: true,
+            ..Default::default()
+        };
+        self.ground_track_with(options)
+            .into_iter()
+            .map(|(_, lla)| Coord::from(lla))
+            .collect()
+    }
+
+    /// Ground track within [-180°, 180°], split into one line per crossing of the
+    /// antimeridian
+    pub fn ground_track_lines(&self) -> MultiLineString<f64> {
+        let points: Vec<LLA> = self
+            .ground_track()
+            .into_iter()
+            .map(|(_, lla)| lla)
+            .collect();
+        split_at_antimeridian(&points)
+            .into_iter()
+            .map(|segment| {
+                segment
+                    .into_iter()
+                    .map(Coord::from)
+                    .collect::<LineString<f64>>()
+            })
+            .collect()
+    }
+
+    /// Area on a spherical Earth from which the satellite is above the mask at an epoch,
+    /// interpolated between states. Longitudes are continuous around the subsatellite
+    /// point, so a footprint over the antimeridian runs past ±180°; one containing a pole is
+    /// closed along that pole's latitude. None without a state near the epoch.
+    pub fn footprint(&self, epoch: DateTime<Utc>, mask_deg: f64) -> Option<Polygon<f64>> {
+        let position = self.interpolate_at(epoch).ok()?;
+        let center = position.to_lla();
+        let radius = position.norm();
+        let mask = mask_deg.to_radians();
+        let ratio = MEAN_EARTH_RADIUS * mask.cos() / radius;
+        if ratio.is_nan() || ratio >= 1.0 {
+            return None; // On or below the surface
+        }
+        // Earth central angle from the subsatellite point to the edge of the footprint
+        let half_angle = ratio.acos() - mask;
+        if half_angle <= 0.0 {
+            return None;
+        }
+
+        let (lat0, lon0) = (center.latitude.to_radians(), center.longitude.to_radians());
+        let mut ring: Vec<Coord<f64>> = Vec::with_capacity(FOOTPRINT_VERTICES + 3);
+        for k in 0..FOOTPRINT_VERTICES {
+            let bearing = (k as f64 * 360.0 / FOOTPRINT_VERTICES as f64).to_radians();
+            let lat = (lat0.sin() * half_angle.cos()
+                + lat0.cos() * half_angle.sin() * bearing.cos())
+            .asin();
+            let lon = lon0
+                + (bearing.sin() * half_angle.sin() * lat0.cos())
+                    .atan2(half_angle.cos() - lat0.sin() * lat.sin());
+            let mut lon = lon.to_degrees();
+            if let Some(previous) = ring.last() {
+                lon = previous.x + (lon - previous.x + 180.0).rem_euclid(360.0) - 180.0;
+            }
+            ring.push(Coord {
+                x: lon,
+                y: lat.to_degrees(),
+            });
+        }
+        // Around a pole the longitudes wind through a full turn instead of closing
+        let first = ring[0];
+        let last = ring[ring.len() - 1];
+        let winding = last.x + (first.x - last.x + 180.0).rem_euclid(360.0) - 180.0 - first.x;
+        if winding.abs() > 180.0 {
+            let pole = if center.latitude >= 0.0 { 90.0 } else { -90.0 };
+            let closing = first.x + winding;
+            ring.push(Coord {
+                x: closing,
+                y: first.y,
+            });
+            ring.push(Coord {
+                x: closing,
+                y: pole,
+            });
+            ring.push(Coord {
+                x: first.x,
+                y: pole,
+            });
+        }
+        Some(Polygon::new(LineString::from(ring), Vec::new()))
+    }
+}
+
+impl Constellation {
+    /// Footprints of the active satellites at an epoch
+    pub fn footprints(&self, epoch: DateTime<Utc>, mask_deg: f64) -> Vec<(SatId, Polygon<f64>)> {
+        self.iter()
+            .filter(|satellite| satellite.active)
+            .filter_map(|satellite| Some((satellite.id, satellite.footprint(epoch, mask_deg)?)))
+            .collect()
+    }
+
+    /// Subsatellite points of the satellites propagated over an epoch
+    pub fn subsatellite_points(&self, epoch: DateTime<Utc>) -> Vec<(SatId, Point<f64>)> {
+        self.iter()
+            .filter_map(|satellite| {
+                let lla = satellite.interpolate_at(epoch).ok()?.to_lla();
+                Some((satellite.id, lla.into()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{propagated_day, start};
+    use rstar::primitives::GeomWithData;
+    use rstar::RTree;
+
+    /// Earth central angle between two points, degrees
+    fn central_angle(a: Coord<f64>, b: Coord<f64>) -> f64 {
+        let (lat1, lat2) = (a.y.to_radians(), b.y.to_radians());
+        let dlon = (b.x - a.x).to_radians();
+        (lat1.sin() * lat2.sin() + lat1.cos() * lat2.cos() * dlon.cos())
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees()
+    }
+
+    #[test]
+    fn x_is_longitude_and_y_latitude() {
+        // Boulder, whose longitude would be an impossible latitude if swapped
+        let lla = LLA::new(40.015, -105.27, 1655.0);
+        let point = Point::from(lla);
+        assert_eq!((point.x(), point.y()), (-105.27, 40.015));
+        let coord = Coord::from(lla);
+        assert_eq!((coord.x, coord.y), (-105.27, 40.015));
+
+        let back = LLA::from(point);
+        assert_eq!(
+            (back.latitude, back.longitude, back.altitude),
+            (40.015, -105.27, 0.0)
+        );
+        let back = LLA::from(Coord { x: 151.2, y: -33.9 });
+        assert_eq!((back.latitude, back.longitude), (-33.9, 151.2));
+    }
+
+    #[test]
+    fn ground_tracks_as_lines() {
+        let constellation = propagated_day();
+        let satellite = constellation.get(17).unwrap();
+        let track = satellite.ground_track();
+
+        let line = satellite.ground_track_line_string();
+        assert_eq!(line.0.len(), track.len());
+        for ((_, lla), coord) in track.iter().zip(&line.0) {
+            assert_eq!(coord.y, lla.latitude);
+            assert_eq!((coord.x - lla.longitude).rem_euclid(360.0) % 360.0, 0.0);
+        }
+        for pair in line.0.windows(2) {
+            assert!((pair[1].x - pair[0].x).abs() < 180.0);
+        }
+
+        let lines = satellite.ground_track_lines();
+        for segment in &lines {
+            for coord in &segment.0 {
+                assert!((-180.0..=180.0).contains(&coord.x) && (-90.0..=90.0).contains(&coord.y));
+            }
+            for pair in segment.0.windows(2) {
+                assert!((pair[1].x - pair[0].x).abs() < 180.0);
+            }
+        }
+        // Each split adds the crossing point to both sides
+        let vertices: usize = lines.iter().map(|segment| segment.0.len()).sum();
+        assert_eq!(vertices, track.len() + 2 * (lines.0.len() - 1));
+    }
+
+    #[test]
+    fn footprints_are_circles_around_the_subsatellite_point() {
+        let constellation = propagated_day();
+        let epoch = start() + chrono::Duration::hours(3);
+        let points = constellation.subsatellite_points(epoch);
+        let footprints = constellation.footprints(epoch, 10.0);
+        assert_eq!(points.len(), footprints.len());
+
+        for ((id, point), (footprint_id, footprint)) in points.iter().zip(&footprints) {
+            assert_eq!(id, footprint_id);
+            let ring = &footprint.exterior().0;
+            assert!(ring.first() == ring.last() && footprint.interiors().is_empty());
+            // GPS at 10° mask: about 66° of central angle on a spherical Earth
+            let polar = ring.iter().any(|coord| coord.y.abs() == 90.0);
+            if !polar {
+                for vertex in ring {
+                    let angle = central_angle(point.0, *vertex);
+                    assert!((60.0..72.0).contains(&angle), "{} {}", id, angle);
+                }
+            }
+            let wider = constellation
+                .get(*id)
+                .unwrap()
+                .footprint(epoch, 0.0)
+                .unwrap();
+            let angle = central_angle(point.0, wider.exterior().0[0]);
+            assert!(angle > central_angle(point.0, ring[0]));
+        }
+        assert!(constellation
+            .get(17)
+            .unwrap()
+            .footprint(start() - chrono::Duration::hours(1), 10.0)
+            .is_none());
+    }
+
+    #[test]
+    fn subsatellite_points_in_an_rtree() {
+        let constellation = propagated_day();
+        let epoch = start() + chrono::Duration::hours(2);
+        let points = constellation.subsatellite_points(epoch);
+        let tree = RTree::bulk_load(
+            points
+                .iter()
+                .map(|(id, point)| GeomWithData::new([point.x(), point.y()], *id))
+                .collect(),
+        );
+        assert_eq!(tree.size(), points.len());
+
+        // Overhead of Boulder, with longitude first as for every other GIS tool
+        let observer: Point<f64> = LLA::new(40.015, -105.27, 1655.0).into();
+        let query = [observer.x(), observer.y()];
+        let nearest = tree.nearest_neighbor(&query).unwrap();
+        let squared = |(_, point): &&(SatId, Point<f64>)| {
+            (point.x() - observer.x()).powi(2) + (point.y() - observer.y()).powi(2)
+        };
+        let expected = points
+            .iter()
+            .min_by(|a, b| squared(a).total_cmp(&squared(b)))
+            .unwrap();
+        assert_eq!(nearest.data, expected.0);
+
+        let satellite = constellation.get(nearest.data).unwrap();
+        let lla = satellite.interpolate_at(epoch).unwrap().to_lla();
+        assert_eq!(*nearest.geom(), [lla.longitude, lla.latitude]);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::constellation::Constellation;
+#[cfg(feature = "ndarray")]
+use crate::dop_map::DopGrid;
+use crate::gnss::LLA;
+#[cfg(feature = "ndarray")]
+use crate::json::number;
+use crate::json::string;
+#[cfg(feature = "ndarray")]
+use crate::positioning::{Dop, SppSolution};
+use crate::satellite::{split_at_antimeridian, Satellite};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std-fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+
+// RFC 7946 GeoJSON: WGS-84 positions ordered [longitude, latitude, altitude], lines that
+// cross the antimeridian split into a MultiLineString.
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+impl Satellite {
+    /// Ground track as a FeatureCollection with a single feature
+    pub fn write_geojson(&self, writer: impl Write) -> io::Result<()> {
+        write_collection(writer, self.track_feature())
+    }
+
+    /// `write_geojson` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_geojson(&self, path: &str) -> io::Result<()> {
+        self.write_geojson(BufWriter::new(File::create(path)?))
+    }
+
+    /// LineString (MultiLineString when split at the antimeridian) feature of the ground
+    /// track with the satellite id, constellation and time span as properties; None
+    /// without propagated states
+    fn track_feature(&self) -> Option<String> {
+        let track = self.ground_track();
+        let ((start, _), (end, _)) = (track.first()?, track.last()?);
+        let points: Vec<LLA> = track.iter().map(|(_, lla)| *lla).collect();
+        let segments = split_at_antimeridian(&points);
+        let line = |segment: &Vec<LLA>| {
+            let positions: Vec<String> = segment.iter().filter_map(position).collect();
+            format!("[{}]", positions.join(","))
+        };
+        let geometry = match &segments[..] {
+            [segment] => format!(r#"{{"type":"LineString","coordinates":{}}}"#, line(segment)),
+            _ => {
+                let lines: Vec<String> = segments.iter().map(line).collect();
+                format!(
+                    r#"{{"type":"MultiLineString","coordinates":[{}]}}"#,
+                    lines.join(",")
+                )
+            }
+        };
+        let properties = format!(
+            r#"{{"sat_id":"{}","constellation":"{:?}","name":{},"start":"{}","end":"{}"}}"#,
+            self.id,
+            self.constellation(),
+            string(&self.name),
+            start.format(TIME_FORMAT),
+            end.format(TIME_FORMAT)
+        );
+        Some(feature(&geometry, &properties))
+    }
+}
+
+impl Constellation {
+    /// Ground tracks of every propagated satellite as one FeatureCollection
+    pub fn write_geojson(&self, writer: impl Write) -> io::Result<()> {
+        write_collection(writer, self.iter().filter_map(Satellite::track_feature))
+    }
+
+    /// `write_geojson` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_geojson(&self, path: &str) -> io::Result<()> {
+        self.write_geojson(BufWriter::new(File::create(path)?))
+    }
+
+    /// Subsatellite points of the propagated satellites at an epoch as Point features,
+    /// interpolated between states; satellites not propagated over the epoch are left out
+    pub fn write_subsatellite_geojson(
+        &self,
+        writer: impl Write,
+        epoch: DateTime<Utc>,
+    ) -> io::Result<()> {
+        let features = self.iter().filter_map(|satellite| {
+            let lla = satellite.interpolate_at(epoch).ok()?.to_lla();
+            let properties = format!(
+                r#"{{"sat_id":"{}","constellation":"{:?}","time":"{}"}}"#,
+                satellite.id,
+                satellite.constellation(),
+                epoch.format(TIME_FORMAT)
+            );
+            Some(feature(&point(&lla)?, &properties))
+        });
+        write_collection(writer, features)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl DopGrid {
+    /// Each cell as a Polygon feature spanning half a step around its center, clipped at
+    /// the poles and the antimeridian, with its DOP components (null where fewer than four
+    /// satellites were ever in view) and availability as properties
+    pub fn write_geojson(&self, writer: impl Write) -> io::Result<()> {
+        let half = self.step / 2.0;
+        let features = self.iter().map(|(lla, dop, availability)| {
+            let (south, north) = (
+                (lla.latitude - half).max(-90.0),
+                (lla.latitude + half).min(90.0),
+            );
+            let (west, east) = (
+                (lla.longitude - half).max(-180.0),
+                (lla.longitude + half).min(180.0),
+            );
+            let geometry = format!(
+                r#"{{"type":"Polygon","coordinates":[[[{w},{s}],[{e},{s}],[{e},{n}],[{w},{n}],[{w},{s}]]]}}"#,
+                w = west,
+                e = east,
+                s = south,
+                n = north
+            );
+            let component = |get: fn(&Dop) -> f64| dop.as_ref().map_or("null".to_string(), |dop| number(get(dop)));
+            let properties = format!(
+                r#"{{"latitude":{},"longitude":{},"gdop":{},"pdop":{},"hdop":{},"vdop":{},"availability":{}}}"#,
+                number(lla.latitude),
+                number(lla.longitude),
+                component(|dop| dop.gdop),
+                component(|dop| dop.pdop),
+                component(|dop| dop.hdop),
+                component(|dop| dop.vdop),
+                number(availability)
+            );
+            feature(&geometry, &properties)
+        });
+        write_collection(writer, features)
+    }
+
+    /// `write_geojson` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_geojson(&self, path: &str) -> io::Result<()> {
+        self.write_geojson(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// SPP fixes as Point features with their epoch, clock bias, satellite count and PDOP
+#[cfg(feature = "ndarray")]
+pub fn write_spp_geojson<'a>(
+    writer: impl Write,
+    solutions: impl IntoIterator<Item = (DateTime<Utc>, &'a SppSolution)>,
+) -> io::Result<()> {
+    let features = solutions.into_iter().filter_map(|(epoch, solution)| {
+        let properties = format!(
+            r#"{{"time":"{}","clock_bias":{},"satellites":{},"pdop":{}}}"#,
+            epoch.format(TIME_FORMAT),
+            number(solution.clock_bias),
+            solution.residuals.len(),
+            number(solution.dop.pdop)
+        );
+        Some(feature(&point(&solution.position.to_lla())?, &properties))
+    });
+    write_collection(writer, features)
+}
+
+fn write_collection(
+    mut writer: impl Write,
+    features: impl IntoIterator<Item = String>,
+) -> io::Result<()> {
+    write!(writer, r#"{{"type":"FeatureCollection","features":["#)?;
+    for (i, feature) in features.into_iter().enumerate() {
+        let separator = if i == 0 { "\n" } else { ",\n" };
+        write!(writer, "{}{}", separator, feature)?;
+    }
+    writeln!(writer, "\n]}}")?;
+    writer.flush()
+}
+
+fn feature(geometry: &str, properties: &str) -> String {
+    format!(
+        r#"{{"type":"Feature","geometry":{},"properties":{}}}"#,
+        geometry, properties
+    )
+}
+
+fn point(lla: &LLA) -> Option<String> {
+    Some(format!(
+        r#"{{"type":"Point","coordinates":{}}}"#,
+        position(lla)?
+    ))
+}
+
+/// [longitude, latitude, altitude], None if any is not finite
+fn position(lla: &LLA) -> Option<String> {
+    let finite = [lla.longitude, lla.latitude, lla.altitude]
+        .iter()
+        .all(|value| value.is_finite());
+    finite.then(|| format!("[{},{},{}]", lla.longitude, lla.latitude, lla.altitude))
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::gnss;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::{start, NAV};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    fn config() -> PropagationConfig {
+        PropagationConfig::new().step(Duration::from_secs(300))
+    }
+
+    fn satellite(duration: Duration) -> Satellite {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut satellite = Satellite::builder(17).name("SVN53 \"IIR-M\"").build();
+        let records = nav.records_for_slice(17.into());
+        satellite
+            .propagate(start(), duration, &config(), records)
+            .unwrap();
+        satellite
+    }
+
+    fn features(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> Vec<Value> {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        let collection: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap().clone();
+        for feature in &features {
+            assert_eq!(feature["type"], "Feature");
+            assert!(feature["properties"].is_object());
+        }
+        features
+    }
+
+    fn triple(position: &Value) -> (f64, f64, f64) {
+        let values: Vec<f64> = position
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_f64().unwrap())
+            .collect();
+        assert_eq!(values.len(), 3);
+        (values[0], values[1], values[2])
+    }
+
+    #[test]
+    fn short_track_is_a_line_string_in_longitude_latitude_order() {
+        let satellite = satellite(Duration::from_secs(1800));
+        let features = features(|out| satellite.write_geojson(out));
+        assert_eq!(features.len(), 1);
+        let geometry = &features[0]["geometry"];
+        assert_eq!(geometry["type"], "LineString");
+        let positions = geometry["coordinates"].as_array().unwrap();
+        let track = satellite.ground_track();
+        assert_eq!(positions.len(), track.len());
+        for (position, (_, lla)) in positions.iter().zip(&track) {
+            assert_eq!(
+                triple(position),
+                (lla.longitude, lla.latitude, lla.altitude)
+            );
+        }
+        // G17 starts over the Indian Ocean near the equator, which a swapped pair misses
+        let (longitude, latitude, _) = triple(&positions[0]);
+        assert!((55.0..65.0).contains(&longitude) && latitude.abs() < 10.0);
+
+        let properties = &features[0]["properties"];
+        assert_eq!(properties["sat_id"], "G17");
+        assert_eq!(properties["constellation"], "Gps");
+        assert_eq!(properties["name"], "SVN53 \"IIR-M\"");
+        assert_eq!(properties["start"], "2023-06-12T00:00:00.000Z");
+        assert_eq!(properties["end"], "2023-06-12T00:25:00.000Z");
+    }
+
+    #[test]
+    fn day_long_track_splits_into_a_multi_line_string() {
+        let satellite = satellite(Duration::from_secs(86400));
+        let features = features(|out| satellite.write_geojson(out));
+        let geometry = &features[0]["geometry"];
+        assert_eq!(geometry["type"], "MultiLineString");
+        let lines = geometry["coordinates"].as_array().unwrap();
+        assert!(lines.len() > 1);
+        for line in lines {
+            let positions: Vec<(f64, f64, f64)> =
+                line.as_array().unwrap().iter().map(triple).collect();
+            for (longitude, latitude, _) in &positions {
+                assert!((-180.0..=180.0).contains(longitude));
+                assert!((-90.0..=90.0).contains(latitude));
+            }
+            assert!(positions
+                .windows(2)
+                .all(|pair| (pair[1].0 - pair[0].0).abs() < 180.0));
+        }
+        // Inner lines run edge to edge
+        for line in &lines[1..lines.len() - 1] {
+            let positions = line.as_array().unwrap();
+            let (first, _, _) = triple(&positions[0]);
+            let (last, _, _) = triple(&positions[positions.len() - 1]);
+            assert_eq!(first.abs(), 180.0);
+            assert_eq!(last, -first);
+        }
+    }
+
+    #[test]
+    fn subsatellite_points_are_point_features() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut constellation = Constellation::from_nav(nav);
+        constellation.propagate_all(start(), Duration::from_secs(3600), &config());
+        let epoch = start() + chrono::Duration::seconds(1234);
+        let features = features(|out| constellation.write_subsatellite_geojson(out, epoch));
+        let propagated = constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .count();
+        assert_eq!(features.len(), propagated);
+        for feature in &features {
+            assert_eq!(feature["geometry"]["type"], "Point");
+            let id: gnss::SatId = feature["properties"]["sat_id"]
+                .as_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            let lla = constellation
+                .get(id)
+                .unwrap()
+                .interpolate_at(epoch)
+                .unwrap()
+                .to_lla();
+            let coordinates = &feature["geometry"]["coordinates"];
+            assert_eq!(
+                triple(coordinates),
+                (lla.longitude, lla.latitude, lla.altitude)
+            );
+            assert_eq!(feature["properties"]["time"], "2023-06-12T00:20:34.000Z");
+        }
+    }
+}
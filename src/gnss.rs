@@ -1,255 +1,2664 @@
-use chrono::{DateTime, TimeZone, Utc};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-
-pub const OMEGA_E_DOT: f64 = 7.2921151467e-5; // WGS-84 earth rotation rate, rad/s
-pub const MU_EARTH: f64 = 398600.5e9; // Earth's gravitational constant
-pub const C_LIGHT: f64 = 299792458.0; // Speed of light, m/s
-
-#[derive(Debug, PartialEq, Default, Clone, Copy)]
-pub struct ECEF {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-}
-
-impl ECEF {
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
-        Self { x, y, z }
-    }
-    pub fn to_lla(&self) -> LLA {
-        LLA {
-            latitude: 0.0,
-            longitude: 0.0,
-            altitude: 0.0,
-        }
-    }
-}
-
-pub struct LLA {
-    pub latitude: f64,
-    pub longitude: f64,
-    pub altitude: f64,
-}
-
-impl LLA {
-    pub fn new(latitude: f64, longitude: f64, altitude: f64) -> Self {
-        Self {
-            latitude,
-            longitude,
-            altitude,
-        }
-    }
-    pub fn to_ecef(&self) -> ECEF {
-        ECEF {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }
-    }
-}
-
-pub struct State {
-    pub time: Vec<f64>,
-    pub position: Vec<ECEF>,
-}
-
-impl State {
-    pub fn new() -> Self {
-        Self {
-            time: vec![0.0],
-            position: vec![ECEF::new(0.0, 0.0, 0.0)],
-        }
-    }
-}
-
-/// Calculate GPS time: milliseconds since GPS epoch (Jan 6, 1980) plus leap seconds
-pub fn calculate_gps_time(time: std::time::SystemTime) -> f64 {
-    let utc_time: DateTime<Utc> = time.into();
-    let gps_epoch: DateTime<Utc> = Utc.with_ymd_and_hms(1980, 1, 6, 0, 0, 0).unwrap();
-    let leap_seconds = 18.0; // As of 2024
-    ((utc_time - gps_epoch).num_microseconds().unwrap() as f64 / 1e6 + leap_seconds) * 1000.0
-}
-
-#[derive(Debug, PartialEq, Default, Clone, Copy)]
-pub struct NavRecord {
-    pub sat_id: u8,
-    pub epoch: (i32, i32, i32, i32, i32, i32),
-    pub gps_millis: f64,
-    pub sv_clock_bias: f64,
-    pub sv_clock_drift: f64,
-    pub sv_clock_drift_rate: f64,
-    pub iode: f64,
-    pub crs: f64,
-    pub delta_n: f64,
-    pub m0: f64,
-    pub cuc: f64,
-    pub eccentricity: f64,
-    pub cus: f64,
-    pub sqrt_a: f64,
-    pub toe: f64,
-    pub cic: f64,
-    pub omega0: f64,
-    pub cis: f64,
-    pub i0: f64,
-    pub crc: f64,
-    pub omega: f64,
-    pub omega_dot: f64,
-    pub idot: f64,
-    pub codes_on_l2_channel: f64,
-    pub gps_week: f64,
-    pub l2_p_data_flag: f64,
-    pub sv_accuracy: f64,
-    pub sv_health: f64,
-    pub tgd: f64,
-    pub iodc: f64,
-    pub transmission_time: f64,
-    pub fit_interval: f64,
-}
-
-pub struct RinexNav {
-    pub records: Vec<NavRecord>,
-}
-
-impl RinexNav {
-    pub fn from_file(filename: &str) -> Self {
-        let mut records = Vec::new();
-        let file = File::open(filename).expect("Failed to open file");
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
-
-        // Skip header
-        while let Some(Ok(line)) = lines.next() {
-            if line.contains("END OF HEADER") {
-                break;
-            }
-        }
-
-        // Parse records
-        while let Some(Ok(line)) = lines.next() {
-            if line.len() < 79 {
-                continue;
-            }
-
-            let sat_id = line[1..3].trim().parse().unwrap_or(0);
-            let epoch = Self::parse_epoch(&line[3..23]);
-            let gps_millis = Self::epoch_to_gps_millis(&epoch);
-            let sv_clock_bias = Self::parse_float(&line[23..42]);
-            let sv_clock_drift = Self::parse_float(&line[42..61]);
-            let sv_clock_drift_rate = Self::parse_float(&line[61..80]);
-
-            let mut record = NavRecord {
-                sat_id,
-                epoch,
-                gps_millis,
-                sv_clock_bias,
-                sv_clock_drift,
-                sv_clock_drift_rate,
-                ..Default::default()
-            };
-
-            // Parse additional lines
-            let mut line_count = 0;
-            for _ in 0..7 {
-                if let Some(Ok(data_line)) = lines.next() {
-                    Self::parse_data_line(&mut record, &data_line, line_count);
-                    line_count += 1;
-                }
-            }
-            records.push(record);
-        }
-        Self { records }
-    }
-
-    fn parse_epoch(s: &str) -> (i32, i32, i32, i32, i32, i32) {
-        let parts: Vec<&str> = s.split_whitespace().collect();
-        (
-            parts[0].parse().unwrap_or(0),
-            parts[1].parse().unwrap_or(0),
-            parts[2].parse().unwrap_or(0),
-            parts[3].parse().unwrap_or(0),
-            parts[4].parse().unwrap_or(0),
-            parts[5].parse().unwrap_or(0),
-        )
-    }
-
-    fn epoch_to_gps_millis(epoch: &(i32, i32, i32, i32, i32, i32)) -> f64 {
-        let utc_time = Utc
-            .with_ymd_and_hms(
-                epoch.0,
-                epoch.1 as u32,
-                epoch.2 as u32,
-                epoch.3 as u32,
-                epoch.4 as u32,
-                epoch.5 as u32,
-            )
-            .unwrap();
-        calculate_gps_time(utc_time.into())
-    }
-
-    fn parse_float(s: &str) -> f64 {
-        s.trim().replace('D', "E").parse().unwrap_or(0.0)
-    }
-
-    fn parse_data_line(record: &mut NavRecord, line: &str, line_number: usize) {
-        let values: Vec<f64> = line[4..]
-            .chars()
-            .collect::<Vec<char>>()
-            .chunks(19)
-            .take(4)
-            .map(|chunk| {
-                chunk
-                    .iter()
-                    .collect::<String>()
-                    .replace('D', "E")
-                    .trim()
-                    .parse()
-                    .unwrap_or(0.0)
-            })
-            .collect();
-
-        match line_number {
-            0 => {
-                record.iode = values.get(0).copied().unwrap_or(0.0);
-                record.crs = values.get(1).copied().unwrap_or(0.0);
-                record.delta_n = values.get(2).copied().unwrap_or(0.0);
-                record.m0 = values.get(3).copied().unwrap_or(0.0);
-            }
-            1 => {
-                record.cuc = values.get(0).copied().unwrap_or(0.0);
-                record.eccentricity = values.get(1).copied().unwrap_or(0.0);
-                record.cus = values.get(2).copied().unwrap_or(0.0);
-                record.sqrt_a = values.get(3).copied().unwrap_or(0.0);
-            }
-            2 => {
-                record.toe = values.get(0).copied().unwrap_or(0.0);
-                record.cic = values.get(1).copied().unwrap_or(0.0);
-                record.omega0 = values.get(2).copied().unwrap_or(0.0);
-                record.cis = values.get(3).copied().unwrap_or(0.0);
-            }
-            3 => {
-                record.i0 = values.get(0).copied().unwrap_or(0.0);
-                record.crc = values.get(1).copied().unwrap_or(0.0);
-                record.omega = values.get(2).copied().unwrap_or(0.0);
-                record.omega_dot = values.get(3).copied().unwrap_or(0.0);
-            }
-            4 => {
-                record.idot = values.get(0).copied().unwrap_or(0.0);
-                record.codes_on_l2_channel = values.get(1).copied().unwrap_or(0.0);
-                record.gps_week = values.get(2).copied().unwrap_or(0.0);
-                record.l2_p_data_flag = values.get(3).copied().unwrap_or(0.0);
-            }
-            5 => {
-                record.sv_accuracy = values.get(0).copied().unwrap_or(0.0);
-                record.sv_health = values.get(1).copied().unwrap_or(0.0);
-                record.tgd = values.get(2).copied().unwrap_or(0.0);
-                record.iodc = values.get(3).copied().unwrap_or(0.0);
-            }
-            6 => {
-                record.transmission_time = values.get(0).copied().unwrap_or(0.0);
-                record.fit_interval = values.get(1).copied().unwrap_or(0.0);
-            }
-            _ => {}
-        }
-    }
-}
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::F64Ext;
+use crate::units::GpsSeconds;
+#[cfg(feature = "std")]
+use chrono::{DateTime, TimeZone, Utc};
+use core::fmt;
+use core::ops::{Add, Mul, Neg, Sub};
+#[cfg(feature = "std")]
+use log::{debug, warn};
+#[cfg(feature = "mmap")]
+use rayon::prelude::*;
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+#[cfg(feature = "std-fs")]
+use std::io::BufReader;
+#[cfg(feature = "std")]
+use std::ops::Range;
+#[cfg(feature = "std")]
+use std::str::FromStr;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+pub const OMEGA_E_DOT: f64 = 7.2921151467e-5; // WGS-84 earth rotation rate, rad/s
+pub const MU_EARTH: f64 = 398600.5e9; // Earth's gravitational constant
+pub const C_LIGHT: f64 = 299792458.0; // Speed of light, m/s
+pub const REL_F: f64 = -4.442807633e-10; // Relativistic clock correction constant, s/sqrt(m)
+pub const SECONDS_PER_WEEK: f64 = 604800.0;
+pub const L1_FREQUENCY: f64 = 1575.42e6; // GPS L1 carrier, Hz
+pub const WGS84_A: f64 = 6378137.0; // WGS-84 semi-major axis, m
+pub const WGS84_F: f64 = 1.0 / 298.257223563; // WGS-84 flattening
+pub const GPS_LEAP_SECONDS: f64 = 18.0; // GPS - UTC as of 2024
+pub const BDT_WEEK_OFFSET: f64 = 1356.0; // GPS week of BDT week 0, 2006-01-01
+pub const BDT_OFFSET: f64 = 14.0; // GPS - BDT, s
+pub const MU_EARTH_CGCS2000: f64 = 3.986004418e14; // BeiDou's gravitational constant
+pub const OMEGA_E_DOT_CGCS2000: f64 = 7.2921150e-5; // BeiDou's earth rotation rate, rad/s
+pub const MU_EARTH_GTRF: f64 = 3.986004418e14; // Galileo's gravitational constant
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ECEF {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl ECEF {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Geodetic latitude/longitude in degrees and height above the WGS-84 ellipsoid
+    pub fn to_lla(&self) -> LLA {
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let p = self.x.hypot(self.y);
+        let longitude = self.y.atan2(self.x);
+        let mut latitude = self.z.atan2(p * (1.0 - e2));
+        let mut altitude = 0.0;
+        for _ in 0..10 {
+            let sin_lat = latitude.sin();
+            let n = WGS84_A / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+            altitude = p * latitude.cos() + self.z * sin_lat - WGS84_A * WGS84_A / n;
+            let next = self.z.atan2(p * (1.0 - e2 * n / (n + altitude)));
+            if (next - latitude).abs() < 1e-14 {
+                latitude = next;
+                break;
+            }
+            latitude = next;
+        }
+        LLA {
+            latitude: latitude.to_degrees(),
+            longitude: longitude.to_degrees(),
+            altitude,
+        }
+    }
+
+    pub fn norm(&self) -> f64 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn dot(&self, other: &ECEF) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &ECEF) -> ECEF {
+        ECEF::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Straight-line distance, m
+    pub fn distance_to(&self, other: &ECEF) -> f64 {
+        (*other - *self).norm()
+    }
+
+    /// Within `tol_meters` of the other point, in 3D distance
+    pub fn approx_eq(&self, other: &ECEF, tol_meters: f64) -> bool {
+        self.distance_to(other) <= tol_meters
+    }
+}
+
+impl Add for ECEF {
+    type Output = ECEF;
+
+    fn add(self, other: ECEF) -> ECEF {
+        ECEF::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for ECEF {
+    type Output = ECEF;
+
+    fn sub(self, other: ECEF) -> ECEF {
+        ECEF::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f64> for ECEF {
+    type Output = ECEF;
+
+    fn mul(self, scale: f64) -> ECEF {
+        ECEF::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+impl Neg for ECEF {
+    type Output = ECEF;
+
+    fn neg(self) -> ECEF {
+        ECEF::new(-self.x, -self.y, -self.z)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LLA {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+impl LLA {
+    pub fn new(latitude: f64, longitude: f64, altitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+
+    pub fn to_ecef(&self) -> ECEF {
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let (sin_lat, cos_lat) = self.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.longitude.to_radians().sin_cos();
+        let n = WGS84_A / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        ECEF {
+            x: (n + self.altitude) * cos_lat * cos_lon,
+            y: (n + self.altitude) * cos_lat * sin_lon,
+            z: (n * (1.0 - e2) + self.altitude) * sin_lat,
+        }
+    }
+
+    /// East/north/up offset of an ECEF point from this observer
+    pub fn enu_to(&self, target: &ECEF) -> ENU {
+        self.rotate_to_enu(&(*target - self.to_ecef()))
+    }
+
+    /// Express an ECEF vector (an offset or a velocity) in the local frame at this point
+    pub fn rotate_to_enu(&self, d: &ECEF) -> ENU {
+        let (sin_lat, cos_lat) = self.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.longitude.to_radians().sin_cos();
+        ENU {
+            east: -sin_lon * d.x + cos_lon * d.y,
+            north: -sin_lat * cos_lon * d.x - sin_lat * sin_lon * d.y + cos_lat * d.z,
+            up: cos_lat * cos_lon * d.x + cos_lat * sin_lon * d.y + sin_lat * d.z,
+        }
+    }
+
+    /// Look angles and slant range from this observer to an ECEF point
+    pub fn aer_to(&self, target: &ECEF) -> AER {
+        self.enu_to(target).to_aer()
+    }
+
+    /// Straight-line distance between the two points through their ECEF positions, m
+    pub fn distance_to(&self, other: &LLA) -> f64 {
+        self.to_ecef().distance_to(&other.to_ecef())
+    }
+
+    /// Longitude difference to another point, wrapped into [-180, 180) degrees
+    pub fn longitude_difference(&self, other: &LLA) -> f64 {
+        (self.longitude - other.longitude + 180.0).rem_euclid(360.0) - 180.0
+    }
+
+    /// Latitude and longitude within `tol_deg`, the longitude compared across the
+    /// antimeridian, and altitude within `tol_alt_m`
+    pub fn approx_eq(&self, other: &LLA, tol_deg: f64, tol_alt_m: f64) -> bool {
+        (self.latitude - other.latitude).abs() <= tol_deg
+            && self.longitude_difference(other).abs() <= tol_deg
+            && (self.altitude - other.altitude).abs() <= tol_alt_m
+    }
+}
+
+/// Componentwise, in meters
+#[cfg(feature = "approx")]
+impl approx::AbsDiffEq for ECEF {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> f64 {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &ECEF, epsilon: f64) -> bool {
+        self.x.abs_diff_eq(&other.x, epsilon)
+            && self.y.abs_diff_eq(&other.y, epsilon)
+            && self.z.abs_diff_eq(&other.z, epsilon)
+    }
+}
+
+#[cfg(feature = "approx")]
+impl approx::RelativeEq for ECEF {
+    fn default_max_relative() -> f64 {
+        f64::default_max_relative()
+    }
+
+    fn relative_eq(&self, other: &ECEF, epsilon: f64, max_relative: f64) -> bool {
+        self.x.relative_eq(&other.x, epsilon, max_relative)
+            && self.y.relative_eq(&other.y, epsilon, max_relative)
+            && self.z.relative_eq(&other.z, epsilon, max_relative)
+    }
+}
+
+/// Componentwise with the one epsilon for degrees and meters, the longitude compared
+/// across the antimeridian
+#[cfg(feature = "approx")]
+impl approx::AbsDiffEq for LLA {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> f64 {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &LLA, epsilon: f64) -> bool {
+        self.latitude.abs_diff_eq(&other.latitude, epsilon)
+            && self.longitude_difference(other).abs() <= epsilon
+            && self.altitude.abs_diff_eq(&other.altitude, epsilon)
+    }
+}
+
+#[cfg(feature = "approx")]
+impl approx::RelativeEq for LLA {
+    fn default_max_relative() -> f64 {
+        f64::default_max_relative()
+    }
+
+    fn relative_eq(&self, other: &LLA, epsilon: f64, max_relative: f64) -> bool {
+        // This longitude taken to the other's side of the antimeridian
+        let longitude = other.longitude + self.longitude_difference(other);
+        self.latitude
+            .relative_eq(&other.latitude, epsilon, max_relative)
+            && longitude.relative_eq(&other.longitude, epsilon, max_relative)
+            && self
+                .altitude
+                .relative_eq(&other.altitude, epsilon, max_relative)
+    }
+}
+
+/// Local-level offsets from an observer, m
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ENU {
+    pub east: f64,
+    pub north: f64,
+    pub up: f64,
+}
+
+impl ENU {
+    pub fn to_aer(&self) -> AER {
+        let range = (self.east * self.east + self.north * self.north + self.up * self.up).sqrt();
+        AER {
+            azimuth: self.east.atan2(self.north).to_degrees().rem_euclid(360.0),
+            elevation: self.up.atan2(self.east.hypot(self.north)).to_degrees(),
+            range,
+        }
+    }
+}
+
+/// Azimuth (clockwise from north, [0, 360)) and elevation in degrees, slant range in m
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AER {
+    pub azimuth: f64,
+    pub elevation: f64,
+    pub range: f64,
+}
+
+/// Unit vector pointing from one point towards another, None if they coincide
+pub fn unit_line_of_sight(from: &ECEF, to: &ECEF) -> Option<ECEF> {
+    let los = *to - *from;
+    let distance = los.norm();
+    (distance > 0.0).then(|| los * (1.0 / distance))
+}
+
+pub fn range(from: &ECEF, to: &ECEF) -> f64 {
+    (*to - *from).norm()
+}
+
+/// Relative velocity projected on the receiver-to-satellite line of sight, m/s. Negative
+/// while the two approach; None if the positions coincide.
+pub fn range_rate(
+    receiver_position: &ECEF,
+    receiver_velocity: &ECEF,
+    satellite_position: &ECEF,
+    satellite_velocity: &ECEF,
+) -> Option<f64> {
+    let unit = unit_line_of_sight(receiver_position, satellite_position)?;
+    Some(unit.dot(&(*satellite_velocity - *receiver_velocity)))
+}
+
+/// Satellite state at one epoch. Series of states live in `StateSeries`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State {
+    pub time: f64, // GPS seconds, see `epoch`
+    pub position: ECEF,
+    pub velocity: Option<ECEF>, // Only set when velocity output is requested
+    pub clock_bias: Option<f64>, // SV clock offset in seconds, only set when requested
+    pub kepler_converged: bool,
+    pub ephemeris_age: f64,    // Epoch minus toe of the record used, s
+    pub extrapolated: bool,    // Ephemeris age beyond the configured maximum
+    pub accuracy: Option<f64>, // 1-sigma position accuracy from URA/SISA, m
+}
+
+impl State {
+    pub fn new(time: f64, position: ECEF) -> Self {
+        Self {
+            time,
+            position,
+            velocity: None,
+            clock_bias: None,
+            kepler_converged: true,
+            ephemeris_age: 0.0,
+            extrapolated: false,
+            accuracy: None,
+        }
+    }
+
+    pub fn epoch(&self) -> GpsTime {
+        GpsTime::from_seconds(self.time)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn time_utc(&self) -> DateTime<Utc> {
+        gps_seconds_to_utc(self.time)
+    }
+}
+
+/// ISO 8601 UTC timestamp and ECEF position in meters
+#[cfg(feature = "std")]
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_epoch_position(f, self.epoch(), self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+fn fmt_epoch_position(f: &mut fmt::Formatter, epoch: GpsTime, position: ECEF) -> fmt::Result {
+    write!(
+        f,
+        "{} ({:.3}, {:.3}, {:.3}) m",
+        epoch, position.x, position.y, position.z
+    )
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new(0.0, ECEF::default())
+    }
+}
+
+/// States of one satellite in columns, one entry per epoch in time order, so a series
+/// takes a handful of allocations however long it is. The velocity and clock columns are
+/// either complete or empty, as for states propagated without them.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "StateSeriesRaw"))]
+pub struct StateSeries {
+    time: Vec<f64>,
+    position: Vec<ECEF>,
+    velocity: Vec<ECEF>,
+    clock_bias: Vec<f64>,
+    kepler_converged: Vec<bool>,
+    ephemeris_age: Vec<f64>,
+    extrapolated: Vec<bool>,
+    accuracy: Vec<Option<f64>>,
+}
+
+/// Deserialized `StateSeries` before its columns are checked to line up
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(serde::Deserialize)]
+struct StateSeriesRaw {
+    time: Vec<f64>,
+    position: Vec<ECEF>,
+    velocity: Vec<ECEF>,
+    clock_bias: Vec<f64>,
+    kepler_converged: Vec<bool>,
+    ephemeris_age: Vec<f64>,
+    extrapolated: Vec<bool>,
+    accuracy: Vec<Option<f64>>,
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+impl TryFrom<StateSeriesRaw> for StateSeries {
+    type Error = String;
+
+    /// Every column holds one entry per epoch, but velocity and clock may be empty
+    fn try_from(raw: StateSeriesRaw) -> Result<Self, Self::Error> {
+        let len = raw.time.len();
+        let columns = [
+            ("position", raw.position.len(), false),
+            ("velocity", raw.velocity.len(), true),
+            ("clock_bias", raw.clock_bias.len(), true),
+            ("kepler_converged", raw.kepler_converged.len(), false),
+            ("ephemeris_age", raw.ephemeris_age.len(), false),
+            ("extrapolated", raw.extrapolated.len(), false),
+            ("accuracy", raw.accuracy.len(), false),
+        ];
+        for (name, found, optional) in columns {
+            if found != len && !(optional && found == 0) {
+                return Err(format!("{} has {} entries for {} epochs", name, found, len));
+            }
+        }
+        Ok(Self {
+            time: raw.time,
+            position: raw.position,
+            velocity: raw.velocity,
+            clock_bias: raw.clock_bias,
+            kepler_converged: raw.kepler_converged,
+            ephemeris_age: raw.ephemeris_age,
+            extrapolated: raw.extrapolated,
+            accuracy: raw.accuracy,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl StateSeries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    /// Drop every state, keeping the columns' allocations
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.time.truncate(len);
+        self.position.truncate(len);
+        self.velocity.truncate(len);
+        self.clock_bias.truncate(len);
+        self.kepler_converged.truncate(len);
+        self.ephemeris_age.truncate(len);
+        self.extrapolated.truncate(len);
+        self.accuracy.truncate(len);
+    }
+
+    /// Append a state; its velocity or clock offset is kept only while every state has one
+    pub fn push(&mut self, state: &State) {
+        let len = self.len();
+        match state.velocity {
+            Some(velocity) if self.velocity.len() == len => self.velocity.push(velocity),
+            _ => self.velocity.clear(),
+        }
+        match state.clock_bias {
+            Some(clock_bias) if self.clock_bias.len() == len => self.clock_bias.push(clock_bias),
+            _ => self.clock_bias.clear(),
+        }
+        self.time.push(state.time);
+        self.position.push(state.position);
+        self.kepler_converged.push(state.kepler_converged);
+        self.ephemeris_age.push(state.ephemeris_age);
+        self.extrapolated.push(state.extrapolated);
+        self.accuracy.push(state.accuracy);
+    }
+
+    pub fn get(&self, index: usize) -> Option<StateRef<'_>> {
+        (index < self.len()).then_some(StateRef {
+            series: self,
+            index,
+        })
+    }
+
+    pub fn first(&self) -> Option<StateRef<'_>> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<StateRef<'_>> {
+        self.len().checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    pub fn iter(&self) -> StateIter<'_> {
+        self.range(0..self.len())
+    }
+
+    /// States at these indices, cut to the series
+    pub fn range(&self, indices: Range<usize>) -> StateIter<'_> {
+        let end = indices.end.min(self.len());
+        StateIter {
+            series: self,
+            indices: indices.start.min(end)..end,
+        }
+    }
+
+    /// Every state in the one-`State`-per-epoch shape, for code written against it
+    pub fn iter_states(&self) -> impl ExactSizeIterator<Item = State> + '_ {
+        self.iter().map(|state| state.to_state())
+    }
+
+    /// GPS seconds of each state
+    pub fn times(&self) -> &[f64] {
+        &self.time
+    }
+
+    pub fn times_gps(&self) -> Vec<GpsTime> {
+        self.time
+            .iter()
+            .copied()
+            .map(GpsTime::from_seconds)
+            .collect()
+    }
+
+    pub fn times_utc(&self) -> Vec<DateTime<Utc>> {
+        self.time.iter().copied().map(gps_seconds_to_utc).collect()
+    }
+
+    pub fn positions(&self) -> &[ECEF] {
+        &self.position
+    }
+
+    /// Empty unless velocities were propagated
+    pub fn velocities(&self) -> &[ECEF] {
+        &self.velocity
+    }
+
+    /// SV clock offsets in seconds, empty unless they were propagated
+    pub fn clock_biases(&self) -> &[f64] {
+        &self.clock_bias
+    }
+
+    pub fn kepler_converged(&self) -> &[bool] {
+        &self.kepler_converged
+    }
+
+    pub fn ephemeris_ages(&self) -> &[f64] {
+        &self.ephemeris_age
+    }
+
+    pub fn extrapolated(&self) -> &[bool] {
+        &self.extrapolated
+    }
+
+    pub fn accuracies(&self) -> &[Option<f64>] {
+        &self.accuracy
+    }
+
+    /// The columns of `len` states after the first `kept`, to be filled in place; the
+    /// series is cut or grown to `kept + len` states, and the velocity and clock columns
+    /// cleared unless both the kept states and the new ones have them
+    pub(crate) fn columns_after(
+        &mut self,
+        kept: usize,
+        len: usize,
+        velocity: bool,
+        clock_bias: bool,
+    ) -> StateColumnsMut<'_> {
+        debug_assert!(kept <= self.len());
+        // Resizing in place leaves the states past `kept` to be overwritten, not refilled
+        let total = kept + len;
+        let optional_len = |column_len: usize, wanted: bool| {
+            if wanted && column_len >= kept {
+                total
+            } else {
+                0
+            }
+        };
+        let velocity_len = optional_len(self.velocity.len(), velocity);
+        let clock_bias_len = optional_len(self.clock_bias.len(), clock_bias);
+        self.time.resize(total, 0.0);
+        self.position.resize(total, ECEF::default());
+        self.velocity.resize(velocity_len, ECEF::default());
+        self.clock_bias.resize(clock_bias_len, 0.0);
+        self.kepler_converged.resize(total, true);
+        self.ephemeris_age.resize(total, 0.0);
+        self.extrapolated.resize(total, false);
+        self.accuracy.resize(total, None);
+        StateColumnsMut {
+            time: &mut self.time[kept..],
+            position: &mut self.position[kept..],
+            velocity: self.velocity.get_mut(kept..).unwrap_or_default(),
+            clock_bias: self.clock_bias.get_mut(kept..).unwrap_or_default(),
+            kepler_converged: &mut self.kepler_converged[kept..],
+            ephemeris_age: &mut self.ephemeris_age[kept..],
+            extrapolated: &mut self.extrapolated[kept..],
+            accuracy: &mut self.accuracy[kept..],
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromIterator<State> for StateSeries {
+    fn from_iter<I: IntoIterator<Item = State>>(states: I) -> Self {
+        let mut series = Self::new();
+        for state in states {
+            series.push(&state);
+        }
+        series
+    }
+}
+
+/// Epoch and position of each state, so `for (epoch, position) in &series` works
+#[cfg(feature = "std")]
+impl<'a> IntoIterator for &'a StateSeries {
+    type Item = (GpsTime, ECEF);
+    type IntoIter = core::iter::Map<
+        core::iter::Zip<core::slice::Iter<'a, f64>, core::slice::Iter<'a, ECEF>>,
+        fn((&f64, &ECEF)) -> (GpsTime, ECEF),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.time
+            .iter()
+            .zip(&self.position)
+            .map(|(&time, &position)| (GpsTime::from_seconds(time), position))
+    }
+}
+
+/// One state of a `StateSeries`, read from its columns
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+pub struct StateRef<'a> {
+    series: &'a StateSeries,
+    index: usize,
+}
+
+#[cfg(feature = "std")]
+impl StateRef<'_> {
+    /// Position of the state in its series
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// GPS seconds
+    pub fn time(&self) -> f64 {
+        self.series.time[self.index]
+    }
+
+    pub fn epoch(&self) -> GpsTime {
+        GpsTime::from_seconds(self.time())
+    }
+
+    pub fn time_utc(&self) -> DateTime<Utc> {
+        gps_seconds_to_utc(self.time())
+    }
+
+    pub fn position(&self) -> ECEF {
+        self.series.position[self.index]
+    }
+
+    pub fn velocity(&self) -> Option<ECEF> {
+        self.series.velocity.get(self.index).copied()
+    }
+
+    /// SV clock offset in seconds
+    pub fn clock_bias(&self) -> Option<f64> {
+        self.series.clock_bias.get(self.index).copied()
+    }
+
+    pub fn kepler_converged(&self) -> bool {
+        self.series.kepler_converged[self.index]
+    }
+
+    /// Epoch minus toe of the record used, s
+    pub fn ephemeris_age(&self) -> f64 {
+        self.series.ephemeris_age[self.index]
+    }
+
+    /// Ephemeris age beyond the configured maximum
+    pub fn extrapolated(&self) -> bool {
+        self.series.extrapolated[self.index]
+    }
+
+    /// 1-sigma position accuracy from URA/SISA, m
+    pub fn accuracy(&self) -> Option<f64> {
+        self.series.accuracy[self.index]
+    }
+
+    /// Copy out as a standalone value
+    pub fn to_state(&self) -> State {
+        State {
+            time: self.time(),
+            position: self.position(),
+            velocity: self.velocity(),
+            clock_bias: self.clock_bias(),
+            kepler_converged: self.kepler_converged(),
+            ephemeris_age: self.ephemeris_age(),
+            extrapolated: self.extrapolated(),
+            accuracy: self.accuracy(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for StateRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StateRef")
+            .field("index", &self.index)
+            .field("time", &self.time())
+            .field("position", &self.position())
+            .field("velocity", &self.velocity())
+            .field("clock_bias", &self.clock_bias())
+            .field("kepler_converged", &self.kepler_converged())
+            .field("ephemeris_age", &self.ephemeris_age())
+            .field("extrapolated", &self.extrapolated())
+            .field("accuracy", &self.accuracy())
+            .finish()
+    }
+}
+
+/// ISO 8601 UTC timestamp and ECEF position in meters
+#[cfg(feature = "std")]
+impl fmt::Display for StateRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_epoch_position(f, self.epoch(), self.position())
+    }
+}
+
+/// States of a `StateSeries` in order, from `StateSeries::iter` or `StateSeries::range`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct StateIter<'a> {
+    series: &'a StateSeries,
+    indices: Range<usize>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Iterator for StateIter<'a> {
+    type Item = StateRef<'a>;
+
+    fn next(&mut self) -> Option<StateRef<'a>> {
+        let index = self.indices.next()?;
+        Some(StateRef {
+            series: self.series,
+            index,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+#[cfg(feature = "std")]
+impl DoubleEndedIterator for StateIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = self.indices.next_back()?;
+        Some(StateRef {
+            series: self.series,
+            index,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl ExactSizeIterator for StateIter<'_> {}
+
+/// Mutable columns of a run of states in a `StateSeries`, filled in place by propagation.
+/// The velocity and clock columns are empty when the series goes without them.
+#[cfg(feature = "std")]
+pub(crate) struct StateColumnsMut<'a> {
+    pub time: &'a mut [f64],
+    pub position: &'a mut [ECEF],
+    pub velocity: &'a mut [ECEF],
+    pub clock_bias: &'a mut [f64],
+    pub kepler_converged: &'a mut [bool],
+    pub ephemeris_age: &'a mut [f64],
+    pub extrapolated: &'a mut [bool],
+    pub accuracy: &'a mut [Option<f64>],
+}
+
+#[cfg(feature = "std")]
+impl<'a> StateColumnsMut<'a> {
+    /// Columns of length one over a single state, filling its velocity and clock offset
+    /// only when asked for
+    pub fn from_state(state: &'a mut State, velocity: bool, clock_bias: bool) -> Self {
+        state.velocity = velocity.then(ECEF::default);
+        state.clock_bias = clock_bias.then_some(0.0);
+        Self {
+            time: core::slice::from_mut(&mut state.time),
+            position: core::slice::from_mut(&mut state.position),
+            velocity: state
+                .velocity
+                .as_mut()
+                .map(core::slice::from_mut)
+                .unwrap_or_default(),
+            clock_bias: state
+                .clock_bias
+                .as_mut()
+                .map(core::slice::from_mut)
+                .unwrap_or_default(),
+            kepler_converged: core::slice::from_mut(&mut state.kepler_converged),
+            ephemeris_age: core::slice::from_mut(&mut state.ephemeris_age),
+            extrapolated: core::slice::from_mut(&mut state.extrapolated),
+            accuracy: core::slice::from_mut(&mut state.accuracy),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    /// The first `mid` states and the rest
+    pub fn split_at(self, mid: usize) -> (Self, Self) {
+        fn split<T>(column: &mut [T], mid: usize) -> (&mut [T], &mut [T]) {
+            column.split_at_mut(mid.min(column.len()))
+        }
+        let (time, time_rest) = split(self.time, mid);
+        let (position, position_rest) = split(self.position, mid);
+        let (velocity, velocity_rest) = split(self.velocity, mid);
+        let (clock_bias, clock_bias_rest) = split(self.clock_bias, mid);
+        let (kepler_converged, kepler_converged_rest) = split(self.kepler_converged, mid);
+        let (ephemeris_age, ephemeris_age_rest) = split(self.ephemeris_age, mid);
+        let (extrapolated, extrapolated_rest) = split(self.extrapolated, mid);
+        let (accuracy, accuracy_rest) = split(self.accuracy, mid);
+        (
+            Self {
+                time,
+                position,
+                velocity,
+                clock_bias,
+                kepler_converged,
+                ephemeris_age,
+                extrapolated,
+                accuracy,
+            },
+            Self {
+                time: time_rest,
+                position: position_rest,
+                velocity: velocity_rest,
+                clock_bias: clock_bias_rest,
+                kepler_converged: kepler_converged_rest,
+                ephemeris_age: ephemeris_age_rest,
+                extrapolated: extrapolated_rest,
+                accuracy: accuracy_rest,
+            },
+        )
+    }
+}
+
+/// Upper bounds of the GPS/QZSS URA index table (IS-GPS-200 20.3.3.3.1.3), m
+const URA_TABLE: [f64; 15] = [
+    2.4, 3.4, 4.85, 6.85, 9.65, 13.65, 24.0, 48.0, 96.0, 192.0, 384.0, 768.0, 1536.0, 3072.0,
+    6144.0,
+];
+
+/// Accuracy bound for a URA index, None for index 15 (no prediction, use at own risk)
+pub fn ura_index_to_meters(index: u8) -> Option<f64> {
+    URA_TABLE.get(index as usize).copied()
+}
+
+/// GPS week number and seconds of week of a time in seconds since the GPS epoch
+pub fn gps_week_seconds(gps_seconds: f64) -> (u32, f64) {
+    let week = (gps_seconds / SECONDS_PER_WEEK).floor();
+    (week as u32, gps_seconds - week * SECONDS_PER_WEEK)
+}
+
+/// Seconds since the GPS epoch of a week number and seconds of week
+pub fn gps_seconds_from_week(week: u32, seconds_of_week: f64) -> f64 {
+    week as f64 * SECONDS_PER_WEEK + seconds_of_week
+}
+
+/// Calculate GPS time: milliseconds since GPS epoch (Jan 6, 1980) plus leap seconds
+#[cfg(feature = "std")]
+pub fn calculate_gps_time(time: std::time::SystemTime) -> f64 {
+    gps_seconds(time.into()) * 1000.0
+}
+
+/// Seconds since the GPS epoch of a UTC epoch, without going through `SystemTime`
+#[cfg(feature = "std")]
+pub fn gps_seconds(epoch: DateTime<Utc>) -> f64 {
+    let gps_epoch: DateTime<Utc> = Utc.with_ymd_and_hms(1980, 1, 6, 0, 0, 0).unwrap();
+    (epoch - gps_epoch).num_microseconds().unwrap() as f64 / 1e6 + GPS_LEAP_SECONDS
+}
+
+/// Inverse of `gps_seconds`
+#[cfg(feature = "std")]
+pub fn gps_seconds_to_utc(gps_seconds: f64) -> DateTime<Utc> {
+    let gps_epoch: DateTime<Utc> = Utc.with_ymd_and_hms(1980, 1, 6, 0, 0, 0).unwrap();
+    let micros = ((gps_seconds - GPS_LEAP_SECONDS) * 1e6).round() as i64;
+    gps_epoch + chrono::Duration::microseconds(micros)
+}
+
+/// Instant on the GPS time scale, seconds since 1980-01-06. Formats as an ISO 8601 UTC
+/// timestamp, leap seconds applied.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct GpsTime(f64);
+
+impl GpsTime {
+    pub fn from_seconds(gps_seconds: f64) -> Self {
+        Self(gps_seconds)
+    }
+
+    pub fn from_week_seconds(week: u32, seconds_of_week: f64) -> Self {
+        Self(gps_seconds_from_week(week, seconds_of_week))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_utc(epoch: DateTime<Utc>) -> Self {
+        Self(gps_seconds(epoch))
+    }
+
+    pub fn seconds(self) -> f64 {
+        self.0
+    }
+
+    pub fn total_cmp(&self, other: &GpsTime) -> core::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+
+    /// GPS week number and seconds of week
+    pub fn week_seconds(self) -> (u32, f64) {
+        gps_week_seconds(self.0)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn to_utc(self) -> DateTime<Utc> {
+        gps_seconds_to_utc(self.0)
+    }
+}
+
+impl Add<GpsSeconds> for GpsTime {
+    type Output = GpsTime;
+
+    fn add(self, span: GpsSeconds) -> GpsTime {
+        GpsTime(self.0 + span.as_f64())
+    }
+}
+
+impl Sub<GpsSeconds> for GpsTime {
+    type Output = GpsTime;
+
+    fn sub(self, span: GpsSeconds) -> GpsTime {
+        GpsTime(self.0 - span.as_f64())
+    }
+}
+
+/// Span between two instants
+impl Sub for GpsTime {
+    type Output = GpsSeconds;
+
+    fn sub(self, other: GpsTime) -> GpsSeconds {
+        GpsSeconds(self.0 - other.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<DateTime<Utc>> for GpsTime {
+    fn from(epoch: DateTime<Utc>) -> Self {
+        Self::from_utc(epoch)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<GpsTime> for DateTime<Utc> {
+    fn from(time: GpsTime) -> Self {
+        time.to_utc()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for GpsTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_utc().format("%Y-%m-%dT%H:%M:%S%.3fZ"))
+    }
+}
+
+/// GNSS a satellite belongs to, identified in RINEX by a single system character
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constellation {
+    #[default]
+    Gps,
+    Glonass,
+    Galileo,
+    BeiDou,
+    Qzss,
+    Irnss,
+    Sbas,
+}
+
+impl Constellation {
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'G' | ' ' => Some(Self::Gps),
+            'R' => Some(Self::Glonass),
+            'E' => Some(Self::Galileo),
+            'C' => Some(Self::BeiDou),
+            'J' => Some(Self::Qzss),
+            'I' => Some(Self::Irnss),
+            'S' => Some(Self::Sbas),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Self::Gps => 'G',
+            Self::Glonass => 'R',
+            Self::Galileo => 'E',
+            Self::BeiDou => 'C',
+            Self::Qzss => 'J',
+            Self::Irnss => 'I',
+            Self::Sbas => 'S',
+        }
+    }
+
+    /// Whether its broadcast ephemeris is a set of Keplerian elements, which the broadcast
+    /// orbit model evaluates; GLONASS and SBAS broadcast state vectors instead
+    pub fn has_keplerian_ephemeris(self) -> bool {
+        !matches!(self, Self::Glonass | Self::Sbas)
+    }
+
+    /// Earth's gravitational constant of the system's broadcast orbit model, m^3/s^2
+    pub fn gravitational_constant(self) -> f64 {
+        match self {
+            Self::BeiDou => MU_EARTH_CGCS2000,
+            Self::Galileo => MU_EARTH_GTRF,
+            _ => MU_EARTH,
+        }
+    }
+
+    /// Earth rotation rate of the system's broadcast orbit model, rad/s
+    pub fn earth_rotation_rate(self) -> f64 {
+        match self {
+            Self::BeiDou => OMEGA_E_DOT_CGCS2000,
+            _ => OMEGA_E_DOT,
+        }
+    }
+
+    /// Number of broadcast orbit lines following the epoch line of a RINEX 3 nav record
+    #[cfg(feature = "std")]
+    fn nav_record_lines(self) -> usize {
+        match self {
+            Self::Glonass | Self::Sbas => 3,
+            _ => 7,
+        }
+    }
+}
+
+/// Satellite identifier that stays unique across constellations, e.g. "G17" or "R05"
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Clone, Copy)]
+pub struct SatId {
+    pub constellation: Constellation,
+    pub prn: u8,
+}
+
+impl SatId {
+    pub fn new(constellation: Constellation, prn: u8) -> Self {
+        Self { constellation, prn }
+    }
+
+    pub fn gps(prn: u8) -> Self {
+        Self::new(Constellation::Gps, prn)
+    }
+
+    /// A BeiDou GEO satellite, C01-C05 or C59-C63, whose orbit the BDS ICD evaluates in
+    /// an inclined frame
+    pub fn is_beidou_geo(self) -> bool {
+        self.constellation == Constellation::BeiDou && matches!(self.prn, 1..=5 | 59..=63)
+    }
+}
+
+/// Bare PRNs are taken to be GPS satellites
+impl From<u8> for SatId {
+    fn from(prn: u8) -> Self {
+        Self::gps(prn)
+    }
+}
+
+impl fmt::Display for SatId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{:02}", self.constellation.to_char(), self.prn)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSatIdError(pub String);
+
+#[cfg(feature = "std")]
+impl fmt::Display for ParseSatIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid satellite id {:?}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseSatIdError {}
+
+#[cfg(feature = "std")]
+impl FromStr for SatId {
+    type Err = ParseSatIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseSatIdError(s.to_string());
+        let mut chars = s.chars();
+        let constellation = chars
+            .next()
+            .and_then(Constellation::from_char)
+            .ok_or_else(err)?;
+        let prn = chars.as_str().trim().parse().map_err(|_| err())?;
+        Ok(Self::new(constellation, prn))
+    }
+}
+
+/// Serialized as its RINEX name, e.g. "G17", so it can key JSON maps
+#[cfg(feature = "serde")]
+impl serde::Serialize for SatId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SatId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavRecord {
+    pub sat_id: SatId,
+    pub epoch: (i32, i32, i32, i32, i32, i32),
+    pub gps_millis: f64,
+    pub sv_clock_bias: f64,
+    pub sv_clock_drift: f64,
+    pub sv_clock_drift_rate: f64,
+    pub iode: f64,
+    pub crs: f64,
+    pub delta_n: f64,
+    pub m0: f64,
+    pub cuc: f64,
+    pub eccentricity: f64,
+    pub cus: f64,
+    pub sqrt_a: f64,
+    pub toe: f64,
+    pub cic: f64,
+    pub omega0: f64,
+    pub cis: f64,
+    pub i0: f64,
+    pub crc: f64,
+    pub omega: f64,
+    pub omega_dot: f64,
+    pub idot: f64,
+    pub codes_on_l2_channel: f64,
+    pub gps_week: f64,
+    pub l2_p_data_flag: f64,
+    pub sv_accuracy: f64,
+    pub sv_health: f64,
+    pub tgd: f64,
+    pub iodc: f64,
+    pub transmission_time: f64,
+    pub fit_interval: f64,
+}
+
+impl NavRecord {
+    /// Time of ephemeris as seconds since the GPS epoch
+    pub fn toe_gps_seconds(&self) -> f64 {
+        self.toe_epoch().seconds()
+    }
+
+    /// Time of clock as seconds since the GPS epoch
+    pub fn toc_gps_seconds(&self) -> f64 {
+        self.toc_epoch().seconds()
+    }
+
+    /// Time of ephemeris, from the week and seconds of week the record carries
+    pub fn toe_epoch(&self) -> GpsTime {
+        GpsTime(self.week_seconds_to_gps(self.toe))
+    }
+
+    /// Seconds since the GPS epoch of `seconds` into the record's week, in the time scale
+    /// of its system: BeiDou counts BDT weeks from 2006, the others GPS weeks
+    fn week_seconds_to_gps(&self, seconds: f64) -> f64 {
+        match self.sat_id.constellation {
+            Constellation::BeiDou => {
+                (self.gps_week + BDT_WEEK_OFFSET) * SECONDS_PER_WEEK + seconds + BDT_OFFSET
+            }
+            _ => self.gps_week * SECONDS_PER_WEEK + seconds,
+        }
+    }
+
+    /// Time of clock, from the record's epoch in milliseconds
+    pub fn toc_epoch(&self) -> GpsTime {
+        GpsTime::default() + GpsSeconds::from_millis(self.gps_millis)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.sv_health == 0.0
+    }
+
+    /// Semi-major axis in meters
+    pub fn semi_major_axis(&self) -> f64 {
+        self.sqrt_a * self.sqrt_a
+    }
+
+    /// Corrected mean motion n0 + delta_n, rad/s
+    pub fn mean_motion(&self) -> f64 {
+        let mu = self.sat_id.constellation.gravitational_constant();
+        (mu / self.semi_major_axis().powi(3)).sqrt() + self.delta_n
+    }
+
+    /// Orbital period in seconds from the corrected mean motion
+    pub fn orbital_period(&self) -> f64 {
+        2.0 * core::f64::consts::PI / self.mean_motion()
+    }
+
+    pub fn apogee_radius(&self) -> f64 {
+        self.semi_major_axis() * (1.0 + self.eccentricity)
+    }
+
+    pub fn perigee_radius(&self) -> f64 {
+        self.semi_major_axis() * (1.0 - self.eccentricity)
+    }
+
+    /// Apogee height above the WGS-84 equatorial radius
+    pub fn apogee_altitude(&self) -> f64 {
+        self.apogee_radius() - WGS84_A
+    }
+
+    /// Perigee height above the WGS-84 equatorial radius
+    pub fn perigee_altitude(&self) -> f64 {
+        self.perigee_radius() - WGS84_A
+    }
+
+    /// URA index behind the nominal meters value RINEX stores for GPS/QZSS records
+    pub fn ura_index(&self) -> u8 {
+        let nominal = |n: u8| match n {
+            0..=6 => (2.0f64.powf(1.0 + n as f64 / 2.0) * 10.0).round() / 10.0,
+            _ => 2.0f64.powi(n as i32 - 2),
+        };
+        (0..15)
+            .find(|&n| self.sv_accuracy <= nominal(n))
+            .unwrap_or(15)
+    }
+
+    /// 1-sigma signal-in-space accuracy in meters: URA table for GPS/QZSS, SISA as
+    /// broadcast for Galileo, the raw value elsewhere. None when no prediction is available.
+    pub fn ura_meters(&self) -> Option<f64> {
+        match self.sat_id.constellation {
+            Constellation::Gps | Constellation::Qzss => ura_index_to_meters(self.ura_index()),
+            // SISA 255 (-1 in some files) means no accuracy prediction available
+            Constellation::Galileo if self.sv_accuracy < 0.0 || self.sv_accuracy >= 255.0 => None,
+            _ if self.sv_accuracy < 0.0 => None,
+            _ => Some(self.sv_accuracy),
+        }
+    }
+
+    /// Rejects records that cannot describe a real orbit, e.g. from a misparsed line
+    pub fn is_plausible(&self) -> bool {
+        self.sqrt_a.is_finite()
+            && (0.0..1.0).contains(&self.eccentricity)
+            && self.semi_major_axis() > WGS84_A
+            && self.perigee_radius() > WGS84_A
+    }
+}
+
+/// Parsed nav records in canonical order: by constellation, PRN, ephemeris time (toe, or
+/// toc for GLONASS and SBAS) and IODE, then transmission time. Every constructor and
+/// `merge` restore it, so the same records come out in the same order whether they were
+/// read from one file, a string, parallel chunks or several merged files, and one
+/// satellite's records can be borrowed as a slice.
+#[cfg(feature = "std")]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "NavFields"))]
+pub struct RinexNav {
+    records: Vec<NavRecord>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub leap_seconds: Option<i32>, // From the LEAP SECONDS header line
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sources: Vec<String>, // Files the records were read from, in merge order
+}
+
+/// Deserialized `RinexNav` before the canonical order is restored
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(serde::Deserialize)]
+struct NavFields {
+    records: Vec<NavRecord>,
+    #[serde(default)]
+    leap_seconds: Option<i32>,
+    #[serde(default)]
+    sources: Vec<String>,
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+impl From<NavFields> for RinexNav {
+    fn from(fields: NavFields) -> Self {
+        let mut nav = Self {
+            records: fields.records,
+            leap_seconds: fields.leap_seconds,
+            sources: fields.sources,
+        };
+        nav.sort_canonical();
+        nav
+    }
+}
+
+#[cfg(feature = "std")]
+impl RinexNav {
+    /// With the `mmap` feature the file is memory-mapped and parsed in parallel chunks,
+    /// with the same result as the sequential parse. Fails only if the file cannot be
+    /// opened; malformed records are skipped as in `from_reader`.
+    #[cfg(feature = "std-fs")]
+    pub fn from_file(filename: &str) -> std::io::Result<Self> {
+        Self::from_file_filtered(filename, |_| true)
+    }
+
+    /// `from_file` keeping only the records of satellites the filter accepts. Other
+    /// records are recognized by their first line and their data lines passed over
+    /// unparsed, so the records kept equal those of the full parse.
+    #[cfg(feature = "std-fs")]
+    pub fn from_file_filtered(
+        filename: &str,
+        filter: impl Fn(SatId) -> bool + Sync,
+    ) -> std::io::Result<Self> {
+        let mut nav = Self::parse_file(filename, filter)?;
+        nav.sources.push(filename.to_string());
+        Ok(nav)
+    }
+
+    #[cfg(feature = "std-fs")]
+    fn parse_file(filename: &str, filter: impl Fn(SatId) -> bool + Sync) -> std::io::Result<Self> {
+        let file = File::open(filename)?;
+        #[cfg(feature = "mmap")]
+        {
+            // SAFETY: the map is only read while parsing; a file truncated meanwhile by
+            // another process is outside what this reader guards against
+            match unsafe { memmap2::Mmap::map(&file) } {
+                Ok(map) => return Ok(Self::from_bytes_parallel(&map, &filter)),
+                Err(error) => debug!(
+                    "{}: not memory-mapped ({}), read sequentially",
+                    filename, error
+                ),
+            }
+        }
+        Ok(Self::from_reader_filtered(BufReader::new(file), filter))
+    }
+
+    /// Several nav files read and combined with `merge`, e.g. consecutive daily files;
+    /// fails on the first that cannot be opened
+    #[cfg(feature = "std-fs")]
+    pub fn merge_files(filenames: &[&str]) -> std::io::Result<Self> {
+        filenames
+            .iter()
+            .try_fold(Self::default(), |merged, filename| {
+                Ok(merged.merge(Self::from_file(filename)?))
+            })
+    }
+
+    /// Records of both, with an ephemeris broadcast in both kept once, sorted by satellite
+    /// and time. Where the leap seconds disagree, the value of the input with the earlier
+    /// first record is kept.
+    pub fn merge(mut self, other: RinexNav) -> Self {
+        self.leap_seconds = match (self.leap_seconds, other.leap_seconds) {
+            (Some(own), Some(others)) if own != others => {
+                let first_toc = |nav: &RinexNav| {
+                    nav.records
+                        .iter()
+                        .map(|record| record.gps_millis)
+                        .min_by(f64::total_cmp)
+                };
+                let earlier = match (first_toc(&self), first_toc(&other)) {
+                    (Some(own_first), Some(others_first)) if others_first < own_first => others,
+                    (None, Some(_)) => others,
+                    _ => own,
+                };
+                warn!(
+                    "merged nav files give {} and {} leap seconds, keeping {}",
+                    own, others, earlier
+                );
+                Some(earlier)
+            }
+            (own, others) => own.or(others),
+        };
+        self.sources.extend(other.sources);
+        self.records.extend(other.records);
+        self.sort_canonical();
+        self.deduplicate();
+        self
+    }
+
+    /// Drop repeated broadcasts of one ephemeris, the same satellite, toe and IODE (toc for
+    /// GLONASS and SBAS), keeping the earliest transmitted
+    pub fn deduplicate(&mut self) {
+        let before = self.records.len();
+        self.records.dedup_by(|later, earlier| {
+            let (later, earlier) = (canonical_key(later), canonical_key(earlier));
+            (later.0, later.1, later.2) == (earlier.0, earlier.1, earlier.2)
+        });
+        debug!(
+            "{} duplicate nav records dropped",
+            before - self.records.len()
+        );
+    }
+
+    /// Parse RINEX navigation text from any buffered source, such as an in-memory upload.
+    /// Malformed records are skipped and unreadable values read as zero, each with a
+    /// warning naming the line.
+    pub fn from_reader(reader: impl BufRead) -> Self {
+        Self::from_reader_filtered(reader, |_| true)
+    }
+
+    /// `from_reader` keeping only the records of satellites the filter accepts, as
+    /// `from_file_filtered`
+    pub fn from_reader_filtered(reader: impl BufRead, filter: impl Fn(SatId) -> bool) -> Self {
+        let started = Instant::now();
+        let mut records = Vec::new();
+        let mut lines = NavLines::new(reader, 0);
+        if !lines.skip_header() {
+            warn!("nav input has no END OF HEADER line");
+        }
+        Self::parse_records(&mut lines, &mut records, &filter);
+        Self::finish(records, lines.leap_seconds, started)
+    }
+
+    /// Whole nav file in memory, split at record boundaries into chunks parsed in parallel
+    /// and concatenated in file order
+    #[cfg(feature = "mmap")]
+    fn from_bytes_parallel(bytes: &[u8], filter: &(impl Fn(SatId) -> bool + Sync)) -> Self {
+        let chunk_len =
+            |body_len: usize| (body_len / (4 * rayon::current_num_threads())).max(MIN_CHUNK_LEN);
+        Self::from_bytes_chunked(bytes, filter, chunk_len)
+    }
+
+    /// `from_bytes_parallel` with chunks of at least `chunk_len(body length)` bytes
+    #[cfg(feature = "mmap")]
+    fn from_bytes_chunked(
+        bytes: &[u8],
+        filter: &(impl Fn(SatId) -> bool + Sync),
+        chunk_len: impl Fn(usize) -> usize,
+    ) -> Self {
+        let started = Instant::now();
+        let mut lines = NavLines::new(bytes, 0);
+        if !lines.skip_header() {
+            warn!("nav input has no END OF HEADER line");
+        }
+        if lines.failed {
+            return Self::finish(Vec::new(), lines.leap_seconds, started);
+        }
+        let (body, header_lines, leap_seconds) = (lines.reader, lines.number, lines.leap_seconds);
+
+        let chunk_len = chunk_len(body.len()).max(1);
+        let mut splits = vec![0];
+        while let Some(split) = record_boundary(body, splits[splits.len() - 1] + chunk_len) {
+            splits.push(split);
+        }
+        splits.push(body.len());
+        let records = parse_chunks(body, header_lines, &splits, filter);
+        Self::finish(records, leap_seconds, started)
+    }
+
+    /// Records after the header of the satellites `filter` accepts, appended to `records`
+    /// until the lines run out; true if they ran out inside the last record
+    fn parse_records(
+        lines: &mut NavLines<impl BufRead>,
+        records: &mut Vec<NavRecord>,
+        filter: &impl Fn(SatId) -> bool,
+    ) -> bool {
+        let (mut line, mut data_line) = (String::new(), String::new());
+        let mut ran_out = false;
+        while let Some(number) = lines.read(&mut line) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.len() < 79 {
+                warn!(
+                    "nav line {}: too short for a record's first line, skipped",
+                    number
+                );
+                continue;
+            }
+
+            let sat_id: Option<SatId> = column(&line, 0, 3).and_then(|id| id.parse().ok());
+            if let Some(sat_id) = sat_id.filter(|&sat_id| !filter(sat_id)) {
+                let data_lines = sat_id.constellation.nav_record_lines();
+                ran_out = (0..data_lines).any(|_| lines.read(&mut data_line).is_none());
+                continue;
+            }
+            let constellation = match sat_id {
+                Some(sat_id) => sat_id.constellation,
+                None => line
+                    .chars()
+                    .next()
+                    .and_then(Constellation::from_char)
+                    .unwrap_or_default(),
+            };
+            let epoch = column(&line, 3, 23).and_then(Self::parse_epoch);
+            let gps_millis = epoch
+                .as_ref()
+                .and_then(|epoch| Self::epoch_to_gps_millis(epoch, constellation));
+
+            let mut record = NavRecord {
+                sat_id: sat_id.unwrap_or_default(),
+                epoch: epoch.unwrap_or_default(),
+                gps_millis: gps_millis.unwrap_or_default(),
+                sv_clock_bias: Self::parse_float(column(&line, 23, 42), number),
+                sv_clock_drift: Self::parse_float(column(&line, 42, 61), number),
+                sv_clock_drift_rate: Self::parse_float(column(&line, 61, 80), number),
+                ..Default::default()
+            };
+
+            // Parse additional lines
+            let mut complete = true;
+            for index in 0..constellation.nav_record_lines() {
+                match lines.read(&mut data_line) {
+                    Some(data_number) => {
+                        Self::parse_data_line(&mut record, &data_line, index, data_number)
+                    }
+                    None => complete = false,
+                }
+            }
+            ran_out = !complete;
+
+            match (sat_id, gps_millis) {
+                (None, _) => warn!(
+                    "nav line {}: invalid satellite id {:?}, record skipped",
+                    number,
+                    line.chars().take(3).collect::<String>()
+                ),
+                (_, None) => warn!("nav line {}: invalid epoch, record skipped", number),
+                _ if !complete => warn!(
+                    "nav line {}: {} record is truncated, skipped",
+                    number, record.sat_id
+                ),
+                _ => records.push(record),
+            }
+        }
+        ran_out
+    }
+
+    fn finish(records: Vec<NavRecord>, leap_seconds: Option<i32>, started: Instant) -> Self {
+        debug!(
+            "parsed {} nav records in {:?}",
+            records.len(),
+            started.elapsed()
+        );
+        let mut nav = Self {
+            records,
+            leap_seconds,
+            sources: Vec::new(),
+        };
+        nav.sort_canonical();
+        nav
+    }
+
+    /// Nav file of records from elsewhere, put in canonical order
+    pub fn from_records(records: Vec<NavRecord>) -> Self {
+        let mut nav = Self {
+            records,
+            ..Self::default()
+        };
+        nav.sort_canonical();
+        nav
+    }
+
+    /// Every record, in canonical order
+    pub fn records(&self) -> &[NavRecord] {
+        &self.records
+    }
+
+    pub fn into_records(self) -> Vec<NavRecord> {
+        self.records
+    }
+
+    /// Records of one satellite in time order, found by binary search; empty for a
+    /// satellite not in the file
+    pub fn records_for_slice(&self, sat_id: SatId) -> &[NavRecord] {
+        let start = self
+            .records
+            .partition_point(|record| record.sat_id < sat_id);
+        let end = start + self.records[start..].partition_point(|record| record.sat_id == sat_id);
+        &self.records[start..end]
+    }
+
+    pub fn records_for(&self, sat_id: SatId) -> impl Iterator<Item = &NavRecord> {
+        self.records_for_slice(sat_id).iter()
+    }
+
+    /// Satellites with records, in order
+    pub fn satellites(&self) -> impl Iterator<Item = SatId> + '_ {
+        self.records
+            .chunk_by(|a, b| a.sat_id == b.sat_id)
+            .map(|group| group[0].sat_id)
+    }
+
+    fn sort_canonical(&mut self) {
+        self.records.sort_by(|a, b| {
+            let (a, b) = (canonical_key(a), canonical_key(b));
+            a.0.cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then(a.2.total_cmp(&b.2))
+                .then(a.3.total_cmp(&b.3))
+        });
+    }
+
+    fn parse_epoch(s: &str) -> Option<(i32, i32, i32, i32, i32, i32)> {
+        let mut parts = s.split_whitespace().map(|part| part.parse().ok());
+        let mut fields = [0; 6];
+        for field in &mut fields {
+            *field = parts.next()??;
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        let [year, month, day, hour, minute, second] = fields;
+        Some((year, month, day, hour, minute, second))
+    }
+
+    /// GPS milliseconds of a record epoch, which RINEX gives in the time scale of the
+    /// satellite's system: GPS time for GPS, Galileo, QZSS, SBAS and IRNSS, BDT for BeiDou
+    /// and UTC for GLONASS
+    fn epoch_to_gps_millis(
+        epoch: &(i32, i32, i32, i32, i32, i32),
+        constellation: Constellation,
+    ) -> Option<f64> {
+        let gps_epoch: DateTime<Utc> = Utc.with_ymd_and_hms(1980, 1, 6, 0, 0, 0).unwrap();
+        let time = Utc
+            .with_ymd_and_hms(
+                epoch.0,
+                u32::try_from(epoch.1).ok()?,
+                u32::try_from(epoch.2).ok()?,
+                u32::try_from(epoch.3).ok()?,
+                u32::try_from(epoch.4).ok()?,
+                u32::try_from(epoch.5).ok()?,
+            )
+            .single()?;
+        let offset = match constellation {
+            Constellation::Glonass => GPS_LEAP_SECONDS,
+            Constellation::BeiDou => BDT_OFFSET,
+            _ => 0.0,
+        };
+        Some((time - gps_epoch).num_milliseconds() as f64 + offset * 1000.0)
+    }
+
+    /// D-exponent value of a field; blank fields are zero, unreadable ones zero with a
+    /// warning
+    fn parse_float(field: Option<&str>, line_number: usize) -> f64 {
+        let Some(field) = field.map(str::trim) else {
+            warn!("nav line {}: unreadable columns, read as 0", line_number);
+            return 0.0;
+        };
+        if field.is_empty() {
+            return 0.0;
+        }
+        // Exact cases directly, the rest by the standard parser with the Fortran D exponent
+        // swapped for E in a stack copy (fields are 19 columns wide)
+        let value = parse_decimal(field.as_bytes()).or_else(|| {
+            let mut buffer = [0u8; 32];
+            let text = buffer.get_mut(..field.len())?;
+            text.copy_from_slice(field.as_bytes());
+            for byte in text.iter_mut().filter(|byte| **byte == b'D') {
+                *byte = b'E';
+            }
+            std::str::from_utf8(text).ok()?.parse().ok()
+        });
+        value.unwrap_or_else(|| {
+            warn!(
+                "nav line {}: unreadable value {:?}, read as 0",
+                line_number, field
+            );
+            0.0
+        })
+    }
+
+    /// Broadcast orbit line `index` (0-based) after a record's first line
+    fn parse_data_line(record: &mut NavRecord, line: &str, index: usize, line_number: usize) {
+        let [a, b, c, d] = [0, 1, 2, 3]
+            .map(|k| Self::parse_float(column(line, 4 + 19 * k, 23 + 19 * k), line_number));
+        match index {
+            0 => [record.iode, record.crs, record.delta_n, record.m0] = [a, b, c, d],
+            1 => [record.cuc, record.eccentricity, record.cus, record.sqrt_a] = [a, b, c, d],
+            2 => [record.toe, record.cic, record.omega0, record.cis] = [a, b, c, d],
+            3 => [record.i0, record.crc, record.omega, record.omega_dot] = [a, b, c, d],
+            4 => {
+                [
+                    record.idot,
+                    record.codes_on_l2_channel,
+                    record.gps_week,
+                    record.l2_p_data_flag,
+                ] = [a, b, c, d]
+            }
+            5 => {
+                [
+                    record.sv_accuracy,
+                    record.sv_health,
+                    record.tgd,
+                    record.iodc,
+                ] = [a, b, c, d]
+            }
+            6 => [record.transmission_time, record.fit_interval] = [a, b],
+            _ => {}
+        }
+    }
+}
+
+/// Decimal with an optional D or E exponent, e.g. "-1.234567890123D-05", for the exact
+/// cases only: a mantissa of at most 2^53 and a power of ten within ±22 are both exact
+/// doubles, so the one multiplication or division rounds correctly, as the standard parser
+/// does. None otherwise, including for anything the fast path does not recognize.
+#[cfg(feature = "std")]
+fn parse_decimal(text: &[u8]) -> Option<f64> {
+    const POWERS: [f64; 23] = [
+        1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+        1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+    ];
+    let (negative, mut rest) = match text {
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        _ => (false, text),
+    };
+    let mut mantissa: u64 = 0;
+    let mut digits = 0;
+    let mut exponent: i32 = 0;
+    let mut seen_point = false;
+    let mut seen_digit = false;
+    while let [byte, tail @ ..] = rest {
+        match byte {
+            b'0'..=b'9' => {
+                seen_digit = true;
+                if digits == 19 {
+                    return None;
+                }
+                mantissa = mantissa * 10 + u64::from(byte - b'0');
+                if mantissa > 0 {
+                    digits += 1;
+                }
+                if seen_point {
+                    exponent -= 1;
+                }
+            }
+            b'.' if !seen_point => seen_point = true,
+            _ => break,
+        }
+        rest = tail;
+    }
+    if !seen_digit {
+        return None;
+    }
+    if let [b'D' | b'E' | b'e', tail @ ..] = rest {
+        let (exponent_negative, tail) = match tail {
+            [b'-', tail @ ..] => (true, tail),
+            [b'+', tail @ ..] => (false, tail),
+            _ => (false, tail),
+        };
+        if tail.is_empty() || tail.len() > 4 || !tail.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let value = tail
+            .iter()
+            .fold(0i32, |value, byte| value * 10 + i32::from(byte - b'0'));
+        exponent += if exponent_negative { -value } else { value };
+    } else if !rest.is_empty() {
+        return None;
+    }
+    // Trailing zeros of the mantissa, as in "1.000000000000D-20", only inflate the power
+    while exponent < 0 && mantissa != 0 && mantissa.is_multiple_of(10) {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    if mantissa > 1 << 53 || exponent.unsigned_abs() as usize >= POWERS.len() {
+        return None;
+    }
+    let power = POWERS[exponent.unsigned_abs() as usize];
+    let value = match exponent < 0 {
+        true => mantissa as f64 / power,
+        false => mantissa as f64 * power,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Lines read into a caller's buffer, without their line ending, numbered on from the
+/// lines before the reader's start; a read error ends the input with a warning
+#[cfg(feature = "std")]
+struct NavLines<R> {
+    reader: R,
+    number: usize,
+    failed: bool,
+    leap_seconds: Option<i32>, // Seen by `skip_header`
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> NavLines<R> {
+    fn new(reader: R, lines_before: usize) -> Self {
+        Self {
+            reader,
+            number: lines_before,
+            failed: false,
+            leap_seconds: None,
+        }
+    }
+
+    /// Reads through the END OF HEADER line, noting the leap seconds; false if there is none
+    fn skip_header(&mut self) -> bool {
+        let mut line = String::new();
+        while self.read(&mut line).is_some() {
+            if line.contains("END OF HEADER") {
+                return true;
+            }
+            if line
+                .get(60..)
+                .is_some_and(|label| label.contains("LEAP SECONDS"))
+            {
+                self.leap_seconds = column(&line, 0, 6).and_then(|value| value.trim().parse().ok());
+            }
+        }
+        false
+    }
+
+    fn read(&mut self, line: &mut String) -> Option<usize> {
+        line.clear();
+        if self.failed {
+            return None;
+        }
+        match self.reader.read_line(line) {
+            Ok(0) => None,
+            Ok(_) => {
+                self.number += 1;
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Some(self.number)
+            }
+            Err(error) => {
+                warn!(
+                    "nav line {}: {}, rest of the input ignored",
+                    self.number + 1,
+                    error
+                );
+                self.failed = true;
+                None
+            }
+        }
+    }
+}
+
+/// Smallest chunk worth a parallel task; a file whose body is smaller is parsed as one
+#[cfg(feature = "mmap")]
+const MIN_CHUNK_LEN: usize = 1 << 20;
+
+/// Records of each chunk of `body` between consecutive `splits`, parsed in parallel and
+/// concatenated in order. A split only holds where the chunk before it ends with a whole
+/// record; otherwise the sequential parse would read on across it, so the two chunks are
+/// merged and parsed again. As in the sequential parse, a read error ends the input at the
+/// chunk it occurs in.
+#[cfg(feature = "mmap")]
+fn parse_chunks(
+    body: &[u8],
+    header_lines: usize,
+    splits: &[usize],
+    filter: &(impl Fn(SatId) -> bool + Sync),
+) -> Vec<NavRecord> {
+    let newlines: Vec<usize> = splits
+        .par_windows(2)
+        .map(|span| {
+            body[span[0]..span[1]]
+                .iter()
+                .filter(|&&byte| byte == b'\n')
+                .count()
+        })
+        .collect();
+    let lines_before = newlines.iter().scan(header_lines, |lines, count| {
+        let before = *lines;
+        *lines += count;
+        Some(before)
+    });
+    let mut starts: Vec<(usize, usize)> = splits.iter().copied().zip(lines_before).collect();
+
+    loop {
+        let chunks: Vec<_> = starts
+            .par_iter()
+            .enumerate()
+            .map(|(index, &(start, lines_before))| {
+                let end = starts.get(index + 1).map_or(body.len(), |&(end, _)| end);
+                let mut lines = NavLines::new(&body[start..end], lines_before);
+                let mut records = Vec::new();
+                let ran_out = RinexNav::parse_records(&mut lines, &mut records, filter);
+                (records, ran_out, lines.failed)
+            })
+            .collect();
+        let used = chunks
+            .iter()
+            .position(|&(_, _, failed)| failed)
+            .map_or(chunks.len(), |failed| failed + 1);
+        let misaligned: Vec<usize> = (1..used).filter(|&index| chunks[index - 1].1).collect();
+        if misaligned.is_empty() {
+            let mut records = Vec::with_capacity(chunks.iter().map(|chunk| chunk.0.len()).sum());
+            for (chunk, _, _) in chunks.into_iter().take(used) {
+                records.extend(chunk);
+            }
+            return records;
+        }
+        debug!(
+            "{} nav chunks split inside a record, parsed again merged",
+            misaligned.len()
+        );
+        for index in misaligned.into_iter().rev() {
+            starts.remove(index);
+        }
+    }
+}
+
+/// First offset at or after `from` where the sequential parse likely starts a record: a
+/// record's first line right after a complete record, so not inside a record or after a
+/// truncated one that would read it as data. `parse_chunks` checks the guess. None if there
+/// is no such line.
+#[cfg(feature = "mmap")]
+fn record_boundary(body: &[u8], from: usize) -> Option<usize> {
+    let line_at = |start: usize| -> &[u8] {
+        let end = body[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(body.len(), |end| start + end);
+        &body[start..end]
+    };
+    let next_line = |start: usize| start + line_at(start).len() + 1;
+    // A complete first line, e.g. "G05 2023 06 12 00 00 00 ...", and its record's data lines
+    let record_lines = |line: &[u8]| -> Option<usize> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let first_line = line.len() >= 79
+            && line[1..3].iter().all(u8::is_ascii_digit)
+            && line[3] == b' '
+            && line[4..8].iter().all(u8::is_ascii_digit);
+        if !first_line {
+            return None;
+        }
+        Constellation::from_char(char::from(line[0])).map(Constellation::nav_record_lines)
+    };
+
+    let mut start = match from {
+        0 => 0,
+        _ => {
+            from + body
+                .get(from - 1..)?
+                .iter()
+                .position(|&byte| byte == b'\n')?
+        }
+    };
+    while start < body.len() {
+        if let Some(data_lines) = record_lines(line_at(start)) {
+            let after = (0..=data_lines).try_fold(start, |line, _| {
+                Some(next_line(line)).filter(|&next| next < body.len())
+            })?;
+            if record_lines(line_at(after)).is_some() {
+                return Some(after);
+            }
+        }
+        start = next_line(start);
+    }
+    None
+}
+
+/// Columns start..end of a fixed-width line, cut short by the line's end; None where the
+/// range splits a character
+#[cfg(feature = "std")]
+fn column(line: &str, start: usize, end: usize) -> Option<&str> {
+    let end = end.min(line.len());
+    if start >= end {
+        return Some("");
+    }
+    line.get(start..end)
+}
+
+/// Satellite, ephemeris time, issue of data, then transmission time, all in seconds, which
+/// `RinexNav` sorts by
+#[cfg(feature = "std")]
+fn canonical_key(record: &NavRecord) -> (SatId, f64, f64, f64) {
+    match record.sat_id.constellation {
+        Constellation::Glonass | Constellation::Sbas => (
+            record.sat_id,
+            record.toc_gps_seconds(),
+            0.0,
+            record.sv_clock_drift_rate, // Message frame time
+        ),
+        _ => (
+            record.sat_id,
+            record.toe_gps_seconds(),
+            record.iode,
+            record.week_seconds_to_gps(record.transmission_time),
+        ),
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromStr for RinexNav {
+    type Err = std::convert::Infallible;
+
+    /// Lenient like `from_reader`: malformed records are skipped or zero-filled
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_reader(text.as_bytes()))
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "std-fs"))]
+impl RinexNav {
+    /// Write the parsed records as JSON, for reloading without parsing the RINEX again
+    pub fn to_json_file(&self, filename: &str) -> std::io::Result<()> {
+        let writer = std::io::BufWriter::new(File::create(filename)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Read records written by `to_json_file`
+    pub fn from_json_file(filename: &str) -> std::io::Result<Self> {
+        let reader = BufReader::new(File::open(filename)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::test_support::NAV;
+
+    fn record(constellation: Constellation, week: f64, toe: f64) -> NavRecord {
+        NavRecord {
+            sat_id: SatId {
+                constellation,
+                prn: 1,
+            },
+            gps_week: week,
+            toe,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn toe_epoch_reads_gps_weeks() {
+        let record = record(Constellation::Gps, 2267.0, 345_600.0);
+        assert_eq!(
+            record.toe_gps_seconds(),
+            2267.0 * SECONDS_PER_WEEK + 345_600.0
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn states_round_trip_through_a_series() {
+        let first = State {
+            velocity: Some(ECEF::new(1.0, 2.0, 3.0)),
+            clock_bias: Some(1e-4),
+            ephemeris_age: -30.0,
+            accuracy: Some(2.4),
+            ..State::new(1e9, ECEF::new(2e7, 1e7, -5e6))
+        };
+        let second = State {
+            time: 1e9 + 30.0,
+            kepler_converged: false,
+            extrapolated: true,
+            ..first
+        };
+        let mut series = StateSeries::new();
+        series.push(&first);
+        series.push(&second);
+        assert!(series.iter_states().eq([first, second]));
+        assert_eq!(series.get(1).unwrap().to_state(), second);
+
+        // A state without a velocity leaves the column out for all
+        series.push(&State::new(1e9 + 60.0, ECEF::new(0.0, 0.0, 0.0)));
+        assert!(series.iter().all(|state| state.velocity().is_none()));
+        assert!(series.iter().all(|state| state.clock_bias().is_none()));
+        assert_eq!(series.get(0).unwrap().accuracy(), Some(2.4));
+    }
+
+    #[test]
+    fn toe_epoch_converts_beidou_weeks() {
+        // BDT week 0 began at 2006-01-01 00:00:00 UTC, which is GPS week 1356 plus the 14
+        // leap seconds GPS was ahead by then
+        let start = record(Constellation::BeiDou, 0.0, 0.0);
+        assert_eq!(start.toe_gps_seconds(), 1356.0 * SECONDS_PER_WEEK + 14.0);
+
+        let gps = record(Constellation::Gps, 2267.0, 345_600.0);
+        let beidou = record(Constellation::BeiDou, 911.0, 345_586.0);
+        assert_eq!(beidou.toe_gps_seconds(), gps.toe_gps_seconds());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn derived_quantities_are_those_of_a_gps_orbit() {
+        let nav: RinexNav = NAV.parse().unwrap();
+        for record in nav.records() {
+            // Half a sidereal day, 20,180 km up
+            assert!((record.orbital_period() - 43_082.0).abs() < 30.0);
+            let altitude = record.semi_major_axis() - WGS84_A;
+            assert!((altitude - 20_180e3).abs() < 30e3);
+            assert!(record.perigee_altitude() <= altitude && altitude <= record.apogee_altitude());
+            let spread = record.apogee_radius() - record.perigee_radius();
+            assert!((spread - 2.0 * record.eccentricity * record.semi_major_axis()).abs() < 1e-6);
+            assert!(record.is_plausible());
+        }
+        let record = &nav.records()[0];
+        let inside_earth = NavRecord {
+            sqrt_a: 2500.0, // a of 6,250 km
+            ..*record
+        };
+        assert!(!inside_earth.is_plausible());
+        let open = NavRecord {
+            eccentricity: 1.0,
+            ..*record
+        };
+        assert!(!open.is_plausible());
+        let grazing = NavRecord {
+            sqrt_a: 2560.0,
+            eccentricity: 0.1, // Perigee at 5,900 km
+            ..*record
+        };
+        assert!(grazing.semi_major_axis() > WGS84_A && !grazing.is_plausible());
+    }
+
+    #[test]
+    fn ura_maps_to_meters_per_system() {
+        let accuracy = |constellation, sv_accuracy| NavRecord {
+            sv_accuracy,
+            ..record(constellation, 2267.0, 0.0)
+        };
+        // Table boundaries: index 0 is 2.4 m, index 15 has no prediction
+        assert_eq!(ura_index_to_meters(0), Some(2.4));
+        assert_eq!(ura_index_to_meters(14), Some(6144.0));
+        assert_eq!(ura_index_to_meters(15), None);
+        // RINEX stores the nominal value of the index
+        let gps = |meters| accuracy(Constellation::Gps, meters);
+        assert_eq!(
+            (gps(2.0).ura_index(), gps(2.0).ura_meters()),
+            (0, Some(2.4))
+        );
+        assert_eq!(
+            (gps(2.1).ura_index(), gps(2.1).ura_meters()),
+            (1, Some(3.4))
+        );
+        assert_eq!(gps(16.0).ura_index(), 6);
+        assert_eq!(gps(32.0).ura_index(), 7);
+        assert_eq!(
+            (gps(4096.0).ura_index(), gps(4096.0).ura_meters()),
+            (14, Some(6144.0))
+        );
+        assert_eq!(
+            (gps(8192.0).ura_index(), gps(8192.0).ura_meters()),
+            (15, None)
+        );
+        assert_eq!(accuracy(Constellation::Qzss, 2.8).ura_meters(), Some(3.4));
+        // Galileo broadcasts SISA in meters, 255 for none
+        assert_eq!(
+            accuracy(Constellation::Galileo, 3.12).ura_meters(),
+            Some(3.12)
+        );
+        assert_eq!(accuracy(Constellation::Galileo, 255.0).ura_meters(), None);
+        assert_eq!(accuracy(Constellation::Galileo, -1.0).ura_meters(), None);
+        assert_eq!(accuracy(Constellation::BeiDou, 2.0).ura_meters(), Some(2.0));
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn records_for_slice_borrows_each_satellites_records() {
+        let nav = RinexNav::from_file("constellation/GCGO00USA_R_20231630000_01D_GN.rnx").unwrap();
+        let all = nav.records().as_ptr_range();
+        let mut seen = 0;
+        for sat_id in nav.satellites() {
+            let records = nav.records_for_slice(sat_id);
+            let expected: Vec<&NavRecord> = nav
+                .records()
+                .iter()
+                .filter(|record| record.sat_id == sat_id)
+                .collect();
+            assert_eq!(records.iter().collect::<Vec<_>>(), expected);
+            assert!(all.contains(&records.as_ptr()));
+            assert!(nav.records_for(sat_id).eq(records.iter()));
+            seen += records.len();
+        }
+        assert_eq!(seen, nav.records().len());
+        assert!(nav.records_for_slice("E01".parse().unwrap()).is_empty());
+    }
+
+    /// The parse before the fast path: the whole field through the standard parser
+    #[cfg(feature = "std")]
+    fn reference_float(field: &str) -> Option<f64> {
+        match field.trim() {
+            "" => Some(0.0),
+            text => text.replace('D', "E").parse().ok(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn fields_parse_bit_identically_to_the_standard_parser() {
+        let mut fields: Vec<&str> = NAV
+            .lines()
+            .skip_while(|line| !line.contains("END OF HEADER"))
+            .skip(1)
+            .flat_map(|line| (0..4).filter_map(move |k| column(line, 4 + 19 * k, 23 + 19 * k)))
+            .collect();
+        assert!(fields.len() > 5_000);
+        fields.extend([
+            "-0.000000000000D+00",
+            "+.5",
+            "5.",
+            "1D0",
+            "1.000000000000D-20",
+            "9007199254740993D0",
+            "1234567890123456789",
+            "12345678901234567890",
+            "1.5D+22",
+            "1.5D+23",
+            "4.9D-324",
+            "1.797693134862D+308",
+            "1D99999",
+            "0.1E-5",
+            "-1.2d-3",
+            "1.",
+            ".",
+            "-",
+            "1D",
+            "1.2.3",
+            "  7  ",
+        ]);
+        for field in fields {
+            let parsed = RinexNav::parse_float(Some(field), 0);
+            let expected = reference_float(field).unwrap_or(0.0);
+            assert_eq!(parsed.to_bits(), expected.to_bits(), "{:?}", field);
+        }
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn in_memory_parsing_matches_the_file() {
+        let path = "constellation/GCGO00USA_R_20231630000_01D_GN.rnx";
+        let from_file = RinexNav::from_file(path).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        let from_str: RinexNav = text.parse().unwrap();
+        // A small buffer splits lines across reads
+        let from_reader =
+            RinexNav::from_reader(std::io::BufReader::with_capacity(7, text.as_bytes()));
+        for nav in [&from_str, &from_reader] {
+            assert_eq!(nav.records(), from_file.records());
+            assert_eq!(nav.leap_seconds, from_file.leap_seconds);
+        }
+        assert!(!from_file.records().is_empty());
+    }
+
+    /// The fixture's header and the records whose toc hour `keep` accepts
+    #[cfg(feature = "std")]
+    fn fixture_hours(keep: impl Fn(u32) -> bool) -> String {
+        let text = NAV;
+        let lines: Vec<&str> = text.lines().collect();
+        let (header, records) = lines.split_at(11);
+        let kept = records
+            .chunks(8)
+            .filter(|record| keep(record[0][15..17].parse().unwrap()))
+            .flatten();
+        header
+            .iter()
+            .chain(kept)
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn records_are_in_canonical_order_whatever_the_file_order() {
+        let text = NAV;
+        let lines: Vec<&str> = text.lines().collect();
+        let (header, records) = lines.split_at(11);
+        // Records in reverse, and every other one followed by the rest
+        let reversed: Vec<&[&str]> = records.chunks(8).rev().collect();
+        let shuffled: Vec<&[&str]> = (0..2)
+            .flat_map(|parity| reversed.iter().skip(parity).step_by(2).copied())
+            .collect();
+        let expected: RinexNav = text.parse().unwrap();
+        for order in [reversed, shuffled] {
+            let reordered: String = header
+                .iter()
+                .chain(order.into_iter().flatten())
+                .map(|line| format!("{}\n", line))
+                .collect();
+            let nav: RinexNav = reordered.parse().unwrap();
+            assert_eq!(nav.records(), expected.records());
+            assert!(nav.satellites().eq(expected.satellites()));
+        }
+
+        let key = |record: &NavRecord| (record.sat_id, record.toe_gps_seconds(), record.iode);
+        for pair in expected.records().windows(2) {
+            assert!(key(&pair[0]) <= key(&pair[1]), "{:?}", pair);
+        }
+        let satellites: Vec<SatId> = expected.satellites().collect();
+        assert!(satellites.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(satellites.len(), 32);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn merging_overlapping_files_gives_the_union() {
+        let full: RinexNav = fixture_hours(|_| true).parse().unwrap();
+        let morning: RinexNav = fixture_hours(|hour| hour < 16).parse().unwrap();
+        let evening: RinexNav = fixture_hours(|hour| hour >= 10).parse().unwrap();
+        let overlap: RinexNav = fixture_hours(|hour| (10..16).contains(&hour))
+            .parse()
+            .unwrap();
+        let overlap = overlap.records().len();
+        assert!(overlap > 0);
+        assert_eq!(
+            morning.records().len() + evening.records().len(),
+            full.records().len() + overlap
+        );
+
+        let merged = evening.merge(morning);
+        assert_eq!(merged.records(), full.records());
+        assert_eq!(merged.leap_seconds, full.leap_seconds);
+        for sat_id in full.satellites() {
+            assert_eq!(
+                merged.records_for_slice(sat_id),
+                full.records_for_slice(sat_id)
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn merging_keeps_the_earliest_broadcast_and_leap_seconds() {
+        let fixture = || -> RinexNav { fixture_hours(|_| true).parse().unwrap() };
+        let full = fixture();
+        let first = full.records()[0];
+        let rebroadcast = NavRecord {
+            transmission_time: first.transmission_time + 600.0,
+            ..first
+        };
+        let mut later = RinexNav::from_records(vec![rebroadcast]);
+        later.leap_seconds = Some(17);
+        let merged = later.merge(fixture());
+        assert_eq!(merged.records(), full.records());
+        // The fixture's first record is the earlier one
+        assert_eq!(merged.leap_seconds, Some(18));
+
+        // A new IODE for the same toe is a different ephemeris
+        let updated = NavRecord {
+            iode: first.iode + 1.0,
+            ..first
+        };
+        let merged = fixture().merge(RinexNav::from_records(vec![updated]));
+        assert_eq!(merged.records().len(), full.records().len() + 1);
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn merge_files_tracks_the_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let (morning, evening) = (path("morning.rnx"), path("evening.rnx"));
+        std::fs::write(&morning, fixture_hours(|hour| hour < 16)).unwrap();
+        std::fs::write(&evening, fixture_hours(|hour| hour >= 10)).unwrap();
+
+        let merged = RinexNav::merge_files(&[&morning, &evening]).unwrap();
+        let full: RinexNav = fixture_hours(|_| true).parse().unwrap();
+        assert_eq!(merged.records(), full.records());
+        assert_eq!(merged.sources, [morning, evening]);
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn from_file_reports_a_missing_file() {
+        let error = RinexNav::from_file("constellation/missing.rnx")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        let files = [
+            "constellation/GCGO00USA_R_20231630000_01D_GN.rnx",
+            "missing.rnx",
+        ];
+        assert!(RinexNav::merge_files(&files).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    const HEADER: &str =
+        "     3.04           N: GNSS NAV DATA    M: MIXED            RINEX VERSION / TYPE
+    18                                                      LEAP SECONDS
+                                                            END OF HEADER
+";
+
+    /// The same broadcast orbit lines after `first_line`, with `week` in place of the week
+    #[cfg(feature = "std")]
+    fn keplerian(first_line: &str, week: &str) -> String {
+        format!(
+            "{}
+     5.000000000000D+00-3.253125000000D+01 4.100527946305D-09 2.500725598676D+00
+    -1.594424247742D-06 1.350355753675D-02 5.898997187614D-06 5.153777248383D+03
+     9.358400000000D+04 1.601874828339D-07 1.016522514860D+00 5.029141902924D-08
+     9.739723224509D-01 2.694687500000D+02-1.405989527759D+00-7.818539959286D-09
+    -3.325138505366D-10 1.000000000000D+00 {} 0.000000000000D+00
+     2.000000000000D+00 0.000000000000D+00-1.117587000000D-08 5.000000000000D+00
+     9.345000000000D+04 4.000000000000D+00
+",
+            first_line, week
+        )
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn record_epochs_are_read_in_their_system_time() {
+        let gps = keplerian(
+            "G17 2023 06 12 01 59 44 7.180687971413D-04 1.250555214938D-12 0.000000000000D+00",
+            "2.266000000000D+03",
+        );
+        // The same ephemeris from BeiDou: its epoch and toe are in BDT, 14 s behind GPS
+        let beidou = keplerian(
+            "C17 2023 06 12 01 59 44 7.180687971413D-04 1.250555214938D-12 0.000000000000D+00",
+            "9.100000000000D+02",
+        );
+        let glonass =
+            "R01 2023 06 12 00 15 00 1.519024372101D-05 0.000000000000D+00 5.184000000000D+05
+     1.182464062500D+04-2.217864990234D+00 1.862645149231D-09 0.000000000000D+00
+     1.259628808594D+04 1.081981658936D+00-9.313225746155D-10 1.000000000000D+00
+     1.924823583984D+04 1.225566864014D+00-1.862645149231D-09 0.000000000000D+00
+";
+        let nav: RinexNav = format!("{}{}{}{}", HEADER, gps, beidou, glonass)
+            .parse()
+            .unwrap();
+        let record = |constellation| {
+            nav.records()
+                .iter()
+                .find(|record| record.sat_id.constellation == constellation)
+                .unwrap()
+        };
+
+        // A GPS toc on the toe is already GPS time
+        let gps = record(Constellation::Gps);
+        assert_eq!(gps.toc_gps_seconds(), gps.toe_gps_seconds());
+        assert_eq!(gps.toc_gps_seconds(), 2266.0 * SECONDS_PER_WEEK + 93_584.0);
+
+        let beidou = record(Constellation::BeiDou);
+        assert_eq!(beidou.toc_gps_seconds(), beidou.toe_gps_seconds());
+        assert_eq!(beidou.toc_gps_seconds(), gps.toc_gps_seconds() + BDT_OFFSET);
+
+        // GLONASS epochs are UTC
+        let utc = Utc.with_ymd_and_hms(2023, 6, 12, 0, 15, 0).unwrap();
+        assert_eq!(
+            record(Constellation::Glonass).toc_gps_seconds(),
+            gps_seconds(utc)
+        );
+    }
+
+    /// Header and a few records of the fixture, one of them cut short after its third data
+    /// line and followed by a blank line, with the given line ending
+    #[cfg(feature = "mmap")]
+    fn adversarial_nav(line_ending: &str) -> String {
+        let text = NAV;
+        let lines: Vec<&str> = text.lines().collect();
+        let body = lines
+            .iter()
+            .position(|line| line.contains("END OF HEADER"))
+            .unwrap()
+            + 1;
+        let mut kept: Vec<&str> = lines[..body + 5 * 8].to_vec();
+        kept.extend(&lines[body + 5 * 8..body + 5 * 8 + 4]);
+        kept.push("");
+        kept.extend(&lines[body + 6 * 8..body + 8 * 8]);
+        kept.iter()
+            .map(|line| format!("{}{}", line, line_ending))
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn filtered_parsing_keeps_the_records_of_the_full_parse() {
+        let text = NAV;
+        let full = RinexNav::from_reader(text.as_bytes());
+        let g17: SatId = "G17".parse().unwrap();
+        let filters: [&dyn Fn(SatId) -> bool; 3] = [
+            &|sat_id| sat_id == g17,
+            &|sat_id| sat_id.prn % 2 == 1,
+            &|_| false,
+        ];
+        for filter in filters {
+            let filtered = RinexNav::from_reader_filtered(text.as_bytes(), filter);
+            let expected: Vec<NavRecord> = full
+                .records()
+                .iter()
+                .filter(|record| filter(record.sat_id))
+                .copied()
+                .collect();
+            assert_eq!(filtered.records(), expected);
+            assert_eq!(filtered.leap_seconds, full.leap_seconds);
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn parallel_parse_matches_sequential_at_every_chunk_length() {
+        let accept_all = |_: SatId| true;
+        for line_ending in ["\r\n", "\n"] {
+            let text = adversarial_nav(line_ending);
+            let sequential = RinexNav::from_reader(text.as_bytes());
+            // The cut record reads on into the next, leaving one record of the two
+            assert_eq!(sequential.records().len(), 6);
+            // Chunks of one byte upwards put a split at every offset into a record, including
+            // between the CR and LF of a line ending
+            for chunk_len in 1..=text.len() {
+                let parallel =
+                    RinexNav::from_bytes_chunked(text.as_bytes(), &accept_all, |_| chunk_len);
+                assert_eq!(
+                    parallel.records(),
+                    sequential.records(),
+                    "chunks of {} bytes, line ending {:?}",
+                    chunk_len,
+                    line_ending
+                );
+                assert_eq!(parallel.leap_seconds, sequential.leap_seconds);
+            }
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn parallel_parse_matches_sequential_on_the_fixture() {
+        let text = NAV;
+        let gps_odd = |sat_id: SatId| sat_id.prn % 2 == 1;
+        let sequential = RinexNav::from_reader_filtered(text.as_bytes(), gps_odd);
+        for chunk_len in [1, 80, 81, 649, 650, 4096] {
+            let parallel = RinexNav::from_bytes_chunked(text.as_bytes(), &gps_odd, |_| chunk_len);
+            assert_eq!(parallel.records(), sequential.records());
+        }
+        assert_eq!(
+            RinexNav::from_bytes_parallel(text.as_bytes(), &|_| true).records(),
+            RinexNav::from_reader(text.as_bytes()).records()
+        );
+    }
+
+    #[test]
+    fn approximate_equality_wraps_longitudes_at_the_antimeridian() {
+        // The same meridian either side of the wrap, and two either side of it
+        let wrapped = LLA::new(12.5, -180.0001, 100.5);
+        let east = LLA::new(12.5, 179.9999, 100.0);
+        let west = LLA::new(12.5, -179.9999, 100.5);
+        assert!((east.longitude - wrapped.longitude).abs() > 359.0);
+        assert!(east.longitude_difference(&wrapped).abs() < 1e-9);
+        assert!(east.approx_eq(&wrapped, 1e-9, 1.0));
+        assert!(!east.approx_eq(&wrapped, 1e-9, 0.1));
+        assert!((east.distance_to(&wrapped) - 0.5).abs() < 1e-6);
+
+        assert!((east.longitude_difference(&west) + 0.0002).abs() < 1e-9);
+        assert!((west.longitude_difference(&east) - 0.0002).abs() < 1e-9);
+        assert!(east.approx_eq(&west, 1e-3, 1.0));
+        assert!(!east.approx_eq(&west, 1e-5, 1.0));
+        // 0.0002 degrees of longitude at 12.5 degrees latitude, and the 0.5 m up
+        assert!(
+            (east.distance_to(&west) - 21.77).abs() < 0.05,
+            "{}",
+            east.distance_to(&west)
+        );
+
+        let a = ECEF::new(6378137.0, 0.0, 0.0);
+        let b = ECEF::new(6378137.0, 3.0, 4.0);
+        assert_eq!(a.distance_to(&b), 5.0);
+        assert!(a.approx_eq(&b, 5.0) && !a.approx_eq(&b, 4.999));
+    }
+
+    #[cfg(feature = "approx")]
+    #[test]
+    fn approx_traits_wrap_longitudes_too() {
+        use approx::{assert_abs_diff_eq, assert_abs_diff_ne, assert_relative_eq};
+        let east = LLA::new(-33.0, 179.9999, 0.0);
+        let west = LLA::new(-33.0, -179.9999, 0.0);
+        assert_abs_diff_eq!(east, west, epsilon = 1e-3);
+        assert_abs_diff_eq!(west, east, epsilon = 1e-3);
+        assert_abs_diff_ne!(east, LLA::new(-33.0, 0.0, 0.0), epsilon = 1e-3);
+        let point = ECEF::new(1.0, 2.0, 3.0);
+        assert_relative_eq!(point, ECEF::new(1.0, 2.0, 3.0 + 4e-16));
+        assert_abs_diff_ne!(point, ECEF::new(1.0, 2.0, 3.1), epsilon = 0.05);
+    }
+
+    #[test]
+    fn local_frame_axes_and_look_angles() {
+        let origin = LLA::new(0.0, 0.0, 0.0);
+        let base = origin.to_ecef();
+        let offset = |x: f64, y: f64, z: f64| base + ECEF::new(x, y, z);
+        // On the equator at the prime meridian, ECEF y is east, z north and x up
+        let east = origin.enu_to(&offset(0.0, 1000.0, 0.0));
+        assert!((east.east - 1000.0).abs() < 1e-6 && east.north.abs() < 1e-6);
+        let north = origin.aer_to(&offset(0.0, 0.0, 1000.0));
+        assert!(north.azimuth.abs() < 1e-9 && north.elevation.abs() < 1e-9);
+        let west = origin.aer_to(&offset(0.0, -1000.0, 0.0));
+        assert!((west.azimuth - 270.0).abs() < 1e-9);
+        let zenith = origin.aer_to(&offset(2e7, 0.0, 0.0));
+        assert!((zenith.elevation - 90.0).abs() < 1e-9 && (zenith.range - 2e7).abs() < 1e-6);
+        let below = origin.aer_to(&offset(-1000.0, 1000.0, 0.0));
+        assert!((below.elevation + 45.0).abs() < 1e-9 && (below.azimuth - 90.0).abs() < 1e-9);
+
+        // Up at 45° N, 90° E points along (0, 1, 1)/√2
+        let up = LLA::new(45.0, 90.0, 0.0).rotate_to_enu(&ECEF::new(0.0, 1.0, 1.0));
+        assert!((up.up - 2f64.sqrt()).abs() < 1e-12);
+        assert!(up.east.abs() < 1e-12 && up.north.abs() < 1e-12);
+    }
+
+    #[test]
+    fn line_of_sight_and_range_rate_signs() {
+        let receiver = ECEF::new(WGS84_A, 0.0, 0.0);
+        let satellite = ECEF::new(WGS84_A + 2e7, 3e6, 0.0);
+        let unit = unit_line_of_sight(&receiver, &satellite).unwrap();
+        assert!((unit.norm() - 1.0).abs() < 1e-15);
+        assert!((range(&receiver, &satellite) - (2e7f64).hypot(3e6)).abs() < 1e-6);
+        assert_eq!(range(&satellite, &receiver), range(&receiver, &satellite));
+        assert_eq!(unit_line_of_sight(&receiver, &receiver), None);
+
+        let still = ECEF::default();
+        let towards = unit * -3000.0;
+        // An approaching satellite closes the range, so its range rate is negative
+        let approaching = range_rate(&receiver, &still, &satellite, &towards).unwrap();
+        assert!((approaching + 3000.0).abs() < 1e-9);
+        let receding = range_rate(&receiver, &still, &satellite, &(unit * 3000.0)).unwrap();
+        assert!((receding - 3000.0).abs() < 1e-9);
+        // A receiver moving towards the satellite closes the range too
+        let moving = range_rate(&receiver, &(unit * 10.0), &satellite, &still).unwrap();
+        assert!((moving + 10.0).abs() < 1e-9);
+        // Motion across the line of sight leaves it unchanged
+        let across = ECEF::new(0.0, 0.0, 3000.0);
+        assert!(
+            range_rate(&receiver, &still, &satellite, &across)
+                .unwrap()
+                .abs()
+                < 1e-9
+        );
+        assert_eq!(range_rate(&receiver, &still, &receiver, &towards), None);
+    }
+}
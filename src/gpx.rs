@@ -0,0 +1,277 @@
+use crate::constellation::Constellation;
+use crate::gnss::LLA;
+use crate::kml::escape;
+use crate::satellite::Satellite;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std-fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+
+const GPX_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="pnt_rust" xmlns="http://www.topografix.com/GPX/1/1">
+<metadata>
+<desc>Elevations are WGS-84 ellipsoidal heights, not heights above the geoid (MSL)</desc>
+</metadata>"#;
+const GPX_FOOTER: &str = "</gpx>";
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// What a GPX export contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpxOptions {
+    pub decimation: usize, // Write every Nth point; the last one is always written
+}
+
+impl Default for GpxOptions {
+    fn default() -> Self {
+        Self { decimation: 1 }
+    }
+}
+
+impl Satellite {
+    /// Ground track as a GPX 1.1 document with one track of timestamped points
+    pub fn write_gpx(&self, writer: impl Write, options: &GpxOptions) -> io::Result<()> {
+        write_gpx_tracks(
+            writer,
+            [(self.id.to_string().as_str(), &self.ground_track()[..])],
+            options,
+        )
+    }
+
+    /// `write_gpx` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_gpx(&self, path: &str, options: &GpxOptions) -> io::Result<()> {
+        self.write_gpx(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+impl Constellation {
+    /// Ground tracks of every propagated satellite as one GPX document, a track each;
+    /// satellites without states are left out
+    pub fn write_gpx(&self, writer: impl Write, options: &GpxOptions) -> io::Result<()> {
+        let tracks: Vec<_> = self
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .map(|satellite| (satellite.id.to_string(), satellite.ground_track()))
+            .collect();
+        write_gpx_tracks(
+            writer,
+            tracks
+                .iter()
+                .map(|(name, points)| (name.as_str(), &points[..])),
+            options,
+        )
+    }
+
+    /// `write_gpx` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_gpx(&self, path: &str, options: &GpxOptions) -> io::Result<()> {
+        self.write_gpx(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+/// Named series of timestamped positions, such as receiver fixes of several sessions, as
+/// GPX tracks. A track is split into segments where it crosses the antimeridian; points
+/// with a non-finite coordinate are left out.
+pub fn write_gpx_tracks<'a>(
+    mut writer: impl Write,
+    tracks: impl IntoIterator<Item = (&'a str, &'a [(DateTime<Utc>, LLA)])>,
+    options: &GpxOptions,
+) -> io::Result<()> {
+    writeln!(writer, "{}", GPX_HEADER)?;
+    let step = options.decimation.max(1);
+    for (name, points) in tracks {
+        let last_index = points.len().saturating_sub(1);
+        let points = points
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % step == 0 || *i == last_index)
+            .map(|(_, point)| point)
+            .filter(|(_, lla)| {
+                [lla.latitude, lla.longitude, lla.altitude]
+                    .iter()
+                    .all(|value| value.is_finite())
+            });
+        writeln!(writer, "<trk>")?;
+        writeln!(writer, "<name>{}</name>", escape(name))?;
+        writeln!(writer, "<trkseg>")?;
+        let mut previous: Option<f64> = None;
+        for (time, lla) in points {
+            if previous.is_some_and(|longitude| (lla.longitude - longitude).abs() > 180.0) {
+                writeln!(writer, "</trkseg>")?;
+                writeln!(writer, "<trkseg>")?;
+            }
+            previous = Some(lla.longitude);
+            writeln!(
+                writer,
+                r#"<trkpt lat="{}" lon="{}"><ele>{}</ele><time>{}</time></trkpt>"#,
+                lla.latitude,
+                lla.longitude,
+                lla.altitude,
+                time.format(TIME_FORMAT)
+            )?;
+        }
+        writeln!(writer, "</trkseg>")?;
+        writeln!(writer, "</trk>")?;
+    }
+    writeln!(writer, "{}", GPX_FOOTER)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::{start, NAV};
+    use chrono::Duration as ChronoDuration;
+    use std::time::Duration;
+
+    fn write(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn count(document: &str, pattern: &str) -> usize {
+        document.matches(pattern).count()
+    }
+
+    /// Every element opened is closed, and the document is a single gpx element
+    fn assert_structure(document: &str) {
+        assert!(document.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        assert!(document.trim_end().ends_with("</gpx>"));
+        for element in ["gpx", "metadata", "trk", "trkseg", "name", "ele", "time"] {
+            assert_eq!(
+                count(document, &format!("<{}>", element))
+                    + count(document, &format!("<{} ", element)),
+                count(document, &format!("</{}>", element)),
+                "unbalanced <{}>",
+                element
+            );
+        }
+        assert_eq!(count(document, "<trkpt "), count(document, "</trkpt>"));
+    }
+
+    fn times(document: &str) -> Vec<&str> {
+        document
+            .split("<time>")
+            .skip(1)
+            .map(|block| &block[..block.find("</time>").unwrap()])
+            .collect()
+    }
+
+    fn track(count: usize) -> Vec<(DateTime<Utc>, LLA)> {
+        (0..count)
+            .map(|i| {
+                let time = start() + ChronoDuration::milliseconds(i as i64 * 60_250);
+                (time, LLA::new(10.0 + i as f64, 20.0, 100.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn satellite_track_has_one_point_per_state() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut satellite = Satellite::new(17, "SVN53".to_string());
+        let config = PropagationConfig::new().step(Duration::from_secs(300));
+        let records = nav.records_for_slice(17.into());
+        satellite
+            .propagate(start(), Duration::from_secs(3600), &config, records)
+            .unwrap();
+        let document = write(|out| satellite.write_gpx(out, &GpxOptions::default()));
+
+        assert_structure(&document);
+        assert_eq!(count(&document, "<trk>"), 1);
+        assert!(document.contains("<name>G17</name>"));
+        let points = count(&document, "<trkpt ");
+        assert_eq!(points, satellite.states.len());
+        assert_eq!(times(&document).first(), Some(&"2023-06-12T00:00:00.000Z"));
+        assert_eq!(times(&document).last(), Some(&"2023-06-12T00:55:00.000Z"));
+    }
+
+    #[test]
+    fn timestamps_are_iso_8601_zulu_with_milliseconds() {
+        let document =
+            write(|out| write_gpx_tracks(out, [("fixes", &track(3)[..])], &GpxOptions::default()));
+        assert_structure(&document);
+        assert_eq!(
+            times(&document),
+            [
+                "2023-06-12T00:00:00.000Z",
+                "2023-06-12T00:01:00.250Z",
+                "2023-06-12T00:02:00.500Z"
+            ]
+        );
+        assert!(document.contains(
+            r#"<trkpt lat="10" lon="20"><ele>100</ele><time>2023-06-12T00:00:00.000Z</time></trkpt>"#
+        ));
+    }
+
+    #[test]
+    fn decimation_keeps_every_nth_point_and_the_last() {
+        let points = track(12);
+        for (decimation, expected) in [(1, 12), (5, 4), (11, 2), (12, 2), (100, 2), (0, 12)] {
+            let options = GpxOptions { decimation };
+            let document = write(|out| write_gpx_tracks(out, [("fixes", &points[..])], &options));
+            assert_eq!(
+                count(&document, "<trkpt "),
+                expected,
+                "every {}",
+                decimation
+            );
+        }
+        let options = GpxOptions { decimation: 5 };
+        let document = write(|out| write_gpx_tracks(out, [("fixes", &points[..])], &options));
+        let latitudes: Vec<&str> = document
+            .split(r#"<trkpt lat=""#)
+            .skip(1)
+            .map(|block| &block[..block.find('"').unwrap()])
+            .collect();
+        assert_eq!(latitudes, ["10", "15", "20", "21"]);
+    }
+
+    #[test]
+    fn antimeridian_splits_segments_and_bad_points_are_dropped() {
+        let mut points = track(4);
+        points[1].1.longitude = 179.5;
+        points[2].1.longitude = -179.5;
+        points[3].1.altitude = f64::NAN;
+        let document = write(|out| {
+            write_gpx_tracks(
+                out,
+                [("a & b", &points[..]), ("empty", &[][..])],
+                &GpxOptions::default(),
+            )
+        });
+        assert_structure(&document);
+        assert!(document.contains("<name>a &amp; b</name>"));
+        assert_eq!(count(&document, "<trk>"), 2);
+        assert_eq!(count(&document, "<trkpt "), 3);
+        // 20° -> 179.5° stays, 179.5° -> -179.5° splits, plus the empty track's segment
+        assert_eq!(count(&document, "<trkseg>"), 3);
+        assert!(!document.contains("NaN"));
+    }
+
+    #[test]
+    fn constellation_has_a_track_per_propagated_satellite() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut constellation = Constellation::from_nav(nav);
+        let config = PropagationConfig::new().step(Duration::from_secs(600));
+        constellation.propagate_all(start(), Duration::from_secs(3600), &config);
+        let document = write(|out| constellation.write_gpx(out, &GpxOptions::default()));
+
+        assert_structure(&document);
+        let propagated: Vec<&Satellite> = constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .collect();
+        assert_eq!(count(&document, "<trk>"), propagated.len());
+        let states: usize = propagated.iter().map(|s| s.states.len()).sum();
+        assert_eq!(count(&document, "<trkpt "), states);
+        for satellite in propagated {
+            assert!(document.contains(&format!("<name>{}</name>", satellite.id)));
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use crate::gnss::AER;
+use std::fmt;
+
+/// Minimum elevation a satellite must clear, possibly depending on its azimuth
+pub trait ElevationMask {
+    /// Degrees, for an azimuth in degrees
+    fn min_elevation(&self, azimuth: f64) -> f64;
+
+    fn is_above(&self, aer: &AER) -> bool {
+        aer.elevation >= self.min_elevation(aer.azimuth)
+    }
+}
+
+/// A flat mask in degrees
+impl ElevationMask for f64 {
+    fn min_elevation(&self, _azimuth: f64) -> f64 {
+        *self
+    }
+}
+
+impl<M: ElevationMask + ?Sized> ElevationMask for &M {
+    fn min_elevation(&self, azimuth: f64) -> f64 {
+        (**self).min_elevation(azimuth)
+    }
+}
+
+/// A line of a horizon profile that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseHorizonError {
+    pub line: usize, // 1-based, 0 for errors about the profile as a whole
+    pub message: String,
+}
+
+impl fmt::Display for ParseHorizonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            line => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ParseHorizonError {}
+
+/// Azimuth-dependent terrain mask: minimum elevations sampled at azimuths, linearly
+/// interpolated between samples and wrapping around through north
+#[derive(Debug, Clone, PartialEq)]
+pub struct HorizonProfile {
+    samples: Vec<(f64, f64)>, // (azimuth, minimum elevation) in degrees, sorted by azimuth in [0, 360)
+}
+
+impl HorizonProfile {
+    /// Profile from (azimuth_deg, min_elevation_deg) pairs in any order. Azimuths are taken
+    /// modulo 360; a repeated azimuth keeps the last elevation given for it.
+    pub fn new(samples: &[(f64, f64)]) -> Result<Self, ParseHorizonError> {
+        let invalid = |message: &str| ParseHorizonError {
+            line: 0,
+            message: message.to_string(),
+        };
+        if samples.is_empty() {
+            return Err(invalid("a horizon profile needs at least one sample"));
+        }
+        if samples
+            .iter()
+            .any(|(azimuth, elevation)| !azimuth.is_finite() || !elevation.is_finite())
+        {
+            return Err(invalid("azimuths and elevations must be finite"));
+        }
+        let mut sorted: Vec<(f64, f64)> = Vec::with_capacity(samples.len());
+        for &(azimuth, elevation) in samples {
+            let azimuth = azimuth.rem_euclid(360.0);
+            match sorted.iter_mut().find(|(existing, _)| *existing == azimuth) {
+                Some(sample) => sample.1 = elevation,
+                None => sorted.push((azimuth, elevation)),
+            }
+        }
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { samples: sorted })
+    }
+
+    /// `azimuth,elevation` lines in degrees. Blank lines, `#` comments and a header line
+    /// that does not start with a number are skipped; `;` and tabs also separate values.
+    pub fn from_csv(text: &str) -> Result<Self, ParseHorizonError> {
+        let mut samples = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line
+                .split([',', ';', '\t'])
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .collect();
+            let values: Vec<Option<f64>> = fields.iter().map(|field| field.parse().ok()).collect();
+            match values.as_slice() {
+                [Some(azimuth), Some(elevation), ..] => samples.push((*azimuth, *elevation)),
+                [None, ..] if samples.is_empty() => {} // Header
+                _ => {
+                    return Err(ParseHorizonError {
+                        line: index + 1,
+                        message: format!("expected azimuth,elevation but found {:?}", line),
+                    })
+                }
+            }
+        }
+        Self::new(&samples)
+    }
+
+    /// A JSON array of `[azimuth, elevation]` pairs or of objects with `azimuth_deg` and
+    /// `min_elevation_deg` members, e.g. `[[0, 25], [90, 5]]`
+    pub fn from_json(text: &str) -> Result<Self, ParseHorizonError> {
+        let mut scanner = JsonScanner::new(text);
+        let samples = scanner.samples()?;
+        scanner.skip_whitespace();
+        match scanner.peek() {
+            None => Self::new(&samples),
+            Some(_) => Err(scanner.error("unexpected text after the profile")),
+        }
+    }
+
+    /// `from_json` for `.json` files, `from_csv` otherwise
+    #[cfg(feature = "std-fs")]
+    pub fn from_file(path: &str) -> Result<Self, ParseHorizonError> {
+        let text = std::fs::read_to_string(path).map_err(|err| ParseHorizonError {
+            line: 0,
+            message: format!("{}: {}", path, err),
+        })?;
+        match path.to_ascii_lowercase().ends_with(".json") {
+            true => Self::from_json(&text),
+            false => Self::from_csv(&text),
+        }
+    }
+
+    pub fn samples(&self) -> &[(f64, f64)] {
+        &self.samples
+    }
+}
+
+impl ElevationMask for HorizonProfile {
+    fn min_elevation(&self, azimuth: f64) -> f64 {
+        let azimuth = azimuth.rem_euclid(360.0);
+        // First sample past the azimuth; the one before it wraps around to the last
+        let next = self
+            .samples
+            .partition_point(|(sample, _)| *sample <= azimuth);
+        let (az1, el1) = match self.samples.get(next) {
+            Some(&sample) => sample,
+            None => (self.samples[0].0 + 360.0, self.samples[0].1),
+        };
+        let (az0, el0) = match next {
+            0 => {
+                let (az, el) = self.samples[self.samples.len() - 1];
+                (az - 360.0, el)
+            }
+            _ => self.samples[next - 1],
+        };
+        match az1 - az0 {
+            span if span > 0.0 => el0 + (el1 - el0) * (azimuth - az0) / span,
+            _ => el0, // A single sample
+        }
+    }
+}
+
+/// Just enough JSON for a list of numeric pairs, with line numbers for errors
+struct JsonScanner<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> JsonScanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, offset: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.offset += c.len_utf8();
+        }
+    }
+
+    fn error(&self, message: &str) -> ParseHorizonError {
+        ParseHorizonError {
+            line: self.text[..self.offset].matches('\n').count() + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn expect(&mut self, token: char) -> Result<(), ParseHorizonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == token => {
+                self.offset += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", token))),
+        }
+    }
+
+    /// Calls `item` for each element of an array until the closing bracket
+    fn array(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<(), ParseHorizonError>,
+    ) -> Result<(), ParseHorizonError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.offset += 1;
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.offset += 1,
+                Some(']') => {
+                    self.offset += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, ParseHorizonError> {
+        self.skip_whitespace();
+        let rest = &self.text[self.offset..];
+        let length = rest
+            .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(rest.len());
+        let value = rest[..length]
+            .parse()
+            .map_err(|_| self.error("expected a number"))?;
+        self.offset += length;
+        Ok(value)
+    }
+
+    fn key(&mut self) -> Result<&'a str, ParseHorizonError> {
+        self.expect('"')?;
+        let rest = &self.text[self.offset..];
+        let length = rest
+            .find('"')
+            .ok_or_else(|| self.error("unterminated string"))?;
+        self.offset += length + 1;
+        Ok(&rest[..length])
+    }
+
+    fn sample(&mut self) -> Result<(f64, f64), ParseHorizonError> {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            let mut values = Vec::new();
+            self.array(|scanner| {
+                values.push(scanner.number()?);
+                Ok(())
+            })?;
+            return match values.as_slice() {
+                [azimuth, elevation] => Ok((*azimuth, *elevation)),
+                _ => Err(self.error("expected an [azimuth, elevation] pair")),
+            };
+        }
+        self.offset += 1;
+        let (mut azimuth, mut elevation) = (None, None);
+        loop {
+            let key = self.key()?;
+            self.expect(':')?;
+            match key {
+                "azimuth_deg" | "azimuth" => azimuth = Some(self.number()?),
+                "min_elevation_deg" | "elevation" => elevation = Some(self.number()?),
+                _ => return Err(self.error(&format!("unknown member \"{}\"", key))),
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.offset += 1,
+                Some('}') => {
+                    self.offset += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+        match (azimuth, elevation) {
+            (Some(azimuth), Some(elevation)) => Ok((azimuth, elevation)),
+            _ => Err(self.error("expected azimuth_deg and min_elevation_deg")),
+        }
+    }
+
+    fn samples(&mut self) -> Result<Vec<(f64, f64)>, ParseHorizonError> {
+        let mut samples = Vec::new();
+        self.array(|scanner| {
+            samples.push(scanner.sample()?);
+            Ok(())
+        })?;
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constellation::Constellation;
+    use crate::gnss::{RinexNav, LLA};
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::NAV;
+    use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+    use std::time::Duration;
+
+    fn aer(azimuth: f64, elevation: f64) -> AER {
+        AER {
+            azimuth,
+            elevation,
+            range: 2.0e7,
+        }
+    }
+
+    /// Terrain higher than any satellite from west-northwest to east-northeast, 10° elsewhere
+    fn wall_to_the_north() -> HorizonProfile {
+        HorizonProfile::new(&[(290.0, 90.0), (70.0, 90.0), (80.0, 10.0), (280.0, 10.0)]).unwrap()
+    }
+
+    #[test]
+    fn profiles_interpolate_and_wrap_through_north() {
+        let profile = HorizonProfile::new(&[(350.0, 20.0), (10.0, 0.0), (180.0, 5.0)]).unwrap();
+        assert_eq!(profile.samples()[0], (10.0, 0.0));
+        assert_eq!(profile.min_elevation(350.0), 20.0);
+        assert_eq!(profile.min_elevation(0.0), 10.0);
+        assert_eq!(profile.min_elevation(360.0), 10.0);
+        assert_eq!(profile.min_elevation(-5.0), 15.0);
+        assert_eq!(profile.min_elevation(95.0), 2.5);
+        assert_eq!(profile.min_elevation(265.0), 12.5);
+        assert!(profile.is_above(&aer(180.0, 5.0)));
+        assert!(!profile.is_above(&aer(355.0, 14.0)));
+
+        let single = HorizonProfile::new(&[(123.0, 7.0)]).unwrap();
+        assert_eq!(single.min_elevation(0.0), 7.0);
+        assert_eq!(single.min_elevation(300.0), 7.0);
+        assert!(10.0.is_above(&aer(0.0, 10.0)) && !10.0.is_above(&aer(0.0, 9.9)));
+    }
+
+    #[test]
+    fn repeated_and_wrapped_azimuths_keep_the_last_sample() {
+        let profile = HorizonProfile::new(&[(0.0, 5.0), (360.0, 8.0), (-90.0, 3.0)]).unwrap();
+        assert_eq!(profile.samples(), [(0.0, 8.0), (270.0, 3.0)]);
+        assert!(HorizonProfile::new(&[]).is_err());
+        assert!(HorizonProfile::new(&[(f64::NAN, 5.0)]).is_err());
+    }
+
+    #[test]
+    fn csv_profiles() {
+        let text = "# surveyed 2023-06-01\nazimuth;elevation\n0, 12.5\n\n90\t3 # trees\n180;0\n";
+        let profile = HorizonProfile::from_csv(text).unwrap();
+        assert_eq!(profile.samples(), [(0.0, 12.5), (90.0, 3.0), (180.0, 0.0)]);
+
+        let error = HorizonProfile::from_csv("0,5\n90,x\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error
+            .to_string()
+            .starts_with("line 2: expected azimuth,elevation"));
+        assert_eq!(HorizonProfile::from_csv("# nothing\n").unwrap_err().line, 0);
+    }
+
+    #[test]
+    fn json_profiles() {
+        let pairs = HorizonProfile::from_json("[[0, 25], [90, 5.5e0]]").unwrap();
+        assert_eq!(pairs.samples(), [(0.0, 25.0), (90.0, 5.5)]);
+        let objects = HorizonProfile::from_json(
+            r#"[{"azimuth_deg": 0, "min_elevation_deg": 25},
+                {"elevation": 5.5, "azimuth": 90}]"#,
+        )
+        .unwrap();
+        assert_eq!(objects, pairs);
+
+        let error = HorizonProfile::from_json("[[0, 25],\n [90]]").unwrap_err();
+        assert_eq!(error.line, 2);
+        let error = HorizonProfile::from_json(r#"[{"azimuth": 0, "height": 1}]"#).unwrap_err();
+        assert!(error.message.contains("unknown member \"height\""));
+        assert!(HorizonProfile::from_json("[[0, 25]] extra").is_err());
+        assert!(HorizonProfile::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn wall_to_the_north_suppresses_northern_passes_only() {
+        let nav: RinexNav = NAV.parse().unwrap();
+        let mut constellation = Constellation::from_nav(nav);
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        constellation.propagate_all(start, Duration::from_secs(86400), &config);
+        let end = start + ChronoDuration::hours(23);
+        let observer = LLA::new(35.0, -100.0, 300.0);
+        let wall = wall_to_the_north();
+
+        let flat = constellation.passes(&observer, 10.0, start, end);
+        let walled = constellation.passes(&observer, &wall, start, end);
+        // Azimuths a pass sweeps, every minute from rise to set
+        let azimuths = |sat_id, rise: DateTime<Utc>, set: DateTime<Utc>| {
+            let satellite = constellation.get(sat_id).unwrap();
+            let minutes = (set - rise).num_minutes();
+            (0..=minutes)
+                .map(|m| (rise + ChronoDuration::minutes(m)).min(set))
+                .map(|time| {
+                    observer
+                        .aer_to(&satellite.interpolate_at(time).unwrap())
+                        .azimuth
+                })
+                .collect::<Vec<f64>>()
+        };
+
+        let (mut southern, mut northern) = (0, 0);
+        for pass in &flat {
+            let swept = azimuths(pass.sat_id, pass.rise, pass.set);
+            if swept.iter().all(|azimuth| (80.0..=280.0).contains(azimuth)) {
+                southern += 1;
+                assert!(walled.contains(pass), "{:?} changed", pass);
+            } else if swept
+                .iter()
+                .all(|azimuth| !(70.0..=290.0).contains(azimuth))
+            {
+                northern += 1;
+                assert!(
+                    !walled.iter().any(|other| other.sat_id == pass.sat_id
+                        && other.rise < pass.set
+                        && other.set > pass.rise),
+                    "{:?} not suppressed",
+                    pass
+                );
+            }
+        }
+        assert!(southern > 0 && northern > 0, "{} {}", southern, northern);
+        // Nothing is seen through the wall
+        for pass in &walled {
+            for azimuth in azimuths(pass.sat_id, pass.rise, pass.set) {
+                assert!(wall.min_elevation(azimuth) < 90.0);
+            }
+        }
+    }
+}
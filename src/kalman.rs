@@ -0,0 +1,447 @@
+use crate::alignment::{AlignedObservation, EpochAligner};
+use crate::clock::{ReceiverClockSeries, CLOCK_JUMP};
+use crate::constellation::Constellation;
+use crate::corrections::Corrections;
+use crate::gnss::{self, SatId, AER, ECEF};
+use crate::positioning::{
+    PositioningError, PseudorangeObservation, SppOptions, SppSolution, Weighting,
+};
+use crate::residuals::{ResidualRecord, Residuals};
+use crate::satellite::PropagationConfig;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+use ndarray::{Array1, Array2, Axis};
+
+const STATE_LEN: usize = 8; // x, y, z, vx, vy, vz, c*dt, c*dt_dot
+const CLOCK: usize = 6;
+const DRIFT: usize = 7;
+
+/// Tuning of `PvFilter`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterConfig {
+    pub acceleration_psd: [f64; 3], // White-noise acceleration density per ECEF axis, m²/s³
+    pub clock_psd: f64,             // Clock bias random walk density, m²/s
+    pub drift_psd: f64,             // Clock drift random walk density, m²/s³
+    pub weighting: Weighting,       // Code sigma as a function of elevation
+    pub doppler_sigma: f64,         // m/s
+    pub elevation_mask: f64,        // Degrees
+    pub innovation_gate: Option<f64>, // Chi-square threshold (1 dof) for rejecting an observation
+    pub initial_velocity_sigma: f64, // m/s
+    pub corrections: Corrections,   // Atmospheric delays applied to the code
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            acceleration_psd: [1.0, 1.0, 1.0],
+            clock_psd: 100.0,
+            drift_psd: 1.0,
+            weighting: Weighting::Sine { sigma0: 1.0 },
+            doppler_sigma: 0.1,
+            elevation_mask: 10.0,
+            innovation_gate: Some(10.83), // 99.9 %
+            initial_velocity_sigma: 100.0,
+            corrections: Corrections::default(),
+        }
+    }
+}
+
+/// Filtered receiver state after one epoch
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterSolution {
+    pub epoch: DateTime<Utc>,
+    pub position: ECEF,
+    pub velocity: ECEF,
+    pub clock_bias: f64,         // s
+    pub clock_drift: f64,        // s/s
+    pub covariance: Array2<f64>, // Of the 8-element state, m and m/s
+    pub used: Vec<SatId>,
+    pub rejected: Vec<SatId>, // Failed the innovation gate
+    pub clock_jump: i64,      // Receiver clock step absorbed at this epoch, ms
+    pub post_fit: Residuals,  // Code residuals of the used satellites at the updated state
+}
+
+/// Code and Doppler rows (h, innovation, variance) of one satellite; the code row is first
+struct SatelliteRows {
+    sat_id: SatId,
+    aer: AER,
+    rows: Vec<(Array1<f64>, f64, f64)>,
+}
+
+/// Extended Kalman filter with a position-velocity-clock state, fed one epoch at a time.
+/// It initialises itself from a least-squares fix on the first epoch.
+pub struct PvFilter<'a> {
+    constellation: &'a Constellation,
+    config: FilterConfig,
+    aligner: EpochAligner<'a>,
+    state: Array1<f64>,
+    covariance: Array2<f64>,
+    last_time: Option<f64>,
+    clock_series: ReceiverClockSeries,
+}
+
+impl<'a> PvFilter<'a> {
+    pub fn new(constellation: &'a Constellation, config: FilterConfig) -> Self {
+        Self {
+            constellation,
+            config,
+            aligner: EpochAligner::new(constellation, PropagationConfig::new().with_velocity(true)),
+            state: Array1::zeros(STATE_LEN),
+            covariance: Array2::zeros((STATE_LEN, STATE_LEN)),
+            last_time: None,
+            clock_series: ReceiverClockSeries::new(true),
+        }
+    }
+
+    /// State vector (x, y, z, vx, vy, vz, c*dt, c*dt_dot) after the last update
+    pub fn state(&self) -> &Array1<f64> {
+        &self.state
+    }
+
+    pub fn covariance(&self) -> &Array2<f64> {
+        &self.covariance
+    }
+
+    /// Receiver clock of every epoch so far, with millisecond steps repaired
+    pub fn clock_series(&self) -> &ReceiverClockSeries {
+        &self.clock_series
+    }
+
+    /// Predict to the epoch and update with its pseudoranges and any Dopplers
+    pub fn update(
+        &mut self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+    ) -> Result<FilterSolution, PositioningError> {
+        let time_tag = gnss::gps_seconds(epoch);
+        let Some(last_time) = self.last_time else {
+            let fix = self.initialise(epoch, observations)?;
+            self.last_time = Some(time_tag);
+            let used = fix.residuals.iter().map(|(sat_id, _)| *sat_id).collect();
+            let mut solution = self.solution(epoch, used, vec![]);
+            solution.post_fit = fix.post_fit;
+            self.clock_series.push_filter(&solution);
+            return Ok(solution);
+        };
+        self.predict(time_tag - last_time);
+        self.last_time = Some(time_tag);
+
+        let mut rows = self.measurement_rows(epoch, observations);
+        // Consumer receivers step their clock by whole milliseconds; re-centre on such a step
+        // before it makes every pseudorange fail the gate
+        let mut code_innovations: Vec<f64> = rows.iter().map(|sat| sat.rows[0].1).collect();
+        code_innovations.sort_by(f64::total_cmp);
+        let clock_jump = code_innovations
+            .get(code_innovations.len() / 2)
+            .map_or(0, |median| {
+                (median / (CLOCK_JUMP * gnss::C_LIGHT)).round() as i64
+            });
+        if clock_jump != 0 {
+            self.state[CLOCK] += clock_jump as f64 * CLOCK_JUMP * gnss::C_LIGHT;
+            rows = self.measurement_rows(epoch, observations);
+        }
+
+        let prior = self.state.clone();
+        let mut used = Vec::new();
+        let mut rejected = Vec::new();
+        for sat in &rows {
+            let mut accepted = true;
+            for (h, prior_innovation, variance) in &sat.rows {
+                let innovation = prior_innovation - h.dot(&(&self.state - &prior));
+                if !self.scalar_update(h, innovation, *variance) {
+                    accepted = false;
+                }
+            }
+            if accepted {
+                used.push(sat.sat_id);
+            } else {
+                rejected.push(sat.sat_id);
+            }
+        }
+
+        let mut post_fit = Residuals::new();
+        for sat in rows.iter().filter(|sat| used.contains(&sat.sat_id)) {
+            let (h, prior_innovation, variance) = &sat.rows[0];
+            let record = ResidualRecord {
+                epoch,
+                residual: prior_innovation - h.dot(&(&self.state - &prior)),
+                elevation: sat.aer.elevation,
+                azimuth: sat.aer.azimuth,
+                weight: 1.0 / variance,
+            };
+            post_fit.push(sat.sat_id, record);
+        }
+        let mut solution = self.solution(epoch, used, rejected);
+        solution.clock_jump = clock_jump;
+        solution.post_fit = post_fit;
+        self.clock_series.push_filter(&solution);
+        Ok(solution)
+    }
+
+    /// Measurement rows of every usable satellite, linearised at the current state
+    fn measurement_rows(
+        &mut self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+    ) -> Vec<SatelliteRows> {
+        let position = ECEF::new(self.state[0], self.state[1], self.state[2]);
+        let velocity = ECEF::new(self.state[3], self.state[4], self.state[5]);
+        let clock_bias = self.state[CLOCK] / gnss::C_LIGHT;
+        let aligned = self
+            .aligner
+            .align(epoch, observations, &position, Some(clock_bias));
+        let receiver = position.to_lla();
+        let mut all_rows = Vec::new();
+        for AlignedObservation {
+            observation: obs,
+            mut model,
+            line_of_sight: unit,
+            satellite_velocity,
+            satellite_clock_drift,
+        } in aligned.observations
+        {
+            if model.aer.elevation < self.config.elevation_mask {
+                continue;
+            }
+            self.config
+                .corrections
+                .apply(obs.sat_id, &receiver, &mut model);
+            let mut rows = Vec::new();
+            let mut h = Array1::zeros(STATE_LEN);
+            h[0] = -unit.x;
+            h[1] = -unit.y;
+            h[2] = -unit.z;
+            h[CLOCK] = 1.0;
+            let predicted = model.total() + self.state[CLOCK];
+            let sigma = self.config.weighting.sigma(model.aer.elevation);
+            rows.push((h, obs.pseudorange - predicted, sigma * sigma));
+            if let (Some(doppler), Some(sat_velocity)) = (obs.doppler, satellite_velocity) {
+                let mut h = Array1::zeros(STATE_LEN);
+                h[3] = -unit.x;
+                h[4] = -unit.y;
+                h[5] = -unit.z;
+                h[DRIFT] = 1.0;
+                let range_rate = unit.dot(&(sat_velocity - velocity));
+                let range_rate =
+                    range_rate - gnss::C_LIGHT * satellite_clock_drift + self.state[DRIFT];
+                let observed = -doppler * Signal::GpsL1.wavelength();
+                let variance = self.config.doppler_sigma.powi(2);
+                rows.push((h, observed - range_rate, variance));
+            }
+            all_rows.push(SatelliteRows {
+                sat_id: obs.sat_id,
+                aer: model.aer,
+                rows,
+            });
+        }
+        all_rows
+    }
+
+    fn solution(
+        &self,
+        epoch: DateTime<Utc>,
+        used: Vec<SatId>,
+        rejected: Vec<SatId>,
+    ) -> FilterSolution {
+        FilterSolution {
+            epoch,
+            position: ECEF::new(self.state[0], self.state[1], self.state[2]),
+            velocity: ECEF::new(self.state[3], self.state[4], self.state[5]),
+            clock_bias: self.state[CLOCK] / gnss::C_LIGHT,
+            clock_drift: self.state[DRIFT] / gnss::C_LIGHT,
+            covariance: self.covariance.clone(),
+            used,
+            rejected,
+            clock_jump: 0,
+            post_fit: Residuals::new(),
+        }
+    }
+
+    /// Start from a least-squares fix with an unknown velocity and clock drift
+    fn initialise(
+        &mut self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+    ) -> Result<SppSolution, PositioningError> {
+        let options = SppOptions {
+            weighting: self.config.weighting,
+            elevation_mask: self.config.elevation_mask,
+            corrections: self.config.corrections.clone(),
+            ..SppOptions::default()
+        };
+        let fix = self
+            .constellation
+            .solve_spp(epoch, observations, &options)?;
+        self.state = Array1::zeros(STATE_LEN);
+        self.state[0] = fix.position.x;
+        self.state[1] = fix.position.y;
+        self.state[2] = fix.position.z;
+        self.state[CLOCK] = fix.clock_bias * gnss::C_LIGHT;
+        self.covariance = Array2::zeros((STATE_LEN, STATE_LEN));
+        let fix_states = [0, 1, 2, CLOCK];
+        for (a, &i) in fix_states.iter().enumerate() {
+            for (b, &k) in fix_states.iter().enumerate() {
+                self.covariance[[i, k]] = fix.covariance[[a, b]];
+            }
+        }
+        let velocity_var = self.config.initial_velocity_sigma.powi(2);
+        for i in 3..6 {
+            self.covariance[[i, i]] = velocity_var;
+        }
+        self.covariance[[DRIFT, DRIFT]] = velocity_var;
+        Ok(fix)
+    }
+
+    /// Constant-velocity prediction over dt seconds
+    fn predict(&mut self, dt: f64) {
+        let mut transition = Array2::eye(STATE_LEN);
+        let mut noise = Array2::zeros((STATE_LEN, STATE_LEN));
+        for axis in 0..3 {
+            transition[[axis, axis + 3]] = dt;
+            let q = self.config.acceleration_psd[axis];
+            noise[[axis, axis]] = q * dt.powi(3) / 3.0;
+            noise[[axis, axis + 3]] = q * dt.powi(2) / 2.0;
+            noise[[axis + 3, axis]] = q * dt.powi(2) / 2.0;
+            noise[[axis + 3, axis + 3]] = q * dt;
+        }
+        transition[[CLOCK, DRIFT]] = dt;
+        let (qc, qd) = (self.config.clock_psd, self.config.drift_psd);
+        noise[[CLOCK, CLOCK]] = qc * dt + qd * dt.powi(3) / 3.0;
+        noise[[CLOCK, DRIFT]] = qd * dt.powi(2) / 2.0;
+        noise[[DRIFT, CLOCK]] = qd * dt.powi(2) / 2.0;
+        noise[[DRIFT, DRIFT]] = qd * dt;
+
+        self.state = transition.dot(&self.state);
+        self.covariance = transition.dot(&self.covariance).dot(&transition.t()) + noise;
+    }
+
+    /// Kalman update with one scalar measurement, false if it was gated out
+    fn scalar_update(&mut self, h: &Array1<f64>, innovation: f64, variance: f64) -> bool {
+        let ph = self.covariance.dot(h);
+        let s = h.dot(&ph) + variance;
+        if let Some(gate) = self.config.innovation_gate {
+            if innovation * innovation / s > gate {
+                return false;
+            }
+        }
+        let gain = &ph / s;
+        self.state = &self.state + &(&gain * innovation);
+        let gain_col = gain.view().insert_axis(Axis(1));
+        let ph_row = ph.view().insert_axis(Axis(0));
+        self.covariance = &self.covariance - &gain_col.dot(&ph_row);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::klobuchar::Klobuchar;
+    use crate::test_support::epochs;
+    use crate::test_support::station;
+    use crate::test_support::NAV;
+    use crate::troposphere::Saastamoinen;
+
+    fn corrections() -> Corrections {
+        Corrections {
+            troposphere: Some(Saastamoinen::new()),
+            klobuchar: Some(Klobuchar::new(
+                [1.8626e-08, 1.4901e-08, -1.1921e-07, -5.9605e-08],
+                [1.2698e+05, 0.0, -1.9661e+05, -6.5536e+04],
+            )),
+            ..Corrections::default()
+        }
+    }
+
+    /// RMS distance of positions from their own mean
+    fn scatter(positions: &[ECEF]) -> f64 {
+        let n = positions.len() as f64;
+        let mean = positions
+            .iter()
+            .fold(ECEF::default(), |sum, position| sum + *position);
+        let mean = ECEF::new(mean.x / n, mean.y / n, mean.z / n);
+        let sum: f64 = positions
+            .iter()
+            .map(|position| position.distance_to(&mean).powi(2))
+            .sum();
+        (sum / n).sqrt()
+    }
+
+    #[test]
+    fn simulated_static_station_converges_tighter_than_spp() {
+        let constellation = Constellation::from_nav(NAV.parse::<gnss::RinexNav>().unwrap());
+        let config = FilterConfig {
+            acceleration_psd: [1e-9; 3],
+            doppler_sigma: 0.01,
+            weighting: Weighting::Sine { sigma0: 0.5 },
+            corrections: corrections(),
+            ..FilterConfig::default()
+        };
+        let options = SppOptions {
+            weighting: config.weighting,
+            elevation_mask: config.elevation_mask,
+            corrections: corrections(),
+            ..SppOptions::default()
+        };
+        let mut filter = PvFilter::new(&constellation, config);
+        let mut filtered = Vec::new();
+        let mut single = Vec::new();
+        let mut sigmas = Vec::new();
+        for (epoch, observations) in epochs() {
+            let solution = filter.update(epoch, &observations).unwrap();
+            assert!(solution.rejected.is_empty(), "{:?}", solution.rejected);
+            let fix = constellation
+                .solve_spp(epoch, &observations, &options)
+                .unwrap();
+            filtered.push(solution.position);
+            single.push(fix.position);
+            sigmas.push(
+                (0..3)
+                    .map(|i| solution.covariance[[i, i]])
+                    .sum::<f64>()
+                    .sqrt(),
+            );
+        }
+
+        // Once settled, the filter wanders far less than the independent fixes
+        let settled = filtered.len() / 2;
+        let (filter_scatter, spp_scatter) =
+            (scatter(&filtered[settled..]), scatter(&single[settled..]));
+        assert!(
+            filter_scatter < 0.5 * spp_scatter,
+            "{} {}",
+            filter_scatter,
+            spp_scatter
+        );
+        let jitter = |positions: &[ECEF]| {
+            let steps = positions
+                .windows(2)
+                .map(|w| w[1].distance_to(&w[0]).powi(2));
+            (steps.sum::<f64>() / (positions.len() - 1) as f64).sqrt()
+        };
+        let (filter_jitter, spp_jitter) =
+            (jitter(&filtered[settled..]), jitter(&single[settled..]));
+        assert!(
+            filter_jitter < 0.2 * spp_jitter,
+            "{} {}",
+            filter_jitter,
+            spp_jitter
+        );
+        // Its position uncertainty keeps shrinking once the velocity is pinned down
+        assert!(
+            sigmas[settled..].windows(2).all(|w| w[1] < w[0]),
+            "{:?}",
+            sigmas
+        );
+        let peak = sigmas.iter().copied().fold(0.0, f64::max);
+        assert!(sigmas[sigmas.len() - 1] < 0.6 * peak, "{:?}", sigmas);
+        let error = filtered.last().unwrap().distance_to(&station());
+        assert!(error < 5.0, "{} m", error);
+        // The station does not move, and the clock drifts at the simulated 2 ns/s
+        let state = filter.state();
+        let speed = (state[3].powi(2) + state[4].powi(2) + state[5].powi(2)).sqrt();
+        assert!(speed < 0.01, "{} m/s", speed);
+        assert!((state[DRIFT] / gnss::C_LIGHT - 2e-9).abs() < 2e-10);
+    }
+}
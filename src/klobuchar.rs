@@ -0,0 +1,157 @@
+use crate::gnss::{self, ECEF, LLA};
+use crate::signal::Signal;
+use std::f64::consts::PI;
+
+const NIGHT_DELAY: f64 = 5e-9; // Constant night-time vertical delay of both models, s
+const GPS_IPP_OFFSET: f64 = 0.416; // Bound on the pierce point latitude, semicircles
+const GPS_MIN_PERIOD: f64 = 72000.0; // s
+const BEIDOU_MIN_PERIOD: f64 = 72000.0;
+const BEIDOU_MAX_PERIOD: f64 = 172800.0;
+const BEIDOU_EARTH_RADIUS: f64 = 6378.0; // km
+const BEIDOU_SHELL_HEIGHT: f64 = 375.0; // Single-layer height, km
+
+/// Broadcast single-frequency ionospheric correction of GPS (IS-GPS-200 20.3.3.5.2.5) and
+/// BeiDou (BDS-SIS-ICD B1I 5.2.4.7): a cosine of local time over a constant night-time
+/// delay, from the eight coefficients of the navigation message
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Klobuchar {
+    pub alpha: [f64; 4], // Amplitude, s per semicircle^n
+    pub beta: [f64; 4],  // Period, s per semicircle^n
+}
+
+impl Klobuchar {
+    pub fn new(alpha: [f64; 4], beta: [f64; 4]) -> Self {
+        Self { alpha, beta }
+    }
+
+    /// GPS model delay on L1 between a receiver and a satellite position at a GPS time, m
+    pub fn delay(&self, receiver: &LLA, satellite: &ECEF, gps_time: f64) -> f64 {
+        let aer = receiver.aer_to(satellite);
+        if aer.elevation <= 0.0 {
+            return 0.0;
+        }
+        // Angles in semicircles, as the coefficients expect
+        let elevation = aer.elevation / 180.0;
+        let azimuth = aer.azimuth.to_radians();
+        let psi = 0.0137 / (elevation + 0.11) - 0.022;
+        let ipp_latitude = (receiver.latitude / 180.0 + psi * azimuth.cos())
+            .clamp(-GPS_IPP_OFFSET, GPS_IPP_OFFSET);
+        let ipp_longitude =
+            receiver.longitude / 180.0 + psi * azimuth.sin() / (ipp_latitude * PI).cos();
+        let geomagnetic = ipp_latitude + 0.064 * ((ipp_longitude - 1.617) * PI).cos();
+        let local_time = (4.32e4 * ipp_longitude + gps_time).rem_euclid(86400.0);
+        let obliquity = 1.0 + 16.0 * (0.53 - elevation).powi(3);
+
+        let amplitude = polynomial(&self.alpha, geomagnetic).max(0.0);
+        let period = polynomial(&self.beta, geomagnetic).max(GPS_MIN_PERIOD);
+        let x = 2.0 * PI * (local_time - 50400.0) / period;
+        let vertical = match x.abs() < 1.57 {
+            true => NIGHT_DELAY + amplitude * (1.0 - x * x / 2.0 + x.powi(4) / 24.0),
+            false => NIGHT_DELAY,
+        };
+        gnss::C_LIGHT * obliquity * vertical
+    }
+
+    /// BeiDou model delay scaled from B1I to L1 between a receiver and a satellite
+    /// position at a GPS time, m
+    pub fn beidou_delay(&self, receiver: &LLA, satellite: &ECEF, gps_time: f64) -> f64 {
+        let aer = receiver.aer_to(satellite);
+        if aer.elevation <= 0.0 {
+            return 0.0;
+        }
+        let elevation = aer.elevation.to_radians();
+        let azimuth = aer.azimuth.to_radians();
+        let (sin_lat, cos_lat) = receiver.latitude.to_radians().sin_cos();
+        let ratio = BEIDOU_EARTH_RADIUS / (BEIDOU_EARTH_RADIUS + BEIDOU_SHELL_HEIGHT);
+        let psi = PI / 2.0 - elevation - (ratio * elevation.cos()).asin();
+        let ipp_latitude = (sin_lat * psi.cos() + cos_lat * psi.sin() * azimuth.cos()).asin();
+        let ipp_longitude = receiver.longitude.to_radians()
+            + (psi.sin() * azimuth.sin() / ipp_latitude.cos()).asin();
+        // Local time at the pierce point in BDT, which runs 14 s behind GPS time
+        let local_time =
+            (gps_time - gnss::BDT_OFFSET + ipp_longitude * 43200.0 / PI).rem_euclid(86400.0);
+
+        // Unlike GPS, the polynomials take the geographic latitude, unsigned
+        let latitude = (ipp_latitude / PI).abs();
+        let amplitude = polynomial(&self.alpha, latitude).max(0.0);
+        let period = polynomial(&self.beta, latitude).clamp(BEIDOU_MIN_PERIOD, BEIDOU_MAX_PERIOD);
+        let vertical = match (local_time - 50400.0).abs() < period / 4.0 {
+            true => NIGHT_DELAY + amplitude * (2.0 * PI * (local_time - 50400.0) / period).cos(),
+            false => NIGHT_DELAY,
+        };
+        let slant = vertical / (1.0 - (ratio * elevation.cos()).powi(2)).sqrt();
+        let scale = (Signal::BeiDouB1I.frequency_hz() / gnss::L1_FREQUENCY).powi(2);
+        gnss::C_LIGHT * slant * scale
+    }
+}
+
+fn polynomial(coefficients: &[f64; 4], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |sum, c| sum * x + c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Of the magnitude the GPS navigation message broadcasts at moderate solar activity
+    const ALPHA: [f64; 4] = [1.8626e-08, 1.4901e-08, -1.1921e-07, -5.9605e-08];
+    const BETA: [f64; 4] = [1.2698e+05, 0.0, -1.9661e+05, -6.5536e+04];
+
+    fn receiver() -> LLA {
+        LLA::new(40.0, -100.0, 0.0)
+    }
+
+    /// A point at a given azimuth and elevation from the receiver at GPS orbit range
+    fn satellite(azimuth: f64, elevation: f64) -> ECEF {
+        let (sin_az, cos_az) = azimuth.to_radians().sin_cos();
+        let (sin_el, cos_el) = elevation.to_radians().sin_cos();
+        let range = 2.2e7;
+        let (east, north, up) = (
+            range * cos_el * sin_az,
+            range * cos_el * cos_az,
+            range * sin_el,
+        );
+        let lla = receiver();
+        let (sin_lat, cos_lat) = lla.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = lla.longitude.to_radians().sin_cos();
+        let origin = lla.to_ecef();
+        ECEF::new(
+            origin.x - sin_lon * east - sin_lat * cos_lon * north + cos_lat * cos_lon * up,
+            origin.y + cos_lon * east - sin_lat * sin_lon * north + cos_lat * sin_lon * up,
+            origin.z + cos_lat * north + sin_lat * up,
+        )
+    }
+
+    #[test]
+    fn gps_delay_matches_the_hand_computed_value() {
+        let model = Klobuchar::new(ALPHA, BETA);
+        // 13:42 local time at the pierce point, near the afternoon maximum
+        let delay = model.delay(&receiver(), &satellite(210.0, 20.0), 247200.0);
+        assert!((delay - 12.720412).abs() < 1e-5, "{}", delay);
+    }
+
+    #[test]
+    fn gps_night_delay_is_the_constant_term() {
+        let model = Klobuchar::new(ALPHA, BETA);
+        // Local midnight at the zenith: 5 ns, mapped by the obliquity factor
+        let gps_time = 86400.0 * 2.0 + 100.0 / 360.0 * 86400.0;
+        let delay = model.delay(&receiver(), &satellite(0.0, 90.0), gps_time);
+        let obliquity = 1.0 + 16.0 * (0.53f64 - 0.5).powi(3);
+        assert!((delay - gnss::C_LIGHT * 5e-9 * obliquity).abs() < 1e-6);
+        assert_eq!(
+            model.delay(&receiver(), &satellite(0.0, -5.0), gps_time),
+            0.0
+        );
+    }
+
+    #[test]
+    fn beidou_delay_matches_the_hand_computed_value() {
+        let model = Klobuchar::new(
+            [1.2107e-08, 1.4901e-07, -1.0729e-06, 1.7881e-06],
+            [1.2902e+05, -1.6384e+05, 3.9322e+05, -3.9322e+05],
+        );
+        let delay = model.beidou_delay(&receiver(), &satellite(210.0, 20.0), 247200.0);
+        assert!((delay - 12.257477).abs() < 1e-5, "{}", delay);
+    }
+}
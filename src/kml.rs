@@ -0,0 +1,331 @@
+use crate::constellation::Constellation;
+use crate::gnss::LLA;
+use crate::satellite::{split_at_antimeridian, Satellite};
+use crate::visibility::Pass;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std-fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+
+const KML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+<Document>"#;
+const KML_FOOTER: &str = "</Document>\n</kml>";
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// What a KML export contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KmlOptions {
+    /// One Placemark per propagation step with a TimeSpan, so Google Earth's time slider
+    /// animates the track, instead of a single Placemark per satellite
+    pub animate: bool,
+}
+
+impl Satellite {
+    /// Write the ground track as a KML document: longitude, latitude and absolute altitude
+    /// of every propagated state, split where it crosses the antimeridian
+    pub fn write_kml(&self, mut writer: impl Write, options: &KmlOptions) -> io::Result<()> {
+        writeln!(writer, "{}", KML_HEADER)?;
+        self.write_kml_placemarks(&mut writer, options)?;
+        writeln!(writer, "{}", KML_FOOTER)?;
+        writer.flush()
+    }
+
+    /// `write_kml` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_kml(&self, path: &str, options: &KmlOptions) -> io::Result<()> {
+        self.write_kml(BufWriter::new(File::create(path)?), options)
+    }
+
+    fn write_kml_placemarks(
+        &self,
+        writer: &mut impl Write,
+        options: &KmlOptions,
+    ) -> io::Result<()> {
+        let track = self.ground_track();
+        if track.is_empty() {
+            return Ok(());
+        }
+        let name = escape(&self.id.to_string());
+        if !options.animate {
+            writeln!(writer, "<Placemark>")?;
+            writeln!(writer, "<name>{}</name>", name)?;
+            if !self.name.is_empty() {
+                writeln!(writer, "<description>{}</description>", escape(&self.name))?;
+            }
+            let points: Vec<LLA> = track.iter().map(|(_, lla)| *lla).collect();
+            write_lines(writer, &split_at_antimeridian(&points))?;
+            return writeln!(writer, "</Placemark>");
+        }
+        writeln!(writer, "<Folder>")?;
+        writeln!(writer, "<name>{}</name>", name)?;
+        for pair in track.windows(2) {
+            let [(begin, from), (end, to)] = pair else {
+                continue;
+            };
+            writeln!(writer, "<Placemark>")?;
+            writeln!(writer, "<name>{}</name>", name)?;
+            write_time_span(writer, *begin, *end)?;
+            write_lines(writer, &split_at_antimeridian(&[*from, *to]))?;
+            writeln!(writer, "</Placemark>")?;
+        }
+        writeln!(writer, "</Folder>")
+    }
+}
+
+impl Constellation {
+    /// Ground tracks of every propagated satellite as one KML document, one Placemark (or
+    /// Folder, when animated) per satellite
+    pub fn write_kml(&self, mut writer: impl Write, options: &KmlOptions) -> io::Result<()> {
+        writeln!(writer, "{}", KML_HEADER)?;
+        for satellite in self.iter() {
+            satellite.write_kml_placemarks(&mut writer, options)?;
+        }
+        writeln!(writer, "{}", KML_FOOTER)?;
+        writer.flush()
+    }
+
+    /// `write_kml` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_kml(&self, path: &str, options: &KmlOptions) -> io::Result<()> {
+        self.write_kml(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+/// Passes as Placemarks at the ground site, with rise, culmination and set times and the
+/// maximum elevation in their descriptions
+pub fn write_passes_kml(mut writer: impl Write, site: &LLA, passes: &[Pass]) -> io::Result<()> {
+    writeln!(writer, "{}", KML_HEADER)?;
+    for pass in passes {
+        writeln!(writer, "<Placemark>")?;
+        writeln!(writer, "<name>{}</name>", escape(&pass.sat_id.to_string()))?;
+        writeln!(
+            writer,
+            "<description>Rise {}, culmination {} at {:.1}°, set {}</description>",
+            pass.rise.format(TIME_FORMAT),
+            pass.culmination.format(TIME_FORMAT),
+            pass.max_elevation,
+            pass.set.format(TIME_FORMAT)
+        )?;
+        write_time_span(&mut writer, pass.rise, pass.set)?;
+        writeln!(
+            writer,
+            "<Point><coordinates>{},{},{}</coordinates></Point>",
+            site.longitude, site.latitude, site.altitude
+        )?;
+        writeln!(writer, "</Placemark>")?;
+    }
+    writeln!(writer, "{}", KML_FOOTER)?;
+    writer.flush()
+}
+
+fn write_time_span(
+    writer: &mut impl Write,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "<TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>",
+        begin.format(TIME_FORMAT),
+        end.format(TIME_FORMAT)
+    )
+}
+
+fn write_lines(writer: &mut impl Write, segments: &[Vec<LLA>]) -> io::Result<()> {
+    writeln!(writer, "<MultiGeometry>")?;
+    for segment in segments {
+        writeln!(writer, "<LineString>")?;
+        writeln!(writer, "<altitudeMode>absolute</altitudeMode>")?;
+        write!(writer, "<coordinates>")?;
+        for (i, point) in segment.iter().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            write!(
+                writer,
+                "{}{},{},{}",
+                separator, point.longitude, point.latitude, point.altitude
+            )?;
+        }
+        writeln!(writer, "</coordinates>")?;
+        writeln!(writer, "</LineString>")?;
+    }
+    writeln!(writer, "</MultiGeometry>")
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::{start, NAV};
+    use std::time::Duration;
+
+    fn config() -> PropagationConfig {
+        PropagationConfig::new().step(Duration::from_secs(300))
+    }
+
+    fn satellite() -> Satellite {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut satellite = Satellite::builder(17).name("SVN53 <IIR-M>").build();
+        let records = nav.records_for_slice(17.into());
+        let day = Duration::from_secs(86400);
+        satellite
+            .propagate(start(), day, &config(), records)
+            .unwrap();
+        satellite
+    }
+
+    fn write(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Every element closed in order, nothing but entities after an ampersand and no
+    /// stray angle brackets in text
+    fn assert_well_formed(document: &str) {
+        let body = document.strip_prefix("<?xml").unwrap();
+        let body = &body[body.find("?>").unwrap() + 2..];
+        let mut open: Vec<&str> = Vec::new();
+        let mut rest = body;
+        while let Some(start) = rest.find('<') {
+            let text = &rest[..start];
+            assert!(!text.contains('>'), "stray > in {:?}", text);
+            for (i, _) in text.match_indices('&') {
+                let entity = &text[i..];
+                assert!(
+                    ["&amp;", "&lt;", "&gt;", "&quot;"]
+                        .iter()
+                        .any(|known| entity.starts_with(known)),
+                    "bare & in {:?}",
+                    text
+                );
+            }
+            let end = start + rest[start..].find('>').unwrap();
+            let tag = &rest[start + 1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "mismatched </{}>", name);
+            } else if !tag.ends_with('/') {
+                open.push(tag.split_whitespace().next().unwrap());
+            }
+            rest = &rest[end + 1..];
+        }
+        assert!(rest.trim().is_empty());
+        assert!(open.is_empty(), "unclosed {:?}", open);
+    }
+
+    fn coordinates(document: &str) -> Vec<Vec<(f64, f64, f64)>> {
+        document
+            .split("<coordinates>")
+            .skip(1)
+            .map(|block| {
+                block[..block.find("</coordinates>").unwrap()]
+                    .split(' ')
+                    .map(|triple| {
+                        let values: Vec<f64> =
+                            triple.split(',').map(|v| v.parse().unwrap()).collect();
+                        (values[0], values[1], values[2])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ground_track_is_well_formed_and_split_at_the_antimeridian() {
+        let satellite = satellite();
+        let document = write(|out| satellite.write_kml(out, &KmlOptions::default()));
+        assert_well_formed(&document);
+        assert_eq!(document.matches("<Placemark>").count(), 1);
+        assert!(document.contains("<description>SVN53 &lt;IIR-M&gt;</description>"));
+        assert_eq!(
+            document
+                .matches("<altitudeMode>absolute</altitudeMode>")
+                .count(),
+            document.matches("<LineString>").count()
+        );
+
+        let track = satellite.ground_track();
+        let crossings = track
+            .windows(2)
+            .filter(|pair| (pair[1].1.longitude - pair[0].1.longitude).abs() > 180.0)
+            .count();
+        assert!(crossings > 0);
+        let lines = coordinates(&document);
+        assert_eq!(lines.len(), crossings + 1);
+        // Each crossing ends one line and starts the next at the interpolated edge
+        let triples: usize = lines.iter().map(Vec::len).sum();
+        assert_eq!(triples, track.len() + 2 * crossings);
+        for line in &lines {
+            assert!(line
+                .windows(2)
+                .all(|pair| (pair[1].0 - pair[0].0).abs() < 180.0));
+        }
+        // Longitude first, then latitude and altitude
+        let (_, first) = track[0];
+        assert_eq!(
+            lines[0][0],
+            (first.longitude, first.latitude, first.altitude)
+        );
+    }
+
+    #[test]
+    fn animation_adds_a_time_spanned_placemark_per_step() {
+        let satellite = satellite();
+        let options = KmlOptions { animate: true };
+        let document = write(|out| satellite.write_kml(out, &options));
+        assert_well_formed(&document);
+        let steps = satellite.states.len() - 1;
+        assert_eq!(document.matches("<Placemark>").count(), steps);
+        assert_eq!(document.matches("<TimeSpan>").count(), steps);
+        assert!(document.contains(
+            "<TimeSpan><begin>2023-06-12T00:00:00Z</begin><end>2023-06-12T00:05:00Z</end></TimeSpan>"
+        ));
+    }
+
+    #[test]
+    fn constellation_writes_a_placemark_per_propagated_satellite() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut constellation = Constellation::from_nav(nav);
+        let statuses = constellation.propagate_all(start(), Duration::from_secs(3600), &config());
+        let propagated = constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .count();
+        assert!(propagated > 20 && propagated < statuses.len());
+        let document = write(|out| constellation.write_kml(out, &KmlOptions::default()));
+        assert_well_formed(&document);
+        assert_eq!(document.matches("<Placemark>").count(), propagated);
+        assert!(document.contains("<name>G17</name>"));
+    }
+
+    #[test]
+    fn passes_are_placemarks_at_the_site() {
+        let site = LLA::new(48.5, -3.25, 120.0);
+        let pass = Pass {
+            sat_id: gnss::SatId::gps(5),
+            rise: start(),
+            set: start() + chrono::Duration::minutes(90),
+            culmination: start() + chrono::Duration::minutes(40),
+            max_elevation: 61.34,
+            rise_clipped: false,
+            set_clipped: false,
+        };
+        let document = write(|out| write_passes_kml(out, &site, &[pass.clone(), pass]));
+        assert_well_formed(&document);
+        assert_eq!(document.matches("<Placemark>").count(), 2);
+        assert!(document.contains(
+            "<description>Rise 2023-06-12T00:00:00Z, culmination 2023-06-12T00:40:00Z at 61.3°, set 2023-06-12T01:30:00Z</description>"
+        ));
+        assert_eq!(coordinates(&document)[0], vec![(-3.25, 48.5, 120.0)]);
+    }
+}
@@ -1,2 +1,130 @@
-pub mod gnss;
-pub mod satellite;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod acquisition;
+#[cfg(feature = "std")]
+pub mod alignment;
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod antex;
+#[cfg(feature = "ndarray")]
+pub mod baseline;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod celestial;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod combination;
+#[cfg(feature = "std")]
+pub mod constellation;
+#[cfg(feature = "std")]
+pub mod corrections;
+#[cfg(feature = "std")]
+pub mod csv;
+#[cfg(feature = "std")]
+pub mod cycle_slip;
+#[cfg(feature = "std")]
+pub mod czml;
+#[cfg(feature = "std")]
+pub mod dcb;
+#[cfg(feature = "ndarray")]
+pub mod dop_map;
+#[cfg(feature = "std")]
+pub mod doppler;
+#[cfg(feature = "ndarray")]
+pub mod double_difference;
+#[cfg(feature = "download")]
+pub mod download;
+#[cfg(feature = "std")]
+pub mod eclipse;
+pub mod ephemeris;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+// Test builds link std, whose inherent methods would shadow these, so there only the
+// module's own tests call them
+#[cfg(any(not(feature = "std"), test))]
+mod float;
+#[cfg(feature = "geo-types")]
+pub mod geo;
+#[cfg(feature = "std")]
+pub mod geojson;
+pub mod gnss;
+#[cfg(feature = "std")]
+pub mod gpx;
+#[cfg(feature = "std")]
+pub mod horizon;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "ndarray")]
+pub mod kalman;
+#[cfg(feature = "std")]
+pub mod klobuchar;
+#[cfg(feature = "std")]
+pub mod kml;
+#[cfg(feature = "ndarray")]
+mod linalg;
+#[cfg(feature = "ndarray")]
+pub mod monte_carlo;
+#[cfg(feature = "std")]
+pub mod nequick;
+#[cfg(feature = "std")]
+pub mod nmea;
+#[cfg(feature = "net")]
+pub mod ntrip;
+#[cfg(feature = "std")]
+pub mod observation;
+#[cfg(feature = "std")]
+pub mod orbit;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "plot")]
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod positioning;
+#[cfg(feature = "std")]
+pub mod propagator;
+#[cfg(feature = "std")]
+pub mod pseudorange;
+#[cfg(feature = "std")]
+pub mod realtime;
+#[cfg(feature = "std")]
+pub mod residuals;
+#[cfg(feature = "ndarray")]
+pub mod rtk;
+#[cfg(feature = "std")]
+pub mod sat_info;
+#[cfg(feature = "std")]
+pub mod satellite;
+#[cfg(feature = "scenario")]
+pub mod scenario;
+#[cfg(feature = "ndarray")]
+pub mod selection;
+#[cfg(feature = "serial")]
+pub mod serial;
+#[cfg(feature = "net")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod signal;
+#[cfg(feature = "std")]
+pub mod simulation;
+#[cfg(feature = "std")]
+pub mod smoothing;
+#[cfg(feature = "std")]
+pub mod sp3;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod summary;
+#[cfg(all(test, feature = "std"))]
+mod test_support;
+#[cfg(feature = "std")]
+pub mod tides;
+#[cfg(feature = "std")]
+pub mod troposphere;
+pub mod units;
+#[cfg(feature = "std")]
+pub mod validation;
+#[cfg(feature = "std")]
+pub mod visibility;
@@ -0,0 +1,36 @@
+use ndarray::Array2;
+
+/// Inverse of a square matrix by Gauss-Jordan elimination with partial pivoting,
+/// None if it is singular to working precision
+pub(crate) fn invert(matrix: &Array2<f64>) -> Option<Array2<f64>> {
+    let n = matrix.nrows();
+    let mut a = matrix.clone();
+    let mut inverse = Array2::eye(n);
+    let scale = a.iter().fold(0.0f64, |max, v| max.max(v.abs()));
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[[i, col]].abs().total_cmp(&a[[j, col]].abs()))?;
+        if a[[pivot, col]].abs() <= scale * 1e-14 {
+            return None;
+        }
+        for k in 0..n {
+            a.swap([col, k], [pivot, k]);
+            inverse.swap([col, k], [pivot, k]);
+        }
+        let diag = a[[col, col]];
+        for k in 0..n {
+            a[[col, k]] /= diag;
+            inverse[[col, k]] /= diag;
+        }
+        for row in 0..n {
+            let factor = a[[row, col]];
+            if row == col || factor == 0.0 {
+                continue;
+            }
+            for k in 0..n {
+                a[[row, k]] -= factor * a[[col, k]];
+                inverse[[row, k]] -= factor * inverse[[col, k]];
+            }
+        }
+    }
+    Some(inverse)
+}
@@ -1,38 +1,294 @@
-use chrono::{DateTime, TimeZone, Utc};
-use pnt_rust::{gnss::RinexNav, satellite::Satellite};
-
-fn main() {
-    let sat_id: u8 = 17;
-    let mut satellite = Satellite::new(sat_id, String::from("ISS"));
-    let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 48).unwrap();
-
-    let duration = std::time::Duration::from_secs(1);
-    let step = std::time::Duration::from_millis(1);
-
-    let nav_data = RinexNav::from_file("constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
-
-    let ephemeris_data: Vec<_> = nav_data
-        .records
-        .clone()
-        .into_iter()
-        .filter(|record| record.sat_id == sat_id)
-        .collect();
-    println!("Total records: {}", nav_data.records.len());
-    println!("Filtered records for {}: {}", sat_id, ephemeris_data.len());
-
-    let begin_time = std::time::SystemTime::now();
-    let n_states = satellite.propagate(start, duration, step, &ephemeris_data);
-    let end_time = std::time::SystemTime::now();
-    let execution_time = end_time.duration_since(begin_time).unwrap();
-
-    let start_datetime: DateTime<Utc> = start.into();
-    let end_datetime: DateTime<Utc> = (start + duration).into();
-
-    println!(
-        "Propagated {} states from {} to {} in {:.3} ms",
-        n_states,
-        start_datetime.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
-        end_datetime.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
-        execution_time.as_micros() as f64 / 1_000.0
-    );
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use pnt_rust::{
+    constellation::Constellation,
+    csv::CsvOptions,
+    gnss::{RinexNav, SatId, ECEF, LLA},
+    kml::KmlOptions,
+    satellite::{PropagationConfig, Satellite},
+    scenario::Scenario,
+};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Broadcast ephemeris tools: inspect RINEX navigation files, propagate satellites, run
+/// scenario files and convert coordinates
+#[derive(Debug, Parser)]
+#[command(name = "pnt", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Records, time span and coverage of each satellite in a nav file
+    Info { nav: String },
+    /// Propagate one satellite and write its states
+    Propagate {
+        nav: String,
+        /// Satellite, e.g. G17
+        #[arg(long)]
+        sat: SatId,
+        /// First epoch, e.g. 2023-06-12T00:00:00Z; the satellite's first toc if omitted
+        #[arg(long)]
+        start: Option<DateTime<Utc>>,
+        /// Span to propagate, s
+        #[arg(long, default_value_t = 3600.0, allow_negative_numbers = true)]
+        duration: f64,
+        /// Interval between states, s
+        #[arg(long, default_value_t = 60.0, allow_negative_numbers = true)]
+        step: f64,
+        /// File to write; standard output if omitted
+        #[arg(long, short)]
+        output: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+    },
+    /// Convert one position between ECEF (m) and geodetic latitude, longitude (degrees)
+    /// and altitude (m)
+    Convert {
+        #[arg(long, value_enum)]
+        from: Frame,
+        #[arg(long, value_enum)]
+        to: Frame,
+        /// The three coordinates in the `from` frame
+        #[arg(
+            num_args = 3,
+            value_names = ["X|LAT", "Y|LON", "Z|ALT"],
+            allow_negative_numbers = true,
+            required = true
+        )]
+        coordinates: Vec<f64>,
+    },
+    /// Satellites above the elevation mask at an epoch, with azimuth, elevation and range
+    Visible {
+        nav: String,
+        /// Observer as latitude,longitude,altitude in degrees and meters
+        #[arg(long, value_parser = parse_site, allow_hyphen_values = true)]
+        site: LLA,
+        /// Elevation mask, degrees
+        #[arg(long, default_value_t = 10.0)]
+        mask: f64,
+        /// Epoch, e.g. 2023-06-12T12:00:00Z; the file's first toc if omitted
+        #[arg(long)]
+        time: Option<DateTime<Utc>>,
+    },
+    /// Carry out the propagation run a TOML scenario file describes
+    Run { scenario: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+    Kml,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Frame {
+    Ecef,
+    Lla,
+}
+
+/// Why a command failed, reported on standard error with a nonzero exit
+#[derive(Debug)]
+enum CliError {
+    Input(String), // Unusable file or argument
+    Io(io::Error), // Writing the output
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Input(message) => f.write_str(message),
+            Self::Io(error) => write!(f, "writing output: {}", error),
+        }
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        Self::Input(message)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command, &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        // Output piped into a reader that stopped early, e.g. `head`
+        Err(CliError::Io(error)) if error.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command, out: &mut impl Write) -> Result<(), CliError> {
+    match command {
+        Command::Info { nav } => {
+            writeln!(out, "{}", read_nav(&nav)?.summary())?;
+            Ok(())
+        }
+        Command::Propagate {
+            nav,
+            sat,
+            start,
+            duration,
+            step,
+            output,
+            format,
+        } => {
+            let nav = read_nav(&nav)?;
+            let start = match start {
+                Some(start) => start,
+                None => {
+                    nav.coverage(sat)
+                        .ok_or_else(|| format!("no records for {}", sat))?
+                        .first
+                }
+            };
+            let config = PropagationConfig::new()
+                .step(seconds("step", step)?)
+                .with_velocity(true);
+            let mut satellite = Satellite::builder(sat).build();
+            satellite
+                .propagate_from_nav(&nav, start, seconds("duration", duration)?, &config)
+                .map_err(|err| err.to_string())?;
+            match output {
+                Some(path) => {
+                    let file = File::create(&path)
+                        .map_err(|err| format!("cannot create {}: {}", path, err))?;
+                    write_states(&satellite, BufWriter::new(file), format)?
+                }
+                None => write_states(&satellite, out, format)?,
+            }
+            Ok(())
+        }
+        Command::Convert {
+            from,
+            to,
+            coordinates,
+        } => {
+            let [a, b, c] = coordinates[..] else {
+                return Err(CliError::Input("expected three coordinates".to_string()));
+            };
+            let ecef = match from {
+                Frame::Ecef => ECEF::new(a, b, c),
+                Frame::Lla => LLA::new(a, b, c).to_ecef(),
+            };
+            match to {
+                Frame::Ecef => writeln!(out, "{:.4} {:.4} {:.4}", ecef.x, ecef.y, ecef.z)?,
+                Frame::Lla => {
+                    let lla = ecef.to_lla();
+                    writeln!(
+                        out,
+                        "{:.9} {:.9} {:.4}",
+                        lla.latitude, lla.longitude, lla.altitude
+                    )?
+                }
+            }
+            Ok(())
+        }
+        Command::Visible {
+            nav,
+            site,
+            mask,
+            time,
+        } => {
+            let nav = read_nav(&nav)?;
+            let epoch = match time {
+                Some(time) => time,
+                None => {
+                    nav.time_span()
+                        .ok_or_else(|| "no toc to start from".to_string())?
+                        .0
+                }
+            };
+            let mut constellation = Constellation::from_nav(nav);
+            constellation.propagate_all(epoch, Duration::ZERO, &PropagationConfig::new());
+            let mut visible = constellation.visible(&site, epoch, mask);
+            visible.sort_by(|(_, a), (_, b)| b.elevation.total_cmp(&a.elevation));
+            writeln!(
+                out,
+                "{} satellites above {}° at {}",
+                visible.len(),
+                mask,
+                epoch.format(TIME_FORMAT)
+            )?;
+            for (sat_id, aer) in visible {
+                writeln!(
+                    out,
+                    "{} az {:6.2}° el {:5.2}° range {:.3} km",
+                    sat_id,
+                    aer.azimuth,
+                    aer.elevation,
+                    aer.range / 1000.0
+                )?;
+            }
+            Ok(())
+        }
+        Command::Run { scenario } => {
+            let report = Scenario::from_file(&scenario)
+                .and_then(|scenario| scenario.run())
+                .map_err(|err| err.to_string())?;
+            writeln!(out, "{}", report)?;
+            Ok(())
+        }
+    }
+}
+
+/// Nav file with at least one record
+fn read_nav(path: &str) -> Result<RinexNav, String> {
+    let nav = RinexNav::from_file(path).map_err(|err| format!("cannot open {}: {}", path, err))?;
+    match nav.records().is_empty() {
+        true => Err(format!("{} holds no navigation records", path)),
+        false => Ok(nav),
+    }
+}
+
+fn seconds(name: &str, value: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(value).map_err(|_| {
+        format!(
+            "{} must be a finite number of seconds >= 0, not {}",
+            name, value
+        )
+    })
+}
+
+fn parse_site(text: &str) -> Result<LLA, String> {
+    let values = text
+        .split(',')
+        .map(|value| value.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+    match values[..] {
+        [latitude, longitude, altitude] => Ok(LLA::new(latitude, longitude, altitude)),
+        [latitude, longitude] => Ok(LLA::new(latitude, longitude, 0.0)),
+        _ => Err("expected latitude,longitude[,altitude]".to_string()),
+    }
+}
+
+fn write_states(satellite: &Satellite, mut writer: impl Write, format: Format) -> io::Result<()> {
+    match format {
+        Format::Csv => satellite.write_csv(writer, &CsvOptions::default()),
+        Format::Kml => satellite.write_kml(writer, &KmlOptions::default()),
+        Format::Json => {
+            writeln!(writer, "{}", satellite.states_to_json()?)?;
+            writer.flush()
+        }
+    }
 }
@@ -0,0 +1,252 @@
+use crate::constellation::Constellation;
+use crate::gnss::{ECEF, ENU};
+use crate::positioning::SppOptions;
+use crate::signal::Signal;
+use crate::simulation::{ErrorModel, GaussianNoise, SimulationConfig};
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Scenario of a Monte Carlo SPP study
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonteCarloConfig {
+    pub position: ECEF, // Truth receiver position
+    pub start: DateTime<Utc>,
+    pub trials: usize,
+    pub epoch_step: Option<Duration>, // Slide each trial's epoch by this to vary the geometry
+    pub code_sigma: f64,              // White code noise, m
+    pub seed: u64, // Trial i uses seed + i, so results do not depend on threading
+    pub simulation: SimulationConfig,
+    pub spp: SppOptions,
+}
+
+impl MonteCarloConfig {
+    pub fn new(position: ECEF, start: DateTime<Utc>, trials: usize) -> Self {
+        Self {
+            position,
+            start,
+            trials,
+            epoch_step: None,
+            code_sigma: 1.0,
+            seed: 0,
+            simulation: SimulationConfig::default(),
+            spp: SppOptions::default(),
+        }
+    }
+}
+
+/// Outcome of one trial
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrialResult {
+    pub epoch: DateTime<Utc>,
+    pub error: ENU, // Solution minus truth
+    pub horizontal_error: f64,
+    pub vertical_error: f64, // Absolute
+    pub hdop: f64,
+    pub vdop: f64,
+    pub satellites: usize,
+}
+
+/// Empirical distribution of an error, m
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorStatistics {
+    pub mean: f64,
+    pub rms: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl ErrorStatistics {
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len() as f64;
+        let rank = ((0.95 * n).ceil() as usize).clamp(1, sorted.len());
+        Self {
+            mean: sorted.iter().sum::<f64>() / n,
+            rms: (sorted.iter().map(|v| v * v).sum::<f64>() / n).sqrt(),
+            p95: sorted[rank - 1],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Results of a Monte Carlo study, with the DOP-predicted RMS errors for comparison
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MonteCarloResult {
+    pub trials: Vec<TrialResult>,
+    pub failures: usize, // Trials without a solution
+    pub horizontal: ErrorStatistics,
+    pub vertical: ErrorStatistics,
+    pub predicted_horizontal_rms: f64, // sigma * RMS of HDOP
+    pub predicted_vertical_rms: f64,   // sigma * RMS of VDOP
+}
+
+impl Constellation {
+    /// Run randomized trials of simulated observations through SPP with white code noise
+    /// of `code_sigma`. Trials run in parallel with the `rayon` feature.
+    pub fn monte_carlo(&self, config: &MonteCarloConfig) -> MonteCarloResult {
+        self.monte_carlo_with(config, |seed| {
+            GaussianNoise::new(config.code_sigma, 0.0, 0.0, seed)
+        })
+    }
+
+    /// Like `monte_carlo`, with each trial's errors drawn from a model built for its seed.
+    /// The predicted RMS errors still assume white noise of `code_sigma`.
+    pub fn monte_carlo_with<M: ErrorModel>(
+        &self,
+        config: &MonteCarloConfig,
+        errors: impl Fn(u64) -> M + Sync,
+    ) -> MonteCarloResult {
+        #[cfg(not(feature = "rayon"))]
+        let trials = (0..config.trials).map(|trial| self.run_trial(config, trial, &errors));
+        #[cfg(feature = "rayon")]
+        let trials = (0..config.trials)
+            .into_par_iter()
+            .map(|trial| self.run_trial(config, trial, &errors));
+        let outcomes: Vec<Option<TrialResult>> = trials.collect();
+        let trials: Vec<TrialResult> = outcomes.iter().flatten().copied().collect();
+
+        let horizontal: Vec<f64> = trials.iter().map(|t| t.horizontal_error).collect();
+        let vertical: Vec<f64> = trials.iter().map(|t| t.vertical_error).collect();
+        let dop_rms = |dop: fn(&TrialResult) -> f64| match trials.len() {
+            0 => 0.0,
+            n => (trials.iter().map(|t| dop(t).powi(2)).sum::<f64>() / n as f64).sqrt(),
+        };
+        MonteCarloResult {
+            failures: outcomes.len() - trials.len(),
+            horizontal: ErrorStatistics::from_samples(&horizontal),
+            vertical: ErrorStatistics::from_samples(&vertical),
+            predicted_horizontal_rms: config.code_sigma * dop_rms(|t| t.hdop),
+            predicted_vertical_rms: config.code_sigma * dop_rms(|t| t.vdop),
+            trials,
+        }
+    }
+
+    fn run_trial<M: ErrorModel>(
+        &self,
+        config: &MonteCarloConfig,
+        trial: usize,
+        errors: &impl Fn(u64) -> M,
+    ) -> Option<TrialResult> {
+        let epoch = match config.epoch_step {
+            Some(step) => config.start + step * trial as i32,
+            None => config.start,
+        };
+        let seed = config.seed.wrapping_add(trial as u64);
+        let mut errors = errors(seed);
+        let observed = self.simulate_observations(
+            &[(epoch, config.position)],
+            &config.simulation,
+            &mut errors,
+        );
+        let observed = observed.first()?;
+        let signal = config
+            .simulation
+            .signals
+            .first()
+            .copied()
+            .unwrap_or(Signal::GpsL1);
+        let observations = observed.pseudoranges(signal);
+        let solution = self
+            .solve_spp(observed.epoch, &observations, &config.spp)
+            .ok()?;
+        let error = config
+            .position
+            .to_lla()
+            .rotate_to_enu(&(solution.position - config.position));
+        Some(TrialResult {
+            epoch,
+            error,
+            horizontal_error: error.east.hypot(error.north),
+            vertical_error: error.up.abs(),
+            hdop: solution.dop.hdop,
+            vdop: solution.dop.vdop,
+            satellites: solution.residuals.len(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "ndarray"))]
+mod tests {
+    use super::*;
+    use crate::test_support::{constellation, station};
+    use chrono::TimeZone;
+
+    fn config(trials: usize) -> MonteCarloConfig {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+        let station = station();
+        MonteCarloConfig {
+            seed: 42,
+            simulation: SimulationConfig {
+                elevation_mask: 10.0,
+                ..SimulationConfig::default()
+            },
+            ..MonteCarloConfig::new(station, start, trials)
+        }
+    }
+
+    #[test]
+    fn statistics_of_known_samples() {
+        let samples: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+        let stats = ErrorStatistics::from_samples(&samples);
+        assert_eq!(stats.mean, 10.5);
+        assert!((stats.rms - (2870.0f64 / 20.0).sqrt()).abs() < 1e-12);
+        assert_eq!(stats.p95, 19.0);
+        assert_eq!(stats.max, 20.0);
+        assert_eq!(
+            ErrorStatistics::from_samples(&[]),
+            ErrorStatistics::default()
+        );
+    }
+
+    #[test]
+    fn empirical_errors_match_the_dop_prediction() {
+        let result = constellation().monte_carlo(&config(300));
+        assert_eq!(result.failures, 0);
+        assert_eq!(result.trials.len(), 300);
+        let horizontal = result.horizontal.rms / result.predicted_horizontal_rms;
+        let vertical = result.vertical.rms / result.predicted_vertical_rms;
+        assert!((0.85..1.15).contains(&horizontal), "{}", horizontal);
+        assert!((0.85..1.15).contains(&vertical), "{}", vertical);
+        assert!(result.horizontal.mean < result.horizontal.rms);
+        assert!(result.horizontal.rms < result.horizontal.p95);
+        assert!(result.horizontal.p95 <= result.horizontal.max);
+    }
+
+    #[test]
+    fn trials_are_reproducible_and_slide_the_epoch() {
+        let constellation = constellation();
+        let mut config = config(6);
+        assert_eq!(
+            constellation.monte_carlo(&config),
+            constellation.monte_carlo(&config)
+        );
+
+        config.epoch_step = Some(Duration::minutes(20));
+        let result = constellation.monte_carlo(&config);
+        for (trial, outcome) in result.trials.iter().enumerate() {
+            assert_eq!(
+                outcome.epoch,
+                config.start + Duration::minutes(20 * trial as i64)
+            );
+        }
+        let hdops: Vec<f64> = result.trials.iter().map(|t| t.hdop).collect();
+        assert!(hdops.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn results_survive_a_json_round_trip() {
+        let result = constellation().monte_carlo(&config(3));
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: MonteCarloResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, result);
+    }
+}
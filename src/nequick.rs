@@ -0,0 +1,787 @@
+use crate::gnss::{self, ECEF, LLA};
+use chrono::{Datelike, Timelike};
+use std::f64::consts::PI;
+use std::fmt;
+#[cfg(feature = "std-fs")]
+use std::fs;
+#[cfg(feature = "std-fs")]
+use std::path::Path;
+use std::sync::Arc;
+#[cfg(nequick_data)]
+use std::sync::OnceLock;
+
+const EARTH_RADIUS: f64 = 6371.2; // Spherical Earth of the model, km
+const MODIP_ROWS: usize = 39; // -95° to 95° latitude in 5° steps, wrapped over the poles
+const MODIP_COLUMNS: usize = 39; // -190° to 190° longitude in 10° steps, wrapped
+const F2_SPATIAL: usize = 76;
+const F2_TEMPORAL: usize = 13;
+const FM3_SPATIAL: usize = 49;
+const FM3_TEMPORAL: usize = 9;
+const F2_LEN: usize = 2 * F2_SPATIAL * F2_TEMPORAL;
+const CCIR_LEN: usize = F2_LEN + 2 * FM3_SPATIAL * FM3_TEMPORAL;
+// Powers of sin(MODIP) used with each longitude harmonic
+const F2_ORDERS: [usize; 9] = [12, 12, 9, 5, 2, 1, 1, 1, 1];
+const FM3_ORDERS: [usize; 7] = [7, 8, 6, 3, 2, 1, 1];
+const HM_E: f64 = 120.0; // E layer peak height, km
+const INTEGRATION_MAX_DEPTH: u32 = 50;
+// Absolute error floor of the integrand, density in 1e11 m⁻³ over km: 1e-8 TECU
+const INTEGRATION_FLOOR: f64 = 1e-6;
+const TECU: f64 = 1e16; // Electrons per m²
+
+/// Failure to load the NeQuick-G data files
+#[derive(Debug, Clone, PartialEq)]
+pub enum NeQuickError {
+    Io {
+        file: String,
+        message: String,
+    },
+    Format {
+        file: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidNumber {
+        file: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for NeQuickError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { file, message } => write!(f, "{}: {}", file, message),
+            Self::Format {
+                file,
+                expected,
+                found,
+            } => write!(f, "{}: expected {} values, found {}", file, expected, found),
+            Self::InvalidNumber { file, value } => write!(f, "{}: invalid number {}", file, value),
+        }
+    }
+}
+
+impl std::error::Error for NeQuickError {}
+
+/// Gridded inputs of NeQuick-G: the MODIP map and the monthly CCIR foF2 / M(3000)F2 maps,
+/// as distributed with the Galileo ionospheric model specification
+#[derive(Clone, PartialEq)]
+pub struct NeQuickData {
+    modip: Vec<f64>,     // MODIP_ROWS x MODIP_COLUMNS, degrees
+    ccir: Vec<Vec<f64>>, // Per month: F2[2][76][13] then Fm3[2][49][9]
+}
+
+impl fmt::Debug for NeQuickData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NeQuickData")
+            .field("modip", &format_args!("{} values", self.modip.len()))
+            .field("ccir", &format_args!("{} months", self.ccir.len()))
+            .finish()
+    }
+}
+
+impl NeQuickData {
+    /// The grids of `data/nequick`, embedded at build time and parsed on first use; only
+    /// built when the Galileo files were in place
+    #[cfg(nequick_data)]
+    pub fn embedded() -> Arc<Self> {
+        macro_rules! grid {
+            ($file:literal) => {
+                include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/data/nequick/", $file))
+            };
+        }
+        static DATA: OnceLock<Arc<NeQuickData>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            let ccir = [
+                grid!("ccir11.asc"),
+                grid!("ccir12.asc"),
+                grid!("ccir13.asc"),
+                grid!("ccir14.asc"),
+                grid!("ccir15.asc"),
+                grid!("ccir16.asc"),
+                grid!("ccir17.asc"),
+                grid!("ccir18.asc"),
+                grid!("ccir19.asc"),
+                grid!("ccir20.asc"),
+                grid!("ccir21.asc"),
+                grid!("ccir22.asc"),
+            ];
+            let data = Self::from_texts(grid!("modipNeQG_wrapped.asc"), &ccir)
+                .expect("embedded NeQuick-G grids are malformed");
+            Arc::new(data)
+        })
+        .clone()
+    }
+
+    /// Load `modipNeQG_wrapped.asc` and `ccir11.asc` to `ccir22.asc` (January to
+    /// December) from a directory
+    #[cfg(feature = "std-fs")]
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, NeQuickError> {
+        let dir = dir.as_ref();
+        let modip = read_values(&dir.join("modipNeQG_wrapped.asc"))?;
+        let ccir = (11..=22)
+            .map(|n| read_values(&dir.join(format!("ccir{}.asc", n))))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(modip, ccir)
+    }
+
+    /// `from_dir` with the file contents already in memory: the MODIP file and the twelve
+    /// CCIR files in month order
+    pub fn from_texts(modip: &str, ccir: &[&str]) -> Result<Self, NeQuickError> {
+        let modip = parse_values(modip, "modipNeQG_wrapped.asc")?;
+        let ccir = ccir
+            .iter()
+            .enumerate()
+            .map(|(i, text)| parse_values(text, &format!("ccir{}.asc", i + 11)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(modip, ccir)
+    }
+
+    /// From the MODIP grid (39 rows of 39, south to north, west to east) and twelve
+    /// monthly CCIR coefficient sets in file order
+    pub fn new(modip: Vec<f64>, ccir: Vec<Vec<f64>>) -> Result<Self, NeQuickError> {
+        let check = |file: String, values: &[f64], expected: usize| match values.len() {
+            found if found == expected => Ok(()),
+            found => Err(NeQuickError::Format {
+                file,
+                expected,
+                found,
+            }),
+        };
+        check("MODIP grid".into(), &modip, MODIP_ROWS * MODIP_COLUMNS)?;
+        if ccir.len() != 12 {
+            return Err(NeQuickError::Format {
+                file: "CCIR maps".into(),
+                expected: 12,
+                found: ccir.len(),
+            });
+        }
+        for (month, values) in ccir.iter().enumerate() {
+            check(format!("CCIR month {}", month + 1), values, CCIR_LEN)?;
+        }
+        Ok(Self { modip, ccir })
+    }
+
+    /// Modified dip latitude at a geographic position, degrees
+    pub fn modip(&self, latitude: f64, longitude: f64) -> f64 {
+        if latitude <= -90.0 {
+            return -90.0;
+        }
+        if latitude >= 90.0 {
+            return 90.0;
+        }
+        let a = (latitude + 90.0) / 5.0;
+        let row = (a.floor() as usize).min(MODIP_ROWS - 4);
+        let x = a - row as f64;
+        let b = (longitude.rem_euclid(360.0) + 180.0).rem_euclid(360.0) / 10.0;
+        let column = (b.floor() as usize).min(35);
+        let y = b - column as f64;
+        let mut by_longitude = [0.0; 4];
+        for (k, value) in by_longitude.iter_mut().enumerate() {
+            let mut by_latitude = [0.0; 4];
+            for (j, z) in by_latitude.iter_mut().enumerate() {
+                *z = self.modip[(row + j) * MODIP_COLUMNS + column + k];
+            }
+            *value = interpolate(&by_latitude, x);
+        }
+        interpolate(&by_longitude, y)
+    }
+}
+
+#[cfg(feature = "std-fs")]
+fn read_values(path: &Path) -> Result<Vec<f64>, NeQuickError> {
+    let file = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|err| NeQuickError::Io {
+        file: file.clone(),
+        message: err.to_string(),
+    })?;
+    parse_values(&text, &file)
+}
+
+fn parse_values(text: &str, file: &str) -> Result<Vec<f64>, NeQuickError> {
+    text.split_whitespace()
+        .map(|value| {
+            value
+                .replace(['D', 'd'], "E")
+                .parse()
+                .map_err(|_| NeQuickError::InvalidNumber {
+                    file: file.to_string(),
+                    value: value.to_string(),
+                })
+        })
+        .collect()
+}
+
+/// Third-order interpolation between the middle two of four equally spaced values
+fn interpolate(z: &[f64; 4], x: f64) -> f64 {
+    if x.abs() < 1e-10 {
+        return z[1];
+    }
+    let delta = 2.0 * x - 1.0;
+    let g1 = z[2] + z[1];
+    let g2 = z[2] - z[1];
+    let g3 = z[3] + z[0];
+    let g4 = (z[3] - z[0]) / 3.0;
+    let a0 = 9.0 * g1 - g3;
+    let a1 = 9.0 * g2 - g4;
+    let a2 = g3 - g1;
+    let a3 = g4 - g2;
+    (a0 + delta * (a1 + delta * (a2 + delta * a3))) / 16.0
+}
+
+/// Smooth step from `low` (x well below 0) to `high` (x well above 0) with steepness `a`
+fn join(high: f64, low: f64, a: f64, x: f64) -> f64 {
+    let e = (a * x).min(700.0).exp();
+    (high * e + low) / (e + 1.0)
+}
+
+/// Epstein layer
+fn epstein(amplitude: f64, thickness: f64, peak: f64, height: f64) -> f64 {
+    let e = ((height - peak) / thickness).clamp(-700.0, 700.0).exp();
+    amplitude * e / ((1.0 + e) * (1.0 + e))
+}
+
+/// Galileo single-frequency ionospheric correction: the NeQuick-G electron density model
+/// driven by the broadcast effective ionisation coefficients and integrated along the
+/// receiver-satellite ray
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeQuickG {
+    pub coefficients: [f64; 3], // ai0 (sfu), ai1 (sfu/deg), ai2 (sfu/deg²)
+    data: Arc<NeQuickData>,
+}
+
+/// Time-dependent quantities shared by every point of a ray
+struct Context {
+    month: u32,
+    ut: f64, // Hours
+    sunspots: f64,
+    az: f64,
+    f2: Vec<f64>,  // foF2 coefficients at this UT
+    fm3: Vec<f64>, // M(3000)F2 coefficients at this UT
+    sin_declination: f64,
+    cos_declination: f64,
+}
+
+/// Vertical profile parameters at one location
+struct Profile {
+    nm_f2: f64, // 1e11 m⁻³
+    hm_f1: f64, // km
+    hm_f2: f64,
+    b2_bot: f64, // km
+    b1_top: f64,
+    b1_bot: f64,
+    be_top: f64,
+    amplitudes: [f64; 3], // F2, F1, E, 1e11 m⁻³
+    h0: f64,              // Topside thickness, km
+}
+
+impl NeQuickG {
+    pub fn new(coefficients: [f64; 3], data: Arc<NeQuickData>) -> Self {
+        Self { coefficients, data }
+    }
+
+    pub fn data(&self) -> &NeQuickData {
+        &self.data
+    }
+
+    /// Effective ionisation level Az at a receiver, sfu
+    pub fn effective_ionisation(&self, receiver: &LLA) -> f64 {
+        let [a0, a1, a2] = self.coefficients;
+        if a0 == 0.0 && a1 == 0.0 && a2 == 0.0 {
+            return 63.7;
+        }
+        let mu = self.data.modip(receiver.latitude, receiver.longitude);
+        (a0 + a1 * mu + a2 * mu * mu).clamp(0.0, 400.0)
+    }
+
+    /// Slant total electron content between a receiver and a satellite (geodetic
+    /// coordinates, heights in m) for a month (1-12) and universal time in hours, TECU
+    pub fn stec(&self, month: u32, ut: f64, receiver: &LLA, satellite: &LLA) -> f64 {
+        let context = self.context(month, ut, receiver);
+        let start = spherical_point(receiver);
+        let end = spherical_point(satellite);
+        let length = (end - start).norm();
+        if length < 1e-6 {
+            return 0.0;
+        }
+        let direction = (end - start) * (1.0 / length);
+        let density = |s: f64| self.density_at(&context, &(start + direction * s));
+
+        // Tighter tolerance where most of the electrons are
+        let mut total = 0.0;
+        let mut from = 0.0;
+        for (height, tolerance) in [(1000.0, 1e-3), (2000.0, 1e-2), (f64::INFINITY, 1e-2)] {
+            let to = distance_to_radius(&start, &direction, EARTH_RADIUS + height).min(length);
+            if to > from {
+                total += integrate(&density, from, to, tolerance, 0);
+                from = to;
+            }
+        }
+        // Density in 1e11 m⁻³ over km
+        total * 1e14 / TECU
+    }
+
+    /// Delay on GPS L1 / Galileo E1 between a receiver and a satellite position at a
+    /// GPS time, m
+    pub fn delay(&self, receiver: &LLA, satellite: &ECEF, gps_time: f64) -> f64 {
+        let utc = gnss::gps_seconds_to_utc(gps_time);
+        let ut = utc.num_seconds_from_midnight() as f64 / 3600.0;
+        let stec = self.stec(utc.month(), ut, receiver, &satellite.to_lla());
+        let frequency = gnss::L1_FREQUENCY;
+        40.3 * stec * TECU / (frequency * frequency)
+    }
+
+    fn context(&self, month: u32, ut: f64, receiver: &LLA) -> Context {
+        let month = month.clamp(1, 12);
+        let az = self.effective_ionisation(receiver);
+        let sunspots = (167273.0 + (az - 63.7) * 1123.6).max(0.0).sqrt() - 408.99;
+        let ccir = &self.data.ccir[month as usize - 1];
+        let (f2_maps, fm3_maps) = ccir.split_at(F2_LEN);
+        let t = (15.0 * ut - 180.0).to_radians();
+        let series = |maps: &[f64], spatial: usize, temporal: usize| -> Vec<f64> {
+            (0..spatial)
+                .map(|i| {
+                    let low = &maps[i * temporal..(i + 1) * temporal];
+                    let high = &maps[(spatial + i) * temporal..(spatial + i + 1) * temporal];
+                    let weight = sunspots / 100.0;
+                    let coefficient = |j: usize| low[j] * (1.0 - weight) + high[j] * weight;
+                    let mut value = coefficient(0);
+                    for k in 1..=(temporal - 1) / 2 {
+                        let kt = k as f64 * t;
+                        value += coefficient(2 * k - 1) * kt.sin() + coefficient(2 * k) * kt.cos();
+                    }
+                    value
+                })
+                .collect()
+        };
+        let f2 = series(f2_maps, F2_SPATIAL, F2_TEMPORAL);
+        let fm3 = series(fm3_maps, FM3_SPATIAL, FM3_TEMPORAL);
+
+        let day = 30.5 * month as f64 - 15.0;
+        let t = day + (18.0 - ut) / 24.0;
+        let mean_anomaly = (0.9856 * t - 3.289).to_radians();
+        let longitude = mean_anomaly
+            + (1.916 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin() + 282.634)
+                .to_radians();
+        let sin_declination = 0.39782 * longitude.sin();
+        Context {
+            month,
+            ut,
+            sunspots,
+            az,
+            f2,
+            fm3,
+            sin_declination,
+            cos_declination: (1.0 - sin_declination * sin_declination).sqrt(),
+        }
+    }
+
+    /// Electron density at a point of the ray given in spherical ECEF km, 1e11 m⁻³
+    fn density_at(&self, context: &Context, point: &ECEF) -> f64 {
+        let radius = point.norm();
+        let latitude = (point.z / radius).asin().to_degrees();
+        let longitude = point.y.atan2(point.x).to_degrees();
+        let profile = self.profile(context, latitude, longitude);
+        profile.density(radius - EARTH_RADIUS)
+    }
+
+    fn profile(&self, context: &Context, latitude: f64, longitude: f64) -> Profile {
+        let mu = self.data.modip(latitude, longitude).to_radians();
+        let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+
+        // E layer from the effective solar zenith angle
+        let local_time = context.ut + longitude / 15.0;
+        let cos_chi = sin_lat * context.sin_declination
+            + cos_lat * context.cos_declination * (PI / 12.0 * (12.0 - local_time)).cos();
+        let chi = (1.0 - cos_chi * cos_chi)
+            .max(0.0)
+            .sqrt()
+            .atan2(cos_chi)
+            .to_degrees();
+        let chi0 = 86.23292796211615;
+        let e = (12.0 * (chi - chi0)).min(700.0).exp();
+        let chi_eff = (chi + (90.0 - 0.24 * (20.0 - 0.2 * chi).exp()) * e) / (1.0 + e);
+        let mut season: f64 = match context.month {
+            1 | 2 | 11 | 12 => -1.0,
+            3 | 4 | 9 | 10 => 0.0,
+            _ => 1.0,
+        };
+        if latitude < 0.0 {
+            season = -season;
+        }
+        let ee = (0.3 * latitude).exp();
+        let season = season * (ee - 1.0) / (ee + 1.0);
+        let fo_e = ((1.112 - 0.019 * season).powi(2)
+            * context.az.sqrt()
+            * chi_eff.to_radians().cos().max(0.0).powf(0.6)
+            + 0.49)
+            .sqrt();
+
+        // F2 layer from the CCIR maps
+        let sin_mu = mu.sin();
+        let fo_f2 = legendre(&context.f2, &F2_ORDERS, sin_mu, cos_lat, longitude);
+        let m3000 = legendre(&context.fm3, &FM3_ORDERS, sin_mu, cos_lat, longitude);
+
+        // F1 layer, fading out smoothly where the E layer is weak or F2 too close
+        let mut fo_f1 = join(1.4 * fo_e, 0.0, 1000.0, fo_e - 2.0);
+        fo_f1 = join(0.0, fo_f1, 1000.0, fo_e - fo_f1);
+        fo_f1 = join(fo_f1, 0.85 * fo_f1, 60.0, 0.85 * fo_f2 - fo_f1);
+        if fo_f1 < 1e-6 {
+            fo_f1 = 0.0;
+        }
+        let nm_e = 0.124 * fo_e * fo_e;
+        let nm_f1 = match fo_f1 <= 0.0 && fo_e > 2.0 {
+            true => 0.124 * (fo_e + 0.5).powi(2),
+            false => 0.124 * fo_f1 * fo_f1,
+        };
+        let nm_f2 = 0.124 * fo_f2 * fo_f2;
+
+        // Peak heights
+        let ratio = fo_f2 / fo_e;
+        let e = (20.0 * (ratio - 1.75)).min(700.0).exp();
+        let ratio = (ratio * e + 1.75) / (e + 1.0);
+        let delta_m = 0.253 / (ratio - 1.215) - 0.012;
+        let m2 = m3000 * m3000;
+        let hm_f2 = 1490.0 * m3000 * ((0.0196 * m2 + 1.0) / (1.2967 * m2 - 1.0)).sqrt()
+            / (m3000 + delta_m)
+            - 176.0;
+        let hm_f1 = (hm_f2 + HM_E) / 2.0;
+
+        // Thicknesses
+        let gradient = 0.01 * (-3.467 + 0.857 * (fo_f2 * fo_f2).ln() + 2.02 * m3000.ln()).exp();
+        let b2_bot = 0.385 * nm_f2 / gradient;
+        let b1_top = 0.3 * (hm_f2 - hm_f1);
+        let b1_bot = 0.5 * (hm_f1 - HM_E);
+        let be_top = b1_bot.max(7.0);
+
+        // Layer amplitudes
+        let a1 = 4.0 * nm_f2;
+        let (a2, a3) = match fo_f1 < 0.5 {
+            true => (0.0, 4.0 * (nm_e - epstein(a1, b2_bot, hm_f2, HM_E))),
+            false => {
+                let mut a2 = 0.0;
+                let mut a3 = 4.0 * nm_e;
+                for _ in 0..5 {
+                    a2 = 4.0
+                        * (nm_f1
+                            - epstein(a1, b2_bot, hm_f2, hm_f1)
+                            - epstein(a3, be_top, HM_E, hm_f1));
+                    a2 = join(a2, 0.8 * nm_f1, 1.0, a2 - 0.8 * nm_f1);
+                    a3 = 4.0
+                        * (nm_e
+                            - epstein(a2, b1_bot, hm_f1, HM_E)
+                            - epstein(a1, b2_bot, hm_f2, HM_E));
+                }
+                (a2, a3)
+            }
+        };
+        let a3 = join(a3, 0.05, 60.0, a3 - 0.005);
+
+        // Topside shape
+        let k = match context.month {
+            4..=9 => 6.705 - 0.014 * context.sunspots - 0.008 * hm_f2,
+            _ => -7.77 + 0.097 * (hm_f2 / b2_bot).powi(2) + 0.153 * nm_f2,
+        };
+        let k = join(k, 2.0, 1.0, k - 2.0);
+        let k = join(8.0, k, 1.0, k - 8.0);
+
+        Profile {
+            nm_f2,
+            hm_f1,
+            hm_f2,
+            b2_bot,
+            b1_top,
+            b1_bot,
+            be_top,
+            amplitudes: [a1, a2, a3],
+            h0: k * b2_bot,
+        }
+    }
+}
+
+impl Profile {
+    /// Electron density at a height in km, 1e11 m⁻³
+    fn density(&self, height: f64) -> f64 {
+        match height > self.hm_f2 {
+            true => self.topside(height),
+            false => self.bottomside(height),
+        }
+    }
+
+    fn topside(&self, height: f64) -> f64 {
+        let (g, r) = (0.125, 100.0);
+        let dh = height - self.hm_f2;
+        let z = dh / (self.h0 * (1.0 + r * g * dh / (r * self.h0 + g * dh)));
+        let e = z.exp();
+        match e > 1e11 {
+            true => 4.0 * self.nm_f2 / e,
+            false => 4.0 * self.nm_f2 * e / ((1.0 + e) * (1.0 + e)),
+        }
+    }
+
+    fn bottomside(&self, height: f64) -> f64 {
+        // Below 100 km a Chapman layer continues the profile from its value and slope there
+        let h = height.max(100.0);
+        let smoothing = (10.0 / (1.0 + (h - self.hm_f2).abs())).exp();
+        let be = if h > HM_E { self.be_top } else { 5.0 };
+        let bf1 = if h > self.hm_f1 {
+            self.b1_top
+        } else {
+            self.b1_bot
+        };
+        let layers = [
+            ((h - self.hm_f2) / self.b2_bot, 1.0 / self.b2_bot),
+            ((h - self.hm_f1) / bf1 * smoothing, smoothing / bf1),
+            ((h - HM_E) / be * smoothing, smoothing / be),
+        ];
+        let mut density = 0.0;
+        let mut slope = 0.0;
+        for ((s, ds), amplitude) in layers.into_iter().zip(self.amplitudes) {
+            if s.abs() > 25.0 {
+                continue;
+            }
+            let e = s.exp();
+            density += amplitude * e / ((1.0 + e) * (1.0 + e));
+            slope += amplitude * e * (1.0 - e) / (1.0 + e).powi(3) * ds;
+        }
+        if height >= 100.0 || density <= 0.0 {
+            return density;
+        }
+        let bc = 1.0 - 10.0 * slope / density;
+        let z = (height - 100.0) / 10.0;
+        density * (1.0 - bc * z - (-z).exp()).exp()
+    }
+}
+
+/// foF2 or M(3000)F2 from its coefficients at a location: powers of sin(MODIP) for each
+/// longitude harmonic, weighted by powers of cos(latitude)
+fn legendre(
+    coefficients: &[f64],
+    orders: &[usize],
+    sin_mu: f64,
+    cos_lat: f64,
+    longitude: f64,
+) -> f64 {
+    let powers: Vec<f64> = (0..orders[0]).map(|k| sin_mu.powi(k as i32)).collect();
+    let mut value: f64 = (0..orders[0]).map(|k| coefficients[k] * powers[k]).sum();
+    let mut idx = orders[0];
+    for (n, &order) in orders.iter().enumerate().skip(1) {
+        let (sin_n, cos_n) = (n as f64 * longitude.to_radians()).sin_cos();
+        let latitude_term = cos_lat.powi(n as i32);
+        for power in powers.iter().take(order) {
+            value +=
+                (coefficients[idx] * cos_n + coefficients[idx + 1] * sin_n) * power * latitude_term;
+            idx += 2;
+        }
+    }
+    value
+}
+
+/// Geodetic coordinates as a point on the model's spherical Earth, km
+fn spherical_point(lla: &LLA) -> ECEF {
+    let radius = EARTH_RADIUS + lla.altitude / 1000.0;
+    let (sin_lat, cos_lat) = lla.latitude.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = lla.longitude.to_radians().sin_cos();
+    ECEF::new(
+        radius * cos_lat * cos_lon,
+        radius * cos_lat * sin_lon,
+        radius * sin_lat,
+    )
+}
+
+/// Distance along a ray from `start` at which it leaves a sphere of the given radius
+fn distance_to_radius(start: &ECEF, direction: &ECEF, radius: f64) -> f64 {
+    let along = start.dot(direction);
+    let discriminant = along * along - start.dot(start) + radius * radius;
+    match discriminant > 0.0 {
+        true => -along + discriminant.sqrt(),
+        false => 0.0,
+    }
+}
+
+/// Adaptive 7-point Gauss / 15-point Kronrod quadrature
+fn integrate(f: &impl Fn(f64) -> f64, a: f64, b: f64, tolerance: f64, depth: u32) -> f64 {
+    const NODES: [f64; 8] = [
+        0.9914553711208126,
+        0.9491079123427585,
+        0.8648644233597691,
+        0.7415311855993945,
+        0.5860872354676911,
+        0.4058451513773972,
+        0.20778495500789848,
+        0.0,
+    ];
+    const KRONROD: [f64; 8] = [
+        0.022935322010529224,
+        0.06309209262997856,
+        0.10479001032225019,
+        0.14065325971552592,
+        0.1690047266392679,
+        0.19035057806478542,
+        0.20443294007529889,
+        0.20948214108472782,
+    ];
+    const GAUSS: [f64; 4] = [
+        0.1294849661688697,
+        0.27970539148927664,
+        0.3818300505051189,
+        0.4179591836734694,
+    ];
+    let half = (b - a) / 2.0;
+    let mid = (a + b) / 2.0;
+    let centre = f(mid);
+    let mut kronrod = KRONROD[7] * centre;
+    let mut gauss = GAUSS[3] * centre;
+    for i in 0..7 {
+        let pair = f(mid - half * NODES[i]) + f(mid + half * NODES[i]);
+        kronrod += KRONROD[i] * pair;
+        if i % 2 == 1 {
+            gauss += GAUSS[i / 2] * pair;
+        }
+    }
+    let (kronrod, gauss) = (kronrod * half, gauss * half);
+    // The absolute floor stops the refinement where the density vanishes
+    let converged = (kronrod - gauss).abs() <= tolerance * kronrod.abs() + INTEGRATION_FLOOR;
+    if converged || depth >= INTEGRATION_MAX_DEPTH {
+        return kronrod;
+    }
+    integrate(f, a, mid, tolerance, depth + 1) + integrate(f, mid, b, tolerance, depth + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl NeQuickData {
+        /// Grids with a constant MODIP and CCIR maps giving the same foF2 (MHz) and
+        /// M(3000)F2 everywhere, at any time and solar activity
+        pub(crate) fn flat(modip: f64, fo_f2: f64, m3000: f64) -> Self {
+            let mut ccir = vec![0.0; CCIR_LEN];
+            ccir[0] = fo_f2;
+            ccir[F2_SPATIAL * F2_TEMPORAL] = fo_f2;
+            ccir[F2_LEN] = m3000;
+            ccir[F2_LEN + FM3_SPATIAL * FM3_TEMPORAL] = m3000;
+            Self::new(vec![modip; MODIP_ROWS * MODIP_COLUMNS], vec![ccir; 12]).unwrap()
+        }
+    }
+
+    /// Grids with a constant MODIP and CCIR maps of zeros
+    fn uniform(modip: f64) -> NeQuickData {
+        NeQuickData::new(
+            vec![modip; MODIP_ROWS * MODIP_COLUMNS],
+            vec![vec![0.0; CCIR_LEN]; 12],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn grids_of_the_wrong_size_are_rejected() {
+        let short = NeQuickData::new(vec![0.0; 10], vec![vec![0.0; CCIR_LEN]; 12]);
+        assert_eq!(
+            short.unwrap_err(),
+            NeQuickError::Format {
+                file: "MODIP grid".into(),
+                expected: MODIP_ROWS * MODIP_COLUMNS,
+                found: 10,
+            }
+        );
+        let months = NeQuickData::new(vec![0.0; MODIP_ROWS * MODIP_COLUMNS], vec![]);
+        assert!(matches!(months, Err(NeQuickError::Format { found: 0, .. })));
+        let text = NeQuickData::from_texts("1.0D+01 x", &[]);
+        assert!(matches!(text, Err(NeQuickError::InvalidNumber { value, .. }) if value == "x"));
+    }
+
+    #[test]
+    fn effective_ionisation_follows_modip() {
+        let receiver = LLA::new(40.0, 10.0, 0.0);
+        let data = Arc::new(uniform(30.0));
+        assert!((data.modip(40.0, 10.0) - 30.0).abs() < 1e-12);
+        let model = NeQuickG::new([100.0, 1.0, 0.01], data.clone());
+        assert!((model.effective_ionisation(&receiver) - 139.0).abs() < 1e-9);
+        // No broadcast coefficients: the default level
+        let model = NeQuickG::new([0.0; 3], data);
+        assert_eq!(model.effective_ionisation(&receiver), 63.7);
+    }
+
+    #[test]
+    fn integration_reaches_an_epstein_layer_total() {
+        // The integral of an Epstein layer over all heights is amplitude times thickness
+        let layer = |h: f64| epstein(4.0, 40.0, 300.0, h);
+        let total = integrate(&layer, -1000.0, 2000.0, 1e-6, 0);
+        assert!((total - 160.0).abs() < 1e-6, "{}", total);
+        // Far below the floor nothing is refined, yet the rule is exact for a line
+        let faint = |h: f64| 1e-9 * h;
+        assert!((integrate(&faint, 0.0, 1000.0, 1e-3, 0) - 5e-4).abs() < 1e-15);
+    }
+
+    #[test]
+    fn vertical_stec_integrates_the_profile() {
+        let data = Arc::new(NeQuickData::flat(50.0, 8.0, 3.0));
+        let model = NeQuickG::new([100.0, 0.0, 0.0], data);
+        let receiver = LLA::new(40.0, 10.0, 0.0);
+        let satellite = LLA::new(40.0, 10.0, 20_200_000.0);
+        let stec = model.stec(6, 12.0, &receiver, &satellite);
+
+        // A vertical ray stays in one profile: sum it in 10 m steps up to 20200 km
+        let context = model.context(6, 12.0, &receiver);
+        let profile = model.profile(&context, 40.0, 10.0);
+        let step = 0.01;
+        let steps = (20_200.0 / step) as usize;
+        let sum: f64 = (0..=steps)
+            .map(|i| {
+                let weight = if i == 0 || i == steps { 0.5 } else { 1.0 };
+                weight * profile.density(i as f64 * step)
+            })
+            .sum();
+        let expected = sum * step * 1e14 / TECU;
+        assert!(stec > 1.0, "{} TECU", stec);
+        assert!(
+            (stec - expected).abs() < 1e-3 * expected,
+            "{} {}",
+            stec,
+            expected
+        );
+    }
+
+    /// The validation vectors of the Galileo ionospheric correction algorithm
+    /// specification, one per line of `data/nequick/validation.txt`, against the grids
+    /// next to them
+    #[test]
+    #[cfg_attr(
+        not(nequick_data),
+        ignore = "needs the NeQuick-G grids and validation.txt in data/nequick"
+    )]
+    fn grids_reproduce_the_validation_vectors() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/data/nequick/");
+        let read = |file: &str| {
+            std::fs::read_to_string(format!("{}{}", dir, file))
+                .unwrap_or_else(|err| panic!("{}{}: {}", dir, file, err))
+        };
+        let modip = read("modipNeQG_wrapped.asc");
+        let ccir: Vec<String> = (11..=22).map(|n| read(&format!("ccir{}.asc", n))).collect();
+        let ccir: Vec<&str> = ccir.iter().map(String::as_str).collect();
+        let data = Arc::new(NeQuickData::from_texts(&modip, &ccir).unwrap());
+        #[cfg(nequick_data)]
+        assert_eq!(*NeQuickData::embedded(), *data);
+        let text = read("validation.txt");
+        let mut count = 0;
+        for line in text
+            .lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        {
+            let v = parse_values(line, "validation.txt").unwrap();
+            assert_eq!(v.len(), 12, "{}", line);
+            let model = NeQuickG::new([v[0], v[1], v[2]], data.clone());
+            let receiver = LLA::new(v[6], v[5], v[7]);
+            let satellite = LLA::new(v[9], v[8], v[10]);
+            let stec = model.stec(v[3] as u32, v[4], &receiver, &satellite);
+            assert!(
+                (stec - v[11]).abs() <= 1e-3 * v[11].max(1.0),
+                "{}: {} TECU",
+                line,
+                stec
+            );
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+}
@@ -0,0 +1,714 @@
+use crate::gnss::{Constellation as System, SatId, AER, LLA};
+#[cfg(feature = "ndarray")]
+use crate::kalman::FilterSolution;
+use crate::positioning::Dop;
+#[cfg(feature = "ndarray")]
+use crate::positioning::SppSolution;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+const KNOTS_PER_MPS: f64 = 3600.0 / 1852.0;
+const GSA_SLOTS: usize = 12; // Satellite id fields of a GSA sentence
+const GSV_PER_SENTENCE: usize = 4;
+
+/// A satellite in view: id, look angles and C/N0 (dB-Hz) if tracked
+pub type InView = (SatId, AER, Option<f64>);
+
+// NMEA 0183 sentences are built and returned without the CR LF terminator.
+
+/// GGA fix quality indicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FixQuality {
+    #[default]
+    Invalid,
+    Gps,
+    Differential,
+    Pps,
+    RtkFixed,
+    RtkFloat,
+    Estimated, // Dead reckoning
+    Manual,
+    Simulation,
+}
+
+impl FixQuality {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        [
+            Self::Invalid,
+            Self::Gps,
+            Self::Differential,
+            Self::Pps,
+            Self::RtkFixed,
+            Self::RtkFloat,
+            Self::Estimated,
+            Self::Manual,
+            Self::Simulation,
+        ]
+        .get(code as usize)
+        .copied()
+    }
+}
+
+/// A position fix to report in GGA and RMC sentences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fix {
+    pub time: DateTime<Utc>,
+    pub position: LLA, // Height is ellipsoidal; there is no geoid model to reduce it to MSL
+    pub quality: FixQuality,
+    pub satellites: usize,
+    pub hdop: Option<f64>,
+    pub speed: Option<f64>,  // Over ground, m/s
+    pub course: Option<f64>, // Over ground, degrees from true north
+}
+
+impl Fix {
+    pub fn new(time: DateTime<Utc>, position: LLA) -> Self {
+        Self {
+            time,
+            position,
+            quality: FixQuality::Gps,
+            satellites: 0,
+            hdop: None,
+            speed: None,
+            course: None,
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn from_spp(epoch: DateTime<Utc>, solution: &SppSolution) -> Self {
+        Self {
+            satellites: solution.residuals.len(),
+            hdop: Some(solution.dop.hdop),
+            ..Self::new(epoch, solution.position.to_lla())
+        }
+    }
+
+    /// Fix with speed and course from the filtered velocity
+    #[cfg(feature = "ndarray")]
+    pub fn from_filter(solution: &FilterSolution) -> Self {
+        let position = solution.position.to_lla();
+        let velocity = position.rotate_to_enu(&solution.velocity);
+        Self {
+            satellites: solution.used.len(),
+            speed: Some(velocity.east.hypot(velocity.north)),
+            course: Some(
+                velocity
+                    .east
+                    .atan2(velocity.north)
+                    .to_degrees()
+                    .rem_euclid(360.0),
+            ),
+            ..Self::new(solution.epoch, position)
+        }
+    }
+
+    /// GGA: time, position, quality, satellites in use, HDOP and height. The geoid
+    /// separation field is left empty, so the height is ellipsoidal.
+    pub fn gga(&self, talker: &str) -> String {
+        let time = round_to_centiseconds(self.time);
+        let (latitude, north) = angle(self.position.latitude, 2);
+        let (longitude, east) = angle(self.position.longitude, 3);
+        sentence(&format!(
+            "{}GGA,{},{},{},{},{},{},{:02},{},{:.2},M,,M,,",
+            talker,
+            hhmmss(time),
+            latitude,
+            if north { 'N' } else { 'S' },
+            longitude,
+            if east { 'E' } else { 'W' },
+            self.quality.code(),
+            self.satellites.min(99),
+            optional(self.hdop, 1),
+            self.position.altitude
+        ))
+    }
+
+    /// RMC: time, status, position, speed in knots, course, date and mode indicator
+    pub fn rmc(&self, talker: &str) -> String {
+        let time = round_to_centiseconds(self.time);
+        let (latitude, north) = angle(self.position.latitude, 2);
+        let (longitude, east) = angle(self.position.longitude, 3);
+        let (status, mode) = match self.quality {
+            FixQuality::Invalid => ('V', 'N'),
+            FixQuality::Differential | FixQuality::RtkFixed | FixQuality::RtkFloat => ('A', 'D'),
+            FixQuality::Estimated => ('A', 'E'),
+            FixQuality::Manual => ('A', 'M'),
+            FixQuality::Simulation => ('A', 'S'),
+            FixQuality::Gps | FixQuality::Pps => ('A', 'A'),
+        };
+        sentence(&format!(
+            "{}RMC,{},{},{},{},{},{},{},{},{},,,{}",
+            talker,
+            hhmmss(time),
+            status,
+            latitude,
+            if north { 'N' } else { 'S' },
+            longitude,
+            if east { 'E' } else { 'W' },
+            optional(self.speed.map(|speed| speed * KNOTS_PER_MPS), 2),
+            optional(self.course, 2),
+            time.format("%d%m%y"),
+            mode
+        ))
+    }
+}
+
+/// Talker id of a system's GSV/GSA sentences; SBAS is reported with GPS
+pub fn talker(system: System) -> &'static str {
+    match system {
+        System::Gps | System::Sbas => "GP",
+        System::Glonass => "GL",
+        System::Galileo => "GA",
+        System::BeiDou => "GB",
+        System::Qzss => "GQ",
+        System::Irnss => "GI",
+    }
+}
+
+/// Satellite id as NMEA numbers it: GPS PRN, SBAS PRN - 87, GLONASS 64 + slot, and the
+/// system's own PRN for the others
+pub fn satellite_id(sat_id: SatId) -> u16 {
+    let prn = sat_id.prn as u16;
+    match sat_id.constellation {
+        System::Sbas => prn + 100 - 87, // RINEX Snn is PRN 100 + nn
+        System::Glonass => prn + 64,
+        _ => prn,
+    }
+}
+
+/// GSA sentences for the satellites used in a fix, 12 per sentence, with the fix type
+/// (2D or 3D) from how many there are
+pub fn gsa(talker: &str, used: &[SatId], dop: &Dop) -> Vec<String> {
+    let fix_type = match used.len() {
+        0..=2 => 1,
+        3 => 2,
+        _ => 3,
+    };
+    let mut sentences = Vec::new();
+    for chunk in used
+        .chunks(GSA_SLOTS)
+        .chain(used.is_empty().then_some(&[][..]))
+    {
+        let mut ids: Vec<String> = chunk
+            .iter()
+            .map(|&sat_id| format!("{:02}", satellite_id(sat_id)))
+            .collect();
+        ids.resize(GSA_SLOTS, String::new());
+        sentences.push(sentence(&format!(
+            "{}GSA,A,{},{},{:.1},{:.1},{:.1}",
+            talker,
+            fix_type,
+            ids.join(","),
+            dop.pdop,
+            dop.hdop,
+            dop.vdop
+        )));
+    }
+    sentences
+}
+
+/// GSV sentences for satellites in view with their look angles and optional C/N0 (dB-Hz),
+/// four per sentence
+pub fn gsv(talker: &str, satellites: &[InView]) -> Vec<String> {
+    let total = satellites.len().div_ceil(GSV_PER_SENTENCE).max(1);
+    (0..total)
+        .map(|number| {
+            let mut body = format!(
+                "{}GSV,{},{},{:02}",
+                talker,
+                total,
+                number + 1,
+                satellites.len()
+            );
+            let start = number * GSV_PER_SENTENCE;
+            let end = (start + GSV_PER_SENTENCE).min(satellites.len());
+            for (sat_id, aer, snr) in &satellites[start..end] {
+                body.push_str(&format!(
+                    ",{:02},{:02},{:03},{}",
+                    satellite_id(*sat_id),
+                    aer.elevation.round().clamp(-90.0, 90.0) as i32,
+                    aer.azimuth.round().rem_euclid(360.0) as i32,
+                    snr.map(|snr| format!("{:02}", snr.round().clamp(0.0, 99.0) as i32))
+                        .unwrap_or_default()
+                ));
+            }
+            sentence(&body)
+        })
+        .collect()
+}
+
+/// GSV groups of a mixed satellite list, one per system under its own talker id
+pub fn gsv_by_system(satellites: &[InView]) -> Vec<String> {
+    let mut by_talker: BTreeMap<&str, Vec<InView>> = BTreeMap::new();
+    for satellite in satellites {
+        by_talker
+            .entry(talker(satellite.0.constellation))
+            .or_default()
+            .push(*satellite);
+    }
+    by_talker
+        .iter()
+        .flat_map(|(talker, satellites)| gsv(talker, satellites))
+        .collect()
+}
+
+/// XOR of the characters between '$' and '*'
+pub fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |sum, byte| sum ^ byte)
+}
+
+/// "$<body>*<checksum>"
+pub fn sentence(body: &str) -> String {
+    format!("${}*{:02X}", body, checksum(body))
+}
+
+fn round_to_centiseconds(time: DateTime<Utc>) -> DateTime<Utc> {
+    let time = time + chrono::Duration::milliseconds(5);
+    time - chrono::Duration::nanoseconds((time.timestamp_subsec_nanos() % 10_000_000) as i64)
+}
+
+fn hhmmss(time: DateTime<Utc>) -> String {
+    format!(
+        "{}.{:02}",
+        time.format("%H%M%S"),
+        time.timestamp_subsec_millis() / 10
+    )
+}
+
+/// Degrees as d(dd)mm.mmmm with the given number of degree digits, and whether the value
+/// is non-negative (north or east)
+fn angle(value: f64, degree_digits: usize) -> (String, bool) {
+    let magnitude = value.abs();
+    let mut degrees = magnitude.trunc();
+    let mut minutes = ((magnitude - degrees) * 60.0 * 1e4).round() / 1e4;
+    if minutes >= 60.0 {
+        degrees += 1.0;
+        minutes -= 60.0;
+    }
+    (
+        format!(
+            "{:0width$}{:07.4}",
+            degrees as u32,
+            minutes,
+            width = degree_digits
+        ),
+        value >= 0.0,
+    )
+}
+
+fn optional(value: Option<f64>, decimals: usize) -> String {
+    value
+        .filter(|value| value.is_finite())
+        .map(|value| format!("{:.*}", decimals, value))
+        .unwrap_or_default()
+}
+
+/// A sentence that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseNmeaError {
+    Framing, // No '$', '*' or checksum
+    Checksum { expected: u8, found: u8 },
+    Field { index: usize, value: String }, // 1-based, after the sentence id
+}
+
+impl fmt::Display for ParseNmeaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Framing => write!(f, "not an NMEA sentence"),
+            Self::Checksum { expected, found } => {
+                write!(f, "checksum {:02X}, computed {:02X}", found, expected)
+            }
+            Self::Field { index, value } => write!(f, "invalid field {}: {:?}", index, value),
+        }
+    }
+}
+
+impl std::error::Error for ParseNmeaError {}
+
+/// A checksum-verified sentence split into its fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawSentence {
+    pub talker: String, // e.g. "GP"
+    pub kind: String,   // e.g. "GGA"
+    pub fields: Vec<String>,
+}
+
+/// Position fix of a GGA sentence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gga {
+    pub time: Option<NaiveTime>,
+    pub latitude: Option<f64>,  // Degrees
+    pub longitude: Option<f64>, // Degrees
+    pub quality: FixQuality,
+    pub satellites: Option<u32>,
+    pub hdop: Option<f64>,
+    pub altitude: Option<f64>,         // m
+    pub geoid_separation: Option<f64>, // m
+}
+
+/// Recommended minimum data of an RMC sentence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rmc {
+    pub time: Option<NaiveTime>,
+    pub valid: bool,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub speed: Option<f64>,  // Knots
+    pub course: Option<f64>, // Degrees
+    pub date: Option<NaiveDate>,
+    pub mode: Option<char>,
+}
+
+/// Satellites used and DOP of a GSA sentence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gsa {
+    pub mode: Option<char>,
+    pub fix_type: Option<u8>, // 1 none, 2 2D, 3 3D
+    pub satellites: Vec<u16>, // NMEA ids
+    pub pdop: Option<f64>,
+    pub hdop: Option<f64>,
+    pub vdop: Option<f64>,
+}
+
+/// One satellite of a GSV sentence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GsvSatellite {
+    pub id: u16,
+    pub elevation: Option<f64>,
+    pub azimuth: Option<f64>,
+    pub snr: Option<f64>, // dB-Hz
+}
+
+/// One sentence of a GSV group
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gsv {
+    pub total: u32,
+    pub number: u32,
+    pub in_view: u32,
+    pub satellites: Vec<GsvSatellite>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sentence {
+    Gga(Gga),
+    Rmc(Rmc),
+    Gsa(Gsa),
+    Gsv(Gsv),
+    Other(RawSentence),
+}
+
+/// Verify the checksum of a sentence and split it into fields. A trailing CR LF is
+/// allowed.
+pub fn parse_raw(line: &str) -> Result<RawSentence, ParseNmeaError> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let body = line.strip_prefix('$').ok_or(ParseNmeaError::Framing)?;
+    let (body, sum) = body.rsplit_once('*').ok_or(ParseNmeaError::Framing)?;
+    let found = u8::from_str_radix(sum, 16).map_err(|_| ParseNmeaError::Framing)?;
+    let expected = checksum(body);
+    if found != expected {
+        return Err(ParseNmeaError::Checksum { expected, found });
+    }
+    let mut fields = body.split(',');
+    let id = fields.next().unwrap_or_default();
+    if id.len() < 5 || !id.is_ascii() {
+        return Err(ParseNmeaError::Framing);
+    }
+    let (talker, kind) = id.split_at(id.len() - 3);
+    Ok(RawSentence {
+        talker: talker.to_string(),
+        kind: kind.to_string(),
+        fields: fields.map(str::to_string).collect(),
+    })
+}
+
+/// Parse a GGA, RMC, GSA or GSV sentence; others are returned as raw fields
+pub fn parse(line: &str) -> Result<Sentence, ParseNmeaError> {
+    let raw = parse_raw(line)?;
+    let f = Fields(&raw.fields);
+    Ok(match raw.kind.as_str() {
+        "GGA" => Sentence::Gga(Gga {
+            time: f.time(0)?,
+            latitude: f.angle(1, 2)?,
+            longitude: f.angle(3, 4)?,
+            quality: f
+                .value::<u8>(5)?
+                .and_then(FixQuality::from_code)
+                .unwrap_or_default(),
+            satellites: f.value(6)?,
+            hdop: f.value(7)?,
+            altitude: f.value(8)?,
+            geoid_separation: f.value(10)?,
+        }),
+        "RMC" => Sentence::Rmc(Rmc {
+            time: f.time(0)?,
+            valid: f.get(1) == "A",
+            latitude: f.angle(2, 3)?,
+            longitude: f.angle(4, 5)?,
+            speed: f.value(6)?,
+            course: f.value(7)?,
+            date: match f.get(8) {
+                "" => None,
+                date => Some(NaiveDate::parse_from_str(date, "%d%m%y").map_err(|_| f.error(8))?),
+            },
+            mode: f.get(11).chars().next(),
+        }),
+        "GSA" => Sentence::Gsa(Gsa {
+            mode: f.get(0).chars().next(),
+            fix_type: f.value(1)?,
+            satellites: (2..2 + GSA_SLOTS)
+                .map(|i| f.value(i))
+                .collect::<Result<Vec<Option<u16>>, _>>()?
+                .into_iter()
+                .flatten()
+                .collect(),
+            pdop: f.value(14)?,
+            hdop: f.value(15)?,
+            vdop: f.value(16)?,
+        }),
+        "GSV" => {
+            let mut satellites = Vec::new();
+            let mut i = 3;
+            while i < raw.fields.len() {
+                // NMEA 4.10 appends a signal id after the last block
+                if let Some(id) = f.value(i)?.filter(|_| i + 3 < raw.fields.len()) {
+                    satellites.push(GsvSatellite {
+                        id,
+                        elevation: f.value(i + 1)?,
+                        azimuth: f.value(i + 2)?,
+                        snr: f.value(i + 3)?,
+                    });
+                }
+                i += 4;
+            }
+            Sentence::Gsv(Gsv {
+                total: f.value(0)?.ok_or_else(|| f.error(0))?,
+                number: f.value(1)?.ok_or_else(|| f.error(1))?,
+                in_view: f.value(2)?.unwrap_or(0),
+                satellites,
+            })
+        }
+        _ => Sentence::Other(raw),
+    })
+}
+
+/// Typed access to the fields of a sentence, empty fields being None
+struct Fields<'a>(&'a [String]);
+
+impl Fields<'_> {
+    fn get(&self, index: usize) -> &str {
+        self.0.get(index).map(String::as_str).unwrap_or("")
+    }
+
+    fn error(&self, index: usize) -> ParseNmeaError {
+        ParseNmeaError::Field {
+            index: index + 1,
+            value: self.get(index).to_string(),
+        }
+    }
+
+    fn value<T: FromStr>(&self, index: usize) -> Result<Option<T>, ParseNmeaError> {
+        match self.get(index) {
+            "" => Ok(None),
+            value => value.parse().map(Some).map_err(|_| self.error(index)),
+        }
+    }
+
+    fn time(&self, index: usize) -> Result<Option<NaiveTime>, ParseNmeaError> {
+        match self.get(index) {
+            "" => Ok(None),
+            value => NaiveTime::parse_from_str(value, "%H%M%S%.f")
+                .map(Some)
+                .map_err(|_| self.error(index)),
+        }
+    }
+
+    /// d(dd)mm.mmmm and its hemisphere as signed degrees
+    fn angle(&self, index: usize, hemisphere: usize) -> Result<Option<f64>, ParseNmeaError> {
+        let Some(value) = self.value::<f64>(index)? else {
+            return Ok(None);
+        };
+        let degrees = (value / 100.0).trunc();
+        let magnitude = degrees + (value - degrees * 100.0) / 60.0;
+        match self.get(hemisphere) {
+            "N" | "E" => Ok(Some(magnitude)),
+            "S" | "W" => Ok(Some(-magnitude)),
+            _ => Err(self.error(hemisphere)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+
+    fn time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 12, 12, 35, 19).unwrap() + chrono::Duration::milliseconds(996)
+    }
+
+    fn fix(latitude: f64, longitude: f64) -> Fix {
+        Fix {
+            quality: FixQuality::Gps,
+            satellites: 8,
+            hdop: Some(0.94),
+            speed: Some(5.4),
+            course: Some(271.3),
+            ..Fix::new(time(), LLA::new(latitude, longitude, 45.25))
+        }
+    }
+
+    #[test]
+    fn gga_and_rmc_are_byte_exact() {
+        let fix = fix(-33.856785, -151.215297);
+        assert_eq!(
+            fix.gga("GP"),
+            "$GPGGA,123520.00,3351.4071,S,15112.9178,W,1,08,0.9,45.25,M,,M,,*7A"
+        );
+        assert_eq!(
+            fix.rmc("GN"),
+            "$GNRMC,123520.00,A,3351.4071,S,15112.9178,W,10.50,271.30,120623,,,A*78"
+        );
+    }
+
+    #[test]
+    fn gga_and_rmc_round_trip_in_every_hemisphere() {
+        let places = [
+            (48.858222, 2.294500),     // North, east
+            (-33.856785, 151.215297),  // South, east, longitude over 100°
+            (40.689247, -74.044502),   // North, west
+            (-54.801912, -168.303070), // South, west, longitude over 100°
+            (0.000001, -179.999999),
+            (-89.999999, 100.000001),
+        ];
+        // Four decimals of minutes, half of which is the largest rounding error
+        let tolerance = 0.5e-4 / 60.0 + 1e-12;
+        for (latitude, longitude) in places {
+            let fix = fix(latitude, longitude);
+            let Sentence::Gga(gga) = parse(&fix.gga("GP")).unwrap() else {
+                panic!("not GGA");
+            };
+            assert!((gga.latitude.unwrap() - latitude).abs() < tolerance);
+            assert!((gga.longitude.unwrap() - longitude).abs() < tolerance);
+            assert_eq!(gga.time.unwrap().second(), 20);
+            assert_eq!(gga.quality, FixQuality::Gps);
+            assert_eq!(gga.satellites, Some(8));
+            assert_eq!(gga.hdop, Some(0.9));
+            assert_eq!(gga.altitude, Some(45.25));
+            assert_eq!(gga.geoid_separation, None);
+
+            let Sentence::Rmc(rmc) = parse(&fix.rmc("GN")).unwrap() else {
+                panic!("not RMC");
+            };
+            assert!(rmc.valid);
+            assert!((rmc.latitude.unwrap() - latitude).abs() < tolerance);
+            assert!((rmc.longitude.unwrap() - longitude).abs() < tolerance);
+            assert_eq!(rmc.speed, Some(10.5));
+            assert_eq!(rmc.course, Some(271.3));
+            assert_eq!(rmc.date, NaiveDate::from_ymd_opt(2023, 6, 12));
+            assert_eq!(rmc.mode, Some('A'));
+        }
+    }
+
+    #[test]
+    fn minutes_rounding_up_carry_into_the_degrees() {
+        let gga = fix(9.9999999, -119.9999999).gga("GP");
+        assert!(gga.contains(",1000.0000,N,12000.0000,W,"), "{}", gga);
+    }
+
+    #[test]
+    fn generated_sentences_carry_valid_checksums() {
+        let gga = fix(-33.856785, -151.215297).gga("GP");
+        let raw = parse_raw(&format!("{}\r\n", gga)).unwrap();
+        assert_eq!((raw.talker.as_str(), raw.kind.as_str()), ("GP", "GGA"));
+
+        // One changed digit, then a changed checksum
+        let corrupted = gga.replacen("3351", "3352", 1);
+        assert!(matches!(
+            parse(&corrupted),
+            Err(ParseNmeaError::Checksum { found: 0x7A, .. })
+        ));
+        let body = gga.trim_end_matches(|c: char| c.is_ascii_hexdigit());
+        assert!(matches!(
+            parse(&format!("{}00", body)),
+            Err(ParseNmeaError::Checksum { found: 0, .. })
+        ));
+        assert_eq!(
+            parse(body.trim_end_matches('*')),
+            Err(ParseNmeaError::Framing)
+        );
+    }
+
+    #[test]
+    fn gsv_and_gsa_round_trip() {
+        let aer = |azimuth, elevation| AER {
+            azimuth,
+            elevation,
+            range: 2.2e7,
+        };
+        let in_view: Vec<InView> = vec![
+            (SatId::new(System::Gps, 3), aer(45.2, 67.6), Some(44.4)),
+            (SatId::new(System::Gps, 17), aer(359.7, 5.2), None),
+            (SatId::new(System::Sbas, 31), aer(201.0, 30.0), Some(38.0)),
+            (SatId::new(System::Gps, 22), aer(120.0, 12.0), Some(31.0)),
+            (SatId::new(System::Gps, 28), aer(300.4, 40.5), Some(47.0)),
+            (SatId::new(System::Glonass, 5), aer(10.0, 20.0), Some(40.0)),
+        ];
+        let sentences = gsv_by_system(&in_view);
+        assert_eq!(sentences.len(), 3);
+        let parsed: Vec<(String, Gsv)> = sentences
+            .iter()
+            .map(|sentence| {
+                let talker = parse_raw(sentence).unwrap().talker;
+                match parse(sentence).unwrap() {
+                    Sentence::Gsv(gsv) => (talker, gsv),
+                    other => panic!("not GSV: {:?}", other),
+                }
+            })
+            .collect();
+
+        // GPS and SBAS share a group of two sentences
+        let (talker, first) = &parsed[0];
+        assert_eq!(talker, "GL");
+        assert_eq!((first.total, first.number, first.in_view), (1, 1, 1));
+        assert_eq!(first.satellites[0].id, 69);
+        let gps: Vec<&GsvSatellite> = parsed[1..]
+            .iter()
+            .flat_map(|(talker, gsv)| {
+                assert_eq!(talker, "GP");
+                assert_eq!((gsv.total, gsv.in_view), (2, 5));
+                &gsv.satellites
+            })
+            .collect();
+        let ids: Vec<u16> = gps.iter().map(|satellite| satellite.id).collect();
+        assert_eq!(ids, [3, 17, 44, 22, 28]);
+        assert_eq!(
+            (gps[0].elevation, gps[0].azimuth, gps[0].snr),
+            (Some(68.0), Some(45.0), Some(44.0))
+        );
+        assert_eq!((gps[1].azimuth, gps[1].snr), (Some(0.0), None));
+
+        let dop = Dop {
+            pdop: 1.84,
+            hdop: 0.96,
+            vdop: 1.57,
+            ..Dop::default()
+        };
+        let used: Vec<SatId> = in_view.iter().map(|(sat_id, ..)| *sat_id).collect();
+        let [gsa] = gsa("GN", &used, &dop).try_into().unwrap();
+        let Sentence::Gsa(gsa) = parse(&gsa).unwrap() else {
+            panic!("not GSA");
+        };
+        assert_eq!((gsa.mode, gsa.fix_type), (Some('A'), Some(3)));
+        assert_eq!(gsa.satellites, [3, 17, 44, 22, 28, 69]);
+        assert_eq!(
+            (gsa.pdop, gsa.hdop, gsa.vdop),
+            (Some(1.8), Some(1.0), Some(1.6))
+        );
+    }
+}
@@ -0,0 +1,863 @@
+//! NTRIP client: mountpoint request over the NTRIP 1.0 or 2.0 handshake with basic
+//! authentication, GGA upload for VRS mountpoints, and reconnection with exponential
+//! backoff. The stream is read as raw bytes or split into CRC-checked RTCM 3 frames.
+
+use crate::nmea::Fix;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PORT: u16 = 2101;
+const MAX_HEADER_LINES: usize = 64;
+const MAX_SOURCE_TABLE: u64 = 1 << 20; // Bytes of a source table kept for the error
+const RTCM_PREAMBLE: u8 = 0xD3;
+
+/// Protocol revision of the mountpoint request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NtripVersion {
+    V1, // "ICY 200 OK", raw stream
+    #[default]
+    V2, // HTTP/1.1, chunked stream
+}
+
+/// Delay between reconnection attempts, doubling from `initial` up to `max`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub max_attempts: Option<u32>, // Consecutive failures before giving up, None retries forever
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// Wait before the attempt following `failures` consecutive failures
+    pub fn delay(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::ZERO;
+        }
+        let factor = 2u32.saturating_pow(failures - 1);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// Caster, mountpoint and connection behaviour
+#[derive(Debug, Clone, PartialEq)]
+pub struct NtripConfig {
+    pub host: String,
+    pub port: u16,
+    pub mountpoint: String,
+    pub credentials: Option<(String, String)>, // User name and password for basic auth
+    pub version: NtripVersion,
+    pub user_agent: String,             // Sent as "NTRIP <user_agent>"
+    pub gga_interval: Option<Duration>, // Resend the GGA position this often, if one is set
+    pub timeout: Duration,              // Connect, write, and read without data before a dropout
+    pub backoff: Backoff,
+}
+
+impl NtripConfig {
+    pub fn new(host: impl Into<String>, mountpoint: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port: DEFAULT_PORT,
+            mountpoint: mountpoint.into(),
+            credentials: None,
+            version: NtripVersion::default(),
+            user_agent: format!("pnt_rust/{}", env!("CARGO_PKG_VERSION")),
+            gga_interval: Some(Duration::from_secs(10)),
+            timeout: Duration::from_secs(30),
+            backoff: Backoff::default(),
+        }
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn credentials(mut self, user: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some((user.into(), password.into()));
+        self
+    }
+
+    pub fn version(mut self, version: NtripVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn gga_interval(mut self, interval: Option<Duration>) -> Self {
+        self.gga_interval = interval;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+/// Why the caster could not be read
+#[derive(Debug)]
+pub enum NtripError {
+    Io(io::Error),
+    Unauthorized,
+    // The caster answered with its source table, or 404
+    MountpointNotFound {
+        source_table: String,
+    },
+    Rejected {
+        code: u16,
+        status: String,
+    },
+    // Response that is neither NTRIP nor HTTP
+    Handshake(String),
+    GaveUp {
+        attempts: u32,
+        last: Box<NtripError>,
+    },
+}
+
+impl NtripError {
+    /// Whether reconnecting may help: network errors and server-side failures
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Io(_) | Self::Handshake(_) => true,
+            Self::Rejected { code, .. } => *code >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for NtripError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "NTRIP I/O error: {}", error),
+            Self::Unauthorized => write!(f, "caster rejected the credentials"),
+            Self::MountpointNotFound { .. } => {
+                write!(f, "mountpoint not found, caster sent its source table")
+            }
+            Self::Rejected { status, .. } => write!(f, "caster refused the request: {}", status),
+            Self::Handshake(line) => write!(f, "unexpected caster response {:?}", line),
+            Self::GaveUp { attempts, last } => {
+                write!(f, "gave up after {} attempts: {}", attempts, last)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NtripError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::GaveUp { last, .. } => Some(last.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NtripError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Blocking client for one mountpoint. Reads reconnect transparently after dropouts;
+/// only authentication and mountpoint errors, or exhausting `Backoff::max_attempts`,
+/// are returned.
+pub struct NtripClient {
+    config: NtripConfig,
+    connection: Option<Connection>,
+    gga: Option<String>,
+    failures: u32,   // Consecutive failed connects and dropouts, reset by received data
+    reconnects: u32, // Successful handshakes after the first
+    connected_once: bool,
+    framer: RtcmFramer,
+}
+
+impl NtripClient {
+    pub fn new(config: NtripConfig) -> Self {
+        Self {
+            config,
+            connection: None,
+            gga: None,
+            failures: 0,
+            reconnects: 0,
+            connected_once: false,
+            framer: RtcmFramer::new(),
+        }
+    }
+
+    pub fn config(&self) -> &NtripConfig {
+        &self.config
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Handshakes completed after a dropout
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects
+    }
+
+    /// Position to report to the caster, sent as GGA after every handshake and then
+    /// every `gga_interval`
+    pub fn set_position(&mut self, fix: &Fix) {
+        self.set_gga(fix.gga("GP"));
+    }
+
+    /// A ready-made GGA sentence, without CR LF
+    pub fn set_gga(&mut self, sentence: impl Into<String>) {
+        self.gga = Some(sentence.into());
+        if let Some(connection) = &mut self.connection {
+            connection.last_gga = None;
+        }
+    }
+
+    /// Connect now rather than on the first read, so configuration errors show early
+    pub fn connect(&mut self) -> Result<(), NtripError> {
+        if self.connection.is_none() {
+            self.reconnect()?;
+        }
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        self.connection = None;
+    }
+
+    /// Read stream bytes into `buf`, reconnecting as needed; never returns 0 for a
+    /// non-empty buffer
+    pub fn read_bytes(&mut self, buf: &mut [u8]) -> Result<usize, NtripError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.connection.is_none() {
+                self.reconnect()?;
+            }
+            let connection = self.connection.as_mut().expect("connected above");
+            let result = connection
+                .send_gga(self.gga.as_deref(), self.config.gga_interval)
+                .and_then(|_| connection.read(buf));
+            match result {
+                Ok(0) => self.dropout(),
+                Ok(n) => {
+                    self.failures = 0;
+                    return Ok(n);
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.dropout(),
+            }
+        }
+    }
+
+    /// Next complete RTCM 3 frame; bytes between frames and frames failing the CRC are
+    /// skipped
+    pub fn next_frame(&mut self) -> Result<RtcmFrame, NtripError> {
+        let mut buf = [0; 4096];
+        loop {
+            if let Some(frame) = self.framer.next_frame() {
+                return Ok(frame);
+            }
+            let n = self.read_bytes(&mut buf)?;
+            self.framer.push(&buf[..n]);
+        }
+    }
+
+    fn dropout(&mut self) {
+        self.connection = None;
+        self.framer.clear(); // A frame cannot continue on a new connection
+        self.failures += 1;
+    }
+
+    fn reconnect(&mut self) -> Result<(), NtripError> {
+        loop {
+            thread::sleep(self.config.backoff.delay(self.failures));
+            match Connection::open(&self.config, self.gga.as_deref()) {
+                Ok(connection) => {
+                    self.connection = Some(connection);
+                    if self.connected_once {
+                        self.reconnects += 1;
+                    }
+                    self.connected_once = true;
+                    return Ok(());
+                }
+                Err(error) if error.is_transient() => {
+                    self.failures += 1;
+                    if let Some(max) = self.config.backoff.max_attempts {
+                        if self.failures >= max {
+                            return Err(NtripError::GaveUp {
+                                attempts: self.failures,
+                                last: Box::new(error),
+                            });
+                        }
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl Read for NtripClient {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_bytes(buf).map_err(|error| match error {
+            NtripError::Io(error) => error,
+            error => io::Error::other(error),
+        })
+    }
+}
+
+/// An open stream past the response header
+struct Connection {
+    reader: BufReader<TcpStream>,
+    chunked: bool,
+    chunk_remaining: usize,
+    last_gga: Option<Instant>,
+}
+
+impl Connection {
+    fn open(config: &NtripConfig, gga: Option<&str>) -> Result<Self, NtripError> {
+        let stream = connect(&config.host, config.port, config.timeout)?;
+        stream.set_read_timeout(Some(config.timeout))?;
+        stream.set_write_timeout(Some(config.timeout))?;
+        (&stream).write_all(request(config, gga).as_bytes())?;
+
+        let mut connection = Self {
+            reader: BufReader::new(stream),
+            chunked: false,
+            chunk_remaining: 0,
+            last_gga: None,
+        };
+        let status = connection.line()?;
+        let mut words = status.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("ICY"), Some("200")) => {}
+            (Some("SOURCETABLE"), Some("200")) => {
+                return Err(NtripError::MountpointNotFound {
+                    source_table: connection.source_table(),
+                });
+            }
+            (Some(protocol), Some(code)) if protocol.starts_with("HTTP/") => {
+                let code: u16 = code
+                    .parse()
+                    .map_err(|_| NtripError::Handshake(status.clone()))?;
+                let mut source_table = false;
+                for _ in 0..MAX_HEADER_LINES {
+                    let line = connection.line()?;
+                    if line.is_empty() {
+                        break;
+                    }
+                    let Some((name, value)) = line.split_once(':') else {
+                        continue;
+                    };
+                    let (name, value) = (name.trim(), value.trim());
+                    if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                        connection.chunked = value.eq_ignore_ascii_case("chunked");
+                    } else if name.eq_ignore_ascii_case("Content-Type") {
+                        source_table = value.eq_ignore_ascii_case("gnss/sourcetable");
+                    }
+                }
+                match code {
+                    200 if source_table => {
+                        return Err(NtripError::MountpointNotFound {
+                            source_table: connection.source_table(),
+                        });
+                    }
+                    200 => {}
+                    401 => return Err(NtripError::Unauthorized),
+                    404 => {
+                        return Err(NtripError::MountpointNotFound {
+                            source_table: String::new(),
+                        })
+                    }
+                    _ => return Err(NtripError::Rejected { code, status }),
+                }
+            }
+            _ => return Err(NtripError::Handshake(status)),
+        }
+        if gga.is_some() {
+            // Both revisions accept the position in the stream right after the header
+            connection.send_gga(gga, None)?;
+        }
+        Ok(connection)
+    }
+
+    /// Header line without its terminator; end of stream is an error here
+    fn line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn source_table(&mut self) -> String {
+        let mut table = String::new();
+        let _ = self.take(MAX_SOURCE_TABLE).read_to_string(&mut table);
+        match table.find("ENDSOURCETABLE") {
+            Some(end) => table[..end].to_string(),
+            None => table,
+        }
+    }
+
+    /// Send the GGA sentence if none went out yet or `interval` has passed
+    fn send_gga(&mut self, gga: Option<&str>, interval: Option<Duration>) -> io::Result<()> {
+        let Some(gga) = gga else {
+            return Ok(());
+        };
+        let due = match (self.last_gga, interval) {
+            (None, _) => true,
+            (Some(last), Some(interval)) => last.elapsed() >= interval,
+            (Some(_), None) => false,
+        };
+        if due {
+            let stream = self.reader.get_mut();
+            stream.write_all(gga.as_bytes())?;
+            stream.write_all(b"\r\n")?;
+            self.last_gga = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+impl Read for Connection {
+    /// Stream bytes with any chunked transfer encoding removed; 0 at the end of stream
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.chunked {
+            return self.reader.read(buf);
+        }
+        while self.chunk_remaining == 0 {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(0);
+            }
+            // Skip the CR LF closing the previous chunk
+            let size = line.split(';').next().unwrap_or("").trim();
+            if size.is_empty() {
+                continue;
+            }
+            self.chunk_remaining = usize::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+            if self.chunk_remaining == 0 {
+                return Ok(0);
+            }
+        }
+        let length = buf.len().min(self.chunk_remaining);
+        let n = self.reader.read(&mut buf[..length])?;
+        self.chunk_remaining -= n;
+        Ok(n)
+    }
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last = io::Error::new(io::ErrorKind::NotFound, format!("{} did not resolve", host));
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last = error,
+        }
+    }
+    Err(last)
+}
+
+/// Request header for the configured revision
+fn request(config: &NtripConfig, gga: Option<&str>) -> String {
+    let mountpoint = config.mountpoint.trim_start_matches('/');
+    let mut request = match config.version {
+        NtripVersion::V1 => format!("GET /{} HTTP/1.0\r\n", mountpoint),
+        NtripVersion::V2 => format!(
+            "GET /{} HTTP/1.1\r\nHost: {}:{}\r\nNtrip-Version: Ntrip/2.0\r\nConnection: close\r\n",
+            mountpoint, config.host, config.port
+        ),
+    };
+    request += &format!("User-Agent: NTRIP {}\r\n", config.user_agent);
+    if let Some((user, password)) = &config.credentials {
+        let token = base64(format!("{}:{}", user, password).as_bytes());
+        request += &format!("Authorization: Basic {}\r\n", token);
+    }
+    if let (NtripVersion::V2, Some(gga)) = (config.version, gga) {
+        request += &format!("Ntrip-GGA: {}\r\n", gga);
+    }
+    request + "\r\n"
+}
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// An RTCM 3 message with its transport framing removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtcmFrame {
+    pub message_type: u16, // First 12 bits of the payload, e.g. 1019 for GPS ephemerides
+    pub payload: Vec<u8>,
+}
+
+/// Splits a byte stream into RTCM 3 frames: preamble 0xD3, 10-bit length, payload and
+/// CRC-24Q
+#[derive(Debug, Clone, Default)]
+pub struct RtcmFramer {
+    buffer: Vec<u8>,
+    pub crc_errors: u64,
+}
+
+impl RtcmFramer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Next verified frame in the buffered bytes, None until one is complete
+    pub fn next_frame(&mut self) -> Option<RtcmFrame> {
+        loop {
+            let start = self.buffer.iter().position(|&byte| byte == RTCM_PREAMBLE);
+            self.buffer.drain(..start.unwrap_or(self.buffer.len()));
+            if self.buffer.len() < 3 {
+                return None;
+            }
+            // Six reserved bits precede the length and must be zero
+            if self.buffer[1] & 0xFC != 0 {
+                self.buffer.remove(0);
+                continue;
+            }
+            let length = ((self.buffer[1] as usize & 0x03) << 8) | self.buffer[2] as usize;
+            let end = 3 + length;
+            if self.buffer.len() < end + 3 {
+                return None;
+            }
+            let crc = u32::from_be_bytes([
+                0,
+                self.buffer[end],
+                self.buffer[end + 1],
+                self.buffer[end + 2],
+            ]);
+            if crc24q(&self.buffer[..end]) != crc {
+                self.crc_errors += 1;
+                self.buffer.remove(0);
+                continue;
+            }
+            let payload = self.buffer[3..end].to_vec();
+            self.buffer.drain(..end + 3);
+            let message_type = match payload[..] {
+                [first, second, ..] => (first as u16) << 4 | (second as u16) >> 4,
+                _ => 0,
+            };
+            return Some(RtcmFrame {
+                message_type,
+                payload,
+            });
+        }
+    }
+}
+
+/// Frame around an RTCM 3 payload, the inverse of `RtcmFramer`
+pub fn rtcm_frame(payload: &[u8]) -> Vec<u8> {
+    let length = payload.len().min(0x3FF);
+    let mut frame = vec![RTCM_PREAMBLE, (length >> 8) as u8, length as u8];
+    frame.extend_from_slice(&payload[..length]);
+    let crc = crc24q(&frame);
+    frame.extend_from_slice(&crc.to_be_bytes()[1..]);
+    frame
+}
+
+fn crc24q(bytes: &[u8]) -> u32 {
+    const POLYNOMIAL: u32 = 0x0186_4CFB;
+    let mut crc = 0u32;
+    for &byte in bytes {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x0100_0000 != 0 {
+                crc ^= POLYNOMIAL;
+            }
+        }
+    }
+    crc & 0x00FF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    const GGA: &str = "$GPGGA,020000.00,4807.0380,N,01131.0000,E,1,08,0.9,545.4,M,,M,,*7C";
+
+    /// What the caster does with a connection once it has read the request header
+    type Session = Box<dyn FnOnce(&mut BufReader<TcpStream>) + Send>;
+
+    /// Caster on a loopback port serving one session per connection, in order. The
+    /// handle returns the request header of every connection.
+    fn caster(sessions: Vec<Session>) -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for session in sessions {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    request += &line;
+                }
+                requests.push(request);
+                session(&mut reader);
+            }
+            requests
+        });
+        (port, handle)
+    }
+
+    fn send(reader: &mut BufReader<TcpStream>, bytes: &[u8]) {
+        reader.get_mut().write_all(bytes).unwrap();
+    }
+
+    fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line
+    }
+
+    /// RTCM frame of the given message type
+    fn frame(message_type: u16, filler: u8) -> Vec<u8> {
+        let payload = [
+            (message_type >> 4) as u8,
+            (message_type << 4) as u8,
+            filler,
+            filler,
+        ];
+        rtcm_frame(&payload)
+    }
+
+    fn chunked(bytes: &[u8]) -> Vec<u8> {
+        let mut chunk = format!("{:x}\r\n", bytes.len()).into_bytes();
+        chunk.extend_from_slice(bytes);
+        chunk.extend_from_slice(b"\r\n");
+        chunk
+    }
+
+    fn config(port: u16) -> NtripConfig {
+        NtripConfig::new("127.0.0.1", "MOUNT")
+            .port(port)
+            .timeout(Duration::from_secs(5))
+            .backoff(Backoff {
+                initial: Duration::from_millis(1),
+                max: Duration::from_millis(10),
+                max_attempts: Some(3),
+            })
+    }
+
+    #[test]
+    fn v2_handshake_with_credentials_and_chunked_frames() {
+        let (port, caster) = caster(vec![Box::new(|reader| {
+            send(
+                reader,
+                b"HTTP/1.1 200 OK\r\nNtrip-Version: Ntrip/2.0\r\nTransfer-Encoding: chunked\r\n\r\n",
+            );
+            assert_eq!(read_line(reader), format!("{}\r\n", GGA));
+            // The second frame is split across chunks, with noise in between
+            let first = frame(1019, 0x11);
+            let second = frame(1005, 0x22);
+            let mut stream = first.clone();
+            stream.extend_from_slice(&[0x00, 0x42]);
+            stream.extend_from_slice(&second[..3]);
+            send(reader, &chunked(&stream));
+            send(reader, &chunked(&second[3..]));
+            send(reader, b"0\r\n\r\n");
+        })]);
+        let mut client = NtripClient::new(config(port).credentials("user", "pass"));
+        client.set_gga(GGA);
+        let first = client.next_frame().unwrap();
+        assert_eq!(first.message_type, 1019);
+        assert_eq!(first.payload[2..], [0x11, 0x11]);
+        let second = client.next_frame().unwrap();
+        assert_eq!(second.message_type, 1005);
+        assert_eq!(client.reconnects(), 0);
+
+        let requests = caster.join().unwrap();
+        let request = &requests[0];
+        assert!(request.starts_with("GET /MOUNT HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("Host: 127.0.0.1:{}\r\n", port)));
+        assert!(request.contains("Ntrip-Version: Ntrip/2.0\r\n"));
+        assert!(request.contains("User-Agent: NTRIP pnt_rust/"));
+        assert!(request.contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+        assert!(request.contains(&format!("Ntrip-GGA: {}\r\n", GGA)));
+    }
+
+    #[test]
+    fn v1_handshake_sends_gga_in_the_stream() {
+        let (port, caster) = caster(vec![Box::new(|reader| {
+            send(reader, b"ICY 200 OK\r\n");
+            assert_eq!(read_line(reader), format!("{}\r\n", GGA));
+            send(reader, &frame(1077, 0x33));
+        })]);
+        let mut client = NtripClient::new(config(port).version(NtripVersion::V1));
+        client.set_gga(GGA);
+        client.connect().unwrap();
+        assert!(client.is_connected());
+        assert_eq!(client.next_frame().unwrap().message_type, 1077);
+
+        let request = &caster.join().unwrap()[0];
+        assert!(request.starts_with("GET /MOUNT HTTP/1.0\r\n"));
+        assert!(!request.contains("Ntrip-GGA"));
+        assert!(!request.contains("Authorization"));
+    }
+
+    #[test]
+    fn dropouts_reconnect_and_discard_partial_frames() {
+        let (port, caster) = caster(vec![
+            Box::new(|reader| {
+                send(reader, b"ICY 200 OK\r\n");
+                let mut stream = frame(1019, 0x44);
+                // Half a frame, then the connection drops
+                stream.extend_from_slice(&frame(1020, 0x55)[..4]);
+                send(reader, &stream);
+            }),
+            // A server error is worth retrying
+            Box::new(|reader| send(reader, b"HTTP/1.1 503 Service Unavailable\r\n\r\n")),
+            Box::new(|reader| {
+                send(reader, b"ICY 200 OK\r\n");
+                send(reader, &frame(1042, 0x66));
+            }),
+        ]);
+        let mut client = NtripClient::new(config(port).version(NtripVersion::V1));
+        assert_eq!(client.next_frame().unwrap().message_type, 1019);
+        let frame = client.next_frame().unwrap();
+        assert_eq!(frame.message_type, 1042);
+        assert_eq!(frame.payload[2..], [0x66, 0x66]);
+        assert_eq!(client.reconnects(), 1);
+        assert_eq!(caster.join().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn refusals_are_returned_without_retrying() {
+        let (port, caster) = caster(vec![
+            Box::new(|reader| send(reader, b"HTTP/1.1 401 Unauthorized\r\n\r\n")),
+            Box::new(|reader| {
+                send(reader, b"SOURCETABLE 200 OK\r\n");
+                send(reader, b"STR;OTHER;Other;RTCM 3.3;\r\nENDSOURCETABLE\r\n");
+            }),
+            Box::new(|reader| {
+                send(
+                    reader,
+                    b"HTTP/1.1 200 OK\r\nContent-Type: gnss/sourcetable\r\n\r\n",
+                );
+                send(reader, b"STR;OTHER;Other;RTCM 3.3;\r\nENDSOURCETABLE\r\n");
+            }),
+        ]);
+        let mut client = NtripClient::new(config(port));
+        assert!(matches!(client.connect(), Err(NtripError::Unauthorized)));
+        match client.connect() {
+            Err(NtripError::MountpointNotFound { source_table }) => {
+                assert_eq!(source_table, "STR;OTHER;Other;RTCM 3.3;\r\n");
+            }
+            other => panic!("{:?}", other.err()),
+        }
+        assert!(matches!(
+            client.connect(),
+            Err(NtripError::MountpointNotFound { .. })
+        ));
+        assert!(!client.is_connected());
+        assert_eq!(caster.join().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn unreachable_casters_give_up_after_max_attempts() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut client = NtripClient::new(config(port));
+        match client.connect() {
+            Err(NtripError::GaveUp { attempts, last }) => {
+                assert_eq!(attempts, 3);
+                assert!(matches!(*last, NtripError::Io(_)));
+            }
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let backoff = Backoff::default();
+        let delays: Vec<u64> = (0..9).map(|n| backoff.delay(n).as_secs()).collect();
+        assert_eq!(delays, [0, 1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff.delay(u32::MAX), backoff.max);
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (text, encoded) in vectors {
+            assert_eq!(base64(text.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn framer_skips_noise_and_crc_failures() {
+        let mut bytes = vec![0x00, 0xD3, 0xFF];
+        let mut corrupt = frame(1019, 0x77);
+        corrupt[4] ^= 0x01;
+        bytes.extend_from_slice(&corrupt);
+        bytes.extend_from_slice(&frame(1019, 0x88));
+        let mut framer = RtcmFramer::new();
+        framer.push(&bytes[..10]);
+        assert_eq!(framer.next_frame(), None);
+        framer.push(&bytes[10..]);
+        let frame = framer.next_frame().unwrap();
+        assert_eq!(frame.payload[2..], [0x88, 0x88]);
+        assert_eq!(framer.crc_errors, 1);
+        assert_eq!(framer.next_frame(), None);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::combination::ObservationPair;
+use crate::gnss::{self, SatId, ECEF};
+use crate::positioning::PseudorangeObservation;
+use crate::signal::Signal;
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Code and carrier measured on one signal. Missing or non-finite values are ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalObservation {
+    pub signal: Signal,
+    pub pseudorange: Option<f64>, // m
+    pub phase: Option<f64>,       // Carrier phase, cycles as in RINEX
+    pub doppler: Option<f64>,     // Hz, positive when approaching
+    pub loss_of_lock: bool,       // RINEX LLI bit 0: a cycle slip may have occurred
+}
+
+impl SignalObservation {
+    pub fn new(signal: Signal, pseudorange: Option<f64>, phase: Option<f64>) -> Self {
+        Self {
+            signal,
+            pseudorange,
+            phase,
+            doppler: None,
+            loss_of_lock: false,
+        }
+    }
+
+    /// Pseudorange, m
+    pub fn code(&self) -> Option<f64> {
+        self.pseudorange.filter(|value| value.is_finite())
+    }
+
+    /// Carrier phase scaled to m
+    pub fn phase_meters(&self) -> Option<f64> {
+        let phase = self.phase.filter(|value| value.is_finite())?;
+        Some(phase * self.signal.wavelength())
+    }
+}
+
+/// Everything observed from one satellite at an epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct SatelliteObservations {
+    pub sat_id: SatId,
+    pub signals: Vec<SignalObservation>,
+}
+
+impl SatelliteObservations {
+    pub fn signal(&self, signal: Signal) -> Option<&SignalObservation> {
+        self.signals.iter().find(|obs| obs.signal == signal)
+    }
+
+    /// The two signals as a pair for forming combinations
+    pub fn pair(
+        &self,
+        epoch: DateTime<Utc>,
+        first: Signal,
+        second: Signal,
+    ) -> Option<ObservationPair> {
+        Some(ObservationPair {
+            sat_id: self.sat_id,
+            epoch,
+            first: *self.signal(first)?,
+            second: *self.signal(second)?,
+        })
+    }
+}
+
+/// All satellites observed by one receiver at one time tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObservationEpoch {
+    pub epoch: DateTime<Utc>,
+    pub satellites: Vec<SatelliteObservations>,
+}
+
+impl ObservationEpoch {
+    pub fn satellite(&self, sat_id: SatId) -> Option<&SatelliteObservations> {
+        self.satellites.iter().find(|obs| obs.sat_id == sat_id)
+    }
+
+    /// Code observations on one signal in the form the positioning solvers take. Dopplers
+    /// are scaled to L1.
+    pub fn pseudoranges(&self, signal: Signal) -> Vec<PseudorangeObservation> {
+        let scale = gnss::L1_FREQUENCY / signal.frequency_hz();
+        self.satellites
+            .iter()
+            .filter_map(|sat| {
+                let obs = sat.signal(signal)?;
+                Some(PseudorangeObservation {
+                    sat_id: sat.sat_id,
+                    pseudorange: obs.code()?,
+                    snr: None,
+                    doppler: obs.doppler.map(|doppler| doppler * scale),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Pair the epochs of two receivers whose time tags differ by at most `tolerance` seconds,
+/// each taking its nearest counterpart. Epochs without one are left out.
+pub fn align_epochs<'a>(
+    first: &'a [ObservationEpoch],
+    second: &'a [ObservationEpoch],
+    tolerance: f64,
+) -> Vec<(&'a ObservationEpoch, &'a ObservationEpoch)> {
+    let time = |obs: &ObservationEpoch| gnss::gps_seconds(obs.epoch);
+    let second_times: Vec<f64> = second.iter().map(time).collect();
+    let mut pairs = Vec::new();
+    for obs in first {
+        let t = time(obs);
+        let nearest = second_times
+            .iter()
+            .enumerate()
+            .min_by(|a, b| (a.1 - t).abs().total_cmp(&(b.1 - t).abs()));
+        if let Some((idx, other)) = nearest {
+            if (other - t).abs() <= tolerance {
+                pairs.push((obs, &second[idx]));
+            }
+        }
+    }
+    pairs
+}
+
+/// A line of a RINEX observation file that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseRinexObsError {
+    pub line: usize, // 1-based
+    pub message: String,
+}
+
+impl fmt::Display for ParseRinexObsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseRinexObsError {}
+
+/// The observations of a RINEX 3 observation file. Time tags are read as GPS time, the
+/// RINEX default for GPS and mixed files. Of several codes on one band the first listed
+/// in the header is kept; signal strengths and event records are skipped.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RinexObs {
+    pub marker: String,
+    pub approx_position: Option<ECEF>,
+    pub epochs: Vec<ObservationEpoch>,
+}
+
+impl FromStr for RinexObs {
+    type Err = ParseRinexObsError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut obs = Self::default();
+        let mut types: BTreeMap<char, Vec<String>> = BTreeMap::new();
+        let mut channels: BTreeMap<SatId, i8> = BTreeMap::new();
+        let mut last_system = ' ';
+        let mut lines = text.lines().enumerate();
+
+        for (idx, line) in lines.by_ref() {
+            let error = |message: &str| ParseRinexObsError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            let data = line.get(..60.min(line.len())).unwrap_or(line);
+            match line.get(60..).unwrap_or("").trim() {
+                "RINEX VERSION / TYPE" => {
+                    let version: f64 = field(line, 0, 9)
+                        .parse()
+                        .map_err(|_| error("invalid version"))?;
+                    if version < 3.0 || field(line, 20, 21) != "O" {
+                        return Err(error("not a RINEX 3 observation file"));
+                    }
+                }
+                "MARKER NAME" => obs.marker = data.trim().to_string(),
+                "APPROX POSITION XYZ" => {
+                    let xyz = data
+                        .split_whitespace()
+                        .map(|value| value.parse().map_err(|_| error("invalid position")))
+                        .collect::<Result<Vec<f64>, _>>()?;
+                    if let [x, y, z] = xyz[..] {
+                        obs.approx_position = Some(ECEF::new(x, y, z));
+                    }
+                }
+                "SYS / # / OBS TYPES" => {
+                    // Continuation lines leave the system blank
+                    if let Some(system) = data.chars().next().filter(|c| *c != ' ') {
+                        last_system = system;
+                    }
+                    let codes = types.entry(last_system).or_default();
+                    codes.extend(
+                        data.get(7..)
+                            .unwrap_or("")
+                            .split_whitespace()
+                            .map(String::from),
+                    );
+                }
+                "GLONASS SLOT / FRQ #" => {
+                    let entries = data.get(4..).unwrap_or("").split_whitespace();
+                    let entries: Vec<&str> = entries.collect();
+                    for pair in entries.chunks(2) {
+                        if let [sat, channel] = pair {
+                            let sat_id = sat.parse().map_err(|_| error("invalid slot"))?;
+                            let channel = channel.parse().map_err(|_| error("invalid channel"))?;
+                            channels.insert(sat_id, channel);
+                        }
+                    }
+                }
+                "END OF HEADER" => break,
+                _ => {}
+            }
+        }
+
+        let mut remaining = 0;
+        let mut skipping = 0;
+        for (idx, line) in lines {
+            let error = |message: &str| ParseRinexObsError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            if skipping > 0 {
+                skipping -= 1;
+                continue;
+            }
+            if line.starts_with('>') {
+                let count: usize = field(line, 32, 35)
+                    .parse()
+                    .map_err(|_| error("invalid satellite count"))?;
+                match field(line, 31, 32) {
+                    "0" | "1" | "" => {}
+                    // Event flags carry that many special records instead of satellites
+                    _ => {
+                        skipping = count;
+                        remaining = 0;
+                        continue;
+                    }
+                }
+                let epoch = parse_epoch(line).ok_or_else(|| error("invalid epoch"))?;
+                obs.epochs.push(ObservationEpoch {
+                    epoch,
+                    satellites: Vec::with_capacity(count),
+                });
+                remaining = count;
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(epoch) = obs.epochs.last_mut().filter(|_| remaining > 0) else {
+                return Err(error("observation outside an epoch"));
+            };
+            remaining -= 1;
+            let sat_id: SatId = field(line, 0, 3)
+                .parse()
+                .map_err(|_| error("invalid satellite"))?;
+            let system = sat_id.constellation.to_char();
+            let codes = types
+                .get(&system)
+                .ok_or_else(|| error("no observation types"))?;
+            let channel = channels.get(&sat_id).copied();
+            let mut signals: Vec<SignalObservation> = Vec::new();
+            for (i, code) in codes.iter().enumerate() {
+                let start = 3 + 16 * i;
+                let text = field(line, start, start + 14);
+                if text.is_empty() {
+                    continue;
+                }
+                let value: f64 = text.parse().map_err(|_| error("invalid observation"))?;
+                let lli = field(line, start + 14, start + 15).parse().unwrap_or(0u8);
+                let Some(signal) = Signal::from_rinex_code(sat_id.constellation, code, channel)
+                else {
+                    continue;
+                };
+                let observation = match signals.iter_mut().position(|obs| obs.signal == signal) {
+                    Some(i) => &mut signals[i],
+                    None => {
+                        signals.push(SignalObservation::new(signal, None, None));
+                        signals.last_mut().unwrap()
+                    }
+                };
+                let slot = match code.as_bytes()[0] {
+                    b'C' => &mut observation.pseudorange,
+                    b'L' => &mut observation.phase,
+                    b'D' => &mut observation.doppler,
+                    _ => continue,
+                };
+                if slot.is_none() {
+                    *slot = Some(value);
+                    if code.starts_with('L') {
+                        observation.loss_of_lock = lli & 1 != 0;
+                    }
+                }
+            }
+            if !signals.is_empty() {
+                epoch
+                    .satellites
+                    .push(SatelliteObservations { sat_id, signals });
+            }
+        }
+        Ok(obs)
+    }
+}
+
+fn field(line: &str, start: usize, end: usize) -> &str {
+    line.get(start..end.min(line.len())).unwrap_or("").trim()
+}
+
+/// Time tag of an epoch line, "> 2023 06 12 04 10  0.0000000  0 13", as UTC
+fn parse_epoch(line: &str) -> Option<DateTime<Utc>> {
+    let number = |start, end| field(line, start, end).parse::<u32>().ok();
+    let second: f64 = field(line, 18, 29).parse().ok()?;
+    let tag = Utc
+        .with_ymd_and_hms(
+            field(line, 2, 6).parse().ok()?,
+            number(7, 9)?,
+            number(10, 12)?,
+            number(13, 15)?,
+            number(16, 18)?,
+            0,
+        )
+        .single()?;
+    let micros = (second * 1e6).round() as i64 - (gnss::GPS_LEAP_SECONDS * 1e6) as i64;
+    Some(tag + chrono::Duration::microseconds(micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{station, OBS};
+
+    /// Header and epochs in the layout of an IGS daily file: GLONASS channels, a wrapped
+    /// observation type list, a lost lock, a blank field and an event record. The values are
+    /// made up.
+    const RNX: &str = "\
+     3.04           OBSERVATION DATA    M                   RINEX VERSION / TYPE
+TEST                                                        MARKER NAME
+  -2281621.6297 -1453585.1138  5756964.9518                  APPROX POSITION XYZ
+G    4 C1C L1C D1C C2W                                      SYS / # / OBS TYPES
+R   14 C1C L1C D1C S1C C1P L1P D1P S1P C2C L2C D2C S2C C2P  SYS / # / OBS TYPES
+       L2P                                                  SYS / # / OBS TYPES
+  1 R09 -2                                                  GLONASS SLOT / FRQ #
+                                                            END OF HEADER
+> 2023 06 12 04 10  0.0000000  0  2
+G17  22836981.912   120009876.543       -1299.537    22836985.118
+R09  21000000.000   112000000.12316        10.000          45.000    21000001.000
+> 2023 06 12 04 10 30.0000000  4  1
+                                                            COMMENT
+> 2023 06 12 04 11  0.0000000  0  1
+G17  22837038.226                       -1299.012
+";
+
+    #[test]
+    fn rinex_observations_are_read_by_signal() {
+        let obs: RinexObs = RNX.parse().unwrap();
+        assert_eq!(obs.marker, "TEST");
+        assert_eq!(obs.approx_position, Some(station()));
+        assert_eq!(obs.epochs.len(), 2);
+        let first = &obs.epochs[0];
+        // 04:10 GPS is 04:09:42 UTC
+        assert_eq!(
+            first.epoch,
+            Utc.with_ymd_and_hms(2023, 6, 12, 4, 9, 42).unwrap()
+        );
+
+        let g17 = first.satellite(SatId::gps(17)).unwrap();
+        let l1 = g17.signal(Signal::GpsL1).unwrap();
+        assert_eq!(l1.pseudorange, Some(22836981.912));
+        assert_eq!(l1.phase, Some(120009876.543));
+        assert_eq!(l1.doppler, Some(-1299.537));
+        assert!(!l1.loss_of_lock);
+        assert_eq!(
+            g17.signal(Signal::GpsL2).unwrap().pseudorange,
+            Some(22836985.118)
+        );
+
+        // The C/A code wins over P on G1, and the channel comes from the header
+        let r09 = first.satellite("R09".parse().unwrap()).unwrap();
+        let g1 = r09.signal(Signal::GlonassG1(-2)).unwrap();
+        assert_eq!(g1.pseudorange, Some(21000000.0));
+        assert_eq!(g1.doppler, Some(10.0));
+        assert!(g1.loss_of_lock);
+        assert_eq!(r09.signals.len(), 1);
+
+        let last = &obs.epochs[1].satellites[0];
+        assert_eq!(last.signals[0].phase, None);
+        assert_eq!(last.signals[0].doppler, Some(-1299.012));
+    }
+
+    #[test]
+    fn malformed_rinex_reports_the_line() {
+        let nav =
+            "     3.04           N: GNSS NAV DATA    G                   RINEX VERSION / TYPE";
+        assert_eq!(nav.parse::<RinexObs>().unwrap_err().line, 1);
+        let text = RNX.replace("G17  22836981.912", "G17  2283698x.912");
+        let error = text.parse::<RinexObs>().unwrap_err();
+        assert_eq!(error.line, 10);
+        assert_eq!(error.to_string(), "line 10: invalid observation");
+    }
+
+    #[test]
+    fn simulated_fixture_reads_as_l1_epochs() {
+        let obs: RinexObs = OBS.parse().unwrap();
+        assert_eq!(obs.marker, "GCGO");
+        assert_eq!(obs.epochs.len(), 40);
+        let pseudoranges = obs.epochs[0].pseudoranges(Signal::GpsL1);
+        assert_eq!(pseudoranges.len(), 13);
+        assert_eq!(pseudoranges[0].sat_id, SatId::gps(1));
+        assert_eq!(pseudoranges[0].pseudorange, 21653529.572);
+        assert_eq!(pseudoranges[0].doppler, Some(-2925.455));
+    }
+}
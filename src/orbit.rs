@@ -0,0 +1,233 @@
+use crate::gnss::{NavRecord, ECEF};
+use crate::satellite::Satellite;
+use std::f64::consts::PI;
+
+const SINGULARITY_TOL: f64 = 1e-11; // Below this e or sin(i) the angle it defines is undefined
+
+/// Osculating Keplerian elements, angles in radians.
+///
+/// Positions and velocities are inertial (the ECEF type is reused as a plain 3-vector).
+/// For circular orbits the argument of perigee is 0 and the true anomaly is the argument
+/// of latitude; for equatorial orbits the RAAN is 0 and the argument of perigee is the
+/// longitude of periapsis.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct KeplerianElements {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    pub raan: f64,
+    pub arg_perigee: f64,
+    pub true_anomaly: f64,
+}
+
+impl KeplerianElements {
+    pub fn from_mean_anomaly(
+        semi_major_axis: f64,
+        eccentricity: f64,
+        inclination: f64,
+        raan: f64,
+        arg_perigee: f64,
+        mean_anomaly: f64,
+    ) -> Self {
+        let (e_anomaly, _, _) = Satellite::kepler_newton(mean_anomaly, eccentricity, 1e-14, 50);
+        let true_anomaly = ((1.0 - eccentricity * eccentricity).sqrt() * e_anomaly.sin())
+            .atan2(e_anomaly.cos() - eccentricity);
+        Self {
+            semi_major_axis,
+            eccentricity,
+            inclination,
+            raan,
+            arg_perigee,
+            true_anomaly,
+        }
+    }
+
+    pub fn from_state(position: &ECEF, velocity: &ECEF, mu: f64) -> Self {
+        let r = position.norm();
+        let v2 = velocity.dot(velocity);
+        let h = position.cross(velocity);
+        let h_norm = h.norm();
+        let node = ECEF::new(-h.y, h.x, 0.0);
+        let e_vec = (*position * (v2 - mu / r) - *velocity * position.dot(velocity)) * (1.0 / mu);
+
+        let eccentricity = e_vec.norm();
+        let semi_major_axis = 1.0 / (2.0 / r - v2 / mu);
+        let inclination = (h.x.hypot(h.y)).atan2(h.z);
+        let circular = eccentricity < SINGULARITY_TOL;
+        let equatorial = inclination.sin().abs() < SINGULARITY_TOL;
+
+        let raan = if equatorial {
+            0.0
+        } else {
+            node.y.atan2(node.x)
+        };
+        // Unit vectors spanning the orbital plane, starting at the node (or x axis)
+        let p_hat = if equatorial {
+            ECEF::new(1.0, 0.0, 0.0)
+        } else {
+            node * (1.0 / node.norm())
+        };
+        let q_hat = h.cross(&p_hat) * (1.0 / h_norm);
+        let angle_in_plane = |v: &ECEF| v.dot(&q_hat).atan2(v.dot(&p_hat));
+
+        let arg_perigee = if circular {
+            0.0
+        } else {
+            angle_in_plane(&e_vec)
+        };
+        let true_anomaly = angle_in_plane(position) - arg_perigee;
+        Self {
+            semi_major_axis,
+            eccentricity,
+            inclination,
+            raan: wrap_two_pi(raan),
+            arg_perigee: wrap_two_pi(arg_perigee),
+            true_anomaly: wrap_two_pi(true_anomaly),
+        }
+    }
+
+    /// Inertial position and velocity
+    pub fn to_state(&self, mu: f64) -> (ECEF, ECEF) {
+        let e = self.eccentricity;
+        let p = self.semi_major_axis * (1.0 - e * e);
+        let (sin_nu, cos_nu) = self.true_anomaly.sin_cos();
+        let r = p / (1.0 + e * cos_nu);
+        let v_scale = (mu / p).sqrt();
+
+        let (sin_raan, cos_raan) = self.raan.sin_cos();
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (sin_w, cos_w) = self.arg_perigee.sin_cos();
+        // Perifocal P and Q axes expressed in the inertial frame
+        let p_axis = ECEF::new(
+            cos_raan * cos_w - sin_raan * sin_w * cos_i,
+            sin_raan * cos_w + cos_raan * sin_w * cos_i,
+            sin_w * sin_i,
+        );
+        let q_axis = ECEF::new(
+            -cos_raan * sin_w - sin_raan * cos_w * cos_i,
+            -sin_raan * sin_w + cos_raan * cos_w * cos_i,
+            cos_w * sin_i,
+        );
+        let position = p_axis * (r * cos_nu) + q_axis * (r * sin_nu);
+        let velocity = p_axis * (-v_scale * sin_nu) + q_axis * (v_scale * (e + cos_nu));
+        (position, velocity)
+    }
+
+    pub fn eccentric_anomaly(&self) -> f64 {
+        let e = self.eccentricity;
+        let (sin_nu, cos_nu) = self.true_anomaly.sin_cos();
+        ((1.0 - e * e).sqrt() * sin_nu).atan2(e + cos_nu)
+    }
+
+    pub fn mean_anomaly(&self) -> f64 {
+        let e_anomaly = self.eccentric_anomaly();
+        wrap_two_pi(e_anomaly - self.eccentricity * e_anomaly.sin())
+    }
+
+    /// ω + ν, well defined for circular orbits
+    pub fn argument_of_latitude(&self) -> f64 {
+        wrap_two_pi(self.arg_perigee + self.true_anomaly)
+    }
+
+    /// Ω + ω, well defined for equatorial orbits
+    pub fn longitude_of_periapsis(&self) -> f64 {
+        wrap_two_pi(self.raan + self.arg_perigee)
+    }
+
+    /// Ω + ω + ν, well defined for circular equatorial orbits
+    pub fn true_longitude(&self) -> f64 {
+        wrap_two_pi(self.raan + self.arg_perigee + self.true_anomaly)
+    }
+}
+
+impl NavRecord {
+    /// Broadcast mean elements at toe, in the inertial frame aligned with ECEF at the
+    /// start of the GPS week (where the broadcast OMEGA0 is the RAAN)
+    pub fn to_keplerian(&self) -> KeplerianElements {
+        KeplerianElements::from_mean_anomaly(
+            self.sqrt_a * self.sqrt_a,
+            self.eccentricity,
+            self.i0,
+            self.omega0,
+            self.omega,
+            self.m0,
+        )
+    }
+}
+
+fn wrap_two_pi(angle: f64) -> f64 {
+    angle.rem_euclid(2.0 * PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::MU_EARTH;
+    use crate::test_support::NAV;
+
+    /// Difference of two angles, wrapped into [-π, π)
+    fn angle_difference(a: f64, b: f64) -> f64 {
+        (a - b + PI).rem_euclid(2.0 * PI) - PI
+    }
+
+    #[test]
+    fn elements_round_trip_through_the_state() {
+        let eccentricities = [0.0, 1e-6, 0.01, 0.1, 0.5, 0.9];
+        let inclinations = [0.0, 1e-6, 0.3, 55f64.to_radians(), PI / 2.0, 2.5];
+        for &eccentricity in &eccentricities {
+            for &inclination in &inclinations {
+                let elements = KeplerianElements::from_mean_anomaly(
+                    26_560_000.0,
+                    eccentricity,
+                    inclination,
+                    1.2,
+                    4.0,
+                    2.1,
+                );
+                let (position, velocity) = elements.to_state(MU_EARTH);
+                let back = KeplerianElements::from_state(&position, &velocity, MU_EARTH);
+                let case = format!("e {} i {}", eccentricity, inclination);
+
+                assert!((back.semi_major_axis / elements.semi_major_axis - 1.0).abs() < 1e-12);
+                assert!((back.eccentricity - eccentricity).abs() < 1e-12, "{}", case);
+                assert!((back.inclination - inclination).abs() < 1e-12, "{}", case);
+                // The angles each singularity leaves defined
+                let true_longitude =
+                    angle_difference(back.true_longitude(), elements.true_longitude());
+                assert!(true_longitude.abs() < 1e-9, "{}", case);
+                if back.eccentricity >= SINGULARITY_TOL {
+                    let periapsis =
+                        back.longitude_of_periapsis() - elements.longitude_of_periapsis();
+                    assert!(angle_difference(periapsis, 0.0).abs() < 1e-6, "{}", case);
+                }
+                if inclination.sin() >= SINGULARITY_TOL {
+                    let latitude = back.argument_of_latitude() - elements.argument_of_latitude();
+                    assert!(angle_difference(latitude, 0.0).abs() < 1e-9, "{}", case);
+                    assert!(angle_difference(back.raan, 1.2).abs() < 1e-9, "{}", case);
+                }
+                if back.eccentricity >= SINGULARITY_TOL && inclination.sin() >= SINGULARITY_TOL {
+                    let mean = angle_difference(back.mean_anomaly(), 2.1);
+                    assert!(mean.abs() < 1e-6, "{}", case);
+                }
+
+                let (position_back, velocity_back) = back.to_state(MU_EARTH);
+                assert!(position_back.approx_eq(&position, 1e-4), "{}", case);
+                assert!(velocity_back.approx_eq(&velocity, 1e-7), "{}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn broadcast_elements_are_a_gps_orbit() {
+        let nav: crate::gnss::RinexNav = NAV.parse().unwrap();
+        let record = &nav.records()[0];
+        let elements = record.to_keplerian();
+        assert_eq!(elements.semi_major_axis, record.sqrt_a * record.sqrt_a);
+        assert!((elements.semi_major_axis - 26_560e3).abs() < 50e3);
+        assert!(angle_difference(elements.mean_anomaly(), record.m0).abs() < 1e-12);
+        let (position, _) = elements.to_state(MU_EARTH);
+        let radius = elements.semi_major_axis
+            * (1.0 - record.eccentricity * elements.eccentric_anomaly().cos());
+        assert!((position.norm() - radius).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::constellation::Constellation;
+use crate::gnss::{self, SatId, StateRef};
+use crate::satellite::Satellite;
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::Compression;
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampNanosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Layout of a Parquet export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParquetOptions {
+    pub batch_size: usize,     // Rows buffered before they are handed to the writer
+    pub row_group_size: usize, // Rows per row group, which the writer holds encoded in memory
+    pub compression: bool,     // Snappy
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 65_536,
+            row_group_size: 1_048_576,
+            compression: true,
+        }
+    }
+}
+
+/// Column buffers of the batch being filled
+#[derive(Default)]
+struct Columns {
+    sat_id: Vec<String>,
+    time: Vec<i64>, // UTC, ns since 1970
+    gps_time: Vec<f64>,
+    x: Vec<f64>,
+    y: Vec<f64>,
+    z: Vec<f64>,
+    vx: Vec<Option<f64>>,
+    vy: Vec<Option<f64>>,
+    vz: Vec<Option<f64>>,
+    clock_bias: Vec<Option<f64>>,
+    kepler_converged: Vec<bool>,
+    extrapolated: Vec<bool>,
+    ephemeris_age: Vec<f64>,
+    accuracy: Vec<Option<f64>>,
+}
+
+/// Writes propagated states as one Parquet table with columns sat_id (string), time
+/// (timestamp[ns, UTC]), gps_time (GPS seconds), x, y, z, vx, vy, vz, clock_bias,
+/// kepler_converged, extrapolated, ephemeris_age and accuracy. Velocity, clock and accuracy
+/// are null where they were not propagated. Rows are flushed every `batch_size` states, so
+/// memory stays bounded however many are written.
+pub struct StateWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    columns: Columns,
+    batch_size: usize,
+}
+
+#[cfg(feature = "std-fs")]
+impl StateWriter<File> {
+    pub fn create(path: &str, options: &ParquetOptions) -> Result<Self, ParquetError> {
+        Self::new(File::create(path)?, options)
+    }
+}
+
+impl<W: Write + Send> StateWriter<W> {
+    pub fn new(writer: W, options: &ParquetOptions) -> Result<Self, ParquetError> {
+        let nullable = |name: &str| Field::new(name, DataType::Float64, true);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sat_id", DataType::Utf8, false),
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("gps_time", DataType::Float64, false),
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+            Field::new("z", DataType::Float64, false),
+            nullable("vx"),
+            nullable("vy"),
+            nullable("vz"),
+            nullable("clock_bias"),
+            Field::new("kepler_converged", DataType::Boolean, false),
+            Field::new("extrapolated", DataType::Boolean, false),
+            Field::new("ephemeris_age", DataType::Float64, false),
+            nullable("accuracy"),
+        ]));
+        let compression = match options.compression {
+            true => Compression::SNAPPY,
+            false => Compression::UNCOMPRESSED,
+        };
+        let properties = WriterProperties::builder()
+            .set_compression(compression)
+            .set_max_row_group_size(options.row_group_size.max(1))
+            .build();
+        Ok(Self {
+            writer: ArrowWriter::try_new(writer, schema.clone(), Some(properties))?,
+            schema,
+            columns: Columns::default(),
+            batch_size: options.batch_size.max(1),
+        })
+    }
+
+    pub fn write_state(&mut self, sat_id: SatId, state: StateRef<'_>) -> Result<(), ParquetError> {
+        let columns = &mut self.columns;
+        let gps_time = state.time();
+        let position = state.position();
+        let velocity = state.velocity();
+        columns.sat_id.push(sat_id.to_string());
+        columns
+            .time
+            .push(gnss::gps_seconds_to_utc(gps_time).timestamp_micros() * 1000);
+        columns.gps_time.push(gps_time);
+        columns.x.push(position.x);
+        columns.y.push(position.y);
+        columns.z.push(position.z);
+        columns.vx.push(velocity.map(|v| v.x));
+        columns.vy.push(velocity.map(|v| v.y));
+        columns.vz.push(velocity.map(|v| v.z));
+        columns.clock_bias.push(state.clock_bias());
+        columns.kepler_converged.push(state.kepler_converged());
+        columns.extrapolated.push(state.extrapolated());
+        columns.ephemeris_age.push(state.ephemeris_age());
+        columns.accuracy.push(state.accuracy());
+        if columns.sat_id.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn write_satellite(&mut self, satellite: &Satellite) -> Result<(), ParquetError> {
+        for state in satellite.states.iter() {
+            self.write_state(satellite.id, state)?;
+        }
+        Ok(())
+    }
+
+    /// Write out the buffered rows and the file footer
+    pub fn finish(mut self) -> Result<W, ParquetError> {
+        self.flush()?;
+        self.writer.into_inner()
+    }
+
+    fn flush(&mut self) -> Result<(), ParquetError> {
+        if self.columns.sat_id.is_empty() {
+            return Ok(());
+        }
+        let columns = std::mem::take(&mut self.columns);
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(columns.sat_id)),
+            Arc::new(TimestampNanosecondArray::from(columns.time).with_timezone("UTC")),
+            Arc::new(Float64Array::from(columns.gps_time)),
+            Arc::new(Float64Array::from(columns.x)),
+            Arc::new(Float64Array::from(columns.y)),
+            Arc::new(Float64Array::from(columns.z)),
+            Arc::new(Float64Array::from(columns.vx)),
+            Arc::new(Float64Array::from(columns.vy)),
+            Arc::new(Float64Array::from(columns.vz)),
+            Arc::new(Float64Array::from(columns.clock_bias)),
+            Arc::new(BooleanArray::from(columns.kepler_converged)),
+            Arc::new(BooleanArray::from(columns.extrapolated)),
+            Arc::new(Float64Array::from(columns.ephemeris_age)),
+            Arc::new(Float64Array::from(columns.accuracy)),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.writer.write(&batch)
+    }
+}
+
+impl Satellite {
+    /// Propagated states as a Parquet file, see `StateWriter`
+    #[cfg(feature = "std-fs")]
+    pub fn export_parquet(&self, path: &str, options: &ParquetOptions) -> Result<(), ParquetError> {
+        let mut writer = StateWriter::create(path, options)?;
+        writer.write_satellite(self)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+impl Constellation {
+    /// States of every propagated satellite as one Parquet table, satellite by satellite
+    #[cfg(feature = "std-fs")]
+    pub fn export_parquet(&self, path: &str, options: &ParquetOptions) -> Result<(), ParquetError> {
+        let mut writer = StateWriter::create(path, options)?;
+        for satellite in self.iter() {
+            writer.write_satellite(satellite)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use super::*;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, TimestampNanosecondType};
+    use arrow_array::Array;
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+
+    fn constellation(config: &PropagationConfig) -> Constellation {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        test_support::propagated(start, Duration::from_secs(3600), config)
+    }
+
+    /// Batches of the file and its row group count
+    fn read(path: &std::path::Path) -> (Vec<RecordBatch>, usize) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let row_groups = builder.metadata().num_row_groups();
+        let batches = builder.build().unwrap().map(Result::unwrap).collect();
+        (batches, row_groups)
+    }
+
+    fn float(batch: &RecordBatch, name: &str, row: usize) -> Option<f64> {
+        let column = batch
+            .column_by_name(name)
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        column.is_valid(row).then(|| column.value(row))
+    }
+
+    #[test]
+    fn round_trip_matches_the_states() {
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(60))
+            .with_velocity(true)
+            .with_clock(true);
+        let constellation = constellation(&config);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("states.parquet");
+        let options = ParquetOptions {
+            batch_size: 7,
+            row_group_size: 500,
+            compression: true,
+        };
+        constellation
+            .export_parquet(path.to_str().unwrap(), &options)
+            .unwrap();
+
+        let (batches, row_groups) = read(&path);
+        let expected: Vec<(SatId, StateRef<'_>)> = constellation
+            .iter()
+            .flat_map(|satellite| {
+                satellite
+                    .states
+                    .iter()
+                    .map(move |state| (satellite.id, state))
+            })
+            .collect();
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, expected.len());
+        assert_eq!(row_groups, expected.len().div_ceil(500));
+
+        let schema = batches[0].schema();
+        assert_eq!(
+            schema.field_with_name("time").unwrap().data_type(),
+            &DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into()))
+        );
+        assert_eq!(
+            schema.field_with_name("x").unwrap().data_type(),
+            &DataType::Float64
+        );
+
+        let rows = batches
+            .iter()
+            .flat_map(|batch| (0..batch.num_rows()).map(move |row| (batch, row)));
+        for ((table, row), (sat_id, state)) in rows.zip(&expected).step_by(37) {
+            let sat_ids = table.column_by_name("sat_id").unwrap().as_string::<i32>();
+            assert_eq!(sat_ids.value(row), sat_id.to_string());
+            let times = table
+                .column_by_name("time")
+                .unwrap()
+                .as_primitive::<TimestampNanosecondType>();
+            let utc = gnss::gps_seconds_to_utc(state.time());
+            assert_eq!(times.value(row), utc.timestamp_nanos_opt().unwrap());
+            assert_eq!(float(table, "gps_time", row), Some(state.time()));
+            let position = state.position();
+            assert_eq!(float(table, "x", row), Some(position.x));
+            assert_eq!(float(table, "y", row), Some(position.y));
+            assert_eq!(float(table, "z", row), Some(position.z));
+            assert_eq!(float(table, "vz", row), state.velocity().map(|v| v.z));
+            assert_eq!(float(table, "clock_bias", row), state.clock_bias());
+            let converged = table
+                .column_by_name("kepler_converged")
+                .unwrap()
+                .as_boolean();
+            assert_eq!(converged.value(row), state.kepler_converged());
+        }
+    }
+
+    #[test]
+    fn unpropagated_columns_are_null() {
+        let constellation = constellation(&PropagationConfig::new().step(Duration::from_secs(600)));
+        let satellite = constellation.get(SatId::gps(17)).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("g17.parquet");
+        satellite
+            .export_parquet(path.to_str().unwrap(), &ParquetOptions::default())
+            .unwrap();
+        let (batches, row_groups) = read(&path);
+        assert_eq!(row_groups, 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 6);
+        for name in ["vx", "vy", "vz", "clock_bias"] {
+            assert_eq!(batch.column_by_name(name).unwrap().null_count(), 6);
+        }
+        assert_eq!(batch.column_by_name("x").unwrap().null_count(), 0);
+    }
+}
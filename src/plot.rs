@@ -0,0 +1,603 @@
+//! Quick-look plots drawn with plotters. `plot_*` writes PNG or SVG depending on the file
+//! extension; the `*_svg` variants return the SVG text instead.
+
+use crate::constellation::Constellation;
+use crate::gnss::{SatId, LLA};
+use crate::positioning::Dop;
+use crate::satellite::{split_at_antimeridian, Satellite};
+use crate::visibility::SkyTrack;
+use chrono::{DateTime, Utc};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+const FONT: &str = "sans-serif";
+
+/// Image size and sampling of the plots
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlotOptions {
+    pub width: u32,
+    pub height: u32,
+    pub title: Option<String>, // Replaces the default title naming the satellite or observer
+    pub step: Duration,        // Sampling of sky tracks and DOP
+    pub mask_deg: f64,         // Elevation mask of sky tracks and DOP
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 700,
+            title: None,
+            step: Duration::from_secs(60),
+            mask_deg: 10.0,
+        }
+    }
+}
+
+/// Why a plot could not be drawn
+#[derive(Debug)]
+pub enum PlotError {
+    UnsupportedFormat(String), // File extension other than png or svg
+    NoData,
+    Drawing(String),
+}
+
+impl fmt::Display for PlotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(path) => {
+                write!(
+                    f,
+                    "cannot tell the image format of {:?}, use .png or .svg",
+                    path
+                )
+            }
+            Self::NoData => write!(f, "nothing to plot"),
+            Self::Drawing(message) => write!(f, "plotting failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PlotError {}
+
+impl<E: std::error::Error + Send + Sync> From<DrawingAreaErrorKind<E>> for PlotError {
+    fn from(error: DrawingAreaErrorKind<E>) -> Self {
+        Self::Drawing(error.to_string())
+    }
+}
+
+impl Satellite {
+    /// Ground track on an equirectangular latitude/longitude grid, broken where it
+    /// crosses the antimeridian
+    pub fn plot_ground_track(&self, path: &str, options: &PlotOptions) -> Result<(), PlotError> {
+        save(&self.ground_track_plot(options)?, path, options)
+    }
+
+    pub fn ground_track_svg(&self, options: &PlotOptions) -> Result<String, PlotError> {
+        svg(&self.ground_track_plot(options)?, options)
+    }
+
+    fn ground_track_plot(&self, options: &PlotOptions) -> Result<GroundTrackPlot, PlotError> {
+        let points: Vec<LLA> = self
+            .ground_track()
+            .into_iter()
+            .map(|(_, lla)| lla)
+            .collect();
+        if points.is_empty() {
+            return Err(PlotError::NoData);
+        }
+        Ok(GroundTrackPlot {
+            title: title(options, || format!("{} ground track", self.id)),
+            segments: split_at_antimeridian(&points),
+        })
+    }
+}
+
+impl Constellation {
+    /// Polar azimuth/elevation plot with one colored track per satellite above the mask,
+    /// broken where a satellite sets or crosses north
+    pub fn plot_skyplot(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        path: &str,
+        options: &PlotOptions,
+    ) -> Result<(), PlotError> {
+        save(
+            &self.sky_plot(observer, start, duration, options)?,
+            path,
+            options,
+        )
+    }
+
+    pub fn skyplot_svg(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        options: &PlotOptions,
+    ) -> Result<String, PlotError> {
+        svg(&self.sky_plot(observer, start, duration, options)?, options)
+    }
+
+    /// GDOP, PDOP, HDOP and VDOP over time, with gaps where fewer than four satellites
+    /// are above the mask
+    pub fn plot_dop(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        path: &str,
+        options: &PlotOptions,
+    ) -> Result<(), PlotError> {
+        save(
+            &self.dop_plot(observer, start, duration, options)?,
+            path,
+            options,
+        )
+    }
+
+    pub fn dop_svg(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        options: &PlotOptions,
+    ) -> Result<String, PlotError> {
+        svg(&self.dop_plot(observer, start, duration, options)?, options)
+    }
+
+    fn sky_plot(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        options: &PlotOptions,
+    ) -> Result<SkyPlot, PlotError> {
+        let tracks = self.skyplot(observer, start, duration, options.step, options.mask_deg);
+        if tracks.is_empty() {
+            return Err(PlotError::NoData);
+        }
+        Ok(SkyPlot {
+            title: title(options, || {
+                format!(
+                    "Sky at {:.3}°, {:.3}° from {}",
+                    observer.latitude,
+                    observer.longitude,
+                    start.format("%Y-%m-%d %H:%M UTC")
+                )
+            }),
+            tracks,
+        })
+    }
+
+    fn dop_plot(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        options: &PlotOptions,
+    ) -> Result<DopPlot, PlotError> {
+        let series: Vec<(f64, Option<Dop>)> = self
+            .dop_series(observer, start, duration, options.step, options.mask_deg)
+            .into_iter()
+            .map(|(epoch, dop)| ((epoch - start).num_milliseconds() as f64 / 3.6e6, dop))
+            .collect();
+        if series.iter().all(|(_, dop)| dop.is_none()) {
+            return Err(PlotError::NoData);
+        }
+        Ok(DopPlot {
+            title: title(options, || {
+                format!(
+                    "DOP at {:.3}°, {:.3}°, mask {}°",
+                    observer.latitude, observer.longitude, options.mask_deg
+                )
+            }),
+            start,
+            series,
+        })
+    }
+}
+
+/// Something that draws itself onto any plotters backend
+trait Plot {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError>;
+}
+
+fn save(plot: &impl Plot, path: &str, options: &PlotOptions) -> Result<(), PlotError> {
+    let size = (options.width, options.height);
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("png") => {
+            let root = BitMapBackend::new(path, size).into_drawing_area();
+            plot.draw(&root)?;
+            root.present()?;
+        }
+        Some("svg") => {
+            let root = SVGBackend::new(path, size).into_drawing_area();
+            plot.draw(&root)?;
+            root.present()?;
+        }
+        _ => return Err(PlotError::UnsupportedFormat(path.to_string())),
+    }
+    Ok(())
+}
+
+fn svg(plot: &impl Plot, options: &PlotOptions) -> Result<String, PlotError> {
+    let mut text = String::new();
+    {
+        let root =
+            SVGBackend::with_string(&mut text, (options.width, options.height)).into_drawing_area();
+        plot.draw(&root)?;
+        root.present()?;
+    }
+    Ok(text)
+}
+
+fn title(options: &PlotOptions, default: impl FnOnce() -> String) -> String {
+    options.title.clone().unwrap_or_else(default)
+}
+
+struct GroundTrackPlot {
+    title: String,
+    segments: Vec<Vec<LLA>>,
+}
+
+impl Plot for GroundTrackPlot {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError> {
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(root)
+            .caption(&self.title, (FONT, 22))
+            .margin(15)
+            .x_label_area_size(40)
+            .y_label_area_size(50)
+            .build_cartesian_2d(-180f64..180f64, -90f64..90f64)?;
+        chart
+            .configure_mesh()
+            .x_labels(13)
+            .y_labels(7)
+            .x_desc("Longitude (°)")
+            .y_desc("Latitude (°)")
+            .draw()?;
+        for segment in &self.segments {
+            chart.draw_series(LineSeries::new(
+                segment.iter().map(|lla| (lla.longitude, lla.latitude)),
+                BLUE.stroke_width(2),
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+struct SkyPlot {
+    title: String,
+    tracks: BTreeMap<SatId, Vec<SkyTrack>>,
+}
+
+/// Plot coordinates of an azimuth and elevation: north up, east right, zenith at the
+/// center and the horizon at radius 90
+fn polar(azimuth: f64, elevation: f64) -> (f64, f64) {
+    let radius = 90.0 - elevation;
+    let (sin, cos) = azimuth.to_radians().sin_cos();
+    (radius * sin, radius * cos)
+}
+
+impl Plot for SkyPlot {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError> {
+        root.fill(&WHITE)?;
+        let root = root.titled(&self.title, (FONT, 22))?;
+        // Square plotting area so circles stay round
+        let (width, height) = root.dim_in_pixel();
+        let side = width.min(height);
+        let area = root.shrink(((width - side) / 2, (height - side) / 2), (side, side));
+        let mut chart = ChartBuilder::on(&area)
+            .margin(20)
+            .build_cartesian_2d(-100f64..100f64, -100f64..100f64)?;
+
+        let grid = BLACK.mix(0.3);
+        let centered = TextStyle::from((FONT, 15)).pos(Pos::new(HPos::Center, VPos::Center));
+        for elevation in [0.0, 30.0, 60.0] {
+            chart.draw_series(LineSeries::new(
+                (0..=360).map(|azimuth| polar(azimuth as f64, elevation)),
+                grid,
+            ))?;
+            chart.draw_series(std::iter::once(Text::new(
+                format!("{}°", elevation),
+                polar(45.0, elevation + 3.0),
+                centered.clone(),
+            )))?;
+        }
+        for azimuth in (0..360).step_by(30) {
+            let azimuth = azimuth as f64;
+            chart.draw_series(LineSeries::new([(0.0, 0.0), polar(azimuth, 0.0)], grid))?;
+        }
+        for (label, azimuth) in [("N", 0.0), ("E", 90.0), ("S", 180.0), ("W", 270.0)] {
+            chart.draw_series(std::iter::once(Text::new(
+                label,
+                polar(azimuth, -6.0),
+                centered.clone(),
+            )))?;
+        }
+
+        for (index, (sat_id, tracks)) in self.tracks.iter().enumerate() {
+            let color = Palette99::pick(index);
+            for track in tracks {
+                chart.draw_series(LineSeries::new(
+                    track
+                        .iter()
+                        .map(|&(_, azimuth, elevation)| polar(azimuth, elevation)),
+                    color.stroke_width(2),
+                ))?;
+            }
+            // Label where the satellite was last seen
+            if let Some(&(_, azimuth, elevation)) = tracks.last().and_then(|track| track.last()) {
+                chart.draw_series(std::iter::once(Text::new(
+                    sat_id.to_string(),
+                    polar(azimuth, elevation),
+                    TextStyle::from((FONT, 13)).color(&color),
+                )))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name, accessor and line color of one DOP curve
+type DopComponent = (&'static str, fn(&Dop) -> f64, RGBColor);
+
+struct DopPlot {
+    title: String,
+    start: DateTime<Utc>,
+    series: Vec<(f64, Option<Dop>)>, // Hours since start
+}
+
+impl Plot for DopPlot {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError> {
+        root.fill(&WHITE)?;
+        let hours = self
+            .series
+            .last()
+            .map_or(0.0, |(hours, _)| *hours)
+            .max(1e-3);
+        let highest = self
+            .series
+            .iter()
+            .filter_map(|(_, dop)| dop.map(|dop| dop.gdop))
+            .filter(|gdop| gdop.is_finite())
+            .fold(1.0, f64::max);
+        let mut chart = ChartBuilder::on(root)
+            .caption(&self.title, (FONT, 22))
+            .margin(15)
+            .x_label_area_size(40)
+            .y_label_area_size(50)
+            .build_cartesian_2d(0f64..hours, 0f64..highest * 1.1)?;
+        chart
+            .configure_mesh()
+            .x_desc(format!(
+                "Hours since {}",
+                self.start.format("%Y-%m-%d %H:%M UTC")
+            ))
+            .y_desc("DOP")
+            .draw()?;
+
+        let components: [DopComponent; 4] = [
+            ("GDOP", |dop| dop.gdop, BLACK),
+            ("PDOP", |dop| dop.pdop, BLUE),
+            ("HDOP", |dop| dop.hdop, GREEN),
+            ("VDOP", |dop| dop.vdop, RED),
+        ];
+        for (name, component, color) in components {
+            // Runs of epochs with a solution, drawn as separate lines
+            let mut runs: Vec<Vec<(f64, f64)>> = vec![Vec::new()];
+            for (hours, dop) in &self.series {
+                match dop {
+                    Some(dop) => runs.last_mut().unwrap().push((*hours, component(dop))),
+                    None if runs.last().is_some_and(|run| !run.is_empty()) => runs.push(Vec::new()),
+                    None => {}
+                }
+            }
+            for (k, run) in runs.into_iter().filter(|run| !run.is_empty()).enumerate() {
+                let series = chart.draw_series(LineSeries::new(run, color.stroke_width(2)))?;
+                if k == 0 {
+                    series.label(name).legend(move |(x, y)| {
+                        PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2))
+                    });
+                }
+            }
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{constellation, propagated_day, start};
+
+    fn observer() -> LLA {
+        LLA::new(34.0689, -118.4452, 100.0) // Los Angeles
+    }
+
+    /// Width and height of the root svg element
+    fn svg_size(svg: &str) -> (u32, u32) {
+        let root = &svg[svg.find("<svg").unwrap()..];
+        let attribute = |name: &str| -> u32 {
+            let start = root.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+            root[start..start + root[start..].find('"').unwrap()]
+                .parse()
+                .unwrap()
+        };
+        (attribute("width"), attribute("height"))
+    }
+
+    /// Contents of the text elements
+    fn texts(svg: &str) -> Vec<&str> {
+        svg.split("<text")
+            .skip(1)
+            .map(|element| {
+                let start = element.find('>').unwrap() + 1;
+                element[start..element.find("</text>").unwrap()].trim()
+            })
+            .collect()
+    }
+
+    /// Polylines drawn in the given color, as plotters writes it
+    fn polylines(svg: &str, color: &str) -> usize {
+        svg.split("<polyline")
+            .skip(1)
+            .filter(|element| element[..element.find("/>").unwrap()].contains(color))
+            .count()
+    }
+
+    #[test]
+    fn ground_track_has_one_line_per_segment() {
+        let constellation = propagated_day();
+        let satellite = constellation.get(SatId::gps(17)).unwrap();
+        let options = PlotOptions {
+            width: 800,
+            height: 400,
+            ..PlotOptions::default()
+        };
+        let svg = satellite.ground_track_svg(&options).unwrap();
+        assert_eq!(svg_size(&svg), (800, 400));
+        assert!(texts(&svg).contains(&"G17 ground track"));
+        let points: Vec<LLA> = satellite
+            .ground_track()
+            .into_iter()
+            .map(|(_, lla)| lla)
+            .collect();
+        let segments = split_at_antimeridian(&points);
+        assert!(segments.len() > 1);
+        assert_eq!(polylines(&svg, "#0000FF"), segments.len());
+        // Every point of a segment, including those added at the antimeridian, is a vertex
+        let vertices: usize = svg
+            .split("<polyline")
+            .skip(1)
+            .filter(|element| element[..element.find("/>").unwrap()].contains("#0000FF"))
+            .map(|element| {
+                let start = element.find("points=\"").unwrap() + 8;
+                element[start..start + element[start..].find('"').unwrap()]
+                    .split_whitespace()
+                    .count()
+            })
+            .sum();
+        assert_eq!(vertices, segments.iter().map(Vec::len).sum::<usize>());
+    }
+
+    #[test]
+    fn skyplot_draws_grid_tracks_and_labels() {
+        let constellation = propagated_day();
+        let options = PlotOptions {
+            title: Some("Sky over LA".to_string()),
+            step: Duration::from_secs(600),
+            ..PlotOptions::default()
+        };
+        let duration = Duration::from_secs(6 * 3600);
+        let svg = constellation
+            .skyplot_svg(&observer(), start(), duration, &options)
+            .unwrap();
+        assert_eq!(svg_size(&svg), (1200, 700));
+        assert!(texts(&svg).contains(&"Sky over LA"));
+        let tracks = constellation.skyplot(&observer(), start(), duration, options.step, 10.0);
+        let lines: usize = tracks.values().map(Vec::len).sum();
+        // Three elevation circles and twelve azimuth spokes besides the tracks
+        assert_eq!(svg.matches("<polyline").count(), 3 + 12 + lines);
+        for sat_id in tracks.keys() {
+            assert!(texts(&svg).contains(&sat_id.to_string().as_str()));
+        }
+        for label in ["N", "E", "S", "W", "0°", "30°", "60°"] {
+            assert!(texts(&svg).contains(&label), "{}", label);
+        }
+    }
+
+    #[test]
+    fn dop_plot_has_four_labelled_curves() {
+        let constellation = propagated_day();
+        let svg = constellation
+            .dop_svg(
+                &observer(),
+                start(),
+                Duration::from_secs(3 * 3600),
+                &PlotOptions::default(),
+            )
+            .unwrap();
+        for name in ["GDOP", "PDOP", "HDOP", "VDOP"] {
+            assert!(texts(&svg).contains(&name), "{} legend", name);
+        }
+        for color in ["#0000FF", "#00FF00", "#FF0000"] {
+            assert!(polylines(&svg, color) >= 1, "{} curve", color);
+        }
+        assert!(texts(&svg).contains(&"Hours since 2023-06-12 00:00 UTC"));
+    }
+
+    #[test]
+    fn files_take_their_format_from_the_extension() {
+        let constellation = propagated_day();
+        let satellite = constellation.get(SatId::gps(17)).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let options = PlotOptions {
+            width: 640,
+            height: 480,
+            ..PlotOptions::default()
+        };
+
+        let png = dir.path().join("track.PNG");
+        satellite
+            .plot_ground_track(png.to_str().unwrap(), &options)
+            .unwrap();
+        let bytes = std::fs::read(&png).unwrap();
+        assert_eq!(bytes[..8], *b"\x89PNG\r\n\x1a\n");
+        let width = u32::from_be_bytes(bytes[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(bytes[20..24].try_into().unwrap());
+        assert_eq!((width, height), (640, 480));
+
+        let svg = dir.path().join("track.svg");
+        satellite
+            .plot_ground_track(svg.to_str().unwrap(), &options)
+            .unwrap();
+        let text = std::fs::read_to_string(&svg).unwrap();
+        assert_eq!(svg_size(&text), (640, 480));
+
+        let jpeg = dir.path().join("track.jpg");
+        let error = satellite
+            .plot_ground_track(jpeg.to_str().unwrap(), &options)
+            .unwrap_err();
+        assert!(matches!(error, PlotError::UnsupportedFormat(_)));
+        assert!(!jpeg.exists());
+    }
+
+    #[test]
+    fn empty_inputs_are_no_data() {
+        let satellite = Satellite::new(5, String::new());
+        let error = satellite
+            .ground_track_svg(&PlotOptions::default())
+            .unwrap_err();
+        assert!(matches!(error, PlotError::NoData));
+
+        let unpropagated = constellation();
+        let hour = Duration::from_secs(3600);
+        let options = PlotOptions::default();
+        let error = unpropagated
+            .skyplot_svg(&observer(), start(), hour, &options)
+            .unwrap_err();
+        assert!(matches!(error, PlotError::NoData));
+        let error = unpropagated
+            .dop_svg(&observer(), start(), hour, &options)
+            .unwrap_err();
+        assert!(matches!(error, PlotError::NoData));
+    }
+}
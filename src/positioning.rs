@@ -0,0 +1,725 @@
+#[cfg(feature = "ndarray")]
+use crate::alignment::{AlignedObservation, EpochAligner};
+#[cfg(feature = "ndarray")]
+use crate::constellation::Constellation;
+use crate::corrections::Corrections;
+#[cfg(feature = "ndarray")]
+use crate::gnss::{self, ENU};
+use crate::gnss::{SatId, ECEF};
+#[cfg(feature = "ndarray")]
+use crate::linalg;
+#[cfg(feature = "ndarray")]
+use crate::pseudorange::PseudorangeModel;
+#[cfg(feature = "ndarray")]
+use crate::residuals::{ResidualRecord, Residuals};
+#[cfg(feature = "ndarray")]
+use crate::satellite::PropagationConfig;
+use crate::satellite::PropagationError;
+#[cfg(feature = "ndarray")]
+use crate::signal::Signal;
+#[cfg(feature = "ndarray")]
+use crate::tides;
+#[cfg(feature = "ndarray")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "ndarray")]
+use ndarray::{Array1, Array2, Axis};
+use std::fmt;
+
+#[cfg(feature = "ndarray")]
+const SIGMA_EPSILON_C: f64 = 1.61e4; // C/A code SIGMA-epsilon constant, m² Hz
+
+/// A code measurement to one satellite at the solution epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PseudorangeObservation {
+    pub sat_id: SatId,
+    pub pseudorange: f64,     // m
+    pub snr: Option<f64>,     // Carrier-to-noise density, dB-Hz
+    pub doppler: Option<f64>, // L1 Doppler shift, Hz
+}
+
+/// Elevation-dependent standard deviation of a code observation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Weighting {
+    #[default]
+    Uniform,
+    /// sigma0 / sin(el)
+    Sine { sigma0: f64 },
+    /// a * (1 + b * exp(-el / el0)), el0 in degrees
+    Exponential { a: f64, b: f64, el0: f64 },
+}
+
+impl Weighting {
+    /// Standard deviation in meters at an elevation in degrees
+    pub fn sigma(&self, elevation_deg: f64) -> f64 {
+        match *self {
+            Self::Uniform => 1.0,
+            Self::Sine { sigma0 } => sigma0 / elevation_deg.to_radians().sin().max(0.05),
+            Self::Exponential { a, b, el0 } => a * (1.0 + b * (-elevation_deg / el0).exp()),
+        }
+    }
+}
+
+/// Options for `Constellation::solve_spp`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SppOptions {
+    pub initial_position: ECEF,
+    pub max_iter: u32,
+    pub tolerance: f64, // Position/clock update below which the solution has converged, m
+    pub elevation_mask: f64, // Degrees, applied once the position is near the Earth's surface
+    pub weighting: Weighting, // Likewise only once near the surface
+    pub include_ura: bool, // Add the satellite's URA-derived accuracy to each variance
+    pub include_snr: bool, // Add the SIGMA-epsilon C/N0 term where an SNR is given
+    pub raim_false_alarm: Option<f64>, // Enables RAIM with this false-alarm probability
+    pub raim_max_exclusions: usize,
+    pub corrections: Corrections, // Atmospheric delays, likewise only once near the surface
+    pub remove_solid_tides: bool, // Report tide-free positions, as station coordinates are published
+}
+
+impl Default for SppOptions {
+    fn default() -> Self {
+        Self {
+            initial_position: ECEF::default(),
+            max_iter: 10,
+            tolerance: 1e-4,
+            elevation_mask: 0.0,
+            weighting: Weighting::default(),
+            include_ura: false,
+            include_snr: false,
+            raim_false_alarm: None,
+            raim_max_exclusions: 1,
+            corrections: Corrections::default(),
+            remove_solid_tides: false,
+        }
+    }
+}
+
+impl SppOptions {
+    /// Variance of one observation in m², from elevation, satellite accuracy and C/N0
+    #[cfg(feature = "ndarray")]
+    fn variance(&self, obs: &PseudorangeObservation, model: &PseudorangeModel) -> f64 {
+        let mut variance = self.weighting.sigma(model.aer.elevation).powi(2);
+        if self.include_ura {
+            variance += model.satellite_state.accuracy.unwrap_or(0.0).powi(2);
+        }
+        if let (true, Some(snr)) = (self.include_snr, obs.snr) {
+            variance += SIGMA_EPSILON_C * 10f64.powf(-snr / 10.0);
+        }
+        variance
+    }
+}
+
+/// Dilution of precision from the geometry of a solution
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dop {
+    pub gdop: f64,
+    pub pdop: f64,
+    pub hdop: f64,
+    pub vdop: f64,
+    pub tdop: f64,
+}
+
+#[cfg(feature = "ndarray")]
+impl Dop {
+    /// DOP from the unweighted cofactor matrix of (x, y, z, c*dt), horizontal and vertical
+    /// components taken in the local frame at `position`
+    pub fn from_cofactor(cofactor: &Array2<f64>, position: &ECEF) -> Self {
+        let lla = position.to_lla();
+        let (sin_lat, cos_lat) = lla.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = lla.longitude.to_radians().sin_cos();
+        let rotation = [
+            [-sin_lon, cos_lon, 0.0],
+            [-sin_lat * cos_lon, -sin_lat * sin_lon, cos_lat],
+            [cos_lat * cos_lon, cos_lat * sin_lon, sin_lat],
+        ];
+        // Diagonal of R Q Rᵀ for the east, north and up rows
+        let local = |row: &[f64; 3]| {
+            let mut sum = 0.0;
+            for i in 0..3 {
+                for j in 0..3 {
+                    sum += row[i] * cofactor[[i, j]] * row[j];
+                }
+            }
+            sum
+        };
+        let (q_e, q_n, q_u) = (
+            local(&rotation[0]),
+            local(&rotation[1]),
+            local(&rotation[2]),
+        );
+        let q_t = cofactor[[3, 3]];
+        Self {
+            gdop: (q_e + q_n + q_u + q_t).sqrt(),
+            pdop: (q_e + q_n + q_u).sqrt(),
+            hdop: (q_e + q_n).sqrt(),
+            vdop: q_u.sqrt(),
+            tdop: q_t.sqrt(),
+        }
+    }
+}
+
+/// Receiver velocity and clock drift from one epoch of Dopplers
+#[cfg(feature = "ndarray")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VelocitySolution {
+    pub velocity: ECEF,
+    pub velocity_enu: ENU, // At the position the solution was computed for
+    pub clock_drift: f64,  // Receiver clock drift, s/s
+    pub residuals: Vec<(SatId, f64)>, // Observed minus modeled range rate, m/s
+    pub covariance: Array2<f64>, // Of (vx, vy, vz, c*dt_dot), scaled like `SppSolution`
+}
+
+/// Receiver position and clock from one epoch of pseudoranges
+#[cfg(feature = "ndarray")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SppSolution {
+    pub position: ECEF,
+    pub clock_bias: f64, // Receiver clock offset, s
+    pub iterations: u32,
+    pub residuals: Vec<(SatId, f64)>, // Observed minus modeled after the last update, m
+    pub covariance: Array2<f64>,      // Of (x, y, z, c*dt), scaled by the a posteriori variance
+    pub dop: Dop,
+    pub excluded: Vec<SatId>, // Removed by RAIM, in order of exclusion
+    pub test_statistic: Option<f64>, // Weighted sum of squared residuals, with RAIM enabled
+    pub post_fit: Residuals,  // `residuals` with elevation, azimuth and weight
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositioningError {
+    TooFewSatellites { usable: usize },
+    NotConverged { iterations: u32 },
+    SingularGeometry,
+    Propagation(SatId, PropagationError),
+}
+
+impl fmt::Display for PositioningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooFewSatellites { usable } => {
+                write!(f, "{} usable satellites, at least 4 needed", usable)
+            }
+            Self::NotConverged { iterations } => {
+                write!(f, "no convergence after {} iterations", iterations)
+            }
+            Self::SingularGeometry => write!(f, "satellite geometry is singular"),
+            Self::Propagation(sat_id, err) => write!(f, "{}: {}", sat_id, err),
+        }
+    }
+}
+
+impl std::error::Error for PositioningError {}
+
+#[cfg(feature = "ndarray")]
+impl Constellation {
+    /// Single point positioning by iterated linearized least squares on (x, y, z, c*dt).
+    /// The epoch is the receiver's time tag; satellites without ephemeris are skipped.
+    ///
+    /// With RAIM enabled, a solution whose weighted residuals fail the chi-square test has
+    /// the satellite with the largest standardized residual removed and is solved again,
+    /// as long as at least 5 satellites would remain.
+    pub fn solve_spp(
+        &self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+        options: &SppOptions,
+    ) -> Result<SppSolution, PositioningError> {
+        let mut observations = observations.to_vec();
+        let mut excluded = Vec::new();
+        loop {
+            let (mut solution, standardized) = self.least_squares(epoch, &observations, options)?;
+            solution.excluded = excluded.clone();
+            let Some(false_alarm) = options.raim_false_alarm else {
+                return Ok(solution);
+            };
+            let dof = solution.residuals.len() - 4;
+            let statistic = standardized.statistic;
+            solution.test_statistic = Some(statistic);
+            let passed = dof == 0 || statistic <= chi_square_threshold(dof, false_alarm);
+            if passed
+                || excluded.len() >= options.raim_max_exclusions
+                || solution.residuals.len() < 6
+            {
+                return Ok(solution);
+            }
+            let worst = standardized
+                .residuals
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+                .map(|(idx, _)| solution.residuals[idx].0)
+                .unwrap();
+            excluded.push(worst);
+            observations.retain(|obs| obs.sat_id != worst);
+        }
+    }
+
+    fn least_squares(
+        &self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+        options: &SppOptions,
+    ) -> Result<(SppSolution, StandardizedResiduals), PositioningError> {
+        let mut aligner = EpochAligner::new(self, PropagationConfig::new());
+        let mut position = options.initial_position;
+        let mut clock_m = 0.0;
+        for iteration in 1..=options.max_iter {
+            let aligned = aligner.align(
+                epoch,
+                observations,
+                &position,
+                Some(clock_m / gnss::C_LIGHT),
+            );
+            // Elevations are meaningless until the position has moved off the Earth's centre
+            let near_surface = position.norm() > 0.5 * gnss::WGS84_A;
+            let receiver = position.to_lla();
+            let mut rows = Vec::new();
+            let mut weights = Vec::new();
+            for AlignedObservation {
+                observation: obs,
+                mut model,
+                ..
+            } in aligned.observations
+            {
+                if near_surface && model.aer.elevation < options.elevation_mask {
+                    continue;
+                }
+                let variance = match near_surface {
+                    true => {
+                        options.corrections.apply(obs.sat_id, &receiver, &mut model);
+                        options.variance(&obs, &model)
+                    }
+                    false => 1.0,
+                };
+                rows.push((obs.sat_id, obs.pseudorange - model.total() - clock_m, model));
+                weights.push(1.0 / variance);
+            }
+            if rows.len() < 4 {
+                return Err(PositioningError::TooFewSatellites { usable: rows.len() });
+            }
+
+            let satellites: Vec<ECEF> = rows.iter().map(|row| row.2.satellite_position).collect();
+            let design = design_matrix(&position, &satellites);
+            let prefit = Array1::from_iter(rows.iter().map(|(_, residual, _)| *residual));
+            let weights = Array1::from(weights);
+            let weighted = &design * &weights.view().insert_axis(Axis(1));
+            let normal_inverse = linalg::invert(&design.t().dot(&weighted))
+                .ok_or(PositioningError::SingularGeometry)?;
+            let update = normal_inverse.dot(&weighted.t().dot(&prefit));
+            position = position + ECEF::new(update[0], update[1], update[2]);
+            clock_m += update[3];
+
+            if update.iter().map(|v| v * v).sum::<f64>().sqrt() < options.tolerance {
+                let postfit = &prefit - &design.dot(&update);
+                let dof = rows.len() - 4;
+                let variance = match dof {
+                    0 => 1.0,
+                    _ => (&postfit * &weights).dot(&postfit) / dof as f64,
+                };
+                // DOP describes the geometry alone, so it uses the unweighted cofactor
+                let cofactor = linalg::invert(&design.t().dot(&design))
+                    .ok_or(PositioningError::SingularGeometry)?;
+                // Residual cofactor diagonal: 1/w - h N⁻¹ hᵀ
+                let standardized = StandardizedResiduals {
+                    statistic: (&postfit * &weights).dot(&postfit),
+                    residuals: (0..rows.len())
+                        .map(|i| {
+                            let h = design.row(i);
+                            let q = 1.0 / weights[i] - h.dot(&normal_inverse.dot(&h));
+                            postfit[i] / q.max(f64::EPSILON).sqrt()
+                        })
+                        .collect(),
+                };
+                let mut post_fit = Residuals::new();
+                for (i, (sat_id, _, model)) in rows.iter().enumerate() {
+                    let record = ResidualRecord {
+                        epoch,
+                        residual: postfit[i],
+                        elevation: model.aer.elevation,
+                        azimuth: model.aer.azimuth,
+                        weight: weights[i],
+                    };
+                    post_fit.push(*sat_id, record);
+                }
+                let position = match options.remove_solid_tides {
+                    true => position - tides::solid_earth_tide(&position, epoch),
+                    false => position,
+                };
+                let solution = SppSolution {
+                    position,
+                    clock_bias: clock_m / gnss::C_LIGHT,
+                    iterations: iteration,
+                    residuals: rows
+                        .iter()
+                        .map(|(sat_id, _, _)| *sat_id)
+                        .zip(postfit)
+                        .collect(),
+                    covariance: &normal_inverse * variance,
+                    dop: Dop::from_cofactor(&cofactor, &position),
+                    excluded: vec![],
+                    test_statistic: None,
+                    post_fit,
+                };
+                return Ok((solution, standardized));
+            }
+        }
+        Err(PositioningError::NotConverged {
+            iterations: options.max_iter,
+        })
+    }
+
+    /// Receiver velocity by least squares on the Dopplers of an epoch, at a known position
+    /// and clock bias (typically from `solve_spp`). Observations without a Doppler are skipped.
+    pub fn solve_velocity(
+        &self,
+        epoch: DateTime<Utc>,
+        observations: &[PseudorangeObservation],
+        fix: &SppSolution,
+        options: &SppOptions,
+    ) -> Result<VelocitySolution, PositioningError> {
+        let with_doppler: Vec<PseudorangeObservation> = observations
+            .iter()
+            .filter(|obs| obs.doppler.is_some())
+            .copied()
+            .collect();
+        let mut aligner = EpochAligner::new(self, PropagationConfig::new().with_velocity(true));
+        let aligned = aligner.align(epoch, &with_doppler, &fix.position, Some(fix.clock_bias));
+        let mut sat_ids = Vec::new();
+        let mut satellites = Vec::new();
+        let mut observed = Vec::new();
+        let mut weights = Vec::new();
+        for aligned in &aligned.observations {
+            let model = &aligned.model;
+            if model.aer.elevation < options.elevation_mask {
+                continue;
+            }
+            let Some(satellite_motion) = aligned.satellite_velocity.and_then(|velocity| {
+                gnss::range_rate(
+                    &fix.position,
+                    &ECEF::default(),
+                    &model.satellite_position,
+                    &velocity,
+                )
+            }) else {
+                continue;
+            };
+            // Observed range rate minus the part the receiver does not influence
+            let obs = &aligned.observation;
+            let range_rate = -obs.doppler.unwrap_or_default() * Signal::GpsL1.wavelength();
+            let modeled = satellite_motion - gnss::C_LIGHT * aligned.satellite_clock_drift;
+            sat_ids.push(obs.sat_id);
+            satellites.push(model.satellite_position);
+            observed.push(range_rate - modeled);
+            weights.push(1.0 / options.weighting.sigma(model.aer.elevation).powi(2));
+        }
+        if satellites.len() < 4 {
+            return Err(PositioningError::TooFewSatellites {
+                usable: satellites.len(),
+            });
+        }
+
+        let design = design_matrix(&fix.position, &satellites);
+        let observed = Array1::from(observed);
+        let weights = Array1::from(weights);
+        let weighted = &design * &weights.view().insert_axis(Axis(1));
+        let normal_inverse =
+            linalg::invert(&design.t().dot(&weighted)).ok_or(PositioningError::SingularGeometry)?;
+        let estimate = normal_inverse.dot(&weighted.t().dot(&observed));
+        let postfit = &observed - &design.dot(&estimate);
+        let dof = satellites.len() - 4;
+        let variance = match dof {
+            0 => 1.0,
+            _ => (&postfit * &weights).dot(&postfit) / dof as f64,
+        };
+        let velocity = ECEF::new(estimate[0], estimate[1], estimate[2]);
+        Ok(VelocitySolution {
+            velocity,
+            velocity_enu: fix.position.to_lla().rotate_to_enu(&velocity),
+            clock_drift: estimate[3] / gnss::C_LIGHT,
+            residuals: sat_ids.into_iter().zip(postfit).collect(),
+            covariance: &normal_inverse * variance,
+        })
+    }
+}
+
+#[cfg(feature = "ndarray")]
+struct StandardizedResiduals {
+    statistic: f64,      // vᵀ W v
+    residuals: Vec<f64>, // Each residual over its own standard deviation
+}
+
+/// Chi-square quantile for a false-alarm probability (Wilson-Hilferty approximation)
+pub fn chi_square_threshold(dof: usize, false_alarm: f64) -> f64 {
+    let k = dof as f64;
+    let z = normal_quantile(1.0 - false_alarm);
+    let term = 2.0 / (9.0 * k);
+    k * (1.0 - term + z * term.sqrt()).powi(3)
+}
+
+/// Inverse of the standard normal CDF (Acklam's rational approximation, ~1e-9 relative)
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Partial derivatives of each modeled range with respect to (x, y, z, c*dt), which are
+/// also those of each range rate with respect to (vx, vy, vz, c*dt_dot)
+#[cfg(feature = "ndarray")]
+fn design_matrix(position: &ECEF, satellites: &[ECEF]) -> Array2<f64> {
+    let mut design = Array2::zeros((satellites.len(), 4));
+    for (i, satellite) in satellites.iter().enumerate() {
+        // A coincident satellite leaves a zero row, which shows up as singular geometry
+        let unit = gnss::unit_line_of_sight(position, satellite).unwrap_or_default();
+        design[[i, 0]] = -unit.x;
+        design[[i, 1]] = -unit.y;
+        design[[i, 2]] = -unit.z;
+        design[[i, 3]] = 1.0;
+    }
+    design
+}
+
+#[cfg(all(test, feature = "ndarray"))]
+mod tests {
+    use super::*;
+    use crate::klobuchar::Klobuchar;
+    use crate::test_support::constellation;
+    use crate::test_support::epochs;
+    use crate::test_support::station;
+    use crate::troposphere::Saastamoinen;
+
+    /// The fixture's ionosphere and troposphere models, with a 10° mask
+    fn options() -> SppOptions {
+        SppOptions {
+            elevation_mask: 10.0,
+            corrections: Corrections {
+                troposphere: Some(Saastamoinen::new()),
+                klobuchar: Some(Klobuchar::new(
+                    [1.8626e-08, 1.4901e-08, -1.1921e-07, -5.9605e-08],
+                    [1.2698e+05, 0.0, -1.9661e+05, -6.5536e+04],
+                )),
+                ..Corrections::default()
+            },
+            ..SppOptions::default()
+        }
+    }
+
+    #[test]
+    fn spp_finds_the_station_in_simulated_observations() {
+        let constellation = constellation();
+        let (epoch, observations) = &epochs()[0];
+        let solution = constellation
+            .solve_spp(*epoch, observations, &options())
+            .unwrap();
+        let error = solution.position.distance_to(&station());
+        assert!(error < 5.0, "{} m", error);
+        // The simulated receiver clock runs 125 µs ahead
+        assert!((solution.clock_bias - 1.25e-4).abs() < 3e-8);
+        assert_eq!(solution.residuals.len(), observations.len());
+        assert!(solution.iterations > 2 && solution.iterations <= 10);
+        assert!(solution.dop.pdop > 1.0 && solution.dop.pdop < 3.0);
+        assert!(solution.covariance[[0, 0]] > 0.0);
+    }
+
+    #[test]
+    fn spp_reports_too_few_satellites_and_no_convergence() {
+        let constellation = constellation();
+        let (epoch, observations) = &epochs()[0];
+        assert_eq!(
+            constellation.solve_spp(*epoch, &observations[..3], &options()),
+            Err(PositioningError::TooFewSatellites { usable: 3 })
+        );
+        let options = SppOptions {
+            max_iter: 2,
+            ..options()
+        };
+        assert_eq!(
+            constellation.solve_spp(*epoch, observations, &options),
+            Err(PositioningError::NotConverged { iterations: 2 })
+        );
+    }
+
+    #[test]
+    fn down_weighting_a_biased_low_satellite_reduces_the_error() {
+        let constellation = constellation();
+        let (epoch, mut observations) = epochs().swap_remove(0);
+        let fix = constellation
+            .solve_spp(epoch, &observations, &options())
+            .unwrap();
+        let lowest = fix
+            .post_fit
+            .iter()
+            .map(|(sat_id, record)| (sat_id, record.elevation))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        assert!(lowest.1 < 20.0, "{:?}", lowest);
+        for obs in observations.iter_mut().filter(|obs| obs.sat_id == lowest.0) {
+            obs.pseudorange += 20.0;
+        }
+
+        let solve = |weighting| {
+            let options = SppOptions {
+                weighting,
+                ..options()
+            };
+            constellation
+                .solve_spp(epoch, &observations, &options)
+                .unwrap()
+        };
+        let uniform = solve(Weighting::Uniform);
+        let sine = solve(Weighting::Sine { sigma0: 0.3 });
+        let exponential = solve(Weighting::Exponential {
+            a: 0.3,
+            b: 10.0,
+            el0: 10.0,
+        });
+        let error = |solution: &SppSolution| solution.position.distance_to(&station());
+        assert!(error(&uniform) > 10.0, "{}", error(&uniform));
+        assert!(error(&sine) < 0.5 * error(&uniform), "{}", error(&sine));
+        assert!(error(&exponential) < 0.5 * error(&uniform));
+        // The weights also reach the post-fit residuals and the covariance
+        let weight = |sat_id| sine.post_fit.records(sat_id)[0].weight;
+        assert!(weight(lowest.0) < weight(17.into()));
+        assert_ne!(sine.covariance, uniform.covariance);
+    }
+
+    #[test]
+    fn simulated_static_receiver_has_near_zero_velocity() {
+        let constellation = constellation();
+        for (epoch, observations) in epochs().into_iter().step_by(10) {
+            let fix = constellation
+                .solve_spp(epoch, &observations, &options())
+                .unwrap();
+            let velocity = constellation
+                .solve_velocity(epoch, &observations, &fix, &options())
+                .unwrap();
+            assert!(velocity.velocity.norm() < 0.03, "{:?}", velocity.velocity);
+            let enu = velocity.velocity_enu;
+            let horizontal = (enu.east.powi(2) + enu.north.powi(2)).sqrt();
+            assert!(horizontal < 0.02 && enu.up.abs() < 0.03, "{:?}", enu);
+            // The simulated receiver clock drifts at 2 ns/s
+            assert!((velocity.clock_drift - 2e-9).abs() < 1e-10);
+            assert_eq!(velocity.residuals.len(), observations.len());
+        }
+    }
+
+    #[test]
+    fn velocity_needs_four_dopplers() {
+        let constellation = constellation();
+        let (epoch, mut observations) = epochs().swap_remove(0);
+        let fix = constellation
+            .solve_spp(epoch, &observations, &options())
+            .unwrap();
+        for obs in observations.iter_mut().skip(3) {
+            obs.doppler = None;
+        }
+        assert_eq!(
+            constellation.solve_velocity(epoch, &observations, &fix, &options()),
+            Err(PositioningError::TooFewSatellites { usable: 3 })
+        );
+    }
+
+    #[test]
+    fn raim_excludes_a_100_m_bias() {
+        let constellation = constellation();
+        let (epoch, mut observations) = epochs().swap_remove(0);
+        let options = SppOptions {
+            weighting: Weighting::Sine { sigma0: 0.8 },
+            raim_false_alarm: Some(1e-3),
+            ..options()
+        };
+        let clean = constellation
+            .solve_spp(epoch, &observations, &options)
+            .unwrap();
+        assert!(clean.excluded.is_empty());
+        let threshold = chi_square_threshold(observations.len() - 4, 1e-3);
+        assert!(clean.test_statistic.unwrap() < threshold);
+
+        let faulty: SatId = "G17".parse().unwrap();
+        for obs in observations.iter_mut().filter(|obs| obs.sat_id == faulty) {
+            obs.pseudorange += 100.0;
+        }
+        let unprotected = SppOptions {
+            raim_false_alarm: None,
+            ..options.clone()
+        };
+        let degraded = constellation
+            .solve_spp(epoch, &observations, &unprotected)
+            .unwrap();
+        assert!(degraded.position.distance_to(&station()) > 10.0);
+        let solution = constellation
+            .solve_spp(epoch, &observations, &options)
+            .unwrap();
+        assert_eq!(solution.excluded, vec![faulty]);
+        assert!(solution
+            .residuals
+            .iter()
+            .all(|(sat_id, _)| *sat_id != faulty));
+        let threshold = chi_square_threshold(observations.len() - 5, 1e-3);
+        assert!(solution.test_statistic.unwrap() < threshold);
+        assert!(solution.position.distance_to(&station()) < 5.0);
+    }
+
+    #[test]
+    fn chi_square_threshold_matches_tables() {
+        // Upper 0.1 % and 5 % points of the chi-square distribution. Wilson-Hilferty is
+        // least accurate at one degree of freedom, where it errs on the conservative side.
+        let tables = [
+            (1, 1e-3, 10.828, 0.04),
+            (5, 1e-3, 20.515, 0.02),
+            (10, 0.05, 18.307, 0.02),
+        ];
+        for (dof, false_alarm, table, tolerance) in tables {
+            let threshold = chi_square_threshold(dof, false_alarm);
+            assert!(
+                (threshold / table - 1.0).abs() < tolerance,
+                "{} {}",
+                dof,
+                threshold
+            );
+        }
+    }
+}
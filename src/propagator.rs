@@ -0,0 +1,85 @@
+use crate::ephemeris::PreparedEphemeris;
+use crate::gnss::{GpsTime, NavRecord, State, StateColumnsMut, ECEF};
+use crate::satellite::{PropagationConfig, PropagationError, Satellite};
+
+/// Orbit model that can be sampled at arbitrary epochs. `Satellite::propagate_with` and
+/// `Constellation::propagate_all_with` drive any implementation over the usual time grid.
+pub trait OrbitPropagator {
+    fn state_at(&self, epoch: GpsTime) -> Result<State, PropagationError>;
+}
+
+/// The broadcast Keplerian model over a set of nav records, using the record with the
+/// nearest toe at each epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BroadcastPropagator {
+    prepared: Vec<PreparedEphemeris>, // Sorted by toe, each with its record
+    config: PropagationConfig,
+}
+
+impl BroadcastPropagator {
+    pub fn new(records: &[NavRecord], config: PropagationConfig) -> Result<Self, PropagationError> {
+        let mut prepared: Vec<PreparedEphemeris> = records
+            .iter()
+            .filter(|record| !config.healthy_only || record.is_healthy())
+            .filter(|record| !config.valid_only || record.is_valid())
+            .map(NavRecord::prepare)
+            .collect();
+        if prepared.is_empty() {
+            return Err(PropagationError::NoEphemeris);
+        }
+        prepared.sort_by(|a, b| a.toe_gps.total_cmp(&b.toe_gps));
+        Ok(Self { prepared, config })
+    }
+
+    /// The records in use, in toe order
+    pub fn records(&self) -> impl Iterator<Item = &NavRecord> {
+        self.prepared.iter().map(|prepared| &prepared.record)
+    }
+
+    /// Record in effect at an epoch, the one with the nearest toe
+    pub fn record_at(&self, epoch: GpsTime) -> &NavRecord {
+        &self.prepared_at(epoch).record
+    }
+
+    /// `record_at` with the record's derived constants
+    fn prepared_at(&self, gps_time: GpsTime) -> &PreparedEphemeris {
+        let idx = self
+            .prepared
+            .partition_point(|prepared| prepared.toe_gps < gps_time);
+        match (idx.checked_sub(1), self.prepared.get(idx)) {
+            // Ties go to the earlier record, as in `Satellite::propagate`
+            (Some(prev), Some(next))
+                if gps_time - self.prepared[prev].toe_gps <= next.toe_gps - gps_time =>
+            {
+                &self.prepared[prev]
+            }
+            (_, Some(next)) => next,
+            (Some(prev), None) => &self.prepared[prev],
+            (None, None) => unreachable!("BroadcastPropagator always holds a record"),
+        }
+    }
+}
+
+impl OrbitPropagator for BroadcastPropagator {
+    fn state_at(&self, epoch: GpsTime) -> Result<State, PropagationError> {
+        let prepared = self.prepared_at(epoch);
+        let gps_time = epoch.seconds();
+        let age = (epoch - prepared.toe_gps).abs().as_f64();
+        if let (Some(max_age), true) = (self.config.max_ephemeris_age, self.config.strict) {
+            if age > max_age {
+                return Err(PropagationError::EphemerisTooOld { gps_time, age });
+            }
+        }
+        let mut state = State::new(gps_time, ECEF::default());
+        let columns = StateColumnsMut::from_state(
+            &mut state,
+            self.config.with_velocity,
+            self.config.with_clock,
+        );
+        Satellite::evaluate_block(prepared, &[gps_time], &self.config, columns);
+        if self.config.strict && !state.kepler_converged {
+            return Err(PropagationError::KeplerNotConverged { gps_time });
+        }
+        Ok(state)
+    }
+}
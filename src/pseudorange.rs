@@ -0,0 +1,125 @@
+use crate::gnss::{self, GpsTime, State, AER, ECEF};
+use crate::propagator::OrbitPropagator;
+use crate::satellite::PropagationError;
+use crate::units::Seconds;
+
+const LIGHT_TIME_TOLERANCE: f64 = 1e-12; // Seconds of signal flight time
+const LIGHT_TIME_MAX_ITER: u32 = 10;
+
+/// Modeled pseudorange split into its terms, all in meters.
+///
+/// Receiver clock is not included. The ionosphere, troposphere and antenna terms start at
+/// zero and are meant to be filled in from `aer` by whichever delay models are in use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PseudorangeModel {
+    pub transmit_time: f64,       // GPS seconds
+    pub satellite_position: ECEF, // At transmission, in the ECEF frame of reception
+    pub geometric_range: f64,     // Non-rotated range from transmit position to receiver
+    pub sagnac: f64,              // Earth rotation during the flight time
+    pub satellite_clock: f64,     // -c * dt_sv, including the relativistic term
+    pub group_delay: f64,         // c * TGD, the L1 correction to the broadcast clock
+    pub ionosphere: f64,
+    pub troposphere: f64,
+    pub antenna: f64,           // Receiver phase center offset and variation
+    pub aer: AER,               // Look angles from the receiver
+    pub satellite_state: State, // Propagated state at transmit time
+}
+
+impl PseudorangeModel {
+    pub fn total(&self) -> f64 {
+        self.geometric_range
+            + self.sagnac
+            + self.satellite_clock
+            + self.group_delay
+            + self.ionosphere
+            + self.troposphere
+            + self.antenna
+    }
+}
+
+/// Model the L1 pseudorange received at a GPS time by a receiver at a known position.
+///
+/// The transmit time is found by iterating the signal flight time, and the satellite
+/// position is rotated by the Earth's rotation during flight. The clock term comes from the
+/// propagated state and is zero if the propagator does not provide clocks.
+pub fn model_pseudorange(
+    receiver: &ECEF,
+    propagator: &impl OrbitPropagator,
+    receive_time: f64,
+    tgd: f64,
+) -> Result<PseudorangeModel, PropagationError> {
+    let mut flight_time = 0.075; // Typical GPS flight time as a starting guess
+    let mut state = propagator.state_at(GpsTime::from_seconds(receive_time - flight_time))?;
+    for _ in 0..LIGHT_TIME_MAX_ITER {
+        let rotated = rotate_z(&state.position, gnss::OMEGA_E_DOT * flight_time);
+        let next = gnss::range(receiver, &rotated) / gnss::C_LIGHT;
+        let converged = (next - flight_time).abs() < LIGHT_TIME_TOLERANCE;
+        flight_time = next;
+        state = propagator.state_at(GpsTime::from_seconds(receive_time - flight_time))?;
+        if converged {
+            break;
+        }
+    }
+
+    let position = state.position;
+    let rotated = rotate_z(&position, gnss::OMEGA_E_DOT * flight_time);
+    let geometric_range = gnss::range(receiver, &position);
+    let satellite_clock = -Seconds(state.clock_bias.unwrap_or(0.0)).to_meters(gnss::C_LIGHT);
+    Ok(PseudorangeModel {
+        transmit_time: receive_time - flight_time,
+        satellite_position: rotated,
+        geometric_range,
+        sagnac: gnss::range(receiver, &rotated) - geometric_range,
+        satellite_clock: satellite_clock.as_f64(),
+        group_delay: Seconds(tgd).to_meters(gnss::C_LIGHT).as_f64(),
+        ionosphere: 0.0,
+        troposphere: 0.0,
+        antenna: 0.0,
+        aer: receiver.to_lla().aer_to(&rotated),
+        satellite_state: state,
+    })
+}
+
+/// Rotate a position about the z axis into a frame that has turned by `angle` since
+fn rotate_z(position: &ECEF, angle: f64) -> ECEF {
+    let (sin_a, cos_a) = angle.sin_cos();
+    ECEF::new(
+        cos_a * position.x + sin_a * position.y,
+        -sin_a * position.x + cos_a * position.y,
+        position.z,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::propagator::BroadcastPropagator;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::{station, NAV};
+
+    #[test]
+    fn terms_match_an_independent_computation() {
+        // G17's 04:00 record seen from the file's station at 04:10 GPS time on 2023-06-12
+        // (week 2266, 101400 s), against a separate IS-GPS-200 evaluation of the same record
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let config = PropagationConfig::new().with_clock(true);
+        let propagator =
+            BroadcastPropagator::new(nav.records_for_slice(17.into()), config).unwrap();
+        let receiver = station();
+        let receive_time = 2266.0 * 604800.0 + 101400.0;
+        let model = model_pseudorange(&receiver, &propagator, receive_time, -1.117587e-8).unwrap();
+
+        assert!((model.transmit_time - (2266.0 * 604800.0 + 101399.9232309566)).abs() < 1e-9);
+        let expected = ECEF::new(-15083118.686891984, 13499305.533314364, 17682267.588857774);
+        assert!(gnss::range(&model.satellite_position, &expected) < 1e-3);
+        assert!((model.geometric_range - 23014767.39570767).abs() < 1e-3);
+        assert!((model.sagnac - 12.824734851717949).abs() < 1e-6);
+        assert!((model.satellite_clock - -215278.87161310206).abs() < 1e-3);
+        assert!((model.group_delay - -3.35044153758846).abs() < 1e-6);
+        assert!((model.total() - 22799497.99838788).abs() < 1e-3);
+        assert_eq!(
+            model.total(),
+            model.geometric_range + model.sagnac + model.satellite_clock + model.group_delay
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! Current satellite states for live displays: a background thread evaluates every
+//! satellite of an `EphemerisStore` at the current time, at a fixed rate, and publishes
+//! the results to readers and subscribers.
+
+use crate::gnss::{GpsTime, RinexNav, SatId, State};
+use crate::satellite::PropagationConfig;
+use crate::store::EphemerisStore;
+use chrono::Utc;
+use log::debug;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The tracker's idea of now
+pub trait Clock: Send + Sync {
+    fn now(&self) -> GpsTime;
+}
+
+/// Wall-clock time read once and advanced with the monotonic clock, so an NTP step or a
+/// manual clock change neither moves the states backwards nor stalls the tracker. Drifts
+/// from the wall clock by the oscillator error; create a new one to resynchronize.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    anchor: GpsTime,
+    started: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            anchor: GpsTime::from_utc(Utc::now()),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> GpsTime {
+        GpsTime::from_seconds(self.anchor.seconds() + self.started.elapsed().as_secs_f64())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerConfig {
+    pub period: Duration, // Between updates, must be positive
+    pub propagation: PropagationConfig,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            period: Duration::from_secs(1),
+            propagation: PropagationConfig::new(),
+        }
+    }
+}
+
+impl TrackerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    pub fn propagation(mut self, propagation: PropagationConfig) -> Self {
+        self.propagation = propagation;
+        self
+    }
+}
+
+/// Why a tracker could not start
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackerError {
+    ZeroPeriod, // The worker would spin without pause
+}
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ZeroPeriod => write!(f, "tracker period is zero"),
+        }
+    }
+}
+
+impl std::error::Error for TrackerError {}
+
+/// A satellite's state at an update, with the ephemeris it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedState {
+    pub state: State,
+    pub toe: GpsTime,
+    pub iode: f64,
+}
+
+/// All satellites at one epoch; those whose evaluation fails are left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerUpdate {
+    pub epoch: GpsTime,
+    pub states: BTreeMap<SatId, TrackedState>,
+    pub handovers: Vec<SatId>, // Satellites whose ephemeris changed since the last update
+}
+
+/// Background thread keeping the current state of every satellite in a nav file.
+///
+/// Each update evaluates the ephemeris nearest in toe to now, so satellites move on to the
+/// next broadcast as time passes their fit intervals; `ingest` swaps in a newer nav file
+/// between updates. Dropping the tracker stops the thread.
+pub struct RealTimeTracker {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    store: EphemerisStore,
+    clock: Box<dyn Clock>,
+    period: Duration,
+    latest: RwLock<Option<Arc<TrackerUpdate>>>,
+    subscribers: Mutex<Vec<Sender<Arc<TrackerUpdate>>>>,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl RealTimeTracker {
+    /// Start updating from the wall clock. Fails with `ZeroPeriod` when the period is zero.
+    pub fn start(nav: RinexNav, config: TrackerConfig) -> Result<Self, TrackerError> {
+        Self::start_with_clock(nav, config, SystemClock::new())
+    }
+
+    /// Start updating from any clock, e.g. one replaying past data faster than real time
+    pub fn start_with_clock(
+        nav: RinexNav,
+        config: TrackerConfig,
+        clock: impl Clock + 'static,
+    ) -> Result<Self, TrackerError> {
+        if config.period.is_zero() {
+            return Err(TrackerError::ZeroPeriod);
+        }
+        let shared = Arc::new(Shared {
+            store: EphemerisStore::new(nav, config.propagation),
+            clock: Box::new(clock),
+            period: config.period,
+            latest: RwLock::new(None),
+            subscribers: Mutex::new(Vec::new()),
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+        });
+        shared.update();
+        let worker = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || shared.run())
+        };
+        Ok(Self {
+            shared,
+            worker: Some(worker),
+        })
+    }
+
+    pub fn current_state(&self, sat_id: SatId) -> Option<TrackedState> {
+        self.latest()?.states.get(&sat_id).cloned()
+    }
+
+    pub fn latest(&self) -> Option<Arc<TrackerUpdate>> {
+        self.shared.latest.read().unwrap().clone()
+    }
+
+    /// Every update from now on; dropping the receiver unsubscribes
+    pub fn subscribe(&self) -> Receiver<Arc<TrackerUpdate>> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Use a newer nav file from the next update on
+    pub fn ingest(&self, nav: RinexNav) {
+        self.shared.store.replace(nav);
+    }
+
+    /// Stop the thread and wait for it to finish its update in progress
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.wake.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for RealTimeTracker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl Shared {
+    fn run(&self) {
+        let mut deadline = Instant::now() + self.period;
+        loop {
+            // Waits on the monotonic clock, so a wall-clock step cannot lengthen them
+            let mut stopped = self.stopped.lock().unwrap();
+            while !*stopped {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                stopped = self.wake.wait_timeout(stopped, deadline - now).unwrap().0;
+            }
+            if *stopped {
+                return;
+            }
+            drop(stopped);
+            self.update();
+            // Updates that overran are skipped rather than caught up on
+            deadline = (deadline + self.period).max(Instant::now());
+        }
+    }
+
+    fn update(&self) {
+        let epoch = self.clock.now();
+        let snapshot = self.store.snapshot();
+        let previous = self.latest.read().unwrap().clone();
+        let mut states = BTreeMap::new();
+        let mut handovers = Vec::new();
+        for sat_id in snapshot.satellites() {
+            let (Some(record), Ok(state)) = (
+                snapshot.best_record(sat_id, epoch),
+                snapshot.state_at(sat_id, epoch),
+            ) else {
+                continue;
+            };
+            let tracked = TrackedState {
+                state,
+                toe: record.toe_epoch(),
+                iode: record.iode,
+            };
+            let before = previous
+                .as_ref()
+                .and_then(|previous| previous.states.get(&sat_id));
+            if before.is_some_and(|before| (before.toe, before.iode) != (tracked.toe, tracked.iode))
+            {
+                debug!(
+                    "{} handed over to the ephemeris of toe {:?}",
+                    sat_id, tracked.toe
+                );
+                handovers.push(sat_id);
+            }
+            states.insert(sat_id, tracked);
+        }
+        let update = Arc::new(TrackerUpdate {
+            epoch,
+            states,
+            handovers,
+        });
+        *self.latest.write().unwrap() = Some(Arc::clone(&update));
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(Arc::clone(&update)).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::NAV;
+    use crate::units::GpsSeconds;
+    use chrono::TimeZone;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// 2023-06-12 02:40 UTC until released, then five minutes later at every reading, so
+    /// subscribers can be in place before the time moves
+    #[derive(Clone, Default)]
+    struct FastClock {
+        released: Arc<AtomicBool>,
+        readings: Arc<AtomicU32>,
+    }
+
+    impl Clock for FastClock {
+        fn now(&self) -> GpsTime {
+            let start = GpsTime::from_utc(Utc.with_ymd_and_hms(2023, 6, 12, 2, 40, 0).unwrap());
+            if !self.released.load(Ordering::SeqCst) {
+                return start;
+            }
+            let readings = self.readings.fetch_add(1, Ordering::SeqCst);
+            GpsTime::from_seconds(start.seconds() + 300.0 * f64::from(readings + 1))
+        }
+    }
+
+    fn tracker(clock: &FastClock) -> RealTimeTracker {
+        let config = TrackerConfig::new().period(Duration::from_millis(1));
+        RealTimeTracker::start_with_clock(NAV.parse().unwrap(), config, clock.clone()).unwrap()
+    }
+
+    #[test]
+    fn zero_period_is_rejected() {
+        let config = TrackerConfig::new().period(Duration::ZERO);
+        let tracker = RealTimeTracker::start(NAV.parse().unwrap(), config);
+        assert_eq!(tracker.err(), Some(TrackerError::ZeroPeriod));
+    }
+
+    #[test]
+    fn states_follow_the_clock_and_hand_over() {
+        let clock = FastClock::default();
+        let tracker = tracker(&clock);
+        let first = tracker.latest().unwrap();
+        assert_eq!(first.states.len(), 32);
+        let g17 = SatId::from(17);
+        let before = tracker.current_state(g17).unwrap();
+
+        let store = EphemerisStore::new(NAV.parse().unwrap(), PropagationConfig::new());
+        let updates = tracker.subscribe();
+        clock.released.store(true, Ordering::SeqCst);
+        let mut previous = first.epoch;
+        let handover = loop {
+            let update = updates.recv_timeout(TIMEOUT).unwrap();
+            // Updates made before the release repeat the first epoch
+            assert!(update.epoch >= previous);
+            previous = update.epoch;
+            let tracked = &update.states[&g17];
+            assert_eq!(tracked.state.time, update.epoch.seconds());
+            assert_eq!(
+                tracked.state.position,
+                store.position_at(g17, update.epoch).unwrap()
+            );
+            if update.handovers.contains(&g17) {
+                break update;
+            }
+            assert_eq!(tracked.toe, before.toe);
+        };
+        // To the next broadcast, once now is nearer its toe than the one before
+        let nav: RinexNav = NAV.parse().unwrap();
+        let toes: Vec<GpsTime> = nav
+            .records_for_slice(g17)
+            .iter()
+            .map(|r| r.toe_epoch())
+            .collect();
+        let after = &handover.states[&g17];
+        let index = toes.iter().position(|&toe| toe == before.toe).unwrap();
+        assert_eq!(after.toe, toes[index + 1]);
+        assert_ne!(after.iode, before.iode);
+        assert!((handover.epoch - after.toe).abs() <= (handover.epoch - before.toe).abs());
+        let last_before = handover.epoch - GpsSeconds(300.0);
+        assert!((last_before - after.toe).abs() >= (last_before - before.toe).abs());
+        tracker.stop();
+    }
+
+    #[test]
+    fn ingested_files_apply_from_the_next_update() {
+        let clock = FastClock::default();
+        let tracker = tracker(&clock);
+        let updates = tracker.subscribe();
+        let g17: SatId = 17.into();
+        let nav: RinexNav = NAV.parse().unwrap();
+        tracker.ingest(RinexNav::from_records(nav.records_for_slice(g17).to_vec()));
+        let update = loop {
+            let update = updates.recv_timeout(TIMEOUT).unwrap();
+            if update.states.len() == 1 {
+                break update;
+            }
+        };
+        assert!(update.states.contains_key(&g17));
+        assert_eq!(tracker.current_state(SatId::from(5)), None);
+
+        // Stopping drops the subscribers once the last update is read
+        tracker.stop();
+        while updates.recv_timeout(TIMEOUT).is_ok() {}
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[test]
+    fn system_clock_never_goes_backwards() {
+        let clock = SystemClock::new();
+        let wall = GpsTime::from_utc(Utc::now());
+        let mut previous = clock.now();
+        assert!((previous - wall).abs() < GpsSeconds(1.0));
+        for _ in 0..1000 {
+            let now = clock.now();
+            assert!(now >= previous);
+            previous = now;
+        }
+    }
+}
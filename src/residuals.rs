@@ -0,0 +1,255 @@
+use crate::gnss::SatId;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Post-fit pseudorange residual of one satellite at one epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResidualRecord {
+    pub epoch: DateTime<Utc>,
+    pub residual: f64,  // Observed minus modeled after the update, m
+    pub elevation: f64, // Degrees
+    pub azimuth: f64,   // Degrees
+    pub weight: f64,    // Inverse variance used by the solver, 1/m²
+}
+
+/// Summary of a set of residuals, m
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResidualStatistics {
+    pub count: usize,
+    pub mean: f64,
+    pub rms: f64,
+    pub max_abs: f64,
+}
+
+impl ResidualStatistics {
+    pub fn from_records<'a>(records: impl IntoIterator<Item = &'a ResidualRecord>) -> Self {
+        let mut stats = Self::default();
+        let mut sum_squares = 0.0;
+        for record in records {
+            stats.count += 1;
+            stats.mean += record.residual;
+            sum_squares += record.residual * record.residual;
+            stats.max_abs = stats.max_abs.max(record.residual.abs());
+        }
+        if stats.count > 0 {
+            stats.mean /= stats.count as f64;
+            stats.rms = (sum_squares / stats.count as f64).sqrt();
+        }
+        stats
+    }
+}
+
+/// Residuals of the satellites whose elevation falls in [min, max) degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ElevationBin {
+    pub min: f64,
+    pub max: f64,
+    pub statistics: ResidualStatistics,
+}
+
+/// Post-fit residuals per satellite, in epoch order, from one solution or accumulated
+/// over many with `append`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Residuals {
+    records: BTreeMap<SatId, Vec<ResidualRecord>>,
+}
+
+impl Residuals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sat_id: SatId, record: ResidualRecord) {
+        self.records.entry(sat_id).or_default().push(record);
+    }
+
+    /// Add every record of another set, e.g. the next epoch's solution
+    pub fn append(&mut self, other: &Residuals) {
+        for (sat_id, records) in &other.records {
+            self.records
+                .entry(*sat_id)
+                .or_default()
+                .extend_from_slice(records);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn satellites(&self) -> impl Iterator<Item = SatId> + '_ {
+        self.records.keys().copied()
+    }
+
+    pub fn records(&self, sat_id: SatId) -> &[ResidualRecord] {
+        self.records.get(&sat_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every record with its satellite, by satellite then epoch
+    pub fn iter(&self) -> impl Iterator<Item = (SatId, &ResidualRecord)> + '_ {
+        self.records
+            .iter()
+            .flat_map(|(sat_id, records)| records.iter().map(move |record| (*sat_id, record)))
+    }
+
+    pub fn statistics(&self, sat_id: SatId) -> ResidualStatistics {
+        ResidualStatistics::from_records(self.records(sat_id))
+    }
+
+    /// Statistics of every satellite
+    pub fn summary(&self) -> BTreeMap<SatId, ResidualStatistics> {
+        self.records
+            .iter()
+            .map(|(sat_id, records)| (*sat_id, ResidualStatistics::from_records(records)))
+            .collect()
+    }
+
+    /// Statistics over all satellites in elevation bins of the given width, from the
+    /// horizon up; empty bins are left out
+    pub fn by_elevation(&self, bin_width: f64) -> Vec<ElevationBin> {
+        let mut bins: BTreeMap<i64, Vec<&ResidualRecord>> = BTreeMap::new();
+        for (_, record) in self.iter() {
+            let bin = (record.elevation.max(0.0) / bin_width).floor() as i64;
+            bins.entry(bin).or_default().push(record);
+        }
+        bins.into_iter()
+            .map(|(bin, records)| ElevationBin {
+                min: bin as f64 * bin_width,
+                max: (bin + 1) as f64 * bin_width,
+                statistics: ResidualStatistics::from_records(records),
+            })
+            .collect()
+    }
+
+    /// The n satellites with the largest RMS residual, largest first
+    pub fn worst(&self, n: usize) -> Vec<(SatId, ResidualStatistics)> {
+        let mut summary: Vec<_> = self.summary().into_iter().collect();
+        summary.sort_by(|a, b| b.1.rms.total_cmp(&a.1.rms));
+        summary.truncate(n);
+        summary
+    }
+
+    /// Write one line per record: satellite, epoch (RFC 3339), residual, elevation,
+    /// azimuth and weight
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "satellite,epoch,residual,elevation,azimuth,weight")?;
+        for (sat_id, record) in self.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                sat_id,
+                record.epoch.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                record.residual,
+                record.elevation,
+                record.azimuth,
+                record.weight
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn record(second: i64, residual: f64, elevation: f64) -> ResidualRecord {
+        ResidualRecord {
+            epoch: Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap() + Duration::seconds(second),
+            residual,
+            elevation,
+            azimuth: 90.0,
+            weight: 4.0,
+        }
+    }
+
+    /// Three satellites over two epochs, G05 noisiest and low, G12 quiet and high
+    fn residuals() -> Residuals {
+        let mut first = Residuals::new();
+        first.push(SatId::gps(12), record(0, 0.5, 70.0));
+        first.push(SatId::gps(5), record(0, 3.0, 12.0));
+        first.push(SatId::gps(9), record(0, -1.0, 35.0));
+        let mut second = Residuals::new();
+        second.push(SatId::gps(12), record(30, -0.5, 71.0));
+        second.push(SatId::gps(5), record(30, -4.0, 11.0));
+        second.push(SatId::gps(9), record(30, 1.5, 36.0));
+        first.append(&second);
+        first
+    }
+
+    #[test]
+    fn statistics_per_satellite() {
+        let residuals = residuals();
+        assert_eq!(residuals.records(SatId::gps(5)).len(), 2);
+        assert!(residuals.records(SatId::gps(1)).is_empty());
+        let stats = residuals.statistics(SatId::gps(5));
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.mean, -0.5);
+        assert_eq!(stats.rms, 12.5f64.sqrt());
+        assert_eq!(stats.max_abs, 4.0);
+        assert_eq!(
+            residuals.statistics(SatId::gps(1)),
+            ResidualStatistics::default()
+        );
+        // By satellite, then epoch
+        let order: Vec<(SatId, f64)> = residuals
+            .iter()
+            .map(|(sat_id, record)| (sat_id, record.residual))
+            .collect();
+        assert_eq!(order[0], (SatId::gps(5), 3.0));
+        assert_eq!(order[1], (SatId::gps(5), -4.0));
+        assert_eq!(order[5], (SatId::gps(12), -0.5));
+    }
+
+    #[test]
+    fn worst_satellites_come_first() {
+        let worst = residuals().worst(2);
+        let satellites: Vec<SatId> = worst.iter().map(|(sat_id, _)| *sat_id).collect();
+        assert_eq!(satellites, [SatId::gps(5), SatId::gps(9)]);
+        assert_eq!(residuals().worst(10).len(), 3);
+    }
+
+    #[test]
+    fn elevation_bins_leave_out_empty_ones() {
+        let bins = residuals().by_elevation(30.0);
+        let edges: Vec<(f64, f64, usize)> = bins
+            .iter()
+            .map(|bin| (bin.min, bin.max, bin.statistics.count))
+            .collect();
+        assert_eq!(edges, [(0.0, 30.0, 2), (30.0, 60.0, 2), (60.0, 90.0, 2)]);
+        assert_eq!(residuals().by_elevation(20.0).len(), 3);
+        assert_eq!(bins[0].statistics.max_abs, 4.0);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_line_per_record() {
+        let mut csv = Vec::new();
+        residuals().write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[0],
+            "satellite,epoch,residual,elevation,azimuth,weight"
+        );
+        assert_eq!(lines[1], "G05,2023-06-12T04:00:00.000Z,3,12,90,4");
+        assert_eq!(lines[6], "G12,2023-06-12T04:00:30.000Z,-0.5,71,90,4");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keys_by_satellite() {
+        let residuals = residuals();
+        let json = serde_json::to_string(&residuals).unwrap();
+        assert!(json.starts_with("{\"G05\":["), "{}", json);
+        let parsed: Residuals = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, residuals);
+    }
+}
@@ -0,0 +1,386 @@
+use crate::baseline::{self, BaselineConfig};
+use crate::constellation::Constellation;
+use crate::double_difference::{DoubleDifferenceConfig, DoubleDifferenceEpoch, DoubleDifferencer};
+use crate::gnss::{self, SatId, ECEF, ENU};
+use crate::linalg;
+use crate::observation::ObservationEpoch;
+use crate::positioning::PositioningError;
+use crate::propagator::BroadcastPropagator;
+use crate::satellite::{PropagationConfig, PropagationError};
+use chrono::{DateTime, Utc};
+use ndarray::{s, Array1, Array2, Axis};
+use std::collections::BTreeMap;
+
+/// Tuning of `FloatRtk`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtkConfig {
+    pub double_difference: DoubleDifferenceConfig,
+    pub position_psd: f64, // Rover random walk density, m²/s; 0 for a static rover
+    pub initial_ambiguity_sigma: f64, // Of a newly added ambiguity around its code estimate, cycles
+}
+
+impl Default for RtkConfig {
+    fn default() -> Self {
+        Self {
+            double_difference: DoubleDifferenceConfig::default(),
+            position_psd: 0.1,
+            initial_ambiguity_sigma: 10.0,
+        }
+    }
+}
+
+/// Float RTK solution after one epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtkSolution {
+    pub epoch: DateTime<Utc>,              // Rover time tag
+    pub position: ECEF,                    // Rover
+    pub baseline: ECEF,                    // Rover minus base
+    pub baseline_enu: ENU,                 // In the local frame at the base
+    pub covariance: Array2<f64>,           // Of the rover position, m²
+    pub ambiguities: BTreeMap<SatId, f64>, // Float DD ambiguities against `references`, cycles
+    pub references: BTreeMap<gnss::Constellation, SatId>,
+}
+
+/// Epoch-recursive float RTK: a Kalman filter over the rover position and one float
+/// double-difference ambiguity per non-reference satellite, updated with DD code and
+/// phase. Ambiguity states come and go with the satellites and follow reference changes.
+pub struct FloatRtk<'a> {
+    constellation: &'a Constellation,
+    base_position: ECEF,
+    config: RtkConfig,
+    differencer: DoubleDifferencer<'a>,
+    propagators: BTreeMap<SatId, Option<BroadcastPropagator>>,
+    state: Array1<f64>, // x, y, z, then the ambiguities in cycles
+    covariance: Array2<f64>,
+    ambiguities: Vec<SatId>, // Satellite of each ambiguity state
+    references: BTreeMap<gnss::Constellation, SatId>,
+    last_time: Option<f64>,
+}
+
+impl<'a> FloatRtk<'a> {
+    pub fn new(constellation: &'a Constellation, base_position: ECEF, config: RtkConfig) -> Self {
+        let differencer = DoubleDifferencer::new(
+            constellation,
+            base_position,
+            config.double_difference.clone(),
+        );
+        Self {
+            constellation,
+            base_position,
+            config,
+            differencer,
+            propagators: BTreeMap::new(),
+            state: Array1::zeros(3),
+            covariance: Array2::zeros((3, 3)),
+            ambiguities: Vec::new(),
+            references: BTreeMap::new(),
+            last_time: None,
+        }
+    }
+
+    /// Process one base/rover epoch pair. Satellites in `slipped` restart their ambiguity.
+    /// The first epoch is initialised from the single-difference code baseline.
+    pub fn update(
+        &mut self,
+        base: &ObservationEpoch,
+        rover: &ObservationEpoch,
+        slipped: &[SatId],
+    ) -> Result<RtkSolution, PositioningError> {
+        let time = gnss::gps_seconds(rover.epoch);
+        let dd = self.differencer.update(base, rover, slipped);
+        self.follow_references(&dd, slipped);
+        match self.last_time {
+            None => {
+                let config = BaselineConfig {
+                    signal: self.config.double_difference.signal,
+                    elevation_mask: self.config.double_difference.elevation_mask,
+                    ..BaselineConfig::default()
+                };
+                let fix =
+                    self.constellation
+                        .solve_baseline(&self.base_position, base, rover, &config)?;
+                let position = self.base_position + fix.baseline;
+                self.state = Array1::from(vec![position.x, position.y, position.z]);
+                self.covariance = fix.covariance.slice(s![0..3, 0..3]).to_owned();
+                self.ambiguities.clear();
+            }
+            Some(last_time) => {
+                let noise = self.config.position_psd * (time - last_time).max(0.0);
+                for axis in 0..3 {
+                    self.covariance[[axis, axis]] += noise;
+                }
+            }
+        }
+        self.last_time = Some(time);
+        self.sync_ambiguities(&dd);
+        self.measurement_update(base, rover, &dd)?;
+        Ok(self.solution(rover.epoch, &dd))
+    }
+
+    /// Re-parent or drop ambiguity states after the differencer changed references
+    fn follow_references(&mut self, dd: &DoubleDifferenceEpoch, slipped: &[SatId]) {
+        let slipped_states: Vec<usize> = (0..self.ambiguities.len())
+            .filter(|&i| slipped.contains(&self.ambiguities[i]))
+            .collect();
+        self.remove_states(&slipped_states);
+        for (&system, &new) in &dd.references {
+            let Some(&old) = self.references.get(&system) else {
+                continue;
+            };
+            if old == new {
+                continue;
+            }
+            let pivot = self.ambiguities.iter().position(|sat_id| *sat_id == new);
+            match (slipped.contains(&old), pivot) {
+                (false, Some(pivot)) => {
+                    // N(new, k) = N(old, k) - N(old, new); the pivot becomes N(new, old)
+                    let n = self.state.len();
+                    let mut transform = Array2::eye(n);
+                    for (i, sat_id) in self.ambiguities.iter().enumerate() {
+                        if sat_id.constellation == system {
+                            transform[[3 + i, 3 + pivot]] = -1.0;
+                        }
+                    }
+                    transform[[3 + pivot, 3 + pivot]] = -1.0;
+                    self.state = transform.dot(&self.state);
+                    self.covariance = transform.dot(&self.covariance).dot(&transform.t());
+                    self.ambiguities[pivot] = old;
+                }
+                _ => {
+                    let stale: Vec<usize> = (0..self.ambiguities.len())
+                        .filter(|&i| self.ambiguities[i].constellation == system)
+                        .collect();
+                    self.remove_states(&stale);
+                }
+            }
+        }
+        self.references
+            .extend(dd.references.iter().map(|(k, v)| (*k, *v)));
+    }
+
+    /// Drop ambiguities of satellites no longer differenced and add states for new ones
+    fn sync_ambiguities(&mut self, dd: &DoubleDifferenceEpoch) {
+        let gone: Vec<usize> = (0..self.ambiguities.len())
+            .filter(|&i| {
+                !dd.differences
+                    .iter()
+                    .any(|d| d.sat_id == self.ambiguities[i])
+            })
+            .collect();
+        self.remove_states(&gone);
+        let variance = self.config.initial_ambiguity_sigma.powi(2);
+        for difference in &dd.differences {
+            if self.ambiguities.contains(&difference.sat_id) {
+                continue;
+            }
+            let n = self.state.len();
+            let mut state = Array1::zeros(n + 1);
+            state.slice_mut(s![..n]).assign(&self.state);
+            state[n] = difference.ambiguity;
+            let mut covariance = Array2::zeros((n + 1, n + 1));
+            covariance.slice_mut(s![..n, ..n]).assign(&self.covariance);
+            covariance[[n, n]] = variance;
+            self.state = state;
+            self.covariance = covariance;
+            self.ambiguities.push(difference.sat_id);
+        }
+    }
+
+    fn remove_states(&mut self, ambiguity_indices: &[usize]) {
+        if ambiguity_indices.is_empty() {
+            return;
+        }
+        let keep: Vec<usize> = (0..self.state.len())
+            .filter(|&i| i < 3 || !ambiguity_indices.contains(&(i - 3)))
+            .collect();
+        self.state = self.state.select(Axis(0), &keep);
+        self.covariance = self
+            .covariance
+            .select(Axis(0), &keep)
+            .select(Axis(1), &keep);
+        let mut idx = 0;
+        self.ambiguities.retain(|_| {
+            idx += 1;
+            !ambiguity_indices.contains(&(idx - 1))
+        });
+    }
+
+    /// Batch update with every DD code and phase; the DD covariance is not diagonal
+    fn measurement_update(
+        &mut self,
+        base: &ObservationEpoch,
+        rover: &ObservationEpoch,
+        dd: &DoubleDifferenceEpoch,
+    ) -> Result<(), PositioningError> {
+        let m = dd.differences.len();
+        if m == 0 {
+            return Ok(());
+        }
+        let base_time = gnss::gps_seconds(base.epoch);
+        let rover_time = gnss::gps_seconds(rover.epoch);
+        let position = ECEF::new(self.state[0], self.state[1], self.state[2]);
+        let wavelength = self.config.double_difference.signal.wavelength();
+        let n = self.state.len();
+        let mut design = Array2::zeros((2 * m, n));
+        let mut innovation = Array1::zeros(2 * m);
+        for (row, difference) in dd.differences.iter().enumerate() {
+            let (sd, unit) =
+                self.single_difference(difference.sat_id, &position, base_time, rover_time)?;
+            let (sd_ref, unit_ref) =
+                self.single_difference(difference.reference, &position, base_time, rover_time)?;
+            let modeled = sd - sd_ref;
+            let ambiguity = 3 + self
+                .ambiguities
+                .iter()
+                .position(|sat_id| *sat_id == difference.sat_id)
+                .unwrap();
+            for (axis, (u, u_ref)) in [
+                (unit.x, unit_ref.x),
+                (unit.y, unit_ref.y),
+                (unit.z, unit_ref.z),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                design[[row, axis]] = u_ref - u;
+                design[[m + row, axis]] = u_ref - u;
+            }
+            design[[m + row, ambiguity]] = wavelength;
+            innovation[row] = difference.pseudorange - modeled;
+            innovation[m + row] = difference.phase - modeled - wavelength * self.state[ambiguity];
+        }
+        let mut noise = Array2::zeros((2 * m, 2 * m));
+        noise.slice_mut(s![..m, ..m]).assign(&dd.code_covariance);
+        noise.slice_mut(s![m.., m..]).assign(&dd.phase_covariance);
+
+        let ph = self.covariance.dot(&design.t());
+        let s = design.dot(&ph) + &noise;
+        let s_inverse = linalg::invert(&s).ok_or(PositioningError::SingularGeometry)?;
+        let gain = ph.dot(&s_inverse);
+        self.state = &self.state + &gain.dot(&innovation);
+        // Joseph form: the phase rows are precise enough to break P - K H P numerically
+        let reduction = Array2::eye(n) - gain.dot(&design);
+        self.covariance =
+            reduction.dot(&self.covariance).dot(&reduction.t()) + gain.dot(&noise).dot(&gain.t());
+        Ok(())
+    }
+
+    /// Modeled rover-minus-base range to a satellite and the rover line of sight
+    fn single_difference(
+        &mut self,
+        sat_id: SatId,
+        rover_position: &ECEF,
+        base_time: f64,
+        rover_time: f64,
+    ) -> Result<(f64, ECEF), PositioningError> {
+        let constellation = self.constellation;
+        let propagator = self.propagators.entry(sat_id).or_insert_with(|| {
+            let config = PropagationConfig::new().with_clock(true);
+            BroadcastPropagator::new(constellation.records(sat_id), config).ok()
+        });
+        let propagator = propagator.as_ref().ok_or(PositioningError::Propagation(
+            sat_id,
+            PropagationError::NoEphemeris,
+        ))?;
+        let rover = baseline::model(rover_position, propagator, rover_time, sat_id)?;
+        let base = baseline::model(&self.base_position, propagator, base_time, sat_id)?;
+        let unit =
+            gnss::unit_line_of_sight(rover_position, &rover.satellite_position).unwrap_or_default();
+        Ok((rover.total() - base.total(), unit))
+    }
+
+    fn solution(&self, epoch: DateTime<Utc>, dd: &DoubleDifferenceEpoch) -> RtkSolution {
+        let position = ECEF::new(self.state[0], self.state[1], self.state[2]);
+        let baseline = position - self.base_position;
+        RtkSolution {
+            epoch,
+            position,
+            baseline,
+            baseline_enu: self.base_position.to_lla().rotate_to_enu(&baseline),
+            covariance: self.covariance.slice(s![0..3, 0..3]).to_owned(),
+            ambiguities: self
+                .ambiguities
+                .iter()
+                .enumerate()
+                .map(|(i, sat_id)| (*sat_id, self.state[3 + i]))
+                .collect(),
+            references: dd.references.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{ErrorModel, GaussianNoise, SimulationConfig};
+    use crate::test_support::{constellation, station as base};
+    use chrono::{Duration, TimeZone};
+
+    fn rover() -> ECEF {
+        base() + ECEF::new(35.0, -20.0, 12.0)
+    }
+
+    /// Five minutes at 1 Hz of a static receiver with its own clock and ambiguities
+    fn observe(
+        constellation: &Constellation,
+        position: ECEF,
+        seed: u64,
+        errors: &mut impl ErrorModel,
+    ) -> Vec<ObservationEpoch> {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+        let truth: Vec<_> = (0..300)
+            .map(|k| (start + Duration::seconds(k), position))
+            .collect();
+        let config = SimulationConfig {
+            zenith_ionosphere: 4.0,
+            zenith_troposphere: 2.3,
+            receiver_clock_bias: seed as f64 * 1e-5,
+            ambiguity_seed: seed,
+            ..SimulationConfig::default()
+        };
+        constellation.simulate_observations(&truth, &config, errors)
+    }
+
+    #[test]
+    fn float_baseline_converges_below_half_a_metre() {
+        let constellation = constellation();
+        let base_obs = observe(
+            &constellation,
+            base(),
+            1,
+            &mut GaussianNoise::new(0.5, 0.003, 0.0, 21),
+        );
+        let rover_obs = observe(
+            &constellation,
+            rover(),
+            2,
+            &mut GaussianNoise::new(0.5, 0.003, 0.0, 22),
+        );
+        let config = RtkConfig {
+            position_psd: 0.0,
+            ..RtkConfig::default()
+        };
+        let mut rtk = FloatRtk::new(&constellation, base(), config);
+        let truth = rover() - base();
+        let errors: Vec<f64> = base_obs
+            .iter()
+            .zip(&rover_obs)
+            .map(|(b, r)| {
+                let solution = rtk.update(b, r, &[]).unwrap();
+                assert!(solution.ambiguities.len() >= 4);
+                (solution.baseline - truth).norm()
+            })
+            .collect();
+
+        assert!(errors[0] > 0.5, "first epoch {}", errors[0]);
+        for (k, error) in errors.iter().enumerate().skip(60) {
+            assert!(*error < 0.5, "epoch {}: {}", k, error);
+        }
+        assert!(
+            errors[299] < errors[0] / 5.0,
+            "{} {}",
+            errors[299],
+            errors[0]
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::gnss::SatId;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fmt;
+#[cfg(feature = "std-fs")]
+use std::fs;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Built-in GPS PRN assignments: sat_id,svn,block,launch_date,valid_from,valid_until.
+/// Each assignment runs until the PRN's next one starts; load a CSV for authoritative dates.
+const BUILTIN_TABLE: &str = "\
+sat_id,svn,block,launch_date,valid_from,valid_until
+G01,49,IIR-M,2009-03-24,2009-03-24,2011-07-16
+G01,63,IIF,2011-07-16,2011-07-16,
+G02,61,IIR,2004-11-06,2004-11-06,
+G03,69,IIF,2014-10-29,2014-10-29,
+G04,74,III,2018-12-23,2018-12-23,
+G05,50,IIR-M,2009-08-17,2009-08-17,
+G06,67,IIF,2014-05-17,2014-05-17,
+G07,48,IIR-M,2008-03-15,2008-03-15,
+G08,72,IIF,2015-07-15,2015-07-15,
+G09,68,IIF,2014-08-02,2014-08-02,
+G10,73,IIF,2015-10-31,2015-10-31,
+G11,46,IIR,1999-10-07,1999-10-07,2021-06-17
+G11,78,III,2021-06-17,2021-06-17,
+G12,58,IIR-M,2006-11-17,2006-11-17,
+G13,43,IIR,1997-07-23,1997-07-23,
+G14,41,IIR,2000-11-10,2000-11-10,2020-11-05
+G14,77,III,2020-11-05,2020-11-05,
+G15,55,IIR-M,2007-10-17,2007-10-17,
+G16,56,IIR,2003-01-29,2003-01-29,
+G17,53,IIR-M,2005-09-26,2005-09-26,
+G18,54,IIR,2001-01-30,2001-01-30,2019-08-22
+G18,75,III,2019-08-22,2019-08-22,
+G19,59,IIR,2004-03-20,2004-03-20,
+G20,51,IIR,2000-05-11,2000-05-11,
+G21,45,IIR,2003-03-31,2003-03-31,
+G22,47,IIR,2003-12-21,2003-12-21,
+G23,60,IIR,2004-06-23,2004-06-23,2020-06-30
+G23,76,III,2020-06-30,2020-06-30,
+G24,65,IIF,2012-10-04,2012-10-04,
+G25,62,IIF,2010-05-28,2010-05-28,
+G26,71,IIF,2015-03-25,2015-03-25,
+G27,66,IIF,2013-05-15,2013-05-15,
+G28,44,IIR,2000-07-16,2000-07-16,2023-01-18
+G28,79,III,2023-01-18,2023-01-18,
+G29,57,IIR-M,2007-12-20,2007-12-20,
+G30,64,IIF,2014-02-21,2014-02-21,
+G31,52,IIR-M,2006-09-25,2006-09-25,
+G32,70,IIF,2016-02-05,2016-02-05,
+";
+
+/// Spacecraft generation, which drives antenna offsets and attitude behaviour
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Block {
+    GpsIIA,
+    GpsIIR,
+    GpsIIRM,
+    GpsIIF,
+    GpsIII,
+    Other(String),
+}
+
+impl FromStr for Block {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "IIA" => Self::GpsIIA,
+            "IIR" => Self::GpsIIR,
+            "IIR-M" => Self::GpsIIRM,
+            "IIF" => Self::GpsIIF,
+            "III" | "IIIA" => Self::GpsIII,
+            other => Self::Other(other.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GpsIIA => write!(f, "IIA"),
+            Self::GpsIIR => write!(f, "IIR"),
+            Self::GpsIIRM => write!(f, "IIR-M"),
+            Self::GpsIIF => write!(f, "IIF"),
+            Self::GpsIII => write!(f, "III"),
+            Self::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Physical spacecraft that transmitted a PRN over a date range
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SatInfo {
+    pub sat_id: SatId,
+    pub svn: u16,
+    pub block: Block,
+    pub launch_date: NaiveDate,
+    pub valid_from: NaiveDate,
+    pub valid_until: Option<NaiveDate>, // Exclusive, None while still assigned
+}
+
+impl SatInfo {
+    /// Look up the spacecraft behind a PRN at an epoch in the built-in table
+    pub fn lookup(sat_id: impl Into<SatId>, epoch: DateTime<Utc>) -> Option<SatInfo> {
+        SatInfoTable::builtin().lookup(sat_id, epoch).cloned()
+    }
+
+    pub fn is_valid_at(&self, epoch: DateTime<Utc>) -> bool {
+        let date = epoch.date_naive();
+        date >= self.valid_from && self.valid_until.is_none_or(|until| date < until)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseSatInfoError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseSatInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseSatInfoError {}
+
+/// PRN to SVN assignments, either built in or loaded from a CSV file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SatInfoTable {
+    pub entries: Vec<SatInfo>,
+}
+
+impl SatInfoTable {
+    pub fn builtin() -> &'static SatInfoTable {
+        static TABLE: OnceLock<SatInfoTable> = OnceLock::new();
+        TABLE.get_or_init(|| BUILTIN_TABLE.parse().expect("Invalid built-in SVN table"))
+    }
+
+    /// Load a table with the same columns as the built-in one
+    #[cfg(feature = "std-fs")]
+    pub fn from_csv_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    pub fn lookup(&self, sat_id: impl Into<SatId>, epoch: DateTime<Utc>) -> Option<&SatInfo> {
+        let sat_id = sat_id.into();
+        self.entries
+            .iter()
+            .find(|info| info.sat_id == sat_id && info.is_valid_at(epoch))
+    }
+
+    /// Every PRN a given SVN has transmitted, oldest first
+    pub fn history(&self, svn: u16) -> Vec<&SatInfo> {
+        let mut history: Vec<&SatInfo> = self.entries.iter().filter(|i| i.svn == svn).collect();
+        history.sort_by_key(|info| info.valid_from);
+        history
+    }
+}
+
+impl FromStr for SatInfoTable {
+    type Err = ParseSatInfoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = Vec::new();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("sat_id") {
+                continue;
+            }
+            let err = |message: &str| ParseSatInfoError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 6 {
+                return Err(err("expected 6 comma-separated fields"));
+            }
+            let date = |field: &str| NaiveDate::parse_from_str(field, "%Y-%m-%d");
+            entries.push(SatInfo {
+                sat_id: fields[0].parse().map_err(|_| err("invalid sat_id"))?,
+                svn: fields[1].parse().map_err(|_| err("invalid svn"))?,
+                block: fields[2].parse().unwrap(),
+                launch_date: date(fields[3]).map_err(|_| err("invalid launch_date"))?,
+                valid_from: date(fields[4]).map_err(|_| err("invalid valid_from"))?,
+                valid_until: match fields[5] {
+                    "" => None,
+                    field => Some(date(field).map_err(|_| err("invalid valid_until"))?),
+                },
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn prn_changes_spacecraft_on_the_new_launch() {
+        let sat_id: SatId = "G28".parse().unwrap();
+        let before = SatInfo::lookup(sat_id, day(2023, 1, 17)).unwrap();
+        assert_eq!((before.svn, before.block), (44, Block::GpsIIR));
+        let after = SatInfo::lookup(sat_id, day(2023, 1, 18)).unwrap();
+        assert_eq!((after.svn, after.block), (79, Block::GpsIII));
+        assert_eq!(
+            after.launch_date,
+            NaiveDate::from_ymd_opt(2023, 1, 18).unwrap()
+        );
+        // Nothing was assigned before the first launch
+        assert_eq!(SatInfo::lookup(sat_id, day(1999, 1, 1)), None);
+    }
+
+    #[test]
+    fn loaded_table_reassigns_an_svn() {
+        let table: SatInfoTable = "\
+sat_id,svn,block,launch_date,valid_from,valid_until
+# SVN 49 moved to PRN 27 after its time as PRN 01
+G01,49,IIR-M,2009-03-24,2009-03-24,2011-07-16
+G27,49,IIR-M,2009-03-24,2011-07-16,2011-08-01
+"
+        .parse()
+        .unwrap();
+        let history: Vec<u8> = table.history(49).iter().map(|i| i.sat_id.prn).collect();
+        assert_eq!(history, [1, 27]);
+        assert_eq!(table.lookup(27, day(2011, 7, 20)).unwrap().svn, 49);
+        assert_eq!(table.lookup(27, day(2011, 8, 1)), None);
+    }
+
+    #[test]
+    fn malformed_rows_report_their_line() {
+        let err = "sat_id,svn,block,launch_date,valid_from,valid_until\nG01,49,IIR-M\n"
+            .parse::<SatInfoTable>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = "G01,49,IIR-M,2009-03-24,2009-13-24,".parse::<SatInfoTable>();
+        assert_eq!(err.unwrap_err().message, "invalid valid_from");
+    }
+}
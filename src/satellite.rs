@@ -1,161 +1,1851 @@
-use crate::gnss;
-use chrono::{DateTime, Utc};
-use ndarray::{Array1, Array2, ArrayView1};
-
-pub struct Satellite {
-    pub id: u8,
-    pub name: String,
-    pub states: Vec<gnss::State>,
-}
-
-impl Satellite {
-    pub fn new(id: u8, name: String) -> Self {
-        Self {
-            id,
-            name,
-            states: vec![],
-        }
-    }
-
-    pub fn propagate(
-        &mut self,
-        start: DateTime<Utc>,
-        duration: std::time::Duration,
-        step: std::time::Duration,
-        ephemeris_data: &[gnss::NavRecord],
-    ) -> usize {
-        let gps_times: Array1<f64> = Array1::from_iter(
-            (0..((duration.as_millis() / step.as_millis()) as usize))
-                .map(|i| gnss::calculate_gps_time((start + step * i as u32).into()) / 1000.0),
-        );
-
-        let closest_indices = gps_times.mapv(|time| {
-            ephemeris_data
-                .iter()
-                .enumerate()
-                .min_by_key(|(_, record)| {
-                    let time_diff = (record.gps_millis - time).abs();
-                    (time_diff * 1000.0) as u64
-                })
-                .map(|(index, _)| index)
-                .unwrap_or(0)
-        });
-
-        let ephem = Array2::from_shape_fn((16, gps_times.len()), |(param, time_idx)| {
-            let nav_record = &ephemeris_data[closest_indices[time_idx]];
-            match param {
-                0 => nav_record.sqrt_a,
-                1 => nav_record.eccentricity,
-                2 => nav_record.i0,
-                3 => nav_record.omega0,
-                4 => nav_record.omega,
-                5 => nav_record.m0,
-                6 => nav_record.toe,
-                7 => nav_record.delta_n,
-                8 => nav_record.omega_dot,
-                9 => nav_record.idot,
-                10 => nav_record.cus,
-                11 => nav_record.cuc,
-                12 => nav_record.crs,
-                13 => nav_record.crc,
-                14 => nav_record.cis,
-                15 => nav_record.cic,
-                _ => unreachable!(),
-            }
-        });
-
-        let a = ephem.row(0).mapv(|x| x.powi(2));
-        let e = ephem.row(1);
-        let i0 = ephem.row(2);
-        let omega0 = ephem.row(3);
-        let omega = ephem.row(4);
-        let m0 = ephem.row(5);
-        let toe = ephem.row(6);
-        let delta_n = ephem.row(7);
-        let omega_dot = ephem.row(8);
-        let idot = ephem.row(9);
-        let cus = ephem.row(10);
-        let cuc = ephem.row(11);
-        let crs = ephem.row(12);
-        let crc = ephem.row(13);
-        let cis = ephem.row(14);
-        let cic = ephem.row(15);
-        let tk = &gps_times - &toe;
-        let half_week = 302400.0;
-        let tk = (&tk + half_week) % (2.0 * half_week) - half_week;
-        let n0 = a.mapv(|a_val| (gnss::MU_EARTH / a_val.powi(3)).sqrt());
-        let n = &n0 + &delta_n;
-        let m = &m0 + &n * &tk;
-        let e_array = Self::solve_kepler_robust(&m.view(), &e);
-
-        let sin_e = e_array.mapv(f64::sin);
-        let cos_e = e_array.mapv(f64::cos);
-        let sqrt_1_minus_e2 = (1.0 - &e * &e).mapv(f64::sqrt);
-        let nu = (&sqrt_1_minus_e2 * &sin_e)
-            .iter()
-            .zip((&cos_e - &e).iter())
-            .map(|(&y, &x)| y.atan2(x))
-            .collect::<Array1<f64>>();
-        let phi = &nu + &omega;
-
-        // Radius and argument of latitude correction
-        let r = &a * (1.0 - &e * &cos_e);
-
-        let phi_2 = &phi * 2.0;
-        let sin_2phi = phi_2.mapv(f64::sin);
-        let cos_2phi = phi_2.mapv(f64::cos);
-
-        let delta_u = &cus * &sin_2phi + &cuc * &cos_2phi;
-        let delta_r = &crs * &sin_2phi + &crc * &cos_2phi;
-        let delta_i = &cis * &sin_2phi + &cic * &cos_2phi;
-
-        // Corrected radius and argument of latitude
-        let u = &phi + &delta_u;
-        let r = &r + &delta_r;
-        let i = &i0 + &delta_i + &idot * &tk;
-
-        // Position in orbital plane
-        let cos_u = u.mapv(f64::cos);
-        let sin_u = u.mapv(f64::sin);
-        let x = &r * &cos_u;
-        let y = &r * &sin_u;
-
-        // Earth-rotation correction
-        let omega = &omega0 + (&omega_dot - gnss::OMEGA_E_DOT) * &tk - gnss::OMEGA_E_DOT * &toe;
-        let cos_omega = omega.mapv(f64::cos);
-        let sin_omega = omega.mapv(f64::sin);
-        let cos_i = i.mapv(f64::cos);
-        let sin_i = i.mapv(f64::sin);
-
-        let x_ecef = &x * &cos_omega - &y * &cos_i * &sin_omega;
-        let y_ecef = &x * &sin_omega + &y * &cos_i * &cos_omega;
-        let z_ecef = &y * &sin_i;
-
-        // Store states
-        self.states.clear();
-        for idx in 0..gps_times.len() {
-            let state = gnss::State {
-                time: vec![gps_times[idx]],
-                position: vec![gnss::ECEF::new(x_ecef[idx], y_ecef[idx], z_ecef[idx])],
-            };
-            self.states.push(state);
-        }
-        println!("{:?}", self.states[0].position[0]);
-        self.states.len()
-    }
-
-    fn solve_kepler_robust(m: &ArrayView1<f64>, e: &ArrayView1<f64>) -> Array1<f64> {
-        let max_iter = 30;
-        let tolerance = 1e-8;
-
-        let mut e_array = m.to_owned();
-        for _ in 0..max_iter {
-            let e_next = m + e * &e_array.mapv(f64::sin);
-            if (&e_next - &e_array).mapv(|x| x.abs()).sum() < tolerance {
-                return e_next;
-            }
-            e_array = e_next;
-        }
-        e_array
-    }
-}
+use crate::ephemeris;
+use crate::gnss;
+use crate::propagator::OrbitPropagator;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+#[cfg(feature = "ndarray")]
+use ndarray::{Array1, ArrayView1};
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+const LAGRANGE_POINTS: usize = 8; // Interpolation window when no velocities are stored
+const EPOCH_TOLERANCE: f64 = 1e-6; // Seconds within which a state matches a requested epoch
+
+const STRIP_LEN: usize = 64; // Epochs per pass of `evaluate_block`, small enough to stay in cache
+const SERIES_LIMIT: f64 = 1e-2; // Largest angle, rad, for which `small_sin_cos` is exact
+
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK_LEN: usize = 16384; // Epochs per parallel work item
+
+/// Where the epoch grid of `Satellite::propagate` stops when the duration is not a whole
+/// number of steps. A zero duration gives the start epoch alone in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GridEnd {
+    /// Whole steps that fit before the end, which is left out; the end epoch and a final
+    /// partial step are dropped
+    #[default]
+    Exclusive,
+    /// Whole steps up to the end, then the end epoch itself, closer than a step to the
+    /// last one when the duration is not a whole number of steps
+    Inclusive,
+    /// The duration rounded to the nearest whole number of steps, end epoch included, so
+    /// every epoch stays on the step grid
+    Snap,
+}
+
+/// Options for `Satellite::propagate`, built with chained setters
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropagationConfig {
+    pub step: Duration, // Must be positive
+    pub grid_end: GridEnd,
+    pub with_velocity: bool,
+    pub with_clock: bool,
+    pub healthy_only: bool,
+    pub valid_only: bool, // Skip records `NavRecord::validate` finds errors in
+    pub max_ephemeris_age: Option<f64>, // Seconds between epoch and toe
+    pub kepler_tolerance: f64, // Radians of eccentric anomaly
+    pub kepler_max_iter: u32,
+    pub accuracy_growth: Option<f64>, // Accuracy inflation per hour of ephemeris age, m
+    pub strict: bool,
+}
+
+impl Default for PropagationConfig {
+    fn default() -> Self {
+        Self {
+            step: Duration::from_secs(1),
+            grid_end: GridEnd::Exclusive,
+            with_velocity: false,
+            with_clock: false,
+            healthy_only: false,
+            valid_only: false,
+            max_ephemeris_age: None,
+            kepler_tolerance: 1e-12,
+            kepler_max_iter: 30,
+            accuracy_growth: None,
+            strict: false,
+        }
+    }
+}
+
+impl PropagationConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn grid_end(mut self, grid_end: GridEnd) -> Self {
+        self.grid_end = grid_end;
+        self
+    }
+
+    /// Also compute ECEF velocity for every state
+    pub fn with_velocity(mut self, enabled: bool) -> Self {
+        self.with_velocity = enabled;
+        self
+    }
+
+    /// Also compute the SV clock offset (polynomial + relativistic term) for every state
+    pub fn with_clock(mut self, enabled: bool) -> Self {
+        self.with_clock = enabled;
+        self
+    }
+
+    /// Ignore records whose SV health word is non-zero
+    pub fn healthy_only(mut self, enabled: bool) -> Self {
+        self.healthy_only = enabled;
+        self
+    }
+
+    /// Ignore records with a validation error, which would evaluate to NaN or no real orbit
+    pub fn valid_only(mut self, enabled: bool) -> Self {
+        self.valid_only = enabled;
+        self
+    }
+
+    /// States further than this from the selected toe are flagged as extrapolated,
+    /// or rejected in strict mode
+    pub fn max_ephemeris_age(mut self, seconds: f64) -> Self {
+        self.max_ephemeris_age = Some(seconds);
+        self
+    }
+
+    /// Newton step size below which the Kepler solver stops
+    pub fn kepler_tolerance(mut self, tolerance: f64) -> Self {
+        self.kepler_tolerance = tolerance;
+        self
+    }
+
+    pub fn kepler_max_iter(mut self, max_iter: u32) -> Self {
+        self.kepler_max_iter = max_iter;
+        self
+    }
+
+    /// Inflate the URA-derived state accuracy by this many meters per hour from toe,
+    /// combined in quadrature
+    pub fn accuracy_growth(mut self, meters_per_hour: f64) -> Self {
+        self.accuracy_growth = Some(meters_per_hour);
+        self
+    }
+
+    /// Fail instead of silently degrading the output
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropagationError {
+    NoEphemeris,
+    EphemerisTooOld { gps_time: f64, age: f64 },
+    KeplerNotConverged { gps_time: f64 },
+    OutsideSpan { gps_time: f64 },
+    NotPropagated,                              // Nothing to extend
+    StepMismatch { expected: f64, found: f64 }, // Step of the stored states and the one given, s
+    NoRecordsForSatellite(gnss::SatId),         // The nav file has no record of the satellite
+    ZeroStep,
+    StepExceedsDuration { step: f64, duration: f64 }, // `GridEnd::Exclusive` would leave no epoch, s
+}
+
+impl fmt::Display for PropagationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoEphemeris => write!(f, "no usable ephemeris records"),
+            Self::EphemerisTooOld { gps_time, age } => write!(
+                f,
+                "ephemeris for GPS time {:.3} s is {:.0} s old",
+                gps_time, age
+            ),
+            Self::KeplerNotConverged { gps_time } => write!(
+                f,
+                "Kepler's equation did not converge at GPS time {:.3} s",
+                gps_time
+            ),
+            Self::OutsideSpan { gps_time } => write!(
+                f,
+                "GPS time {:.3} s is outside the propagated span",
+                gps_time
+            ),
+            Self::NotPropagated => write!(f, "no propagated states to extend"),
+            Self::StepMismatch { expected, found } => write!(
+                f,
+                "step of {} s does not match the {} s of the propagated states",
+                found, expected
+            ),
+            Self::NoRecordsForSatellite(sat_id) => write!(f, "no nav records for {}", sat_id),
+            Self::ZeroStep => write!(f, "propagation step is zero"),
+            Self::StepExceedsDuration { step, duration } => write!(
+                f,
+                "step of {} s is longer than the {} s duration, so no epoch fits",
+                step, duration
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PropagationError {}
+
+/// Eccentric anomalies from `Satellite::solve_kepler` with per-element diagnostics
+#[cfg(feature = "ndarray")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeplerSolution {
+    pub eccentric_anomaly: Array1<f64>,
+    pub iterations: Array1<u32>,
+    pub converged: Array1<bool>,
+}
+
+/// Summary of a `Satellite::propagate` run
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PropagationReport {
+    pub states: usize,
+    pub kepler_failures: usize, // States whose Kepler solve hit the iteration limit
+    pub fresh: usize,           // States within the maximum ephemeris age
+    pub extrapolated: usize,    // States beyond it
+}
+
+/// Options for `Satellite::ground_track_with`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GroundTrackOptions {
+    pub geocentric: bool,       // Geocentric instead of geodetic latitude
+    pub unwrap_longitude: bool, // Continuous longitudes instead of [-180, 180]
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Satellite {
+    pub id: gnss::SatId,
+    pub name: String,
+    pub norad_id: Option<u32>,
+    pub frequency_channel: Option<i8>, // GLONASS FDMA channel number k
+    pub active: bool,
+    pub states: gnss::StateSeries,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    workspace: Workspace,
+}
+
+/// Buffers `Satellite::propagate_into` keeps between runs, and the span of the stored
+/// states for `Satellite::extend`
+#[derive(Debug, Clone, Default)]
+struct Workspace {
+    span: Option<(f64, Duration)>, // First epoch, GPS s, and the duration asked for
+    gps_times: Vec<f64>,
+    records: Vec<ephemeris::PreparedEphemeris>, // The records in use, in toe order
+    segments: Vec<(usize, Range<usize>)>,       // Record index and the epochs it covers
+}
+
+/// Epochs of a propagation: `on_grid` multiples of the step from the start, then the end
+/// epoch when `GridEnd::Inclusive` puts it off the step grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct EpochGrid {
+    step: f64,
+    on_grid: usize,
+    end: Option<f64>, // Seconds after the start
+}
+
+impl EpochGrid {
+    pub(crate) fn new(
+        duration: Duration,
+        step: Duration,
+        grid_end: GridEnd,
+    ) -> Result<Self, PropagationError> {
+        if step.is_zero() {
+            return Err(PropagationError::ZeroStep);
+        }
+        let (duration_ns, step_ns) = (duration.as_nanos(), step.as_nanos());
+        let (whole, rest) = ((duration_ns / step_ns) as usize, duration_ns % step_ns);
+        let (on_grid, end) = match grid_end {
+            _ if duration.is_zero() => (1, None),
+            GridEnd::Exclusive if whole == 0 => {
+                return Err(PropagationError::StepExceedsDuration {
+                    step: step.as_secs_f64(),
+                    duration: duration.as_secs_f64(),
+                })
+            }
+            GridEnd::Exclusive => (whole, None),
+            GridEnd::Inclusive => (whole + 1, (rest > 0).then_some(duration.as_secs_f64())),
+            GridEnd::Snap => (whole + 1 + usize::from(2 * rest >= step_ns), None),
+        };
+        Ok(Self {
+            step: step.as_secs_f64(),
+            on_grid,
+            end,
+        })
+    }
+
+    /// Epochs from the `from`-th on as GPS seconds
+    pub(crate) fn times(&self, start_gps: f64, from: usize) -> impl Iterator<Item = f64> {
+        let step = self.step;
+        let end = self.end.filter(|_| from <= self.on_grid);
+        (from..self.on_grid)
+            .map(move |k| start_gps + step * k as f64)
+            .chain(end.map(|end| start_gps + end))
+    }
+}
+
+/// Chained setters for the optional `Satellite` metadata, from `Satellite::builder`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SatelliteBuilder {
+    id: gnss::SatId,
+    name: String,
+    norad_id: Option<u32>,
+    frequency_channel: Option<i8>,
+    active: bool,
+}
+
+impl SatelliteBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn norad(mut self, norad_id: u32) -> Self {
+        self.norad_id = Some(norad_id);
+        self
+    }
+
+    pub fn frequency_channel(mut self, channel: i8) -> Self {
+        self.frequency_channel = Some(channel);
+        self
+    }
+
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    pub fn build(self) -> Satellite {
+        Satellite {
+            id: self.id,
+            name: self.name,
+            norad_id: self.norad_id,
+            frequency_channel: self.frequency_channel,
+            active: self.active,
+            states: gnss::StateSeries::new(),
+            workspace: Workspace::default(),
+        }
+    }
+}
+
+impl fmt::Display for Satellite {
+    /// "G17 (BIIR-9)", or just "G17" when unnamed
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}", self.id)
+        } else {
+            write!(f, "{} ({})", self.id, self.name)
+        }
+    }
+}
+
+impl Satellite {
+    pub fn new(id: impl Into<gnss::SatId>, name: String) -> Self {
+        Self::builder(id).name(name).build()
+    }
+
+    pub fn builder(id: impl Into<gnss::SatId>) -> SatelliteBuilder {
+        SatelliteBuilder {
+            id: id.into(),
+            name: String::new(),
+            norad_id: None,
+            frequency_channel: None,
+            active: true,
+        }
+    }
+
+    pub fn constellation(&self) -> gnss::Constellation {
+        self.id.constellation
+    }
+
+    /// Propagate from borrowed records, such as `RinexNav::records_for(id)` or a slice of
+    /// them, into the stored states
+    pub fn propagate<'a>(
+        &mut self,
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+        ephemeris_data: impl IntoIterator<Item = &'a gnss::NavRecord>,
+    ) -> Result<PropagationReport, PropagationError> {
+        let mut states = std::mem::take(&mut self.states);
+        let report = self.propagate_into(start, duration, config, ephemeris_data, &mut states);
+        // Only a Kepler failure comes after the states are replaced
+        if let Ok(_) | Err(PropagationError::KeplerNotConverged { .. }) = report {
+            self.workspace.span = states.first().map(|first| (first.time(), duration));
+        }
+        self.states = states;
+        report
+    }
+
+    /// `propagate` over this satellite's records in a nav file, found through its
+    /// per-satellite grouping. Fails with `NoRecordsForSatellite` when the file has none.
+    pub fn propagate_from_nav(
+        &mut self,
+        nav: &gnss::RinexNav,
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+    ) -> Result<PropagationReport, PropagationError> {
+        match nav.records_for_slice(self.id) {
+            [] => Err(PropagationError::NoRecordsForSatellite(self.id)),
+            records => self.propagate(start, duration, config, records),
+        }
+    }
+
+    /// `propagate` into a caller's buffer, overwriting the states already in it and
+    /// truncating the rest, so repeated runs over grids of the same size allocate nothing
+    /// once warmed up. The satellite's own states are left alone; on an error before any
+    /// epoch is evaluated `out` is too.
+    pub fn propagate_into<'a>(
+        &mut self,
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+        ephemeris_data: impl IntoIterator<Item = &'a gnss::NavRecord>,
+        out: &mut gnss::StateSeries,
+    ) -> Result<PropagationReport, PropagationError> {
+        let started = Instant::now();
+        let grid = EpochGrid::new(duration, config.step, config.grid_end)?;
+        let times = grid.times(gnss::gps_seconds(start), 0);
+        let report = self.propagate_grid(times, config, ephemeris_data, out, 0)?;
+        info!(
+            "{}: propagated {} epochs in {:?}",
+            self.id,
+            report.states,
+            started.elapsed()
+        );
+        Ok(report)
+    }
+
+    /// Continue the stored states by `additional` on the same grid, as if the first
+    /// `propagate` had covered the longer span: the new epochs and the record chosen for
+    /// each are the same, across ephemeris handovers too. `config.step` must be the step
+    /// of the stored states. The report counts the new states only.
+    ///
+    /// Fails with `NotPropagated` when there are no states and `StepMismatch` when the step
+    /// differs; the states are left as they are then.
+    pub fn extend<'a>(
+        &mut self,
+        additional: Duration,
+        config: &PropagationConfig,
+        ephemeris_data: impl IntoIterator<Item = &'a gnss::NavRecord>,
+    ) -> Result<PropagationReport, PropagationError> {
+        let started = Instant::now();
+        let (first, mut kept) = match self.states.first() {
+            Some(first) => (first.time(), self.states.len()),
+            None => return Err(PropagationError::NotPropagated),
+        };
+        let step = config.step.as_secs_f64();
+        if let Some(second) = self.states.get(1) {
+            let found = second.time() - first;
+            if (found - step).abs() > EPOCH_TOLERANCE {
+                return Err(PropagationError::StepMismatch {
+                    expected: found,
+                    found: step,
+                });
+            }
+        }
+
+        // Part of a step left over at the end of the span counts towards the extension
+        let span = match self.workspace.span {
+            Some((start, span)) if start == first => span,
+            // The end epoch is among the states unless the grid excludes it
+            _ => match config.grid_end {
+                GridEnd::Exclusive => config.step * kept as u32,
+                GridEnd::Inclusive | GridEnd::Snap => config.step * (kept - 1) as u32,
+            },
+        };
+        let total = span + additional;
+        // An end epoch off the step grid is not on the longer grid, so it is evaluated anew
+        if let Ok(stored) = EpochGrid::new(span, config.step, config.grid_end) {
+            kept = kept.min(stored.on_grid);
+        }
+        let grid = EpochGrid::new(total, config.step, config.grid_end)?;
+        let times = grid.times(first, kept);
+        let mut states = std::mem::take(&mut self.states);
+        let report = self.propagate_grid(times, config, ephemeris_data, &mut states, kept);
+        if report.is_ok() || states.len() > kept {
+            self.workspace.span = Some((first, total));
+        }
+        self.states = states;
+        if let Ok(report) = &report {
+            info!(
+                "{}: extended by {} epochs in {:?}",
+                self.id,
+                report.states,
+                started.elapsed()
+            );
+        }
+        report
+    }
+
+    /// Evaluate the records over the grid epochs into `out` after its first `kept` states,
+    /// overwriting the states there and truncating the rest
+    fn propagate_grid<'a>(
+        &mut self,
+        grid: impl Iterator<Item = f64>,
+        config: &PropagationConfig,
+        ephemeris_data: impl IntoIterator<Item = &'a gnss::NavRecord>,
+        out: &mut gnss::StateSeries,
+        kept: usize,
+    ) -> Result<PropagationReport, PropagationError> {
+        let mut gps_times = std::mem::take(&mut self.workspace.gps_times);
+        gps_times.clear();
+        gps_times.extend(grid);
+        let report = self.propagate_times(&gps_times, config, ephemeris_data, out, kept);
+        self.workspace.gps_times = gps_times;
+        report
+    }
+
+    /// `propagate_grid` over epochs already laid out
+    fn propagate_times<'a>(
+        &mut self,
+        gps_times: &[f64],
+        config: &PropagationConfig,
+        ephemeris_data: impl IntoIterator<Item = &'a gnss::NavRecord>,
+        out: &mut gnss::StateSeries,
+        kept: usize,
+    ) -> Result<PropagationReport, PropagationError> {
+        let Workspace {
+            records, segments, ..
+        } = &mut self.workspace;
+        let (mut unhealthy, mut invalid) = (0, 0);
+        records.clear();
+        records.extend(
+            ephemeris_data
+                .into_iter()
+                .filter(|record| {
+                    let healthy = !config.healthy_only || record.is_healthy();
+                    let valid = !config.valid_only || record.is_valid();
+                    unhealthy += usize::from(!healthy);
+                    invalid += usize::from(healthy && !valid);
+                    healthy && valid
+                })
+                .map(gnss::NavRecord::prepare),
+        );
+        if unhealthy > 0 {
+            debug!("{}: {} unhealthy records left out", self.id, unhealthy);
+        }
+        if invalid > 0 {
+            debug!("{}: {} invalid records left out", self.id, invalid);
+        }
+        if records.is_empty() {
+            return Err(PropagationError::NoEphemeris);
+        }
+        // Records usually come in toe order already, which spares the sort its buffer
+        if !records.is_sorted_by(|a, b| a.toe_gps <= b.toe_gps) {
+            records.sort_by(|a, b| a.toe_gps.total_cmp(&b.toe_gps));
+        }
+
+        // Each record covers the epochs closer to its toe than to its neighbours' toes
+        segments.clear();
+        let mut block_start = 0;
+        for (k, record) in records.iter().enumerate() {
+            let toe = record.toe_gps;
+            let block_end = match records.get(k + 1) {
+                Some(next) => {
+                    let midpoint = toe + (next.toe_gps - toe) / 2.0;
+                    gps_times.partition_point(|&time| gnss::GpsTime::from_seconds(time) <= midpoint)
+                }
+                None => gps_times.len(),
+            };
+            let block_end = block_end.max(block_start);
+            if let (Some(max_age), true) = (config.max_ephemeris_age, config.strict) {
+                let age = |time: f64| (gnss::GpsTime::from_seconds(time) - toe).abs().as_f64();
+                let block = &gps_times[block_start..block_end];
+                if let Some(&time) = block.iter().find(|&&time| age(time) > max_age) {
+                    return Err(PropagationError::EphemerisTooOld {
+                        gps_time: time,
+                        age: age(time),
+                    });
+                }
+            }
+            if block_start < block_end {
+                debug!(
+                    "{}: record with toe {} covers {} epochs from GPS second {}",
+                    self.id,
+                    toe,
+                    block_end - block_start,
+                    gps_times[block_start]
+                );
+                segments.push((k, block_start..block_end));
+            }
+            block_start = block_end;
+        }
+
+        // The segments cover the whole grid, so every state after the kept ones is overwritten
+        let mut columns = out.columns_after(
+            kept,
+            gps_times.len(),
+            config.with_velocity,
+            config.with_clock,
+        );
+        let mut report = PropagationReport::default();
+        for (k, range) in segments.iter() {
+            let (record, times) = (&records[*k], &gps_times[range.clone()]);
+            let (block, rest) = columns.split_at(times.len());
+            columns = rest;
+            #[cfg(not(feature = "rayon"))]
+            {
+                report.kepler_failures += Self::evaluate_block(record, times, config, block);
+            }
+            #[cfg(feature = "rayon")]
+            {
+                report.kepler_failures += Self::evaluate_parallel(record, times, config, block);
+            }
+        }
+
+        Self::count_freshness(&out.extrapolated()[kept..], &mut report);
+        if config.strict {
+            let converged = &out.kepler_converged()[kept..];
+            if let Some(idx) = converged.iter().position(|&converged| !converged) {
+                return Err(PropagationError::KeplerNotConverged {
+                    gps_time: out.times()[kept + idx],
+                });
+            }
+        }
+
+        if report.kepler_failures > 0 {
+            warn!(
+                "{}: Kepler's equation did not converge at {} of {} epochs",
+                self.id,
+                report.kepler_failures,
+                out.len() - kept
+            );
+        }
+        if report.extrapolated > 0 {
+            debug!(
+                "{}: {} states are past the maximum ephemeris age",
+                self.id, report.extrapolated
+            );
+        }
+        report.states = out.len() - kept;
+        Ok(report)
+    }
+
+    /// Sample any orbit model over the grid `propagate` lays out for the same config,
+    /// replacing the stored states. The step and grid end come from `config`; what each
+    /// state holds is up to the propagator.
+    pub fn propagate_with(
+        &mut self,
+        start: DateTime<Utc>,
+        duration: Duration,
+        config: &PropagationConfig,
+        propagator: &impl OrbitPropagator,
+    ) -> Result<PropagationReport, PropagationError> {
+        let grid = EpochGrid::new(duration, config.step, config.grid_end)?;
+        let gps_times = grid.times(gnss::gps_seconds(start), 0);
+        self.workspace.span = None;
+        self.states.clear();
+        let mut report = PropagationReport::default();
+        for time in gps_times {
+            let state = propagator.state_at(gnss::GpsTime::from_seconds(time))?;
+            if !state.kepler_converged {
+                report.kepler_failures += 1;
+            }
+            self.states.push(&state);
+        }
+        self.workspace.span = self.states.first().map(|first| (first.time(), duration));
+        if report.kepler_failures > 0 {
+            warn!(
+                "{}: orbit model did not converge at {} of {} epochs",
+                self.id,
+                report.kepler_failures,
+                self.states.len()
+            );
+        }
+        Self::count_freshness(self.states.extrapolated(), &mut report);
+        report.states = self.states.len();
+        Ok(report)
+    }
+
+    fn count_freshness(extrapolated: &[bool], report: &mut PropagationReport) {
+        report.extrapolated = extrapolated
+            .iter()
+            .filter(|&&extrapolated| extrapolated)
+            .count();
+        report.fresh = extrapolated.len() - report.extrapolated;
+    }
+
+    /// Position at an arbitrary epoch inside the propagated span. Uses cubic Hermite
+    /// interpolation when velocities were propagated, Lagrange over positions otherwise.
+    pub fn interpolate_at(&self, epoch: DateTime<Utc>) -> Result<gnss::ECEF, PropagationError> {
+        let time = gnss::gps_seconds(epoch);
+        let outside = PropagationError::OutsideSpan { gps_time: time };
+        let (times, positions) = (self.states.times(), self.states.positions());
+        let (first, last) = match (times.first(), times.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return Err(outside),
+        };
+        if time < first || time > last {
+            return Err(outside);
+        }
+
+        let idx = times.partition_point(|&state_time| state_time < time);
+        if times[idx] == time {
+            return Ok(positions[idx]);
+        }
+        let velocities = self.states.velocities();
+        if !velocities.is_empty() {
+            let (t0, t1) = (times[idx - 1], times[idx]);
+            let (p0, p1) = (positions[idx - 1], positions[idx]);
+            let (v0, v1) = (velocities[idx - 1], velocities[idx]);
+            return Ok(Self::hermite((t0, p0, v0), (t1, p1, v1), time));
+        }
+
+        // Lagrange polynomial over the nearest positions
+        let half = LAGRANGE_POINTS / 2;
+        let start = idx
+            .saturating_sub(half)
+            .min(times.len().saturating_sub(LAGRANGE_POINTS));
+        let window = start..(start + LAGRANGE_POINTS).min(times.len());
+        let mut position = gnss::ECEF::default();
+        for j in window.clone() {
+            let weight: f64 = window
+                .clone()
+                .filter(|&k| k != j)
+                .map(|k| (time - times[k]) / (times[j] - times[k]))
+                .product();
+            position.x += weight * positions[j].x;
+            position.y += weight * positions[j].y;
+            position.z += weight * positions[j].z;
+        }
+        Ok(position)
+    }
+
+    /// Propagated state at exactly this epoch (to within a microsecond)
+    pub fn state_at(&self, epoch: DateTime<Utc>) -> Option<gnss::StateRef<'_>> {
+        let time = gnss::gps_seconds(epoch);
+        let times = self.states.times();
+        let idx = times.partition_point(|&state_time| state_time < time - EPOCH_TOLERANCE);
+        self.states
+            .get(idx)
+            .filter(|state| (state.time() - time).abs() <= EPOCH_TOLERANCE)
+    }
+
+    /// Propagated state closest in time to this epoch, None only if there are no states
+    pub fn state_nearest(&self, epoch: DateTime<Utc>) -> Option<gnss::StateRef<'_>> {
+        let time = gnss::gps_seconds(epoch);
+        let idx = self
+            .states
+            .times()
+            .partition_point(|&state_time| state_time < time);
+        let after = self.states.get(idx);
+        let before = idx.checked_sub(1).and_then(|idx| self.states.get(idx));
+        match (before, after) {
+            (Some(before), Some(after)) if time - before.time() <= after.time() - time => {
+                Some(before)
+            }
+            (_, Some(after)) => Some(after),
+            (before, None) => before,
+        }
+    }
+
+    /// States with start <= epoch <= end, empty if the range misses the propagated span
+    pub fn states_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> gnss::StateIter<'_> {
+        let start = gnss::gps_seconds(start);
+        let end = gnss::gps_seconds(end);
+        let times = self.states.times();
+        let first = times.partition_point(|&time| time < start - EPOCH_TOLERANCE);
+        let last = times.partition_point(|&time| time <= end + EPOCH_TOLERANCE);
+        self.states.range(first..last.max(first))
+    }
+
+    fn hermite(
+        (t0, p0, v0): (f64, gnss::ECEF, gnss::ECEF),
+        (t1, p1, v1): (f64, gnss::ECEF, gnss::ECEF),
+        time: f64,
+    ) -> gnss::ECEF {
+        let h = t1 - t0;
+        let s = (time - t0) / h;
+        let h00 = 2.0 * s.powi(3) - 3.0 * s.powi(2) + 1.0;
+        let h10 = s.powi(3) - 2.0 * s.powi(2) + s;
+        let h01 = -2.0 * s.powi(3) + 3.0 * s.powi(2);
+        let h11 = s.powi(3) - s.powi(2);
+        gnss::ECEF::new(
+            h00 * p0.x + h10 * h * v0.x + h01 * p1.x + h11 * h * v1.x,
+            h00 * p0.y + h10 * h * v0.y + h01 * p1.y + h11 * h * v1.y,
+            h00 * p0.z + h10 * h * v0.z + h01 * p1.z + h11 * h * v1.z,
+        )
+    }
+
+    /// Geodetic subsatellite point of every propagated state, longitudes in [-180, 180]
+    pub fn ground_track(&self) -> Vec<(DateTime<Utc>, gnss::LLA)> {
+        self.ground_track_with(GroundTrackOptions::default())
+    }
+
+    pub fn ground_track_with(
+        &self,
+        options: GroundTrackOptions,
+    ) -> Vec<(DateTime<Utc>, gnss::LLA)> {
+        let mut track: Vec<(DateTime<Utc>, gnss::LLA)> = Vec::with_capacity(self.states.len());
+        for (epoch, position) in &self.states {
+            let mut lla = position.to_lla();
+            if options.geocentric {
+                lla.latitude = position.z.atan2(position.x.hypot(position.y)).to_degrees();
+            }
+            if options.unwrap_longitude {
+                if let Some((_, previous)) = track.last() {
+                    let jump = lla.longitude - previous.longitude;
+                    lla.longitude -= 360.0 * (jump / 360.0).round();
+                }
+            }
+            track.push((epoch.to_utc(), lla));
+        }
+        track
+    }
+
+    /// Evaluate one record over a contiguous slice of epochs into as many states of the
+    /// columns, leaving out velocity and clock when their columns are empty. Returns how
+    /// many of them failed to converge in the Kepler solver.
+    ///
+    /// Epochs go through in strips that stay in cache, one pass per stage, so the passes
+    /// without calls vectorize. Per epoch only Kepler's equation, the eccentric anomaly and
+    /// the node take sin/cos: the argument of latitude and the inclination follow by angle
+    /// addition, with their small harmonic corrections by series.
+    pub(crate) fn evaluate_block(
+        prepared: &ephemeris::PreparedEphemeris,
+        times: &[f64],
+        config: &PropagationConfig,
+        states: gnss::StateColumnsMut<'_>,
+    ) -> usize {
+        debug_assert_eq!(times.len(), states.len());
+        let with_velocity = !states.velocity.is_empty();
+        let with_clock = !states.clock_bias.is_empty();
+        let record = &prepared.record;
+        let (a, n, sqrt_1_minus_e2) = (prepared.a, prepared.n, prepared.sqrt_1_minus_e2);
+        let e = record.eccentricity;
+        let (omega_rate, earth_rotation) = (prepared.omega_rate, prepared.earth_rotation);
+        let (sin_w, cos_w) = record.omega.sin_cos();
+        let (sin_i0, cos_i0) = record.i0.sin_cos();
+        let ura = record.ura_meters();
+        let mut failures = 0;
+
+        let mut tk = [0.0; STRIP_LEN];
+        let (mut sin_e, mut cos_e) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let (mut sin_omega, mut cos_omega) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let mut converged = [false; STRIP_LEN];
+        let (mut sin_phi, mut cos_phi) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let (mut sin_2phi, mut cos_2phi) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let (mut delta_u, mut delta_i) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let (mut r, mut sin_u, mut cos_u) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let (mut sin_i, mut cos_i) = ([0.0; STRIP_LEN], [0.0; STRIP_LEN]);
+        let mut position = [[0.0; STRIP_LEN]; 3];
+        let mut velocity = [[0.0; STRIP_LEN]; 3];
+        let mut clock_bias = [0.0; STRIP_LEN];
+
+        let mut rest = states;
+        for times in times.chunks(STRIP_LEN) {
+            let len = times.len();
+            let (states, strip_rest) = rest.split_at(len);
+            rest = strip_rest;
+
+            // Mean anomaly, Kepler's equation and the node, the calls of the strip
+            for k in 0..len {
+                tk[k] = (gnss::GpsTime::from_seconds(times[k]) - prepared.toe_gps).as_f64();
+                let m = record.m0 + n * tk[k];
+                let (e_anomaly, _, done) =
+                    Self::kepler_newton(m, e, config.kepler_tolerance, config.kepler_max_iter);
+                (sin_e[k], cos_e[k]) = e_anomaly.sin_cos();
+                converged[k] = done;
+                let omega = record.omega0 + omega_rate * tk[k] - earth_rotation * record.toe;
+                (sin_omega[k], cos_omega[k]) = omega.sin_cos();
+            }
+
+            // True anomaly from E, then the argument of latitude phi = nu + omega and the
+            // second-harmonic corrections
+            for k in 0..len {
+                let one_minus_e_cos_e = 1.0 - e * cos_e[k];
+                let sin_nu = sqrt_1_minus_e2 * sin_e[k] / one_minus_e_cos_e;
+                let cos_nu = (cos_e[k] - e) / one_minus_e_cos_e;
+                sin_phi[k] = sin_nu * cos_w + cos_nu * sin_w;
+                cos_phi[k] = cos_nu * cos_w - sin_nu * sin_w;
+                sin_2phi[k] = 2.0 * sin_phi[k] * cos_phi[k];
+                cos_2phi[k] = (cos_phi[k] - sin_phi[k]) * (cos_phi[k] + sin_phi[k]);
+
+                delta_u[k] = record.cus * sin_2phi[k] + record.cuc * cos_2phi[k];
+                r[k] =
+                    a * one_minus_e_cos_e + (record.crs * sin_2phi[k] + record.crc * cos_2phi[k]);
+                delta_i[k] =
+                    record.cis * sin_2phi[k] + record.cic * cos_2phi[k] + record.idot * tk[k];
+
+                let (sin_du, cos_du) = small_sin_cos(delta_u[k]);
+                sin_u[k] = sin_phi[k] * cos_du + cos_phi[k] * sin_du;
+                cos_u[k] = cos_phi[k] * cos_du - sin_phi[k] * sin_du;
+                let (sin_di, cos_di) = small_sin_cos(delta_i[k]);
+                sin_i[k] = sin_i0 * cos_di + cos_i0 * sin_di;
+                cos_i[k] = cos_i0 * cos_di - sin_i0 * sin_di;
+            }
+
+            // Corrections too large for the series, only from implausible records
+            for k in 0..len {
+                if delta_u[k].abs() >= SERIES_LIMIT {
+                    let u = sin_phi[k].atan2(cos_phi[k]) + delta_u[k];
+                    (sin_u[k], cos_u[k]) = u.sin_cos();
+                }
+                if delta_i[k].abs() >= SERIES_LIMIT {
+                    (sin_i[k], cos_i[k]) = (record.i0 + delta_i[k]).sin_cos();
+                }
+            }
+
+            // Position in the orbital plane, rotated to ECEF
+            let [x_ecef, y_ecef, z_ecef] = &mut position;
+            for k in 0..len {
+                let (x, y) = (r[k] * cos_u[k], r[k] * sin_u[k]);
+                x_ecef[k] = x * cos_omega[k] - y * cos_i[k] * sin_omega[k];
+                y_ecef[k] = x * sin_omega[k] + y * cos_i[k] * cos_omega[k];
+                z_ecef[k] = y * sin_i[k];
+            }
+
+            // Velocity from the time derivatives of the same terms
+            if with_velocity {
+                let [vx, vy, vz] = &mut velocity;
+                for k in 0..len {
+                    let one_minus_e_cos_e = 1.0 - e * cos_e[k];
+                    let e_dot = n / one_minus_e_cos_e;
+                    let nu_dot = e_dot * sqrt_1_minus_e2 / one_minus_e_cos_e;
+                    let u_dot = nu_dot
+                        * (1.0 + 2.0 * (record.cus * cos_2phi[k] - record.cuc * sin_2phi[k]));
+                    let r_dot = a * e * sin_e[k] * e_dot
+                        + 2.0 * (record.crs * cos_2phi[k] - record.crc * sin_2phi[k]) * nu_dot;
+                    let i_dot = record.idot
+                        + 2.0 * (record.cis * cos_2phi[k] - record.cic * sin_2phi[k]) * nu_dot;
+
+                    let y = r[k] * sin_u[k];
+                    let x_dot = r_dot * cos_u[k] - r[k] * u_dot * sin_u[k];
+                    let y_dot = r_dot * sin_u[k] + r[k] * u_dot * cos_u[k];
+                    vx[k] = x_dot * cos_omega[k] - y_dot * cos_i[k] * sin_omega[k]
+                        + y * sin_i[k] * sin_omega[k] * i_dot
+                        - y_ecef[k] * omega_rate;
+                    vy[k] = x_dot * sin_omega[k] + y_dot * cos_i[k] * cos_omega[k]
+                        - y * sin_i[k] * cos_omega[k] * i_dot
+                        + x_ecef[k] * omega_rate;
+                    vz[k] = y_dot * sin_i[k] + y * cos_i[k] * i_dot;
+                }
+            }
+
+            // BeiDou GEO, from the inclined frame of its model into ECEF
+            if prepared.geo {
+                let [vx, vy, vz] = &mut velocity;
+                for k in 0..len {
+                    let (p, v) = prepared.geo_to_ecef(
+                        gnss::ECEF::new(x_ecef[k], y_ecef[k], z_ecef[k]),
+                        gnss::ECEF::new(vx[k], vy[k], vz[k]),
+                        tk[k],
+                    );
+                    [x_ecef[k], y_ecef[k], z_ecef[k]] = [p.x, p.y, p.z];
+                    [vx[k], vy[k], vz[k]] = [v.x, v.y, v.z];
+                }
+            }
+
+            // SV clock offset: polynomial plus relativistic correction, TGD not applied
+            if with_clock {
+                for k in 0..len {
+                    let dt = (gnss::GpsTime::from_seconds(times[k]) - prepared.toc_gps).as_f64();
+                    clock_bias[k] = record.sv_clock_bias
+                        + record.sv_clock_drift * dt
+                        + record.sv_clock_drift_rate * dt * dt
+                        + gnss::REL_F * e * record.sqrt_a * sin_e[k];
+                }
+            }
+
+            // Store states
+            states.time.copy_from_slice(times);
+            for (k, position) in states.position.iter_mut().enumerate() {
+                *position = gnss::ECEF::new(x_ecef[k], y_ecef[k], z_ecef[k]);
+            }
+            let [vx, vy, vz] = &velocity;
+            for (k, velocity) in states.velocity.iter_mut().enumerate() {
+                *velocity = gnss::ECEF::new(vx[k], vy[k], vz[k]);
+            }
+            if with_clock {
+                states.clock_bias.copy_from_slice(&clock_bias[..len]);
+            }
+            states.kepler_converged.copy_from_slice(&converged[..len]);
+            let toe = prepared.toe_gps;
+            for (age, time) in states.ephemeris_age.iter_mut().zip(times) {
+                *age = (gnss::GpsTime::from_seconds(*time) - toe).as_f64();
+            }
+            for (extrapolated, age) in states.extrapolated.iter_mut().zip(&*states.ephemeris_age) {
+                *extrapolated = config
+                    .max_ephemeris_age
+                    .is_some_and(|max_age| age.abs() > max_age);
+            }
+            for (accuracy, age) in states.accuracy.iter_mut().zip(&*states.ephemeris_age) {
+                *accuracy = ura.map(|ura| match config.accuracy_growth {
+                    Some(rate) => {
+                        let age_hours = age.abs() / 3600.0;
+                        ura.hypot(rate * age_hours)
+                    }
+                    None => ura,
+                });
+            }
+            failures += converged[..len].iter().filter(|&&done| !done).count();
+        }
+        failures
+    }
+
+    /// `evaluate_block` split into work items of `PARALLEL_CHUNK_LEN` epochs across threads
+    #[cfg(feature = "rayon")]
+    fn evaluate_parallel(
+        record: &ephemeris::PreparedEphemeris,
+        times: &[f64],
+        config: &PropagationConfig,
+        states: gnss::StateColumnsMut<'_>,
+    ) -> usize {
+        if times.len() <= PARALLEL_CHUNK_LEN {
+            return Self::evaluate_block(record, times, config, states);
+        }
+        let mid = times.len() / 2 / PARALLEL_CHUNK_LEN * PARALLEL_CHUNK_LEN;
+        let mid = mid.max(PARALLEL_CHUNK_LEN);
+        let (times, times_rest) = times.split_at(mid);
+        let (states, states_rest) = states.split_at(mid);
+        let (failures, failures_rest) = rayon::join(
+            || Self::evaluate_parallel(record, times, config, states),
+            || Self::evaluate_parallel(record, times_rest, config, states_rest),
+        );
+        failures + failures_rest
+    }
+
+    /// Solve Kepler's equation E - e*sin(E) = M element-wise with Newton-Raphson
+    #[cfg(feature = "ndarray")]
+    pub fn solve_kepler(
+        m: &ArrayView1<f64>,
+        e: f64,
+        tolerance: f64,
+        max_iter: u32,
+    ) -> KeplerSolution {
+        let mut solution = KeplerSolution {
+            eccentric_anomaly: Array1::zeros(m.len()),
+            iterations: Array1::zeros(m.len()),
+            converged: Array1::from_elem(m.len(), false),
+        };
+        for (idx, &m_val) in m.iter().enumerate() {
+            let (e_val, iterations, converged) = Self::kepler_newton(m_val, e, tolerance, max_iter);
+            solution.eccentric_anomaly[idx] = e_val;
+            solution.iterations[idx] = iterations;
+            solution.converged[idx] = converged;
+        }
+        solution
+    }
+
+    /// Scalar Newton-Raphson solve returning (E, iterations, converged)
+    pub fn kepler_newton(m: f64, e: f64, tolerance: f64, max_iter: u32) -> (f64, u32, bool) {
+        ephemeris::solve_kepler(m, e, tolerance, max_iter)
+    }
+}
+
+/// sin and cos of an angle below `SERIES_LIMIT` by their Taylor series, exact to double
+/// precision, with no calls so that it vectorizes
+#[inline]
+fn small_sin_cos(x: f64) -> (f64, f64) {
+    let x2 = x * x;
+    let sin = x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0)));
+    let cos = 1.0 - x2 / 2.0 * (1.0 - x2 / 12.0 * (1.0 - x2 / 30.0 * (1.0 - x2 / 56.0)));
+    (sin, cos)
+}
+
+/// Break a track into segments that each stay within [-180°, 180°], ending and restarting
+/// at the antimeridian with the latitude and altitude interpolated there
+pub fn split_at_antimeridian(points: &[gnss::LLA]) -> Vec<Vec<gnss::LLA>> {
+    let mut segments: Vec<Vec<gnss::LLA>> = Vec::new();
+    let mut current: Vec<gnss::LLA> = Vec::new();
+    for point in points {
+        if let Some(previous) = current.last().copied() {
+            let jump = point.longitude - previous.longitude;
+            if jump.abs() > 180.0 {
+                // Crossing westward lands below -180°, eastward above 180°
+                let edge = if jump > 0.0 { -180.0 } else { 180.0 };
+                let unwrapped = point.longitude - 360.0 * jump.signum();
+                let t = (edge - previous.longitude) / (unwrapped - previous.longitude);
+                let latitude = previous.latitude + (point.latitude - previous.latitude) * t;
+                let altitude = previous.altitude + (point.altitude - previous.altitude) * t;
+                current.push(gnss::LLA::new(latitude, edge, altitude));
+                segments.push(std::mem::take(&mut current));
+                current.push(gnss::LLA::new(latitude, -edge, altitude));
+            }
+        }
+        current.push(*point);
+    }
+    if current.len() > 1 || segments.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+#[cfg(feature = "serde")]
+impl Satellite {
+    /// Propagated states as a JSON array
+    pub fn states_to_json(&self) -> serde_json::Result<String> {
+        let states: Vec<gnss::State> = self.states.iter_states().collect();
+        serde_json::to_string(&states)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::propagator::BroadcastPropagator;
+    use crate::test_support::NAV;
+    use chrono::TimeZone;
+
+    fn records(prn: u8) -> Vec<gnss::NavRecord> {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        nav.records_for_slice(prn.into()).to_vec()
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap()
+    }
+
+    fn propagate(
+        prn: u8,
+        duration: Duration,
+        config: &PropagationConfig,
+    ) -> Result<(Satellite, PropagationReport), PropagationError> {
+        let mut satellite = Satellite::builder(prn).build();
+        let report = satellite.propagate(start(), duration, config, &records(prn))?;
+        Ok((satellite, report))
+    }
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn builder_sets_each_field() {
+        let satellite = Satellite::builder("R07".parse::<gnss::SatId>().unwrap())
+            .name("GLONASS-M 745")
+            .norad(37868)
+            .frequency_channel(5)
+            .active(false)
+            .build();
+        assert_eq!(satellite.id, "R07".parse().unwrap());
+        assert_eq!(satellite.name, "GLONASS-M 745");
+        assert_eq!(satellite.norad_id, Some(37868));
+        assert_eq!(satellite.frequency_channel, Some(5));
+        assert!(!satellite.active);
+        assert!(satellite.states.is_empty());
+
+        let plain = Satellite::builder(17).build();
+        assert_eq!(plain.id, gnss::SatId::from(17));
+        assert!(plain.name.is_empty());
+        assert_eq!((plain.norad_id, plain.frequency_channel), (None, None));
+        assert!(plain.active);
+        assert_eq!(Satellite::new(17, "BIIR-9".into()).name, "BIIR-9");
+    }
+
+    #[test]
+    fn display_adds_the_name_when_there_is_one() {
+        assert_eq!(
+            Satellite::new(17, "BIIR-9".into()).to_string(),
+            "G17 (BIIR-9)"
+        );
+        assert_eq!(Satellite::builder(17).build().to_string(), "G17");
+    }
+
+    #[test]
+    fn step_spaces_the_epochs() {
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        let (satellite, report) = propagate(17, HOUR, &config).unwrap();
+        assert_eq!(report.states, 60);
+        let times = satellite.states.times();
+        assert_eq!(times[0], gnss::gps_seconds(start()));
+        assert!(times.windows(2).all(|pair| pair[1] - pair[0] == 60.0));
+    }
+
+    #[test]
+    fn states_are_labelled_with_the_utc_of_the_grid() {
+        let config = PropagationConfig::new().step(Duration::from_secs(90));
+        let (satellite, _) = propagate(17, HOUR, &config).unwrap();
+        let states = &satellite.states;
+        let expected: Vec<DateTime<Utc>> = (0..40)
+            .map(|k| start() + chrono::Duration::seconds(90 * k))
+            .collect();
+        assert_eq!(states.times_utc(), expected);
+        // GPS time runs 18 s ahead of UTC in 2023
+        let gps = states.times_gps();
+        assert_eq!(gps[0], gnss::GpsTime::from_week_seconds(2266, 93_618.0));
+        assert!(gps
+            .iter()
+            .zip(&expected)
+            .all(|(gps, utc)| gps.to_utc() == *utc));
+
+        let mut count = 0;
+        for ((epoch, position), state) in states.into_iter().zip(states.iter()) {
+            assert_eq!((epoch, position), (state.epoch(), state.position()));
+            count += 1;
+        }
+        assert_eq!(count, 40);
+        assert_eq!(states.positions().len(), 40);
+
+        let line = states.first().unwrap().to_string();
+        assert!(line.starts_with("2023-06-12T02:00:00.000Z ("), "{}", line);
+        assert!(line.ends_with(") m"));
+        assert_eq!(
+            states.get(1).unwrap().to_state().to_string()[..24],
+            *"2023-06-12T02:01:30.000Z"
+        );
+    }
+
+    #[test]
+    fn propagating_from_a_nav_file_picks_the_satellites_records() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let config = PropagationConfig::new().step(Duration::from_secs(300));
+        let mut from_nav = Satellite::builder(17).build();
+        let report = from_nav
+            .propagate_from_nav(&nav, start(), HOUR, &config)
+            .unwrap();
+        let (from_records, expected) = propagate(17, HOUR, &config).unwrap();
+        assert_eq!(report, expected);
+        assert!(from_nav.states == from_records.states);
+
+        let mut galileo = Satellite::builder("E11".parse::<gnss::SatId>().unwrap()).build();
+        match galileo.propagate_from_nav(&nav, start(), HOUR, &config) {
+            Err(PropagationError::NoRecordsForSatellite(sat_id)) => {
+                assert_eq!(sat_id.to_string(), "E11")
+            }
+            result => panic!("{:?}", result),
+        }
+        assert!(galileo.states.is_empty());
+    }
+
+    #[test]
+    fn grid_end_places_the_last_epoch() {
+        let duration = Duration::from_secs(3630);
+        let count = |grid_end| {
+            let config = PropagationConfig::new()
+                .step(Duration::from_secs(60))
+                .grid_end(grid_end);
+            let (satellite, _) = propagate(17, duration, &config).unwrap();
+            let times = satellite.states.times();
+            (times.len(), times[times.len() - 1] - times[0])
+        };
+        assert_eq!(count(GridEnd::Exclusive), (60, 3540.0));
+        assert_eq!(count(GridEnd::Inclusive), (62, 3630.0));
+        assert_eq!(count(GridEnd::Snap), (62, 3660.0));
+    }
+
+    #[test]
+    fn grid_end_modes_give_these_epochs() {
+        let epochs = |duration: u64, grid_end| {
+            let config = PropagationConfig::new()
+                .step(Duration::from_secs(60))
+                .grid_end(grid_end);
+            let (satellite, _) = propagate(17, Duration::from_secs(duration), &config).unwrap();
+            let times = satellite.states.times();
+            times.iter().map(|t| t - times[0]).collect::<Vec<_>>()
+        };
+        // One epoch per whole step, the partial step after 120 s dropped with the end
+        assert_eq!(epochs(150, GridEnd::Exclusive), [0.0, 60.0]);
+        assert_eq!(epochs(150, GridEnd::Inclusive), [0.0, 60.0, 120.0, 150.0]);
+        assert_eq!(epochs(150, GridEnd::Snap), [0.0, 60.0, 120.0, 180.0]);
+        assert_eq!(epochs(130, GridEnd::Snap), [0.0, 60.0, 120.0]);
+        // A whole number of steps only differs in whether the end is kept
+        assert_eq!(epochs(120, GridEnd::Exclusive), [0.0, 60.0]);
+        assert_eq!(epochs(120, GridEnd::Inclusive), [0.0, 60.0, 120.0]);
+        assert_eq!(epochs(120, GridEnd::Snap), [0.0, 60.0, 120.0]);
+        // Less than a step
+        assert_eq!(epochs(30, GridEnd::Inclusive), [0.0, 30.0]);
+        assert_eq!(epochs(30, GridEnd::Snap), [0.0, 60.0]);
+        assert_eq!(epochs(29, GridEnd::Snap), [0.0]);
+        for grid_end in [GridEnd::Exclusive, GridEnd::Inclusive, GridEnd::Snap] {
+            assert_eq!(epochs(0, grid_end), [0.0], "{:?}", grid_end);
+        }
+    }
+
+    #[test]
+    fn degenerate_steps_are_errors() {
+        let config = PropagationConfig::new().step(Duration::ZERO);
+        assert_eq!(
+            propagate(17, HOUR, &config).err(),
+            Some(PropagationError::ZeroStep)
+        );
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        assert_eq!(
+            propagate(17, Duration::from_secs(30), &config).err(),
+            Some(PropagationError::StepExceedsDuration {
+                step: 60.0,
+                duration: 30.0
+            })
+        );
+    }
+
+    #[test]
+    fn with_velocity_adds_velocities_matching_the_positions() {
+        let config = PropagationConfig::new();
+        let (satellite, _) = propagate(17, HOUR, &config).unwrap();
+        assert!(satellite.states.velocities().is_empty());
+        assert!(satellite.states.first().unwrap().velocity().is_none());
+
+        let (satellite, _) = propagate(17, HOUR, &config.with_velocity(true)).unwrap();
+        let states = &satellite.states;
+        assert_eq!(states.velocities().len(), states.len());
+        // Central difference over one-second steps, away from the handover to the next record
+        let ages = states.ephemeris_ages();
+        for k in (1..states.len() - 1).filter(|&k| ages[k + 1] - ages[k - 1] == 2.0) {
+            let (before, after) = (states.positions()[k - 1], states.positions()[k + 1]);
+            let velocity = states.velocities()[k];
+            assert!(((after.x - before.x) / 2.0 - velocity.x).abs() < 1e-3);
+            assert!(((after.y - before.y) / 2.0 - velocity.y).abs() < 1e-3);
+            assert!(((after.z - before.z) / 2.0 - velocity.z).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn interpolation_at_a_30_s_step_is_sub_millimetre() {
+        // One record, so the truth has no handover for the interpolants to jump over
+        let records = &records(17)[..1];
+        let propagate = |step: u64, velocity: bool| {
+            let config = PropagationConfig::new()
+                .step(Duration::from_secs(step))
+                .with_velocity(velocity);
+            let mut satellite = Satellite::builder(17).build();
+            satellite
+                .propagate(start(), HOUR, &config, records)
+                .unwrap();
+            satellite
+        };
+        let truth = propagate(1, false);
+        for coarse in [propagate(30, true), propagate(30, false)] {
+            let last = *coarse.states.times().last().unwrap();
+            for state in truth.states.iter().filter(|state| state.time() <= last) {
+                let interpolated = coarse.interpolate_at(state.time_utc()).unwrap();
+                assert!(interpolated.approx_eq(&state.position(), 1e-3));
+            }
+            let after = gnss::gps_seconds_to_utc(last + 1.0);
+            assert!(matches!(
+                coarse.interpolate_at(after),
+                Err(PropagationError::OutsideSpan { .. })
+            ));
+            let before = start() - chrono::Duration::seconds(1);
+            assert!(coarse.interpolate_at(before).is_err());
+        }
+    }
+
+    #[test]
+    fn ground_track_stays_within_the_inclination() {
+        let config = PropagationConfig::new().step(Duration::from_secs(300));
+        let (satellite, _) = propagate(17, 12 * HOUR, &config).unwrap();
+        let inclination = records(17)[0].i0.to_degrees();
+        assert!((inclination - 55.0).abs() < 1.0);
+
+        let geocentric = satellite.ground_track_with(GroundTrackOptions {
+            geocentric: true,
+            ..Default::default()
+        });
+        assert_eq!(geocentric.len(), 144);
+        assert!(geocentric
+            .iter()
+            .all(|(_, lla)| lla.latitude.abs() <= inclination + 0.01));
+        // A full orbit reaches both extremes
+        let highest = geocentric
+            .iter()
+            .map(|(_, lla)| lla.latitude)
+            .fold(0.0, f64::max);
+        assert!(highest > inclination - 1.0);
+        // Geodetic latitudes run a little higher than geocentric, wrapped longitudes jump
+        let track = satellite.ground_track();
+        assert!(track
+            .iter()
+            .all(|(_, lla)| lla.latitude.abs() <= inclination + 0.1));
+        assert!(track.iter().all(|(_, lla)| lla.longitude.abs() <= 180.0));
+
+        let unwrapped = satellite.ground_track_with(GroundTrackOptions {
+            unwrap_longitude: true,
+            ..Default::default()
+        });
+        assert!(unwrapped
+            .windows(2)
+            .all(|pair| (pair[1].1.longitude - pair[0].1.longitude).abs() < 180.0));
+    }
+
+    #[test]
+    fn beidou_batch_states_match_single_epochs() {
+        let meo = gnss::NavRecord {
+            sat_id: "C20".parse().unwrap(),
+            gps_week: 910.0,
+            toe: 345_600.0,
+            sqrt_a: 5282.6,
+            eccentricity: 0.002,
+            delta_n: 4e-9,
+            i0: 0.96,
+            omega0: 1.0,
+            omega: 0.2,
+            m0: 0.3,
+            cus: 1e-5,
+            crs: 30.0,
+            ..Default::default()
+        };
+        // The GEO path rotates the states into ECEF after the rest
+        let geo = gnss::NavRecord {
+            sat_id: "C03".parse().unwrap(),
+            sqrt_a: 6493.4,
+            i0: 0.09,
+            ..meo
+        };
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(60))
+            .with_velocity(true);
+        for record in [meo, geo] {
+            let mut satellite = Satellite::builder(record.sat_id).build();
+            let start = record.toe_epoch().to_utc() - chrono::Duration::minutes(30);
+            satellite
+                .propagate(start, HOUR, &config, &[record])
+                .unwrap();
+
+            assert_eq!(satellite.states.ephemeris_ages()[30], 0.0);
+            for state in satellite.states.iter() {
+                let single = record.evaluate(
+                    state.epoch(),
+                    config.kepler_tolerance,
+                    config.kepler_max_iter,
+                );
+                assert!(state.position().approx_eq(&single.position, 1e-6));
+                assert!(state.velocity().unwrap().approx_eq(&single.velocity, 1e-9));
+            }
+        }
+    }
+
+    #[test]
+    fn small_sin_cos_matches_std_below_the_limit() {
+        for k in -1000..=1000 {
+            let x = SERIES_LIMIT * k as f64 / 1000.0;
+            let (sin, cos) = small_sin_cos(x);
+            assert!((sin - x.sin()).abs() <= f64::EPSILON * x.abs(), "{}", x);
+            assert!((cos - x.cos()).abs() <= f64::EPSILON, "{}", x);
+        }
+    }
+
+    /// The strip-wise batch evaluation against the plain per-epoch formulas, with direct
+    /// sin, cos and atan2, over the fixture and a record whose corrections skip the series
+    #[test]
+    fn strips_match_the_scalar_model() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(30))
+            .with_velocity(true)
+            .with_clock(true);
+        // Corrections beyond the series, for the fallback to sin/cos
+        let mut implausible = records(17)[0];
+        implausible.cus = 0.02;
+        implausible.cis = -0.03;
+        let implausible = [implausible];
+        let sets = nav
+            .satellites()
+            .map(|sat_id| nav.records_for_slice(sat_id))
+            .chain([&implausible[..]]);
+
+        let (mut position, mut velocity, mut clock) = (0.0f64, 0.0f64, 0.0f64);
+        for records in sets {
+            let mut satellite = Satellite::builder(records[0].sat_id).build();
+            let start = match records.len() {
+                1 => records[0].toe_epoch().to_utc() - chrono::Duration::hours(2),
+                _ => start,
+            };
+            satellite
+                .propagate(start, 4 * HOUR, &config, records)
+                .unwrap();
+            let prepared: Vec<_> = records.iter().map(gnss::NavRecord::prepare).collect();
+            for state in satellite.states.iter() {
+                let epoch = state.epoch();
+                let record = prepared
+                    .iter()
+                    .find(|record| {
+                        let age = (epoch - record.toe_gps).as_f64();
+                        (age - state.ephemeris_age()).abs() < EPOCH_TOLERANCE
+                    })
+                    .unwrap();
+                let single =
+                    record.evaluate(epoch, config.kepler_tolerance, config.kepler_max_iter);
+                position = position.max((state.position() - single.position).norm());
+                velocity = velocity.max((state.velocity().unwrap() - single.velocity).norm());
+                clock = clock.max((state.clock_bias().unwrap() - single.clock_bias.0).abs());
+            }
+        }
+        // A few units in the last place of a 26,000 km radius
+        assert!(position < 1e-7, "{} m", position);
+        assert!(velocity < 1e-10, "{} m/s", velocity);
+        assert!(clock < 1e-18, "{} s", clock);
+    }
+
+    #[test]
+    fn extending_matches_one_propagation_over_the_whole_span() {
+        let records = records(17);
+        // Extensions of whole and partial steps, across the two-hourly handovers
+        let pieces = [25, 300, 1805, 3600, 7195, 1].map(Duration::from_secs);
+        let total: Duration = pieces.iter().sum::<Duration>() + Duration::from_secs(5400);
+        for grid_end in [GridEnd::Exclusive, GridEnd::Inclusive, GridEnd::Snap] {
+            let config = PropagationConfig::new()
+                .step(Duration::from_secs(60))
+                .grid_end(grid_end)
+                .with_velocity(true)
+                .with_clock(true);
+            let mut whole = Satellite::builder(17).build();
+            whole.propagate(start(), total, &config, &records).unwrap();
+
+            let mut extended = Satellite::builder(17).build();
+            extended
+                .propagate(start(), Duration::from_secs(5400), &config, &records)
+                .unwrap();
+            for piece in pieces {
+                extended.extend(piece, &config, &records).unwrap();
+            }
+            assert!(extended.states == whole.states, "{:?}", grid_end);
+        }
+    }
+
+    #[test]
+    fn propagating_with_the_broadcast_model_matches_propagate() {
+        let records = records(17);
+        // Across the 04:00 handover, with a partial step at the end
+        let duration = Duration::from_secs(3 * 3600 + 45);
+        for grid_end in [GridEnd::Exclusive, GridEnd::Inclusive, GridEnd::Snap] {
+            let config = PropagationConfig::new()
+                .step(Duration::from_secs(30))
+                .grid_end(grid_end)
+                .with_velocity(true)
+                .with_clock(true);
+            let mut satellite = Satellite::builder(17).build();
+            let report = satellite
+                .propagate(start(), duration, &config, &records)
+                .unwrap();
+
+            let propagator = BroadcastPropagator::new(&records, config.clone()).unwrap();
+            let mut with = Satellite::builder(17).build();
+            let with_report = with
+                .propagate_with(start(), duration, &config, &propagator)
+                .unwrap();
+            assert_eq!(with_report, report, "{:?}", grid_end);
+            assert!(with.states == satellite.states, "{:?}", grid_end);
+
+            // Extending continues either the same way
+            satellite.extend(HOUR, &config, &records).unwrap();
+            with.extend(HOUR, &config, &records).unwrap();
+            assert!(with.states == satellite.states, "{:?}", grid_end);
+        }
+        let config = PropagationConfig::new().step(Duration::ZERO);
+        let propagator = BroadcastPropagator::new(&records, config.clone()).unwrap();
+        let mut satellite = Satellite::builder(17).build();
+        assert_eq!(
+            satellite
+                .propagate_with(start(), HOUR, &config, &propagator)
+                .err(),
+            Some(PropagationError::ZeroStep)
+        );
+    }
+
+    #[test]
+    fn extending_needs_states_on_the_same_step() {
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        let mut satellite = Satellite::builder(17).build();
+        assert!(matches!(
+            satellite.extend(HOUR, &config, &records(17)),
+            Err(PropagationError::NotPropagated)
+        ));
+
+        satellite
+            .propagate(start(), HOUR, &config, &records(17))
+            .unwrap();
+        let before = satellite.states.clone();
+        let other = config.clone().step(Duration::from_secs(30));
+        match satellite.extend(HOUR, &other, &records(17)) {
+            Err(PropagationError::StepMismatch { expected, found }) => {
+                assert_eq!((expected, found), (60.0, 30.0));
+            }
+            result => panic!("{:?}", result),
+        }
+        assert!(satellite.states == before);
+
+        let report = satellite.extend(HOUR, &config, &records(17)).unwrap();
+        assert_eq!((report.states, satellite.states.len()), (60, 120));
+    }
+
+    #[test]
+    fn with_clock_adds_the_clock_offset() {
+        let config = PropagationConfig::new();
+        let (satellite, _) = propagate(17, HOUR, &config).unwrap();
+        assert!(satellite.states.clock_biases().is_empty());
+
+        let (satellite, _) = propagate(17, HOUR, &config.with_clock(true)).unwrap();
+        let first = satellite.states.first().unwrap();
+        let record = records(17)[0];
+        let dt = first.time() - record.toc_gps_seconds();
+        assert_eq!(dt, 34.0);
+        // The polynomial plus a relativistic term of at most 31 ns for this orbit
+        let polynomial = record.sv_clock_bias + record.sv_clock_drift * dt;
+        let relativistic = first.clock_bias().unwrap() - polynomial;
+        assert!(relativistic != 0.0 && relativistic.abs() < 3.1e-8);
+    }
+
+    #[test]
+    fn healthy_only_leaves_out_unhealthy_records() {
+        assert!(propagate(22, HOUR, &PropagationConfig::new()).is_ok());
+        let config = PropagationConfig::new().healthy_only(true);
+        assert_eq!(
+            propagate(22, HOUR, &config).err(),
+            Some(PropagationError::NoEphemeris)
+        );
+    }
+
+    #[test]
+    fn valid_only_leaves_out_invalid_records() {
+        let mut records = records(17);
+        records[0].eccentricity = 1.5;
+        let evaluate = |config: &PropagationConfig| {
+            let mut satellite = Satellite::builder(17).build();
+            satellite
+                .propagate(start(), HOUR, config, &records)
+                .unwrap();
+            satellite.states
+        };
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        let states = evaluate(&config);
+        assert!(states
+            .positions()
+            .iter()
+            .any(|position| position.x.is_nan()));
+
+        let states = evaluate(&config.valid_only(true));
+        assert!(states
+            .positions()
+            .iter()
+            .all(|position| position.x.is_finite()));
+        assert!(states.ephemeris_ages().iter().all(|age| age.abs() > 3600.0));
+    }
+
+    #[test]
+    fn max_ephemeris_age_flags_old_states() {
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        let (satellite, report) = propagate(17, HOUR, &config).unwrap();
+        assert_eq!((report.fresh, report.extrapolated), (60, 0));
+        assert!(satellite.states.extrapolated().iter().all(|&old| !old));
+
+        let config = config.max_ephemeris_age(1800.0);
+        let (satellite, report) = propagate(17, HOUR, &config).unwrap();
+        assert_eq!(report.fresh + report.extrapolated, 60);
+        assert!(report.extrapolated > 0 && report.fresh > 0);
+        for state in satellite.states.iter() {
+            assert_eq!(state.extrapolated(), state.ephemeris_age().abs() > 1800.0);
+        }
+    }
+
+    #[test]
+    fn states_beyond_the_files_coverage_are_flagged() {
+        // The fixture's G17 records stop at 06:00 until 14:00, so from 04:00 they cover
+        // only the first half of eight hours at a two-hour maximum age
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(600))
+            .max_ephemeris_age(7200.0);
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+        let mut satellite = Satellite::builder(17).build();
+        let report = satellite
+            .propagate(start, 8 * HOUR, &config, &records(17))
+            .unwrap();
+        // 08:00 UTC is 18 s past two hours from the 06:00 GPS time toe
+        assert_eq!((report.fresh, report.extrapolated), (24, 24));
+        let half = records(17)[2].toe_gps_seconds() + 7200.0;
+        assert_eq!(half, gnss::gps_seconds(start) + 4.0 * 3600.0 - 18.0);
+        for state in satellite.states.iter() {
+            assert_eq!(state.extrapolated(), state.time() > half);
+        }
+    }
+
+    #[test]
+    fn lookups_by_time_handle_epochs_outside_the_span() {
+        let config = PropagationConfig::new().step(Duration::from_secs(60));
+        let (satellite, _) = propagate(17, HOUR, &config).unwrap();
+        let minutes = |m: i64| start() + chrono::Duration::minutes(m);
+        let seconds = |s: i64| start() + chrono::Duration::seconds(s);
+
+        assert_eq!(
+            satellite.state_at(minutes(10)).unwrap().time_utc(),
+            minutes(10)
+        );
+        assert!(satellite.state_at(seconds(630)).is_none());
+        // Before the first state and after the last
+        assert!(satellite.state_at(minutes(-1)).is_none());
+        assert!(satellite.state_at(minutes(60)).is_none());
+        assert_eq!(satellite.state_nearest(minutes(-90)).unwrap().index(), 0);
+        assert_eq!(satellite.state_nearest(minutes(600)).unwrap().index(), 59);
+        assert_eq!(satellite.state_nearest(seconds(629)).unwrap().index(), 10);
+        assert_eq!(satellite.state_nearest(seconds(631)).unwrap().index(), 11);
+
+        let between = satellite.states_between(minutes(-30), minutes(2));
+        assert_eq!(
+            between.map(|state| state.index()).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            satellite.states_between(minutes(58), minutes(90)).count(),
+            2
+        );
+        assert_eq!(
+            satellite.states_between(minutes(-30), minutes(-1)).count(),
+            0
+        );
+        assert_eq!(
+            satellite.states_between(minutes(61), minutes(90)).count(),
+            0
+        );
+        assert_eq!(satellite.states_between(minutes(5), minutes(4)).count(), 0);
+
+        let empty = Satellite::builder(17).build();
+        assert!(empty.state_nearest(start()).is_none());
+        assert!(empty.state_at(start()).is_none());
+    }
+
+    #[test]
+    fn strict_rejects_old_ephemerides() {
+        let config = PropagationConfig::new().max_ephemeris_age(1800.0).strict();
+        assert!(matches!(
+            propagate(17, HOUR, &config),
+            Err(PropagationError::EphemerisTooOld { age, .. }) if age > 1800.0
+        ));
+        let config = PropagationConfig::new().max_ephemeris_age(7200.0).strict();
+        assert!(propagate(17, HOUR, &config).is_ok());
+    }
+
+    #[test]
+    fn kepler_max_iter_limits_the_solver() {
+        let config = PropagationConfig::new().kepler_max_iter(0);
+        let (satellite, report) = propagate(17, HOUR, &config).unwrap();
+        assert_eq!(report.kepler_failures, report.states);
+        assert!(satellite
+            .states
+            .kepler_converged()
+            .iter()
+            .all(|&done| !done));
+
+        assert!(matches!(
+            propagate(17, HOUR, &config.strict()),
+            Err(PropagationError::KeplerNotConverged { .. })
+        ));
+    }
+
+    #[test]
+    fn kepler_tolerance_sets_the_precision() {
+        let (exact, _) = propagate(17, HOUR, &PropagationConfig::new()).unwrap();
+        let config = PropagationConfig::new().kepler_tolerance(1e-3);
+        let (loose, report) = propagate(17, HOUR, &config).unwrap();
+        assert_eq!(report.kepler_failures, 0);
+        let largest = exact
+            .states
+            .positions()
+            .iter()
+            .zip(loose.states.positions())
+            .map(|(exact, loose)| exact.distance_to(loose))
+            .fold(0.0, f64::max);
+        // One more Newton step from an error of 1e-3 rad leaves under a micrometer
+        assert!(largest > 0.0);
+        assert!(largest < 1.0);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn solve_kepler_flags_each_element() {
+        // Near perigee of a highly eccentric orbit Newton needs more steps than elsewhere
+        let m = ndarray::array![0.01, 2.0, 3.0];
+        let solution = Satellite::solve_kepler(&m.view(), 0.97, 1e-12, 6);
+        assert_eq!(solution.converged.to_vec(), [false, true, true]);
+        assert_eq!(solution.iterations[0], 6);
+        for k in 1..3 {
+            let e_anomaly = solution.eccentric_anomaly[k];
+            assert!((e_anomaly - 0.97 * e_anomaly.sin() - m[k]).abs() < 1e-12);
+        }
+    }
+
+    /// Each epoch evaluated alone with the record of nearest toe found by a linear scan,
+    /// as propagation did before epochs were grouped by record
+    fn propagate_per_epoch(
+        records: &[gnss::NavRecord],
+        times: &[f64],
+        config: &PropagationConfig,
+    ) -> gnss::StateSeries {
+        let mut prepared: Vec<_> = records.iter().map(gnss::NavRecord::prepare).collect();
+        prepared.sort_by(|a, b| a.toe_gps.total_cmp(&b.toe_gps));
+        let mut out = gnss::StateSeries::new();
+        for (k, &time) in times.iter().enumerate() {
+            let epoch = gnss::GpsTime::from_seconds(time);
+            // The first of equally near records, the earlier one
+            let record = prepared
+                .iter()
+                .min_by(|a, b| {
+                    let age = |record: &ephemeris::PreparedEphemeris| {
+                        (epoch - record.toe_gps).abs().as_f64()
+                    };
+                    age(a).total_cmp(&age(b))
+                })
+                .unwrap();
+            let columns = out.columns_after(k, 1, config.with_velocity, config.with_clock);
+            Satellite::evaluate_block(record, &[time], config, columns);
+        }
+        out
+    }
+
+    #[test]
+    fn grouping_by_record_matches_per_epoch_evaluation() {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(30))
+            .with_velocity(true)
+            .with_clock(true)
+            .max_ephemeris_age(7200.0);
+        for sat_id in nav.satellites() {
+            let records = nav.records_for_slice(sat_id);
+            let mut satellite = Satellite::builder(sat_id).build();
+            satellite
+                .propagate(start, 24 * HOUR, &config, records)
+                .unwrap();
+            let expected = propagate_per_epoch(records, satellite.states.times(), &config);
+            assert_eq!(satellite.states.len(), 2880);
+            assert!(satellite.states == expected, "{} differs", sat_id);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_evaluation_matches_serial() {
+        let prepared = records(17)[0].prepare();
+        let first = gnss::gps_seconds(start());
+        // Several work items and a partial one at the end
+        let times: Vec<f64> = (0..3 * PARALLEL_CHUNK_LEN + 123)
+            .map(|k| first + k as f64 * 0.1)
+            .collect();
+        let config = PropagationConfig::new()
+            .with_velocity(true)
+            .with_clock(true)
+            .max_ephemeris_age(600.0);
+        let evaluate = |parallel: bool| {
+            let mut out = gnss::StateSeries::new();
+            let columns = out.columns_after(0, times.len(), true, true);
+            match parallel {
+                true => Satellite::evaluate_parallel(&prepared, &times, &config, columns),
+                false => Satellite::evaluate_block(&prepared, &times, &config, columns),
+            };
+            out
+        };
+        assert!(evaluate(true) == evaluate(false));
+    }
+
+    #[test]
+    fn accuracy_growth_inflates_the_accuracy() {
+        let config = PropagationConfig::new();
+        let (satellite, _) = propagate(17, HOUR, &config).unwrap();
+        let ura = records(17)[1].ura_meters().unwrap();
+        assert!(satellite
+            .states
+            .accuracies()
+            .iter()
+            .all(|&accuracy| accuracy == Some(ura)));
+
+        let (satellite, _) = propagate(17, HOUR, &config.accuracy_growth(2.0)).unwrap();
+        for state in satellite.states.iter() {
+            let grown = ura.hypot(2.0 * state.ephemeris_age().abs() / 3600.0);
+            assert_eq!(state.accuracy(), Some(grown));
+        }
+    }
+}
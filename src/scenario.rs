@@ -0,0 +1,434 @@
+//! Propagation runs described in TOML: nav files, satellites, time window, step, output
+//! and ground sites, read into a `Scenario` and carried out by `Scenario::run`.
+//!
+//! ```toml
+//! nav = ["brdc1630.23n"]          # Merged when several; relative to the scenario file
+//! satellites = ["G03", "G17"]     # All in the files when left out
+//! start = "2023-06-12T06:00:00Z"
+//! duration = 7200.0               # s
+//! step = 30.0                     # s
+//! mask = 10.0                     # Degrees, for sites without their own
+//!
+//! [output]
+//! format = "czml"                 # csv, json, kml, geojson, czml or sp3
+//! path = "orbits.czml"            # A directory for csv and json, one file per satellite
+//!
+//! [[sites]]
+//! name = "Palo Alto"
+//! latitude = 37.4
+//! longitude = -122.1
+//! altitude = 30.0
+//! horizon = "palo_alto.csv"       # Optional terrain profile on top of the mask
+//! ```
+
+use crate::constellation::{Constellation, SatelliteStatus};
+use crate::csv::CsvOptions;
+use crate::czml::CzmlOptions;
+use crate::gnss::{RinexNav, SatId, LLA};
+use crate::horizon::{ElevationMask, HorizonProfile, ParseHorizonError};
+use crate::kml::KmlOptions;
+use crate::satellite::{PropagationConfig, Satellite};
+use crate::sp3::Sp3Options;
+use crate::visibility::Pass;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A propagation run, read from TOML with unknown keys rejected
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub nav: Vec<PathBuf>,
+    #[serde(default)]
+    pub satellites: Vec<SatId>, // Empty for every satellite in the nav files
+    pub start: DateTime<Utc>,
+    pub duration: f64, // s
+    pub step: f64,     // s
+    #[serde(default)]
+    pub velocity: bool,
+    #[serde(default)]
+    pub mask: f64, // Degrees, for sites without their own
+    pub output: Option<ScenarioOutput>,
+    #[serde(default)]
+    pub sites: Vec<Site>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioOutput {
+    pub format: OutputFormat,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Csv,  // One file per satellite in the output directory
+    Json, // One file per satellite in the output directory
+    Kml,
+    Geojson,
+    Czml,
+    Sp3,
+}
+
+/// Ground site whose passes the run reports
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Site {
+    pub name: String,
+    pub latitude: f64,  // Degrees
+    pub longitude: f64, // Degrees
+    #[serde(default)]
+    pub altitude: f64, // m
+    pub mask: Option<f64>, // Degrees; the scenario's mask if left out
+    pub horizon: Option<PathBuf>, // Terrain profile, CSV or JSON, applied on top of the mask
+}
+
+impl Site {
+    pub fn lla(&self) -> LLA {
+        LLA::new(self.latitude, self.longitude, self.altitude)
+    }
+}
+
+/// What `Scenario::run` did
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScenarioReport {
+    pub records: usize, // Nav records of the scenario's satellites
+    pub statuses: BTreeMap<SatId, SatelliteStatus>, // `NoData` for satellites without records
+    pub outputs: Vec<PathBuf>, // Files written
+    pub passes: Vec<(String, Vec<Pass>)>, // Per site, in scenario order
+}
+
+impl ScenarioReport {
+    pub fn propagated(&self) -> usize {
+        self.statuses
+            .values()
+            .filter(|status| matches!(status, SatelliteStatus::Propagated(_)))
+            .count()
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} records, {} of {} satellites propagated",
+            self.records,
+            self.propagated(),
+            self.statuses.len()
+        )?;
+        for (sat_id, status) in &self.statuses {
+            match status {
+                SatelliteStatus::Propagated(_) => {}
+                SatelliteStatus::Failed(err) => write!(f, "\n{} failed: {}", sat_id, err)?,
+                other => write!(f, "\n{} skipped: {:?}", sat_id, other)?,
+            }
+        }
+        for path in &self.outputs {
+            write!(f, "\nwrote {}", path.display())?;
+        }
+        for (site, passes) in &self.passes {
+            write!(f, "\n{}: {} passes", site, passes.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a scenario could not be read or run
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Toml(toml::de::Error),
+    Invalid(String), // A value out of range or a path that does not exist
+    Horizon {
+        path: PathBuf,
+        error: ParseHorizonError,
+    },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Self::Toml(error) => write!(f, "invalid scenario: {}", error),
+            Self::Invalid(message) => write!(f, "invalid scenario: {}", message),
+            Self::Horizon { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Toml(error) => Some(error),
+            Self::Horizon { error, .. } => Some(error),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+impl Scenario {
+    /// Parse and check the values; paths stay as written, relative to the working directory
+    pub fn from_toml(text: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = toml::from_str(text).map_err(ScenarioError::Toml)?;
+        scenario.check_values()?;
+        Ok(scenario)
+    }
+
+    /// Read a scenario file, with relative paths taken from its directory, and check that
+    /// its input files exist
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|error| ScenarioError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let mut scenario: Self = toml::from_str(&text).map_err(ScenarioError::Toml)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        scenario.resolve_paths(base);
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Relative paths taken from `base` instead of the working directory
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let paths = self.nav.iter_mut().chain(
+            self.output.iter_mut().map(|output| &mut output.path).chain(
+                self.sites
+                    .iter_mut()
+                    .filter_map(|site| site.horizon.as_mut()),
+            ),
+        );
+        for path in paths {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// Values in range, input files present and the output path usable for its format
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        self.check_values()?;
+        let inputs = self
+            .nav
+            .iter()
+            .chain(self.sites.iter().filter_map(|site| site.horizon.as_ref()));
+        for path in inputs {
+            if path.to_str().is_none() {
+                return invalid(format!("{} is not a UTF-8 path", path.display()));
+            }
+            if !path.is_file() {
+                return invalid(format!("{} is not a file", path.display()));
+            }
+        }
+        // Missing directories are created when writing
+        if let Some(output) = &self.output {
+            let path = &output.path;
+            match output.format {
+                OutputFormat::Csv | OutputFormat::Json if path.is_file() => {
+                    return invalid(format!("{} is a file, not a directory", path.display()))
+                }
+                OutputFormat::Kml
+                | OutputFormat::Geojson
+                | OutputFormat::Czml
+                | OutputFormat::Sp3
+                    if path.is_dir() =>
+                {
+                    return invalid(format!("{} is a directory", path.display()))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_values(&self) -> Result<(), ScenarioError> {
+        if self.nav.is_empty() {
+            return invalid("no nav files".to_string());
+        }
+        for (name, value) in [("duration", self.duration), ("step", self.step)] {
+            if Duration::try_from_secs_f64(value).is_err() {
+                return invalid(format!("{} must be finite and >= 0 s, not {}", name, value));
+            }
+        }
+        if self.step == 0.0 {
+            return invalid("step must be above 0 s".to_string());
+        }
+        let masks =
+            std::iter::once(self.mask).chain(self.sites.iter().filter_map(|site| site.mask));
+        for mask in masks {
+            if !(-90.0..=90.0).contains(&mask) {
+                return invalid(format!("mask of {}° is outside [-90, 90]", mask));
+            }
+        }
+        for (index, site) in self.sites.iter().enumerate() {
+            if !(-90.0..=90.0).contains(&site.latitude)
+                || !(-180.0..=360.0).contains(&site.longitude)
+                || !site.altitude.is_finite()
+            {
+                return invalid(format!("site {} has an invalid position", site.name));
+            }
+            if self.sites[..index]
+                .iter()
+                .any(|other| other.name == site.name)
+            {
+                return invalid(format!("site {} is listed twice", site.name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn config(&self) -> PropagationConfig {
+        PropagationConfig::new()
+            .step(Duration::from_secs_f64(self.step))
+            .with_velocity(self.velocity)
+    }
+
+    /// Read the nav files, propagate, write the output and find each site's passes
+    pub fn run(&self) -> Result<ScenarioReport, ScenarioError> {
+        self.validate()?;
+        let nav = self
+            .nav
+            .iter()
+            .try_fold(RinexNav::default(), |merged, path| {
+                let nav =
+                    RinexNav::from_file_filtered(path.to_str().unwrap_or_default(), |sat_id| {
+                        self.satellites.is_empty() || self.satellites.contains(&sat_id)
+                    })
+                    .map_err(|error| ScenarioError::Io {
+                        path: path.clone(),
+                        error,
+                    })?;
+                Ok(merged.merge(nav))
+            })?;
+
+        let mut constellation = Constellation::from_nav(nav);
+        let duration = Duration::from_secs_f64(self.duration);
+        let mut statuses = constellation.propagate_all(self.start, duration, &self.config());
+        for sat_id in &self.satellites {
+            statuses.entry(*sat_id).or_insert(SatelliteStatus::NoData);
+        }
+
+        let outputs = match &self.output {
+            Some(output) => write_output(&constellation, output)?,
+            None => Vec::new(),
+        };
+
+        let end = self.start + duration;
+        let mut passes = Vec::new();
+        for site in &self.sites {
+            let horizon = match &site.horizon {
+                Some(path) => Some(
+                    HorizonProfile::from_file(path.to_str().unwrap_or_default()).map_err(
+                        |error| ScenarioError::Horizon {
+                            path: path.clone(),
+                            error,
+                        },
+                    )?,
+                ),
+                None => None,
+            };
+            let mask = SiteMask {
+                flat: site.mask.unwrap_or(self.mask),
+                horizon,
+            };
+            let site_passes = constellation.passes(&site.lla(), &mask, self.start, end);
+            passes.push((site.name.clone(), site_passes));
+        }
+
+        Ok(ScenarioReport {
+            records: constellation.nav().records().len(),
+            statuses,
+            outputs,
+            passes,
+        })
+    }
+}
+
+/// Flat mask, raised where the terrain profile is higher
+struct SiteMask {
+    flat: f64,
+    horizon: Option<HorizonProfile>,
+}
+
+impl ElevationMask for SiteMask {
+    fn min_elevation(&self, azimuth: f64) -> f64 {
+        match &self.horizon {
+            Some(horizon) => self.flat.max(horizon.min_elevation(azimuth)),
+            None => self.flat,
+        }
+    }
+}
+
+fn invalid<T>(message: String) -> Result<T, ScenarioError> {
+    Err(ScenarioError::Invalid(message))
+}
+
+fn write_output(
+    constellation: &Constellation,
+    output: &ScenarioOutput,
+) -> Result<Vec<PathBuf>, ScenarioError> {
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |error| ScenarioError::Io { path, error }
+    };
+    let create = |path: &Path| {
+        File::create(path)
+            .map(BufWriter::new)
+            .map_err(io_error(path))
+    };
+    let per_satellite = |extension: &str| -> Result<Vec<PathBuf>, ScenarioError> {
+        fs::create_dir_all(&output.path).map_err(io_error(&output.path))?;
+        let mut written = Vec::new();
+        for satellite in constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+        {
+            let path = output.path.join(format!("{}.{}", satellite.id, extension));
+            let writer = create(&path)?;
+            let result = match output.format {
+                OutputFormat::Csv => satellite.write_csv(writer, &CsvOptions::default()),
+                _ => write_json(satellite, writer),
+            };
+            result.map_err(io_error(&path))?;
+            written.push(path);
+        }
+        Ok(written)
+    };
+    match output.format {
+        OutputFormat::Csv => per_satellite("csv"),
+        OutputFormat::Json => per_satellite("json"),
+        format => {
+            if let Some(parent) = output
+                .path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                fs::create_dir_all(parent).map_err(io_error(parent))?;
+            }
+            let writer = create(&output.path)?;
+            let result = match format {
+                OutputFormat::Kml => constellation.write_kml(writer, &KmlOptions::default()),
+                OutputFormat::Geojson => constellation.write_geojson(writer),
+                OutputFormat::Czml => constellation.write_czml(writer, &CzmlOptions::default()),
+                _ => constellation.write_sp3(writer, &Sp3Options::default()),
+            };
+            result.map_err(io_error(&output.path))?;
+            Ok(vec![output.path.clone()])
+        }
+    }
+}
+
+fn write_json(satellite: &Satellite, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "{}", satellite.states_to_json()?)?;
+    writer.flush()
+}
@@ -0,0 +1,257 @@
+use crate::gnss::{SatId, ECEF};
+use crate::linalg;
+use crate::positioning::Dop;
+use ndarray::{Array1, Array2, Axis};
+
+/// How `select_subset` searches the candidate subsets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionMethod {
+    /// Every combination of the target count; exact but combinatorial
+    Exhaustive,
+    /// Start from all satellites and repeatedly drop the one whose removal raises GDOP the
+    /// least, using rank-one downdates of the cofactor matrix
+    Greedy,
+    /// Exhaustive while the number of combinations stays within the limit, else greedy
+    Auto { max_combinations: u64 },
+}
+
+impl Default for SelectionMethod {
+    fn default() -> Self {
+        Self::Auto {
+            max_combinations: 100_000,
+        }
+    }
+}
+
+/// Chosen satellites and the GDOP they achieve
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubsetSelection {
+    pub satellites: Vec<SatId>,
+    pub gdop: f64,
+    pub exhaustive: bool, // Whether the search was exhaustive, i.e. the result is optimal
+}
+
+/// GDOP of a set of receiver-to-satellite unit vectors, None if fewer than 4 or singular
+pub fn gdop(lines_of_sight: &[ECEF]) -> Option<f64> {
+    let cofactor = cofactor(lines_of_sight.iter())?;
+    Some(cofactor.diag().sum().sqrt())
+}
+
+/// All DOP components of unit line-of-sight vectors from a receiver at `position`, None if
+/// fewer than 4 or singular
+pub fn dop(lines_of_sight: &[ECEF], position: &ECEF) -> Option<Dop> {
+    let cofactor = cofactor(lines_of_sight.iter())?;
+    Some(Dop::from_cofactor(&cofactor, position))
+}
+
+/// The `count` satellites with the lowest GDOP among candidates given as unit line-of-sight
+/// vectors from the receiver. None if fewer than 4 are asked for or no subset is solvable.
+pub fn select_subset(
+    candidates: &[(SatId, ECEF)],
+    count: usize,
+    method: SelectionMethod,
+) -> Option<SubsetSelection> {
+    if count < 4 {
+        return None;
+    }
+    let count = count.min(candidates.len());
+    let exhaustive = match method {
+        SelectionMethod::Exhaustive => true,
+        SelectionMethod::Greedy => false,
+        SelectionMethod::Auto { max_combinations } => {
+            combinations(candidates.len(), count) <= max_combinations
+        }
+    };
+    let chosen = match exhaustive {
+        true => exhaustive_search(candidates, count)?,
+        false => greedy_search(candidates, count)?,
+    };
+    let lines_of_sight: Vec<ECEF> = chosen.iter().map(|&i| candidates[i].1).collect();
+    Some(SubsetSelection {
+        satellites: chosen.iter().map(|&i| candidates[i].0).collect(),
+        gdop: gdop(&lines_of_sight)?,
+        exhaustive,
+    })
+}
+
+fn exhaustive_search(candidates: &[(SatId, ECEF)], count: usize) -> Option<Vec<usize>> {
+    let n = candidates.len();
+    let mut indices: Vec<usize> = (0..count).collect();
+    let mut best: Option<(f64, Vec<usize>)> = None;
+    loop {
+        if let Some(q) = cofactor(indices.iter().map(|&i| &candidates[i].1)) {
+            let trace = q.diag().sum();
+            if best.as_ref().is_none_or(|(lowest, _)| trace < *lowest) {
+                best = Some((trace, indices.clone()));
+            }
+        }
+        // Advance to the next combination in lexicographic order
+        let Some(pos) = (0..count).rev().find(|&pos| indices[pos] < n - count + pos) else {
+            break;
+        };
+        indices[pos] += 1;
+        for next in pos + 1..count {
+            indices[next] = indices[next - 1] + 1;
+        }
+    }
+    best.map(|(_, indices)| indices)
+}
+
+fn greedy_search(candidates: &[(SatId, ECEF)], count: usize) -> Option<Vec<usize>> {
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut q = cofactor(candidates.iter().map(|(_, los)| los))?;
+    while remaining.len() > count {
+        // Removing row h: Q' = Q + Q h hᵀ Q / (1 - hᵀ Q h), so trace grows by |Q h|² / (1 - hᵀ Q h)
+        let mut best: Option<(f64, usize, Array1<f64>, f64)> = None;
+        for (pos, &i) in remaining.iter().enumerate() {
+            let h = row(&candidates[i].1);
+            let qh = q.dot(&h);
+            let leverage = 1.0 - h.dot(&qh);
+            if leverage <= 1e-12 {
+                continue; // Removing it would make the geometry singular
+            }
+            let increase = qh.dot(&qh) / leverage;
+            if best.as_ref().is_none_or(|b| increase < b.0) {
+                best = Some((increase, pos, qh, leverage));
+            }
+        }
+        let (_, pos, qh, leverage) = best?;
+        let qh_col = qh.view().insert_axis(Axis(1));
+        q = &q + &(qh_col.dot(&qh_col.t()) / leverage);
+        remaining.remove(pos);
+    }
+    Some(remaining)
+}
+
+/// (HᵀH)⁻¹ for rows (-u, 1)
+fn cofactor<'a>(lines_of_sight: impl Iterator<Item = &'a ECEF>) -> Option<Array2<f64>> {
+    let mut normal = Array2::zeros((4, 4));
+    let mut rows = 0;
+    for los in lines_of_sight {
+        let h = row(los);
+        for i in 0..4 {
+            for j in 0..4 {
+                normal[[i, j]] += h[i] * h[j];
+            }
+        }
+        rows += 1;
+    }
+    if rows < 4 {
+        return None;
+    }
+    linalg::invert(&normal)
+}
+
+fn row(los: &ECEF) -> Array1<f64> {
+    Array1::from(vec![-los.x, -los.y, -los.z, 1.0])
+}
+
+/// n choose k, saturating
+fn combinations(n: usize, k: usize) -> u64 {
+    let k = k.min(n - k) as u128;
+    let mut result: u128 = 1;
+    for i in 0..k {
+        // Exact at every step: result is C(n, i + 1) afterwards
+        let Some(product) = result.checked_mul(n as u128 - i) else {
+            return u64::MAX;
+        };
+        result = product / (i + 1);
+    }
+    result.try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::Constellation;
+
+    /// Unit vector at an azimuth and elevation, in a local east-north-up frame; GDOP does not
+    /// depend on the orientation of the frame
+    fn look(azimuth: f64, elevation: f64) -> ECEF {
+        let (sin_az, cos_az) = azimuth.to_radians().sin_cos();
+        let (sin_el, cos_el) = elevation.to_radians().sin_cos();
+        ECEF::new(cos_el * sin_az, cos_el * cos_az, sin_el)
+    }
+
+    /// A tight cluster overhead and a ring of satellites low in the sky
+    fn candidates() -> Vec<(SatId, ECEF)> {
+        [
+            (10.0, 85.0),
+            (100.0, 80.0),
+            (200.0, 78.0),
+            (300.0, 75.0),
+            (50.0, 70.0),
+            (20.0, 15.0),
+            (140.0, 20.0),
+            (260.0, 12.0),
+            (330.0, 35.0),
+            (190.0, 40.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(azimuth, elevation))| {
+            let sat_id = SatId {
+                constellation: Constellation::Gps,
+                prn: i as u8 + 1,
+            };
+            (sat_id, look(azimuth, elevation))
+        })
+        .collect()
+    }
+
+    #[test]
+    fn exhaustive_and_greedy_agree_and_beat_the_highest_satellites() {
+        let candidates = candidates();
+        // Up to six satellites the heuristic finds the optimum here; at seven it misses by
+        // a fraction of a percent
+        for count in 4..=6 {
+            let exhaustive =
+                select_subset(&candidates, count, SelectionMethod::Exhaustive).unwrap();
+            let greedy = select_subset(&candidates, count, SelectionMethod::Greedy).unwrap();
+            assert!(exhaustive.exhaustive && !greedy.exhaustive);
+            let mut chosen = greedy.satellites.clone();
+            chosen.sort();
+            assert_eq!(chosen, exhaustive.satellites, "{} satellites", count);
+            assert!((greedy.gdop - exhaustive.gdop).abs() < 1e-9);
+
+            // The first candidates are the highest
+            let highest: Vec<ECEF> = candidates[..count].iter().map(|(_, los)| *los).collect();
+            let naive = gdop(&highest).unwrap();
+            assert!(
+                exhaustive.gdop < naive / 2.0,
+                "{} vs {}",
+                exhaustive.gdop,
+                naive
+            );
+        }
+    }
+
+    #[test]
+    fn auto_switches_on_the_number_of_combinations() {
+        assert_eq!(combinations(10, 4), 210);
+        assert_eq!(combinations(60, 30), 118264581564861424);
+        assert_eq!(combinations(200, 100), u64::MAX);
+        let candidates = candidates();
+        let method = |max_combinations| SelectionMethod::Auto { max_combinations };
+        assert!(
+            select_subset(&candidates, 5, method(252))
+                .unwrap()
+                .exhaustive
+        );
+        assert!(
+            !select_subset(&candidates, 5, method(251))
+                .unwrap()
+                .exhaustive
+        );
+    }
+
+    #[test]
+    fn too_few_satellites() {
+        let candidates = candidates();
+        assert!(select_subset(&candidates, 3, SelectionMethod::Exhaustive).is_none());
+        assert!(gdop(&[look(0.0, 90.0), look(0.0, 30.0), look(120.0, 30.0)]).is_none());
+        // Asking for more than there are takes them all
+        let all = select_subset(&candidates, 20, SelectionMethod::Greedy).unwrap();
+        assert_eq!(all.satellites.len(), candidates.len());
+    }
+}
@@ -0,0 +1,751 @@
+//! Live receiver input: NMEA 0183 sentences and u-blox UBX messages read from a serial
+//! port, or any byte stream, as they arrive. Each message's framing is recognized from
+//! its first bytes, so receivers that interleave both protocols work. Garbage between
+//! messages and messages failing their checksum are skipped. A serial port that is
+//! unplugged or falls silent is reopened until it comes back.
+
+use crate::gnss::{Constellation as System, SatId, LLA};
+use crate::nmea::{self, ParseNmeaError, Sentence};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use log::debug;
+use serialport::{SerialPort, SerialPortType};
+use std::io::{self, Read};
+use std::thread;
+use std::time::Duration;
+
+const UBX_SYNC: [u8; 2] = [0xB5, 0x62];
+const UBX_OVERHEAD: usize = 8; // Sync, class, id and length before the payload, checksum after
+const MAX_UBX_PAYLOAD: usize = 4096; // Longer claimed lengths are taken as garbage
+const MAX_NMEA_LENGTH: usize = 128; // NMEA 0183 allows 82; proprietary sentences run longer
+const READ_BUFFER: usize = 4096;
+
+/// A UBX message with its transport framing removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UbxFrame {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Navigation solution of a UBX-NAV-PVT message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavPvt {
+    pub itow: u32,                   // GPS time of week, ms
+    pub time: Option<DateTime<Utc>>, // When the receiver flags both date and time valid
+    pub fix_type: u8,                // 0 none, 2 2D, 3 3D, 4 GNSS + dead reckoning, 5 time
+    pub fix_ok: bool,                // Within the receiver's accuracy masks
+    pub satellites: u8,              // Used in the solution
+    pub position: LLA,               // Height above the ellipsoid
+    pub height_msl: f64,             // m
+    pub horizontal_accuracy: f64,    // m
+    pub vertical_accuracy: f64,      // m
+    pub velocity: (f64, f64, f64),   // North, east, down, m/s
+    pub speed: f64,                  // Over ground, m/s
+    pub course: f64,                 // Of motion, degrees from true north
+    pub pdop: f64,
+}
+
+/// One satellite of a UBX-NAV-SAT message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SatelliteInfo {
+    pub sat_id: SatId,
+    pub cn0: f64,               // dB-Hz, 0 when not tracked
+    pub elevation: Option<f64>, // Degrees, None when unknown
+    pub azimuth: Option<f64>,   // Degrees
+    pub used: bool,             // In the navigation solution
+}
+
+/// Satellites the receiver knows of, from UBX-NAV-SAT
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavSat {
+    pub itow: u32,                      // ms
+    pub satellites: Vec<SatelliteInfo>, // Systems without a `SatId`, e.g. IMES, left out
+}
+
+/// A navigation message subframe, page or string as broadcast, from UBX-RXM-SFRBX
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subframe {
+    pub sat_id: SatId,
+    pub frequency_channel: Option<i8>, // GLONASS only
+    pub words: Vec<u32>,               // Layout depends on the system, see the u-blox manual
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UbxMessage {
+    NavPvt(NavPvt),
+    NavSat(NavSat),
+    Subframe(Subframe),
+    Other(UbxFrame), // Unsupported or too short for its type
+}
+
+/// One message from the receiver. NMEA sentences with an unreadable field come as
+/// `Sentence::Other`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Nmea(Sentence),
+    Ubx(UbxMessage),
+}
+
+impl UbxFrame {
+    pub fn parse(self) -> UbxMessage {
+        let payload = Payload(&self.payload);
+        let message = match (self.class, self.id) {
+            (0x01, 0x07) => payload.nav_pvt().map(UbxMessage::NavPvt),
+            (0x01, 0x35) => payload.nav_sat().map(UbxMessage::NavSat),
+            (0x02, 0x13) => payload.subframe().map(UbxMessage::Subframe),
+            _ => None,
+        };
+        message.unwrap_or(UbxMessage::Other(self))
+    }
+}
+
+/// Splits a byte stream into NMEA sentences and UBX messages. A message starts at '$' or
+/// at the UBX sync bytes. When a start does not lead to a valid message, the search
+/// resumes one byte later, so a message is found right after any garbage before it.
+#[derive(Debug, Clone, Default)]
+pub struct MessageDecoder {
+    buffer: Vec<u8>,
+    pub skipped_bytes: u64,   // Outside any valid message
+    pub checksum_errors: u64, // Framed messages whose checksum did not match
+}
+
+/// What the bytes at the start of the buffer hold
+enum Framed {
+    Message(Message, usize), // And its length in bytes
+    Incomplete,
+    Invalid,
+}
+
+impl MessageDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Next message in the buffered bytes, None until one is complete
+    pub fn next_message(&mut self) -> Option<Message> {
+        self.next(false)
+    }
+
+    /// Next message once the stream has ended: a start whose message would need more
+    /// bytes is garbage, so the messages buffered after it are still found
+    pub fn finish(&mut self) -> Option<Message> {
+        self.next(true)
+    }
+
+    fn next(&mut self, ended: bool) -> Option<Message> {
+        loop {
+            let start = self
+                .buffer
+                .iter()
+                .position(|&byte| byte == b'$' || byte == UBX_SYNC[0])
+                .unwrap_or(self.buffer.len());
+            self.skipped_bytes += start as u64;
+            self.buffer.drain(..start);
+            if self.buffer.is_empty() {
+                return None;
+            }
+            let framed = match self.buffer[0] {
+                b'$' => self.nmea(),
+                _ => self.ubx(),
+            };
+            match framed {
+                Framed::Message(message, length) => {
+                    self.buffer.drain(..length);
+                    return Some(message);
+                }
+                Framed::Incomplete if !ended => return None,
+                Framed::Incomplete | Framed::Invalid => {
+                    self.skipped_bytes += 1;
+                    self.buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    /// Printable ASCII from '$' up to LF, read by `nmea::parse`
+    fn nmea(&mut self) -> Framed {
+        let window = &self.buffer[..self.buffer.len().min(MAX_NMEA_LENGTH)];
+        let mut end = None;
+        for (i, &byte) in window.iter().enumerate().skip(1) {
+            match byte {
+                b'\n' => {
+                    end = Some(i + 1);
+                    break;
+                }
+                b'\r' | b' '..=b'~' if byte != b'$' => {}
+                _ => return Framed::Invalid,
+            }
+        }
+        let Some(end) = end else {
+            return match window.len() < MAX_NMEA_LENGTH {
+                true => Framed::Incomplete,
+                false => Framed::Invalid,
+            };
+        };
+        let line = std::str::from_utf8(&self.buffer[..end]).expect("checked ASCII");
+        match nmea::parse(line) {
+            Ok(sentence) => Framed::Message(Message::Nmea(sentence), end),
+            Err(ParseNmeaError::Field { .. }) => match nmea::parse_raw(line) {
+                Ok(raw) => Framed::Message(Message::Nmea(Sentence::Other(raw)), end),
+                Err(_) => Framed::Invalid,
+            },
+            Err(ParseNmeaError::Checksum { .. }) => {
+                self.checksum_errors += 1;
+                Framed::Invalid
+            }
+            Err(ParseNmeaError::Framing) => Framed::Invalid,
+        }
+    }
+
+    /// Sync bytes, class, id, little-endian length, payload and Fletcher checksum
+    fn ubx(&mut self) -> Framed {
+        let buffer = &self.buffer;
+        match buffer.get(1) {
+            None => return Framed::Incomplete,
+            Some(&byte) if byte != UBX_SYNC[1] => return Framed::Invalid,
+            Some(_) => {}
+        }
+        if buffer.len() < 6 {
+            return Framed::Incomplete;
+        }
+        let length = u16::from_le_bytes([buffer[4], buffer[5]]) as usize;
+        if length > MAX_UBX_PAYLOAD {
+            return Framed::Invalid;
+        }
+        let end = length + UBX_OVERHEAD;
+        if buffer.len() < end {
+            return Framed::Incomplete;
+        }
+        if ubx_checksum(&buffer[2..end - 2]) != [buffer[end - 2], buffer[end - 1]] {
+            self.checksum_errors += 1;
+            return Framed::Invalid;
+        }
+        let frame = UbxFrame {
+            class: buffer[2],
+            id: buffer[3],
+            payload: buffer[6..end - 2].to_vec(),
+        };
+        Framed::Message(Message::Ubx(frame.parse()), end)
+    }
+}
+
+/// Frame around a UBX payload, the inverse of `MessageDecoder`; e.g. to send
+/// configuration messages
+pub fn ubx_frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = UBX_SYNC.to_vec();
+    frame.extend_from_slice(&[class, id]);
+    frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    frame.extend_from_slice(payload);
+    let checksum = ubx_checksum(&frame[2..]);
+    frame.extend_from_slice(&checksum);
+    frame
+}
+
+/// 8-bit Fletcher checksum over class, id, length and payload
+fn ubx_checksum(bytes: &[u8]) -> [u8; 2] {
+    let (mut a, mut b) = (0u8, 0u8);
+    for &byte in bytes {
+        a = a.wrapping_add(byte);
+        b = b.wrapping_add(a);
+    }
+    [a, b]
+}
+
+/// Little-endian fields of a UBX payload by byte offset
+struct Payload<'a>(&'a [u8]);
+
+impl Payload<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.0.get(offset..offset + N)?.try_into().ok()
+    }
+
+    fn u8(&self, offset: usize) -> Option<u8> {
+        self.0.get(offset).copied()
+    }
+
+    fn i8(&self, offset: usize) -> Option<i8> {
+        Some(self.u8(offset)? as i8)
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn i16(&self, offset: usize) -> Option<i16> {
+        Some(i16::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn i32(&self, offset: usize) -> Option<i32> {
+        Some(i32::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn nav_pvt(&self) -> Option<NavPvt> {
+        let millimeters = |offset| Some(self.i32(offset)? as f64 * 1e-3);
+        let valid = self.u8(11)?;
+        // validDate and validTime; nano corrects the rounded second and may be negative
+        let time = (valid & 0x03 == 0x03)
+            .then(|| {
+                let date = NaiveDate::from_ymd_opt(
+                    self.u16(4)? as i32,
+                    self.u8(6)? as u32,
+                    self.u8(7)? as u32,
+                )?;
+                let time =
+                    date.and_hms_opt(self.u8(8)? as u32, self.u8(9)? as u32, self.u8(10)? as u32)?;
+                Some(time.and_utc() + ChronoDuration::nanoseconds(self.i32(16)? as i64))
+            })
+            .flatten();
+        Some(NavPvt {
+            itow: self.u32(0)?,
+            time,
+            fix_type: self.u8(20)?,
+            fix_ok: self.u8(21)? & 0x01 != 0,
+            satellites: self.u8(23)?,
+            position: LLA::new(
+                self.i32(28)? as f64 * 1e-7,
+                self.i32(24)? as f64 * 1e-7,
+                millimeters(32)?,
+            ),
+            height_msl: millimeters(36)?,
+            horizontal_accuracy: self.u32(40)? as f64 * 1e-3,
+            vertical_accuracy: self.u32(44)? as f64 * 1e-3,
+            velocity: (millimeters(48)?, millimeters(52)?, millimeters(56)?),
+            speed: millimeters(60)?,
+            course: self.i32(64)? as f64 * 1e-5,
+            pdop: self.u16(76)? as f64 * 0.01,
+        })
+    }
+
+    fn nav_sat(&self) -> Option<NavSat> {
+        let count = self.u8(5)? as usize;
+        let mut satellites = Vec::with_capacity(count);
+        for i in 0..count {
+            let block = 8 + 12 * i;
+            let flags = self.u32(block + 8)?;
+            let Some(sat_id) = sat_id(self.u8(block)?, self.u8(block + 1)?) else {
+                continue;
+            };
+            // Elevation beyond ±90 marks look angles the receiver does not know
+            let elevation = self.i8(block + 3)?;
+            let known = (-90..=90).contains(&elevation);
+            satellites.push(SatelliteInfo {
+                sat_id,
+                cn0: self.u8(block + 2)? as f64,
+                elevation: known.then_some(elevation as f64),
+                azimuth: known.then_some(self.i16(block + 4)? as f64),
+                used: flags & 0x08 != 0,
+            });
+        }
+        Some(NavSat {
+            itow: self.u32(0)?,
+            satellites,
+        })
+    }
+
+    fn subframe(&self) -> Option<Subframe> {
+        let sat_id = sat_id(self.u8(0)?, self.u8(1)?)?;
+        let words = (0..self.u8(4)? as usize)
+            .map(|i| self.u32(8 + 4 * i))
+            .collect::<Option<Vec<u32>>>()?;
+        Some(Subframe {
+            sat_id,
+            frequency_channel: (sat_id.constellation == System::Glonass)
+                .then(|| self.u8(3).map(|id| id as i8 - 7))
+                .flatten(),
+            words,
+        })
+    }
+}
+
+/// Satellite of a u-blox gnssId and svId; SBAS svIds are PRNs 120-158, RINEX Snn
+fn sat_id(gnss_id: u8, sv_id: u8) -> Option<SatId> {
+    let (system, prn) = match gnss_id {
+        0 => (System::Gps, sv_id),
+        1 => (System::Sbas, sv_id.checked_sub(100)?),
+        2 => (System::Galileo, sv_id),
+        3 => (System::BeiDou, sv_id),
+        5 => (System::Qzss, sv_id),
+        6 => (System::Glonass, sv_id),
+        7 => (System::Irnss, sv_id),
+        _ => return None,
+    };
+    Some(SatId::new(system, prn))
+}
+
+/// Messages of any byte stream: a serial port, a TCP socket, a replayed log file. Reads
+/// block until a message is complete; the iterator ends with the stream, once the
+/// messages buffered behind one it cut off are out.
+pub struct MessageReader<R> {
+    reader: R,
+    decoder: MessageDecoder,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: MessageDecoder::new(),
+        }
+    }
+
+    /// Skipped bytes and checksum errors so far
+    pub fn decoder(&self) -> &MessageDecoder {
+        &self.decoder
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for MessageReader<R> {
+    type Item = io::Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0; READ_BUFFER];
+        loop {
+            if let Some(message) = self.decoder.next_message() {
+                return Some(Ok(message));
+            }
+            match self.reader.read(&mut buf) {
+                Ok(0) => return self.decoder.finish().map(Ok),
+                Ok(n) => self.decoder.push(&buf[..n]),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// Port, line speed and reconnection behaviour
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerialConfig {
+    pub path: String, // e.g. "/dev/ttyACM0" or "COM3"
+    pub baud_rate: u32,
+    pub timeout: Duration, // Without data before the port is taken as gone and reopened
+    pub reconnect_interval: Duration, // Between attempts to reopen
+    pub max_attempts: Option<u32>, // Consecutive failed reopens before giving up, None retries forever
+}
+
+impl SerialConfig {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            baud_rate: 9600,
+            timeout: Duration::from_secs(5),
+            reconnect_interval: Duration::from_secs(1),
+            max_attempts: None,
+        }
+    }
+
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn reconnect_interval(mut self, interval: Duration) -> Self {
+        self.reconnect_interval = interval;
+        self
+    }
+
+    pub fn max_attempts(mut self, attempts: Option<u32>) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+}
+
+/// A serial port that reopens itself. Reads block through unplugging and replugging the
+/// receiver, and only fail once `SerialConfig::max_attempts` reopens in a row have.
+/// A message cut off by a dropout is skipped like any other garbage.
+pub struct SerialSource {
+    config: SerialConfig,
+    port: Option<Box<dyn SerialPort>>,
+    failures: u32,   // Consecutive failed reopens
+    reconnects: u32, // Successful reopens
+}
+
+impl SerialSource {
+    /// Open the port now, so a wrong path or speed shows before the first read
+    pub fn open(config: SerialConfig) -> Result<Self, serialport::Error> {
+        let port = Self::open_port(&config)?;
+        Ok(Self {
+            config,
+            port: Some(port),
+            failures: 0,
+            reconnects: 0,
+        })
+    }
+
+    /// Messages read from the port
+    pub fn messages(self) -> MessageReader<Self> {
+        MessageReader::new(self)
+    }
+
+    pub fn config(&self) -> &SerialConfig {
+        &self.config
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.port.is_some()
+    }
+
+    /// Reopens after a dropout
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects
+    }
+
+    fn open_port(config: &SerialConfig) -> Result<Box<dyn SerialPort>, serialport::Error> {
+        serialport::new(&config.path, config.baud_rate)
+            .timeout(config.timeout)
+            .open()
+    }
+
+    fn reconnect(&mut self) -> io::Result<()> {
+        loop {
+            thread::sleep(self.config.reconnect_interval);
+            match Self::open_port(&self.config) {
+                Ok(port) => {
+                    debug!("reopened {}", self.config.path);
+                    self.port = Some(port);
+                    self.failures = 0;
+                    self.reconnects += 1;
+                    return Ok(());
+                }
+                Err(error) => {
+                    self.failures += 1;
+                    if self
+                        .config
+                        .max_attempts
+                        .is_some_and(|max| self.failures >= max)
+                    {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotConnected,
+                            format!(
+                                "gave up reopening {} after {} attempts: {}",
+                                self.config.path, self.failures, error
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Read for SerialSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let Some(port) = &mut self.port else {
+                self.reconnect()?;
+                continue;
+            };
+            match port.read(buf) {
+                Ok(0) => {}
+                Ok(n) => return Ok(n),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => debug!("{} dropped out: {}", self.config.path, error),
+            }
+            // Closed, failed, or silent for the whole timeout
+            self.port = None;
+        }
+    }
+}
+
+/// USB serial ports present now, the usual home of a GNSS receiver, by name
+pub fn usb_ports() -> Vec<String> {
+    serialport::available_ports()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|port| matches!(port.port_type, SerialPortType::UsbPort(_)))
+        .map(|port| port.port_name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random numbers for the noise, so a failure reproduces
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            self.0 >> 33
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            self.next() as usize % n
+        }
+    }
+
+    /// An in-memory port handing out 1 to 7 bytes a read, now and then interrupted
+    struct Chunky {
+        bytes: Vec<u8>,
+        position: usize,
+        rng: Lcg,
+    }
+
+    impl Read for Chunky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.rng.below(10) == 0 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let n = (1 + self.rng.below(7))
+                .min(buf.len())
+                .min(self.bytes.len() - self.position);
+            buf[..n].copy_from_slice(&self.bytes[self.position..self.position + n]);
+            self.position += n;
+            Ok(n)
+        }
+    }
+
+    fn nmea_line(body: &str) -> Vec<u8> {
+        format!("{}\r\n", nmea::sentence(body)).into_bytes()
+    }
+
+    /// The receiver's valid messages: NMEA GGA, RMC and GSV, and UBX NAV-PVT, NAV-SAT and
+    /// RXM-SFRBX
+    fn valid_messages() -> Vec<Vec<u8>> {
+        let mut pvt = vec![0; 92];
+        pvt[0..4].copy_from_slice(&93_600_000u32.to_le_bytes());
+        pvt[4..6].copy_from_slice(&2023u16.to_le_bytes());
+        pvt[6..12].copy_from_slice(&[6, 12, 2, 0, 0, 0x03]);
+        pvt[20..24].copy_from_slice(&[3, 0x01, 0, 9]);
+        pvt[24..28].copy_from_slice(&(-1_050_000_000i32).to_le_bytes());
+        pvt[28..32].copy_from_slice(&400_000_000i32.to_le_bytes());
+        pvt[32..36].copy_from_slice(&1_600_000i32.to_le_bytes());
+
+        let mut sat = vec![0, 0, 0, 0, 1, 2, 0, 0];
+        sat.extend_from_slice(&[0, 17, 45, 60, 0x2C, 0x01, 0, 0, 0x08, 0, 0, 0]);
+        sat.extend_from_slice(&[6, 3, 38, 20, 0x5A, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut sfrbx = vec![0, 17, 0, 0, 10, 0, 2, 0];
+        for word in 0..10u32 {
+            sfrbx.extend_from_slice(&(0x22C0_0000 + word).to_le_bytes());
+        }
+
+        vec![
+            nmea_line("GPGGA,020000.00,4000.0000,N,10500.0000,W,1,09,0.9,1600.0,M,-21.0,M,,"),
+            ubx_frame(0x01, 0x07, &pvt),
+            nmea_line("GPRMC,020000.00,A,4000.0000,N,10500.0000,W,0.0,0.0,120623,,,A"),
+            ubx_frame(0x01, 0x35, &sat),
+            nmea_line("GPGSV,1,1,02,17,60,300,45,03,20,090,38"),
+            ubx_frame(0x02, 0x13, &sfrbx),
+        ]
+    }
+
+    /// Bytes outside any message, with stray '$' and sync bytes among them
+    fn garbage(rng: &mut Lcg) -> Vec<u8> {
+        (0..rng.below(40))
+            .map(|_| match rng.below(8) {
+                0 => b'$',
+                1 => UBX_SYNC[0],
+                2 => b'\n',
+                _ => rng.next() as u8,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn noisy_log_replays_every_valid_message() {
+        let valid = valid_messages();
+        let expected: Vec<Message> = valid
+            .iter()
+            .map(|bytes| {
+                let mut decoder = MessageDecoder::new();
+                decoder.push(bytes);
+                decoder.next_message().unwrap()
+            })
+            .collect();
+        assert!(matches!(expected[1], Message::Ubx(UbxMessage::NavPvt(_))));
+        assert!(matches!(expected[3], Message::Ubx(UbxMessage::NavSat(_))));
+        assert!(matches!(expected[5], Message::Ubx(UbxMessage::Subframe(_))));
+
+        let mut rng = Lcg(0x5EED);
+        let (mut log, mut corrupted) = (Vec::new(), 0);
+        for round in 0..50 {
+            for bytes in &valid {
+                log.extend(garbage(&mut rng));
+                // A copy cut off partway, or with one byte changed, before the real one
+                let mut damaged = bytes.clone();
+                match rng.below(3) {
+                    0 => damaged.truncate(1 + rng.below(bytes.len() - 2)),
+                    1 => {
+                        let at = 1 + rng.below(bytes.len() - 4);
+                        damaged[at] ^= 0x01;
+                        damaged.truncate(if round % 2 == 0 { bytes.len() } else { at + 1 });
+                        corrupted += usize::from(damaged.len() == bytes.len());
+                    }
+                    _ => damaged.clear(),
+                }
+                log.extend(damaged);
+                log.extend(bytes);
+            }
+        }
+        log.extend(garbage(&mut rng));
+
+        let mut reader = MessageReader::new(Chunky {
+            bytes: log,
+            position: 0,
+            rng: Lcg(7),
+        });
+        let messages: Vec<Message> = reader.by_ref().map(Result::unwrap).collect();
+        assert_eq!(messages.len(), 50 * valid.len());
+        for (index, message) in messages.iter().enumerate() {
+            assert_eq!(*message, expected[index % valid.len()], "message {}", index);
+        }
+        assert!(reader.decoder().checksum_errors >= corrupted as u64);
+        assert!(reader.decoder().skipped_bytes > 0);
+        assert_eq!(reader.get_ref().position, reader.get_ref().bytes.len());
+    }
+
+    #[test]
+    fn unplugged_port_gives_up_after_max_attempts() {
+        let config = SerialConfig::new("/dev/pnt-rust-no-such-port")
+            .reconnect_interval(Duration::from_millis(1))
+            .max_attempts(Some(3));
+        assert!(SerialSource::open(config.clone()).is_err());
+
+        // As left by a dropout: no port, reopening from the next read
+        let mut source = SerialSource {
+            config,
+            port: None,
+            failures: 0,
+            reconnects: 0,
+        };
+        let error = source.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotConnected);
+        assert!(error.to_string().contains("after 3 attempts"), "{}", error);
+        assert!(!source.is_connected());
+        assert_eq!(source.reconnects(), 0);
+        assert_eq!(source.read(&mut []).unwrap(), 0);
+    }
+}
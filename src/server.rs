@@ -0,0 +1,808 @@
+//! Live state streaming over TCP or WebSocket. `StateServer::run` walks a propagated
+//! constellation against the wall clock and publishes one JSON message per satellite
+//! and tick: newline-delimited on plain TCP, one text frame each on WebSocket.
+//!
+//! Clients start subscribed to every satellite and send commands as lines (text frames
+//! on WebSocket): "subscribe G01 G05" narrows to those satellites, "unsubscribe G05"
+//! removes one, "subscribe all" and "unsubscribe all" reset. Each client has a bounded
+//! queue drained by its own writer thread, so a slow client is decimated or dropped and
+//! never stalls publishing.
+
+use crate::constellation::Constellation;
+use crate::gnss::{self, SatId, ECEF};
+use crate::json;
+use crate::ntrip::base64;
+use crate::satellite::Satellite;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+const DETECT_TIMEOUT: Duration = Duration::from_millis(200); // Wait for "GET " before plain TCP
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_FRAME: u64 = 64 * 1024; // Largest client WebSocket frame accepted
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How simulated time advances
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    Now,                   // Publish the states at the current UTC time
+    Replay { speed: f64 }, // From the first state, simulated seconds per wall-clock second
+}
+
+/// What to do with a client whose queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowClient {
+    #[default]
+    Decimate, // Skip messages until the queue drains
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServerConfig {
+    pub interval: Duration, // Wall-clock time between publications
+    pub pacing: Pacing,
+    pub queue: usize, // Messages buffered per client
+    pub slow_client: SlowClient,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            pacing: Pacing::Replay { speed: 1.0 },
+            queue: 256,
+            slow_client: SlowClient::default(),
+        }
+    }
+}
+
+/// Counters since the server started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ServerStats {
+    pub connected: u64, // Clients accepted
+    pub ticks: u64,
+    pub messages: u64, // Queued to clients
+    pub skipped: u64,  // Not queued because a client was behind
+    pub dropped: u64,  // Clients disconnected for being behind
+}
+
+/// Publishes satellite positions to connected clients; stops when dropped
+pub struct StateServer {
+    address: SocketAddr,
+    shared: Arc<Shared>,
+    accept: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    config: ServerConfig,
+    clients: Mutex<Vec<Client>>,
+    stop: AtomicBool,
+    stats: Mutex<ServerStats>,
+}
+
+struct Client {
+    sender: SyncSender<Outgoing>,
+    stream: TcpStream, // For shutting the connection down from the publisher
+    subscription: Arc<Mutex<Subscription>>,
+    alive: Arc<AtomicBool>,
+}
+
+enum Outgoing {
+    Message(Arc<str>),
+    Pong(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Tcp,
+    WebSocket,
+}
+
+/// Satellites a client wants; starts with all of them
+#[derive(Debug, Default)]
+struct Subscription {
+    narrowed: bool,
+    satellites: BTreeSet<SatId>,
+}
+
+impl Subscription {
+    fn wants(&self, sat_id: SatId) -> bool {
+        !self.narrowed || self.satellites.contains(&sat_id)
+    }
+
+    /// Apply one command line; unknown commands and ids are ignored
+    fn apply(&mut self, command: &str) {
+        let mut words = command.split_whitespace();
+        let subscribe = match words.next() {
+            Some(word) if word.eq_ignore_ascii_case("subscribe") => true,
+            Some(word) if word.eq_ignore_ascii_case("unsubscribe") => false,
+            _ => return,
+        };
+        for word in words {
+            if word.eq_ignore_ascii_case("all") {
+                self.narrowed = !subscribe;
+                self.satellites.clear();
+                continue;
+            }
+            let Ok(sat_id) = word.trim_matches(',').parse::<SatId>() else {
+                continue;
+            };
+            if subscribe {
+                if !self.narrowed {
+                    self.narrowed = true;
+                    self.satellites.clear();
+                }
+                self.satellites.insert(sat_id);
+            } else {
+                self.satellites.remove(&sat_id);
+            }
+        }
+    }
+}
+
+impl StateServer {
+    /// Listen on `address` (port 0 picks a free one) and accept clients in the background
+    pub fn bind(address: impl ToSocketAddrs, config: ServerConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            config,
+            clients: Mutex::new(Vec::new()),
+            stop: AtomicBool::new(false),
+            stats: Mutex::new(ServerStats::default()),
+        });
+        let accept = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || accept_loop(listener, shared))
+        };
+        Ok(Self {
+            address,
+            shared,
+            accept: Some(accept),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn client_count(&self) -> usize {
+        let mut clients = self.shared.clients.lock().unwrap();
+        clients.retain(|client| client.alive.load(Ordering::Relaxed));
+        clients.len()
+    }
+
+    pub fn stats(&self) -> ServerStats {
+        *self.shared.stats.lock().unwrap()
+    }
+
+    /// Publish the constellation's propagated states, interpolated to each tick, until
+    /// simulated time passes the last state or `stop` is called
+    pub fn run(&self, constellation: &Constellation) -> ServerStats {
+        let satellites: Vec<&Satellite> = constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .collect();
+        let first = satellites
+            .iter()
+            .filter_map(|satellite| satellite.states.first())
+            .map(|state| state.time())
+            .fold(f64::INFINITY, f64::min);
+        let last = satellites
+            .iter()
+            .filter_map(|satellite| satellite.states.last())
+            .map(|state| state.time())
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let started = Instant::now();
+        let mut ticks = 0u32;
+        while !self.shared.stop.load(Ordering::Relaxed) {
+            let time = match self.shared.config.pacing {
+                Pacing::Now => gnss::gps_seconds(chrono::Utc::now()),
+                Pacing::Replay { speed } => first + started.elapsed().as_secs_f64() * speed,
+            };
+            if time.is_nan() || time > last {
+                break;
+            }
+            let epoch = gnss::gps_seconds_to_utc(time);
+            let messages: Vec<(SatId, Arc<str>)> = satellites
+                .iter()
+                .filter_map(|satellite| {
+                    let position = satellite.interpolate_at(epoch).ok()?;
+                    Some((satellite.id, message(satellite.id, time, position).into()))
+                })
+                .collect();
+            self.broadcast(&messages);
+            self.shared.stats.lock().unwrap().ticks += 1;
+
+            ticks += 1;
+            let next = started + self.shared.config.interval * ticks;
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+        }
+        self.stats()
+    }
+
+    /// Publish one position outside of `run`
+    pub fn publish(&self, sat_id: SatId, gps_time: f64, position: ECEF) {
+        self.broadcast(&[(sat_id, message(sat_id, gps_time, position).into())]);
+    }
+
+    /// Make `run` return after the current tick
+    pub fn stop(&self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Queue every message a client subscribes to, without ever blocking on a client
+    fn broadcast(&self, messages: &[(SatId, Arc<str>)]) {
+        let mut stats = ServerStats::default();
+        let mut clients = self.shared.clients.lock().unwrap();
+        for client in clients.iter() {
+            let subscription = client.subscription.lock().unwrap();
+            for (sat_id, message) in messages {
+                if !subscription.wants(*sat_id) {
+                    continue;
+                }
+                match client
+                    .sender
+                    .try_send(Outgoing::Message(Arc::clone(message)))
+                {
+                    Ok(()) => stats.messages += 1,
+                    Err(TrySendError::Full(_)) => match self.shared.config.slow_client {
+                        SlowClient::Decimate => stats.skipped += 1,
+                        SlowClient::Disconnect => {
+                            stats.dropped += 1;
+                            client.alive.store(false, Ordering::Relaxed);
+                            break;
+                        }
+                    },
+                    Err(TrySendError::Disconnected(_)) => {
+                        client.alive.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+        }
+        clients.retain(|client| {
+            let alive = client.alive.load(Ordering::Relaxed);
+            if !alive {
+                let _ = client.stream.shutdown(Shutdown::Both);
+            }
+            alive
+        });
+        drop(clients);
+
+        let mut total = self.shared.stats.lock().unwrap();
+        total.messages += stats.messages;
+        total.skipped += stats.skipped;
+        total.dropped += stats.dropped;
+    }
+}
+
+impl Drop for StateServer {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+        for client in self.shared.clients.lock().unwrap().drain(..) {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// One satellite position: id, UTC and GPS time, ECEF meters and geodetic coordinates
+fn message(sat_id: SatId, gps_time: f64, position: ECEF) -> String {
+    let lla = position.to_lla();
+    let utc = gnss::gps_seconds_to_utc(gps_time);
+    format!(
+        "{{\"sat\":{},\"time\":{},\"gps_time\":{},\"x\":{},\"y\":{},\"z\":{},\"lat\":{},\"lon\":{},\"alt\":{}}}",
+        json::string(&sat_id.to_string()),
+        json::string(&utc.format(TIME_FORMAT).to_string()),
+        json::number(gps_time),
+        json::number(position.x),
+        json::number(position.y),
+        json::number(position.z),
+        json::number(lla.latitude),
+        json::number(lla.longitude),
+        json::number(lla.altitude)
+    )
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    while !shared.stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let _ = serve_client(stream, shared);
+                });
+            }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(_) => thread::sleep(ACCEPT_POLL),
+        }
+    }
+}
+
+/// Detect the protocol, register the client and read its commands until it leaves
+fn serve_client(stream: TcpStream, shared: Arc<Shared>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    stream.set_read_timeout(Some(DETECT_TIMEOUT))?;
+    let mut first = [0; 4];
+    let protocol = match stream.peek(&mut first) {
+        Ok(4) if &first == b"GET " => Protocol::WebSocket,
+        Ok(_) => Protocol::Tcp,
+        Err(error)
+            if matches!(
+                error.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Protocol::Tcp
+        }
+        Err(error) => return Err(error),
+    };
+    stream.set_read_timeout(None)?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    if protocol == Protocol::WebSocket {
+        handshake(&mut reader, &stream)?;
+    }
+
+    let (sender, receiver) = mpsc::sync_channel(shared.config.queue.max(1));
+    let subscription = Arc::new(Mutex::new(Subscription::default()));
+    let alive = Arc::new(AtomicBool::new(true));
+    shared.clients.lock().unwrap().push(Client {
+        sender: sender.clone(),
+        stream: stream.try_clone()?,
+        subscription: Arc::clone(&subscription),
+        alive: Arc::clone(&alive),
+    });
+    shared.stats.lock().unwrap().connected += 1;
+    {
+        let stream = stream.try_clone()?;
+        let alive = Arc::clone(&alive);
+        thread::spawn(move || write_loop(stream, protocol, receiver, alive));
+    }
+
+    let result = match protocol {
+        Protocol::Tcp => read_lines(&mut reader, &subscription),
+        Protocol::WebSocket => read_frames(&mut reader, &subscription, &sender),
+    };
+    alive.store(false, Ordering::Relaxed);
+    let _ = stream.shutdown(Shutdown::Both);
+    result
+}
+
+fn write_loop(
+    mut stream: TcpStream,
+    protocol: Protocol,
+    receiver: Receiver<Outgoing>,
+    alive: Arc<AtomicBool>,
+) {
+    for outgoing in receiver {
+        let result = match (protocol, outgoing) {
+            (Protocol::Tcp, Outgoing::Message(message)) => stream
+                .write_all(message.as_bytes())
+                .and_then(|_| stream.write_all(b"\n")),
+            (Protocol::Tcp, Outgoing::Pong(_)) => Ok(()),
+            (Protocol::WebSocket, Outgoing::Message(message)) => {
+                write_frame(&mut stream, 0x1, message.as_bytes())
+            }
+            (Protocol::WebSocket, Outgoing::Pong(payload)) => {
+                write_frame(&mut stream, 0xA, &payload)
+            }
+        };
+        if result.is_err() || !alive.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    alive.store(false, Ordering::Relaxed);
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn read_lines(reader: &mut impl BufRead, subscription: &Mutex<Subscription>) -> io::Result<()> {
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        subscription.lock().unwrap().apply(&line);
+        line.clear();
+    }
+    Ok(())
+}
+
+/// Upgrade an HTTP request to a WebSocket per RFC 6455
+fn handshake(reader: &mut impl BufRead, mut stream: &TcpStream) -> io::Result<()> {
+    let mut key = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Sec-WebSocket-Key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+    let Some(key) = key else {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n")?;
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a WebSocket upgrade",
+        ));
+    };
+    let accept = base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()));
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )
+}
+
+/// Client frames: text frames carry commands, pings are answered, close ends the session
+fn read_frames(
+    reader: &mut impl Read,
+    subscription: &Mutex<Subscription>,
+    sender: &SyncSender<Outgoing>,
+) -> io::Result<()> {
+    loop {
+        let mut header = [0; 2];
+        reader.read_exact(&mut header)?;
+        let opcode = header[0] & 0x0F;
+        let masked = header[1] & 0x80 != 0;
+        let length = match header[1] & 0x7F {
+            126 => {
+                let mut length = [0; 2];
+                reader.read_exact(&mut length)?;
+                u16::from_be_bytes(length) as u64
+            }
+            127 => {
+                let mut length = [0; 8];
+                reader.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            length => length as u64,
+        };
+        if length > MAX_FRAME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "WebSocket frame too large",
+            ));
+        }
+        let mut mask = [0; 4];
+        if masked {
+            reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; length as usize];
+        reader.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        match opcode {
+            0x1 => {
+                let text = String::from_utf8_lossy(&payload);
+                let mut subscription = subscription.lock().unwrap();
+                text.lines().for_each(|command| subscription.apply(command));
+            }
+            0x8 => return Ok(()),
+            0x9 => {
+                let _ = sender.try_send(Outgoing::Pong(payload));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Unmasked, unfragmented server frame
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+/// SHA-1, needed only for the WebSocket accept key
+fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((message.len() as u64) * 8).to_be_bytes());
+
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, value) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::propagated_hour;
+
+    const INTERVAL: Duration = Duration::from_millis(50);
+    const SPEED: f64 = 2000.0; // 100 simulated seconds per tick
+
+    fn server() -> StateServer {
+        let config = ServerConfig {
+            interval: INTERVAL,
+            pacing: Pacing::Replay { speed: SPEED },
+            ..ServerConfig::default()
+        };
+        StateServer::bind("127.0.0.1:0", config).unwrap()
+    }
+
+    /// Wait until the server has registered `count` clients and read their commands
+    fn wait_for_clients(server: &StateServer, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.client_count() < count {
+            assert!(Instant::now() < deadline, "clients did not connect");
+            thread::sleep(Duration::from_millis(5));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    /// Raw JSON value of `key` in a flat message
+    fn field<'a>(message: &'a str, key: &str) -> &'a str {
+        let start = message.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3;
+        let rest = &message[start..];
+        &rest[..rest.find([',', '}']).unwrap()]
+    }
+
+    fn number(message: &str, key: &str) -> f64 {
+        field(message, key).parse().unwrap()
+    }
+
+    fn masked_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+        frame
+    }
+
+    /// Opcode and payload of an unmasked server frame
+    fn read_frame(reader: &mut impl Read) -> (u8, Vec<u8>) {
+        let mut header = [0; 2];
+        reader.read_exact(&mut header).unwrap();
+        assert_eq!(header[1] & 0x80, 0, "server frames are unmasked");
+        let length = match header[1] & 0x7F {
+            126 => {
+                let mut length = [0; 2];
+                reader.read_exact(&mut length).unwrap();
+                u16::from_be_bytes(length) as usize
+            }
+            length => length as usize,
+        };
+        let mut payload = vec![0; length];
+        reader.read_exact(&mut payload).unwrap();
+        (header[0] & 0x0F, payload)
+    }
+
+    #[test]
+    fn tcp_clients_get_their_satellites_every_tick() {
+        let constellation = propagated_hour();
+        let server = server();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"subscribe G05, G17\n").unwrap();
+        let reader = thread::spawn(move || {
+            BufReader::new(stream)
+                .lines()
+                .map(Result::unwrap)
+                .collect::<Vec<String>>()
+        });
+        wait_for_clients(&server, 1);
+
+        let stats = server.run(&constellation);
+        drop(server);
+        let messages = reader.join().unwrap();
+
+        assert_eq!(stats.connected, 1);
+        assert_eq!(stats.skipped + stats.dropped, 0);
+        assert_eq!(messages.len() as u64, stats.messages);
+        // The 3540 s of states at 100 s a tick, both satellites every tick
+        assert!((30..=36).contains(&stats.ticks), "{} ticks", stats.ticks);
+        assert_eq!(messages.len() as u64, 2 * stats.ticks);
+
+        let mut times = Vec::new();
+        for pair in messages.chunks(2) {
+            let ids: Vec<&str> = pair.iter().map(|m| field(m, "sat")).collect();
+            assert_eq!(ids, ["\"G05\"", "\"G17\""]);
+            assert_eq!(number(&pair[0], "gps_time"), number(&pair[1], "gps_time"));
+            times.push(number(&pair[0], "gps_time"));
+        }
+        // Ticks follow the replay speed, give or take scheduling jitter
+        let step = INTERVAL.as_secs_f64() * SPEED;
+        for pair in times.windows(2) {
+            let advance = pair[1] - pair[0];
+            assert!(
+                (advance - step).abs() < 0.5 * step,
+                "advanced {} s",
+                advance
+            );
+        }
+
+        for message in &messages {
+            let sat_id: SatId = field(message, "sat").trim_matches('"').parse().unwrap();
+            let gps_time = number(message, "gps_time");
+            let utc = gnss::gps_seconds_to_utc(gps_time);
+            let time = format!("\"{}\"", utc.format(TIME_FORMAT));
+            assert_eq!(field(message, "time"), time);
+            let satellite = constellation.get(sat_id).unwrap();
+            let expected = satellite.interpolate_at(utc).unwrap();
+            let position = ECEF::new(
+                number(message, "x"),
+                number(message, "y"),
+                number(message, "z"),
+            );
+            assert!(position.approx_eq(&expected, 1e-3));
+            let lla = expected.to_lla();
+            assert!((number(message, "lat") - lla.latitude).abs() < 1e-9);
+            assert!((number(message, "lon") - lla.longitude).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn websocket_clients_subscribe_ping_and_close() {
+        let server = server();
+        let stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        // The example key of RFC 6455
+        writer
+            .write_all(
+                b"GET /states HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                  Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        let mut response = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            response.push(line);
+        }
+        assert_eq!(response[0], "HTTP/1.1 101 Switching Protocols\r\n");
+        assert!(response.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n".into()));
+
+        writer
+            .write_all(&masked_frame(0x1, b"subscribe G17"))
+            .unwrap();
+        wait_for_clients(&server, 1);
+        let position = ECEF::new(15_600_000.0, 7_540_000.0, 20_140_000.0);
+        server.publish(SatId::gps(5), 1_370_570_418.0, position);
+        server.publish(SatId::gps(17), 1_370_570_418.0, position);
+        let (opcode, payload) = read_frame(&mut reader);
+        assert_eq!(opcode, 0x1);
+        let message = String::from_utf8(payload).unwrap();
+        assert_eq!(field(&message, "sat"), "\"G17\"");
+        assert_eq!(number(&message, "x"), 15_600_000.0);
+
+        writer.write_all(&masked_frame(0x9, b"ping")).unwrap();
+        assert_eq!(read_frame(&mut reader), (0xA, b"ping".to_vec()));
+
+        writer.write_all(&masked_frame(0x8, &[])).unwrap();
+        let mut rest = Vec::new();
+        assert!(reader.read_to_end(&mut rest).is_ok() && rest.is_empty());
+        assert_eq!(server.client_count(), 0);
+    }
+
+    #[test]
+    fn requests_without_a_key_are_refused() {
+        let server = server();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert_eq!(server.stats().connected, 0);
+    }
+
+    #[test]
+    fn subscription_commands() {
+        let mut subscription = Subscription::default();
+        assert!(subscription.wants(SatId::gps(1)));
+        subscription.apply("SUBSCRIBE G01 G02 bogus");
+        assert!(subscription.wants(SatId::gps(2)) && !subscription.wants(SatId::gps(3)));
+        subscription.apply("unsubscribe G02");
+        assert!(subscription.wants(SatId::gps(1)) && !subscription.wants(SatId::gps(2)));
+        subscription.apply("hello G03");
+        assert!(!subscription.wants(SatId::gps(3)));
+        subscription.apply("unsubscribe all");
+        assert!(!subscription.wants(SatId::gps(1)));
+        subscription.apply("subscribe all");
+        assert!(subscription.wants(SatId::gps(3)));
+    }
+
+    #[test]
+    fn sha1_matches_fips_180_vectors() {
+        let hex = |digest: [u8; 20]| -> String {
+            digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+        };
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::gnss::{self, Constellation};
+use std::fmt;
+
+const GLONASS_G1_BASE: f64 = 1602.0e6; // G1 FDMA centre for channel 0, Hz
+const GLONASS_G1_STEP: f64 = 0.5625e6; // G1 spacing per frequency channel, Hz
+const GLONASS_G2_BASE: f64 = 1246.0e6;
+const GLONASS_G2_STEP: f64 = 0.4375e6;
+
+/// Carrier a GNSS observation is tracked on. GLONASS FDMA signals carry the satellite's
+/// frequency channel k (-7..=6).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Signal {
+    GpsL1,
+    GpsL2,
+    GpsL5,
+    GlonassG1(i8),
+    GlonassG2(i8),
+    GlonassG3,
+    GalileoE1,
+    GalileoE5a,
+    GalileoE5b,
+    GalileoE5,
+    GalileoE6,
+    BeiDouB1I,
+    BeiDouB1C,
+    BeiDouB2a,
+    BeiDouB2b,
+    BeiDouB3,
+}
+
+impl Signal {
+    /// Carrier frequency in Hz
+    pub fn frequency_hz(self) -> f64 {
+        match self {
+            Self::GpsL1 | Self::GalileoE1 | Self::BeiDouB1C => gnss::L1_FREQUENCY,
+            Self::GpsL2 => 1227.60e6,
+            Self::GpsL5 | Self::GalileoE5a | Self::BeiDouB2a => 1176.45e6,
+            Self::GlonassG1(k) => GLONASS_G1_BASE + k as f64 * GLONASS_G1_STEP,
+            Self::GlonassG2(k) => GLONASS_G2_BASE + k as f64 * GLONASS_G2_STEP,
+            Self::GlonassG3 => 1202.025e6,
+            Self::GalileoE5b | Self::BeiDouB2b => 1207.14e6,
+            Self::GalileoE5 => 1191.795e6,
+            Self::GalileoE6 => 1278.75e6,
+            Self::BeiDouB1I => 1561.098e6,
+            Self::BeiDouB3 => 1268.52e6,
+        }
+    }
+
+    /// System whose satellites transmit the signal
+    pub fn constellation(self) -> Constellation {
+        match self {
+            Self::GpsL1 | Self::GpsL2 | Self::GpsL5 => Constellation::Gps,
+            Self::GlonassG1(_) | Self::GlonassG2(_) | Self::GlonassG3 => Constellation::Glonass,
+            Self::GalileoE1
+            | Self::GalileoE5a
+            | Self::GalileoE5b
+            | Self::GalileoE5
+            | Self::GalileoE6 => Constellation::Galileo,
+            Self::BeiDouB1I
+            | Self::BeiDouB1C
+            | Self::BeiDouB2a
+            | Self::BeiDouB2b
+            | Self::BeiDouB3 => Constellation::BeiDou,
+        }
+    }
+
+    /// Chipping rate of the primary ranging code, chips/s: C/A for GPS L1 and GLONASS,
+    /// the data component's code otherwise (L2 CM, L5-I, E1-B, E5a-I, B1C data, ...)
+    pub fn chip_rate(self) -> f64 {
+        match self {
+            Self::GpsL1 | Self::GalileoE1 | Self::BeiDouB1C => 1.023e6,
+            Self::GpsL2 => 0.5115e6,
+            Self::GlonassG1(_) | Self::GlonassG2(_) => 0.511e6,
+            Self::BeiDouB1I => 2.046e6,
+            Self::GalileoE6 => 5.115e6,
+            Self::GpsL5
+            | Self::GlonassG3
+            | Self::GalileoE5a
+            | Self::GalileoE5b
+            | Self::GalileoE5
+            | Self::BeiDouB2a
+            | Self::BeiDouB2b
+            | Self::BeiDouB3 => 10.23e6,
+        }
+    }
+
+    /// Length of the primary ranging code `chip_rate` refers to, chips
+    pub fn code_length(self) -> u32 {
+        match self {
+            Self::GpsL1 => 1023,
+            Self::GlonassG1(_) | Self::GlonassG2(_) => 511,
+            Self::BeiDouB1I => 2046,
+            Self::GalileoE1 => 4092,
+            Self::GalileoE6 => 5115,
+            Self::GpsL2
+            | Self::GpsL5
+            | Self::GlonassG3
+            | Self::GalileoE5a
+            | Self::GalileoE5b
+            | Self::GalileoE5
+            | Self::BeiDouB1C
+            | Self::BeiDouB2a
+            | Self::BeiDouB2b
+            | Self::BeiDouB3 => 10230,
+        }
+    }
+
+    /// Duration of one primary code period, s
+    pub fn code_period(self) -> f64 {
+        self.code_length() as f64 / self.chip_rate()
+    }
+
+    /// Carrier wavelength in m
+    pub fn wavelength(self) -> f64 {
+        gnss::C_LIGHT / self.frequency_hz()
+    }
+
+    /// First-order ionospheric delay on this signal relative to the delay on `reference`,
+    /// (f_ref / f)²
+    pub fn iono_factor(self, reference: Signal) -> f64 {
+        (reference.frequency_hz() / self.frequency_hz()).powi(2)
+    }
+
+    /// Signal of a RINEX 3 observation code such as "C1C", "L2W" or "D5Q". The band digit
+    /// is what matters; QZSS and SBAS share the GPS carriers. GLONASS G1/G2 need the
+    /// satellite's frequency channel and give None without one.
+    pub fn from_rinex_code(
+        constellation: Constellation,
+        code: &str,
+        frequency_channel: Option<i8>,
+    ) -> Option<Self> {
+        let mut chars = code.chars();
+        if !matches!(chars.next()?, 'C' | 'L' | 'D' | 'S') {
+            return None;
+        }
+        let band = chars.next()?;
+        match (constellation, band) {
+            (Constellation::Gps | Constellation::Qzss | Constellation::Sbas, '1') => {
+                Some(Self::GpsL1)
+            }
+            (Constellation::Gps | Constellation::Qzss, '2') => Some(Self::GpsL2),
+            (
+                Constellation::Gps
+                | Constellation::Qzss
+                | Constellation::Sbas
+                | Constellation::Irnss,
+                '5',
+            ) => Some(Self::GpsL5),
+            (Constellation::Glonass, '1') => frequency_channel.map(Self::GlonassG1),
+            (Constellation::Glonass, '2') => frequency_channel.map(Self::GlonassG2),
+            (Constellation::Glonass, '3') => Some(Self::GlonassG3),
+            (Constellation::Galileo, '1') => Some(Self::GalileoE1),
+            (Constellation::Galileo, '5') => Some(Self::GalileoE5a),
+            (Constellation::Galileo, '7') => Some(Self::GalileoE5b),
+            (Constellation::Galileo, '8') => Some(Self::GalileoE5),
+            (Constellation::Galileo, '6') => Some(Self::GalileoE6),
+            (Constellation::BeiDou, '2') => Some(Self::BeiDouB1I),
+            (Constellation::BeiDou, '1') => Some(Self::BeiDouB1C),
+            (Constellation::BeiDou, '5') => Some(Self::BeiDouB2a),
+            (Constellation::BeiDou, '7') => Some(Self::BeiDouB2b),
+            (Constellation::BeiDou, '6') => Some(Self::BeiDouB3),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GpsL1 => write!(f, "L1"),
+            Self::GpsL2 => write!(f, "L2"),
+            Self::GpsL5 => write!(f, "L5"),
+            Self::GlonassG1(k) => write!(f, "G1({:+})", k),
+            Self::GlonassG2(k) => write!(f, "G2({:+})", k),
+            Self::GlonassG3 => write!(f, "G3"),
+            Self::GalileoE1 => write!(f, "E1"),
+            Self::GalileoE5a => write!(f, "E5a"),
+            Self::GalileoE5b => write!(f, "E5b"),
+            Self::GalileoE5 => write!(f, "E5"),
+            Self::GalileoE6 => write!(f, "E6"),
+            Self::BeiDouB1I => write!(f, "B1I"),
+            Self::BeiDouB1C => write!(f, "B1C"),
+            Self::BeiDouB2a => write!(f, "B2a"),
+            Self::BeiDouB2b => write!(f, "B2b"),
+            Self::BeiDouB3 => write!(f, "B3I"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequencies_are_those_of_the_icds() {
+        let expected = [
+            (Signal::GpsL1, 1575.42e6),
+            (Signal::GpsL2, 1227.60e6),
+            (Signal::GpsL5, 1176.45e6),
+            (Signal::GlonassG1(0), 1602.0e6),
+            (Signal::GlonassG1(-7), 1598.0625e6),
+            (Signal::GlonassG1(6), 1605.375e6),
+            (Signal::GlonassG2(0), 1246.0e6),
+            (Signal::GlonassG2(-7), 1242.9375e6),
+            (Signal::GlonassG2(6), 1248.625e6),
+            (Signal::GlonassG3, 1202.025e6),
+            (Signal::GalileoE1, 1575.42e6),
+            (Signal::GalileoE5a, 1176.45e6),
+            (Signal::GalileoE5b, 1207.14e6),
+            (Signal::GalileoE5, 1191.795e6),
+            (Signal::GalileoE6, 1278.75e6),
+            (Signal::BeiDouB1I, 1561.098e6),
+            (Signal::BeiDouB1C, 1575.42e6),
+            (Signal::BeiDouB2a, 1176.45e6),
+            (Signal::BeiDouB2b, 1207.14e6),
+            (Signal::BeiDouB3, 1268.52e6),
+        ];
+        for (signal, frequency) in expected {
+            assert_eq!(signal.frequency_hz(), frequency, "{}", signal);
+        }
+        assert!((Signal::GpsL1.wavelength() - 0.190293672798).abs() < 1e-12);
+        assert!((Signal::GpsL2.wavelength() - 0.244210213425).abs() < 1e-12);
+        assert!(
+            (Signal::GpsL2.iono_factor(Signal::GpsL1) - (77.0f64 / 60.0).powi(2)).abs() < 1e-12
+        );
+    }
+
+    #[test]
+    fn rinex_codes_map_to_signals() {
+        use Constellation::*;
+        let expected = [
+            (Gps, "C1C", Some(Signal::GpsL1)),
+            (Gps, "L2W", Some(Signal::GpsL2)),
+            (Gps, "C2L", Some(Signal::GpsL2)),
+            (Gps, "C5Q", Some(Signal::GpsL5)),
+            (Qzss, "L1C", Some(Signal::GpsL1)),
+            (Sbas, "C1C", Some(Signal::GpsL1)),
+            (Glonass, "C3Q", Some(Signal::GlonassG3)),
+            (Galileo, "C1C", Some(Signal::GalileoE1)),
+            (Galileo, "L5Q", Some(Signal::GalileoE5a)),
+            (Galileo, "C7Q", Some(Signal::GalileoE5b)),
+            (Galileo, "D8Q", Some(Signal::GalileoE5)),
+            (Galileo, "S6C", Some(Signal::GalileoE6)),
+            (BeiDou, "C2I", Some(Signal::BeiDouB1I)),
+            (BeiDou, "C1P", Some(Signal::BeiDouB1C)),
+            (BeiDou, "C5P", Some(Signal::BeiDouB2a)),
+            (BeiDou, "C7I", Some(Signal::BeiDouB2b)),
+            (BeiDou, "C6I", Some(Signal::BeiDouB3)),
+            (Gps, "C7Q", None),
+            (Gps, "X1C", None),
+            (Gps, "C", None),
+        ];
+        for (constellation, code, signal) in expected {
+            assert_eq!(
+                Signal::from_rinex_code(constellation, code, None),
+                signal,
+                "{:?} {}",
+                constellation,
+                code
+            );
+        }
+        // GLONASS FDMA needs the frequency channel
+        assert_eq!(Signal::from_rinex_code(Glonass, "C1C", None), None);
+        assert_eq!(
+            Signal::from_rinex_code(Glonass, "C1C", Some(-4)),
+            Some(Signal::GlonassG1(-4))
+        );
+        assert_eq!(
+            Signal::from_rinex_code(Glonass, "L2P", Some(3)),
+            Some(Signal::GlonassG2(3))
+        );
+        assert_eq!(Signal::GlonassG1(-4).constellation(), Glonass);
+    }
+}
@@ -0,0 +1,568 @@
+use crate::constellation::Constellation;
+use crate::gnss::{self, GpsTime, SatId, ECEF};
+use crate::observation::{ObservationEpoch, SatelliteObservations, SignalObservation};
+use crate::positioning::Weighting;
+use crate::propagator::BroadcastPropagator;
+use crate::pseudorange;
+use crate::satellite::PropagationConfig;
+use crate::signal::Signal;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+
+/// Small seedable generator (SplitMix64) so simulations are reproducible without pulling in
+/// a random number crate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal by the Box-Muller transform
+    pub fn gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.uniform(); // (0, 1], keeps ln finite
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// Measurement errors added to simulated observations. Implement it to inject multipath,
+/// clock jumps or anything else; each method is called once per satellite, signal and epoch,
+/// in time order. Implementations draw from a seeded `Rng` so runs are reproducible.
+pub trait ErrorModel {
+    /// Pseudorange error, m
+    fn pseudorange_error(
+        &mut self,
+        sat_id: SatId,
+        signal: Signal,
+        elevation: f64,
+        epoch: DateTime<Utc>,
+    ) -> f64;
+
+    /// Carrier phase error, m
+    fn phase_error(
+        &mut self,
+        _sat_id: SatId,
+        _signal: Signal,
+        _elevation: f64,
+        _epoch: DateTime<Utc>,
+    ) -> f64 {
+        0.0
+    }
+
+    /// Doppler error, Hz
+    fn doppler_error(
+        &mut self,
+        _sat_id: SatId,
+        _signal: Signal,
+        _elevation: f64,
+        _epoch: DateTime<Utc>,
+    ) -> f64 {
+        0.0
+    }
+}
+
+/// White Gaussian noise with a fixed sigma per observable
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaussianNoise {
+    pub code_sigma: f64,    // m
+    pub phase_sigma: f64,   // m
+    pub doppler_sigma: f64, // Hz
+    pub rng: Rng,
+}
+
+impl GaussianNoise {
+    pub fn new(code_sigma: f64, phase_sigma: f64, doppler_sigma: f64, seed: u64) -> Self {
+        Self {
+            code_sigma,
+            phase_sigma,
+            doppler_sigma,
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl ErrorModel for GaussianNoise {
+    fn pseudorange_error(&mut self, _: SatId, _: Signal, _: f64, _: DateTime<Utc>) -> f64 {
+        self.code_sigma * self.rng.gaussian()
+    }
+
+    fn phase_error(&mut self, _: SatId, _: Signal, _: f64, _: DateTime<Utc>) -> f64 {
+        self.phase_sigma * self.rng.gaussian()
+    }
+
+    fn doppler_error(&mut self, _: SatId, _: Signal, _: f64, _: DateTime<Utc>) -> f64 {
+        self.doppler_sigma * self.rng.gaussian()
+    }
+}
+
+/// White Gaussian noise whose sigma depends on the elevation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElevationNoise {
+    pub code: Weighting,  // m
+    pub phase: Weighting, // m
+    pub rng: Rng,
+}
+
+impl ElevationNoise {
+    pub fn new(code: Weighting, phase: Weighting, seed: u64) -> Self {
+        Self {
+            code,
+            phase,
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl ErrorModel for ElevationNoise {
+    fn pseudorange_error(&mut self, _: SatId, _: Signal, elevation: f64, _: DateTime<Utc>) -> f64 {
+        self.code.sigma(elevation) * self.rng.gaussian()
+    }
+
+    fn phase_error(&mut self, _: SatId, _: Signal, elevation: f64, _: DateTime<Utc>) -> f64 {
+        self.phase.sigma(elevation) * self.rng.gaussian()
+    }
+}
+
+/// First-order Gauss-Markov code error per satellite and signal, a stand-in for multipath:
+/// stationary with the given sigma and autocorrelation exp(-|dt| / time_constant)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaussMarkov {
+    pub sigma: f64,         // m
+    pub time_constant: f64, // s
+    pub rng: Rng,
+    states: BTreeMap<(SatId, Signal), (DateTime<Utc>, f64)>,
+}
+
+impl GaussMarkov {
+    pub fn new(sigma: f64, time_constant: f64, seed: u64) -> Self {
+        Self {
+            sigma,
+            time_constant,
+            rng: Rng::new(seed),
+            states: BTreeMap::new(),
+        }
+    }
+}
+
+impl ErrorModel for GaussMarkov {
+    fn pseudorange_error(
+        &mut self,
+        sat_id: SatId,
+        signal: Signal,
+        _: f64,
+        epoch: DateTime<Utc>,
+    ) -> f64 {
+        let draw = self.rng.gaussian();
+        let value = match self.states.get(&(sat_id, signal)) {
+            Some(&(last, value)) if epoch >= last => {
+                let dt = (epoch - last).num_microseconds().unwrap_or(i64::MAX) as f64 * 1e-6;
+                let phi = (-dt / self.time_constant).exp();
+                phi * value + (1.0 - phi * phi).sqrt() * self.sigma * draw
+            }
+            // A new satellite or a step back in time starts from the stationary distribution
+            _ => self.sigma * draw,
+        };
+        self.states.insert((sat_id, signal), (epoch, value));
+        value
+    }
+}
+
+/// Sum of several error models, e.g. white noise plus multipath
+#[derive(Default)]
+pub struct Composite {
+    pub components: Vec<Box<dyn ErrorModel>>,
+}
+
+impl Composite {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, model: impl ErrorModel + 'static) -> Self {
+        self.components.push(Box::new(model));
+        self
+    }
+}
+
+impl ErrorModel for Composite {
+    fn pseudorange_error(
+        &mut self,
+        sat_id: SatId,
+        signal: Signal,
+        elevation: f64,
+        epoch: DateTime<Utc>,
+    ) -> f64 {
+        self.components
+            .iter_mut()
+            .map(|model| model.pseudorange_error(sat_id, signal, elevation, epoch))
+            .sum()
+    }
+
+    fn phase_error(
+        &mut self,
+        sat_id: SatId,
+        signal: Signal,
+        elevation: f64,
+        epoch: DateTime<Utc>,
+    ) -> f64 {
+        self.components
+            .iter_mut()
+            .map(|model| model.phase_error(sat_id, signal, elevation, epoch))
+            .sum()
+    }
+
+    fn doppler_error(
+        &mut self,
+        sat_id: SatId,
+        signal: Signal,
+        elevation: f64,
+        epoch: DateTime<Utc>,
+    ) -> f64 {
+        self.components
+            .iter_mut()
+            .map(|model| model.doppler_error(sat_id, signal, elevation, epoch))
+            .sum()
+    }
+}
+
+/// Error-free observations
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NoError;
+
+impl ErrorModel for NoError {
+    fn pseudorange_error(&mut self, _: SatId, _: Signal, _: f64, _: DateTime<Utc>) -> f64 {
+        0.0
+    }
+}
+
+/// What the simulated receiver tracks and which deterministic effects are included
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationConfig {
+    pub signals: Vec<Signal>, // GLONASS FDMA entries take each satellite's own channel
+    pub elevation_mask: f64,  // Degrees
+    pub zenith_ionosphere: f64, // L1 zenith delay, m, thin-shell mapped; 0 to leave out
+    pub zenith_troposphere: f64, // Zenith delay, m, mapped by 1/sin(el); 0 to leave out
+    pub receiver_clock_bias: f64, // At the first epoch, s
+    pub receiver_clock_drift: f64, // s/s
+    pub ambiguity_seed: u64,  // Draws the integer carrier ambiguities
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            signals: vec![Signal::GpsL1],
+            elevation_mask: 5.0,
+            zenith_ionosphere: 0.0,
+            zenith_troposphere: 0.0,
+            receiver_clock_bias: 0.0,
+            receiver_clock_drift: 0.0,
+            ambiguity_seed: 0,
+        }
+    }
+}
+
+const IONO_SHELL_HEIGHT: f64 = 350e3; // Thin-shell height for the ionospheric mapping, m
+
+impl Constellation {
+    /// Observations a receiver following `truth` (true GPS epochs and ECEF positions; one
+    /// point repeated for a static receiver) would make. Epochs are tagged with the
+    /// receiver clock; code includes the satellite clock and group delay, phase a fixed
+    /// integer ambiguity per satellite and signal, and Doppler the motion of both ends.
+    pub fn simulate_observations(
+        &self,
+        truth: &[(DateTime<Utc>, ECEF)],
+        config: &SimulationConfig,
+        errors: &mut impl ErrorModel,
+    ) -> Vec<ObservationEpoch> {
+        let propagation = PropagationConfig::new()
+            .with_clock(true)
+            .with_velocity(true);
+        let propagators: BTreeMap<SatId, BroadcastPropagator> = self
+            .iter()
+            .filter_map(|sat| {
+                let propagator =
+                    BroadcastPropagator::new(self.records(sat.id), propagation.clone());
+                Some((sat.id, propagator.ok()?))
+            })
+            .collect();
+        let mut ambiguity_rng = Rng::new(config.ambiguity_seed);
+        let mut ambiguities: BTreeMap<(SatId, Signal), f64> = BTreeMap::new();
+        let start = truth.first().map(|(epoch, _)| *epoch);
+
+        let mut epochs = Vec::with_capacity(truth.len());
+        for (idx, &(epoch, position)) in truth.iter().enumerate() {
+            let time = gnss::gps_seconds(epoch);
+            let velocity = path_velocity(truth, idx);
+            let elapsed = (epoch - start.unwrap_or(epoch))
+                .num_microseconds()
+                .unwrap_or(0) as f64
+                * 1e-6;
+            let clock = config.receiver_clock_bias + config.receiver_clock_drift * elapsed;
+            let mut satellites = Vec::new();
+            for (&sat_id, propagator) in &propagators {
+                let record = propagator.record_at(GpsTime::from_seconds(time));
+                let Ok(model) =
+                    pseudorange::model_pseudorange(&position, propagator, time, record.tgd)
+                else {
+                    continue;
+                };
+                let elevation = model.aer.elevation;
+                if elevation < config.elevation_mask {
+                    continue;
+                }
+                let range_rate = gnss::range_rate(
+                    &position,
+                    &velocity,
+                    &model.satellite_position,
+                    &model.satellite_state.velocity.unwrap_or_default(),
+                )
+                .unwrap_or(0.0);
+                let ionosphere = config.zenith_ionosphere * ionosphere_mapping(elevation);
+                let troposphere =
+                    config.zenith_troposphere / elevation.to_radians().sin().max(0.05);
+                let channel = self.get(sat_id).and_then(|sat| sat.frequency_channel);
+
+                let mut signals = Vec::new();
+                for &signal in &config.signals {
+                    let Some(signal) = satellite_signal(signal, sat_id, channel) else {
+                        continue;
+                    };
+                    let scale = signal.iono_factor(Signal::GpsL1);
+                    let wavelength = signal.wavelength();
+                    // Clock-corrected range shared by code and phase
+                    let common = model.geometric_range
+                        + model.sagnac
+                        + model.satellite_clock
+                        + troposphere
+                        + gnss::C_LIGHT * clock;
+                    let code = common
+                        + model.group_delay * scale
+                        + ionosphere * scale
+                        + errors.pseudorange_error(sat_id, signal, elevation, epoch);
+                    let ambiguity = *ambiguities
+                        .entry((sat_id, signal))
+                        .or_insert_with(|| (ambiguity_rng.uniform() * 2e6 - 1e6).round());
+                    let phase = (common - ionosphere * scale
+                        + errors.phase_error(sat_id, signal, elevation, epoch))
+                        / wavelength
+                        + ambiguity;
+                    let rate = range_rate + gnss::C_LIGHT * config.receiver_clock_drift
+                        - gnss::C_LIGHT * record.sv_clock_drift;
+                    let doppler =
+                        -rate / wavelength + errors.doppler_error(sat_id, signal, elevation, epoch);
+                    let mut obs = SignalObservation::new(signal, Some(code), Some(phase));
+                    obs.doppler = Some(doppler);
+                    signals.push(obs);
+                }
+                if !signals.is_empty() {
+                    satellites.push(SatelliteObservations { sat_id, signals });
+                }
+            }
+            let nanos = (clock * 1e9).round() as i64;
+            epochs.push(ObservationEpoch {
+                epoch: epoch + Duration::nanoseconds(nanos),
+                satellites,
+            });
+        }
+        epochs
+    }
+}
+
+/// The signal as transmitted by this satellite, None if it belongs to another system
+fn satellite_signal(signal: Signal, sat_id: SatId, channel: Option<i8>) -> Option<Signal> {
+    if signal.constellation() != sat_id.constellation {
+        return None;
+    }
+    match signal {
+        Signal::GlonassG1(_) => channel.map(Signal::GlonassG1),
+        Signal::GlonassG2(_) => channel.map(Signal::GlonassG2),
+        _ => Some(signal),
+    }
+}
+
+/// Slant factor of a thin ionospheric shell at an elevation in degrees
+fn ionosphere_mapping(elevation: f64) -> f64 {
+    let ratio = gnss::WGS84_A / (gnss::WGS84_A + IONO_SHELL_HEIGHT);
+    let cos_zenith = ratio * elevation.to_radians().cos();
+    1.0 / (1.0 - cos_zenith * cos_zenith).sqrt()
+}
+
+/// Velocity of a truth path by central differences, zero for a single point
+fn path_velocity(truth: &[(DateTime<Utc>, ECEF)], idx: usize) -> ECEF {
+    let before = truth[idx.saturating_sub(1)];
+    let after = truth[(idx + 1).min(truth.len() - 1)];
+    let dt = (after.0 - before.0).num_microseconds().unwrap_or(0) as f64 * 1e-6;
+    match dt > 0.0 {
+        true => (after.1 - before.1) * (1.0 / dt),
+        false => ECEF::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "ndarray")]
+    use crate::positioning::SppOptions;
+    #[cfg(feature = "ndarray")]
+    use crate::test_support::{constellation, station};
+    use chrono::TimeZone;
+
+    /// Twenty epochs half a minute apart of the static station with a millisecond clock
+    #[cfg(feature = "ndarray")]
+    fn observe(errors: &mut impl ErrorModel) -> Vec<ObservationEpoch> {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap();
+        let truth: Vec<_> = (0..20)
+            .map(|k| (start + Duration::seconds(30 * k), station()))
+            .collect();
+        let config = SimulationConfig {
+            elevation_mask: 10.0,
+            receiver_clock_bias: 1e-3,
+            receiver_clock_drift: 1e-8,
+            ..SimulationConfig::default()
+        };
+        constellation().simulate_observations(&truth, &config, errors)
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn spp_recovers_the_true_position_from_exact_observations() {
+        let constellation = constellation();
+        for (k, epoch) in observe(&mut NoError).iter().enumerate() {
+            let observations = epoch.pseudoranges(Signal::GpsL1);
+            let solution = constellation
+                .solve_spp(epoch.epoch, &observations, &SppOptions::default())
+                .unwrap();
+            assert!(solution.position.approx_eq(&station(), 0.01));
+            let clock = 1e-3 + 1e-8 * 30.0 * k as f64;
+            assert!((solution.clock_bias - clock).abs() < 1e-10);
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn spp_error_stays_within_the_injected_noise() {
+        let constellation = constellation();
+        let sigma = 1.0;
+        let epochs = observe(&mut GaussianNoise::new(sigma, 0.0, 0.0, 7));
+        let mut squared = 0.0;
+        let mut predicted = 0.0;
+        for epoch in &epochs {
+            let observations = epoch.pseudoranges(Signal::GpsL1);
+            let solution = constellation
+                .solve_spp(epoch.epoch, &observations, &SppOptions::default())
+                .unwrap();
+            let error = (solution.position - station()).norm();
+            assert!(error < 4.0 * sigma * solution.dop.pdop, "{}", error);
+            squared += error * error;
+            predicted += (sigma * solution.dop.pdop).powi(2);
+        }
+        // The 3D RMS error matches what the noise and the geometry predict
+        let ratio = (squared / predicted).sqrt();
+        assert!((0.5..1.5).contains(&ratio), "{}", ratio);
+    }
+
+    /// Sample autocorrelation of a series at a lag
+    fn autocorrelation(series: &[f64], lag: usize) -> f64 {
+        let n = series.len() - lag;
+        let mean = series.iter().sum::<f64>() / series.len() as f64;
+        let variance = series.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / series.len() as f64;
+        let covariance = (0..n)
+            .map(|k| (series[k] - mean) * (series[k + lag] - mean))
+            .sum::<f64>()
+            / n as f64;
+        covariance / variance
+    }
+
+    #[test]
+    fn gauss_markov_autocorrelation_follows_its_time_constant() {
+        let (sigma, time_constant) = (2.0, 30.0);
+        let mut model = GaussMarkov::new(sigma, time_constant, 3);
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let series: Vec<f64> = (0..200_000)
+            .map(|k| {
+                let epoch = start + Duration::seconds(k);
+                model.pseudorange_error(SatId::gps(1), Signal::GpsL1, 45.0, epoch)
+            })
+            .collect();
+
+        let variance = series.iter().map(|v| v * v).sum::<f64>() / series.len() as f64;
+        assert!(
+            (variance.sqrt() / sigma - 1.0).abs() < 0.03,
+            "{}",
+            variance.sqrt()
+        );
+        for lag in [1, 10, 30, 60] {
+            let expected = (-(lag as f64) / time_constant).exp();
+            let measured = autocorrelation(&series, lag);
+            assert!(
+                (measured - expected).abs() < 0.03,
+                "lag {}: {}",
+                lag,
+                measured
+            );
+        }
+        // The correlation first drops below 1/e one time constant out
+        let crossing = (1..200)
+            .find(|&lag| autocorrelation(&series, lag) < (-1.0f64).exp())
+            .unwrap();
+        assert!((28..=33).contains(&crossing), "{}", crossing);
+    }
+
+    #[test]
+    fn gauss_markov_keeps_a_state_per_satellite() {
+        let mut model = GaussMarkov::new(1.0, 1e9, 5);
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let g01 = model.pseudorange_error(SatId::gps(1), Signal::GpsL1, 45.0, start);
+        let g02 = model.pseudorange_error(SatId::gps(2), Signal::GpsL1, 45.0, start);
+        assert_ne!(g01, g02);
+        // With a very long time constant each satellite's error barely moves
+        let later = start + Duration::seconds(1);
+        let next = model.pseudorange_error(SatId::gps(1), Signal::GpsL1, 45.0, later);
+        assert!((next - g01).abs() < 1e-3);
+    }
+
+    #[test]
+    fn composite_sums_its_components_reproducibly() {
+        let epoch = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let mut composite = Composite::new()
+            .with(GaussianNoise::new(1.0, 0.01, 0.1, 1))
+            .with(GaussianNoise::new(3.0, 0.0, 0.0, 2));
+        let mut first = GaussianNoise::new(1.0, 0.01, 0.1, 1);
+        let mut second = GaussianNoise::new(3.0, 0.0, 0.0, 2);
+        let sat_id = SatId::gps(7);
+        for _ in 0..10 {
+            let sum = composite.pseudorange_error(sat_id, Signal::GpsL1, 30.0, epoch);
+            let expected = first.pseudorange_error(sat_id, Signal::GpsL1, 30.0, epoch)
+                + second.pseudorange_error(sat_id, Signal::GpsL1, 30.0, epoch);
+            assert_eq!(sum, expected);
+        }
+        assert_eq!(NoError.phase_error(sat_id, Signal::GpsL1, 30.0, epoch), 0.0);
+    }
+
+    #[test]
+    fn gaussian_draws_are_standard_normal() {
+        let mut rng = Rng::new(11);
+        let draws: Vec<f64> = (0..100_000).map(|_| rng.gaussian()).collect();
+        let mean = draws.iter().sum::<f64>() / draws.len() as f64;
+        let variance = draws.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / draws.len() as f64;
+        assert!(mean.abs() < 0.01 && (variance - 1.0).abs() < 0.02);
+        assert_eq!(Rng::new(11).gaussian(), draws[0]);
+    }
+}
@@ -0,0 +1,268 @@
+use crate::gnss::{self, SatId};
+use crate::positioning::PseudorangeObservation;
+use crate::signal::Signal;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// Code observation with the carrier phase tracked alongside it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CarrierObservation {
+    pub code: PseudorangeObservation,
+    pub signal: Signal,
+    pub phase: Option<f64>, // Carrier phase, cycles
+    pub loss_of_lock: bool, // LLI bit or `SlipFlags::slipped`: restart the smoothing
+}
+
+/// Tuning of `HatchFilter`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HatchConfig {
+    pub window: usize,       // Maximum number of epochs averaged
+    pub max_gap: f64,        // Longest outage the smoothing survives, s
+    pub slip_threshold: f64, // Code minus smoothed prediction beyond which the carrier is distrusted, m
+}
+
+impl Default for HatchConfig {
+    fn default() -> Self {
+        Self {
+            window: 100,
+            max_gap: 30.0,
+            slip_threshold: 30.0,
+        }
+    }
+}
+
+/// Running smoothing state of one satellite
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Channel {
+    smoothed: f64,   // m
+    last_phase: f64, // m
+    last_time: f64,  // GPS seconds
+    count: usize,    // Epochs in the average, up to the window
+}
+
+/// Carrier-smoothed pseudoranges by the Hatch filter. Each satellite restarts from its raw
+/// code after a loss of lock, a detected slip, a data gap or a missing phase.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HatchFilter {
+    config: HatchConfig,
+    channels: BTreeMap<SatId, Channel>,
+    resets: usize,
+}
+
+impl HatchFilter {
+    pub fn new(config: HatchConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Smooth one epoch, returning observations that can be passed straight to the solvers
+    pub fn update(
+        &mut self,
+        epoch: DateTime<Utc>,
+        observations: &[CarrierObservation],
+    ) -> Vec<PseudorangeObservation> {
+        let time = gnss::gps_seconds(epoch);
+        let mut smoothed = Vec::with_capacity(observations.len());
+        for obs in observations {
+            let sat_id = obs.code.sat_id;
+            let code = obs.code.pseudorange;
+            let Some(phase) = obs.phase.map(|cycles| cycles * obs.signal.wavelength()) else {
+                self.channels.remove(&sat_id);
+                smoothed.push(obs.code);
+                continue;
+            };
+
+            let channel = self.channels.get(&sat_id).copied().and_then(|channel| {
+                let gap = time - channel.last_time;
+                let predicted = channel.smoothed + phase - channel.last_phase;
+                let consistent = (code - predicted).abs() <= self.config.slip_threshold;
+                (!obs.loss_of_lock && gap > 0.0 && gap <= self.config.max_gap && consistent)
+                    .then_some((channel, predicted))
+            });
+            let next = match channel {
+                Some((channel, predicted)) => {
+                    let count = (channel.count + 1).min(self.config.window.max(1));
+                    let n = count as f64;
+                    Channel {
+                        smoothed: code / n + predicted * (n - 1.0) / n,
+                        last_phase: phase,
+                        last_time: time,
+                        count,
+                    }
+                }
+                None => {
+                    if self.channels.contains_key(&sat_id) {
+                        self.resets += 1;
+                    }
+                    Channel {
+                        smoothed: code,
+                        last_phase: phase,
+                        last_time: time,
+                        count: 1,
+                    }
+                }
+            };
+            self.channels.insert(sat_id, next);
+            smoothed.push(PseudorangeObservation {
+                pseudorange: next.smoothed,
+                ..obs.code
+            });
+        }
+        smoothed
+    }
+
+    /// Epochs in a satellite's current smoothing average, 0 if it is not being tracked
+    pub fn count(&self, sat_id: SatId) -> usize {
+        self.channels
+            .get(&sat_id)
+            .map_or(0, |channel| channel.count)
+    }
+
+    /// Restarts caused by slips, outages or loss of lock so far
+    pub fn resets(&self) -> usize {
+        self.resets
+    }
+
+    /// Forget every satellite's smoothing
+    pub fn reset(&mut self) {
+        self.channels.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Rng;
+    use chrono::{Duration, TimeZone};
+
+    const SIGMA: f64 = 1.0; // Code noise, m
+
+    fn range(t: f64) -> f64 {
+        21_500_000.0 + 480.0 * t - 0.05 * t * t
+    }
+
+    /// Noisy code and clean carrier of a satellite `t` seconds into the run; the carrier
+    /// carries an arbitrary ambiguity and `slip` extra cycles
+    fn observation(prn: u8, t: f64, rng: &mut Rng, slip: f64) -> CarrierObservation {
+        let wavelength = Signal::GpsL1.wavelength();
+        CarrierObservation {
+            code: PseudorangeObservation {
+                sat_id: SatId::from(prn),
+                pseudorange: range(t) + SIGMA * rng.gaussian(),
+                snr: None,
+                doppler: None,
+            },
+            signal: Signal::GpsL1,
+            phase: Some(range(t) / wavelength + 1234567.0 + prn as f64 + slip),
+            loss_of_lock: false,
+        }
+    }
+
+    fn epoch(t: f64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap() + Duration::seconds(t as i64)
+    }
+
+    #[test]
+    fn noise_falls_as_one_over_root_n() {
+        let mut rng = Rng::new(348);
+        let mut filter = HatchFilter::new(HatchConfig::default());
+        let checkpoints = [1, 4, 16, 64];
+        let mut sums = [0.0; 4];
+        let mut samples = 0;
+        for _ in 0..20 {
+            filter.reset();
+            for k in 1..=64 {
+                let t = k as f64;
+                let observations: Vec<CarrierObservation> = (1..=32)
+                    .map(|prn| observation(prn, t, &mut rng, 0.0))
+                    .collect();
+                let smoothed = filter.update(epoch(t), &observations);
+                if let Some(i) = checkpoints.iter().position(|&n| n == k) {
+                    for obs in &smoothed {
+                        sums[i] += (obs.pseudorange - range(t)).powi(2);
+                    }
+                }
+            }
+            samples += 32;
+        }
+        assert_eq!(filter.count(SatId::from(5)), 64);
+        for (n, sum) in checkpoints.iter().zip(sums) {
+            let rms = (sum / samples as f64).sqrt();
+            let expected = SIGMA / (*n as f64).sqrt();
+            assert!(
+                (rms / expected - 1.0).abs() < 0.1,
+                "{}: {} vs {}",
+                n,
+                rms,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn window_bounds_the_average() {
+        // Past the window the filter averages exponentially, settling at σ/√(2N-1)
+        let mut rng = Rng::new(1);
+        let config = HatchConfig {
+            window: 10,
+            ..HatchConfig::default()
+        };
+        let mut filter = HatchFilter::new(config);
+        let mut sum = 0.0;
+        let mut samples = 0;
+        for k in 1..=400 {
+            let t = k as f64;
+            let observations: Vec<CarrierObservation> = (1..=32)
+                .map(|prn| observation(prn, t, &mut rng, 0.0))
+                .collect();
+            let smoothed = filter.update(epoch(t), &observations);
+            if k > 50 {
+                sum += smoothed
+                    .iter()
+                    .map(|obs| (obs.pseudorange - range(t)).powi(2))
+                    .sum::<f64>();
+                samples += smoothed.len();
+            }
+        }
+        assert_eq!(filter.count(SatId::from(1)), 10);
+        let rms = (sum / samples as f64).sqrt();
+        assert!((rms / (SIGMA / 19f64.sqrt()) - 1.0).abs() < 0.1, "{}", rms);
+    }
+
+    #[test]
+    fn slips_and_gaps_restart_from_the_raw_code() {
+        let mut rng = Rng::new(7);
+        let mut filter = HatchFilter::new(HatchConfig::default());
+        for k in 1..=20 {
+            filter.update(epoch(k as f64), &[observation(3, k as f64, &mut rng, 0.0)]);
+        }
+        assert_eq!(filter.count(SatId::from(3)), 20);
+
+        // A slip flagged by the receiver restarts the average
+        let mut flagged = observation(3, 21.0, &mut rng, 3.0);
+        flagged.loss_of_lock = true;
+        let out = filter.update(epoch(21.0), &[flagged]);
+        assert_eq!(out[0].pseudorange, flagged.code.pseudorange);
+        assert_eq!((filter.count(SatId::from(3)), filter.resets()), (1, 1));
+
+        // An unflagged 200-cycle slip (38 m) is caught by the code check instead of biasing
+        // the output
+        for k in 22..=30 {
+            filter.update(epoch(k as f64), &[observation(3, k as f64, &mut rng, 3.0)]);
+        }
+        let slipped = observation(3, 31.0, &mut rng, 203.0);
+        let out = filter.update(epoch(31.0), &[slipped]);
+        assert_eq!(out[0].pseudorange, slipped.code.pseudorange);
+        assert_eq!(filter.resets(), 2);
+        let next = filter.update(epoch(32.0), &[observation(3, 32.0, &mut rng, 203.0)]);
+        assert!((next[0].pseudorange - range(32.0)).abs() < 4.0 * SIGMA);
+
+        // So does an outage longer than `max_gap`
+        let late = observation(3, 100.0, &mut rng, 203.0);
+        let out = filter.update(epoch(100.0), &[late]);
+        assert_eq!(out[0].pseudorange, late.code.pseudorange);
+        assert_eq!(filter.resets(), 3);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::constellation::Constellation;
+use crate::gnss::{SatId, StateRef};
+use crate::satellite::Satellite;
+use chrono::{DateTime, Duration, TimeZone, Timelike, Utc};
+use std::collections::BTreeMap;
+#[cfg(feature = "std-fs")]
+use std::fs::File;
+#[cfg(feature = "std-fs")]
+use std::io::BufWriter;
+use std::io::{self, Write};
+
+const IDS_PER_LINE: usize = 17;
+const MIN_ID_LINES: usize = 5;
+const BAD_CLOCK: f64 = 999_999.999_999;
+const GPS_EPOCH_MJD: i64 = 44_244;
+const SECONDS_PER_WEEK: f64 = 604_800.0;
+
+/// Header fields and content of an SP3 export
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sp3Options {
+    pub velocities: bool,          // Write a V record after every P record
+    pub coordinate_system: String, // Up to 5 characters
+    pub orbit_type: String,        // Up to 3 characters, "BCT" for broadcast
+    pub agency: String,            // Up to 4 characters
+    pub comments: Vec<String>,     // Written as /* lines, each cut to 57 characters
+}
+
+impl Default for Sp3Options {
+    fn default() -> Self {
+        Self {
+            velocities: false,
+            coordinate_system: "WGS84".to_string(),
+            orbit_type: "BCT".to_string(),
+            agency: "PNT".to_string(),
+            comments: vec!["Propagated from broadcast ephemerides".to_string()],
+        }
+    }
+}
+
+impl Constellation {
+    /// Propagated states as an SP3-c file in GPS time: positions in km and clocks in µs
+    /// (999999.999999 where the clock was not propagated), and with `velocities` also
+    /// dm/s V records. Every satellite's states must lie on one uniformly spaced grid;
+    /// a satellite missing an epoch gets a zero position and a bad clock there.
+    pub fn write_sp3(&self, mut writer: impl Write, options: &Sp3Options) -> io::Result<()> {
+        let satellites: Vec<&Satellite> = self
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .collect();
+        let mut epochs: BTreeMap<i64, Vec<(SatId, StateRef<'_>)>> = BTreeMap::new();
+        for satellite in &satellites {
+            for state in satellite.states.iter() {
+                epochs
+                    .entry(milliseconds(state.time()))
+                    .or_default()
+                    .push((satellite.id, state));
+            }
+        }
+        let times: Vec<i64> = epochs.keys().copied().collect();
+        let interval = match times[..] {
+            [] => return Err(invalid("no propagated states")),
+            [_] => 0,
+            [first, second, ..] => second - first,
+        };
+        if times.windows(2).any(|pair| pair[1] - pair[0] != interval) {
+            return Err(invalid("states are not on a uniform time grid"));
+        }
+
+        let start = times[0] as f64 / 1000.0;
+        let system = match satellites[0].constellation() {
+            first if satellites.iter().all(|s| s.constellation() == first) => first.to_char(),
+            _ => 'M',
+        };
+        writeln!(
+            writer,
+            "#c{}{} {:7} ORBIT {:<5} {:<3} {:<4}",
+            if options.velocities { 'V' } else { 'P' },
+            calendar(start),
+            times.len(),
+            truncate(&options.coordinate_system, 5),
+            truncate(&options.orbit_type, 3),
+            truncate(&options.agency, 4)
+        )?;
+        let mjd = start / 86_400.0;
+        writeln!(
+            writer,
+            "## {:4} {:15.8} {:14.8} {:5} {:15.13}",
+            (start / SECONDS_PER_WEEK).floor() as i64,
+            start.rem_euclid(SECONDS_PER_WEEK),
+            interval as f64 / 1000.0,
+            GPS_EPOCH_MJD + mjd.floor() as i64,
+            mjd.fract()
+        )?;
+
+        let mut accuracy_lines = Vec::new();
+        let lines = satellites.len().div_ceil(IDS_PER_LINE).max(MIN_ID_LINES);
+        for line in 0..lines {
+            let slots = satellites
+                .iter()
+                .skip(line * IDS_PER_LINE)
+                .take(IDS_PER_LINE);
+            let ids: String = slots.clone().map(|s| s.id.to_string()).collect();
+            let accuracies: String = slots.map(|s| format!("{:3}", accuracy_code(s))).collect();
+            let padding = IDS_PER_LINE - ids.len() / 3;
+            match line {
+                0 => write!(writer, "+   {:2}   ", satellites.len())?,
+                _ => write!(writer, "+        ")?,
+            }
+            writeln!(writer, "{}{}", ids, "  0".repeat(padding))?;
+            accuracy_lines.push(format!("++       {}{}", accuracies, "  0".repeat(padding)));
+        }
+        for line in accuracy_lines {
+            writeln!(writer, "{}", line)?;
+        }
+        writeln!(
+            writer,
+            "%c {}  cc GPS ccc cccc cccc cccc cccc ccccc ccccc ccccc ccccc",
+            system
+        )?;
+        writeln!(
+            writer,
+            "%c cc cc ccc ccc cccc cccc cccc cccc ccccc ccccc ccccc ccccc"
+        )?;
+        for _ in 0..2 {
+            writeln!(
+                writer,
+                "%f  1.2500000  1.025000000  0.00000000000  0.000000000000000"
+            )?;
+        }
+        for _ in 0..2 {
+            writeln!(
+                writer,
+                "%i    0    0    0    0      0      0      0      0         0"
+            )?;
+        }
+        for i in 0..options.comments.len().max(4) {
+            let comment = options.comments.get(i).map(String::as_str).unwrap_or("");
+            writeln!(writer, "/* {}", truncate(comment, 57))?;
+        }
+
+        for (time, states) in &epochs {
+            writeln!(writer, "*  {}", calendar(*time as f64 / 1000.0))?;
+            for satellite in &satellites {
+                let state = states
+                    .iter()
+                    .find(|(sat_id, _)| *sat_id == satellite.id)
+                    .map(|(_, state)| *state);
+                write_records(&mut writer, satellite.id, state, options.velocities)?;
+            }
+        }
+        writeln!(writer, "EOF")?;
+        writer.flush()
+    }
+
+    /// `write_sp3` to a file
+    #[cfg(feature = "std-fs")]
+    pub fn export_sp3(&self, path: &str, options: &Sp3Options) -> io::Result<()> {
+        self.write_sp3(BufWriter::new(File::create(path)?), options)
+    }
+}
+
+/// P (and V) record of one satellite at an epoch; zeros and bad clocks where it has no state
+fn write_records(
+    writer: &mut impl Write,
+    sat_id: SatId,
+    state: Option<StateRef<'_>>,
+    velocities: bool,
+) -> io::Result<()> {
+    let position = state.map(|state| state.position());
+    let clock = state.and_then(|state| state.clock_bias());
+    let [x, y, z] = position.map_or([0.0; 3], |p| [p.x / 1e3, p.y / 1e3, p.z / 1e3]);
+    writeln!(
+        writer,
+        "P{}{:14.6}{:14.6}{:14.6}{:14.6}",
+        sat_id,
+        x,
+        y,
+        z,
+        clock.map_or(BAD_CLOCK, |clock| clock * 1e6)
+    )?;
+    if !velocities {
+        return Ok(());
+    }
+    let velocity = state.and_then(|state| state.velocity());
+    let [vx, vy, vz] = velocity.map_or([0.0; 3], |v| [v.x * 10.0, v.y * 10.0, v.z * 10.0]);
+    writeln!(
+        writer,
+        "V{}{:14.6}{:14.6}{:14.6}{:14.6}",
+        sat_id, vx, vy, vz, BAD_CLOCK
+    )
+}
+
+/// "yyyy mm dd hh mm ss.ssssssss" of GPS seconds, in GPS time
+fn calendar(gps_seconds: f64) -> String {
+    let gps_epoch: DateTime<Utc> = Utc.with_ymd_and_hms(1980, 1, 6, 0, 0, 0).unwrap();
+    let time = gps_epoch + Duration::microseconds((gps_seconds * 1e6).round() as i64);
+    let seconds = time.second() as f64 + time.nanosecond() as f64 / 1e9;
+    format!("{} {:11.8}", time.format("%Y %_m %_d %_H %_M"), seconds)
+}
+
+/// Accuracy exponent of the header: the first known accuracy as 2^n mm, 0 if unknown
+fn accuracy_code(satellite: &Satellite) -> u32 {
+    satellite
+        .states
+        .accuracies()
+        .iter()
+        .find_map(|&accuracy| accuracy)
+        .filter(|accuracy| *accuracy > 0.0)
+        .map_or(0, |accuracy| {
+            (accuracy * 1e3).log2().round().clamp(1.0, 99.0) as u32
+        })
+}
+
+fn milliseconds(gps_seconds: f64) -> i64 {
+    (gps_seconds * 1000.0).round() as i64
+}
+
+fn truncate(text: &str, length: usize) -> &str {
+    text.char_indices()
+        .nth(length)
+        .map_or(text, |(end, _)| &text[..end])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support;
+    use std::time::Duration as StdDuration;
+
+    fn constellation(config: &PropagationConfig) -> Constellation {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        test_support::propagated(start, StdDuration::from_secs(3600), config)
+    }
+
+    fn sp3(constellation: &Constellation, options: &Sp3Options) -> String {
+        let mut out = Vec::new();
+        constellation.write_sp3(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Satellite id and the four fixed-width values of a P or V record
+    fn record(line: &str) -> (&str, [f64; 4]) {
+        let field = |i: usize| line[4 + 14 * i..18 + 14 * i].trim().parse().unwrap();
+        (&line[1..4], [field(0), field(1), field(2), field(3)])
+    }
+
+    #[test]
+    fn header_describes_the_epochs_and_satellites() {
+        let constellation =
+            constellation(&PropagationConfig::new().step(StdDuration::from_secs(300)));
+        let text = sp3(&constellation, &Sp3Options::default());
+        let lines: Vec<&str> = text.lines().collect();
+        let propagated: Vec<&Satellite> = constellation
+            .iter()
+            .filter(|satellite| !satellite.states.is_empty())
+            .collect();
+
+        assert_eq!(
+            lines[0],
+            "#cP2023  6 12  2  0 18.00000000      12 ORBIT WGS84 BCT PNT "
+        );
+        // 02:00 UTC is 02:00:18 GPS on the Monday of week 2266, MJD 60107.0835...
+        assert_eq!(
+            lines[1],
+            "## 2266  93618.00000000   300.00000000 60107 0.0835416666669"
+        );
+        assert_eq!(
+            lines[2][4..6].trim().parse::<usize>().unwrap(),
+            propagated.len()
+        );
+        let ids: String = lines
+            .iter()
+            .filter(|line| line.starts_with("+ "))
+            .map(|line| &line[9..])
+            .collect();
+        let expected: String = propagated.iter().map(|s| s.id.to_string()).collect();
+        assert!(ids.starts_with(&expected));
+        assert_eq!(
+            lines.iter().filter(|line| line.starts_with("+ ")).count(),
+            lines.iter().filter(|line| line.starts_with("++")).count()
+        );
+        assert!(lines.iter().all(|line| line.len() <= 80));
+        assert_eq!(
+            lines.iter().filter(|line| line.starts_with('*')).count(),
+            12
+        );
+        assert_eq!(
+            lines.iter().filter(|line| line.starts_with('P')).count(),
+            12 * propagated.len()
+        );
+        assert_eq!(lines.last(), Some(&"EOF"));
+    }
+
+    #[test]
+    fn records_read_back_to_the_millimetre() {
+        let config = PropagationConfig::new()
+            .step(StdDuration::from_secs(600))
+            .with_velocity(true)
+            .with_clock(true);
+        let constellation = constellation(&config);
+        let options = Sp3Options {
+            velocities: true,
+            ..Sp3Options::default()
+        };
+        let text = sp3(&constellation, &options);
+        assert!(text.starts_with("#cV"));
+
+        let mut epoch = 0;
+        let mut lines = text
+            .lines()
+            .skip_while(|line| !line.starts_with('*'))
+            .peekable();
+        while let Some(line) = lines.next() {
+            if line == "EOF" {
+                break;
+            }
+            assert!(line.starts_with("*  "), "{}", line);
+            while let Some(p) = lines.next_if(|line| line.starts_with('P')) {
+                let (id, [x, y, z, clock]) = record(p);
+                let satellite = constellation
+                    .iter()
+                    .find(|s| s.id.to_string() == id)
+                    .unwrap();
+                let state = satellite.states.get(epoch).unwrap();
+                let position = state.position();
+                assert!((x * 1e3 - position.x).abs() <= 5e-4);
+                assert!((y * 1e3 - position.y).abs() <= 5e-4);
+                assert!((z * 1e3 - position.z).abs() <= 5e-4);
+                assert!((clock - state.clock_bias().unwrap() * 1e6).abs() <= 5e-7);
+
+                let (v_id, [vx, vy, vz, v_clock]) = record(lines.next().unwrap());
+                assert_eq!(v_id, id);
+                let velocity = state.velocity().unwrap();
+                assert!((vx / 10.0 - velocity.x).abs() <= 5e-8);
+                assert!((vy / 10.0 - velocity.y).abs() <= 5e-8);
+                assert!((vz / 10.0 - velocity.z).abs() <= 5e-8);
+                assert_eq!(v_clock, BAD_CLOCK);
+            }
+            epoch += 1;
+        }
+        assert_eq!(epoch, 6);
+    }
+
+    #[test]
+    fn unpropagated_clocks_are_bad_values() {
+        let constellation =
+            constellation(&PropagationConfig::new().step(StdDuration::from_secs(1800)));
+        let text = sp3(&constellation, &Sp3Options::default());
+        let clocks: Vec<f64> = text
+            .lines()
+            .filter(|line| line.starts_with('P'))
+            .map(|line| record(line).1[3])
+            .collect();
+        assert!(!clocks.is_empty());
+        assert!(clocks.iter().all(|&clock| clock == BAD_CLOCK));
+    }
+
+    #[test]
+    fn empty_or_ragged_grids_are_rejected() {
+        let empty = test_support::constellation();
+        let error = empty
+            .write_sp3(Vec::new(), &Sp3Options::default())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let mut ragged = constellation(&PropagationConfig::new().step(StdDuration::from_secs(600)));
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 7, 0).unwrap();
+        let records = ragged.records(SatId::gps(17)).to_vec();
+        let satellite = ragged.get_mut(SatId::gps(17)).unwrap();
+        let config = PropagationConfig::new().step(StdDuration::from_secs(600));
+        satellite
+            .propagate(start, StdDuration::from_secs(600), &config, &records)
+            .unwrap();
+        let error = ragged
+            .write_sp3(Vec::new(), &Sp3Options::default())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn text_fields_are_truncated() {
+        assert_eq!(truncate("IGS14-long", 5), "IGS14");
+        assert_eq!(truncate("BCT", 3), "BCT");
+        assert_eq!(truncate("ab", 4), "ab");
+        assert_eq!(calendar(0.0), "1980  1  6  0  0  0.00000000");
+    }
+}
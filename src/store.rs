@@ -0,0 +1,175 @@
+use crate::gnss::{GpsTime, NavRecord, RinexNav, SatId, State, ECEF};
+use crate::propagator::{BroadcastPropagator, OrbitPropagator};
+use crate::satellite::{PropagationConfig, PropagationError};
+use log::debug;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// One nav file, deduplicated and indexed by satellite, queried without any locking.
+/// Immutable once built, so any number of threads can share it.
+pub struct EphemerisSnapshot {
+    nav: RinexNav,
+    propagators: BTreeMap<SatId, BroadcastPropagator>, // Satellites with a usable record
+}
+
+impl EphemerisSnapshot {
+    /// Satellites whose records `config` filters out entirely are left out
+    pub fn new(mut nav: RinexNav, config: &PropagationConfig) -> Self {
+        nav.deduplicate();
+        let propagators = nav
+            .satellites()
+            .filter_map(|sat_id| {
+                let records = nav.records_for_slice(sat_id);
+                let propagator = BroadcastPropagator::new(records, config.clone()).ok()?;
+                Some((sat_id, propagator))
+            })
+            .collect();
+        Self { nav, propagators }
+    }
+
+    pub fn nav(&self) -> &RinexNav {
+        &self.nav
+    }
+
+    pub fn satellites(&self) -> impl Iterator<Item = SatId> + '_ {
+        self.propagators.keys().copied()
+    }
+
+    /// Record with the toe nearest to the epoch, as propagation would use
+    pub fn best_record(&self, sat_id: SatId, epoch: GpsTime) -> Option<&NavRecord> {
+        let propagator = self.propagators.get(&sat_id)?;
+        Some(propagator.record_at(epoch))
+    }
+
+    pub fn state_at(&self, sat_id: SatId, epoch: GpsTime) -> Result<State, PropagationError> {
+        self.propagators
+            .get(&sat_id)
+            .ok_or(PropagationError::NoRecordsForSatellite(sat_id))?
+            .state_at(epoch)
+    }
+
+    pub fn position_at(&self, sat_id: SatId, epoch: GpsTime) -> Result<ECEF, PropagationError> {
+        Ok(self.state_at(sat_id, epoch)?.position)
+    }
+}
+
+/// Ephemerides shared between threads, with the nav file swapped out as a whole when the
+/// next broadcast file arrives.
+///
+/// Queries work on the snapshot current when they start: the lock is held only to clone
+/// its `Arc`, never while evaluating, and a swap never waits for queries in flight. A
+/// handler that makes several queries should take one `snapshot` so they all see the same
+/// file.
+pub struct EphemerisStore {
+    current: RwLock<Arc<EphemerisSnapshot>>,
+    config: PropagationConfig,
+}
+
+impl EphemerisStore {
+    pub fn new(nav: RinexNav, config: PropagationConfig) -> Self {
+        let snapshot = EphemerisSnapshot::new(nav, &config);
+        Self {
+            current: RwLock::new(Arc::new(snapshot)),
+            config,
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<EphemerisSnapshot> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Index a newly parsed file and make it current, returning the one it replaces.
+    /// Indexing happens before the lock is taken, so queries go on meanwhile.
+    pub fn replace(&self, nav: RinexNav) -> Arc<EphemerisSnapshot> {
+        let snapshot = Arc::new(EphemerisSnapshot::new(nav, &self.config));
+        debug!(
+            "ephemeris store now holds {} records of {} satellites",
+            snapshot.nav.records().len(),
+            snapshot.propagators.len()
+        );
+        std::mem::replace(&mut *self.current.write().unwrap(), snapshot)
+    }
+
+    pub fn best_record(&self, sat_id: SatId, epoch: GpsTime) -> Option<NavRecord> {
+        self.snapshot().best_record(sat_id, epoch).copied()
+    }
+
+    pub fn state_at(&self, sat_id: SatId, epoch: GpsTime) -> Result<State, PropagationError> {
+        self.snapshot().state_at(sat_id, epoch)
+    }
+
+    pub fn position_at(&self, sat_id: SatId, epoch: GpsTime) -> Result<ECEF, PropagationError> {
+        self.snapshot().position_at(sat_id, epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::NAV;
+    use chrono::{TimeZone, Utc};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    /// The fixture, and the same records on orbits a metre wider so every position differs
+    fn files() -> [Vec<NavRecord>; 2] {
+        let nav: RinexNav = NAV.parse().unwrap();
+        let mut widened = nav.records().to_vec();
+        for record in &mut widened {
+            record.sqrt_a = (record.semi_major_axis() + 1.0).sqrt();
+        }
+        [nav.into_records(), widened]
+    }
+
+    fn positions(snapshot: &EphemerisSnapshot, epoch: GpsTime) -> Vec<(SatId, ECEF)> {
+        snapshot
+            .satellites()
+            .map(|sat_id| (sat_id, snapshot.position_at(sat_id, epoch).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn queries_see_one_whole_file_while_it_is_swapped() {
+        let epoch = GpsTime::from_utc(Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap());
+        let config = PropagationConfig::new();
+        let files = files();
+        let expected = files.clone().map(|records| {
+            positions(
+                &EphemerisSnapshot::new(RinexNav::from_records(records), &config),
+                epoch,
+            )
+        });
+        assert_ne!(expected[0], expected[1]);
+
+        let store = EphemerisStore::new(RinexNav::from_records(files[0].clone()), config);
+        let swapping = AtomicBool::new(true);
+        thread::scope(|scope| {
+            let readers: Vec<_> = (0..12)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut queries = 0;
+                        while swapping.load(Ordering::Relaxed) || queries == 0 {
+                            let seen = positions(&store.snapshot(), epoch);
+                            assert!(expected.contains(&seen), "a snapshot mixed two files");
+                            for (index, &(sat_id, _)) in expected[0].iter().enumerate() {
+                                let position = store.position_at(sat_id, epoch).unwrap();
+                                assert!(expected.iter().any(|file| file[index].1 == position));
+                            }
+                            queries += 1;
+                        }
+                        queries
+                    })
+                })
+                .collect();
+
+            for swap in 0..200 {
+                store.replace(RinexNav::from_records(files[(swap + 1) % 2].clone()));
+            }
+            swapping.store(false, Ordering::Relaxed);
+            for reader in readers {
+                assert!(reader.join().unwrap() > 0);
+            }
+        });
+        assert_eq!(positions(&store.snapshot(), epoch), expected[0]);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::gnss::{self, Constellation, NavRecord, RinexNav, SatId};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// One line per record: satellite, toe in UTC, IODE, health, URA, orbit size, shape and
+/// inclination, then the clock polynomial. GLONASS and SBAS records, which carry no
+/// Keplerian elements, show their toc, clock offset and drift only.
+impl fmt::Display for NavRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keplerian = !matches!(
+            self.sat_id.constellation,
+            Constellation::Glonass | Constellation::Sbas
+        );
+        let (label, time) = match keplerian {
+            true => ("toe", self.toe_gps_seconds()),
+            false => ("toc", self.toc_gps_seconds()),
+        };
+        write!(
+            f,
+            "{} {} {}",
+            self.sat_id,
+            label,
+            gnss::gps_seconds_to_utc(time).format(TIME_FORMAT)
+        )?;
+        if keplerian {
+            write!(f, " IODE {:.0}", self.iode)?;
+        }
+        match self.health_flags().raw {
+            0 => write!(f, " healthy")?,
+            raw => write!(f, " unhealthy ({:#x})", raw)?,
+        }
+        match self.ura_meters() {
+            Some(ura) => write!(f, " URA {:.2} m", ura)?,
+            None => write!(f, " URA n/a")?,
+        }
+        if keplerian {
+            write!(
+                f,
+                " a {:.3} km e {:.7} i {:.4}°",
+                self.semi_major_axis() / 1000.0,
+                self.eccentricity,
+                self.i0.to_degrees()
+            )?;
+        }
+        write!(
+            f,
+            " af0 {:.6e} s af1 {:.6e} s/s",
+            self.sv_clock_bias, self.sv_clock_drift
+        )?;
+        // GLONASS and SBAS records hold the message frame time in the third clock field
+        if keplerian {
+            write!(f, " af2 {:.6e} s/s²", self.sv_clock_drift_rate)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a nav file holds for one satellite, from `RinexNav::coverage`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coverage {
+    pub records: usize,
+    pub first: DateTime<Utc>, // Earliest toc
+    pub last: DateTime<Utc>,  // Latest toc
+    pub iodes: usize,         // Distinct IODE values
+    pub max_toe_gap: f64,     // Largest step between consecutive toes, s; 0 for one record
+}
+
+/// `RinexNav::summary` overview: coverage per satellite and the toc span, in UTC.
+/// Displays as one line for the file and one per satellite.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavSummary {
+    pub records: usize,
+    pub span: Option<(DateTime<Utc>, DateTime<Utc>)>, // None without records
+    pub satellites: BTreeMap<SatId, Coverage>,
+}
+
+impl fmt::Display for NavSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} records of {} satellites",
+            self.records,
+            self.satellites.len()
+        )?;
+        if let Some((first, last)) = self.span {
+            write!(
+                f,
+                ", {} to {}",
+                first.format(TIME_FORMAT),
+                last.format(TIME_FORMAT)
+            )?;
+        }
+        for (sat_id, coverage) in &self.satellites {
+            write!(
+                f,
+                "\n{} {:>4} records, {} to {}, {} IODEs, largest toe gap {:.0} s",
+                sat_id,
+                coverage.records,
+                coverage.first.format(TIME_FORMAT),
+                coverage.last.format(TIME_FORMAT),
+                coverage.iodes,
+                coverage.max_toe_gap
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl RinexNav {
+    pub fn summary(&self) -> NavSummary {
+        NavSummary {
+            records: self.records().len(),
+            span: self.time_span(),
+            satellites: self
+                .satellites()
+                .filter_map(|sat_id| Some((sat_id, self.coverage(sat_id)?)))
+                .collect(),
+        }
+    }
+
+    /// Records, toc span, IODEs and largest toe gap of one satellite; None if it has no
+    /// records. Reads only that satellite's records.
+    pub fn coverage(&self, sat_id: SatId) -> Option<Coverage> {
+        let records = self.records_for_slice(sat_id);
+        let (first, last) = toc_span(records)?;
+        let mut toes: Vec<f64> = records.iter().map(NavRecord::toe_gps_seconds).collect();
+        toes.sort_by(f64::total_cmp);
+        let mut iodes: Vec<f64> = records.iter().map(|record| record.iode).collect();
+        iodes.sort_by(f64::total_cmp);
+        iodes.dedup();
+        Some(Coverage {
+            records: records.len(),
+            first,
+            last,
+            iodes: iodes.len(),
+            max_toe_gap: toes
+                .windows(2)
+                .map(|pair| pair[1] - pair[0])
+                .fold(0.0, f64::max),
+        })
+    }
+
+    /// Earliest and latest toc of the whole file, in UTC; None without records
+    pub fn time_span(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        toc_span(self.records())
+    }
+}
+
+fn toc_span(records: &[NavRecord]) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let tocs = records.iter().map(NavRecord::toc_gps_seconds);
+    let first = tocs.clone().min_by(f64::total_cmp)?;
+    let last = tocs.max_by(f64::total_cmp)?;
+    Some((
+        gnss::gps_seconds_to_utc(first),
+        gnss::gps_seconds_to_utc(last),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::nav;
+    use chrono::TimeZone;
+
+    #[test]
+    fn records_display_in_conventional_units() {
+        let nav = nav();
+        let g17 = nav.records_for_slice("G17".parse().unwrap());
+        assert_eq!(
+            g17[0].to_string(),
+            "G17 toe 2023-06-12T01:59:26Z IODE 5 healthy URA 2.40 m a 26561.420 km \
+             e 0.0135036 i 55.8045° af0 7.180688e-4 s af1 1.250555e-12 s/s af2 0.000000e0 s/s²"
+        );
+
+        let mut unhealthy = g17[1];
+        unhealthy.sv_health = 63.0;
+        unhealthy.sv_accuracy = f64::NAN;
+        let line = unhealthy.to_string();
+        assert!(
+            line.contains(" IODE 28 unhealthy (0x3f) URA n/a a "),
+            "{}",
+            line
+        );
+
+        // No Keplerian elements: the toc, the clock offset and drift only
+        let glonass = NavRecord {
+            sat_id: "R05".parse().unwrap(),
+            ..g17[0]
+        };
+        assert_eq!(
+            glonass.to_string(),
+            "R05 toc 2023-06-12T01:59:26Z healthy URA 2.00 m af0 7.180688e-4 s af1 1.250555e-12 s/s"
+        );
+    }
+
+    #[test]
+    fn summary_snapshot() {
+        let summary = nav().summary().to_string();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines.len(), 33);
+        assert_eq!(
+            lines[..3],
+            [
+                "196 records of 32 satellites, 2023-06-12T01:59:26Z to 2023-06-12T23:59:42Z",
+                "G01    6 records, 2023-06-12T01:59:42Z to 2023-06-12T15:59:42Z, 6 IODEs, \
+                 largest toe gap 28816 s",
+                "G02    7 records, 2023-06-12T01:59:42Z to 2023-06-12T23:59:42Z, 7 IODEs, \
+                 largest toe gap 28800 s",
+            ]
+        );
+        assert_eq!(
+            lines[32],
+            "G32    5 records, 2023-06-12T01:59:42Z to 2023-06-12T17:59:42Z, 5 IODEs, \
+             largest toe gap 36000 s"
+        );
+        assert_eq!(
+            RinexNav::default().summary().to_string(),
+            "0 records of 0 satellites"
+        );
+    }
+
+    #[test]
+    fn coverage_and_span_of_the_fixture() {
+        let nav = nav();
+        let utc = |hour, minute, second| {
+            Utc.with_ymd_and_hms(2023, 6, 12, hour, minute, second)
+                .unwrap()
+        };
+        assert_eq!(nav.satellites().count(), 32);
+        assert_eq!(nav.time_span(), Some((utc(1, 59, 26), utc(23, 59, 42))));
+        assert_eq!(
+            nav.coverage("G17".parse().unwrap()),
+            Some(Coverage {
+                records: 6,
+                first: utc(1, 59, 26),
+                last: utc(17, 59, 42),
+                iodes: 6,
+                max_toe_gap: 28800.0,
+            })
+        );
+        let g04 = nav.coverage("G04".parse().unwrap()).unwrap();
+        assert_eq!((g04.records, g04.max_toe_gap), (5, 36000.0));
+        assert_eq!(nav.coverage("E01".parse().unwrap()), None);
+
+        let summary = nav.summary();
+        let total: usize = summary
+            .satellites
+            .values()
+            .map(|coverage| coverage.records)
+            .sum();
+        assert_eq!(total, summary.records);
+        assert_eq!(RinexNav::default().time_span(), None);
+    }
+}
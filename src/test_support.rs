@@ -0,0 +1,79 @@
+//! Fixtures shared by the unit tests: the bundled nav file and the station that logged it
+
+use crate::constellation::Constellation;
+use crate::gnss::{RinexNav, ECEF};
+use crate::observation::RinexObs;
+use crate::positioning::PseudorangeObservation;
+use crate::satellite::PropagationConfig;
+use crate::signal::Signal;
+use chrono::{DateTime, TimeZone, Utc};
+use std::time::Duration;
+
+/// GPS broadcast ephemerides of 2023-06-12 logged by GCGO00USA: 196 records of 32
+/// satellites, G22 unhealthy throughout
+pub const NAV: &str = include_str!("../constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
+
+/// GPS L1 C/A observations at the station, 04:10 to 04:29:30 GPS every 30 s. Simulated
+/// from the nav file by scripts/simulate_observations.py, not logged by a receiver.
+pub const OBS: &str = include_str!("../data/observations/GCGO_20230612_0400_sim.rnx");
+
+/// GCGO00USA, from the position in the fixture's header
+pub fn station() -> ECEF {
+    ECEF::new(-2281621.6297, -1453585.1138, 5756964.9518)
+}
+
+/// The fixture parsed
+pub fn nav() -> RinexNav {
+    NAV.parse().unwrap()
+}
+
+/// Every satellite of the fixture, without states
+pub fn constellation() -> Constellation {
+    Constellation::from_nav(nav())
+}
+
+/// Midnight UTC at the start of the fixture's day, 2023-06-12
+pub fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap()
+}
+
+/// Every satellite of the fixture over the first twelve hours of the day at a
+/// five-minute step
+#[cfg(any(feature = "geo-types", feature = "plot"))]
+pub fn propagated_day() -> Constellation {
+    let config = PropagationConfig::new().step(Duration::from_secs(300));
+    propagated(start(), Duration::from_secs(12 * 3600), &config)
+}
+
+/// 02:00 UTC on the fixture's day, the first epoch of [`propagated_hour`]
+#[cfg(any(feature = "serde", feature = "ndarray", feature = "net"))]
+pub fn hour_start() -> DateTime<Utc> {
+    start() + chrono::Duration::hours(2)
+}
+
+/// Every satellite of the fixture from [`hour_start`] over an hour at a one-minute step
+#[cfg(any(feature = "serde", feature = "ndarray", feature = "net"))]
+pub fn propagated_hour() -> Constellation {
+    let config = PropagationConfig::new().step(Duration::from_secs(60));
+    propagated(hour_start(), Duration::from_secs(3600), &config)
+}
+
+/// Every satellite of the fixture propagated over a window
+pub fn propagated(
+    start: DateTime<Utc>,
+    duration: Duration,
+    config: &PropagationConfig,
+) -> Constellation {
+    let mut constellation = constellation();
+    constellation.propagate_all(start, duration, config);
+    constellation
+}
+
+/// The L1 observations of each epoch of `OBS`, in time order
+pub fn epochs() -> Vec<(DateTime<Utc>, Vec<PseudorangeObservation>)> {
+    let obs: RinexObs = OBS.parse().unwrap();
+    obs.epochs
+        .iter()
+        .map(|epoch| (epoch.epoch, epoch.pseudoranges(Signal::GpsL1)))
+        .collect()
+}
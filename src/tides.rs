@@ -0,0 +1,286 @@
+use crate::celestial::{julian_date, julian_date_tt, moon_position, sun_position, J2000_JD};
+use crate::gnss::{self, ECEF, ENU};
+use chrono::{DateTime, Utc};
+
+const EARTH_RADIUS: f64 = 6378136.6; // IERS equatorial radius, m
+const MOON_EARTH_MASS_RATIO: f64 = 0.0123000371;
+const SUN_EARTH_MASS_RATIO: f64 = 332946.0482;
+const H2: f64 = 0.6078; // Degree-2 Love number, before its latitude dependence
+const L2: f64 = 0.0847; // Degree-2 Shida number
+const H3: f64 = 0.292;
+const L3: f64 = 0.015;
+// Imaginary parts of the diurnal and semidiurnal Love and Shida numbers
+const H_DIURNAL_OUT_OF_PHASE: f64 = -0.0025;
+const L_DIURNAL_OUT_OF_PHASE: f64 = -0.0007;
+const H_SEMIDIURNAL_OUT_OF_PHASE: f64 = -0.0022;
+const L_SEMIDIURNAL_OUT_OF_PHASE: f64 = -0.0007;
+// Latitude-dependent l(1) Shida numbers of the diurnal and semidiurnal bands
+const L1_DIURNAL: f64 = 0.0012;
+const L1_SEMIDIURNAL: f64 = 0.0024;
+
+// Frequency-dependent corrections of step 2 (IERS Conventions 2010, tables 7.3a and 7.3b):
+// multipliers of the Delaunay-style arguments s, h, p, N' and ps, then the in-phase and
+// out-of-phase radial and the in-phase and out-of-phase transverse amplitudes, mm. The
+// diurnal arguments add τ.
+#[rustfmt::skip]
+const DIURNAL: [([f64; 5], [f64; 4]); 31] = [
+    ([-3.0, 0.0, 2.0, 0.0, 0.0], [-0.01, 0.0, 0.0, 0.0]),
+    ([-3.0, 2.0, 0.0, 0.0, 0.0], [-0.01, 0.0, 0.0, 0.0]),
+    ([-2.0, 0.0, 1.0, -1.0, 0.0], [-0.02, 0.0, 0.0, 0.0]),
+    ([-2.0, 0.0, 1.0, 0.0, 0.0], [-0.08, 0.0, -0.01, 0.01]),
+    ([-2.0, 2.0, -1.0, 0.0, 0.0], [-0.02, 0.0, 0.0, 0.0]),
+    ([-1.0, 0.0, 0.0, -1.0, 0.0], [-0.10, 0.0, 0.0, 0.0]),
+    ([-1.0, 0.0, 0.0, 0.0, 0.0], [-0.51, 0.0, -0.02, 0.03]),
+    ([-1.0, 2.0, 0.0, 0.0, 0.0], [0.01, 0.0, 0.0, 0.0]),
+    ([0.0, -2.0, 1.0, 0.0, 0.0], [0.01, 0.0, 0.0, 0.0]),
+    ([0.0, 0.0, -1.0, 0.0, 0.0], [0.02, 0.0, 0.0, 0.0]),
+    ([0.0, 0.0, 1.0, 0.0, 0.0], [0.06, 0.0, 0.0, 0.0]),
+    ([0.0, 0.0, 1.0, 1.0, 0.0], [0.01, 0.0, 0.0, 0.0]),
+    ([0.0, 2.0, -1.0, 0.0, 0.0], [0.01, 0.0, 0.0, 0.0]),
+    ([1.0, -3.0, 0.0, 0.0, 1.0], [-0.06, 0.0, 0.0, 0.0]),
+    ([1.0, -2.0, 0.0, -1.0, 0.0], [0.01, 0.0, 0.0, 0.0]),
+    ([1.0, -2.0, 0.0, 0.0, 0.0], [-1.23, -0.07, 0.06, 0.01]),
+    ([1.0, -1.0, 0.0, 0.0, -1.0], [0.02, 0.0, 0.0, 0.0]),
+    ([1.0, -1.0, 0.0, 0.0, 1.0], [0.04, 0.0, 0.0, 0.0]),
+    ([1.0, 0.0, 0.0, -1.0, 0.0], [-0.22, 0.01, 0.01, 0.0]),
+    ([1.0, 0.0, 0.0, 0.0, 0.0], [12.00, -0.80, -0.67, -0.03]),
+    ([1.0, 0.0, 0.0, 1.0, 0.0], [1.73, -0.12, -0.10, 0.0]),
+    ([1.0, 0.0, 0.0, 2.0, 0.0], [-0.04, 0.0, 0.0, 0.0]),
+    ([1.0, 1.0, 0.0, 0.0, -1.0], [-0.50, -0.01, 0.03, 0.0]),
+    ([1.0, 1.0, 0.0, 0.0, 1.0], [0.01, 0.0, 0.0, 0.0]),
+    ([0.0, 1.0, 0.0, 1.0, -1.0], [-0.01, 0.0, 0.0, 0.0]),
+    ([1.0, 2.0, -2.0, 0.0, 0.0], [-0.01, 0.0, 0.0, 0.0]),
+    ([1.0, 2.0, 0.0, 0.0, 0.0], [-0.11, 0.01, 0.01, 0.0]),
+    ([2.0, -2.0, 1.0, 0.0, 0.0], [-0.01, 0.0, 0.0, 0.0]),
+    ([2.0, 0.0, -1.0, 0.0, 0.0], [-0.02, 0.0, 0.0, 0.0]),
+    ([3.0, 0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0]),
+    ([3.0, 0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 0.0]),
+];
+// Long-period terms: in-phase radial and transverse, then out-of-phase radial and
+// transverse, mm
+#[rustfmt::skip]
+const LONG_PERIOD: [([f64; 5], [f64; 4]); 5] = [
+    ([0.0, 0.0, 0.0, 1.0, 0.0], [0.47, 0.23, 0.16, 0.07]),
+    ([0.0, 2.0, 0.0, 0.0, 0.0], [-0.20, -0.12, -0.11, -0.05]),
+    ([1.0, 0.0, -1.0, 0.0, 0.0], [-0.11, -0.08, -0.09, -0.04]),
+    ([2.0, 0.0, 0.0, 0.0, 0.0], [-0.13, -0.11, -0.15, -0.07]),
+    ([2.0, 0.0, 0.0, 1.0, 0.0], [-0.05, -0.05, -0.06, -0.03]),
+];
+
+// Solid Earth tide displacement after IERS Conventions (2010) 7.1.1, the model of its
+// DEHANTTIDEINEL routine: step 1 with latitude-dependent Love and Shida numbers, degree-3
+// terms, the out-of-phase terms of the diurnal and semidiurnal bands and their l(1)
+// contribution, and the frequency-dependent corrections of step 2 in the diurnal and
+// long-period bands. Latitudes are geocentric, as in the Conventions.
+
+/// Solid Earth tide displacement of a station at a UTC epoch, ECEF m, with the analytic
+/// Sun and Moon positions. The permanent tide is included, so subtracting the displacement
+/// from an instantaneous position gives conventional tide-free (ITRF) coordinates.
+pub fn solid_earth_tide(station: &ECEF, epoch: DateTime<Utc>) -> ECEF {
+    let time = gnss::gps_seconds(epoch);
+    solid_earth_tide_from(station, &sun_position(time), &moon_position(time), time)
+}
+
+/// Solid Earth tide displacement of a station in its local frame, m
+pub fn solid_earth_tide_enu(station: &ECEF, epoch: DateTime<Utc>) -> ENU {
+    station
+        .to_lla()
+        .rotate_to_enu(&solid_earth_tide(station, epoch))
+}
+
+/// Solid Earth tide displacement of a station for given ECEF Sun and Moon positions at a
+/// GPS time, m. The time only sets the phases of the step-2 corrections.
+pub fn solid_earth_tide_from(station: &ECEF, sun: &ECEF, moon: &ECEF, gps_seconds: f64) -> ECEF {
+    let frame = Frame::new(station);
+    let up = frame.up;
+    // Love and Shida numbers vary with latitude through P2(sin φ)
+    let p2 = (3.0 * up.z * up.z - 1.0) / 2.0;
+    let h2 = H2 - 0.0006 * p2;
+    let l2 = L2 + 0.0002 * p2;
+
+    let mut displacement = ECEF::default();
+    for (body, mass_ratio) in [(moon, MOON_EARTH_MASS_RATIO), (sun, SUN_EARTH_MASS_RATIO)] {
+        let distance = body.norm();
+        let direction = *body * (1.0 / distance);
+        let cos_angle = direction.dot(&up);
+        let transverse = direction - up * cos_angle;
+
+        let scale2 = mass_ratio * EARTH_RADIUS.powi(4) / distance.powi(3);
+        let radial2 = h2 * (1.5 * cos_angle * cos_angle - 0.5);
+        displacement = displacement + (up * radial2 + transverse * (3.0 * l2 * cos_angle)) * scale2;
+
+        let scale3 = mass_ratio * EARTH_RADIUS.powi(5) / distance.powi(4);
+        let radial3 = H3 * (2.5 * cos_angle.powi(3) - 1.5 * cos_angle);
+        let transverse3 = L3 * (7.5 * cos_angle * cos_angle - 1.5);
+        displacement = displacement + (up * radial3 + transverse * transverse3) * scale3;
+
+        displacement = displacement + frame.out_of_phase(&direction, scale2);
+    }
+    displacement + frame.step2(gps_seconds)
+}
+
+/// Geocentric latitude and longitude of a station, for the terms given in its local frame
+struct Frame {
+    up: ECEF,
+    sin_lat: f64,
+    cos_lat: f64,
+    sin_lon: f64,
+    cos_lon: f64,
+    longitude: f64, // Radians
+}
+
+impl Frame {
+    fn new(station: &ECEF) -> Self {
+        let up = *station * (1.0 / station.norm());
+        let cos_lat = up.x.hypot(up.y);
+        Self {
+            up,
+            sin_lat: up.z,
+            cos_lat,
+            sin_lon: up.y / cos_lat,
+            cos_lon: up.x / cos_lat,
+            longitude: up.y.atan2(up.x),
+        }
+    }
+
+    /// Radial, north and east components in ECEF
+    fn to_ecef(&self, radial: f64, north: f64, east: f64) -> ECEF {
+        let horizontal = radial * self.cos_lat - north * self.sin_lat;
+        ECEF::new(
+            horizontal * self.cos_lon - east * self.sin_lon,
+            horizontal * self.sin_lon + east * self.cos_lon,
+            radial * self.sin_lat + north * self.cos_lat,
+        )
+    }
+
+    /// Step-1 terms of a body in the diurnal and semidiurnal bands: the imaginary parts of
+    /// the Love and Shida numbers and the l(1) Shida numbers. `direction` is the body's
+    /// unit vector and `scale` the degree-2 scale factor, m.
+    fn out_of_phase(&self, direction: &ECEF, scale: f64) -> ECEF {
+        let (sin_lat, cos_lat) = (self.sin_lat, self.cos_lat);
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (sin_2lon, cos_2lon) = (2.0 * self.longitude).sin_cos();
+        // Diurnal band, with Z(X sin λ - Y cos λ) and Z(X cos λ + Y sin λ)
+        let diurnal_sin = z * (x * self.sin_lon - y * self.cos_lon);
+        let diurnal_cos = z * (x * self.cos_lon + y * self.sin_lon);
+        // Semidiurnal band, with the same combinations at twice the longitude
+        let semidiurnal_sin = (x * x - y * y) * sin_2lon - 2.0 * x * y * cos_2lon;
+        let semidiurnal_cos = (x * x - y * y) * cos_2lon + 2.0 * x * y * sin_2lon;
+        let cos_2lat = cos_lat * cos_lat - sin_lat * sin_lat;
+
+        let radial = -3.0 * H_DIURNAL_OUT_OF_PHASE * sin_lat * cos_lat * diurnal_sin
+            - 0.75 * H_SEMIDIURNAL_OUT_OF_PHASE * cos_lat * cos_lat * semidiurnal_sin;
+        let north = -3.0 * L_DIURNAL_OUT_OF_PHASE * cos_2lat * diurnal_sin
+            + 1.5 * L_SEMIDIURNAL_OUT_OF_PHASE * sin_lat * cos_lat * semidiurnal_sin
+            - 3.0 * L1_DIURNAL * sin_lat * sin_lat * diurnal_cos
+            - 1.5 * L1_SEMIDIURNAL * sin_lat * cos_lat * semidiurnal_cos;
+        let east = -3.0 * L_DIURNAL_OUT_OF_PHASE * sin_lat * diurnal_cos
+            - 1.5 * L_SEMIDIURNAL_OUT_OF_PHASE * cos_lat * semidiurnal_cos
+            + 3.0 * L1_DIURNAL * sin_lat * cos_2lat * diurnal_sin
+            - 1.5 * L1_SEMIDIURNAL * sin_lat * sin_lat * cos_lat * semidiurnal_sin;
+        self.to_ecef(radial, north, east) * scale
+    }
+
+    /// Frequency-dependent corrections of step 2 at a GPS time, m
+    fn step2(&self, gps_seconds: f64) -> ECEF {
+        let t = (julian_date_tt(gps_seconds) - J2000_JD) / 36525.0;
+        let ut_hours = (julian_date(gps_seconds) - 0.5).rem_euclid(1.0) * 24.0;
+        let (tau, arguments) = fundamental_arguments(t, ut_hours);
+        let (sin_lat, cos_lat) = (self.sin_lat, self.cos_lat);
+        let phase = |multipliers: &[f64; 5]| -> f64 {
+            multipliers
+                .iter()
+                .zip(arguments)
+                .map(|(m, argument)| m * argument)
+                .sum()
+        };
+
+        let (mut radial, mut north, mut east) = (0.0, 0.0, 0.0);
+        for (multipliers, [r_in, r_out, t_in, t_out]) in DIURNAL {
+            let (sin, cos) = ((tau + phase(&multipliers)).to_radians() + self.longitude).sin_cos();
+            radial += 2.0 * sin_lat * cos_lat * (r_in * sin + r_out * cos);
+            north += (cos_lat * cos_lat - sin_lat * sin_lat) * (t_in * sin + t_out * cos);
+            east += sin_lat * (t_in * cos - t_out * sin);
+        }
+        for (multipliers, [r_in, t_in, r_out, t_out]) in LONG_PERIOD {
+            let (sin, cos) = phase(&multipliers).to_radians().sin_cos();
+            radial += (1.5 * sin_lat * sin_lat - 0.5) * (r_in * cos + r_out * sin);
+            north += 2.0 * sin_lat * cos_lat * (t_in * cos + t_out * sin);
+        }
+        self.to_ecef(radial, north, east) * 1e-3
+    }
+}
+
+/// τ and the arguments s, h, p, N' and ps of the step-2 tables, degrees, at a time in
+/// Julian centuries of TT since J2000 and the UT hour of day
+fn fundamental_arguments(t: f64, ut_hours: f64) -> (f64, [f64; 5]) {
+    let s = 218.31664563 + (481267.88194 + (-0.0014663889 + 0.00000185139 * t) * t) * t;
+    let tau =
+        280.4606184 + (36000.7700536 + (0.00038793 - 0.0000000258 * t) * t) * t + ut_hours * 15.0
+            - s;
+    let precession = (1.396971278 + (0.000308889 + (0.000000021 + 0.000000007 * t) * t) * t) * t;
+    let h = 280.46645
+        + (36000.7697489 + (0.00030322222 + (0.000000020 - 0.00000000654 * t) * t) * t) * t;
+    let p = 83.35324312
+        + (4069.01363525 + (-0.01032172222 + (-0.0000124991 + 0.00000005263 * t) * t) * t) * t;
+    let node = 234.95544499
+        + (1934.13626197 + (-0.00207561111 + (-0.00000213944 + 0.00000001650 * t) * t) * t) * t;
+    let ps = 282.93734098
+        + (1.71945766667 + (0.00045688889 + (-0.00000001778 - 0.00000000334 * t) * t) * t) * t;
+    (
+        tau.rem_euclid(360.0),
+        [s + precession, h, p, node, ps].map(|angle| angle.rem_euclid(360.0)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // Test case of DEHANTTIDEINEL, the IERS Conventions (2010) software: station, Sun and
+    // Moon ECEF, m, its epoch, 2009-04-13 0h UTC, and the displacement it gives. The Sun and
+    // Moon are inputs, not that epoch's ephemerides.
+    fn iers_case() -> (ECEF, ECEF, ECEF, f64, ECEF) {
+        (
+            ECEF::new(4075578.385, 931852.890, 4801570.154),
+            ECEF::new(137859926952.015, 54228127881.4350, 23509422341.6960),
+            ECEF::new(-179996231.920342, -312468450.131567, -169288918.592160),
+            gnss::gps_seconds(Utc.with_ymd_and_hms(2009, 4, 13, 0, 0, 0).unwrap()),
+            ECEF::new(
+                0.07700420357108126,
+                0.06304056321824968,
+                0.05516568152597247,
+            ),
+        )
+    }
+
+    #[test]
+    fn displacement_matches_the_iers_test_case() {
+        let (station, sun, moon, time, expected) = iers_case();
+        let difference = solid_earth_tide_from(&station, &sun, &moon, time) - expected;
+        assert!(difference.norm() < 1e-6, "{:?}", difference);
+    }
+
+    #[test]
+    fn displacement_stays_within_the_tidal_range() {
+        let station = ECEF::new(4075578.385, 931852.890, 4801570.154);
+        let start = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+        let ups: Vec<f64> = (0..24 * 30)
+            .map(|hour| solid_earth_tide_enu(&station, start + chrono::Duration::hours(hour)).up)
+            .collect();
+        let enu = solid_earth_tide_enu(&station, start);
+        let ecef = solid_earth_tide(&station, start);
+        assert!((station.to_lla().rotate_to_enu(&ecef).up - enu.up).abs() < 1e-12);
+        let (low, high) = ups.iter().fold((f64::MAX, f64::MIN), |(low, high), &up| {
+            (low.min(up), high.max(up))
+        });
+        // Tens of centimetres peak to peak, never beyond about 40 cm either way
+        assert!(
+            high - low > 0.2 && high < 0.4 && low > -0.4,
+            "{} {}",
+            low,
+            high
+        );
+    }
+}
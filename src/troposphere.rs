@@ -0,0 +1,286 @@
+use crate::gnss::LLA;
+
+const MIN_HEIGHT: f64 = 0.0; // m, heights below are treated as sea level
+const MAX_HEIGHT: f64 = 11e3; // m, the tropopause, where the lapse-rate atmosphere ends
+const MIN_ELEVATION: f64 = 5.0; // Degrees, lower elevations are mapped as if at this one
+
+// Niell (1996) coefficients (a, b, c) at latitudes 15°, 30°, 45°, 60° and 75°
+const NIELL_LATITUDES: [f64; 5] = [15.0, 30.0, 45.0, 60.0, 75.0];
+const NIELL_HYDROSTATIC_MEAN: [[f64; 3]; 5] = [
+    [1.2769934e-3, 2.9153695e-3, 62.610505e-3],
+    [1.2683230e-3, 2.9152299e-3, 62.837393e-3],
+    [1.2465397e-3, 2.9288445e-3, 63.721774e-3],
+    [1.2196049e-3, 2.9022565e-3, 63.824265e-3],
+    [1.2045996e-3, 2.9024912e-3, 64.258455e-3],
+];
+const NIELL_HYDROSTATIC_AMPLITUDE: [[f64; 3]; 5] = [
+    [0.0, 0.0, 0.0],
+    [1.2709626e-5, 2.1414979e-5, 9.0128400e-5],
+    [2.6523662e-5, 3.0160779e-5, 4.3497037e-5],
+    [3.4000452e-5, 7.2562722e-5, 84.795348e-5],
+    [4.1202191e-5, 11.723375e-5, 170.37206e-5],
+];
+const NIELL_WET: [[f64; 3]; 5] = [
+    [5.8021897e-4, 1.4275268e-3, 4.3472961e-2],
+    [5.6794847e-4, 1.5138625e-3, 4.6729510e-2],
+    [5.8118019e-4, 1.4572752e-3, 4.3908931e-2],
+    [5.9727542e-4, 1.5007428e-3, 4.4626982e-2],
+    [6.1641693e-4, 1.7599082e-3, 5.4736038e-2],
+];
+const NIELL_HEIGHT: [f64; 3] = [2.53e-5, 5.49e-3, 1.14e-3];
+
+/// Surface meteorological conditions at the receiver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Meteo {
+    pub pressure: f64,          // Total pressure, hPa
+    pub temperature: f64,       // K
+    pub relative_humidity: f64, // 0 to 1
+}
+
+impl Meteo {
+    /// Standard atmosphere at a height above sea level (m) with 50 % humidity
+    pub fn standard(height: f64) -> Self {
+        let height = height.clamp(MIN_HEIGHT, MAX_HEIGHT);
+        Self {
+            pressure: 1013.25 * (1.0 - 2.2557e-5 * height).powf(5.2568),
+            temperature: 288.15 - 6.5e-3 * height,
+            relative_humidity: 0.5,
+        }
+    }
+
+    /// Partial pressure of water vapour, hPa
+    pub fn water_vapour_pressure(&self) -> f64 {
+        let t = self.temperature;
+        self.relative_humidity.clamp(0.0, 1.0) * 6.108 * ((17.15 * t - 4684.0) / (t - 38.45)).exp()
+    }
+}
+
+/// Hydrostatic and wet zenith delays, m
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ZenithDelay {
+    pub hydrostatic: f64,
+    pub wet: f64,
+}
+
+impl ZenithDelay {
+    pub fn total(&self) -> f64 {
+        self.hydrostatic + self.wet
+    }
+}
+
+/// How zenith delays are projected onto the line of sight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MappingFunction {
+    /// 1/sin(el) for both components; poor below about 15°
+    #[default]
+    Cosecant,
+    /// Niell (1996): separate hydrostatic and wet continued fractions by latitude, season
+    /// and height
+    Niell,
+}
+
+impl MappingFunction {
+    /// Hydrostatic and wet mapping factors at an elevation in degrees, for a receiver on a
+    /// fractional day of the year (1 = January 1st, 0h UT)
+    pub fn factors(&self, receiver: &LLA, elevation: f64, day_of_year: f64) -> (f64, f64) {
+        let sin_el = elevation.to_radians().sin();
+        match self {
+            Self::Cosecant => (1.0 / sin_el, 1.0 / sin_el),
+            Self::Niell => {
+                // Seasonal phase from January 28th, shifted half a year in the south
+                let mut phase = (day_of_year - 28.0) / 365.25;
+                if receiver.latitude < 0.0 {
+                    phase += 0.5;
+                }
+                let seasonal = (2.0 * std::f64::consts::PI * phase).cos();
+                let latitude = receiver.latitude.abs();
+                let mean = niell_interpolate(&NIELL_HYDROSTATIC_MEAN, latitude);
+                let amplitude = niell_interpolate(&NIELL_HYDROSTATIC_AMPLITUDE, latitude);
+                let hydrostatic = [0, 1, 2].map(|i| mean[i] - amplitude[i] * seasonal);
+                let height = receiver.altitude.clamp(MIN_HEIGHT, MAX_HEIGHT) / 1000.0;
+                let height_correction =
+                    (1.0 / sin_el - continued_fraction(sin_el, &NIELL_HEIGHT)) * height;
+                (
+                    continued_fraction(sin_el, &hydrostatic) + height_correction,
+                    continued_fraction(sin_el, &niell_interpolate(&NIELL_WET, latitude)),
+                )
+            }
+        }
+    }
+}
+
+/// Marini's continued fraction, normalised to 1 at the zenith
+fn continued_fraction(sin_el: f64, [a, b, c]: &[f64; 3]) -> f64 {
+    (1.0 + a / (1.0 + b / (1.0 + c))) / (sin_el + a / (sin_el + b / (sin_el + c)))
+}
+
+/// Coefficients at an absolute latitude, linear between the tabulated ones and constant
+/// beyond them
+fn niell_interpolate(table: &[[f64; 3]; 5], latitude: f64) -> [f64; 3] {
+    let latitude = latitude.clamp(NIELL_LATITUDES[0], NIELL_LATITUDES[4]);
+    let i = NIELL_LATITUDES
+        .iter()
+        .rposition(|&tabulated| tabulated <= latitude)
+        .unwrap_or(0)
+        .min(3);
+    let t = (latitude - NIELL_LATITUDES[i]) / (NIELL_LATITUDES[i + 1] - NIELL_LATITUDES[i]);
+    [0, 1, 2].map(|k| table[i][k] + (table[i + 1][k] - table[i][k]) * t)
+}
+
+/// Saastamoinen zenith delays projected by a selectable mapping function. Heights are
+/// clamped to [0, 11 km] and elevations below 5° mapped as 5°, so the delay stays finite
+/// and bounded.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Saastamoinen {
+    pub meteo: Option<Meteo>, // Measured conditions; None for the standard atmosphere
+    pub mapping: MappingFunction,
+}
+
+impl Saastamoinen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_meteo(meteo: Meteo) -> Self {
+        Self {
+            meteo: Some(meteo),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_mapping(mut self, mapping: MappingFunction) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Zenith delays at a receiver
+    pub fn zenith_delay(&self, receiver: &LLA) -> ZenithDelay {
+        let height = receiver.altitude.clamp(MIN_HEIGHT, MAX_HEIGHT);
+        let meteo = self.meteo.unwrap_or_else(|| Meteo::standard(height));
+        // Gravity at the column's centre of mass varies with latitude and height
+        let gravity = 1.0
+            - 0.00266 * (2.0 * receiver.latitude.to_radians()).cos()
+            - 0.00028 * height / 1000.0;
+        ZenithDelay {
+            hydrostatic: 0.0022768 * meteo.pressure / gravity,
+            wet: 0.002277 * (1255.0 / meteo.temperature + 0.05) * meteo.water_vapour_pressure(),
+        }
+    }
+
+    /// Slant delay at an elevation in degrees on a fractional day of the year, m
+    pub fn slant_delay(&self, receiver: &LLA, elevation: f64, day_of_year: f64) -> f64 {
+        let zenith = self.zenith_delay(receiver);
+        let (hydrostatic, wet) =
+            self.mapping
+                .factors(receiver, elevation.max(MIN_ELEVATION), day_of_year);
+        zenith.hydrostatic * hydrostatic + zenith.wet * wet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zenith_hydrostatic_delay_at_sea_level_is_2_3_m() {
+        let model = Saastamoinen::new();
+        let zenith = model.zenith_delay(&LLA::new(45.0, 10.0, 0.0));
+        assert!(
+            (zenith.hydrostatic - 2.3070).abs() < 1e-4,
+            "{}",
+            zenith.hydrostatic
+        );
+        // 50 % humidity at 15 °C
+        assert!((zenith.wet - 0.0860).abs() < 1e-4, "{}", zenith.wet);
+        // Slightly more at the equator, where gravity is weaker
+        let equator = model.zenith_delay(&LLA::new(0.0, 10.0, 0.0));
+        assert!((equator.hydrostatic - 2.3131).abs() < 1e-4);
+        // And less a kilometre up
+        let up = model.zenith_delay(&LLA::new(45.0, 10.0, 1000.0));
+        assert!((up.hydrostatic - 2.0468).abs() < 1e-4, "{}", up.hydrostatic);
+    }
+
+    #[test]
+    fn measured_meteo_replaces_the_standard_atmosphere() {
+        let meteo = Meteo {
+            pressure: 1000.0,
+            temperature: 300.0,
+            relative_humidity: 0.0,
+        };
+        let zenith = Saastamoinen::with_meteo(meteo).zenith_delay(&LLA::new(45.0, 0.0, 0.0));
+        assert!((zenith.hydrostatic - 2.2768).abs() < 1e-12);
+        assert_eq!(zenith.wet, 0.0);
+    }
+
+    #[test]
+    fn out_of_range_inputs_are_clamped() {
+        let model = Saastamoinen::new();
+        let sea_level = LLA::new(45.0, 10.0, 0.0);
+        assert_eq!(
+            model.zenith_delay(&LLA::new(45.0, 10.0, -50.0)),
+            model.zenith_delay(&sea_level)
+        );
+        assert_eq!(
+            model.zenith_delay(&LLA::new(45.0, 10.0, 20e3)),
+            model.zenith_delay(&LLA::new(45.0, 10.0, 11e3))
+        );
+        let at_mask = model.slant_delay(&sea_level, 5.0, 100.0);
+        for elevation in [4.0, 0.5, 0.0, -3.0] {
+            assert_eq!(model.slant_delay(&sea_level, elevation, 100.0), at_mask);
+        }
+        assert!(at_mask.is_finite() && at_mask < 30.0);
+
+        // Cosecant: twice the zenith delay at 30°
+        let zenith = model.zenith_delay(&sea_level).total();
+        assert!((model.slant_delay(&sea_level, 30.0, 100.0) - 2.0 * zenith).abs() < 1e-12);
+        assert!((model.slant_delay(&sea_level, 90.0, 100.0) - zenith).abs() < 1e-12);
+    }
+
+    #[test]
+    fn niell_matches_an_independent_evaluation() {
+        // (latitude, height, day of year, elevation) and the hydrostatic and wet factors
+        // evaluated separately from the tables of Niell (1996), J. Geophys. Res. 101(B2)
+        let samples = [
+            ((45.0, 0.0, 28.0, 5.0), (10.151761745, 10.750884210)),
+            ((45.0, 0.0, 28.0, 10.0), (5.555763191, 5.657127345)),
+            ((15.0, 0.0, 200.0, 5.0), (10.100346891, 10.750678456)),
+            ((-60.0, 500.0, 28.0, 7.0), (7.650148230, 7.914058946)),
+            ((75.0, 2000.0, 200.0, 15.0), (3.802814088, 3.831571261)),
+            ((30.0, 0.0, 100.0, 3.0), (14.589621487, 16.474200051)),
+        ];
+        for ((latitude, height, day, elevation), (hydrostatic, wet)) in samples {
+            let receiver = LLA::new(latitude, 0.0, height);
+            let factors = MappingFunction::Niell.factors(&receiver, elevation, day);
+            assert!((factors.0 - hydrostatic).abs() < 1e-8, "{:?}", factors);
+            assert!((factors.1 - wet).abs() < 1e-8, "{:?}", factors);
+        }
+        let zenith = MappingFunction::Niell.factors(&LLA::new(45.0, 0.0, 0.0), 90.0, 28.0);
+        assert!((zenith.0 - 1.0).abs() < 1e-12 && (zenith.1 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn niell_departs_from_the_cosecant_at_low_elevations() {
+        let receiver = LLA::new(45.0, 0.0, 0.0);
+        let ratio = |elevation: f64| {
+            let niell = MappingFunction::Niell
+                .factors(&receiver, elevation, 180.0)
+                .0;
+            niell * elevation.to_radians().sin()
+        };
+        // Within a percent above 30°, then increasingly below the flat-Earth cosecant
+        assert!((ratio(30.0) - 1.0).abs() < 0.01);
+        assert!(ratio(15.0) < ratio(30.0) && ratio(10.0) < ratio(15.0));
+        assert!((0.85..0.92).contains(&ratio(5.0)), "{}", ratio(5.0));
+
+        // At 5° the cosecant overstates the slant delay by about three metres
+        let cosecant = Saastamoinen::new().slant_delay(&receiver, 5.0, 180.0);
+        let niell = Saastamoinen::new()
+            .with_mapping(MappingFunction::Niell)
+            .slant_delay(&receiver, 5.0, 180.0);
+        assert!(
+            (2.5..4.0).contains(&(cosecant - niell)),
+            "{}",
+            cosecant - niell
+        );
+    }
+}
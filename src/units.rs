@@ -0,0 +1,234 @@
+//! Unit-bearing scalars for clock offsets, delays and spans of GPS time, so seconds cannot
+//! be added to meters or milliseconds taken for seconds without a conversion in the code.
+//! Each wraps a plain f64; `as_f64` unwraps it where a formula needs the bare number.
+
+use core::fmt;
+use core::iter::Sum;
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Clock offset or signal delay, s
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Seconds(pub f64);
+
+/// Range or range-equivalent delay, m
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Meters(pub f64);
+
+/// Span between two `GpsTime` instants, s; negative when the second comes first
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct GpsSeconds(pub f64);
+
+impl Seconds {
+    /// Distance light covers in this time at `speed`, e.g. `gnss::C_LIGHT`
+    pub fn to_meters(self, speed: f64) -> Meters {
+        Meters(self.0 * speed)
+    }
+}
+
+impl Meters {
+    /// Time light needs for this distance at `speed`
+    pub fn to_seconds(self, speed: f64) -> Seconds {
+        Seconds(self.0 / speed)
+    }
+}
+
+impl GpsSeconds {
+    pub fn from_millis(millis: f64) -> Self {
+        Self(millis / 1000.0)
+    }
+
+    pub fn as_millis(self) -> f64 {
+        self.0 * 1000.0
+    }
+}
+
+/// A `core::time::Duration` is never negative, so every one converts
+impl From<core::time::Duration> for GpsSeconds {
+    fn from(duration: core::time::Duration) -> Self {
+        Self(duration.as_secs_f64())
+    }
+}
+
+/// Arithmetic within one unit and scaling by plain numbers; the ratio of two values of a
+/// unit is a plain number
+macro_rules! unit_scalar {
+    ($unit:ident, $symbol:literal) => {
+        impl $unit {
+            pub fn as_f64(self) -> f64 {
+                self.0
+            }
+
+            pub fn abs(self) -> Self {
+                Self(self.0.abs())
+            }
+        }
+
+        impl Add for $unit {
+            type Output = $unit;
+
+            fn add(self, other: $unit) -> $unit {
+                $unit(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $unit {
+            type Output = $unit;
+
+            fn sub(self, other: $unit) -> $unit {
+                $unit(self.0 - other.0)
+            }
+        }
+
+        impl AddAssign for $unit {
+            fn add_assign(&mut self, other: $unit) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $unit {
+            fn sub_assign(&mut self, other: $unit) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $unit {
+            type Output = $unit;
+
+            fn neg(self) -> $unit {
+                $unit(-self.0)
+            }
+        }
+
+        impl Mul<f64> for $unit {
+            type Output = $unit;
+
+            fn mul(self, factor: f64) -> $unit {
+                $unit(self.0 * factor)
+            }
+        }
+
+        impl Mul<$unit> for f64 {
+            type Output = $unit;
+
+            fn mul(self, value: $unit) -> $unit {
+                $unit(self * value.0)
+            }
+        }
+
+        impl Div<f64> for $unit {
+            type Output = $unit;
+
+            fn div(self, divisor: f64) -> $unit {
+                $unit(self.0 / divisor)
+            }
+        }
+
+        impl Div for $unit {
+            type Output = f64;
+
+            fn div(self, other: $unit) -> f64 {
+                self.0 / other.0
+            }
+        }
+
+        impl Sum for $unit {
+            fn sum<I: Iterator<Item = $unit>>(iter: I) -> $unit {
+                $unit(iter.map(|value| value.0).sum())
+            }
+        }
+
+        impl fmt::Display for $unit {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)?;
+                f.write_str($symbol)
+            }
+        }
+    };
+}
+
+unit_scalar!(Seconds, " s");
+unit_scalar!(Meters, " m");
+unit_scalar!(GpsSeconds, " s");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::{GpsTime, C_LIGHT};
+    #[cfg(feature = "std")]
+    use crate::test_support::NAV;
+
+    #[test]
+    fn arithmetic_stays_within_a_unit() {
+        let bias = Seconds(2e-4) + Seconds(1e-4) - Seconds(5e-5);
+        assert_eq!(bias, Seconds(2e-4 + 1e-4 - 5e-5));
+        assert_eq!(-bias * 2.0, Seconds(-(2e-4 + 1e-4 - 5e-5) * 2.0));
+        assert_eq!(Meters(10.0) / Meters(4.0), 2.5);
+        assert_eq!(Meters(10.0) / 4.0, Meters(2.5));
+        assert_eq!(0.5 * GpsSeconds(-30.0), GpsSeconds(-15.0));
+        assert_eq!(GpsSeconds(-30.0).abs(), GpsSeconds(30.0));
+        let mut total: Meters = [1.0, 2.0, 3.5].into_iter().map(Meters).sum();
+        total -= Meters(0.5);
+        total += Meters(1.0);
+        assert_eq!(total, Meters(7.0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn display_carries_the_unit() {
+        assert_eq!(Seconds(1.5).to_string(), "1.5 s");
+        assert_eq!(format!("{:.2}", Meters(20200000.0)), "20200000.00 m");
+    }
+
+    #[test]
+    fn conversions_between_units() {
+        let bias = Seconds(1e-3);
+        let range = bias.to_meters(C_LIGHT);
+        assert_eq!(range, Meters(299_792.458));
+        assert_eq!(range.to_seconds(C_LIGHT), bias);
+        assert_eq!(GpsSeconds::from_millis(1500.0), GpsSeconds(1.5));
+        assert_eq!(GpsSeconds(1.5).as_millis(), 1500.0);
+        assert_eq!(
+            GpsSeconds::from(core::time::Duration::from_millis(2500)),
+            GpsSeconds(2.5)
+        );
+        let toe = GpsTime::from_week_seconds(2266, 93_600.0);
+        assert_eq!(toe + GpsSeconds(60.0) - toe, GpsSeconds(60.0));
+        assert_eq!((toe - GpsSeconds(7200.0)).week_seconds(), (2266, 86_400.0));
+    }
+
+    /// The epoch stored in milliseconds reads back as seconds, and the clock terms meet at
+    /// toc without any scaling left over
+    #[cfg(feature = "std")]
+    #[test]
+    fn record_times_and_clocks_are_converted_once() {
+        let nav: crate::gnss::RinexNav = NAV.parse().unwrap();
+        // G17 2023 06 12 01 59 44 in GPS time
+        let record = nav.records_for_slice(17.into())[0];
+        let toc = record.toc_epoch();
+        assert_eq!(toc, GpsTime::from_week_seconds(2266, 93_584.0));
+        assert_eq!(record.gps_millis, toc.seconds() * 1000.0);
+
+        let state = record.evaluate(toc, 1e-12, 30);
+        let e_anomaly_term = state.clock_bias - Seconds(record.sv_clock_bias);
+        // Only the relativistic term, under 50 ns for a near-circular orbit
+        assert!(e_anomaly_term.abs() < Seconds(5e-8), "{}", e_anomaly_term);
+        let later = record.evaluate(toc + GpsSeconds(1000.0), 1e-12, 30);
+        let drift = (later.clock_bias - state.clock_bias) / Seconds(1000.0);
+        assert!((drift - record.sv_clock_drift).abs() < 1e-10, "{}", drift);
+    }
+}
@@ -0,0 +1,467 @@
+use crate::gnss::{Constellation, NavRecord, RinexNav, SatId, SECONDS_PER_WEEK, WGS84_A};
+use std::collections::BTreeMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fmt;
+
+const MAX_USUAL_ECCENTRICITY: f64 = 0.1; // Above this no operational GNSS orbit flies
+
+/// How far a record can be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// Unusual, but the record evaluates to finite states
+    Warning,
+    /// The record evaluates to NaN or to no meaningful orbit
+    Error,
+}
+
+/// Check of `NavRecord::validate` an issue comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValidationRule {
+    NonFinite,       // A NaN or infinite field
+    Eccentricity,    // Outside [0, 1), or higher than any GNSS orbit
+    SemiMajorAxis,   // sqrt_a non-positive, perigee inside the Earth, or off the constellation band
+    ToeOutsideWeek,  // toe outside [0, 604800) s
+    Inclination,     // |i0| beyond pi, or outside [0, pi/2]
+    ClockBias,       // af0 beyond what the message can broadcast
+    ClockDrift,      // af1 beyond what the message can broadcast
+    ClockDriftRate,  // af2 beyond what the message can broadcast
+    DataInvalid,     // Health word flags the navigation data itself
+    SignalUnhealthy, // Health word flags a signal component
+}
+
+impl fmt::Display for ValidationRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::NonFinite => "non-finite value",
+            Self::Eccentricity => "eccentricity",
+            Self::SemiMajorAxis => "sqrt(A)",
+            Self::ToeOutsideWeek => "toe outside the week",
+            Self::Inclination => "inclination",
+            Self::ClockBias => "clock bias",
+            Self::ClockDrift => "clock drift",
+            Self::ClockDriftRate => "clock drift rate",
+            Self::DataInvalid => "navigation data invalid",
+            Self::SignalUnhealthy => "signal unhealthy",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One failed check, with the offending value
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationIssue {
+    pub rule: ValidationRule,
+    pub severity: Severity,
+    pub value: f64,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {} ({:e})", severity, self.rule, self.value)
+    }
+}
+
+/// Decoded SV health word
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HealthFlags {
+    pub data_invalid: bool,     // The navigation data should not be used
+    pub signal_unhealthy: bool, // At least one signal component is out of service
+    pub raw: u32,
+}
+
+impl HealthFlags {
+    pub fn is_healthy(&self) -> bool {
+        !self.data_invalid && !self.signal_unhealthy
+    }
+}
+
+/// Validation counts of one satellite's records
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SatelliteValidation {
+    pub records: usize,
+    pub rejected: usize, // Records with at least one error
+    pub warnings: usize,
+    pub errors: usize,
+    pub rules: BTreeMap<ValidationRule, usize>, // Issues per rule
+}
+
+/// `RinexNav::validate` summary, per satellite and in total
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationReport {
+    pub records: usize,
+    pub rejected: usize,
+    pub warnings: usize,
+    pub errors: usize,
+    pub satellites: BTreeMap<SatId, SatelliteValidation>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.warnings == 0 && self.errors == 0
+    }
+
+    fn add(&mut self, record: &NavRecord) {
+        let issues = record.validate();
+        let errors = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        let warnings = issues.len() - errors;
+        let rejected = usize::from(errors > 0);
+        self.records += 1;
+        self.rejected += rejected;
+        self.warnings += warnings;
+        self.errors += errors;
+
+        let satellite = self.satellites.entry(record.sat_id).or_default();
+        satellite.records += 1;
+        satellite.rejected += rejected;
+        satellite.warnings += warnings;
+        satellite.errors += errors;
+        for issue in &issues {
+            *satellite.rules.entry(issue.rule).or_default() += 1;
+        }
+    }
+}
+
+/// Largest |af0|, |af1|, |af2| the constellation's message can carry, s, s/s, s/s².
+/// GLONASS and SBAS have no third term; RINEX stores the message frame time there.
+fn clock_limits(constellation: Constellation) -> (f64, f64, Option<f64>) {
+    match constellation {
+        Constellation::Gps | Constellation::Qzss | Constellation::Irnss => {
+            (0.5f64.powi(10), 0.5f64.powi(28), Some(0.5f64.powi(48)))
+        }
+        Constellation::Galileo => (0.5f64.powi(4), 0.5f64.powi(26), Some(0.5f64.powi(54))),
+        Constellation::BeiDou => (0.5f64.powi(10), 0.5f64.powi(29), Some(0.5f64.powi(56))),
+        Constellation::Glonass => (0.5f64.powi(9), 0.5f64.powi(30), None),
+        Constellation::Sbas => (0.5f64.powi(20), 0.5f64.powi(33), None),
+    }
+}
+
+/// Usual sqrt(A) range of the constellation's orbits, sqrt(m); None without Keplerian elements
+fn sqrt_a_band(constellation: Constellation) -> Option<(f64, f64)> {
+    match constellation {
+        Constellation::Gps => Some((5000.0, 5300.0)),
+        Constellation::Galileo => Some((5300.0, 5600.0)),
+        Constellation::BeiDou => Some((5100.0, 6600.0)), // MEO up to GEO/IGSO
+        Constellation::Qzss | Constellation::Irnss => Some((6300.0, 6700.0)),
+        Constellation::Glonass | Constellation::Sbas => None,
+    }
+}
+
+impl NavRecord {
+    /// SV health word split into data and signal flags as the constellation defines it
+    pub fn health_flags(&self) -> HealthFlags {
+        let raw = self.sv_health as u32;
+        let (data_invalid, signal_unhealthy) = match self.sat_id.constellation {
+            // Bit 5 is the NAV data summary, bits 0-4 the signal components
+            Constellation::Gps | Constellation::Qzss => (raw & 0x20 != 0, raw & 0x1f != 0),
+            // Data validity status of E1B, E5a, E5b in bits 0, 3, 6, signal health after each
+            Constellation::Galileo => (raw & 0x49 != 0, raw & 0x1b6 != 0),
+            _ => (false, self.sv_health != 0.0),
+        };
+        HealthFlags {
+            data_invalid,
+            signal_unhealthy,
+            raw,
+        }
+    }
+
+    /// Semantic checks of the broadcast values, beyond what parsing can catch. GLONASS and
+    /// SBAS records carry no Keplerian elements, so only their clock and health are checked.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |rule, severity, value| {
+            issues.push(ValidationIssue {
+                rule,
+                severity,
+                value,
+            })
+        };
+        let constellation = self.sat_id.constellation;
+        let keplerian = sqrt_a_band(constellation);
+
+        let clock = [
+            self.sv_clock_bias,
+            self.sv_clock_drift,
+            self.sv_clock_drift_rate,
+        ];
+        let orbit = [
+            self.crs,
+            self.delta_n,
+            self.m0,
+            self.cuc,
+            self.eccentricity,
+            self.cus,
+            self.sqrt_a,
+            self.toe,
+            self.cic,
+            self.omega0,
+            self.cis,
+            self.i0,
+            self.crc,
+            self.omega,
+            self.omega_dot,
+            self.idot,
+            self.gps_week,
+        ];
+        let orbit: &[f64] = if keplerian.is_some() { &orbit } else { &[] };
+        for &value in clock.iter().chain(orbit) {
+            if !value.is_finite() {
+                issue(ValidationRule::NonFinite, Severity::Error, value);
+            }
+        }
+
+        let (af0_max, af1_max, af2_max) = clock_limits(constellation);
+        let terms = [
+            (ValidationRule::ClockBias, self.sv_clock_bias, Some(af0_max)),
+            (
+                ValidationRule::ClockDrift,
+                self.sv_clock_drift,
+                Some(af1_max),
+            ),
+            (
+                ValidationRule::ClockDriftRate,
+                self.sv_clock_drift_rate,
+                af2_max,
+            ),
+        ];
+        for (rule, value, max) in terms {
+            if max.is_some_and(|max| value.abs() > max) {
+                issue(rule, Severity::Warning, value);
+            }
+        }
+
+        if let Some((sqrt_a_min, sqrt_a_max)) = keplerian {
+            let e = self.eccentricity;
+            if e.is_finite() && !(0.0..1.0).contains(&e) {
+                issue(ValidationRule::Eccentricity, Severity::Error, e);
+            } else if e > MAX_USUAL_ECCENTRICITY {
+                issue(ValidationRule::Eccentricity, Severity::Warning, e);
+            }
+
+            let sqrt_a = self.sqrt_a;
+            if sqrt_a.is_finite() {
+                // With e unusable the perigee is checked at e = 0
+                let perigee = match (0.0..1.0).contains(&e) {
+                    true => self.perigee_radius(),
+                    false => self.semi_major_axis(),
+                };
+                if sqrt_a <= 0.0 || perigee <= WGS84_A {
+                    issue(ValidationRule::SemiMajorAxis, Severity::Error, sqrt_a);
+                } else if !(sqrt_a_min..=sqrt_a_max).contains(&sqrt_a) {
+                    issue(ValidationRule::SemiMajorAxis, Severity::Warning, sqrt_a);
+                }
+            }
+
+            if self.toe.is_finite() && !(0.0..SECONDS_PER_WEEK).contains(&self.toe) {
+                issue(ValidationRule::ToeOutsideWeek, Severity::Error, self.toe);
+            }
+
+            let i0 = self.i0;
+            if i0.is_finite() && i0.abs() > PI {
+                issue(ValidationRule::Inclination, Severity::Error, i0);
+            } else if i0.is_finite() && !(0.0..=FRAC_PI_2).contains(&i0) {
+                issue(ValidationRule::Inclination, Severity::Warning, i0);
+            }
+        }
+
+        let health = self.health_flags();
+        if health.data_invalid {
+            issue(
+                ValidationRule::DataInvalid,
+                Severity::Warning,
+                self.sv_health,
+            );
+        }
+        if health.signal_unhealthy {
+            issue(
+                ValidationRule::SignalUnhealthy,
+                Severity::Warning,
+                self.sv_health,
+            );
+        }
+        issues
+    }
+
+    /// No `validate` issue is an error, so the record evaluates to a meaningful orbit
+    pub fn is_valid(&self) -> bool {
+        self.validate()
+            .iter()
+            .all(|issue| issue.severity != Severity::Error)
+    }
+}
+
+impl RinexNav {
+    /// `NavRecord::validate` over every record, counted per satellite and rule
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for record in self.records() {
+            report.add(record);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::GpsTime;
+    use crate::test_support::NAV;
+
+    fn g17() -> NavRecord {
+        let nav: RinexNav = NAV.parse().unwrap();
+        nav.records_for_slice("G17".parse().unwrap())[0]
+    }
+
+    type Corruption = fn(&mut NavRecord);
+
+    /// The one issue of a G17 record changed by `corrupt`
+    fn single_issue(corrupt: impl FnOnce(&mut NavRecord)) -> (ValidationRule, Severity) {
+        let mut record = g17();
+        corrupt(&mut record);
+        match record.validate()[..] {
+            [issue] => (issue.rule, issue.severity),
+            ref issues => panic!("{:?}", issues),
+        }
+    }
+
+    #[test]
+    fn the_fixture_passes_except_for_an_unhealthy_satellite() {
+        let nav: RinexNav = NAV.parse().unwrap();
+        let report = nav.validate();
+        assert_eq!(report.records, nav.records().len());
+        assert_eq!((report.rejected, report.errors), (0, 0));
+        for (sat_id, satellite) in &report.satellites {
+            let unhealthy = satellite.rules.keys().all(|rule| {
+                matches!(
+                    rule,
+                    ValidationRule::DataInvalid | ValidationRule::SignalUnhealthy
+                )
+            });
+            assert!(unhealthy, "{}: {:?}", sat_id, satellite.rules);
+        }
+        assert!(g17().validate().is_empty());
+    }
+
+    #[test]
+    fn each_rule_catches_its_corruption() {
+        use Severity::{Error, Warning};
+        use ValidationRule::*;
+        let cases: [(Corruption, ValidationRule, Severity); 16] = [
+            (|r| r.m0 = f64::NAN, NonFinite, Error),
+            (|r| r.omega_dot = f64::INFINITY, NonFinite, Error),
+            (|r| r.eccentricity = 1.0, Eccentricity, Error),
+            (|r| r.eccentricity = -0.01, Eccentricity, Error),
+            (|r| r.eccentricity = 0.3, Eccentricity, Warning),
+            (|r| r.sqrt_a = -5153.6, SemiMajorAxis, Error),
+            (|r| r.sqrt_a = 2000.0, SemiMajorAxis, Error),
+            (|r| r.sqrt_a = 5500.0, SemiMajorAxis, Warning),
+            (|r| r.toe = SECONDS_PER_WEEK, ToeOutsideWeek, Error),
+            (|r| r.toe = -30.0, ToeOutsideWeek, Error),
+            (|r| r.i0 = 4.0, Inclination, Error),
+            (|r| r.i0 = -0.9, Inclination, Warning),
+            (|r| r.sv_clock_bias = 2e-3, ClockBias, Warning),
+            (|r| r.sv_clock_drift = 1e-8, ClockDrift, Warning),
+            (|r| r.sv_clock_drift_rate = 1e-14, ClockDriftRate, Warning),
+            (|r| r.sv_health = 1.0, SignalUnhealthy, Warning),
+        ];
+        for (corrupt, rule, severity) in cases {
+            assert_eq!(single_issue(corrupt), (rule, severity));
+        }
+        // The data summary bit of GPS LNAV comes with its signal bits or on its own
+        assert_eq!(single_issue(|r| r.sv_health = 32.0), (DataInvalid, Warning));
+    }
+
+    #[test]
+    fn records_evaluating_to_nan_are_errors() {
+        let time = GpsTime::from_week_seconds(2266, 93_600.0);
+        let corruptions: [Corruption; 2] = [|r| r.eccentricity = 1.5, |r| r.delta_n = f64::NAN];
+        for corrupt in corruptions {
+            let mut record = g17();
+            corrupt(&mut record);
+            assert!(!record.is_valid());
+            let position = record.position_at(time);
+            assert!(!position.norm().is_finite(), "{:?}", position);
+        }
+        // A warning leaves a usable orbit
+        let mut record = g17();
+        record.eccentricity = 0.3;
+        assert!(record.is_valid());
+        assert!(record.position_at(time).norm() > WGS84_A);
+    }
+
+    #[test]
+    fn health_words_per_constellation() {
+        let flags = |constellation, health: f64| {
+            let record = NavRecord {
+                sat_id: SatId::new(constellation, 1),
+                sv_health: health,
+                ..Default::default()
+            };
+            let flags = record.health_flags();
+            (flags.data_invalid, flags.signal_unhealthy)
+        };
+        assert_eq!(flags(Constellation::Gps, 0.0), (false, false));
+        assert_eq!(flags(Constellation::Gps, 63.0), (true, true));
+        assert_eq!(flags(Constellation::Qzss, 2.0), (false, true));
+        // Galileo E5a data invalid, then E1B signal health
+        assert_eq!(flags(Constellation::Galileo, 8.0), (true, false));
+        assert_eq!(flags(Constellation::Galileo, 2.0), (false, true));
+        assert_eq!(flags(Constellation::BeiDou, 1.0), (false, true));
+        assert!(NavRecord::default().health_flags().is_healthy());
+    }
+
+    #[test]
+    fn the_report_counts_per_satellite_and_rule() {
+        let mut records = vec![g17(), g17(), g17()];
+        records[1].eccentricity = 1.2;
+        records[1].sv_clock_bias = 0.01;
+        records[2].i0 = -0.5;
+        let mut other = g17();
+        other.sat_id = "G05".parse().unwrap();
+        other.toe = 700_000.0;
+        records.push(other);
+
+        let report = RinexNav::from_records(records).validate();
+        assert!(!report.is_clean());
+        assert_eq!(
+            (
+                report.records,
+                report.rejected,
+                report.warnings,
+                report.errors
+            ),
+            (4, 2, 2, 2)
+        );
+        let g17 = &report.satellites[&"G17".parse().unwrap()];
+        assert_eq!(
+            (g17.records, g17.rejected, g17.warnings, g17.errors),
+            (3, 1, 2, 1)
+        );
+        let rules: Vec<_> = g17
+            .rules
+            .iter()
+            .map(|(&rule, &count)| (rule, count))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (ValidationRule::Eccentricity, 1),
+                (ValidationRule::Inclination, 1),
+                (ValidationRule::ClockBias, 1),
+            ]
+        );
+        let g05 = &report.satellites[&"G05".parse().unwrap()];
+        assert_eq!(g05.rules[&ValidationRule::ToeOutsideWeek], 1);
+    }
+}
@@ -0,0 +1,484 @@
+use crate::constellation::Constellation;
+#[cfg(feature = "ndarray")]
+use crate::gnss::ECEF;
+use crate::gnss::{self, SatId, LLA};
+use crate::horizon::ElevationMask;
+#[cfg(feature = "ndarray")]
+use crate::positioning::Dop;
+use crate::satellite::Satellite;
+#[cfg(feature = "ndarray")]
+use crate::selection;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+const REFINE_TOLERANCE: f64 = 1.0; // Seconds to which rise, set and culmination are refined
+
+/// (epoch, azimuth, elevation), angles in degrees
+pub type SkyPoint = (DateTime<Utc>, f64, f64);
+
+/// Samples of one continuous arc across the sky
+pub type SkyTrack = Vec<SkyPoint>;
+
+/// An interval with the satellite above the elevation mask or terrain horizon
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pass {
+    pub sat_id: SatId,
+    pub rise: DateTime<Utc>,
+    pub set: DateTime<Utc>,
+    pub culmination: DateTime<Utc>,
+    pub max_elevation: f64, // Degrees
+    pub rise_clipped: bool, // Already up at the start of the span, rise is the span start
+    pub set_clipped: bool,  // Still up at the end of the span, set is the span end
+}
+
+impl Pass {
+    pub fn duration_seconds(&self) -> f64 {
+        (self.set - self.rise).num_milliseconds() as f64 / 1000.0
+    }
+}
+
+/// `G17 rise 2023-06-12 04:10:05, culmination 05:40:12 at 78.3°, set 07:12:40 UTC`, with
+/// "up at" and "still up at" for the span ends of clipped passes and the date repeated
+/// when the pass runs past midnight
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |epoch: DateTime<Utc>| match epoch.date_naive() == self.rise.date_naive() {
+            true => epoch.format("%H:%M:%S"),
+            false => epoch.format("%Y-%m-%d %H:%M:%S"),
+        };
+        let rise = match self.rise_clipped {
+            true => "up at",
+            false => "rise",
+        };
+        let set = match self.set_clipped {
+            true => "still up at",
+            false => "set",
+        };
+        write!(
+            f,
+            "{} {} {}, culmination {} at {:.1}°, {} {} UTC",
+            self.sat_id,
+            rise,
+            self.rise.format("%Y-%m-%d %H:%M:%S"),
+            time(self.culmination),
+            self.max_elevation,
+            set,
+            time(self.set)
+        )
+    }
+}
+
+impl Satellite {
+    /// Passes above the mask between start and end, found on the propagated grid and
+    /// refined by bisection on the interpolated orbit. With a horizon profile, rise and set
+    /// are where the satellite clears the terrain.
+    pub fn passes(
+        &self,
+        observer: &LLA,
+        mask: impl ElevationMask,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<Pass> {
+        let aer_at = |time: f64| {
+            self.interpolate_at(gnss::gps_seconds_to_utc(time))
+                .ok()
+                .map(|position| observer.aer_to(&position))
+        };
+        let elevation_at = |time: f64| aer_at(time).map_or(f64::NEG_INFINITY, |aer| aer.elevation);
+        let above = |time: f64| aer_at(time).is_some_and(|aer| mask.is_above(&aer));
+        let mut up = Vec::new();
+        let samples: Vec<(f64, f64)> = self
+            .states_between(start, end)
+            .map(|state| {
+                let aer = observer.aer_to(&state.position());
+                up.push(mask.is_above(&aer));
+                (state.time(), aer.elevation)
+            })
+            .collect();
+
+        let mut passes = Vec::new();
+        // Rise time, whether it was clipped, and the index of the highest sample so far
+        let mut current: Option<(f64, bool, usize)> = None;
+        for (idx, (&(time, elevation), &is_up)) in samples.iter().zip(&up).enumerate() {
+            match current.as_mut() {
+                None if is_up => {
+                    current = Some(match idx {
+                        0 => (time, true, idx),
+                        _ => (bisect(samples[idx - 1].0, time, &above), false, idx),
+                    });
+                }
+                Some((_, _, peak)) if is_up && elevation > samples[*peak].1 => *peak = idx,
+                Some(_) if is_up => {}
+                Some(&mut (rise, rise_clipped, peak)) => {
+                    let set = bisect(samples[idx - 1].0, time, &above);
+                    passes.push(self.make_pass(
+                        (rise, rise_clipped),
+                        (set, false),
+                        &samples,
+                        peak,
+                        &elevation_at,
+                    ));
+                    current = None;
+                }
+                None => {}
+            }
+        }
+        if let (Some((rise, rise_clipped, peak)), Some(&(last, _))) = (current, samples.last()) {
+            passes.push(self.make_pass(
+                (rise, rise_clipped),
+                (last, true),
+                &samples,
+                peak,
+                &elevation_at,
+            ));
+        }
+        passes
+    }
+
+    fn make_pass(
+        &self,
+        (rise, rise_clipped): (f64, bool),
+        (set, set_clipped): (f64, bool),
+        samples: &[(f64, f64)],
+        peak: usize,
+        elevation_at: &impl Fn(f64) -> f64,
+    ) -> Pass {
+        // The maximum lies within one grid step of the highest sample
+        let lo = samples[peak.saturating_sub(1)].0.max(rise);
+        let hi = samples[(peak + 1).min(samples.len() - 1)].0.min(set);
+        let culmination = golden_section_max(lo, hi, elevation_at);
+        let (culmination, max_elevation) = match elevation_at(culmination) {
+            elevation if elevation >= samples[peak].1 => (culmination, elevation),
+            _ => samples[peak],
+        };
+        Pass {
+            sat_id: self.id,
+            rise: gnss::gps_seconds_to_utc(rise),
+            set: gnss::gps_seconds_to_utc(set),
+            culmination: gnss::gps_seconds_to_utc(culmination),
+            max_elevation,
+            rise_clipped,
+            set_clipped,
+        }
+    }
+}
+
+impl Constellation {
+    /// Passes of every satellite, ordered by rise time
+    pub fn passes(
+        &self,
+        observer: &LLA,
+        mask: impl ElevationMask,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<Pass> {
+        let mut passes: Vec<Pass> = self
+            .iter()
+            .flat_map(|satellite| satellite.passes(observer, &mask, start, end))
+            .collect();
+        passes.sort_by_key(|pass| (pass.rise, pass.sat_id));
+        passes
+    }
+
+    /// Azimuth/elevation tracks sampled every step. A satellite gets a new track each time it
+    /// rises above the mask, and tracks are also broken where the azimuth wraps through north
+    /// (with points interpolated at 360 and 0), so polar plots never draw across the sky.
+    pub fn skyplot(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        step: Duration,
+        mask: impl ElevationMask,
+    ) -> BTreeMap<SatId, Vec<SkyTrack>> {
+        let mut tracks: BTreeMap<SatId, Vec<SkyTrack>> = BTreeMap::new();
+        let mut last_seen: BTreeMap<SatId, usize> = BTreeMap::new();
+        let steps = duration.as_millis() / step.as_millis();
+        for k in 0..steps as usize {
+            let epoch = start + step * k as u32;
+            for (sat_id, aer) in self.visible(observer, epoch, &mask) {
+                let point = (epoch, aer.azimuth, aer.elevation);
+                let sat_tracks = tracks.entry(sat_id).or_default();
+                match sat_tracks.last_mut() {
+                    Some(track) if k > 0 && last_seen.get(&sat_id) == Some(&(k - 1)) => {
+                        let previous = *track.last().unwrap();
+                        if (aer.azimuth - previous.1).abs() > 180.0 {
+                            let (end, begin) = split_at_north(previous, point);
+                            track.push(end);
+                            sat_tracks.push(vec![begin, point]);
+                        } else {
+                            track.push(point);
+                        }
+                    }
+                    _ => sat_tracks.push(vec![point]),
+                }
+                last_seen.insert(sat_id, k);
+            }
+        }
+        tracks
+    }
+
+    /// Geometry-only DOP of the satellites above the mask or local horizon, sampled every
+    /// step; None where fewer than four are visible
+    #[cfg(feature = "ndarray")]
+    pub fn dop_series(
+        &self,
+        observer: &LLA,
+        start: DateTime<Utc>,
+        duration: Duration,
+        step: Duration,
+        mask: impl ElevationMask,
+    ) -> Vec<(DateTime<Utc>, Option<Dop>)> {
+        let steps = duration.as_millis() / step.as_millis();
+        (0..steps as usize)
+            .map(|k| {
+                let epoch = start + step * k as u32;
+                (epoch, self.dop_at(observer, epoch, &mask))
+            })
+            .collect()
+    }
+
+    /// Geometry-only DOP of the satellites above the mask or local horizon at one epoch;
+    /// None if fewer than four are visible
+    #[cfg(feature = "ndarray")]
+    pub fn dop_at(
+        &self,
+        observer: &LLA,
+        epoch: DateTime<Utc>,
+        mask: impl ElevationMask,
+    ) -> Option<Dop> {
+        let receiver = observer.to_ecef();
+        let lines_of_sight: Vec<ECEF> = self
+            .visible(observer, epoch, mask)
+            .into_iter()
+            .filter_map(|(sat_id, _)| {
+                let satellite = self.get(sat_id)?;
+                let position = match satellite.state_at(epoch) {
+                    Some(state) => state.position(),
+                    None => satellite.interpolate_at(epoch).ok()?,
+                };
+                gnss::unit_line_of_sight(&receiver, &position)
+            })
+            .collect();
+        selection::dop(&lines_of_sight, &receiver)
+    }
+}
+
+/// Points at azimuth 360 and 0 where the segment between two samples crosses north
+fn split_at_north((t0, az0, el0): SkyPoint, (t1, az1, el1): SkyPoint) -> (SkyPoint, SkyPoint) {
+    // Unwrap the second azimuth next to the first, then find where it passes 0/360
+    let az1_unwrapped = az1 + 360.0 * ((az0 - az1) / 360.0).round();
+    let boundary = if az1_unwrapped > az0 { 360.0 } else { 0.0 };
+    let fraction = (boundary - az0) / (az1_unwrapped - az0);
+    let time = t0
+        + chrono::Duration::milliseconds(
+            ((t1 - t0).num_milliseconds() as f64 * fraction).round() as i64
+        );
+    let elevation = el0 + fraction * (el1 - el0);
+    (
+        (time, boundary, elevation),
+        (time, 360.0 - boundary, elevation),
+    )
+}
+
+/// Time of the state change between lo and hi, where `above` differs at the two ends
+fn bisect(mut lo: f64, mut hi: f64, above: &impl Fn(f64) -> bool) -> f64 {
+    let above_lo = above(lo);
+    while hi - lo > REFINE_TOLERANCE {
+        let mid = 0.5 * (lo + hi);
+        if above(mid) == above_lo {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+fn golden_section_max(mut lo: f64, mut hi: f64, f: &impl Fn(f64) -> f64) -> f64 {
+    let ratio = 0.5 * (5.0f64.sqrt() - 1.0);
+    while hi - lo > REFINE_TOLERANCE {
+        let a = hi - ratio * (hi - lo);
+        let b = lo + ratio * (hi - lo);
+        if f(a) < f(b) {
+            lo = a;
+        } else {
+            hi = b;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::satellite::PropagationConfig;
+    use crate::test_support::station;
+    use crate::test_support::NAV;
+    use chrono::TimeZone;
+
+    /// The fixture's station, from the position in its header
+    fn observer() -> LLA {
+        station().to_lla()
+    }
+
+    fn satellite(prn: u8, step: u64, start: DateTime<Utc>, hours: u64) -> Satellite {
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let config = PropagationConfig::new()
+            .step(Duration::from_secs(step))
+            .with_velocity(true);
+        let mut satellite = Satellite::builder(prn).build();
+        let records = nav.records_for_slice(prn.into());
+        satellite
+            .propagate(start, Duration::from_secs(hours * 3600), &config, records)
+            .unwrap();
+        satellite
+    }
+
+    /// Passes read off a one-second elevation series: first and last second above the mask
+    /// and the highest sample, up to a time
+    fn brute_force(satellite: &Satellite, mask: f64, until: f64) -> Vec<(f64, f64, f64, f64)> {
+        let mut passes = Vec::new();
+        let mut current: Option<(f64, f64, f64, f64)> = None;
+        for state in satellite
+            .states
+            .iter()
+            .filter(|state| state.time() <= until)
+        {
+            let elevation = observer().aer_to(&state.position()).elevation;
+            match (elevation >= mask, current.as_mut()) {
+                (true, None) => {
+                    current = Some((state.time(), state.time(), state.time(), elevation))
+                }
+                (true, Some(pass)) => {
+                    pass.1 = state.time();
+                    if elevation > pass.3 {
+                        (pass.2, pass.3) = (state.time(), elevation);
+                    }
+                }
+                (false, Some(_)) => passes.push(current.take().unwrap()),
+                (false, None) => {}
+            }
+        }
+        passes.extend(current);
+        passes
+    }
+
+    #[test]
+    fn split_at_north_interpolates_both_sides() {
+        let t0 = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let t1 = t0 + chrono::Duration::seconds(60);
+        let (end, begin) = split_at_north((t0, 350.0, 20.0), (t1, 10.0, 22.0));
+        let middle = t0 + chrono::Duration::seconds(30);
+        assert_eq!((end, begin), ((middle, 360.0, 21.0), (middle, 0.0, 21.0)));
+        // Westward through north
+        let (end, begin) = split_at_north((t0, 5.0, 40.0), (t1, 345.0, 40.0));
+        let quarter = t0 + chrono::Duration::seconds(15);
+        assert_eq!((end, begin), ((quarter, 0.0, 40.0), (quarter, 360.0, 40.0)));
+    }
+
+    #[test]
+    fn skyplot_tracks_break_at_the_mask_and_at_north() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+        let day = Duration::from_secs(86400);
+        let step = Duration::from_secs(60);
+        let nav: gnss::RinexNav = NAV.parse().unwrap();
+        let mut constellation = Constellation::from_nav(nav);
+        let config = PropagationConfig::new().step(Duration::from_secs(300));
+        constellation.propagate_all(start, day, &config);
+        let tracks = constellation.skyplot(&observer(), start, day, step, 10.0);
+
+        let mut wraps = 0;
+        for (sat_id, sat_tracks) in &tracks {
+            for track in sat_tracks {
+                for &(_, azimuth, elevation) in track {
+                    assert!((0.0..=360.0).contains(&azimuth));
+                    assert!(elevation >= 10.0, "{} {}", sat_id, elevation);
+                }
+                for pair in track.windows(2) {
+                    assert!((pair[1].1 - pair[0].1).abs() < 180.0);
+                    assert!(
+                        pair[1].0 > pair[0].0
+                            && pair[1].0 - pair[0].0 <= chrono::Duration::seconds(60)
+                    );
+                }
+            }
+            for pair in sat_tracks.windows(2) {
+                let (end, begin) = (*pair[0].last().unwrap(), pair[1][0]);
+                if end.0 == begin.0 {
+                    // Split at north: the same point on both sides of 0/360
+                    assert_eq!(end.1 + begin.1, 360.0);
+                    assert_eq!(end.2, begin.2);
+                    wraps += 1;
+                } else {
+                    // Set below the mask and rose again
+                    assert!(begin.0 - end.0 > chrono::Duration::seconds(60));
+                }
+            }
+        }
+        assert!(wraps > 0);
+        // G17 rises twice in the day
+        assert!(tracks[&SatId::gps(17)].len() >= 2);
+        // Unhealthy G22 is never plotted
+        assert!(!tracks.contains_key(&SatId::gps(22)));
+    }
+
+    #[test]
+    fn passes_match_a_one_second_elevation_series() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 12, 2, 0, 0).unwrap();
+        let end = start + chrono::Duration::hours(12);
+        let mut total = 0;
+        for prn in [5, 17, 20, 30] {
+            let coarse = satellite(prn, 300, start, 12);
+            let truth = satellite(prn, 1, start, 12);
+            // Passes end with the propagated span, at its last state
+            let last = *coarse.states.times().last().unwrap();
+            let passes = coarse.passes(&observer(), 10.0, start, end);
+            let expected = brute_force(&truth, 10.0, last);
+            assert_eq!(passes.len(), expected.len(), "G{:02}", prn);
+            for (pass, (rise, set, culmination, max_elevation)) in passes.iter().zip(expected) {
+                assert!((gnss::gps_seconds(pass.rise) - rise).abs() <= 2.0);
+                if !pass.set_clipped {
+                    assert!((gnss::gps_seconds(pass.set) - set).abs() <= 2.0);
+                }
+                assert_eq!(pass.rise_clipped, pass.rise == start);
+                assert!((gnss::gps_seconds(pass.culmination) - culmination).abs() <= 30.0);
+                assert!((pass.max_elevation - max_elevation).abs() < 0.01);
+                // A pass clipped while still rising culminates at the end of the span
+                assert!(pass.rise < pass.culmination && pass.culmination <= pass.set);
+                assert!(pass.duration_seconds() > 0.0);
+            }
+            total += passes.len();
+        }
+        assert!(total >= 4);
+    }
+
+    #[test]
+    fn passes_display_their_times() {
+        let at = |h, m, s| Utc.with_ymd_and_hms(2023, 6, 12, h, m, s).unwrap();
+        let mut pass = Pass {
+            sat_id: SatId::gps(17),
+            rise: at(4, 10, 5),
+            set: at(7, 12, 40),
+            culmination: at(5, 40, 12),
+            max_elevation: 78.26,
+            rise_clipped: false,
+            set_clipped: false,
+        };
+        assert_eq!(
+            pass.to_string(),
+            "G17 rise 2023-06-12 04:10:05, culmination 05:40:12 at 78.3°, set 07:12:40 UTC"
+        );
+        pass.rise_clipped = true;
+        pass.set_clipped = true;
+        pass.set = Utc.with_ymd_and_hms(2023, 6, 13, 0, 20, 0).unwrap();
+        assert_eq!(
+            pass.to_string(),
+            "G17 up at 2023-06-12 04:10:05, culmination 05:40:12 at 78.3°, \
+             still up at 2023-06-13 00:20:00 UTC"
+        );
+    }
+}
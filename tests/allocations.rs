@@ -0,0 +1,80 @@
+//! Propagating from a nav file borrows its records: neither building a constellation nor
+//! propagating a satellite copies them, and a warmed-up buffer is refilled in place.
+//! One test only, as the allocation counter is shared by the whole process.
+
+#![cfg(feature = "std")]
+
+use chrono::{TimeZone, Utc};
+use pnt_rust::constellation::Constellation;
+use pnt_rust::gnss::{NavRecord, RinexNav, StateSeries};
+use pnt_rust::satellite::{PropagationConfig, Satellite};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Bytes allocated while running `f`
+fn allocated<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = f();
+    (value, ALLOCATED.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn records_are_borrowed_not_copied() {
+    let text = include_str!("../constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
+    let nav: Arc<RinexNav> = Arc::new(text.parse().unwrap());
+    let firsts: Vec<NavRecord> = nav
+        .satellites()
+        .map(|sat_id| nav.records_for_slice(sat_id)[0])
+        .collect();
+    let firsts = Arc::new(RinexNav::from_records(firsts));
+
+    // Several records a satellite cost no more than one, once the spacecraft table is loaded
+    Constellation::from_nav(Arc::clone(&firsts));
+    let (constellation, bytes) = allocated(|| Constellation::from_nav(Arc::clone(&nav)));
+    assert_eq!(constellation.len(), 32);
+    let (_, single_record_bytes) = allocated(|| Constellation::from_nav(firsts));
+    assert_eq!(bytes, single_record_bytes);
+
+    let sat_id = "G17".parse().unwrap();
+    let start = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+    let config = PropagationConfig::new().with_velocity(true);
+    let mut satellite = Satellite::builder(sat_id).build();
+    let mut states = StateSeries::new();
+    let mut propagate = || {
+        satellite
+            .propagate_into(
+                start,
+                Duration::from_secs(3600),
+                &config,
+                nav.records_for(sat_id),
+                &mut states,
+            )
+            .unwrap()
+    };
+    let (report, warm_up) = allocated(&mut propagate);
+    assert_eq!(report.states, 3600);
+    assert!(warm_up > 0);
+    let (report, bytes) = allocated(&mut propagate);
+    assert_eq!(report.states, 3600);
+    assert_eq!(bytes, 0);
+}
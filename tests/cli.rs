@@ -0,0 +1,189 @@
+//! The `pnt` binary run on the bundled nav file, one test per subcommand and its failures
+
+#![cfg(feature = "cli")]
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+
+const NAV: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/constellation/GCGO00USA_R_20231630000_01D_GN.rnx"
+);
+
+fn pnt() -> Command {
+    Command::cargo_bin("pnt").unwrap()
+}
+
+fn stdout(command: &mut Command) -> String {
+    let output = command.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn info_summarizes_each_satellite() {
+    let text = stdout(pnt().args(["info", NAV]));
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        "196 records of 32 satellites, 2023-06-12T01:59:26Z to 2023-06-12T23:59:42Z"
+    );
+    assert_eq!(lines.len(), 1 + 32);
+    assert!(lines[1].starts_with("G01    6 records"));
+}
+
+#[test]
+fn propagate_writes_csv_to_standard_output() {
+    let text = stdout(pnt().args(["propagate", NAV, "--sat", "G17"]));
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].starts_with("time,gps_week,tow,x,y,z,vx,vy,vz,"));
+    // An hour at the default 60 s step from the first toc
+    assert_eq!(lines.len(), 1 + 60);
+    assert!(lines[1].starts_with("2023-06-12T01:59:26.000Z,2266,93584,"));
+    assert!(lines[60].starts_with("2023-06-12T02:58:26.000Z,"));
+}
+
+#[test]
+fn propagate_writes_json_and_kml_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("g17.json");
+    let kml = dir.path().join("g17.kml");
+    let args = [
+        "propagate",
+        NAV,
+        "--sat",
+        "G17",
+        "--start",
+        "2023-06-12T02:00:00Z",
+        "--duration",
+        "600",
+        "--step",
+        "30",
+    ];
+
+    pnt()
+        .args(args)
+        .args(["--format", "json", "--output", json.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout("");
+    let states: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+    let states = states.as_array().unwrap();
+    assert_eq!(states.len(), 20);
+    assert!(states[0]["velocity"]["x"].is_number());
+
+    pnt()
+        .args(args)
+        .args(["--format", "kml", "-o", kml.to_str().unwrap()])
+        .assert()
+        .success();
+    let text = fs::read_to_string(&kml).unwrap();
+    assert!(text.starts_with("<?xml"));
+    assert!(text.contains("<name>G17</name>"));
+}
+
+#[test]
+fn convert_round_trips_a_position() {
+    let ecef = stdout(pnt().args([
+        "convert", "--from", "lla", "--to", "ecef", "40", "-105", "1600",
+    ]));
+    assert_eq!(ecef, "-1266643.1360 -4727176.5388 4079014.0324\n");
+    let coordinates: Vec<&str> = ecef.split_whitespace().collect();
+    let lla = stdout(
+        pnt()
+            .args(["convert", "--from", "ecef", "--to", "lla"])
+            .args(coordinates),
+    );
+    // Back to within the 0.1 mm the ECEF output is rounded to
+    let lla: Vec<f64> = lla
+        .split_whitespace()
+        .map(|value| value.parse().unwrap())
+        .collect();
+    assert!((lla[0] - 40.0).abs() < 1e-8);
+    assert!((lla[1] + 105.0).abs() < 1e-8);
+    assert!((lla[2] - 1600.0).abs() < 1e-3);
+}
+
+#[test]
+fn visible_lists_satellites_by_elevation() {
+    let text = stdout(pnt().args([
+        "visible",
+        NAV,
+        "--site",
+        "40,-105,1600",
+        "--time",
+        "2023-06-12T02:00:00Z",
+    ]));
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "10 satellites above 10° at 2023-06-12T02:00:00Z");
+    assert_eq!(lines.len(), 1 + 10);
+    let elevations: Vec<f64> = lines[1..]
+        .iter()
+        .map(|line| {
+            let after = line.split(" el ").nth(1).unwrap();
+            after.split('°').next().unwrap().trim().parse().unwrap()
+        })
+        .collect();
+    assert!(elevations.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert!(elevations.iter().all(|&elevation| elevation >= 10.0));
+}
+
+#[test]
+fn run_carries_out_a_scenario() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = dir.path().join("scenario.toml");
+    fs::write(
+        &scenario,
+        format!(
+            r#"
+nav = [{:?}]
+satellites = ["G03", "G17", "G26"]
+start = "2023-06-12T06:00:00Z"
+duration = 3600.0
+step = 60.0
+mask = 10.0
+
+[output]
+format = "csv"
+path = "orbits"
+"#,
+            NAV
+        ),
+    )
+    .unwrap();
+
+    let text = stdout(pnt().arg("run").arg(&scenario));
+    assert!(text.starts_with("18 records, 3 of 3 satellites propagated"));
+    for sat in ["G03", "G17", "G26"] {
+        let csv = fs::read_to_string(dir.path().join("orbits").join(format!("{}.csv", sat)));
+        assert_eq!(csv.unwrap().lines().count(), 1 + 60);
+    }
+}
+
+#[test]
+fn failures_exit_nonzero_with_a_message() {
+    pnt()
+        .args(["info", "missing.rnx"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with(
+            "error: cannot open missing.rnx",
+        ));
+    pnt()
+        .args(["propagate", NAV, "--sat", "E11"])
+        .assert()
+        .failure()
+        .stderr("error: no records for E11\n");
+    pnt()
+        .args(["propagate", NAV, "--sat", "G17", "--step", "-60"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("step must be a finite number"));
+    pnt()
+        .args(["run", "missing.toml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("error: missing.toml"));
+    pnt().arg("propagate").assert().failure().code(2);
+}
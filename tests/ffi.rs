@@ -0,0 +1,72 @@
+//! Builds the library as a cdylib, compiles examples/ffi/propagate.c against the generated
+//! header with the system C compiler and runs it on the fixture. It needs `cc` and a
+//! separate target directory for the cdylib build, so it only runs when asked for:
+//!
+//!     cargo test --features ffi --test ffi -- --ignored
+
+#![cfg(feature = "ffi")]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+#[ignore = "builds a cdylib and needs a C compiler"]
+fn c_example_loads_propagates_and_reads_states() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .current_dir(root)
+        .args([
+            "rustc",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            "ffi",
+        ])
+        .args(["--crate-type", "cdylib", "--target-dir"])
+        .arg(&target)
+        .status()
+        .unwrap();
+    assert!(status.success(), "cdylib build failed");
+
+    let library = target.join("debug");
+    let program = target.join("propagate");
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(root.join("examples/ffi/propagate.c"))
+        .arg(format!("-I{}", root.join("include").display()))
+        .arg(format!("-L{}", library.display()))
+        .args(["-lpnt_rust", "-o"])
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(status.success(), "compiling the C example failed");
+
+    let output = Command::new(&program)
+        .current_dir(root)
+        .env("LD_LIBRARY_PATH", &library)
+        .env("DYLD_LIBRARY_PATH", &library)
+        .args(["constellation/GCGO00USA_R_20231630000_01D_GN.rnx", "G05"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(
+        lines[0].ends_with("records, 6 states of G05"),
+        "{}",
+        lines[0]
+    );
+    // One line per state, 10 minutes apart from 2023-06-12 00:00:00 UTC
+    for (i, line) in lines[1..7].iter().enumerate() {
+        let time: f64 = line.split_whitespace().next().unwrap().parse().unwrap();
+        assert_eq!(time, 1_686_528_000.0 + 600.0 * i as f64);
+    }
+    assert_eq!(lines[7], "expected error: state 6 of 6");
+}
@@ -0,0 +1,101 @@
+//! The nav parser reports every problem of a corrupted file as one warning naming the
+//! line, and a clean file as nothing above debug. Propagation ends with one timing summary
+//! at info. One test only, as the logger is global to the process.
+
+#![cfg(feature = "std")]
+
+use chrono::{TimeZone, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pnt_rust::gnss::{RinexNav, SatId};
+use pnt_rust::satellite::{PropagationConfig, Satellite};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const NAV: &str = include_str!("../constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
+const HEADER_LINES: usize = 11;
+const RECORD_LINES: usize = 8;
+
+struct Capture(Mutex<Vec<(Level, String)>>);
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let message = record.args().to_string();
+        self.0.lock().unwrap().push((record.level(), message));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+
+/// Messages logged at `level` or above while running `run`
+fn logged<T>(level: Level, run: impl FnOnce() -> T) -> (T, Vec<String>) {
+    LOGGER.0.lock().unwrap().clear();
+    let value = run();
+    let messages = LOGGER
+        .0
+        .lock()
+        .unwrap()
+        .drain(..)
+        .filter(|(logged, _)| *logged <= level)
+        .map(|(_, message)| message)
+        .collect();
+    (value, messages)
+}
+
+/// The fixture with a bad satellite id, a bad epoch, an unreadable value, a stray short
+/// line and a truncated last record
+fn corrupted() -> String {
+    let mut lines: Vec<String> = NAV.lines().map(str::to_string).collect();
+    let first_line = |record: usize| HEADER_LINES + RECORD_LINES * record;
+    lines[first_line(0)].replace_range(0..1, "X");
+    lines[first_line(1)].replace_range(9..11, "13");
+    lines[first_line(2) + 2].replace_range(61..80, " not a number here ");
+    lines.insert(first_line(4), "G01 junk".to_string());
+    lines.truncate(lines.len() - 3);
+    lines.join("\n")
+}
+
+#[test]
+fn diagnostics_are_logged_at_their_levels() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let (clean, messages) = logged(Level::Info, || NAV.parse::<RinexNav>().unwrap());
+    assert!(messages.is_empty(), "{:?}", messages);
+    let (_, messages) = logged(Level::Debug, || NAV.parse::<RinexNav>().unwrap());
+    assert!(messages.iter().any(|m| m.starts_with("parsed ")));
+
+    let (nav, warnings) = logged(Level::Warn, || corrupted().parse::<RinexNav>().unwrap());
+    assert_eq!(warnings.len(), 5, "{:#?}", warnings);
+    let expected = [
+        "nav line 12: invalid satellite id \"X17\", record skipped",
+        "nav line 20: invalid epoch, record skipped",
+        "nav line 30: unreadable value \"not a number here\", read as 0",
+        "nav line 44: too short for a record's first line, skipped",
+    ];
+    for message in expected {
+        assert!(warnings.iter().any(|w| w == message), "{}", message);
+    }
+    assert!(warnings
+        .iter()
+        .any(|w| w.ends_with("record is truncated, skipped")));
+    // The skipped id, epoch and truncated records are missing; the unreadable value is not
+    assert_eq!(nav.records().len(), clean.records().len() - 3);
+
+    // An hour of G17 over its 04:00 and 06:00 records: the summary and nothing else
+    let start = Utc.with_ymd_and_hms(2023, 6, 12, 4, 30, 0).unwrap();
+    let config = PropagationConfig::new();
+    let mut satellite = Satellite::builder(17).build();
+    let records = clean.records_for_slice(SatId::gps(17));
+    let (report, messages) = logged(Level::Info, || {
+        satellite.propagate(start, Duration::from_secs(3600), &config, records)
+    });
+    assert_eq!(report.unwrap().states, 3600);
+    assert_eq!(messages.len(), 1, "{:?}", messages);
+    assert!(messages[0].starts_with("G17: propagated 3600 epochs in "));
+}
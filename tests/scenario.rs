@@ -0,0 +1,126 @@
+//! A scenario file next to a copy of the bundled nav file, read and run end to end
+
+#![cfg(feature = "scenario")]
+
+use chrono::{TimeZone, Utc};
+use pnt_rust::constellation::SatelliteStatus;
+use pnt_rust::gnss::{RinexNav, SatId};
+use pnt_rust::scenario::{Scenario, ScenarioError};
+use std::fs;
+use std::path::Path;
+
+const NAV: &str = include_str!("../constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
+const SATELLITES: [&str; 4] = ["G03", "G17", "G22", "G26"];
+
+/// Six hours of four GPS satellites and one without records, written as CZML, with
+/// passes over one location under a flat mask and under a 30° wall of terrain
+fn write_scenario(dir: &Path) -> std::path::PathBuf {
+    fs::create_dir(dir.join("nav")).unwrap();
+    fs::write(dir.join("nav/brdc.rnx"), NAV).unwrap();
+    fs::write(dir.join("wall.csv"), "azimuth,elevation\n0,30\n180,30\n").unwrap();
+    let path = dir.join("scenario.toml");
+    fs::write(
+        &path,
+        r#"
+nav = ["nav/brdc.rnx"]
+satellites = ["G03", "G17", "G22", "G26", "E11"]
+start = "2023-06-12T06:00:00Z"
+duration = 21600.0
+step = 30.0
+velocity = true
+mask = 10.0
+
+[output]
+format = "czml"
+path = "out/orbits.czml"
+
+[[sites]]
+name = "Open"
+latitude = 37.4
+longitude = -122.1
+altitude = 30.0
+
+[[sites]]
+name = "Walled"
+latitude = 37.4
+longitude = -122.1
+altitude = 30.0
+mask = 5.0
+horizon = "wall.csv"
+"#,
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn scenario_runs_end_to_end() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = Scenario::from_file(write_scenario(dir.path())).unwrap();
+    assert_eq!(scenario.nav, [dir.path().join("nav/brdc.rnx")]);
+    assert_eq!(
+        scenario.start,
+        Utc.with_ymd_and_hms(2023, 6, 12, 6, 0, 0).unwrap()
+    );
+
+    let report = scenario.run().unwrap();
+    let nav: RinexNav = NAV.parse().unwrap();
+    let records: usize = SATELLITES
+        .iter()
+        .map(|sat| nav.records_for_slice(sat.parse().unwrap()).len())
+        .sum();
+    assert_eq!(report.records, records);
+
+    let status = |sat: &str| &report.statuses[&sat.parse::<SatId>().unwrap()];
+    assert_eq!(report.statuses.len(), 5);
+    for sat in ["G03", "G17", "G26"] {
+        match status(sat) {
+            SatelliteStatus::Propagated(propagation) => assert_eq!(propagation.states, 720),
+            other => panic!("{} {:?}", sat, other),
+        }
+    }
+    assert_eq!(*status("G22"), SatelliteStatus::Unhealthy);
+    assert_eq!(*status("E11"), SatelliteStatus::NoData);
+    assert_eq!(report.propagated(), 3);
+
+    let czml = dir.path().join("out/orbits.czml");
+    assert_eq!(report.outputs, std::slice::from_ref(&czml));
+    let packets: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(czml).unwrap()).unwrap();
+    assert_eq!(packets.as_array().unwrap().len(), 1 + 3);
+
+    let sites: Vec<&str> = report
+        .passes
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(sites, ["Open", "Walled"]);
+    let (open, walled) = (&report.passes[0].1, &report.passes[1].1);
+    assert!(!open.is_empty());
+    assert!(walled.len() <= open.len());
+    let end = scenario.start + chrono::Duration::seconds(21600);
+    for pass in open {
+        assert!(pass.max_elevation >= 10.0);
+        assert!(scenario.start <= pass.rise && pass.rise <= pass.set && pass.set <= end);
+    }
+    assert!(walled.iter().all(|pass| pass.max_elevation >= 30.0));
+
+    let text = report.to_string();
+    assert!(text.starts_with(&format!(
+        "{} records, 3 of 5 satellites propagated",
+        records
+    )));
+    assert!(text.contains("\nE11 skipped: NoData"));
+    assert!(text.contains("\nOpen: "));
+}
+
+#[test]
+fn scenario_without_its_nav_file_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_scenario(dir.path());
+    fs::remove_file(dir.path().join("nav/brdc.rnx")).unwrap();
+    match Scenario::from_file(path) {
+        Err(ScenarioError::Invalid(message)) => assert!(message.ends_with("is not a file")),
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,148 @@
+//! Struct → JSON → struct round trips of the serializable types
+
+#![cfg(all(feature = "serde", feature = "std-fs", feature = "ndarray"))]
+
+use chrono::{TimeZone, Utc};
+use pnt_rust::constellation::Constellation;
+use pnt_rust::gnss::{self, RinexNav, SatId, State, ECEF, LLA};
+use pnt_rust::positioning::{SppOptions, SppSolution, Weighting};
+use pnt_rust::sat_info::SatInfo;
+use pnt_rust::satellite::{PropagationConfig, Satellite};
+use pnt_rust::signal::Signal;
+use pnt_rust::simulation::{GaussianNoise, SimulationConfig};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::time::Duration;
+
+const NAV: &str = include_str!("../constellation/GCGO00USA_R_20231630000_01D_GN.rnx");
+
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) -> String {
+    let json = serde_json::to_string(value).unwrap();
+    let parsed: T = serde_json::from_str(&json).unwrap();
+    assert_eq!(&parsed, value, "{}", json);
+    json
+}
+
+#[test]
+fn coordinates_and_identifiers() {
+    round_trip(&ECEF::new(-2281621.6297, -1453585.1138, 5756964.9518));
+    let json = round_trip(&LLA::new(65.2, -147.5, 1.0 / 3.0));
+    assert!(json.contains("\"latitude\":65.2"), "{}", json);
+    // Satellites are written as their RINEX names, so they can key JSON objects
+    assert_eq!(round_trip(&SatId::gps(5)), "\"G05\"");
+    round_trip(&SatId::new(gnss::Constellation::BeiDou, 59));
+    round_trip(&gnss::Constellation::Glonass);
+    round_trip(&Signal::GlonassG1(-7));
+    round_trip(&Weighting::Sine { sigma0: 0.5 });
+    let epoch = Utc.with_ymd_and_hms(2023, 6, 12, 0, 0, 0).unwrap();
+    round_trip(&SatInfo::lookup(SatId::gps(17), epoch).unwrap());
+}
+
+#[test]
+fn nav_records_and_files() {
+    let nav: RinexNav = NAV.parse().unwrap();
+    for record in nav.records().iter().take(10) {
+        round_trip(record);
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nav.json");
+    let path = path.to_str().unwrap();
+    nav.to_json_file(path).unwrap();
+    let reloaded = RinexNav::from_json_file(path).unwrap();
+    assert_eq!(reloaded.records(), nav.records());
+    assert_eq!(reloaded.leap_seconds, nav.leap_seconds);
+    assert_eq!(
+        reloaded.records_for_slice(SatId::gps(17)),
+        nav.records_for_slice(SatId::gps(17))
+    );
+
+    // Per-input inventories for a pipeline's log
+    let json = round_trip(&nav.summary());
+    assert!(json.contains("\"G17\":{\"records\":6,"), "{}", json);
+    round_trip(&nav.coverage(SatId::gps(17)).unwrap());
+    round_trip(&nav.validate());
+}
+
+#[test]
+fn propagated_states() {
+    let nav: RinexNav = NAV.parse().unwrap();
+    let mut satellite = Satellite::new(17, "GPS BIIR-2  (PRN 17)".to_string());
+    let start = Utc.with_ymd_and_hms(2023, 6, 12, 6, 0, 0).unwrap();
+    let config = PropagationConfig::new()
+        .with_velocity(true)
+        .with_clock(true);
+    satellite
+        .propagate_from_nav(&nav, start, Duration::from_secs(3600), &config)
+        .unwrap();
+    round_trip(&satellite.states);
+    let states: Vec<State> = serde_json::from_str(&satellite.states_to_json().unwrap()).unwrap();
+    assert_eq!(states, satellite.states.iter_states().collect::<Vec<_>>());
+
+    let json = serde_json::to_string(&satellite).unwrap();
+    let parsed: Satellite = serde_json::from_str(&json).unwrap();
+    assert_eq!((parsed.id, &parsed.name), (satellite.id, &satellite.name));
+    assert_eq!(parsed.states, satellite.states);
+
+    let comparison = satellite.compare_with(&satellite);
+    assert_eq!(comparison.epochs, 3600);
+    round_trip(&comparison);
+
+    // Under the satellite at the start of the hour
+    let below = satellite.states.first().unwrap().position().to_lla();
+    let observer = LLA::new(below.latitude, below.longitude, 0.0);
+    let passes = satellite.passes(&observer, 10.0, start, start + chrono::Duration::hours(1));
+    assert!(!passes.is_empty());
+    round_trip(&passes);
+}
+
+#[test]
+fn state_series_with_ragged_columns_are_rejected() {
+    let nav: RinexNav = NAV.parse().unwrap();
+    let mut satellite = Satellite::new(17, "GPS BIIR-2  (PRN 17)".to_string());
+    let start = Utc.with_ymd_and_hms(2023, 6, 12, 6, 0, 0).unwrap();
+    let config = PropagationConfig::new().step(Duration::from_secs(600));
+    satellite
+        .propagate_from_nav(&nav, start, Duration::from_secs(3600), &config)
+        .unwrap();
+    let mut json: serde_json::Value = serde_json::to_value(&satellite.states).unwrap();
+    // Without velocities or clock offsets their columns are empty, which is allowed
+    assert_eq!(json["velocity"], serde_json::json!([]));
+    let parsed: gnss::StateSeries = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(parsed, satellite.states);
+
+    json["ephemeris_age"].as_array_mut().unwrap().pop();
+    let error = serde_json::from_value::<gnss::StateSeries>(json.clone()).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("ephemeris_age has 5 entries for 6 epochs"),
+        "{}",
+        error
+    );
+    // A velocity column must be complete if it is there at all
+    json["ephemeris_age"] = json["time"].clone();
+    json["velocity"] = serde_json::json!([{ "x": 0.0, "y": 0.0, "z": 0.0 }]);
+    assert!(serde_json::from_value::<gnss::StateSeries>(json).is_err());
+}
+
+#[test]
+fn spp_solutions() {
+    let constellation = Constellation::from_nav(NAV.parse::<RinexNav>().unwrap());
+    let station = ECEF::new(-2281621.6297, -1453585.1138, 5756964.9518);
+    let truth = [(Utc.with_ymd_and_hms(2023, 6, 12, 4, 0, 0).unwrap(), station)];
+    let epoch = &constellation.simulate_observations(
+        &truth,
+        &SimulationConfig::default(),
+        &mut GaussianNoise::new(1.0, 0.0, 0.0, 1),
+    )[0];
+    let solution: SppSolution = constellation
+        .solve_spp(
+            epoch.epoch,
+            &epoch.pseudoranges(Signal::GpsL1),
+            &SppOptions::default(),
+        )
+        .unwrap();
+    let json = round_trip(&solution);
+    assert!(json.contains("\"post_fit\":{\"G"), "{}", json);
+}